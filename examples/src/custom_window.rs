@@ -1,5 +1,5 @@
 use cgmath::{One, Quaternion, Rotation3};
-use gears::{new_entity, prelude::*};
+use gears::prelude::*;
 use log::warn;
 
 pub fn example_gui(ui: &egui::Context) {
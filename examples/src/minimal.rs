@@ -1,9 +1,21 @@
 use cgmath::{One, Quaternion, Rotation3};
-use gears::{new_entity, prelude::*};
+use gears::prelude::*;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let mut app = GearsApp::default();
+    // Embed the sphere, so the example runs without the res/ directory next to it
+    let mut app = GearsApp::new(Config {
+        assets: AssetConfig {
+            embedded: embed_assets!(
+                dir = "..",
+                "res/models/sphere/sphere.obj",
+                "res/models/sphere/sphere.mtl",
+                "res/models/sphere/blue_red_gradient.jpg",
+            ),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
 
     // Add fixed camera
     new_entity!(
@@ -1,7 +1,5 @@
-use app::GearsApp;
 use cgmath::Rotation3;
-use ecs::traits::EntityBuilder;
-use gears::{new_entity, prelude::*};
+use gears::prelude::*;
 use std::f32::consts::PI;
 
 #[tokio::main]
@@ -17,7 +15,8 @@ async fn main() -> anyhow::Result<()> {
             look_at: cgmath::Point3::new(0.0, 0.0, 0.0),
             speed: 10.0,
             sensitivity: 0.5,
-        }
+        },
+        components::CameraLens::new(cgmath::Deg(60.0), 0.1, 200.0)
     );
 
     // Add ambient light
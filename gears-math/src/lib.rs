@@ -0,0 +1,43 @@
+//! The math utilities of the engine, usable by tools without the engine dependencies.
+//! The crate is `no_std`, the `cgmath` feature adds the constants as cgmath types.
+#![no_std]
+
+use core::f32::consts::FRAC_PI_2;
+
+/// Applied to the OpenGL style projections for the clip space of wgpu, the columns in order.
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU: [[f32; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 0.5, 0.5],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// `OPENGL_TO_WGPU` as a cgmath matrix.
+#[cfg(feature = "cgmath")]
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.5,
+    0.0, 0.0, 0.0, 1.0,
+);
+
+/// The largest pitch of a camera, just below a right angle so the view never flips.
+pub const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constants() {
+        const { assert!(SAFE_FRAC_PI_2 < FRAC_PI_2 && SAFE_FRAC_PI_2 > FRAC_PI_2 - 0.001) };
+        // The depth is halved and the x and y are kept
+        assert_eq!(OPENGL_TO_WGPU[2][2], 0.5);
+        assert_eq!((OPENGL_TO_WGPU[0][0], OPENGL_TO_WGPU[1][1]), (1.0, 1.0));
+
+        #[cfg(feature = "cgmath")]
+        assert_eq!(OPENGL_TO_WGPU_MATRIX, cgmath::Matrix4::from(OPENGL_TO_WGPU));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+
+struct Entry {
+    value: Box<dyn Any + Send + Sync>,
+    version: u64,
+}
+
+/// A typed key-value store shared between AI agents, e.g. for squad coordination.
+/// Attach it to a squad or level entity, every write to a key is announced with a
+/// `BlackboardChanged` event by `update_blackboards`.
+#[derive(Default)]
+pub struct Blackboard {
+    entries: HashMap<String, Entry>,
+    changed: Vec<String>,
+    /// The version of the last write to any key.
+    last_version: u64,
+}
+
+impl Component for Blackboard {}
+
+impl fmt::Debug for Blackboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Blackboard")
+            .field("keys", &self.entries.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Blackboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write a value to a key, replacing the previous value of any type.
+    pub fn set<T: Any + Send + Sync>(&mut self, key: impl Into<String>, value: T) {
+        let key = key.into();
+        let version = self.next_version();
+
+        if !self.changed.contains(&key) {
+            self.changed.push(key.clone());
+        }
+        self.entries.insert(
+            key,
+            Entry {
+                value: Box::new(value),
+                version,
+            },
+        );
+    }
+
+    /// Read the value of a key.
+    ///
+    /// # Returns
+    ///
+    /// The value, or `None` if the key is not set or holds a value of a different type.
+    pub fn get<T: Any + Send + Sync>(&self, key: &str) -> Option<&T> {
+        self.entries.get(key)?.value.downcast_ref::<T>()
+    }
+
+    pub fn get_mut<T: Any + Send + Sync>(&mut self, key: &str) -> Option<&mut T> {
+        let version = self.last_version + 1;
+        let entry = self.entries.get_mut(key)?;
+        let value = entry.value.downcast_mut::<T>()?;
+
+        self.last_version = version;
+        entry.version = version;
+        if !self.changed.iter().any(|k| k == key) {
+            self.changed.push(key.to_string());
+        }
+        Some(value)
+    }
+
+    /// Remove a key.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the key was set.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let removed = self.entries.remove(key).is_some();
+        if removed && !self.changed.iter().any(|k| k == key) {
+            self.changed.push(key.to_string());
+        }
+        removed
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Get the version of the last write to a key, 0 if it is not set.
+    /// The versions of all keys come from one counter, so a key removed and set again gets a new one.
+    /// Agents can store the version to poll for changes without events.
+    pub fn version(&self, key: &str) -> u64 {
+        self.entries.get(key).map_or(0, |e| e.version)
+    }
+
+    fn next_version(&mut self) -> u64 {
+        self.last_version += 1;
+        self.last_version
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+}
+
+/// Sent when a key of a blackboard is written or removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlackboardChanged {
+    pub blackboard: Entity,
+    pub key: String,
+}
+
+/// Send the `BlackboardChanged` events for the changes since the last update.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+pub fn update_blackboards(ecs: &ecs::Manager) {
+    for (entity, blackboard) in ecs.get_all_components_of_type::<Blackboard>() {
+        let changed = std::mem::take(&mut blackboard.write().unwrap().changed);
+        for key in changed {
+            ecs.send_event(BlackboardChanged {
+                blackboard: entity,
+                key,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector3;
+
+    #[test]
+    fn test_typed_values() {
+        let mut blackboard = Blackboard::new();
+        blackboard.set("alarm", true);
+        blackboard.set("last_known_player", Vector3::new(1.0f32, 0.0, 2.0));
+
+        assert_eq!(blackboard.get::<bool>("alarm"), Some(&true));
+        assert_eq!(blackboard.get::<f32>("alarm"), None);
+        assert_eq!(
+            blackboard.get::<Vector3<f32>>("last_known_player"),
+            Some(&Vector3::new(1.0, 0.0, 2.0))
+        );
+
+        let version = blackboard.version("alarm");
+        *blackboard.get_mut::<bool>("alarm").unwrap() = false;
+        assert!(blackboard.version("alarm") > version);
+        assert!(blackboard.remove("alarm"));
+        assert!(!blackboard.contains("alarm"));
+        assert_eq!(blackboard.version("alarm"), 0);
+    }
+
+    #[test]
+    fn test_version_after_remove() {
+        let mut blackboard = Blackboard::new();
+        blackboard.set("alarm", true);
+        blackboard.set("alarm", false);
+        let version = blackboard.version("alarm");
+
+        // Setting a removed key again never repeats a version an agent may have stored
+        blackboard.remove("alarm");
+        blackboard.set("alarm", true);
+        assert!(blackboard.version("alarm") > version);
+        blackboard.remove("alarm");
+        blackboard.set("alarm", true);
+        blackboard.set("alarm", true);
+        assert!(blackboard.version("alarm") > version + 1);
+    }
+
+    #[test]
+    fn test_change_events() {
+        let manager = ecs::Manager::default();
+        let squad = manager.create_entity();
+        manager.add_component_to_entity(squad, Blackboard::new());
+
+        let blackboard = manager
+            .get_component_from_entity::<Blackboard>(squad)
+            .unwrap();
+        blackboard.write().unwrap().set("alarm", true);
+        blackboard.write().unwrap().set("alarm", false);
+
+        update_blackboards(&manager);
+        assert_eq!(
+            manager.drain_events::<BlackboardChanged>(),
+            vec![BlackboardChanged {
+                blackboard: squad,
+                key: "alarm".to_string()
+            }]
+        );
+
+        update_blackboards(&manager);
+        assert!(manager.drain_events::<BlackboardChanged>().is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+use crate::core::Dt;
+use crate::ecs::components::{Pos3, Velocity};
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::{InnerSpace, Vector3, Zero};
+
+/// A component that applies local avoidance steering to an agent.
+/// A `PathFollower` or user code sets the `preferred_velocity`, and the crowd system
+/// blends it with boids-style separation and alignment before moving the agent.
+#[derive(Debug, Copy, Clone)]
+pub struct CrowdAgent {
+    /// The velocity the agent would move with if there were no other agents around.
+    pub preferred_velocity: Vector3<f32>,
+    /// The radius of the agent, used for the personal space.
+    pub radius: f32,
+    pub max_speed: f32,
+    /// The distance in which other agents are taken into account.
+    pub neighbor_distance: f32,
+    /// How strongly the agent keeps away from its neighbors.
+    pub separation_weight: f32,
+    /// How strongly the agent matches the velocity of its neighbors.
+    pub alignment_weight: f32,
+    pub enabled: bool,
+}
+
+impl Component for CrowdAgent {}
+
+impl Default for CrowdAgent {
+    fn default() -> Self {
+        Self {
+            preferred_velocity: Vector3::zero(),
+            radius: 0.5,
+            max_speed: 3.0,
+            neighbor_distance: 3.0,
+            separation_weight: 2.0,
+            alignment_weight: 0.3,
+            enabled: true,
+        }
+    }
+}
+
+impl CrowdAgent {
+    pub fn new(radius: f32, max_speed: f32) -> Self {
+        Self {
+            radius,
+            max_speed,
+            ..Default::default()
+        }
+    }
+}
+
+struct AgentSnapshot {
+    entity: Entity,
+    pos: Vector3<f32>,
+    velocity: Vector3<f32>,
+    agent: CrowdAgent,
+}
+
+/// Compute the steered velocity of an agent.
+fn steer(agent: &AgentSnapshot, neighbors: &[AgentSnapshot]) -> Vector3<f32> {
+    let settings = &agent.agent;
+    let mut separation = Vector3::zero();
+    let mut alignment = Vector3::zero();
+    let mut count = 0;
+
+    for other in neighbors.iter().filter(|o| o.entity != agent.entity) {
+        // Agents only avoid each other on the ground plane
+        let mut offset = agent.pos - other.pos;
+        offset.y = 0.0;
+        let distance = offset.magnitude();
+        if distance >= settings.neighbor_distance {
+            continue;
+        }
+
+        let personal_space = settings.radius + other.agent.radius;
+        let direction = if distance > f32::EPSILON {
+            offset / distance
+        } else {
+            // Agents at the same position are pushed apart in a stable direction
+            Vector3::new(1.0, 0.0, 0.0)
+                * if agent.entity.0 < other.entity.0 {
+                    1.0
+                } else {
+                    -1.0
+                }
+        };
+
+        // The push gets stronger the more the personal spaces overlap
+        let strength = ((settings.neighbor_distance - distance)
+            / (settings.neighbor_distance - personal_space).max(f32::EPSILON))
+        .min(4.0);
+        separation += direction * strength;
+        alignment += other.velocity;
+        count += 1;
+    }
+
+    let mut velocity = settings.preferred_velocity;
+    if count > 0 {
+        let alignment = alignment / count as f32 - agent.velocity;
+        velocity += separation * settings.separation_weight * settings.max_speed / count as f32
+            + alignment * settings.alignment_weight;
+    }
+    velocity.y = settings.preferred_velocity.y;
+
+    if velocity.magnitude() > settings.max_speed {
+        velocity = velocity.normalize_to(settings.max_speed);
+    }
+
+    velocity
+}
+
+/// Steer all crowd agents away from each other and move them.
+/// The steered velocity is written to the `Velocity` of the agent if it has one.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_crowd_agents(ecs: &ecs::Manager, dt: Dt) {
+    let agents = ecs.query_components::<CrowdAgent>();
+    let snapshots = ecs
+        .frame_arena()
+        .collect(agents.iter().filter_map(|(entity, agent)| {
+            let entity = *entity;
+            let agent = *agent.read().unwrap();
+            if !agent.enabled {
+                return None;
+            }
+
+            let pos = ecs
+                .get_component_from_entity::<Pos3>(entity)?
+                .read()
+                .unwrap()
+                .pos;
+            let velocity = ecs
+                .get_component_from_entity::<Velocity>(entity)
+                .map_or(agent.preferred_velocity, |v| v.read().unwrap().0);
+
+            Some(AgentSnapshot {
+                entity,
+                pos,
+                velocity,
+                agent,
+            })
+        }));
+
+    // Use the snapshots for every agent, so the result doesn't depend on the update order
+    for agent in snapshots.iter() {
+        let velocity = steer(agent, &snapshots);
+
+        if let Some(pos) = ecs.get_component_from_entity::<Pos3>(agent.entity) {
+            pos.write().unwrap().pos += velocity * dt.as_secs_f32();
+        }
+        if let Some(v) = ecs.get_component_from_entity::<Velocity>(agent.entity) {
+            v.write().unwrap().0 = velocity;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instant::Duration;
+
+    fn spawn_agent(ecs: &ecs::Manager, x: f32, preferred: Vector3<f32>) -> Entity {
+        let entity = ecs.create_entity();
+        ecs.add_component_to_entity(entity, Pos3::new(Vector3::new(x, 0.0, 0.0)));
+        ecs.add_component_to_entity(entity, Velocity::default());
+        ecs.add_component_to_entity(
+            entity,
+            CrowdAgent {
+                preferred_velocity: preferred,
+                ..Default::default()
+            },
+        );
+        entity
+    }
+
+    #[test]
+    fn test_agents_separate() {
+        let manager = ecs::Manager::default();
+        let a = spawn_agent(&manager, -0.2, Vector3::zero());
+        let b = spawn_agent(&manager, 0.2, Vector3::zero());
+
+        for _ in 0..30 {
+            update_crowd_agents(&manager, Duration::from_millis(50));
+        }
+
+        let pos_a = manager.get_component_from_entity::<Pos3>(a).unwrap();
+        let pos_b = manager.get_component_from_entity::<Pos3>(b).unwrap();
+        let distance = (pos_b.read().unwrap().pos - pos_a.read().unwrap().pos).magnitude();
+        assert!(distance >= 1.0, "agents are still clumped: {distance}");
+    }
+
+    #[test]
+    fn test_lone_agent_follows_preferred_velocity() {
+        let manager = ecs::Manager::default();
+        let agent = spawn_agent(&manager, 0.0, Vector3::new(1.0, 0.0, 0.0));
+
+        update_crowd_agents(&manager, Duration::from_secs(1));
+
+        let velocity = manager
+            .get_component_from_entity::<Velocity>(agent)
+            .unwrap();
+        assert_eq!(velocity.read().unwrap().0, Vector3::new(1.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,224 @@
+use crate::core::Dt;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use std::collections::VecDeque;
+
+/// The number of transitions a state machine remembers.
+const HISTORY_LEN: usize = 16;
+
+/// A condition of a transition, checked for the entity of the state machine.
+/// It must not lock the `StateMachine` of the entity for writing.
+pub type Condition = fn(&ecs::Manager, Entity) -> bool;
+
+/// A state change of a state machine.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub from: String,
+    pub to: String,
+    /// The time since the state machine was created when the state changed.
+    pub time: f32,
+    /// Whether the state was changed with `request` instead of a condition.
+    pub requested: bool,
+}
+
+/// A component that drives the behavior of an AI agent with named states.
+/// The transitions of the current state are checked in order by `update_state_machines`,
+/// the first one whose condition holds changes the state.
+/// The agent systems read `state` and `sub_state` to decide what to do.
+#[derive(Debug, Clone)]
+pub struct StateMachine {
+    state: String,
+    sub_state: Option<String>,
+    time_in_state: f32,
+    elapsed: f32,
+    transitions: Vec<(String, String, Condition)>,
+    history: VecDeque<Transition>,
+    requested: Option<String>,
+}
+
+impl Component for StateMachine {}
+
+impl StateMachine {
+    pub fn new(initial: impl Into<String>) -> Self {
+        Self {
+            state: initial.into(),
+            sub_state: None,
+            time_in_state: 0.0,
+            elapsed: 0.0,
+            transitions: Vec::new(),
+            history: VecDeque::new(),
+            requested: None,
+        }
+    }
+
+    pub fn with_transition(
+        mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+        condition: Condition,
+    ) -> Self {
+        self.transitions.push((from.into(), to.into(), condition));
+        self
+    }
+
+    pub fn state(&self) -> &str {
+        &self.state
+    }
+
+    /// Get the detail of the current state, e.g. the phase of an attack. It is cleared when the state changes.
+    pub fn sub_state(&self) -> Option<&str> {
+        self.sub_state.as_deref()
+    }
+
+    pub fn set_sub_state(&mut self, sub_state: Option<impl Into<String>>) {
+        self.sub_state = sub_state.map(Into::into);
+    }
+
+    /// Get the time in seconds since the state was entered.
+    pub fn time_in_state(&self) -> f32 {
+        self.time_in_state
+    }
+
+    /// Get the states named by the transitions, starting with the current one.
+    pub fn states(&self) -> Vec<&str> {
+        let mut states = vec![self.state.as_str()];
+        for (from, to, _) in &self.transitions {
+            for state in [from, to] {
+                if !states.contains(&state.as_str()) {
+                    states.push(state);
+                }
+            }
+        }
+        states
+    }
+
+    /// Get the recent transitions, the oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &Transition> {
+        self.history.iter()
+    }
+
+    /// Change the state on the next update, regardless of the conditions.
+    pub fn request(&mut self, state: impl Into<String>) {
+        self.requested = Some(state.into());
+    }
+
+    fn enter(&mut self, state: String, requested: bool) -> Transition {
+        let transition = Transition {
+            from: std::mem::replace(&mut self.state, state),
+            to: self.state.clone(),
+            time: self.elapsed,
+            requested,
+        };
+        self.sub_state = None;
+        self.time_in_state = 0.0;
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(transition.clone());
+        transition
+    }
+}
+
+/// Sent when the state of a state machine changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateChanged {
+    pub entity: Entity,
+    pub transition: Transition,
+}
+
+/// Advance the state machines and take the transitions whose condition holds.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_state_machines(ecs: &ecs::Manager, dt: Dt) {
+    let dt = dt.as_secs_f32();
+    for (entity, machine) in ecs.get_all_components_of_type::<StateMachine>() {
+        let (requested, candidates) = {
+            let mut machine = machine.write().unwrap();
+            machine.elapsed += dt;
+            machine.time_in_state += dt;
+            let candidates = machine
+                .transitions
+                .iter()
+                .filter(|(from, _, _)| *from == machine.state)
+                .map(|(_, to, condition)| (to.clone(), *condition))
+                .collect::<Vec<_>>();
+            (machine.requested.take(), candidates)
+        };
+
+        // The conditions run without the lock, so they can read the state machine
+        let next = match requested {
+            Some(state) => Some((state, true)),
+            None => candidates
+                .into_iter()
+                .find(|(_, condition)| condition(ecs, entity))
+                .map(|(state, _)| (state, false)),
+        };
+
+        let Some((state, requested)) = next else {
+            continue;
+        };
+        let mut machine = machine.write().unwrap();
+        if machine.state != state {
+            let transition = machine.enter(state, requested);
+            ecs.send_event(StateChanged { entity, transition });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::health::Health;
+    use instant::Duration;
+
+    fn is_hurt(ecs: &ecs::Manager, entity: Entity) -> bool {
+        ecs.get_component_from_entity::<Health>(entity)
+            .is_some_and(|health| health.read().unwrap().current < 5.0)
+    }
+
+    fn rested(ecs: &ecs::Manager, entity: Entity) -> bool {
+        ecs.get_component_from_entity::<StateMachine>(entity)
+            .is_some_and(|machine| machine.read().unwrap().time_in_state() >= 1.0)
+    }
+
+    #[test]
+    fn test_transitions() {
+        let ecs = ecs::Manager::default();
+        let agent = ecs.create_entity();
+        ecs.add_component_to_entity(agent, Health::new(10.0));
+        let machine = StateMachine::new("patrol")
+            .with_transition("patrol", "flee", is_hurt)
+            .with_transition("flee", "patrol", rested);
+        assert_eq!(machine.states(), vec!["patrol", "flee"]);
+        ecs.add_component_to_entity(agent, machine);
+
+        update_state_machines(&ecs, Duration::from_millis(500));
+        let machine = ecs
+            .get_component_from_entity::<StateMachine>(agent)
+            .unwrap();
+        assert_eq!(machine.read().unwrap().state(), "patrol");
+
+        let health = ecs.get_component_from_entity::<Health>(agent).unwrap();
+        health.write().unwrap().damage(8.0);
+        update_state_machines(&ecs, Duration::from_millis(500));
+        assert_eq!(machine.read().unwrap().state(), "flee");
+        let events = ecs.drain_events::<StateChanged>();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].transition.time, 1.0);
+
+        update_state_machines(&ecs, Duration::from_secs(1));
+        assert_eq!(machine.read().unwrap().state(), "patrol");
+
+        // A requested state is entered even without a transition
+        machine.write().unwrap().request("dead");
+        update_state_machines(&ecs, Duration::from_millis(10));
+        let machine = machine.read().unwrap();
+        assert_eq!(machine.state(), "dead");
+        let last = machine.history().last().unwrap();
+        assert!(last.requested);
+        assert_eq!(machine.history().count(), 3);
+    }
+}
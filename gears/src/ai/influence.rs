@@ -0,0 +1,264 @@
+use crate::core::Dt;
+use crate::ecs;
+use crate::ecs::components::Pos3;
+use crate::ecs::traits::Component;
+use cgmath::{InnerSpace, Vector2, Vector3};
+
+/// A component that makes an entity spread influence on the influence maps of its layer.
+/// Positive strength can be used for threat, negative strength for safety.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct InfluenceSource {
+    pub layer: u32,
+    /// The influence at the center of the source, it falls off linearly to the radius.
+    pub strength: f32,
+    pub radius: f32,
+}
+
+impl Component for InfluenceSource {}
+
+/// A grid of influence values on the XZ plane.
+/// The map is decayed and stamped with the `InfluenceSource`s of its layer by
+/// `update_influence_maps`, events like damage or noise can be stamped with `add_influence`.
+#[derive(Debug, Clone)]
+pub struct InfluenceMap {
+    pub layer: u32,
+    /// The position of the corner of the first cell on the XZ plane.
+    pub origin: Vector2<f32>,
+    pub cell_size: f32,
+    /// The fraction of the influence lost per second.
+    pub decay: f32,
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+}
+
+impl Component for InfluenceMap {}
+
+impl InfluenceMap {
+    pub fn new(origin: Vector2<f32>, width: usize, height: usize, cell_size: f32) -> Self {
+        assert!(cell_size > 0.0);
+        Self {
+            layer: 0,
+            origin,
+            cell_size,
+            decay: 0.5,
+            width,
+            height,
+            values: vec![0.0; width * height],
+        }
+    }
+
+    pub fn with_layer(mut self, layer: u32) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    pub fn with_decay(mut self, decay: f32) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Get the value of a cell.
+    pub fn get(&self, x: usize, z: usize) -> Option<f32> {
+        (x < self.width && z < self.height).then(|| self.values[z * self.width + x])
+    }
+
+    /// Get the world position of the center of a cell, with the y coordinate set to 0.
+    pub fn cell_center(&self, x: usize, z: usize) -> Vector3<f32> {
+        Vector3::new(
+            self.origin.x + (x as f32 + 0.5) * self.cell_size,
+            0.0,
+            self.origin.y + (z as f32 + 0.5) * self.cell_size,
+        )
+    }
+
+    /// Convert a world position to fractional cell coordinates relative to the cell centers.
+    fn to_grid(&self, pos: Vector3<f32>) -> Vector2<f32> {
+        Vector2::new(
+            (pos.x - self.origin.x) / self.cell_size - 0.5,
+            (pos.z - self.origin.y) / self.cell_size - 0.5,
+        )
+    }
+
+    pub fn clear(&mut self) {
+        self.values.fill(0.0);
+    }
+
+    /// Add influence around a position, falling off linearly to the radius.
+    pub fn add_influence(&mut self, pos: Vector3<f32>, strength: f32, radius: f32) {
+        if radius <= 0.0 || self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        let center = self.to_grid(pos);
+        let cells = radius / self.cell_size;
+        let min_x = (center.x - cells).floor().max(0.0) as usize;
+        let min_z = (center.y - cells).floor().max(0.0) as usize;
+        let max_x = ((center.x + cells).ceil().max(0.0) as usize).min(self.width - 1);
+        let max_z = ((center.y + cells).ceil().max(0.0) as usize).min(self.height - 1);
+
+        for z in min_z..=max_z {
+            for x in min_x..=max_x {
+                let mut offset = self.cell_center(x, z) - pos;
+                offset.y = 0.0;
+                let distance = offset.magnitude();
+                if distance < radius {
+                    self.values[z * self.width + x] += strength * (1.0 - distance / radius);
+                }
+            }
+        }
+    }
+
+    /// Sample the bilinearly interpolated influence at a world position.
+    /// Positions outside of the map are clamped to the border.
+    pub fn sample(&self, pos: Vector3<f32>) -> f32 {
+        if self.width == 0 || self.height == 0 {
+            return 0.0;
+        }
+
+        let grid = self.to_grid(pos);
+        let gx = grid.x.clamp(0.0, (self.width - 1) as f32);
+        let gz = grid.y.clamp(0.0, (self.height - 1) as f32);
+        let (x0, z0) = (gx.floor() as usize, gz.floor() as usize);
+        let (x1, z1) = ((x0 + 1).min(self.width - 1), (z0 + 1).min(self.height - 1));
+        let (tx, tz) = (gx - x0 as f32, gz - z0 as f32);
+
+        let value = |x: usize, z: usize| self.values[z * self.width + x];
+        let top = value(x0, z0) * (1.0 - tx) + value(x1, z0) * tx;
+        let bottom = value(x0, z1) * (1.0 - tx) + value(x1, z1) * tx;
+        top * (1.0 - tz) + bottom * tz
+    }
+
+    /// Get the direction of the steepest increase of influence at a world position.
+    /// Move against the gradient to get away from threats.
+    pub fn gradient(&self, pos: Vector3<f32>) -> Vector3<f32> {
+        let h = self.cell_size;
+        let dx =
+            self.sample(pos + Vector3::unit_x() * h) - self.sample(pos - Vector3::unit_x() * h);
+        let dz =
+            self.sample(pos + Vector3::unit_z() * h) - self.sample(pos - Vector3::unit_z() * h);
+        Vector3::new(dx, 0.0, dz) / (2.0 * h)
+    }
+
+    /// Find the center of the cell with the lowest influence within a radius, e.g. to pick a cover position.
+    pub fn lowest_within(&self, pos: Vector3<f32>, radius: f32) -> Option<Vector3<f32>> {
+        self.cells_within(pos, radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(center, _)| center)
+    }
+
+    /// Find the center of the cell with the highest influence within a radius.
+    pub fn highest_within(&self, pos: Vector3<f32>, radius: f32) -> Option<Vector3<f32>> {
+        self.cells_within(pos, radius)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(center, _)| center)
+    }
+
+    fn cells_within(
+        &self,
+        pos: Vector3<f32>,
+        radius: f32,
+    ) -> impl Iterator<Item = (Vector3<f32>, f32)> + '_ {
+        (0..self.height)
+            .flat_map(move |z| (0..self.width).map(move |x| (x, z)))
+            .filter_map(move |(x, z)| {
+                let center = self.cell_center(x, z);
+                let mut offset = center - pos;
+                offset.y = 0.0;
+                (offset.magnitude() <= radius).then(|| (center, self.values[z * self.width + x]))
+            })
+    }
+}
+
+/// Decay all influence maps and stamp the influence sources of their layer.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_influence_maps(ecs: &ecs::Manager, dt: Dt) {
+    let sources: Vec<(Vector3<f32>, InfluenceSource)> = ecs
+        .get_all_components_of_type::<InfluenceSource>()
+        .into_iter()
+        .filter_map(|(entity, source)| {
+            let pos = ecs.get_component_from_entity::<Pos3>(entity)?;
+            let pos = pos.read().unwrap().pos;
+            Some((pos, *source.read().unwrap()))
+        })
+        .collect();
+
+    for (_, map) in ecs.get_all_components_of_type::<InfluenceMap>() {
+        let mut map = map.write().unwrap();
+
+        // Scale the sources by the time step, so the map converges to the same values at any frame rate
+        let keep = (1.0 - map.decay).clamp(0.0, 1.0).powf(dt.as_secs_f32());
+        map.values.iter_mut().for_each(|v| *v *= keep);
+
+        let weight = 1.0 - keep;
+        let layer = map.layer;
+        for (pos, source) in sources.iter().filter(|(_, s)| s.layer == layer) {
+            map.add_influence(*pos, source.strength * weight, source.radius);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instant::Duration;
+
+    #[test]
+    fn test_sample_and_gradient() {
+        let mut map = InfluenceMap::new(Vector2::new(-5.0, -5.0), 10, 10, 1.0);
+        map.add_influence(Vector3::new(0.5, 0.0, 0.5), 1.0, 4.0);
+
+        assert!((map.sample(Vector3::new(0.5, 0.0, 0.5)) - 1.0).abs() < 1e-4);
+        assert!(map.sample(Vector3::new(4.5, 0.0, 4.5)) == 0.0);
+
+        // The threat is towards positive x from here
+        let gradient = map.gradient(Vector3::new(-1.5, 0.0, 0.5));
+        assert!(gradient.x > 0.0);
+
+        let safe = map.lowest_within(Vector3::new(0.5, 0.0, 0.5), 3.0).unwrap();
+        assert!(map.sample(safe) < 0.5);
+    }
+
+    #[test]
+    fn test_sources_and_decay() {
+        let manager = ecs::Manager::default();
+        let map_entity = manager.create_entity();
+        manager.add_component_to_entity(
+            map_entity,
+            InfluenceMap::new(Vector2::new(0.0, 0.0), 8, 8, 1.0),
+        );
+
+        let enemy = manager.create_entity();
+        manager.add_component_to_entity(enemy, Pos3::new(Vector3::new(4.5, 0.0, 4.5)));
+        manager.add_component_to_entity(
+            enemy,
+            InfluenceSource {
+                layer: 0,
+                strength: 1.0,
+                radius: 3.0,
+            },
+        );
+
+        for _ in 0..100 {
+            update_influence_maps(&manager, Duration::from_millis(100));
+        }
+
+        let map = manager
+            .get_component_from_entity::<InfluenceMap>(map_entity)
+            .unwrap();
+        let value = map.read().unwrap().get(4, 4).unwrap();
+        assert!((value - 1.0).abs() < 0.01, "{value}");
+    }
+}
@@ -0,0 +1,7 @@
+pub mod blackboard;
+pub mod crowd;
+pub mod fsm;
+pub mod influence;
+pub mod pathfinding;
+pub mod patrol;
+pub mod perception;
@@ -0,0 +1,490 @@
+use super::crowd::CrowdAgent;
+use crate::core::Dt;
+use crate::ecs::components::Pos3;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::{InnerSpace, Vector2, Vector3, Zero};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// A component that stores a walkability grid on the XZ plane, searched with A*.
+#[derive(Debug, Clone)]
+pub struct NavGrid {
+    /// The position of the corner of the first cell on the XZ plane.
+    pub origin: Vector2<f32>,
+    pub cell_size: f32,
+    width: usize,
+    height: usize,
+    blocked: Vec<bool>,
+}
+
+impl Component for NavGrid {}
+
+/// An entry of the open set, ordered so the `BinaryHeap` pops the lowest estimate first.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Node {
+    estimate: f32,
+    cell: usize,
+}
+
+impl Eq for Node {}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| other.cell.cmp(&self.cell))
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl NavGrid {
+    /// Create a grid with every cell walkable.
+    pub fn new(origin: Vector2<f32>, width: usize, height: usize, cell_size: f32) -> Self {
+        assert!(cell_size > 0.0);
+        Self {
+            origin,
+            cell_size,
+            width,
+            height,
+            blocked: vec![false; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Check if a cell is inside the grid and not blocked.
+    pub fn is_walkable(&self, x: usize, z: usize) -> bool {
+        x < self.width && z < self.height && !self.blocked[z * self.width + x]
+    }
+
+    /// Block or unblock a cell, cells outside of the grid are ignored.
+    pub fn set_blocked(&mut self, x: usize, z: usize, blocked: bool) {
+        if x < self.width && z < self.height {
+            self.blocked[z * self.width + x] = blocked;
+        }
+    }
+
+    /// Block every cell whose center is inside a box on the XZ plane, e.g. the footprint of a wall.
+    pub fn block_area(&mut self, min: Vector3<f32>, max: Vector3<f32>) {
+        for z in 0..self.height {
+            for x in 0..self.width {
+                let center = self.cell_center(x, z);
+                if (min.x..=max.x).contains(&center.x) && (min.z..=max.z).contains(&center.z) {
+                    self.blocked[z * self.width + x] = true;
+                }
+            }
+        }
+    }
+
+    /// Get the world position of the center of a cell, with the y coordinate set to 0.
+    pub fn cell_center(&self, x: usize, z: usize) -> Vector3<f32> {
+        Vector3::new(
+            self.origin.x + (x as f32 + 0.5) * self.cell_size,
+            0.0,
+            self.origin.y + (z as f32 + 0.5) * self.cell_size,
+        )
+    }
+
+    /// Get the cell that contains a world position.
+    pub fn cell_at(&self, pos: Vector3<f32>) -> Option<(usize, usize)> {
+        let x = ((pos.x - self.origin.x) / self.cell_size).floor();
+        let z = ((pos.z - self.origin.y) / self.cell_size).floor();
+        (x >= 0.0 && z >= 0.0 && (x as usize) < self.width && (z as usize) < self.height)
+            .then_some((x as usize, z as usize))
+    }
+
+    /// Find a path between two world positions with A*.
+    /// Agents move in eight directions, but never cut the corner of a blocked cell.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The position the path starts from.
+    /// * `goal` - The position the path leads to.
+    ///
+    /// # Returns
+    ///
+    /// The points to move through, ending at the goal, without the cells on a straight line.
+    /// `None` if either position is outside of the walkable cells or the goal can't be reached.
+    pub fn find_path(&self, start: Vector3<f32>, goal: Vector3<f32>) -> Option<Vec<Vector3<f32>>> {
+        let (start_x, start_z) = self.cell_at(start)?;
+        let (goal_x, goal_z) = self.cell_at(goal)?;
+        if !self.is_walkable(start_x, start_z) || !self.is_walkable(goal_x, goal_z) {
+            return None;
+        }
+
+        let start_cell = start_z * self.width + start_x;
+        let goal_cell = goal_z * self.width + goal_x;
+        let heuristic = |cell: usize| {
+            // The octile distance, exact on an empty grid
+            let dx = (cell % self.width).abs_diff(goal_x) as f32;
+            let dz = (cell / self.width).abs_diff(goal_z) as f32;
+            dx.max(dz) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dz)
+        };
+
+        let mut cost = vec![f32::INFINITY; self.blocked.len()];
+        let mut came_from = vec![usize::MAX; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+        cost[start_cell] = 0.0;
+        open.push(Node {
+            estimate: heuristic(start_cell),
+            cell: start_cell,
+        });
+
+        while let Some(Node { estimate, cell }) = open.pop() {
+            if cell == goal_cell {
+                break;
+            }
+            // Skip the entries left behind when a cheaper way to the cell was found
+            if estimate > cost[cell] + heuristic(cell) {
+                continue;
+            }
+
+            let (x, z) = ((cell % self.width) as isize, (cell / self.width) as isize);
+            for (dx, dz) in [
+                (-1, 0),
+                (1, 0),
+                (0, -1),
+                (0, 1),
+                (-1, -1),
+                (1, -1),
+                (-1, 1),
+                (1, 1),
+            ] {
+                let (nx, nz) = (x + dx, z + dz);
+                if nx < 0 || nz < 0 || !self.is_walkable(nx as usize, nz as usize) {
+                    continue;
+                }
+                let diagonal = dx != 0 && dz != 0;
+                if diagonal
+                    && (!self.is_walkable((x + dx) as usize, z as usize)
+                        || !self.is_walkable(x as usize, (z + dz) as usize))
+                {
+                    continue;
+                }
+
+                let next = nz as usize * self.width + nx as usize;
+                let step = if diagonal {
+                    std::f32::consts::SQRT_2
+                } else {
+                    1.0
+                };
+                let next_cost = cost[cell] + step;
+                if next_cost < cost[next] {
+                    cost[next] = next_cost;
+                    came_from[next] = cell;
+                    open.push(Node {
+                        estimate: next_cost + heuristic(next),
+                        cell: next,
+                    });
+                }
+            }
+        }
+
+        if cost[goal_cell].is_infinite() {
+            return None;
+        }
+
+        let mut cells = vec![goal_cell];
+        while let Some(&cell) = cells.last() {
+            if cell == start_cell {
+                break;
+            }
+            cells.push(came_from[cell]);
+        }
+        cells.reverse();
+
+        // Keep only the cells where the direction changes, the last one is replaced by the goal
+        let mut path = Vec::new();
+        for window in cells.windows(3) {
+            let direction = |a: usize, b: usize| {
+                (
+                    (b % self.width) as isize - (a % self.width) as isize,
+                    (b / self.width) as isize - (a / self.width) as isize,
+                )
+            };
+            if direction(window[0], window[1]) != direction(window[1], window[2]) {
+                let mut point = self.cell_center(window[1] % self.width, window[1] / self.width);
+                point.y = goal.y;
+                path.push(point);
+            }
+        }
+        path.push(goal);
+
+        Some(path)
+    }
+}
+
+/// A component that moves an agent to a target along a path on a `NavGrid`.
+/// Agents with a `CrowdAgent` are steered through its preferred velocity, so the local
+/// avoidance is applied after the path following. Others are moved directly.
+#[derive(Debug, Clone)]
+pub struct PathFollower {
+    /// The entity with the `NavGrid` the paths are searched on.
+    pub grid: Entity,
+    pub speed: f32,
+    /// The distance at which a point of the path counts as reached.
+    pub arrive_distance: f32,
+    pub enabled: bool,
+    target: Option<Vector3<f32>>,
+    path: Vec<Vector3<f32>>,
+    next: usize,
+    /// Whether the path has to be searched again before moving.
+    dirty: bool,
+}
+
+impl Component for PathFollower {}
+
+impl PathFollower {
+    pub fn new(grid: Entity, speed: f32) -> Self {
+        Self {
+            grid,
+            speed,
+            arrive_distance: 0.25,
+            enabled: true,
+            target: None,
+            path: Vec::new(),
+            next: 0,
+            dirty: false,
+        }
+    }
+
+    /// Move to a position, the path is searched on the next update.
+    pub fn set_target(&mut self, target: Vector3<f32>) {
+        if self.target != Some(target) {
+            self.target = Some(target);
+            self.dirty = true;
+        }
+    }
+
+    /// Stop moving and forget the target.
+    pub fn clear_target(&mut self) {
+        self.target = None;
+        self.path.clear();
+        self.next = 0;
+        self.dirty = false;
+    }
+
+    /// Search the path again on the next update, e.g. after the grid changed.
+    pub fn repath(&mut self) {
+        self.dirty = self.target.is_some();
+    }
+
+    pub fn target(&self) -> Option<Vector3<f32>> {
+        self.target
+    }
+
+    /// Get the points of the path that are left.
+    pub fn remaining_path(&self) -> &[Vector3<f32>] {
+        &self.path[self.next.min(self.path.len())..]
+    }
+}
+
+/// Sent when a path follower reaches its target.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DestinationReached {
+    pub entity: Entity,
+    pub target: Vector3<f32>,
+}
+
+/// Sent when no path to the target of a path follower was found, the target is cleared.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PathNotFound {
+    pub entity: Entity,
+    pub target: Vector3<f32>,
+}
+
+/// Search the paths of the followers with a new target and move all followers along their paths.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_path_followers(ecs: &ecs::Manager, dt: Dt) {
+    let dt = dt.as_secs_f32();
+
+    for (entity, follower) in ecs.get_all_components_of_type::<PathFollower>() {
+        let mut follower = follower.write().unwrap();
+        let crowd_agent = ecs.get_component_from_entity::<CrowdAgent>(entity);
+        let Some(pos) = ecs.get_component_from_entity::<Pos3>(entity) else {
+            continue;
+        };
+        let mut pos = pos.write().unwrap();
+
+        if follower.dirty {
+            follower.dirty = false;
+            let Some(target) = follower.target else {
+                continue;
+            };
+            let path = ecs
+                .get_component_from_entity::<NavGrid>(follower.grid)
+                .and_then(|grid| grid.read().unwrap().find_path(pos.pos, target));
+            match path {
+                Some(path) => {
+                    follower.path = path;
+                    follower.next = 0;
+                }
+                None => {
+                    log::warn!("[AI] No path from {:?} to {:?}", pos.pos, target);
+                    follower.clear_target();
+                    ecs.send_event(PathNotFound { entity, target });
+                }
+            }
+        }
+
+        let mut velocity = Vector3::zero();
+        if follower.enabled {
+            // Skip the points that are already reached
+            while let Some(&point) = follower.path.get(follower.next) {
+                let mut offset = point - pos.pos;
+                offset.y = 0.0;
+                if offset.magnitude() > follower.arrive_distance {
+                    break;
+                }
+                follower.next += 1;
+                if follower.next == follower.path.len() {
+                    ecs.send_event(DestinationReached {
+                        entity,
+                        target: point,
+                    });
+                    follower.clear_target();
+                }
+            }
+
+            if let Some(point) = follower.path.get(follower.next) {
+                let mut to_point = point - pos.pos;
+                to_point.y = 0.0;
+                let distance = to_point.magnitude();
+                if crowd_agent.is_some() {
+                    velocity = to_point / distance * follower.speed;
+                } else {
+                    // Do not overshoot the point
+                    let step = (follower.speed * dt).min(distance);
+                    pos.pos += to_point / distance * step;
+                }
+            }
+        }
+
+        if let Some(agent) = crowd_agent {
+            agent.write().unwrap().preferred_velocity = velocity;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instant::Duration;
+
+    /// A 5x5 grid with a wall along x = 2, open only at z = 4.
+    fn walled_grid() -> NavGrid {
+        let mut grid = NavGrid::new(Vector2::zero(), 5, 5, 1.0);
+        for z in 0..4 {
+            grid.set_blocked(2, z, true);
+        }
+        grid
+    }
+
+    #[test]
+    fn test_find_path_around_wall() {
+        let grid = walled_grid();
+        let start = Vector3::new(0.5, 0.0, 0.5);
+        let goal = Vector3::new(4.5, 0.0, 0.5);
+
+        let path = grid.find_path(start, goal).unwrap();
+        assert_eq!(*path.last().unwrap(), goal);
+        // The path has to go through the gap at the top of the wall
+        assert!(path.iter().any(|p| p.z > 3.0));
+        for window in path.windows(2) {
+            for i in 0..=10 {
+                let point = window[0] + (window[1] - window[0]) * (i as f32 / 10.0);
+                let (x, z) = grid.cell_at(point).unwrap();
+                assert!(grid.is_walkable(x, z), "the path crosses ({x}, {z})");
+            }
+        }
+
+        // A straight line needs no points between the start and the goal
+        let path = grid.find_path(start, Vector3::new(0.5, 0.0, 4.5)).unwrap();
+        assert_eq!(path, vec![Vector3::new(0.5, 0.0, 4.5)]);
+    }
+
+    #[test]
+    fn test_unreachable_goal() {
+        let mut grid = walled_grid();
+        grid.set_blocked(2, 4, true);
+
+        assert!(grid
+            .find_path(Vector3::new(0.5, 0.0, 0.5), Vector3::new(4.5, 0.0, 0.5))
+            .is_none());
+        assert!(grid
+            .find_path(Vector3::new(0.5, 0.0, 0.5), Vector3::new(2.5, 0.0, 0.5))
+            .is_none());
+        assert!(grid
+            .find_path(Vector3::new(0.5, 0.0, 0.5), Vector3::new(-1.0, 0.0, 0.5))
+            .is_none());
+    }
+
+    #[test]
+    fn test_follow_path() {
+        let manager = ecs::Manager::default();
+        let grid = manager.create_entity();
+        manager.add_component_to_entity(grid, walled_grid());
+
+        let agent = manager.create_entity();
+        manager.add_component_to_entity(agent, Pos3::new(Vector3::new(0.5, 0.0, 0.5)));
+        let mut follower = PathFollower::new(grid, 2.0);
+        follower.set_target(Vector3::new(4.5, 0.0, 0.5));
+        manager.add_component_to_entity(agent, follower);
+
+        for _ in 0..100 {
+            update_path_followers(&manager, Duration::from_millis(100));
+        }
+
+        let pos = manager.get_component_from_entity::<Pos3>(agent).unwrap();
+        assert!((pos.read().unwrap().pos - Vector3::new(4.5, 0.0, 0.5)).magnitude() < 0.3);
+        assert_eq!(manager.drain_events::<DestinationReached>().len(), 1);
+        let follower = manager
+            .get_component_from_entity::<PathFollower>(agent)
+            .unwrap();
+        assert!(follower.read().unwrap().target().is_none());
+    }
+
+    #[test]
+    fn test_follower_steers_crowd_agent() {
+        let manager = ecs::Manager::default();
+        let grid = manager.create_entity();
+        manager.add_component_to_entity(grid, walled_grid());
+
+        let agent = manager.create_entity();
+        manager.add_component_to_entity(agent, Pos3::new(Vector3::new(0.5, 0.0, 0.5)));
+        manager.add_component_to_entity(agent, CrowdAgent::default());
+        let mut follower = PathFollower::new(grid, 2.0);
+        follower.set_target(Vector3::new(0.5, 0.0, 4.5));
+        manager.add_component_to_entity(agent, follower);
+
+        update_path_followers(&manager, Duration::from_millis(100));
+
+        // The follower only sets the preferred velocity, the crowd system moves the agent
+        let pos = manager.get_component_from_entity::<Pos3>(agent).unwrap();
+        assert_eq!(pos.read().unwrap().pos, Vector3::new(0.5, 0.0, 0.5));
+        let crowd_agent = manager
+            .get_component_from_entity::<CrowdAgent>(agent)
+            .unwrap();
+        assert_eq!(
+            crowd_agent.read().unwrap().preferred_velocity,
+            Vector3::new(0.0, 0.0, 2.0)
+        );
+    }
+}
@@ -0,0 +1,460 @@
+use super::crowd::CrowdAgent;
+use super::pathfinding::PathFollower;
+use crate::core::Dt;
+use crate::ecs::components::Pos3;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::{InnerSpace, Vector3, Zero};
+use rand::Rng;
+
+/// A point of a patrol route.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waypoint {
+    pub pos: Vector3<f32>,
+    /// The time in seconds an agent waits after reaching the waypoint.
+    pub pause: f32,
+    /// The waypoints that can follow this one with `PatrolOrder::Random`, all others if empty.
+    /// Links to missing waypoints are skipped.
+    pub links: Vec<usize>,
+}
+
+impl Waypoint {
+    pub fn new(pos: Vector3<f32>, pause: f32) -> Self {
+        Self {
+            pos,
+            pause,
+            links: Vec::new(),
+        }
+    }
+
+    pub fn with_links(mut self, links: Vec<usize>) -> Self {
+        self.links = links;
+        self
+    }
+}
+
+/// The order in which an agent visits the waypoints of a route.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum PatrolOrder {
+    /// Visit the waypoints in order and start over.
+    #[default]
+    Loop,
+    /// Visit the waypoints in order, then in reverse.
+    PingPong,
+    /// Move to a random linked waypoint.
+    Random,
+}
+
+/// A component that stores a named waypoint graph.
+/// Attach it to a route entity and reference the entity from `Patroller`s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatrolRoute {
+    pub name: String,
+    pub waypoints: Vec<Waypoint>,
+}
+
+impl Component for PatrolRoute {}
+
+impl PatrolRoute {
+    pub fn new(name: impl Into<String>, waypoints: Vec<Waypoint>) -> Self {
+        Self {
+            name: name.into(),
+            waypoints,
+        }
+    }
+
+    /// Parse a route from text data.
+    /// Each non-empty line is a waypoint as `x y z [pause] [-> link link ...]`, lines starting with `#` are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use gears::ai::patrol::PatrolRoute;
+    ///
+    /// let route = PatrolRoute::parse("gate", "0 0 0 2.0\n10 0 0 -> 0\n").unwrap();
+    /// assert_eq!(route.waypoints.len(), 2);
+    /// ```
+    pub fn parse(name: impl Into<String>, data: &str) -> anyhow::Result<Self> {
+        let mut waypoints = Vec::new();
+
+        for (line_number, line) in data.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (point, links) = match line.split_once("->") {
+                Some((point, links)) => (point, Some(links)),
+                None => (line, None),
+            };
+
+            let values = point
+                .split_whitespace()
+                .map(str::parse::<f32>)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| {
+                    anyhow::anyhow!("Invalid waypoint on line {}: {}", line_number + 1, e)
+                })?;
+            if !(3..=4).contains(&values.len()) {
+                anyhow::bail!(
+                    "Invalid waypoint on line {}: expected `x y z [pause]`",
+                    line_number + 1
+                );
+            }
+
+            let links = match links {
+                Some(links) => links
+                    .split_whitespace()
+                    .map(str::parse::<usize>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| {
+                        anyhow::anyhow!("Invalid link on line {}: {}", line_number + 1, e)
+                    })?,
+                None => Vec::new(),
+            };
+
+            waypoints.push(Waypoint {
+                pos: Vector3::new(values[0], values[1], values[2]),
+                pause: values.get(3).copied().unwrap_or(0.0),
+                links,
+            });
+        }
+
+        if let Some(link) = waypoints
+            .iter()
+            .flat_map(|w| w.links.iter())
+            .find(|l| **l >= waypoints.len())
+        {
+            anyhow::bail!("Link to a missing waypoint: {}", link);
+        }
+
+        Ok(Self::new(name, waypoints))
+    }
+
+    /// Get the waypoint that follows the current one.
+    fn next(&self, current: usize, order: PatrolOrder, forward: &mut bool) -> usize {
+        let count = self.waypoints.len();
+        if count <= 1 {
+            return 0;
+        }
+
+        match order {
+            PatrolOrder::Loop => (current + 1) % count,
+            PatrolOrder::PingPong => {
+                if *forward && current + 1 >= count {
+                    *forward = false;
+                } else if !*forward && current == 0 {
+                    *forward = true;
+                }
+                if *forward {
+                    current + 1
+                } else {
+                    current - 1
+                }
+            }
+            PatrolOrder::Random => {
+                let mut rng = rand::thread_rng();
+                let links = self.waypoints[current]
+                    .links
+                    .iter()
+                    .copied()
+                    .filter(|link| *link < count)
+                    .collect::<Vec<_>>();
+                if links.is_empty() {
+                    // Pick any other waypoint
+                    (current + rng.gen_range(1..count)) % count
+                } else {
+                    links[rng.gen_range(0..links.len())]
+                }
+            }
+        }
+    }
+}
+
+/// A component that makes an agent follow a `PatrolRoute`.
+/// Agents with a `PathFollower` walk to each waypoint along a path around the obstacles of its grid.
+/// Otherwise agents with a `CrowdAgent` are steered straight through its preferred velocity, others are moved directly.
+#[derive(Debug, Clone)]
+pub struct Patroller {
+    pub route: Entity,
+    pub order: PatrolOrder,
+    pub speed: f32,
+    /// The distance at which a waypoint counts as reached.
+    pub arrive_distance: f32,
+    pub enabled: bool,
+    current: usize,
+    forward: bool,
+    wait: f32,
+    /// The waypoint the path follower was sent to.
+    requested: Option<usize>,
+}
+
+impl Component for Patroller {}
+
+impl Patroller {
+    pub fn new(route: Entity, speed: f32) -> Self {
+        Self {
+            route,
+            order: PatrolOrder::Loop,
+            speed,
+            arrive_distance: 0.25,
+            enabled: true,
+            current: 0,
+            forward: true,
+            wait: 0.0,
+            requested: None,
+        }
+    }
+
+    pub fn with_order(mut self, order: PatrolOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Start the patrol at a waypoint.
+    pub fn with_start(mut self, waypoint: usize) -> Self {
+        self.current = waypoint;
+        self
+    }
+
+    /// Get the index of the waypoint the agent is heading to or waiting at.
+    pub fn current_waypoint(&self) -> usize {
+        self.current
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.wait > 0.0
+    }
+}
+
+/// Sent when a patroller reaches a waypoint.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WaypointReached {
+    pub entity: Entity,
+    pub route: Entity,
+    pub waypoint: usize,
+}
+
+/// Move all patrollers along their routes.
+/// Run it before `update_path_followers`, so a new waypoint is searched for in the same frame.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_patrollers(ecs: &ecs::Manager, dt: Dt) {
+    let dt = dt.as_secs_f32();
+
+    for (entity, patroller) in ecs.get_all_components_of_type::<Patroller>() {
+        let mut patroller = patroller.write().unwrap();
+        let crowd_agent = ecs.get_component_from_entity::<CrowdAgent>(entity);
+        let follower = ecs.get_component_from_entity::<PathFollower>(entity);
+
+        let (Some(route), Some(pos)) = (
+            ecs.get_component_from_entity::<PatrolRoute>(patroller.route),
+            ecs.get_component_from_entity::<Pos3>(entity),
+        ) else {
+            continue;
+        };
+        let route = route.read().unwrap();
+
+        let mut velocity = Vector3::zero();
+        if patroller.enabled && !route.waypoints.is_empty() {
+            if patroller.current >= route.waypoints.len() {
+                patroller.current = 0;
+            }
+
+            if patroller.wait > 0.0 {
+                patroller.wait -= dt;
+                if patroller.wait <= 0.0 {
+                    patroller.wait = 0.0;
+                    let order = patroller.order;
+                    let current = patroller.current;
+                    patroller.current = route.next(current, order, &mut patroller.forward);
+                }
+            } else {
+                let waypoint = &route.waypoints[patroller.current];
+                let mut pos = pos.write().unwrap();
+                let to_target = waypoint.pos - pos.pos;
+                let distance = to_target.magnitude();
+                let arrive_distance = follower.as_ref().map_or(patroller.arrive_distance, |f| {
+                    patroller
+                        .arrive_distance
+                        .max(f.read().unwrap().arrive_distance)
+                });
+
+                if distance <= arrive_distance {
+                    ecs.send_event(WaypointReached {
+                        entity,
+                        route: patroller.route,
+                        waypoint: patroller.current,
+                    });
+                    patroller.requested = None;
+                    if let Some(follower) = &follower {
+                        follower.write().unwrap().clear_target();
+                    }
+
+                    if waypoint.pause > 0.0 {
+                        patroller.wait = waypoint.pause;
+                    } else {
+                        let order = patroller.order;
+                        let current = patroller.current;
+                        patroller.current = route.next(current, order, &mut patroller.forward);
+                    }
+                } else if let Some(follower) = &follower {
+                    let mut follower = follower.write().unwrap();
+                    if patroller.requested != Some(patroller.current) {
+                        patroller.requested = Some(patroller.current);
+                        follower.speed = patroller.speed;
+                        follower.set_target(waypoint.pos);
+                    } else if follower.target().is_none() {
+                        // The follower gave up, there is no path to the waypoint
+                        log::warn!(
+                            "[AI] Waypoint {} of route {} can't be reached, skipping it",
+                            patroller.current,
+                            route.name
+                        );
+                        patroller.requested = None;
+                        let order = patroller.order;
+                        let current = patroller.current;
+                        patroller.current = route.next(current, order, &mut patroller.forward);
+                    }
+                } else if crowd_agent.is_some() {
+                    velocity = to_target / distance * patroller.speed;
+                } else {
+                    // Do not overshoot the waypoint
+                    let step = (patroller.speed * dt).min(distance);
+                    pos.pos += to_target / distance * step;
+                }
+            }
+        }
+
+        // The path follower steers the crowd agent itself
+        if let (Some(agent), None) = (crowd_agent, &follower) {
+            agent.write().unwrap().preferred_velocity = velocity;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::pathfinding::{update_path_followers, NavGrid};
+    use super::*;
+    use instant::Duration;
+
+    #[test]
+    fn test_parse_route() {
+        let route = PatrolRoute::parse(
+            "yard",
+            "# corners of the yard\n0 0 0 1.5\n10 0 0\n10 0 10 -> 0 1\n",
+        )
+        .unwrap();
+        assert_eq!(route.waypoints.len(), 3);
+        assert_eq!(route.waypoints[0].pause, 1.5);
+        assert_eq!(route.waypoints[2].links, vec![0, 1]);
+
+        assert!(PatrolRoute::parse("broken", "0 0").is_err());
+        assert!(PatrolRoute::parse("broken", "0 0 0 -> 3").is_err());
+    }
+
+    #[test]
+    fn test_missing_links_are_skipped() {
+        let route = PatrolRoute::new(
+            "built",
+            vec![
+                Waypoint::new(Vector3::zero(), 0.0).with_links(vec![5, 1]),
+                Waypoint::new(Vector3::unit_x(), 0.0).with_links(vec![7]),
+                Waypoint::new(Vector3::unit_z(), 0.0),
+            ],
+        );
+
+        let mut forward = true;
+        for _ in 0..32 {
+            assert_eq!(route.next(0, PatrolOrder::Random, &mut forward), 1);
+            assert_ne!(route.next(1, PatrolOrder::Random, &mut forward), 1);
+        }
+    }
+
+    #[test]
+    fn test_patrol_with_pause() {
+        let manager = ecs::Manager::default();
+        let route = manager.create_entity();
+        manager.add_component_to_entity(
+            route,
+            PatrolRoute::new(
+                "line",
+                vec![
+                    Waypoint::new(Vector3::new(0.0, 0.0, 0.0), 0.0),
+                    Waypoint::new(Vector3::new(2.0, 0.0, 0.0), 1.0),
+                ],
+            ),
+        );
+
+        let guard = manager.create_entity();
+        manager.add_component_to_entity(guard, Pos3::default());
+        manager.add_component_to_entity(
+            guard,
+            Patroller::new(route, 1.0).with_order(PatrolOrder::PingPong),
+        );
+
+        // Reach the first waypoint, then walk for two seconds to the second
+        for _ in 0..3 {
+            update_patrollers(&manager, Duration::from_secs(1));
+        }
+        update_patrollers(&manager, Duration::from_millis(100));
+
+        let patroller = manager
+            .get_component_from_entity::<Patroller>(guard)
+            .unwrap();
+        assert!(patroller.read().unwrap().is_waiting());
+        assert_eq!(manager.drain_events::<WaypointReached>().len(), 2);
+
+        update_patrollers(&manager, Duration::from_secs(1));
+        assert!(!patroller.read().unwrap().is_waiting());
+        assert_eq!(patroller.read().unwrap().current_waypoint(), 0);
+    }
+
+    #[test]
+    fn test_patrol_around_wall() {
+        let manager = ecs::Manager::default();
+        let mut grid = NavGrid::new(cgmath::Vector2::zero(), 5, 5, 1.0);
+        for z in 0..4 {
+            grid.set_blocked(2, z, true);
+        }
+        let grid_entity = manager.create_entity();
+        manager.add_component_to_entity(grid_entity, grid.clone());
+
+        let route = manager.create_entity();
+        manager.add_component_to_entity(
+            route,
+            PatrolRoute::new(
+                "wall",
+                vec![
+                    Waypoint::new(Vector3::new(0.5, 0.0, 0.5), 0.0),
+                    Waypoint::new(Vector3::new(4.5, 0.0, 0.5), 0.0),
+                ],
+            ),
+        );
+
+        let guard = manager.create_entity();
+        manager.add_component_to_entity(guard, Pos3::new(Vector3::new(0.5, 0.0, 0.5)));
+        manager.add_component_to_entity(guard, Patroller::new(route, 2.0));
+        manager.add_component_to_entity(guard, PathFollower::new(grid_entity, 1.0));
+
+        for _ in 0..150 {
+            update_patrollers(&manager, Duration::from_millis(100));
+            update_path_followers(&manager, Duration::from_millis(100));
+
+            let pos = manager.get_component_from_entity::<Pos3>(guard).unwrap();
+            let (x, z) = grid.cell_at(pos.read().unwrap().pos).unwrap();
+            assert!(grid.is_walkable(x, z), "the guard walked into ({x}, {z})");
+        }
+
+        let reached = manager
+            .drain_events::<WaypointReached>()
+            .iter()
+            .map(|e| e.waypoint)
+            .collect::<Vec<_>>();
+        assert!(reached.starts_with(&[0, 1, 0]), "reached {reached:?}");
+    }
+}
@@ -0,0 +1,347 @@
+use crate::core::Dt;
+use crate::ecs::components::Pos3;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::{Deg, InnerSpace, Rad, Vector3};
+
+/// A component that marks an entity as something AI agents can see.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Perceivable;
+
+impl Component for Perceivable {}
+
+crate::impl_marker!(Perceivable, "can be seen by AI agents", requires[Pos3]);
+
+/// A component that blocks the line of sight with a sphere around its `Pos3`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Occluder {
+    pub radius: f32,
+}
+
+impl Component for Occluder {}
+
+/// A noise that can be heard by AI agents.
+/// Send it with `ecs::Manager::send_event`, it is consumed by `update_perception`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Noise {
+    /// The entity that made the noise, if any.
+    pub source: Option<Entity>,
+    pub position: Vector3<f32>,
+    /// Multiplies the hearing radius of the listeners.
+    pub loudness: f32,
+}
+
+/// A noise heard by an agent.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HeardNoise {
+    pub source: Option<Entity>,
+    pub position: Vector3<f32>,
+}
+
+/// A component that gives an agent sight and hearing.
+/// The facts gathered by `update_perception` can be read from the component or
+/// received as `TargetSpotted`, `TargetLost` and `NoiseHeard` events.
+#[derive(Debug, Clone)]
+pub struct Perception {
+    pub view_distance: f32,
+    /// The full angle of the sight cone.
+    pub fov: Deg<f32>,
+    pub hearing_radius: f32,
+    /// The direction the agent looks at in local space, rotated by the `Pos3` rotation.
+    pub forward: Vector3<f32>,
+    /// The height of the eyes above the `Pos3` of the agent.
+    pub eye_height: f32,
+    pub enabled: bool,
+    visible: Vec<Entity>,
+    heard: Vec<HeardNoise>,
+}
+
+impl Component for Perception {}
+
+impl Default for Perception {
+    fn default() -> Self {
+        Self {
+            view_distance: 20.0,
+            fov: Deg(90.0),
+            hearing_radius: 10.0,
+            forward: Vector3::unit_z(),
+            eye_height: 0.0,
+            enabled: true,
+            visible: Vec::new(),
+            heard: Vec::new(),
+        }
+    }
+}
+
+impl Perception {
+    pub fn new(view_distance: f32, fov: Deg<f32>, hearing_radius: f32) -> Self {
+        Self {
+            view_distance,
+            fov,
+            hearing_radius,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_eye_height(mut self, eye_height: f32) -> Self {
+        self.eye_height = eye_height;
+        self
+    }
+
+    /// Get the entities that were visible in the last update.
+    pub fn visible(&self) -> &[Entity] {
+        &self.visible
+    }
+
+    pub fn can_see(&self, entity: Entity) -> bool {
+        self.visible.contains(&entity)
+    }
+
+    /// Get the noises that were heard in the last update.
+    pub fn heard(&self) -> &[HeardNoise] {
+        &self.heard
+    }
+
+    fn in_view(&self, eye: Vector3<f32>, forward: Vector3<f32>, target: Vector3<f32>) -> bool {
+        let to_target = target - eye;
+        let distance = to_target.magnitude();
+        if distance > self.view_distance {
+            return false;
+        }
+        if distance <= f32::EPSILON {
+            return true;
+        }
+
+        let half_fov: Rad<f32> = (self.fov / 2.0).into();
+        to_target.dot(forward) / distance >= half_fov.0.cos()
+    }
+}
+
+/// Sent when a `Perceivable` entity becomes visible to an agent.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TargetSpotted {
+    pub observer: Entity,
+    pub target: Entity,
+}
+
+/// Sent when a `Perceivable` entity is no longer visible to an agent.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TargetLost {
+    pub observer: Entity,
+    pub target: Entity,
+}
+
+/// Sent when an agent hears a noise.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NoiseHeard {
+    pub listener: Entity,
+    pub noise: HeardNoise,
+}
+
+/// Check if the line between two points is blocked by an `Occluder`.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `from` - The start of the line.
+/// * `to` - The end of the line.
+/// * `ignore` - The entities whose occluders are skipped, e.g. the observer and the target.
+pub fn line_of_sight(
+    ecs: &ecs::Manager,
+    from: Vector3<f32>,
+    to: Vector3<f32>,
+    ignore: &[Entity],
+) -> bool {
+    occluders_between(ecs, from, to, ignore) == 0
+}
+
+/// Count the `Occluder`s that intersect the line between two points.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `from` - The start of the line.
+/// * `to` - The end of the line.
+/// * `ignore` - The entities whose occluders are skipped.
+pub fn occluders_between(
+    ecs: &ecs::Manager,
+    from: Vector3<f32>,
+    to: Vector3<f32>,
+    ignore: &[Entity],
+) -> usize {
+    let line = to - from;
+    let length = line.magnitude();
+    if length <= f32::EPSILON {
+        return 0;
+    }
+    let direction = line / length;
+
+    ecs.get_all_components_of_type::<Occluder>()
+        .into_iter()
+        .filter(|(entity, occluder)| {
+            if ignore.contains(entity) {
+                return false;
+            }
+            let Some(pos) = ecs.get_component_from_entity::<Pos3>(*entity) else {
+                return false;
+            };
+
+            let radius = occluder.read().unwrap().radius;
+            let center = pos.read().unwrap().pos;
+            let t = (center - from).dot(direction).clamp(0.0, length);
+            let closest = from + direction * t;
+            (center - closest).magnitude2() <= radius * radius
+        })
+        .count()
+}
+
+/// Update the sight and hearing of all agents with a `Perception`.
+/// All pending `Noise` events are consumed.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `_dt` - The delta time since the last update.
+pub fn update_perception(ecs: &ecs::Manager, _dt: Dt) {
+    let noises = ecs.drain_events::<Noise>();
+    let targets: Vec<(Entity, Vector3<f32>)> = ecs
+        .get_entites_with_component::<Perceivable>()
+        .into_iter()
+        .filter_map(|entity| {
+            let pos = ecs.get_component_from_entity::<Pos3>(entity)?;
+            let pos = pos.read().unwrap().pos;
+            Some((entity, pos))
+        })
+        .collect();
+
+    for (observer, perception) in ecs.get_all_components_of_type::<Perception>() {
+        let Some(pos) = ecs.get_component_from_entity::<Pos3>(observer) else {
+            continue;
+        };
+        let pos = *pos.read().unwrap();
+        let mut perception = perception.write().unwrap();
+
+        if !perception.enabled {
+            perception.visible.clear();
+            perception.heard.clear();
+            continue;
+        }
+
+        let eye = pos.pos + Vector3::unit_y() * perception.eye_height;
+        let forward = match pos.rot {
+            Some(rot) => rot * perception.forward,
+            None => perception.forward,
+        }
+        .normalize();
+
+        let visible: Vec<Entity> = targets
+            .iter()
+            .filter(|(target, target_pos)| {
+                *target != observer
+                    && perception.in_view(eye, forward, *target_pos)
+                    && line_of_sight(ecs, eye, *target_pos, &[observer, *target])
+            })
+            .map(|(target, _)| *target)
+            .collect();
+
+        for target in visible.iter().filter(|t| !perception.visible.contains(t)) {
+            ecs.send_event(TargetSpotted {
+                observer,
+                target: *target,
+            });
+        }
+        for target in perception.visible.iter().filter(|t| !visible.contains(t)) {
+            ecs.send_event(TargetLost {
+                observer,
+                target: *target,
+            });
+        }
+        perception.visible = visible;
+
+        perception.heard = noises
+            .iter()
+            .filter(|noise| {
+                noise.source != Some(observer)
+                    && (noise.position - pos.pos).magnitude()
+                        <= perception.hearing_radius * noise.loudness
+            })
+            .map(|noise| HeardNoise {
+                source: noise.source,
+                position: noise.position,
+            })
+            .collect();
+        for noise in perception.heard.iter() {
+            ecs.send_event(NoiseHeard {
+                listener: observer,
+                noise: *noise,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instant::Duration;
+
+    fn spawn(ecs: &ecs::Manager, pos: Vector3<f32>) -> Entity {
+        let entity = ecs.create_entity();
+        ecs.add_component_to_entity(entity, Pos3::new(pos));
+        entity
+    }
+
+    #[test]
+    fn test_sight_cone_and_occlusion() {
+        let manager = ecs::Manager::default();
+        let guard = spawn(&manager, Vector3::new(0.0, 0.0, 0.0));
+        manager.add_component_to_entity(guard, Perception::new(10.0, Deg(90.0), 5.0));
+
+        let front = spawn(&manager, Vector3::new(0.0, 0.0, 5.0));
+        manager.add_component_to_entity(front, Perceivable);
+        let behind = spawn(&manager, Vector3::new(0.0, 0.0, -5.0));
+        manager.add_component_to_entity(behind, Perceivable);
+
+        update_perception(&manager, Duration::from_millis(16));
+        let perception = manager
+            .get_component_from_entity::<Perception>(guard)
+            .unwrap();
+        assert_eq!(perception.read().unwrap().visible(), &[front]);
+        assert_eq!(manager.drain_events::<TargetSpotted>().len(), 1);
+
+        let wall = spawn(&manager, Vector3::new(0.0, 0.0, 2.5));
+        manager.add_component_to_entity(wall, Occluder { radius: 1.0 });
+
+        update_perception(&manager, Duration::from_millis(16));
+        assert!(!perception.read().unwrap().can_see(front));
+        assert_eq!(
+            manager.drain_events::<TargetLost>(),
+            vec![TargetLost {
+                observer: guard,
+                target: front
+            }]
+        );
+    }
+
+    #[test]
+    fn test_hearing() {
+        let manager = ecs::Manager::default();
+        let guard = spawn(&manager, Vector3::new(0.0, 0.0, 0.0));
+        manager.add_component_to_entity(guard, Perception::new(10.0, Deg(90.0), 5.0));
+
+        manager.send_event(Noise {
+            source: None,
+            position: Vector3::new(-4.0, 0.0, 0.0),
+            loudness: 1.0,
+        });
+        manager.send_event(Noise {
+            source: None,
+            position: Vector3::new(-8.0, 0.0, 0.0),
+            loudness: 1.0,
+        });
+
+        update_perception(&manager, Duration::from_millis(16));
+        let heard = manager.drain_events::<NoiseHeard>();
+        assert_eq!(heard.len(), 1);
+        assert_eq!(heard[0].noise.position, Vector3::new(-4.0, 0.0, 0.0));
+    }
+}
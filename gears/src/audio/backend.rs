@@ -0,0 +1,379 @@
+use super::environment::AudioEnvironment;
+use super::mixer::{Mixer, Sound, VoiceParams};
+use super::music::MusicController;
+use super::source::{AudioListener, AudioSource};
+use crate::core::Dt;
+use crate::ecs::{self, Entity};
+use crate::gameplay::dialogue::DialoguePlayer;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// What a voice of the mixer is played for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VoiceKey {
+    /// The `AudioSource` of an entity.
+    Source(Entity),
+    /// A stem of a track played by the `MusicController` of an entity.
+    Music {
+        controller: Entity,
+        playback: u64,
+        stem: String,
+    },
+    /// The voice over of the current line of the `DialoguePlayer` of an entity.
+    Dialogue(Entity),
+}
+
+/// How far in seconds a music voice may drift from its controller before it is moved back.
+const MUSIC_DRIFT: f32 = 0.2;
+
+/// The resource that plays the audio components through a `Mixer`.
+/// The mixer is shared with the output device, which pulls the mixed samples from it.
+pub struct AudioBackend {
+    mixer: Arc<Mutex<Mixer<VoiceKey>>>,
+    /// The decoded sounds by path, `None` for the ones that failed to load.
+    sounds: HashMap<String, Option<Sound>>,
+    /// The path each voice was started with, a voice is restarted when it changes.
+    paths: HashMap<VoiceKey, String>,
+    /// The line each dialogue player had when its voice over was started.
+    lines: HashMap<Entity, u64>,
+}
+
+impl AudioBackend {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            mixer: Arc::new(Mutex::new(Mixer::new(sample_rate))),
+            sounds: HashMap::new(),
+            paths: HashMap::new(),
+            lines: HashMap::new(),
+        }
+    }
+
+    pub fn mixer(&self) -> Arc<Mutex<Mixer<VoiceKey>>> {
+        Arc::clone(&self.mixer)
+    }
+
+    /// Add a sound that is played instead of loading its path, e.g. to preload it or a generated sound.
+    pub fn insert_sound(&mut self, path: impl Into<String>, sound: Sound) {
+        self.sounds.insert(path.into(), Some(sound));
+    }
+
+    /// Get a sound, it is loaded from the vfs the first time.
+    fn sound(&mut self, path: &str) -> Option<Sound> {
+        self.sounds
+            .entry(path.to_string())
+            .or_insert_with(|| {
+                Sound::load(path)
+                    .map_err(|e| log::warn!("[Audio] {}", e))
+                    .ok()
+            })
+            .clone()
+    }
+
+    /// Start a voice, or change how it is heard if it is already playing the same path.
+    fn play(
+        &mut self,
+        mixer: &mut Mixer<VoiceKey>,
+        key: VoiceKey,
+        path: &str,
+        params: VoiceParams,
+        position: f32,
+    ) {
+        if self.paths.get(&key).is_some_and(|p| p == path) {
+            mixer.set_params(&key, params);
+            return;
+        }
+        self.paths.insert(key.clone(), path.to_string());
+        match self.sound(path) {
+            Some(sound) => mixer.play(key, sound, params, position),
+            None => mixer.stop(&key),
+        }
+    }
+}
+
+/// Play the audio components through the `AudioBackend` resource, nothing is played without it.
+/// The gain, panning and filtering of the sources is taken from the last `update_audio_sources`,
+/// the reverb from the `AudioEnvironment` of the listener. The stems of the music controllers
+/// follow their `voices`, so the crossfades and the intensity layers are heard.
+/// The voice over of the current line of each dialogue player is played until the line ends.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_audio_backend(ecs: &ecs::Manager, _dt: Dt) {
+    let Some(backend) = ecs.get_resource::<AudioBackend>() else {
+        return;
+    };
+    let mut backend = backend.write().unwrap();
+    let mixer = backend.mixer();
+    let mut mixer = mixer.lock().unwrap();
+    let mut playing = HashSet::new();
+
+    let reverb = ecs
+        .get_entites_with_component::<AudioListener>()
+        .into_iter()
+        .find_map(|entity| ecs.get_component_from_entity::<AudioEnvironment>(entity))
+        .map(|environment| environment.read().unwrap().reverb())
+        .unwrap_or_default();
+    mixer.set_reverb(reverb);
+
+    for (entity, source) in ecs.get_all_components_of_type::<AudioSource>() {
+        let source = source.read().unwrap();
+        let key = VoiceKey::Source(entity);
+        let params = VoiceParams {
+            gain: source.gain(),
+            pan: source.pan(),
+            lowpass: source.lowpass(),
+            looping: source.looping,
+            // Only the sounds of the world are heard in the room of the listener
+            reverb: source.spatial,
+            ..Default::default()
+        };
+        backend.play(&mut mixer, key.clone(), &source.path, params, 0.0);
+        playing.insert(key);
+    }
+
+    for (entity, controller) in ecs.get_all_components_of_type::<MusicController>() {
+        for voice in controller.read().unwrap().voices() {
+            let key = VoiceKey::Music {
+                controller: entity,
+                playback: voice.playback,
+                stem: voice.stem,
+            };
+            let params = VoiceParams {
+                gain: voice.volume,
+                looping: voice.loop_start.is_some(),
+                loop_start: voice.loop_start.unwrap_or_default(),
+                ..Default::default()
+            };
+            backend.play(&mut mixer, key.clone(), &voice.path, params, voice.position);
+
+            // The stems of a track are kept in sync with the time of the controller
+            if mixer
+                .position(&key)
+                .is_some_and(|position| (position - voice.position).abs() > MUSIC_DRIFT)
+            {
+                mixer.seek(&key, voice.position);
+            }
+            playing.insert(key);
+        }
+    }
+
+    for (entity, player) in ecs.get_all_components_of_type::<DialoguePlayer>() {
+        let player = player.read().unwrap();
+        let Some(path) = player.current().and_then(|line| line.audio.as_ref()) else {
+            continue;
+        };
+        let key = VoiceKey::Dialogue(entity);
+        // A new line is started from the beginning, even with the voice over of the last one
+        if backend.lines.insert(entity, player.lines_started()) != Some(player.lines_started()) {
+            backend.paths.remove(&key);
+        }
+        backend.play(
+            &mut mixer,
+            key.clone(),
+            path,
+            VoiceParams::default(),
+            player.elapsed(),
+        );
+        playing.insert(key);
+    }
+
+    mixer.retain(|key| playing.contains(key));
+    backend.paths.retain(|key, _| playing.contains(key));
+    backend
+        .lines
+        .retain(|entity, _| playing.contains(&VoiceKey::Dialogue(*entity)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::music::{update_music, MusicCommand, MusicTrack, Stem};
+    use crate::audio::source::update_audio_sources;
+    use crate::ecs::components::Pos3;
+    use crate::gameplay::dialogue::{update_dialogue, Dialogue, DialogueLine};
+    use cgmath::Vector3;
+    use instant::Duration;
+
+    fn backend(manager: &ecs::Manager) -> Arc<Mutex<Mixer<VoiceKey>>> {
+        let mut backend = AudioBackend::new(48000);
+        backend.insert_sound("hum.wav", Sound::from_samples(vec![1.0; 100], 1, 100));
+        let mixer = backend.mixer();
+        manager.insert_resource(backend);
+        mixer
+    }
+
+    #[test]
+    fn test_sources_are_played() {
+        let manager = ecs::Manager::default();
+        let mixer = backend(&manager);
+
+        let listener = manager.create_entity();
+        manager.add_component_to_entity(listener, Pos3::default());
+        manager.add_component_to_entity(listener, AudioListener);
+        let hum = manager.create_entity();
+        manager.add_component_to_entity(hum, Pos3::new(Vector3::new(4.0, 0.0, 0.0)));
+        manager.add_component_to_entity(hum, AudioSource::new("hum.wav").spatial(1.0, 10.0));
+        let missing = manager.create_entity();
+        manager.add_component_to_entity(missing, AudioSource::new("missing.wav"));
+
+        update_audio_sources(&manager, Duration::from_millis(16));
+        update_audio_backend(&manager, Duration::from_millis(16));
+
+        let mut out = vec![0.0; 20];
+        mixer.lock().unwrap().mix(&mut out);
+        // A quarter of the volume at four times the full volume distance, on the right side
+        assert_eq!((out[0], out[1]), (0.0, 0.25));
+        assert!(!mixer.lock().unwrap().contains(&VoiceKey::Source(missing)));
+
+        manager.remove_component_from_entity::<AudioSource>(hum);
+        update_audio_backend(&manager, Duration::from_millis(16));
+        assert_eq!(mixer.lock().unwrap().keys().count(), 0);
+    }
+
+    #[test]
+    fn test_occluded_source_is_muffled() {
+        let manager = ecs::Manager::default();
+        let mixer = backend(&manager);
+
+        let listener = manager.create_entity();
+        manager.add_component_to_entity(listener, Pos3::default());
+        manager.add_component_to_entity(listener, AudioListener);
+        manager.add_component_to_entity(listener, AudioEnvironment::default());
+        let hum = manager.create_entity();
+        manager.add_component_to_entity(hum, Pos3::new(Vector3::new(4.0, 0.0, 0.0)));
+        manager.add_component_to_entity(hum, AudioSource::new("hum.wav").spatial(10.0, 20.0));
+        let wall = manager.create_entity();
+        manager.add_component_to_entity(wall, Pos3::new(Vector3::new(2.0, 0.0, 0.0)));
+        manager.add_component_to_entity(wall, crate::ai::perception::Occluder { radius: 0.5 });
+
+        update_audio_sources(&manager, Duration::from_millis(16));
+        update_audio_backend(&manager, Duration::from_millis(16));
+
+        let mut out = vec![0.0; 20];
+        mixer.lock().unwrap().mix(&mut out);
+        // The lowpass makes the step of the sound rise slowly, below the occluded volume
+        let occluded = AudioEnvironment::default().occluder_gain;
+        assert!(out[1] > 0.0 && out[1] < occluded * 0.5, "{}", out[1]);
+    }
+
+    #[test]
+    fn test_music_crossfade() {
+        let manager = ecs::Manager::default();
+        let mixer = backend(&manager);
+        {
+            let backend = manager.get_resource::<AudioBackend>().unwrap();
+            let mut backend = backend.write().unwrap();
+            backend.insert_sound("calm.ogg", Sound::from_samples(vec![0.5; 1000], 1, 100));
+            backend.insert_sound("drums.ogg", Sound::from_samples(vec![0.25; 1000], 1, 100));
+        }
+
+        let music = manager.create_entity();
+        manager.add_component_to_entity(
+            music,
+            MusicController::new()
+                .with_track(MusicTrack::new(
+                    "explore",
+                    10.0,
+                    vec![
+                        Stem::new("base", 0.0).with_path("calm.ogg"),
+                        Stem::new("combat", 0.5).with_path("drums.ogg"),
+                    ],
+                ))
+                .with_track(MusicTrack::new(
+                    "boss",
+                    10.0,
+                    vec![Stem::new("base", 0.0).with_path("hum.wav")],
+                )),
+        );
+        let mut out = vec![0.0; 2];
+        let mut step = |command: Option<MusicCommand>, seconds: f32| {
+            if let Some(command) = command {
+                manager.send_event(command);
+            }
+            update_music(&manager, Duration::from_secs_f32(seconds));
+            update_audio_backend(&manager, Duration::from_secs_f32(seconds));
+            mixer.lock().unwrap().mix(&mut out);
+            out[0]
+        };
+
+        let play = |track: &str| MusicCommand::Play {
+            track: track.to_string(),
+            fade: 2.0,
+        };
+        step(Some(play("explore")), 2.0);
+        assert_eq!(step(None, 0.0), 0.5);
+        // The combat stem fades in over the stem fade of two seconds
+        step(Some(MusicCommand::SetIntensity(1.0)), 2.0);
+        assert_eq!(step(None, 0.0), 0.75);
+
+        // Halfway through the crossfade both tracks are heard at half volume
+        assert_eq!(step(Some(play("boss")), 1.0), 0.75 * 0.5 + 0.5);
+        assert_eq!(step(None, 1.0), 1.0);
+        assert_eq!(mixer.lock().unwrap().keys().count(), 1);
+    }
+
+    #[test]
+    fn test_music_stays_in_sync() {
+        let manager = ecs::Manager::default();
+        let mixer = backend(&manager);
+        {
+            let backend = manager.get_resource::<AudioBackend>().unwrap();
+            let sound = Sound::from_samples(vec![1.0; 150], 1, 100);
+            backend.write().unwrap().insert_sound("loop.ogg", sound);
+        }
+        let mut controller = MusicController::new().with_track(
+            MusicTrack::new(
+                "loop",
+                1.0,
+                vec![Stem::new("base", 0.0).with_path("loop.ogg")],
+            )
+            .with_intro(0.5),
+        );
+        controller.play("loop", 0.0);
+        let music = manager.create_entity();
+        manager.add_component_to_entity(music, controller);
+
+        update_audio_backend(&manager, Duration::ZERO);
+        update_music(&manager, Duration::from_secs_f32(1.25));
+        update_audio_backend(&manager, Duration::ZERO);
+
+        // The mixer is moved to the time of the controller, and loops after the intro
+        let key = mixer.lock().unwrap().keys().next().unwrap().clone();
+        assert_eq!(mixer.lock().unwrap().position(&key), Some(1.25));
+        let mut out = vec![0.0; 2 * 24000];
+        mixer.lock().unwrap().mix(&mut out);
+        let position = mixer.lock().unwrap().position(&key).unwrap();
+        assert!((position - 0.75).abs() < 0.01, "{position}");
+    }
+
+    #[test]
+    fn test_voice_over() {
+        let manager = ecs::Manager::default();
+        let mixer = backend(&manager);
+        let key = |entity| VoiceKey::Dialogue(entity);
+
+        let guard = manager.create_entity();
+        let mut player = DialoguePlayer::new();
+        let halt = DialogueLine::new("Guard", "Halt!", 1.0).with_audio("hum.wav");
+        player.play(&Dialogue::new(
+            "gate",
+            vec![halt.clone(), halt, DialogueLine::new("Guard", "...", 1.0)],
+        ));
+        manager.add_component_to_entity(guard, player);
+
+        let step = |seconds: f32| {
+            update_dialogue(&manager, Duration::from_secs_f32(seconds));
+            update_audio_backend(&manager, Duration::from_secs_f32(seconds));
+            mixer.lock().unwrap().position(&key(guard))
+        };
+        assert_eq!(step(0.0), Some(0.0));
+        assert_eq!(step(0.5), Some(0.0));
+        // The same voice over starts again for the next line
+        mixer.lock().unwrap().mix(&mut [0.0; 2 * 4800]);
+        assert_eq!(step(0.75), Some(0.25));
+        // A line without a voice over stops the last one
+        assert_eq!(step(1.0), None);
+    }
+}
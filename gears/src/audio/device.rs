@@ -0,0 +1,89 @@
+use super::backend::AudioBackend;
+use crate::ecs;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+/// Plays the mixer of the `AudioBackend` on the default output device with cpal.
+/// The sound stops when it is dropped.
+pub(crate) struct AudioOutput {
+    _stream: cpal::Stream,
+}
+
+impl AudioOutput {
+    /// Open the default output device and insert an `AudioBackend` at its sample rate.
+    ///
+    /// # Returns
+    ///
+    /// `None` if there is no output device or it can not be opened, the audio components are silent then.
+    pub fn open(ecs: &ecs::Manager) -> Option<Self> {
+        match Self::try_open(ecs) {
+            Ok(output) => Some(output),
+            Err(err) => {
+                log::warn!("[Audio] The sound can not be played: {}", err);
+                None
+            }
+        }
+    }
+
+    fn try_open(ecs: &ecs::Manager) -> anyhow::Result<Self> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| anyhow::anyhow!("No output device"))?;
+        let config = device.default_output_config()?;
+        let backend = AudioBackend::new(config.sample_rate().0);
+
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => Self::build::<f32>(&device, &config.into(), &backend),
+            cpal::SampleFormat::I16 => Self::build::<i16>(&device, &config.into(), &backend),
+            cpal::SampleFormat::U16 => Self::build::<u16>(&device, &config.into(), &backend),
+            format => anyhow::bail!("Unsupported sample format: {}", format),
+        }?;
+        stream.play()?;
+
+        log::info!(
+            "[Audio] Playing on {}",
+            device
+                .name()
+                .unwrap_or_else(|_| "the default device".to_string())
+        );
+        ecs.insert_resource(backend);
+        Ok(Self { _stream: stream })
+    }
+
+    fn build<T: cpal::SizedSample + cpal::FromSample<f32>>(
+        device: &cpal::Device,
+        config: &cpal::StreamConfig,
+        backend: &AudioBackend,
+    ) -> anyhow::Result<cpal::Stream> {
+        let mixer = backend.mixer();
+        let channels = config.channels as usize;
+        let mut stereo = Vec::new();
+
+        let stream = device.build_output_stream(
+            config,
+            move |data: &mut [T], _| {
+                let frames = data.len() / channels;
+                stereo.resize(frames * 2, 0.0);
+                mixer.lock().unwrap().mix(&mut stereo);
+
+                for (frame, (left, right)) in data
+                    .chunks_mut(channels)
+                    .zip(stereo.chunks(2).map(|s| (s[0], s[1])))
+                {
+                    // The first two channels are the front speakers, the others are left silent
+                    match frame {
+                        [mono] => *mono = T::from_sample((left + right) * 0.5),
+                        [l, r, rest @ ..] => {
+                            *l = T::from_sample(left);
+                            *r = T::from_sample(right);
+                            rest.fill(T::EQUILIBRIUM);
+                        }
+                        [] => {}
+                    }
+                }
+            },
+            |err| log::error!("[Audio] {}", err),
+            None,
+        )?;
+        Ok(stream)
+    }
+}
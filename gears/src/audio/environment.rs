@@ -0,0 +1,283 @@
+use crate::ai::perception;
+use crate::core::Dt;
+use crate::ecs::components::Pos3;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::{Vector3, Zero};
+
+/// The parameters of a reverb effect.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ReverbParams {
+    /// The mix of the reverberated signal, 0 is fully dry.
+    pub wet: f32,
+    /// The time in seconds for the reverb to decay by 60 dB.
+    pub decay_time: f32,
+    /// The damping of high frequencies in the reverb tail, in the range 0-1.
+    pub damping: f32,
+}
+
+impl Default for ReverbParams {
+    /// A dry environment, e.g. an open field.
+    fn default() -> Self {
+        Self {
+            wet: 0.0,
+            decay_time: 0.5,
+            damping: 0.5,
+        }
+    }
+}
+
+impl ReverbParams {
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        Self {
+            wet: mix(self.wet, other.wet),
+            decay_time: mix(self.decay_time, other.decay_time),
+            damping: mix(self.damping, other.damping),
+        }
+    }
+}
+
+/// A component that defines a box volume around its `Pos3` with its own reverb.
+/// Outside of the box the reverb fades out over the fade distance.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ReverbZone {
+    pub half_extents: Vector3<f32>,
+    pub fade_distance: f32,
+    pub params: ReverbParams,
+}
+
+impl Component for ReverbZone {}
+
+impl ReverbZone {
+    pub fn new(half_extents: Vector3<f32>, params: ReverbParams) -> Self {
+        Self {
+            half_extents,
+            fade_distance: 2.0,
+            params,
+        }
+    }
+
+    pub fn with_fade_distance(mut self, fade_distance: f32) -> Self {
+        self.fade_distance = fade_distance;
+        self
+    }
+
+    /// Get the weight of the zone at a position relative to its center, 1 inside and 0 beyond the fade distance.
+    fn weight(&self, local: Vector3<f32>) -> f32 {
+        let outside = Vector3::new(
+            (local.x.abs() - self.half_extents.x).max(0.0),
+            (local.y.abs() - self.half_extents.y).max(0.0),
+            (local.z.abs() - self.half_extents.z).max(0.0),
+        );
+        let distance =
+            (outside.x * outside.x + outside.y * outside.y + outside.z * outside.z).sqrt();
+
+        if distance <= 0.0 {
+            1.0
+        } else if self.fade_distance <= 0.0 {
+            0.0
+        } else {
+            (1.0 - distance / self.fade_distance).max(0.0)
+        }
+    }
+}
+
+/// Get the blended reverb of all zones at a position.
+/// Overlapping zones are averaged by their weights, any missing weight is filled with the default dry reverb.
+pub fn reverb_at(ecs: &ecs::Manager, pos: Vector3<f32>) -> ReverbParams {
+    let mut total = 0.0;
+    let mut blended = ReverbParams {
+        wet: 0.0,
+        decay_time: 0.0,
+        damping: 0.0,
+    };
+
+    for (entity, zone) in ecs.get_all_components_of_type::<ReverbZone>() {
+        let Some(center) = ecs.get_component_from_entity::<Pos3>(entity) else {
+            continue;
+        };
+
+        let zone = zone.read().unwrap();
+        let weight = zone.weight(pos - center.read().unwrap().pos);
+        if weight > 0.0 {
+            blended.wet += zone.params.wet * weight;
+            blended.decay_time += zone.params.decay_time * weight;
+            blended.damping += zone.params.damping * weight;
+            total += weight;
+        }
+    }
+
+    if total <= 0.0 {
+        return ReverbParams::default();
+    }
+
+    let averaged = ReverbParams {
+        wet: blended.wet / total,
+        decay_time: blended.decay_time / total,
+        damping: blended.damping / total,
+    };
+    if total >= 1.0 {
+        averaged
+    } else {
+        ReverbParams::default().lerp(&averaged, total)
+    }
+}
+
+/// The filtering of a sound caused by the geometry between the source and the listener.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Occlusion {
+    /// The volume multiplier.
+    pub gain: f32,
+    /// The fraction of the high frequencies that pass through, 1 is unfiltered.
+    pub lowpass: f32,
+}
+
+impl Default for Occlusion {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            lowpass: 1.0,
+        }
+    }
+}
+
+/// A component that stores the acoustic state of a listener, e.g. the camera entity.
+/// `update_audio_environments` crossfades the reverb towards the zones around the listener.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AudioEnvironment {
+    /// The time in seconds to crossfade to a new reverb.
+    pub crossfade_time: f32,
+    /// The volume multiplier applied by each occluder between a source and the listener.
+    pub occluder_gain: f32,
+    /// The high frequency multiplier applied by each occluder between a source and the listener.
+    pub occluder_lowpass: f32,
+    reverb: ReverbParams,
+}
+
+impl Component for AudioEnvironment {}
+
+impl Default for AudioEnvironment {
+    fn default() -> Self {
+        Self {
+            crossfade_time: 0.5,
+            occluder_gain: 0.6,
+            occluder_lowpass: 0.3,
+            reverb: ReverbParams::default(),
+        }
+    }
+}
+
+impl AudioEnvironment {
+    /// Get the current reverb at the listener.
+    pub fn reverb(&self) -> ReverbParams {
+        self.reverb
+    }
+
+    /// Get the occlusion of a sound source as heard by a listener.
+    /// The sphere `Occluder`s between the two are counted, every one of them attenuates the sound.
+    ///
+    /// # Arguments
+    ///
+    /// * `ecs` - The entity component system manager.
+    /// * `listener` - The position of the listener.
+    /// * `source` - The position of the sound source.
+    /// * `ignore` - The entities whose occluders are skipped, e.g. the emitter itself.
+    pub fn occlusion(
+        &self,
+        ecs: &ecs::Manager,
+        listener: Vector3<f32>,
+        source: Vector3<f32>,
+        ignore: &[Entity],
+    ) -> Occlusion {
+        let count = perception::occluders_between(ecs, listener, source, ignore) as i32;
+        Occlusion {
+            gain: self.occluder_gain.powi(count),
+            lowpass: self.occluder_lowpass.powi(count),
+        }
+    }
+}
+
+/// Crossfade the reverb of all listeners with an `AudioEnvironment` towards the zones around them.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_audio_environments(ecs: &ecs::Manager, dt: Dt) {
+    for (entity, environment) in ecs.get_all_components_of_type::<AudioEnvironment>() {
+        let pos = ecs
+            .get_component_from_entity::<Pos3>(entity)
+            .map_or(Vector3::zero(), |p| p.read().unwrap().pos);
+        let target = reverb_at(ecs, pos);
+
+        let mut environment = environment.write().unwrap();
+        let t = if environment.crossfade_time > 0.0 {
+            (dt.as_secs_f32() / environment.crossfade_time).min(1.0)
+        } else {
+            1.0
+        };
+        environment.reverb = environment.reverb.lerp(&target, t);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::perception::Occluder;
+    use instant::Duration;
+
+    const HALL: ReverbParams = ReverbParams {
+        wet: 0.8,
+        decay_time: 3.0,
+        damping: 0.2,
+    };
+
+    #[test]
+    fn test_reverb_zone_fades() {
+        let manager = ecs::Manager::default();
+        let hall = manager.create_entity();
+        manager.add_component_to_entity(hall, Pos3::default());
+        manager.add_component_to_entity(
+            hall,
+            ReverbZone::new(Vector3::new(5.0, 5.0, 5.0), HALL).with_fade_distance(2.0),
+        );
+
+        assert_eq!(reverb_at(&manager, Vector3::new(1.0, 0.0, 0.0)), HALL);
+        let edge = reverb_at(&manager, Vector3::new(6.0, 0.0, 0.0));
+        assert!((edge.wet - 0.4).abs() < 1e-5);
+        assert_eq!(
+            reverb_at(&manager, Vector3::new(10.0, 0.0, 0.0)),
+            ReverbParams::default()
+        );
+
+        let listener = manager.create_entity();
+        manager.add_component_to_entity(listener, Pos3::default());
+        manager.add_component_to_entity(listener, AudioEnvironment::default());
+        update_audio_environments(&manager, Duration::from_millis(250));
+
+        let environment = manager
+            .get_component_from_entity::<AudioEnvironment>(listener)
+            .unwrap();
+        let wet = environment.read().unwrap().reverb().wet;
+        assert!(wet > 0.0 && wet < HALL.wet);
+    }
+
+    #[test]
+    fn test_occlusion() {
+        let manager = ecs::Manager::default();
+        let wall = manager.create_entity();
+        manager.add_component_to_entity(wall, Pos3::new(Vector3::new(5.0, 0.0, 0.0)));
+        manager.add_component_to_entity(wall, Occluder { radius: 1.0 });
+
+        let environment = AudioEnvironment::default();
+        let clear =
+            environment.occlusion(&manager, Vector3::zero(), Vector3::new(0.0, 0.0, 10.0), &[]);
+        assert_eq!(clear, Occlusion::default());
+
+        let blocked =
+            environment.occlusion(&manager, Vector3::zero(), Vector3::new(10.0, 0.0, 0.0), &[]);
+        assert_eq!(blocked.gain, environment.occluder_gain);
+        assert_eq!(blocked.lowpass, environment.occluder_lowpass);
+    }
+}
@@ -0,0 +1,533 @@
+use super::environment::ReverbParams;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io::Cursor;
+use std::sync::Arc;
+
+/// Decoded audio, shared by the voices that play it.
+#[derive(Debug, Clone)]
+pub struct Sound {
+    sample_rate: u32,
+    channels: u16,
+    /// The interleaved samples in the range -1 to 1.
+    samples: Arc<[f32]>,
+}
+
+impl Sound {
+    /// # Arguments
+    ///
+    /// * `samples` - The interleaved samples in the range -1 to 1.
+    /// * `channels` - The number of channels, more than two are mixed down to stereo.
+    /// * `sample_rate` - The frames per second.
+    pub fn from_samples(samples: Vec<f32>, channels: u16, sample_rate: u32) -> Self {
+        assert!(channels > 0 && sample_rate > 0);
+        Self {
+            sample_rate,
+            channels,
+            samples: samples.into(),
+        }
+    }
+
+    /// Decode a WAV or an Ogg Vorbis file, the format is chosen by the extension of the path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file, only used for the format and the errors.
+    /// * `bytes` - The contents of the file.
+    pub fn decode(path: &str, bytes: Vec<u8>) -> anyhow::Result<Self> {
+        let extension = path.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("wav") => Self::decode_wav(bytes),
+            Some("ogg") => Self::decode_ogg(bytes),
+            _ => anyhow::bail!("Unsupported sound format: {}", path),
+        }
+        .map_err(|e| anyhow::anyhow!("Failed to decode {}: {}", path, e))
+    }
+
+    /// Load and decode a sound from the vfs.
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        Self::decode(path, crate::core::vfs::read(path)?)
+    }
+
+    fn decode_wav(bytes: Vec<u8>) -> anyhow::Result<Self> {
+        let mut reader = hound::WavReader::new(Cursor::new(bytes))?;
+        let spec = reader.spec();
+        let samples = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 * scale))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+        Ok(Self::from_samples(samples, spec.channels, spec.sample_rate))
+    }
+
+    fn decode_ogg(bytes: Vec<u8>) -> anyhow::Result<Self> {
+        let mut reader = lewton::inside_ogg::OggStreamReader::new(Cursor::new(bytes))?;
+        let mut samples = Vec::new();
+        while let Some(packet) = reader.read_dec_packet_itl()? {
+            samples.extend(packet.into_iter().map(|s| s as f32 / 32768.0));
+        }
+        Ok(Self::from_samples(
+            samples,
+            reader.ident_hdr.audio_channels as u16,
+            reader.ident_hdr.audio_sample_rate,
+        ))
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    /// Get the length in seconds.
+    pub fn duration(&self) -> f32 {
+        self.frames() as f32 / self.sample_rate as f32
+    }
+
+    /// Get a frame as stereo, mono is played on both sides and further channels are dropped.
+    fn frame(&self, index: usize) -> (f32, f32) {
+        let start = index * self.channels as usize;
+        match self.channels {
+            1 => (self.samples[start], self.samples[start]),
+            _ => (self.samples[start], self.samples[start + 1]),
+        }
+    }
+}
+
+/// How a voice is heard, set from the state of the audio components.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct VoiceParams {
+    pub gain: f32,
+    /// -1 is the left ear only and 1 the right ear only.
+    pub pan: f32,
+    /// The fraction of the high frequencies that pass through, 1 is unfiltered.
+    pub lowpass: f32,
+    pub looping: bool,
+    /// The position in seconds a looping voice jumps back to, e.g. the end of the intro of a music track.
+    pub loop_start: f32,
+    /// Send the voice through the reverb of the listener, for the sounds of the world.
+    pub reverb: bool,
+}
+
+impl Default for VoiceParams {
+    fn default() -> Self {
+        Self {
+            gain: 1.0,
+            pan: 0.0,
+            lowpass: 1.0,
+            looping: false,
+            loop_start: 0.0,
+            reverb: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Voice {
+    sound: Sound,
+    params: VoiceParams,
+    /// The position in frames of the sound.
+    position: f64,
+    /// The gain at the end of the last mix, the gain is ramped from it to avoid clicks.
+    gain: f32,
+    filter: (f32, f32),
+    finished: bool,
+}
+
+/// A comb filter with a lowpass in its feedback path, the building block of the reverb.
+#[derive(Debug)]
+struct Comb {
+    buffer: Vec<f32>,
+    index: usize,
+    filtered: f32,
+}
+
+impl Comb {
+    fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length.max(1)],
+            index: 0,
+            filtered: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let output = self.buffer[self.index];
+        self.filtered = output * (1.0 - damping) + self.filtered * damping;
+        self.buffer[self.index] = input + self.filtered * feedback;
+        self.index = (self.index + 1) % self.buffer.len();
+        output
+    }
+}
+
+/// A Schroeder reverb, parallel combs followed by allpasses for each side.
+#[derive(Debug)]
+struct Reverb {
+    params: ReverbParams,
+    combs: [Vec<Comb>; 2],
+    allpasses: [Vec<Comb>; 2],
+}
+
+/// The comb lengths of the Freeverb reverb at 44.1 kHz, the right side is spread by 23 samples.
+const COMB_LENGTHS: [usize; 4] = [1116, 1277, 1422, 1557];
+const ALLPASS_LENGTHS: [usize; 2] = [556, 441];
+
+impl Reverb {
+    fn new(sample_rate: u32) -> Self {
+        let scale = sample_rate as f32 / 44100.0;
+        let side = |spread: usize, lengths: &[usize]| {
+            lengths
+                .iter()
+                .map(|l| Comb::new(((l + spread) as f32 * scale) as usize))
+                .collect::<Vec<_>>()
+        };
+        Self {
+            params: ReverbParams::default(),
+            combs: [side(0, &COMB_LENGTHS), side(23, &COMB_LENGTHS)],
+            allpasses: [side(0, &ALLPASS_LENGTHS), side(23, &ALLPASS_LENGTHS)],
+        }
+    }
+
+    /// Get the feedback of each comb that makes it lose 60 dB over the decay time.
+    fn feedbacks(&self) -> [f32; COMB_LENGTHS.len()] {
+        let decay_time = self.params.decay_time.max(0.01);
+        COMB_LENGTHS.map(|length| 0.001f32.powf(length as f32 / 44100.0 / decay_time))
+    }
+
+    fn process(&mut self, input: f32, side: usize, feedbacks: &[f32]) -> f32 {
+        let damping = self.params.damping.clamp(0.0, 1.0);
+        let mut output = 0.0;
+        for (comb, feedback) in self.combs[side].iter_mut().zip(feedbacks) {
+            output += comb.process(input, *feedback, damping);
+        }
+        output /= self.combs[side].len() as f32;
+        for allpass in self.allpasses[side].iter_mut() {
+            let delayed = allpass.buffer[allpass.index];
+            allpass.buffer[allpass.index] = output + delayed * 0.5;
+            allpass.index = (allpass.index + 1) % allpass.buffer.len();
+            output = delayed - output;
+        }
+        output
+    }
+}
+
+/// Mixes the playing voices into interleaved stereo.
+/// The voices are keyed by whatever identifies their source, e.g. the entity of an `AudioSource`.
+#[derive(Debug)]
+pub struct Mixer<K> {
+    pub volume: f32,
+    sample_rate: u32,
+    voices: HashMap<K, Voice>,
+    reverb: Reverb,
+    /// The samples sent to the reverb, kept to not allocate while mixing.
+    send: Vec<f32>,
+}
+
+impl<K: Eq + Hash + Clone> Mixer<K> {
+    pub fn new(sample_rate: u32) -> Self {
+        assert!(sample_rate > 0);
+        Self {
+            volume: 1.0,
+            sample_rate,
+            voices: HashMap::new(),
+            reverb: Reverb::new(sample_rate),
+            send: Vec::new(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Start playing a sound, a voice with the same key is replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key the voice is changed with later.
+    /// * `sound` - The sound to play.
+    /// * `params` - How the voice is heard.
+    /// * `position` - The position in seconds to start from.
+    pub fn play(&mut self, key: K, sound: Sound, params: VoiceParams, position: f32) {
+        let position = position.max(0.0) as f64 * sound.sample_rate as f64;
+        self.voices.insert(
+            key,
+            Voice {
+                sound,
+                params,
+                position,
+                gain: params.gain,
+                filter: (0.0, 0.0),
+                finished: false,
+            },
+        );
+    }
+
+    /// Change how a voice is heard, the gain is ramped over the next mix.
+    pub fn set_params(&mut self, key: &K, params: VoiceParams) {
+        if let Some(voice) = self.voices.get_mut(key) {
+            voice.params = params;
+        }
+    }
+
+    /// Jump to a position in seconds.
+    pub fn seek(&mut self, key: &K, position: f32) {
+        if let Some(voice) = self.voices.get_mut(key) {
+            voice.position = position.max(0.0) as f64 * voice.sound.sample_rate as f64;
+            voice.finished = false;
+        }
+    }
+
+    pub fn stop(&mut self, key: &K) {
+        self.voices.remove(key);
+    }
+
+    /// Stop the voices whose key is not kept.
+    pub fn retain(&mut self, mut keep: impl FnMut(&K) -> bool) {
+        self.voices.retain(|key, _| keep(key));
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.voices.contains_key(key)
+    }
+
+    /// Check if a voice is still playing, a voice that is not looping stays in the mixer when it ends.
+    pub fn is_playing(&self, key: &K) -> bool {
+        self.voices.get(key).is_some_and(|voice| !voice.finished)
+    }
+
+    /// Get the playback position of a voice in seconds.
+    pub fn position(&self, key: &K) -> Option<f32> {
+        self.voices
+            .get(key)
+            .map(|voice| (voice.position / voice.sound.sample_rate as f64) as f32)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.voices.keys()
+    }
+
+    pub fn set_reverb(&mut self, params: ReverbParams) {
+        self.reverb.params = params;
+    }
+
+    /// Mix the voices into a buffer, the previous contents are overwritten.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - The interleaved stereo samples to fill.
+    pub fn mix(&mut self, out: &mut [f32]) {
+        out.fill(0.0);
+        let frames = out.len() / 2;
+        if frames == 0 {
+            return;
+        }
+        let mut send = std::mem::take(&mut self.send);
+        send.clear();
+        send.resize(out.len(), 0.0);
+
+        for voice in self.voices.values_mut().filter(|v| !v.finished) {
+            let params = voice.params;
+            let step = voice.sound.sample_rate as f64 / self.sample_rate as f64;
+            let length = voice.sound.frames();
+            let loop_start = (params.loop_start.max(0.0) as f64 * voice.sound.sample_rate as f64)
+                .min(length.saturating_sub(1) as f64);
+            let left_gain = (1.0 - params.pan).clamp(0.0, 1.0);
+            let right_gain = (1.0 + params.pan).clamp(0.0, 1.0);
+            // The one pole lowpass, its cutoff goes from 20 Hz to 20 kHz
+            let cutoff = 20.0 * 1000f32.powf(params.lowpass.clamp(0.0, 1.0));
+            let alpha = if params.lowpass >= 1.0 {
+                1.0
+            } else {
+                1.0 - (-2.0 * std::f32::consts::PI * cutoff / self.sample_rate as f32).exp()
+            };
+            let gain_step = (params.gain - voice.gain) / frames as f32;
+
+            for i in 0..frames {
+                if voice.position >= length as f64 {
+                    if params.looping && length > 0 {
+                        let loop_length = length as f64 - loop_start;
+                        voice.position =
+                            loop_start + (voice.position - length as f64) % loop_length;
+                    } else {
+                        voice.finished = true;
+                        break;
+                    }
+                }
+
+                // Interpolate linearly between the frames, for sounds of another sample rate
+                let index = voice.position as usize;
+                let t = (voice.position - index as f64) as f32;
+                let next = if index + 1 < length {
+                    index + 1
+                } else if params.looping {
+                    loop_start as usize
+                } else {
+                    index
+                };
+                let (l0, r0) = voice.sound.frame(index);
+                let (l1, r1) = voice.sound.frame(next);
+                let (l, r) = (l0 + (l1 - l0) * t, r0 + (r1 - r0) * t);
+
+                voice.filter.0 += alpha * (l - voice.filter.0);
+                voice.filter.1 += alpha * (r - voice.filter.1);
+                voice.gain += gain_step;
+                let l = voice.filter.0 * voice.gain * left_gain;
+                let r = voice.filter.1 * voice.gain * right_gain;
+
+                out[i * 2] += l;
+                out[i * 2 + 1] += r;
+                if params.reverb {
+                    send[i * 2] += l;
+                    send[i * 2 + 1] += r;
+                }
+                voice.position += step;
+            }
+            voice.gain = params.gain;
+        }
+
+        let wet = self.reverb.params.wet.clamp(0.0, 1.0);
+        let feedbacks = self.reverb.feedbacks();
+        for (i, sample) in out.iter_mut().enumerate() {
+            // Keep the reverb running while it's dry, so its tail fades out when leaving a zone
+            let reverb = self.reverb.process(send[i], i % 2, &feedbacks);
+            *sample = (*sample + reverb * wet) * self.volume;
+        }
+        self.send = send;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f32, seconds: f32, sample_rate: u32) -> Sound {
+        let samples = (0..(seconds * sample_rate as f32) as usize)
+            .map(|i| (i as f32 / sample_rate as f32 * frequency * std::f32::consts::TAU).sin())
+            .collect();
+        Sound::from_samples(samples, 1, sample_rate)
+    }
+
+    fn energy(samples: &[f32]) -> f32 {
+        samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32
+    }
+
+    #[test]
+    fn test_decode_wav() {
+        let mut bytes = Cursor::new(Vec::new());
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 22050,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for sample in [16384i16, -16384, 0, 32767] {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let sound = Sound::decode("beep.WAV", bytes.into_inner()).unwrap();
+        assert_eq!((sound.channels(), sound.sample_rate()), (2, 22050));
+        assert_eq!(sound.frames(), 2);
+        assert_eq!(sound.frame(0), (0.5, -0.5));
+        assert!(Sound::decode("beep.mp3", Vec::new()).is_err());
+        assert!(Sound::decode("beep.ogg", vec![0; 16]).is_err());
+    }
+
+    #[test]
+    fn test_gain_pan_and_end() {
+        let mut mixer = Mixer::new(100);
+        let sound = Sound::from_samples(vec![1.0; 50], 1, 100);
+        let params = VoiceParams {
+            gain: 0.5,
+            pan: 1.0,
+            ..Default::default()
+        };
+        mixer.play(0, sound, params, 0.0);
+
+        let mut out = vec![0.0; 200];
+        mixer.mix(&mut out);
+        assert_eq!((out[0], out[1]), (0.0, 0.5));
+        // The sound ends after half of the buffer
+        assert_eq!((out[100], out[101]), (0.0, 0.0));
+        assert!(mixer.contains(&0) && !mixer.is_playing(&0));
+    }
+
+    #[test]
+    fn test_resample_and_loop() {
+        let mut mixer = Mixer::new(200);
+        // Two seconds of sound at half the rate of the mixer, looping from the first second
+        let samples = (0..200).map(|i| if i < 100 { 0.0 } else { 1.0 }).collect();
+        let params = VoiceParams {
+            looping: true,
+            loop_start: 1.0,
+            ..Default::default()
+        };
+        mixer.play("music", Sound::from_samples(samples, 1, 100), params, 0.0);
+
+        let mut out = vec![0.0; 2 * 500];
+        mixer.mix(&mut out);
+        assert_eq!(out[2 * 100], 0.0);
+        assert_eq!(out[2 * 450], 1.0);
+        assert!(mixer.is_playing(&"music"));
+        assert!((mixer.position(&"music").unwrap() - 1.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_lowpass_muffles() {
+        let mut out = vec![0.0; 2 * 4800];
+        let mut heard = |lowpass: f32| {
+            let mut mixer = Mixer::new(48000);
+            let params = VoiceParams {
+                lowpass,
+                ..Default::default()
+            };
+            mixer.play((), sine(8000.0, 1.0, 48000), params, 0.0);
+            mixer.mix(&mut out);
+            energy(&out)
+        };
+        let clear = heard(1.0);
+        let occluded = heard(0.3);
+        assert!(occluded < clear * 0.1, "{occluded} vs {clear}");
+    }
+
+    #[test]
+    fn test_reverb_tail() {
+        let mut mixer = Mixer::new(44100);
+        mixer.set_reverb(ReverbParams {
+            wet: 1.0,
+            decay_time: 2.0,
+            damping: 0.2,
+        });
+        let dry = VoiceParams::default();
+        let wet = VoiceParams {
+            reverb: true,
+            ..Default::default()
+        };
+        mixer.play(0, sine(440.0, 0.1, 44100), dry, 0.0);
+        mixer.play(1, sine(440.0, 0.1, 44100), wet, 0.0);
+
+        let mut out = vec![0.0; 2 * 4410];
+        mixer.mix(&mut out);
+        mixer.stop(&0);
+        // Only the reverb of the sound that was sent to it is heard after the sounds ended
+        mixer.mix(&mut out);
+        assert!(energy(&out) > 1e-4);
+
+        mixer.stop(&1);
+        mixer.set_reverb(ReverbParams::default());
+        mixer.mix(&mut out);
+        assert_eq!(energy(&out), 0.0);
+    }
+}
@@ -0,0 +1,9 @@
+#[cfg(feature = "audio")]
+pub mod backend;
+#[cfg(feature = "audio-device")]
+pub(crate) mod device;
+pub mod environment;
+#[cfg(feature = "audio")]
+pub mod mixer;
+pub mod music;
+pub mod source;
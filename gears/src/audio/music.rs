@@ -0,0 +1,372 @@
+use crate::core::Dt;
+use crate::ecs;
+use crate::ecs::traits::Component;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A layer of a music track that fades in above an intensity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stem {
+    pub name: String,
+    /// The path of the audio file of the stem, the name by default.
+    pub path: String,
+    /// The intensity at which the stem becomes audible, 0 for the base layer.
+    pub min_intensity: f32,
+}
+
+impl Stem {
+    pub fn new(name: impl Into<String>, min_intensity: f32) -> Self {
+        let name = name.into();
+        Self {
+            path: name.clone(),
+            name,
+            min_intensity,
+        }
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+}
+
+/// A music track made of synchronized stems with an optional intro before the looping section.
+/// The audio file of each stem holds the intro followed by one loop.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MusicTrack {
+    pub name: String,
+    /// The length of the intro in seconds, it is only played once.
+    pub intro: f32,
+    /// The length of the looping section in seconds.
+    pub loop_length: f32,
+    pub stems: Vec<Stem>,
+}
+
+impl MusicTrack {
+    pub fn new(name: impl Into<String>, loop_length: f32, stems: Vec<Stem>) -> Self {
+        Self {
+            name: name.into(),
+            intro: 0.0,
+            loop_length,
+            stems,
+        }
+    }
+
+    pub fn with_intro(mut self, intro: f32) -> Self {
+        self.intro = intro;
+        self
+    }
+
+    /// Get the position in the audio data for a time since the track started.
+    pub fn position(&self, time: f32) -> f32 {
+        if time < self.intro || self.loop_length <= 0.0 {
+            time
+        } else {
+            self.intro + (time - self.intro) % self.loop_length
+        }
+    }
+}
+
+/// The state of a single stem that should be sent to the mixer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MusicVoice {
+    /// Identifies the playback of the track, a track played again while it fades out is a new playback.
+    pub playback: u64,
+    pub track: String,
+    pub stem: String,
+    /// The path of the audio file of the stem.
+    pub path: String,
+    pub volume: f32,
+    /// The playback position in seconds.
+    pub position: f32,
+    /// The position in seconds the audio jumps back to at the end of the loop, `None` if it doesn't loop.
+    pub loop_start: Option<f32>,
+}
+
+/// A request to the music controllers, send it with `ecs::Manager::send_event`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MusicCommand {
+    /// Crossfade to a track by name over the given seconds.
+    Play {
+        track: String,
+        fade: f32,
+    },
+    /// Fade out the music over the given seconds.
+    Stop {
+        fade: f32,
+    },
+    SetIntensity(f32),
+}
+
+#[derive(Debug, Clone)]
+struct Playback {
+    id: u64,
+    track: Arc<MusicTrack>,
+    time: f32,
+    volume: f32,
+    target: f32,
+    /// The volume change per second.
+    fade_rate: f32,
+    stem_volumes: Vec<f32>,
+}
+
+fn approach(value: f32, target: f32, step: f32) -> f32 {
+    if value < target {
+        (value + step).min(target)
+    } else {
+        (value - step).max(target)
+    }
+}
+
+/// A component that plays music tracks with crossfades and intensity-layered stems.
+/// It only computes the state of the music, the `voices` are played by `update_audio_backend`.
+#[derive(Debug, Clone)]
+pub struct MusicController {
+    /// The time in seconds for a stem to fade in or out when the intensity changes.
+    pub stem_fade: f32,
+    pub volume: f32,
+    tracks: HashMap<String, Arc<MusicTrack>>,
+    playbacks: Vec<Playback>,
+    intensity: f32,
+    /// The id of the next playback.
+    next_playback: u64,
+}
+
+impl Component for MusicController {}
+
+impl Default for MusicController {
+    fn default() -> Self {
+        Self {
+            stem_fade: 2.0,
+            volume: 1.0,
+            tracks: HashMap::new(),
+            playbacks: Vec::new(),
+            intensity: 0.0,
+            next_playback: 0,
+        }
+    }
+}
+
+impl MusicController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a track that can be played by name.
+    pub fn add_track(&mut self, track: MusicTrack) {
+        self.tracks.insert(track.name.clone(), Arc::new(track));
+    }
+
+    pub fn with_track(mut self, track: MusicTrack) -> Self {
+        self.add_track(track);
+        self
+    }
+
+    /// Get the name of the track that is playing or fading in.
+    pub fn current_track(&self) -> Option<&str> {
+        self.playbacks
+            .last()
+            .filter(|p| p.target > 0.0)
+            .map(|p| p.track.name.as_str())
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity.max(0.0);
+    }
+
+    /// Crossfade to a track.
+    ///
+    /// # Returns
+    ///
+    /// `false` if there is no track with the name.
+    pub fn play(&mut self, name: &str, fade: f32) -> bool {
+        let Some(track) = self.tracks.get(name).cloned() else {
+            log::warn!("Music track not found: {}", name);
+            return false;
+        };
+
+        if self.current_track() == Some(name) {
+            return true;
+        }
+
+        self.stop(fade);
+        let stem_volumes = track
+            .stems
+            .iter()
+            .map(|s| {
+                if self.intensity >= s.min_intensity {
+                    1.0
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        self.next_playback += 1;
+        self.playbacks.push(Playback {
+            id: self.next_playback,
+            track,
+            time: 0.0,
+            volume: if fade > 0.0 { 0.0 } else { 1.0 },
+            target: 1.0,
+            fade_rate: if fade > 0.0 {
+                1.0 / fade
+            } else {
+                f32::INFINITY
+            },
+            stem_volumes,
+        });
+        true
+    }
+
+    /// Fade out all playing tracks.
+    pub fn stop(&mut self, fade: f32) {
+        for playback in self.playbacks.iter_mut() {
+            playback.target = 0.0;
+            playback.fade_rate = if fade > 0.0 {
+                1.0 / fade
+            } else {
+                f32::INFINITY
+            };
+        }
+    }
+
+    pub fn apply(&mut self, command: &MusicCommand) {
+        match command {
+            MusicCommand::Play { track, fade } => {
+                self.play(track, *fade);
+            }
+            MusicCommand::Stop { fade } => self.stop(*fade),
+            MusicCommand::SetIntensity(intensity) => self.set_intensity(*intensity),
+        }
+    }
+
+    /// Advance the playback, the fades and the stem volumes.
+    pub fn update(&mut self, dt: f32) {
+        let stem_step = if self.stem_fade > 0.0 {
+            dt / self.stem_fade
+        } else {
+            f32::INFINITY
+        };
+        let intensity = self.intensity;
+
+        for playback in self.playbacks.iter_mut() {
+            playback.time += dt;
+            playback.volume = approach(playback.volume, playback.target, playback.fade_rate * dt);
+
+            for (stem, volume) in playback
+                .track
+                .stems
+                .iter()
+                .zip(playback.stem_volumes.iter_mut())
+            {
+                let target = if intensity >= stem.min_intensity {
+                    1.0
+                } else {
+                    0.0
+                };
+                *volume = approach(*volume, target, stem_step);
+            }
+        }
+
+        self.playbacks.retain(|p| p.target > 0.0 || p.volume > 0.0);
+    }
+
+    /// Get the stems that should be audible, with their volume and playback position.
+    pub fn voices(&self) -> Vec<MusicVoice> {
+        self.playbacks
+            .iter()
+            .flat_map(|playback| {
+                let position = playback.track.position(playback.time);
+                let loop_start = (playback.track.loop_length > 0.0).then_some(playback.track.intro);
+                playback
+                    .track
+                    .stems
+                    .iter()
+                    .zip(playback.stem_volumes.iter())
+                    .map(move |(stem, stem_volume)| MusicVoice {
+                        playback: playback.id,
+                        track: playback.track.name.clone(),
+                        stem: stem.name.clone(),
+                        path: stem.path.clone(),
+                        volume: self.volume * playback.volume * stem_volume,
+                        position,
+                        loop_start,
+                    })
+            })
+            .filter(|voice| voice.volume > 0.0)
+            .collect()
+    }
+}
+
+/// Apply the pending `MusicCommand` events to all music controllers and advance them.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_music(ecs: &ecs::Manager, dt: Dt) {
+    let commands = ecs.drain_events::<MusicCommand>();
+
+    for (_, controller) in ecs.get_all_components_of_type::<MusicController>() {
+        let mut controller = controller.write().unwrap();
+        for command in commands.iter() {
+            controller.apply(command);
+        }
+        controller.update(dt.as_secs_f32());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn explore() -> MusicTrack {
+        MusicTrack::new(
+            "explore",
+            10.0,
+            vec![Stem::new("base", 0.0), Stem::new("combat", 0.5)],
+        )
+        .with_intro(4.0)
+    }
+
+    #[test]
+    fn test_intro_and_loop() {
+        let track = explore();
+        assert_eq!(track.position(2.0), 2.0);
+        assert_eq!(track.position(15.0), 5.0);
+    }
+
+    #[test]
+    fn test_crossfade_and_stems() {
+        let mut controller = MusicController::new()
+            .with_track(explore())
+            .with_track(MusicTrack::new("boss", 20.0, vec![Stem::new("base", 0.0)]));
+
+        controller.play("explore", 0.0);
+        assert_eq!(controller.voices().len(), 1);
+
+        controller.set_intensity(1.0);
+        controller.update(1.0);
+        let combat = controller
+            .voices()
+            .into_iter()
+            .find(|v| v.stem == "combat")
+            .unwrap();
+        assert_eq!(combat.volume, 0.5);
+
+        controller.play("boss", 2.0);
+        controller.update(1.0);
+        assert_eq!(controller.current_track(), Some("boss"));
+        assert_eq!(controller.voices().len(), 3);
+
+        controller.update(1.0);
+        let voices = controller.voices();
+        assert_eq!(voices.len(), 1);
+        assert_eq!(voices[0].track, "boss");
+        assert_eq!(voices[0].volume, 1.0);
+    }
+}
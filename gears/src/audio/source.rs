@@ -0,0 +1,196 @@
+use super::environment::AudioEnvironment;
+use crate::core::Dt;
+use crate::ecs;
+use crate::ecs::components::Pos3;
+use crate::ecs::traits::Component;
+use cgmath::{InnerSpace, Rotation, Vector3, Zero};
+
+/// A component that marks the entity the spatial sounds are heard from, e.g. the camera entity.
+/// Its `Pos3` gives the position and the rotation, the right ear is towards its local +X.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct AudioListener;
+
+impl Component for AudioListener {}
+
+/// A component that plays a sound file, from its `Pos3` if it is spatial.
+/// It only computes how the sound is heard, the `gain`, `pan` and `lowpass`
+/// have to be applied by the audio backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioSource {
+    pub path: String,
+    pub volume: f32,
+    pub looping: bool,
+    /// Attenuate and pan the sound by its position relative to the `AudioListener`.
+    pub spatial: bool,
+    /// The distance up to which the sound is heard at full volume, it gets quieter inversely with the distance after.
+    pub min_distance: f32,
+    /// The distance beyond which the sound is not heard.
+    pub max_distance: f32,
+    gain: f32,
+    pan: f32,
+    lowpass: f32,
+}
+
+impl Component for AudioSource {}
+
+impl AudioSource {
+    /// A non spatial sound played once at full volume.
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            volume: 1.0,
+            looping: false,
+            spatial: false,
+            min_distance: 1.0,
+            max_distance: 50.0,
+            gain: 1.0,
+            pan: 0.0,
+            lowpass: 1.0,
+        }
+    }
+
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self.gain = volume;
+        self
+    }
+
+    pub fn looping(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+
+    /// Make the sound spatial, heard fully within `min_distance` and not at all beyond `max_distance`.
+    pub fn spatial(mut self, min_distance: f32, max_distance: f32) -> Self {
+        self.spatial = true;
+        self.min_distance = min_distance;
+        self.max_distance = max_distance;
+        self
+    }
+
+    /// Get the volume to play the sound at, with the distance and the occlusion applied.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Get the stereo panning, -1 is the left ear only and 1 the right ear only.
+    pub fn pan(&self) -> f32 {
+        self.pan
+    }
+
+    /// Get the fraction of the high frequencies that pass through, 1 is unfiltered.
+    pub fn lowpass(&self) -> f32 {
+        self.lowpass
+    }
+
+    /// Get the volume multiplier of a distance from the listener.
+    fn attenuation(&self, distance: f32) -> f32 {
+        if distance >= self.max_distance {
+            0.0
+        } else if distance <= self.min_distance {
+            1.0
+        } else {
+            self.min_distance / distance
+        }
+    }
+}
+
+/// Update the gain, panning and filtering of all audio sources from their positions
+/// relative to the first `AudioListener`. The occlusion is applied if the listener
+/// has an `AudioEnvironment`. Without a listener the spatial sounds are heard as if they were at it.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_audio_sources(ecs: &ecs::Manager, _dt: Dt) {
+    let listener = ecs
+        .get_entites_with_component::<AudioListener>()
+        .into_iter()
+        .find_map(|entity| {
+            let pos = *ecs
+                .get_component_from_entity::<Pos3>(entity)?
+                .read()
+                .unwrap();
+            Some((entity, pos))
+        });
+    let environment = listener.and_then(|(entity, _)| {
+        ecs.get_component_from_entity::<AudioEnvironment>(entity)
+            .map(|environment| *environment.read().unwrap())
+    });
+
+    for (entity, source) in ecs.get_all_components_of_type::<AudioSource>() {
+        let mut source = source.write().unwrap();
+        let Some((listener, listener_pos)) = listener.filter(|_| source.spatial) else {
+            source.gain = source.volume;
+            source.pan = 0.0;
+            source.lowpass = 1.0;
+            continue;
+        };
+        let pos = ecs
+            .get_component_from_entity::<Pos3>(entity)
+            .map_or(Vector3::zero(), |p| p.read().unwrap().pos);
+
+        let offset = pos - listener_pos.pos;
+        let distance = offset.magnitude();
+        let local = match listener_pos.rot {
+            Some(rot) => rot.invert().rotate_vector(offset),
+            None => offset,
+        };
+        let occlusion = environment
+            .map(|environment| {
+                environment.occlusion(ecs, listener_pos.pos, pos, &[entity, listener])
+            })
+            .unwrap_or_default();
+
+        source.gain = source.volume * source.attenuation(distance) * occlusion.gain;
+        source.pan = if distance > f32::EPSILON {
+            (local.x / distance).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+        source.lowpass = occlusion.lowpass;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instant::Duration;
+
+    #[test]
+    fn test_attenuation_and_panning() {
+        let manager = ecs::Manager::default();
+        let camera = manager.create_entity();
+        manager.add_component_to_entity(camera, Pos3::default());
+        manager.add_component_to_entity(camera, AudioListener);
+
+        let near = manager.create_entity();
+        manager.add_component_to_entity(near, Pos3::new(Vector3::new(-1.0, 0.0, 0.0)));
+        manager.add_component_to_entity(near, AudioSource::new("hum.ogg").spatial(2.0, 20.0));
+        let far = manager.create_entity();
+        manager.add_component_to_entity(far, Pos3::new(Vector3::new(8.0, 0.0, 0.0)));
+        manager.add_component_to_entity(
+            far,
+            AudioSource::new("hum.ogg")
+                .with_volume(0.5)
+                .spatial(2.0, 20.0),
+        );
+        let music = manager.create_entity();
+        manager.add_component_to_entity(music, AudioSource::new("theme.ogg").looping());
+        update_audio_sources(&manager, Duration::from_millis(16));
+
+        let get = |entity| {
+            manager
+                .get_component_from_entity::<AudioSource>(entity)
+                .unwrap()
+                .read()
+                .unwrap()
+                .clone()
+        };
+        let (near, far, music) = (get(near), get(far), get(music));
+        assert_eq!((near.gain(), near.pan()), (1.0, -1.0));
+        assert_eq!((far.gain(), far.pan()), (0.5 * 2.0 / 8.0, 1.0));
+        assert_eq!((music.gain(), music.pan()), (1.0, 0.0));
+    }
+}
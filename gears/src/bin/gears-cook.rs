@@ -0,0 +1,39 @@
+//! Cook the assets of a directory for release.
+//!
+//! Usage: `gears-cook <assets dir> <output dir> [prefix] [--pak <archive>]`
+//!
+//! The scenes are converted to the binary format and the other assets are copied,
+//! with a content-hash manifest. The prefix defaults to `res`.
+//! With `--pak` the cooked assets are also packed into an archive.
+
+use gears::core::{cook, vfs};
+use std::fs::File;
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let pak = match args.iter().position(|arg| arg == "--pak") {
+        Some(i) if i + 1 < args.len() => Some(args.drain(i..i + 2).nth(1).unwrap()),
+        Some(_) => anyhow::bail!("--pak needs the path of the archive"),
+        None => None,
+    };
+    let (dir, out, prefix) = match &args[..] {
+        [dir, out] => (dir, out, "res"),
+        [dir, out, prefix] => (dir, out, prefix.as_str()),
+        _ => {
+            anyhow::bail!("Usage: gears-cook <assets dir> <output dir> [prefix] [--pak <archive>]")
+        }
+    };
+
+    let report = cook::cook_dir(dir, prefix, out)?;
+    println!(
+        "Cooked {} scenes, copied {} assets, {} unchanged",
+        report.cooked, report.copied, report.unchanged
+    );
+
+    if let Some(pak) = pak {
+        let count = vfs::pack_dir(out, "", File::create(&pak)?)?;
+        println!("Packed {} files into {}", count, pak);
+    }
+
+    Ok(())
+}
@@ -1,9 +1,17 @@
-use super::config::{self, Config, LogConfig, LogLevel};
+use super::checksum;
+use super::config::{self, Config};
+use super::schedule::{AsyncSystem, Schedule, Stage, System};
 use super::Dt;
 use super::{event::EventQueue, threadpool::ThreadPool};
+use crate::ecs;
 use crate::ecs::traits::Component;
 use crate::ecs::Entity;
-use crate::{ecs, renderer};
+use crate::editor::play;
+use crate::gameplay::{inventory, status, teleporter};
+use crate::net::server::Server;
+use crate::physics;
+#[cfg(feature = "renderer")]
+use crate::renderer;
 use log::info;
 use std::env;
 use std::future::Future;
@@ -33,6 +41,7 @@ pub struct GearsApp {
     pub thread_pool: ThreadPool,
     event_queue: EventQueue,
     egui_windows: Option<Vec<Box<dyn FnMut(&egui::Context)>>>,
+    schedule: Schedule,
     tx_dt: Option<broadcast::Sender<Dt>>,
     rx_dt: Option<broadcast::Receiver<Dt>>,
     is_running: Arc<AtomicBool>,
@@ -66,6 +75,7 @@ impl App for GearsApp {
             config,
             ecs: Arc::new(Mutex::new(ecs::Manager::default())),
             egui_windows: None,
+            schedule: default_schedule(),
             tx_dt: Some(tx_dt),
             rx_dt: Some(rx_dt),
             is_running: Arc::new(AtomicBool::new(true)),
@@ -74,27 +84,69 @@ impl App for GearsApp {
 
     /// Run the application and start the event loop.
     async fn run(&mut self) -> anyhow::Result<()> {
+        self.config.validate()?;
+
         // TODO env builder should be initialized by the user (in main.rs)
         // Initialize logger
         let mut env_builder = env_logger::Builder::new();
-        // Set the minimum log level from the config.
-        env_builder.filter_level(match self.config.log.level {
-            LogLevel::Error => log::LevelFilter::Error,
-            LogLevel::Warn => log::LevelFilter::Warn,
-            LogLevel::Info => log::LevelFilter::Info,
-            LogLevel::Debug => log::LevelFilter::Debug,
-            LogLevel::Trace => log::LevelFilter::Trace,
-        });
+        // Let every level through the logger, the level of the config is set as the maximum level below,
+        // so it can be changed with the `RuntimeConfig` while the app runs
+        env_builder.filter_level(log::LevelFilter::Trace);
         // Filter out specific log messages
         env_builder.filter_module("wgpu_core::device::resource", log::LevelFilter::Warn);
-        env_builder.init();
+        match self.config.crash_report.clone() {
+            Some(crash_report) => {
+                super::crash::install(crash_report, env_builder.build(), Arc::downgrade(&self.ecs))?
+            }
+            None => env_builder.init(),
+        }
+        log::set_max_level(self.config.log.level.to_filter());
 
         info!("Starting Gears...");
 
+        if let Some(telemetry) = self.config.telemetry {
+            super::telemetry::telemetry().serve(telemetry)?;
+        }
+
+        super::vfs::configure(&self.config.assets)?;
+
+        {
+            let ecs = self.ecs.lock().unwrap();
+            let entity = ecs.create_entity();
+            ecs.add_component_to_entity(entity, self.config.runtime());
+        }
+
+        // The schedule runs even without systems, so they can be added while the app runs
+        info!("Running {} scheduled systems", self.schedule.len());
+        self.schedule
+            .set_fixed_update_hz(self.config.fixed_update_hz);
+        let schedule = Arc::new(Mutex::new(std::mem::take(&mut self.schedule)));
+        self.update_loop_async(move |ecs, dt| {
+            let schedule = Arc::clone(&schedule);
+            Box::pin(async move {
+                let async_systems = update_schedule(&ecs, &mut schedule.lock().unwrap(), dt);
+                for system in async_systems {
+                    system.run(Arc::clone(&ecs), dt).await;
+                }
+            })
+        })
+        .await?;
+
         let tx = self.tx_dt.take().unwrap();
 
-        // Run the event loop
-        renderer::run(Arc::clone(&self.ecs), tx, self.egui_windows.take()).await
+        // A dedicated server runs the systems without a window
+        if let Some(server) = self.config.server {
+            let server = Server::bind(server).await?;
+            return server
+                .run(Arc::clone(&self.ecs), tx, Arc::clone(&self.is_running))
+                .await;
+        }
+
+        // The output stays open until the window is closed
+        #[cfg(feature = "audio-device")]
+        let _audio = crate::audio::device::AudioOutput::open(&self.ecs.lock().unwrap());
+
+        self.run_event_loop(tx).await
     }
 
     /// Get the delta time channel.
@@ -124,7 +176,12 @@ impl App for GearsApp {
         tokio::spawn(async move {
             while is_running.load(std::sync::atomic::Ordering::Relaxed) {
                 match rx_dt.recv().await {
-                    Ok(dt) => f(Arc::clone(&ecs), dt),
+                    Ok(dt) => {
+                        // The game is stopped while the world is edited
+                        if play::is_playing(&ecs.lock().unwrap()) {
+                            f(Arc::clone(&ecs), dt)
+                        }
+                    }
                     Err(e) => {
                         eprintln!("Failed to receive: {:?}", e);
                     }
@@ -151,7 +208,221 @@ impl App for GearsApp {
     }
 }
 
+/// The schedule of a new app, with the systems of the engine.
+/// A game takes one out with `GearsApp::remove_system` to run its own instead.
+///
+/// * `update_physics` - Steps the rigid bodies on each fixed update.
+/// * `update_teleporters` - Moves the entities through the teleporters and sends a `TeleportEvent` for each.
+/// * `update_pickups` - Collects the pickups in range of the inventories.
+/// * `update_status_effects` - Ticks the status effects and removes the expired ones.
+/// * `update_decals` - Ages the decals and sends the expiry events, with the `decals` feature.
+/// * `update_audio_backend` - Plays the audio components through the mixer, with the `audio` feature.
+/// * `propagate_transforms` - Moves the children with their parents after the physics and the updates.
+/// * `update_world_checksum` - Hashes the world for the `WorldChecksum` once everything has moved.
+fn default_schedule() -> Schedule {
+    let mut schedule = Schedule::new();
+    let systems = [
+        System::new("update_physics", physics::world::update_physics).in_stage(Stage::FixedUpdate),
+        System::new("update_teleporters", |ecs, dt| {
+            for event in teleporter::update_teleporters(ecs, dt) {
+                ecs.send_event(event);
+            }
+        }),
+        System::new("update_pickups", |ecs, _| inventory::update_pickups(ecs)),
+        System::new("update_status_effects", status::update_status_effects),
+        System::new("propagate_transforms", |ecs, _| {
+            ecs::hierarchy::propagate_transforms(ecs)
+        })
+        .in_stage(Stage::PostUpdate),
+        System::new("update_world_checksum", checksum::update_world_checksum).in_stage(Stage::Last),
+    ];
+    #[cfg(feature = "decals")]
+    let systems = systems.into_iter().chain([System::new(
+        "update_decals",
+        renderer::decals::update_decals,
+    )]);
+    #[cfg(feature = "audio")]
+    let systems = systems.into_iter().chain([System::new(
+        "update_audio_backend",
+        crate::audio::backend::update_audio_backend,
+    )
+    .in_stage(Stage::Last)]);
+    for system in systems {
+        schedule
+            .add(system)
+            .expect("The systems of the engine have unique names");
+    }
+    schedule
+}
+
+/// Run the scheduled systems once.
+///
+/// # Returns
+///
+/// The async systems to run after them.
+fn update_schedule(ecs: &Mutex<ecs::Manager>, schedule: &mut Schedule, dt: Dt) -> Vec<AsyncSystem> {
+    let ecs = ecs.lock().unwrap();
+    ecs.run_task_callbacks();
+    config::update_log_level(&ecs);
+    schedule.apply_commands(&ecs);
+    schedule.run(&ecs, dt);
+    ecs.frame_arena().reset();
+    schedule.async_systems().to_vec()
+}
+
 impl GearsApp {
+    /// Run the systems and render frames without a window, for golden image tests
+    /// and thumbnails on a server. Each frame is one update at the fixed update rate of the config,
+    /// so a run gives the same frames every time. The logger is not set up, unlike `run`.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - The number of frames to update and render.
+    ///
+    /// # Returns
+    ///
+    /// The last frame in RGBA, its size is the window size of the config or 1280x720.
+    #[cfg(feature = "renderer")]
+    pub async fn run_headless(&mut self, frames: u32) -> anyhow::Result<Vec<u8>> {
+        self.config.validate()?;
+        super::vfs::configure(&self.config.assets)?;
+        {
+            let ecs = self.ecs.lock().unwrap();
+            let entity = ecs.create_entity();
+            ecs.add_component_to_entity(entity, self.config.runtime());
+        }
+
+        let mut renderer = renderer::headless::HeadlessRenderer::new(
+            Arc::clone(&self.ecs),
+            self.config.renderer(),
+        )
+        .await?;
+
+        self.schedule
+            .set_fixed_update_hz(self.config.fixed_update_hz);
+        let dt = Dt::from_secs_f64(1.0 / self.config.fixed_update_hz as f64);
+        for _ in 0..frames {
+            if play::is_playing(&self.ecs.lock().unwrap()) {
+                for system in update_schedule(&self.ecs, &mut self.schedule, dt) {
+                    system.run(Arc::clone(&self.ecs), dt).await;
+                }
+            }
+            renderer.render_frame(dt).await;
+        }
+
+        renderer.read_pixels()
+    }
+
+    /// Run the window and the renderer.
+    #[cfg(feature = "renderer")]
+    async fn run_event_loop(&mut self, tx: broadcast::Sender<Dt>) -> anyhow::Result<()> {
+        renderer::run(
+            Arc::clone(&self.ecs),
+            tx,
+            self.egui_windows.take(),
+            self.config.renderer(),
+        )
+        .await
+    }
+
+    /// Without the renderer the update loops are driven at the fixed frame rate of the pacing setting,
+    /// or at 60 Hz if it depends on the display.
+    #[cfg(not(feature = "renderer"))]
+    async fn run_event_loop(&mut self, tx: broadcast::Sender<Dt>) -> anyhow::Result<()> {
+        let frame_rate =
+            super::pacing::target_frame_rate(self.config.display.frame_pacing, None, false)
+                .unwrap_or(60.0);
+        let dt = Dt::from_secs_f64(1.0 / frame_rate as f64);
+        let mut interval = tokio::time::interval(dt);
+
+        while self.is_running.load(std::sync::atomic::Ordering::Relaxed) {
+            interval.tick().await;
+            let Some(dt) = super::clock::simulation_dt(&self.ecs.lock().unwrap(), dt) else {
+                continue;
+            };
+            if let Err(e) = tx.send(dt) {
+                log::warn!("Failed to send delta time: {:?}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get a sender of intents for the windows of the app, the intents arrive to the systems as events.
+    /// See `ecs::intent::IntentSender`.
+    pub fn intent_sender(&self) -> ecs::intent::IntentSender {
+        self.ecs.lock().unwrap().intent_sender()
+    }
+
+    /// Add a system to the schedule of the app, the scheduled systems run in order on each update.
+    /// While the app runs, send a `ScheduleCommand` to the manager instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - The system with its stage, dependencies and component access.
+    ///
+    /// # Returns
+    ///
+    /// An error if the name is taken or the dependencies can not be ordered.
+    pub fn add_system(&mut self, system: System) -> anyhow::Result<()> {
+        self.schedule.add(system)
+    }
+
+    /// Add a system running at the fixed update rate of the config, zero or more times per update
+    /// with a constant delta time. Use it for the systems which are unstable with a variable delta time,
+    /// like the physics. The `FixedTime` resource has the fraction of a step left to interpolate with.
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - The system, it is moved to the `FixedUpdate` stage.
+    ///
+    /// # Returns
+    ///
+    /// An error if the name is taken or the dependencies can not be ordered.
+    pub fn add_fixed_system(&mut self, system: System) -> anyhow::Result<()> {
+        self.schedule.add(system.in_stage(Stage::FixedUpdate))
+    }
+
+    /// Add an async system to the schedule of the app, it runs after the scheduled systems on each update.
+    /// While the app runs, send a `ScheduleCommand` to the manager instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The unique name of the system.
+    /// * `f` - The function to run on each update.
+    ///
+    /// # Returns
+    ///
+    /// An error if the name is taken.
+    pub fn add_async_system<F>(&mut self, name: impl Into<String>, f: F) -> anyhow::Result<()>
+    where
+        F: Fn(Arc<Mutex<ecs::Manager>>, Dt) -> Pin<Box<dyn Future<Output = ()> + Send>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.schedule.add_async(AsyncSystem::new(name, f))
+    }
+
+    /// Add an async system created with `async_system!` to the schedule of the app.
+    ///
+    /// # Returns
+    ///
+    /// An error if the name is taken.
+    pub fn add_async(&mut self, system: AsyncSystem) -> anyhow::Result<()> {
+        self.schedule.add_async(system)
+    }
+
+    /// Remove a system or an async system from the schedule of the app.
+    /// While the app runs, send a `ScheduleCommand` to the manager instead.
+    ///
+    /// # Returns
+    ///
+    /// True if the system was scheduled.
+    pub fn remove_system(&mut self, name: &str) -> bool {
+        self.schedule.remove(name)
+    }
+
     /// Create a new update job.
     /// This will create a new async task that will run the given update function on each update.
     #[warn(unstable_features)]
@@ -173,7 +444,9 @@ impl GearsApp {
             while is_running.load(std::sync::atomic::Ordering::Relaxed) {
                 match rx_dt.recv().await {
                     Ok(dt) => {
-                        f(Arc::clone(&ecs), dt).await;
+                        if play::is_playing(&ecs.lock().unwrap()) {
+                            f(Arc::clone(&ecs), dt).await;
+                        }
                     }
                     Err(e) => {
                         eprintln!("Failed to receive: {:?}", e);
@@ -218,14 +491,33 @@ impl ecs::traits::EntityBuilder for GearsApp {
         self
     }
 
+    fn add_bundle(&mut self, bundle: impl ecs::traits::Bundle) -> &mut Self {
+        {
+            let ecs = self.ecs.lock().unwrap();
+
+            let entity = if let Some(e) = ecs.get_last() {
+                e
+            } else {
+                ecs.create_entity()
+            };
+
+            bundle.insert_into(&ecs, entity);
+        }
+
+        self
+    }
+
     fn build(&mut self) -> ecs::Entity {
         let ecs = self.ecs.lock().unwrap();
 
-        if let Some(e) = ecs.get_last() {
+        let entity = if let Some(e) = ecs.get_last() {
             e
         } else {
             ecs.create_entity()
-        }
+        };
+        ecs.check_spawned(entity);
+
+        entity
     }
 }
 
@@ -279,4 +571,54 @@ mod tests {
             .unwrap();
         assert_eq!(component.read().unwrap().value, 10);
     }
+
+    #[test]
+    fn test_new_entity_macro_conditions_and_bundles() {
+        let mut app = crate::core::app::GearsApp::default();
+
+        let kept = true;
+        let skipped = false;
+        let entity = new_entity!(
+            app,
+            if kept => TestComponent { value: 10 },
+            ..(ecs::components::Name("Bundled"), ecs::components::Pos3::default()),
+        );
+        let other = new_entity!(
+            app,
+            if skipped => TestComponent { value: 20 },
+            ..(ecs::components::Name("Other"), ecs::components::Pos3::default()),
+        );
+
+        let ecs = app.ecs.lock().unwrap();
+        assert_eq!(ecs.entity_count(), 2);
+        assert!(ecs.has_component::<TestComponent>(entity));
+        assert!(ecs.has_component::<ecs::components::Name>(entity));
+        assert!(!ecs.has_component::<TestComponent>(other));
+        assert!(ecs.has_component::<ecs::components::Pos3>(other));
+    }
+
+    #[test]
+    fn test_default_schedule() {
+        let mut app = GearsApp::default();
+        let order = app.schedule.order();
+        let physics = order.iter().position(|name| *name == "update_physics");
+        let propagate = order
+            .iter()
+            .position(|name| *name == "propagate_transforms");
+        assert!(physics.unwrap() < propagate.unwrap());
+
+        let ball = app
+            .new_entity()
+            .add_component(ecs::components::Pos3::new(cgmath::Vector3::new(
+                0.0, 10.0, 0.0,
+            )))
+            .add_component(physics::body::RigidBody::new(1.0))
+            .build();
+        let dt = app.schedule.fixed_time().step;
+        update_schedule(&app.ecs, &mut app.schedule, dt);
+
+        let ecs = app.ecs.lock().unwrap();
+        let pos = ecs.get_component_from_entity::<ecs::components::Pos3>(ball);
+        assert!(pos.unwrap().read().unwrap().pos.y < 10.0);
+    }
 }
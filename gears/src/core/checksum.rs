@@ -0,0 +1,267 @@
+use crate::core::Dt;
+use crate::ecs::components::{Pos3, Velocity};
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use std::any::TypeId;
+use std::collections::VecDeque;
+use std::hash::Hasher;
+
+/// A 64 bit FNV-1a hasher, its output does not depend on the platform or the Rust version.
+#[derive(Debug, Copy, Clone)]
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+impl StableHasher {
+    /// Hash the bits of a float, so any difference in the simulation changes the checksum.
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_u32(value.to_bits());
+    }
+}
+
+/// Component data which can be part of the world checksum.
+pub trait StableHash {
+    fn stable_hash(&self, hasher: &mut StableHasher);
+}
+
+impl StableHash for Pos3 {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        for value in [self.pos.x, self.pos.y, self.pos.z] {
+            hasher.write_f32(value);
+        }
+        if let Some(rot) = self.rot {
+            for value in [rot.v.x, rot.v.y, rot.v.z, rot.s] {
+                hasher.write_f32(value);
+            }
+        }
+    }
+}
+
+impl StableHash for Velocity {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        for value in [self.0.x, self.0.y, self.0.z] {
+            hasher.write_f32(value);
+        }
+    }
+}
+
+type ComponentHasher = fn(&ecs::Manager, &mut StableHasher);
+
+/// Hash the components of a type in the order of the entities.
+fn hash_components<T: StableHash + Send + Sync + 'static>(
+    ecs: &ecs::Manager,
+    hasher: &mut StableHasher,
+) {
+    let mut components = ecs.get_all_components_of_type::<T>();
+    components.sort_by_key(|(entity, _)| entity.id());
+
+    hasher.write(std::any::type_name::<T>().as_bytes());
+    for (entity, component) in components {
+        hasher.write_u32(entity.id());
+        component.read().unwrap().stable_hash(hasher);
+    }
+}
+
+/// Hashes the registered component types of the world every tick.
+/// Compare the history of two peers or of a replay and its recording to find the first desynced tick.
+pub struct WorldChecksum {
+    pub enabled: bool,
+    /// Log the checksum every this many ticks, 0 to not log it.
+    pub log_interval: u64,
+    /// The number of ticks kept in the history.
+    pub history_len: usize,
+    hashers: Vec<(TypeId, ComponentHasher)>,
+    tick: u64,
+    history: VecDeque<(u64, u64)>,
+}
+
+impl Component for WorldChecksum {}
+
+impl Default for WorldChecksum {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            log_interval: 600,
+            history_len: 1024,
+            hashers: Vec::new(),
+            tick: 0,
+            history: VecDeque::new(),
+        }
+    }
+}
+
+impl WorldChecksum {
+    /// Create a checksum hashing the transforms and the velocities.
+    pub fn new() -> Self {
+        Self::default()
+            .with_component::<Pos3>()
+            .with_component::<Velocity>()
+    }
+
+    /// Add a component type to the checksum.
+    pub fn with_component<T: StableHash + Send + Sync + 'static>(mut self) -> Self {
+        let type_id = TypeId::of::<T>();
+        if !self.hashers.iter().any(|(id, _)| *id == type_id) {
+            self.hashers.push((type_id, hash_components::<T>));
+        }
+        self
+    }
+
+    pub fn with_log_interval(mut self, log_interval: u64) -> Self {
+        self.log_interval = log_interval;
+        self
+    }
+
+    /// Get the number of ticks hashed so far.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Get the checksum of the latest tick.
+    pub fn value(&self) -> Option<u64> {
+        self.history.back().map(|(_, value)| *value)
+    }
+
+    /// Get the recent `(tick, checksum)` pairs, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.history.iter().copied()
+    }
+
+    /// Get the first tick both histories contain with a different checksum.
+    pub fn first_mismatch(&self, other: impl IntoIterator<Item = (u64, u64)>) -> Option<u64> {
+        other
+            .into_iter()
+            .filter_map(|(tick, value)| {
+                self.history
+                    .iter()
+                    .find(|(own_tick, _)| *own_tick == tick)
+                    .filter(|(_, own_value)| *own_value != value)
+                    .map(|_| tick)
+            })
+            .min()
+    }
+
+    fn compute(&self, ecs: &ecs::Manager) -> u64 {
+        let mut hasher = StableHasher::default();
+        for (_, hash) in &self.hashers {
+            hash(ecs, &mut hasher);
+        }
+        hasher.finish()
+    }
+
+    fn push(&mut self, value: u64) {
+        self.tick += 1;
+        while self.history.len() >= self.history_len.max(1) {
+            self.history.pop_front();
+        }
+        self.history.push_back((self.tick, value));
+    }
+}
+
+/// Hash the world for every enabled `WorldChecksum`.
+/// The default schedule runs it once per update, in the last stage after the systems changing the hashed components.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time, unused as the checksum counts ticks.
+pub fn update_world_checksum(ecs: &ecs::Manager, _dt: Dt) {
+    let checksums: Vec<(Entity, _)> = ecs.get_all_components_of_type::<WorldChecksum>();
+
+    for (entity, checksum) in checksums {
+        let value = {
+            let checksum = checksum.read().unwrap();
+            if !checksum.enabled {
+                continue;
+            }
+            checksum.compute(ecs)
+        };
+
+        let mut checksum = checksum.write().unwrap();
+        checksum.push(value);
+        if checksum.log_interval > 0 && checksum.tick % checksum.log_interval == 0 {
+            log::info!(
+                "[Checksum] Entity {} tick {}: {:016x}",
+                entity.id(),
+                checksum.tick,
+                value
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector3;
+
+    fn world(x: f32) -> ecs::Manager {
+        let ecs = ecs::Manager::default();
+        for i in 0..4 {
+            let entity = ecs.create_entity();
+            ecs.add_component_to_entity(entity, Pos3::new(Vector3::new(i as f32, 0.0, 0.0)));
+        }
+        let moving = ecs.create_entity();
+        ecs.add_component_to_entity(moving, Pos3::new(Vector3::new(x, 1.0, 2.0)));
+        ecs.add_component_to_entity(moving, Velocity(Vector3::new(1.0, 0.0, 0.0)));
+
+        let checksum = ecs.create_entity();
+        ecs.add_component_to_entity(checksum, WorldChecksum::new());
+        ecs
+    }
+
+    fn history(ecs: &ecs::Manager) -> Vec<(u64, u64)> {
+        let (_, checksum) = ecs.get_all_components_of_type::<WorldChecksum>()[0].clone();
+        let history = checksum.read().unwrap().history().collect();
+        history
+    }
+
+    #[test]
+    fn test_checksum_is_stable() {
+        let a = world(5.0);
+        let b = world(5.0);
+        update_world_checksum(&a, Dt::ZERO);
+        update_world_checksum(&b, Dt::ZERO);
+
+        // Same data gives the same checksum, regardless of the hash map order of the entities
+        assert_eq!(history(&a), history(&b));
+        assert_eq!(history(&a)[0].0, 1);
+    }
+
+    #[test]
+    fn test_first_mismatch() {
+        let a = world(5.0);
+        let b = world(5.0);
+        update_world_checksum(&a, Dt::ZERO);
+        update_world_checksum(&b, Dt::ZERO);
+
+        // Desync the second world
+        let (_, velocity) = b.get_all_components_of_type::<Velocity>()[0].clone();
+        velocity.write().unwrap().0.x = 1.0001;
+        update_world_checksum(&a, Dt::ZERO);
+        update_world_checksum(&b, Dt::ZERO);
+
+        let (_, checksum) = a.get_all_components_of_type::<WorldChecksum>()[0].clone();
+        assert_eq!(
+            checksum.read().unwrap().first_mismatch(history(&b)),
+            Some(2)
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use super::Dt;
+use crate::ecs;
+use crate::ecs::traits::Component;
+
+/// The speed of the simulation, to debug the game systems frame by frame.
+/// Add it to an entity to pause the simulation, advance it one tick at a time or slow it down,
+/// while the renderer keeps drawing. Without it the simulation runs at full speed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SimulationClock {
+    paused: bool,
+    pending_steps: u32,
+    time_scale: f32,
+    /// The delta time of a single step.
+    pub step_dt: Dt,
+}
+
+impl Component for SimulationClock {}
+
+impl Default for SimulationClock {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            pending_steps: 0,
+            time_scale: 1.0,
+            step_dt: Dt::from_secs_f64(1.0 / 60.0),
+        }
+    }
+}
+
+impl SimulationClock {
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resume the simulation, the steps not taken yet are dropped.
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.pending_steps = 0;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.paused {
+            self.resume();
+        } else {
+            self.pause();
+        }
+    }
+
+    /// Pause the simulation and advance it by one tick of `step_dt` on the next frame.
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.pending_steps += 1;
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Set how fast the simulation runs compared to the real time, e.g. 0.25 for a quarter speed.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    /// Advance the clock by a frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `dt` - The real delta time of the frame.
+    ///
+    /// # Returns
+    ///
+    /// The delta time the simulation advances by, `None` if it does not run this frame.
+    pub fn advance(&mut self, dt: Dt) -> Option<Dt> {
+        if !self.paused {
+            return Some(dt.mul_f32(self.time_scale));
+        }
+        if self.pending_steps > 0 {
+            self.pending_steps -= 1;
+            return Some(self.step_dt);
+        }
+        None
+    }
+}
+
+/// Get the delta time the simulation advances by in a frame.
+/// It is called once per frame, before the delta time is sent to the update loops.
+///
+/// # Returns
+///
+/// The delta time, `None` if the simulation is paused.
+pub fn simulation_dt(ecs: &ecs::Manager, dt: Dt) -> Option<Dt> {
+    match ecs.get_all_components_of_type::<SimulationClock>().pop() {
+        Some((_, clock)) => clock.write().unwrap().advance(dt),
+        None => Some(dt),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_and_scale() {
+        let frame = Dt::from_millis(20);
+        let mut clock = SimulationClock::default();
+        assert_eq!(clock.advance(frame), Some(frame));
+
+        clock.set_time_scale(0.25);
+        assert_eq!(clock.advance(frame), Some(Dt::from_millis(5)));
+
+        // Paused, a step advances exactly one tick regardless of the frame time
+        clock.pause();
+        assert_eq!(clock.advance(frame), None);
+        clock.step();
+        clock.step();
+        assert_eq!(clock.advance(frame), Some(clock.step_dt));
+        assert_eq!(clock.advance(frame), Some(clock.step_dt));
+        assert_eq!(clock.advance(frame), None);
+
+        clock.step();
+        clock.toggle_pause();
+        assert!(!clock.is_paused());
+        assert_eq!(clock.advance(frame), Some(Dt::from_millis(5)));
+    }
+}
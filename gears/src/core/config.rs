@@ -1,3 +1,15 @@
+use super::crash::CrashConfig;
+use super::telemetry::TelemetryConfig;
+use super::vfs::AssetConfig;
+use crate::ecs;
+use crate::ecs::traits::Component;
+use crate::net::server::ServerConfig;
+use std::path::PathBuf;
+
+/// The largest width and height of the window.
+pub const MAX_WINDOW_SIZE: u32 = 16384;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum LogLevel {
     Error = 1,
     Warn = 2,
@@ -6,13 +18,341 @@ pub enum LogLevel {
     Trace = 5,
 }
 
+impl LogLevel {
+    pub fn to_filter(self) -> log::LevelFilter {
+        match self {
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct LogConfig {
     pub level: LogLevel,
 }
 
+/// The window opened by the renderer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowConfig {
+    pub title: String,
+    /// The inner size of the window in physical pixels, `None` to let the platform pick it.
+    pub size: Option<(u32, u32)>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "Gears".to_string(),
+            size: None,
+        }
+    }
+}
+
+/// How the screen is used when its aspect ratio differs from the target.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum AspectMode {
+    /// Use the whole window, the HUD is anchored to the window.
+    #[default]
+    Fill,
+    /// Keep the target aspect ratio by letterboxing or pillarboxing the view.
+    Fit,
+}
+
+/// How the frames are paced when vsync is off.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum FramePacing {
+    /// Render as fast as possible.
+    Unlimited,
+    /// Pace to the refresh rate of the monitor the window is on.
+    #[default]
+    Display,
+    /// Pace to an integer divisor of the refresh rate, e.g. 2 for 72 fps on a 144 Hz display.
+    DisplayDivisor(u32),
+    /// Pace to a fixed frame rate.
+    Fixed(f32),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DisplayConfig {
+    /// The width divided by the height the game is designed for, e.g. `16.0 / 9.0`.
+    pub target_aspect: Option<f32>,
+    pub aspect_mode: AspectMode,
+    /// The fraction of the viewport kept free of HUD elements on each side.
+    pub safe_area_margin: f32,
+    pub vsync: bool,
+    pub frame_pacing: FramePacing,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            target_aspect: None,
+            aspect_mode: AspectMode::Fill,
+            safe_area_margin: 0.05,
+            vsync: true,
+            frame_pacing: FramePacing::Display,
+        }
+    }
+}
+
+/// Exports the presented frames into a video, see `renderer::recorder`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordingConfig {
+    /// The video file to write, the container is picked by ffmpeg from the extension.
+    pub path: PathBuf,
+    /// The frame rate of the video, the simulation runs at a fixed step of this rate while recording.
+    pub frame_rate: u32,
+    /// The ffmpeg executable to pipe the frames to.
+    pub ffmpeg: String,
+}
+
+impl RecordingConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            frame_rate: 60,
+            ffmpeg: "ffmpeg".to_string(),
+        }
+    }
+
+    pub fn with_frame_rate(mut self, frame_rate: u32) -> Self {
+        self.frame_rate = frame_rate;
+        self
+    }
+
+    pub fn with_ffmpeg(mut self, ffmpeg: impl Into<String>) -> Self {
+        self.ffmpeg = ffmpeg.into();
+        self
+    }
+}
+
+/// Streams the textures of the models, see `renderer::streaming`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextureStreamingConfig {
+    /// The GPU memory the streamed textures can use in bytes.
+    pub budget: u64,
+    /// The mip levels up to this size are uploaded as soon as a texture is decoded.
+    pub resident_size: u32,
+    /// The largest number of textures refined or evicted per frame.
+    pub uploads_per_frame: usize,
+    /// The camera distance up to which the full resolution is wanted, it halves with every doubling.
+    pub full_resolution_distance: f32,
+}
+
+impl Default for TextureStreamingConfig {
+    fn default() -> Self {
+        Self {
+            budget: 256 * 1024 * 1024,
+            resident_size: 64,
+            uploads_per_frame: 4,
+            full_resolution_distance: 10.0,
+        }
+    }
+}
+
+/// Packs the small textures of a model into an atlas at load, so its meshes share a bind group,
+/// see `renderer::atlas`. The textures of the meshes with UVs outside of 0 to 1 are not packed, as they tile.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TextureAtlasConfig {
+    /// The textures up to this width and height are packed.
+    pub max_texture_size: u32,
+    /// The width and the largest height of an atlas.
+    pub atlas_size: u32,
+    /// The pixels around each texture, repeating its edge.
+    pub padding: u32,
+}
+
+impl Default for TextureAtlasConfig {
+    fn default() -> Self {
+        Self {
+            max_texture_size: 256,
+            atlas_size: 2048,
+            padding: 2,
+        }
+    }
+}
+
+/// The graphics API of the renderer.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// The native API of the platform: Direct3D 12 or Vulkan on Windows, Metal on macOS and iOS,
+    /// Vulkan or OpenGL elsewhere.
+    #[default]
+    Auto,
+    Vulkan,
+    Dx12,
+    Metal,
+    /// OpenGL, for the GPUs without drivers for the other APIs.
+    Gl,
+}
+
+impl Backend {
+    /// Check if the backend can be used on this platform.
+    pub fn is_supported(self) -> bool {
+        match self {
+            Backend::Auto | Backend::Vulkan | Backend::Gl => true,
+            Backend::Dx12 => cfg!(windows),
+            Backend::Metal => cfg!(any(target_os = "macos", target_os = "ios")),
+        }
+    }
+}
+
+/// Picks the lights sent to the shaders each frame by their importance, their brightness and how much
+/// of the view their range covers. The lights leaving or entering the selection fade out or in.
+/// The ambient and directional lights are always the most important.
+/// The selected lights are binned into screen tiles, so a pixel only shades the lights reaching it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LightCullingConfig {
+    /// The most lights selected per frame, the lights fading out are drawn on top of them.
+    pub max_lights: u32,
+    /// The lights below this importance are not selected.
+    pub min_importance: f32,
+    /// The seconds a light takes to fade in or out.
+    pub fade_time: f32,
+}
+
+impl Default for LightCullingConfig {
+    fn default() -> Self {
+        Self {
+            max_lights: 256,
+            min_importance: 0.001,
+            fade_time: 0.3,
+        }
+    }
+}
+
+/// The optional render passes, the disabled ones create no GPU resources and are skipped each frame.
+/// A pass is only available if its cargo feature is enabled too.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RenderPassesConfig {
+    /// The GPU particles, needs the `particles` feature.
+    pub particles: bool,
+    /// The instanced skinned crowds, needs the `crowds` feature.
+    pub crowds: bool,
+    /// The projected decals, needs the `decals` feature.
+    pub decals: bool,
+    /// Render the scene into a floating point target tone mapped into the frame, needs the `hdr` feature.
+    pub hdr: bool,
+    /// The crosshair in the middle of the views, needs the `crosshair` feature.
+    /// It is shown with the `RuntimeConfig`.
+    pub crosshair: bool,
+    /// The wireframe of the models, needs the `wireframe` feature and a GPU drawing lines.
+    /// It is shown with the `RuntimeConfig`.
+    pub wireframe: bool,
+    /// The egui UI: the custom windows, the HUD, the editor tools and the overlays, needs the `ui` feature.
+    pub ui: bool,
+}
+
+impl Default for RenderPassesConfig {
+    fn default() -> Self {
+        Self {
+            particles: true,
+            crowds: true,
+            decals: true,
+            hdr: true,
+            crosshair: true,
+            wireframe: true,
+            ui: true,
+        }
+    }
+}
+
+/// The starting points of a config.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConfigPreset {
+    /// Verbose logging and the metrics served.
+    Debug,
+    /// Only the warnings and errors logged, no metrics.
+    Release,
+    /// For the editor and the tools, the metrics served and the textures loaded fully.
+    Tooling,
+}
+
+/// The settings the renderer is started with, taken from the `Config` by `Config::renderer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RendererConfig {
+    pub window: WindowConfig,
+    pub display: DisplayConfig,
+    pub backend: Backend,
+    pub recording: Option<RecordingConfig>,
+    pub texture_streaming: Option<TextureStreamingConfig>,
+    pub texture_atlas: Option<TextureAtlasConfig>,
+    pub light_culling: LightCullingConfig,
+    pub render_passes: RenderPassesConfig,
+}
+
+/// The settings which can be changed while the app runs.
+/// The app adds it to an entity when it starts, the changes of the component are applied on the next frame.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RuntimeConfig {
+    pub log_level: LogLevel,
+    /// The display settings, except `vsync` which is only read at startup.
+    pub display: DisplayConfig,
+    /// Show the built-in overlay of the FPS, the frame times, the renderer statistics and the system timings.
+    pub stats_overlay: bool,
+    /// Scales the zoom of the mouse wheel, a negative value inverts it.
+    pub scroll_sensitivity: f32,
+    /// Show the crosshair, if its pass is enabled in the `RenderPassesConfig`.
+    pub crosshair: bool,
+    /// Draw the wireframe of the models over them, if its pass is enabled in the `RenderPassesConfig`.
+    pub wireframe: bool,
+}
+
+impl Component for RuntimeConfig {}
+
+/// Apply the log level of the `RuntimeConfig`.
+pub fn update_log_level(ecs: &ecs::Manager) {
+    let Some((_, runtime)) = ecs.get_all_components_of_type::<RuntimeConfig>().pop() else {
+        return;
+    };
+    let level = runtime.read().unwrap().log_level.to_filter();
+    if log::max_level() != level {
+        log::set_max_level(level);
+    }
+}
+
+/// The configuration of the app, created from a preset and the `with_*` methods.
+/// It is checked with `validate` when the app starts.
+///
+/// The fields are read once when the app starts, the ones which can change later
+/// are in the `RuntimeConfig` component.
 pub struct Config {
     pub log: LogConfig,
     pub threadpool_size: usize,
+    /// The steps per second of the fixed update systems, see `GearsApp::add_fixed_system`.
+    pub fixed_update_hz: u32,
+    pub window: WindowConfig,
+    pub display: DisplayConfig,
+    pub backend: Backend,
+    /// Record the frames from the start, `None` to not record.
+    pub recording: Option<RecordingConfig>,
+    /// Stream the model textures in the background, `None` to load them fully before the first frame.
+    pub texture_streaming: Option<TextureStreamingConfig>,
+    /// Pack the small model textures into atlases, `None` to give every texture its own bind group.
+    /// The packed textures are not streamed.
+    pub texture_atlas: Option<TextureAtlasConfig>,
+    pub light_culling: LightCullingConfig,
+    pub render_passes: RenderPassesConfig,
+    /// Serve the engine metrics, `None` to not collect them.
+    pub telemetry: Option<TelemetryConfig>,
+    /// Write a crash report on panic, `None` to keep the default panic handling.
+    pub crash_report: Option<CrashConfig>,
+    /// Run as a dedicated server instead of opening a window, `None` to run as a client.
+    pub server: Option<ServerConfig>,
+    pub assets: AssetConfig,
+    /// Show the built-in stats overlay from the start, it can be toggled with the `RuntimeConfig`.
+    pub stats_overlay: bool,
+    /// Scales the zoom of the mouse wheel: the free camera moves along its view, a followed target is
+    /// zoomed to by the distance and a fixed camera narrows its field of view. A negative value inverts it.
+    pub scroll_sensitivity: f32,
+    /// Show the crosshair from the start, it can be toggled with the `RuntimeConfig`.
+    pub crosshair: bool,
+    /// Draw the wireframe of the models from the start, it can be toggled with the `RuntimeConfig`.
+    pub wireframe: bool,
 }
 
 impl Default for Config {
@@ -22,6 +362,310 @@ impl Default for Config {
                 level: LogLevel::Info,
             },
             threadpool_size: 8,
+            fixed_update_hz: 60,
+            window: WindowConfig::default(),
+            display: DisplayConfig::default(),
+            backend: Backend::default(),
+            recording: None,
+            texture_streaming: Some(TextureStreamingConfig::default()),
+            texture_atlas: Some(TextureAtlasConfig::default()),
+            light_culling: LightCullingConfig::default(),
+            render_passes: RenderPassesConfig::default(),
+            telemetry: None,
+            crash_report: Some(CrashConfig::default()),
+            server: None,
+            assets: AssetConfig::default(),
+            stats_overlay: false,
+            scroll_sensitivity: 1.0,
+            crosshair: false,
+            wireframe: false,
+        }
+    }
+}
+
+impl Config {
+    /// Create a config from a preset, the settings not covered by the presets are the defaults.
+    pub fn preset(preset: ConfigPreset) -> Self {
+        let config = Self::default();
+        match preset {
+            ConfigPreset::Debug => Self {
+                log: LogConfig {
+                    level: LogLevel::Debug,
+                },
+                telemetry: Some(TelemetryConfig::default()),
+                ..config
+            },
+            ConfigPreset::Release => Self {
+                log: LogConfig {
+                    level: LogLevel::Warn,
+                },
+                telemetry: None,
+                ..config
+            },
+            ConfigPreset::Tooling => Self {
+                telemetry: Some(TelemetryConfig::default()),
+                texture_streaming: None,
+                ..config
+            },
         }
     }
+
+    pub fn with_log_level(mut self, level: LogLevel) -> Self {
+        self.log.level = level;
+        self
+    }
+
+    pub fn with_threadpool_size(mut self, threadpool_size: usize) -> Self {
+        self.threadpool_size = threadpool_size;
+        self
+    }
+
+    pub fn with_fixed_update_hz(mut self, fixed_update_hz: u32) -> Self {
+        self.fixed_update_hz = fixed_update_hz;
+        self
+    }
+
+    pub fn with_window_title(mut self, title: impl Into<String>) -> Self {
+        self.window.title = title.into();
+        self
+    }
+
+    pub fn with_window_size(mut self, width: u32, height: u32) -> Self {
+        self.window.size = Some((width, height));
+        self
+    }
+
+    pub fn with_display(mut self, display: DisplayConfig) -> Self {
+        self.display = display;
+        self
+    }
+
+    pub fn with_recording(mut self, recording: RecordingConfig) -> Self {
+        self.recording = Some(recording);
+        self
+    }
+
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn with_texture_streaming(
+        mut self,
+        texture_streaming: Option<TextureStreamingConfig>,
+    ) -> Self {
+        self.texture_streaming = texture_streaming;
+        self
+    }
+
+    pub fn with_texture_atlas(mut self, texture_atlas: Option<TextureAtlasConfig>) -> Self {
+        self.texture_atlas = texture_atlas;
+        self
+    }
+
+    pub fn with_light_culling(mut self, light_culling: LightCullingConfig) -> Self {
+        self.light_culling = light_culling;
+        self
+    }
+
+    pub fn with_render_passes(mut self, render_passes: RenderPassesConfig) -> Self {
+        self.render_passes = render_passes;
+        self
+    }
+
+    pub fn with_telemetry(mut self, telemetry: Option<TelemetryConfig>) -> Self {
+        self.telemetry = telemetry;
+        self
+    }
+
+    pub fn with_crash_report(mut self, crash_report: Option<CrashConfig>) -> Self {
+        self.crash_report = crash_report;
+        self
+    }
+
+    pub fn with_server(mut self, server: ServerConfig) -> Self {
+        self.server = Some(server);
+        self
+    }
+
+    pub fn with_assets(mut self, assets: AssetConfig) -> Self {
+        self.assets = assets;
+        self
+    }
+
+    pub fn with_stats_overlay(mut self, stats_overlay: bool) -> Self {
+        self.stats_overlay = stats_overlay;
+        self
+    }
+
+    /// Scale the zoom of the mouse wheel, a negative value inverts it.
+    pub fn with_scroll_sensitivity(mut self, scroll_sensitivity: f32) -> Self {
+        self.scroll_sensitivity = scroll_sensitivity;
+        self
+    }
+
+    pub fn with_crosshair(mut self, crosshair: bool) -> Self {
+        self.crosshair = crosshair;
+        self
+    }
+
+    pub fn with_wireframe(mut self, wireframe: bool) -> Self {
+        self.wireframe = wireframe;
+        self
+    }
+
+    pub fn renderer(&self) -> RendererConfig {
+        RendererConfig {
+            window: self.window.clone(),
+            display: self.display,
+            backend: self.backend,
+            recording: self.recording.clone(),
+            texture_streaming: self.texture_streaming,
+            texture_atlas: self.texture_atlas,
+            light_culling: self.light_culling,
+            render_passes: self.render_passes,
+        }
+    }
+
+    /// Get the settings which can be changed while the app runs.
+    pub fn runtime(&self) -> RuntimeConfig {
+        RuntimeConfig {
+            log_level: self.log.level,
+            display: self.display,
+            stats_overlay: self.stats_overlay,
+            scroll_sensitivity: self.scroll_sensitivity,
+            crosshair: self.crosshair,
+            wireframe: self.wireframe,
+        }
+    }
+
+    /// Check if the settings are in their valid ranges.
+    ///
+    /// # Returns
+    ///
+    /// An error listing every invalid setting.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut errors = Vec::new();
+
+        if self.threadpool_size == 0 {
+            errors.push("the thread pool needs at least one thread".to_string());
+        }
+        if !(1..=1000).contains(&self.fixed_update_hz) {
+            errors.push(format!(
+                "the fixed update rate {} Hz is not within 1 and 1000",
+                self.fixed_update_hz
+            ));
+        }
+        if let Some((width, height)) = self.window.size {
+            if !(1..=MAX_WINDOW_SIZE).contains(&width) || !(1..=MAX_WINDOW_SIZE).contains(&height) {
+                errors.push(format!(
+                    "the window size {}x{} is not within 1x1 and {}x{}",
+                    width, height, MAX_WINDOW_SIZE, MAX_WINDOW_SIZE
+                ));
+            }
+        }
+        if let Some(aspect) = self.display.target_aspect {
+            if !aspect.is_finite() || aspect <= 0.0 {
+                errors.push(format!("the target aspect {} is not positive", aspect));
+            }
+        }
+        if !(0.0..0.5).contains(&self.display.safe_area_margin) {
+            errors.push(format!(
+                "the safe area margin {} is not within 0 and 0.5",
+                self.display.safe_area_margin
+            ));
+        }
+        match self.display.frame_pacing {
+            FramePacing::DisplayDivisor(0) => {
+                errors.push("the refresh rate divisor is 0".to_string())
+            }
+            FramePacing::Fixed(frame_rate) if !frame_rate.is_finite() || frame_rate <= 0.0 => {
+                errors.push(format!("the frame rate {} is not positive", frame_rate))
+            }
+            _ => {}
+        }
+        if !self.backend.is_supported() {
+            errors.push(format!(
+                "the {:?} backend is not available on this platform",
+                self.backend
+            ));
+        }
+        if let Some(recording) = &self.recording {
+            if !(1..=240).contains(&recording.frame_rate) {
+                errors.push(format!(
+                    "the recording frame rate {} is not within 1 and 240",
+                    recording.frame_rate
+                ));
+            }
+        }
+        if let Some(streaming) = &self.texture_streaming {
+            if streaming.budget == 0 || streaming.uploads_per_frame == 0 {
+                errors.push(
+                    "the texture streaming budget and uploads per frame must not be 0".to_string(),
+                );
+            }
+        }
+        if let Some(atlas) = &self.texture_atlas {
+            if atlas.max_texture_size == 0
+                || atlas.max_texture_size + 2 * atlas.padding > atlas.atlas_size
+            {
+                errors.push(format!(
+                    "the atlas of {} pixels can not hold the textures of {} pixels with a padding of {}",
+                    atlas.atlas_size, atlas.max_texture_size, atlas.padding
+                ));
+            }
+        }
+        if self.light_culling.max_lights == 0 {
+            errors.push("the light budget is 0".to_string());
+        }
+        if !self.scroll_sensitivity.is_finite() {
+            errors.push(format!(
+                "the scroll sensitivity {} is not finite",
+                self.scroll_sensitivity
+            ));
+        }
+
+        if !errors.is_empty() {
+            anyhow::bail!("Invalid config: {}", errors.join(", "));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_and_validation() {
+        let release = Config::preset(ConfigPreset::Release);
+        assert_eq!(release.log.level, LogLevel::Warn);
+        assert!(release.telemetry.is_none());
+        assert!(Config::preset(ConfigPreset::Tooling)
+            .texture_streaming
+            .is_none());
+
+        let config = Config::preset(ConfigPreset::Debug)
+            .with_window_title("Sandbox")
+            .with_window_size(1280, 720);
+        assert!(config.validate().is_ok());
+        assert_eq!(config.runtime().log_level, LogLevel::Debug);
+        let metal = Config::default().with_backend(Backend::Metal);
+        assert_eq!(
+            metal.validate().is_ok(),
+            cfg!(any(target_os = "macos", target_os = "ios"))
+        );
+
+        let err = config
+            .with_window_size(0, 720)
+            .with_threadpool_size(0)
+            .validate()
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "Invalid config: the thread pool needs at least one thread, \
+             the window size 0x720 is not within 1x1 and 16384x16384"
+        );
+    }
 }
@@ -0,0 +1,264 @@
+use super::checksum::StableHasher;
+use super::scene::Scene;
+use super::vfs;
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+
+/// The name of the manifest written next to the cooked assets.
+pub const MANIFEST: &str = "cooked.manifest";
+
+/// Get the stable hash of the content of an asset.
+pub fn content_hash(data: &[u8]) -> u64 {
+    let mut hasher = StableHasher::default();
+    hasher.write(data);
+    hasher.finish()
+}
+
+/// Check if an asset is a scene source, `.scene.json` or `.scene.ron`.
+pub fn is_scene(path: &str) -> bool {
+    let path = path.to_lowercase();
+    path.ends_with(".scene.json") || path.ends_with(".scene.ron")
+}
+
+/// Get the path of the cooked asset, `level.scene.json` is cooked to `level.scene.bin`.
+/// The other assets keep their path.
+pub fn cooked_path(path: &str) -> String {
+    let path = vfs::normalize(path);
+    if !is_scene(&path) {
+        return path;
+    }
+    let stem = &path[..path.rfind('.').unwrap_or(path.len())];
+    format!("{}.bin", stem)
+}
+
+/// A cooked asset of the manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// The hash of the source asset, an unchanged source is not cooked again.
+    pub source_hash: u64,
+    pub cooked: String,
+    /// The hash of the cooked asset, checked when it is loaded.
+    pub cooked_hash: u64,
+}
+
+/// The cooked assets by source path.
+///
+/// The manifest is a text file with one asset per line:
+/// the source hash, the cooked hash, the source path and the cooked path, separated by tabs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub entries: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut entries = BTreeMap::new();
+        for (line_number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let parts = line.split('\t').collect::<Vec<_>>();
+            let [source_hash, cooked_hash, source, cooked] = parts[..] else {
+                anyhow::bail!("Invalid manifest entry on line {}", line_number + 1);
+            };
+            entries.insert(
+                source.to_string(),
+                ManifestEntry {
+                    source_hash: u64::from_str_radix(source_hash, 16)?,
+                    cooked: cooked.to_string(),
+                    cooked_hash: u64::from_str_radix(cooked_hash, 16)?,
+                },
+            );
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn get(&self, path: &str) -> Option<&ManifestEntry> {
+        self.entries.get(&vfs::normalize(path))
+    }
+}
+
+impl std::fmt::Display for Manifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (source, entry) in &self.entries {
+            writeln!(
+                f,
+                "{:016x}\t{:016x}\t{}\t{}",
+                entry.source_hash, entry.cooked_hash, source, entry.cooked
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of `cook_dir`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CookReport {
+    /// The number of scenes converted to the binary format.
+    pub cooked: usize,
+    /// The number of raw assets copied as they are.
+    pub copied: usize,
+    /// The number of assets left alone because their source did not change.
+    pub unchanged: usize,
+}
+
+/// Cook every asset of a directory for release: scenes are converted to the binary format
+/// and the other assets are copied, with a content-hash manifest listing them.
+/// Assets whose source hash matches the previous manifest are not cooked again.
+/// Call it from the build script of a game, or use the `gears-cook` command.
+/// The output directory can be mounted as the asset root or packed with `vfs::pack_dir`.
+///
+/// # Arguments
+///
+/// * `dir` - The directory of the source assets.
+/// * `prefix` - The path the assets are stored under, e.g. `res` to keep the `res/models/...` paths.
+/// * `out` - The directory to write the cooked assets and the manifest to.
+pub fn cook_dir(
+    dir: impl AsRef<Path>,
+    prefix: &str,
+    out: impl AsRef<Path>,
+) -> anyhow::Result<CookReport> {
+    fn collect(dir: &Path, name: &str, files: &mut Vec<(String, PathBuf)>) -> anyhow::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let name = vfs::normalize(&format!("{}/{}", name, file_name));
+            if path.is_dir() {
+                collect(&path, &name, files)?;
+            } else {
+                files.push((name, path));
+            }
+        }
+        Ok(())
+    }
+
+    let out = out.as_ref();
+    let previous = match std::fs::read_to_string(out.join(MANIFEST)) {
+        Ok(text) => Manifest::parse(&text).unwrap_or_default(),
+        Err(_) => Manifest::default(),
+    };
+
+    let mut files = Vec::new();
+    collect(dir.as_ref(), prefix, &mut files)?;
+    files.sort();
+
+    let mut manifest = Manifest::default();
+    let mut report = CookReport::default();
+    for (name, path) in files {
+        let data =
+            std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let source_hash = content_hash(&data);
+        let cooked = cooked_path(&name);
+
+        if let Some(entry) = previous.get(&name) {
+            if entry.source_hash == source_hash && out.join(&entry.cooked).is_file() {
+                manifest.entries.insert(name, entry.clone());
+                report.unchanged += 1;
+                continue;
+            }
+        }
+
+        let cooked_data = if is_scene(&name) {
+            report.cooked += 1;
+            let text = std::str::from_utf8(&data)?;
+            Scene::parse(text)
+                .with_context(|| format!("Failed to cook {}", name))?
+                .to_bytes()
+        } else {
+            report.copied += 1;
+            data
+        };
+
+        let cooked_file = out.join(&cooked);
+        if let Some(parent) = cooked_file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&cooked_file, &cooked_data)
+            .with_context(|| format!("Failed to write {}", cooked_file.display()))?;
+
+        manifest.entries.insert(
+            name,
+            ManifestEntry {
+                source_hash,
+                cooked,
+                cooked_hash: content_hash(&cooked_data),
+            },
+        );
+    }
+
+    std::fs::write(out.join(MANIFEST), manifest.to_string())?;
+    Ok(report)
+}
+
+/// Read the cooked version of an asset from the VFS.
+///
+/// # Returns
+///
+/// `None` if no manifest lists the asset, an error if the cooked data does not match its hash.
+pub fn load_cooked(path: &str) -> Option<anyhow::Result<Vec<u8>>> {
+    if !vfs::exists(MANIFEST) {
+        return None;
+    }
+    let manifest = match vfs::read_to_string(MANIFEST).and_then(|text| Manifest::parse(&text)) {
+        Ok(manifest) => manifest,
+        Err(e) => return Some(Err(e)),
+    };
+    let entry = manifest.get(path)?;
+
+    let read = || {
+        let data = vfs::read(&entry.cooked)?;
+        if content_hash(&data) != entry.cooked_hash {
+            anyhow::bail!("{} does not match the cook manifest", entry.cooked);
+        }
+        Ok(data)
+    };
+    Some(read())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cook_dir() {
+        let dir = std::env::temp_dir().join(format!("gears_cook_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (src, out) = (dir.join("src"), dir.join("out"));
+        std::fs::create_dir_all(src.join("scenes")).unwrap();
+        std::fs::write(
+            src.join("scenes/level.scene.json"),
+            r#"{ "entities": [{ "name": "Ball", "position": [1, 2, 3] }] }"#,
+        )
+        .unwrap();
+        std::fs::write(src.join("icon.png"), [1u8, 2, 3]).unwrap();
+
+        let report = cook_dir(&src, "res", &out).unwrap();
+        assert_eq!((report.cooked, report.copied, report.unchanged), (1, 1, 0));
+
+        let manifest = Manifest::parse(&std::fs::read_to_string(out.join(MANIFEST)).unwrap());
+        let manifest = manifest.unwrap();
+        let entry = manifest.get("res/scenes/level.scene.json").unwrap();
+        assert_eq!(entry.cooked, "res/scenes/level.scene.bin");
+        let cooked = std::fs::read(out.join(&entry.cooked)).unwrap();
+        assert_eq!(content_hash(&cooked), entry.cooked_hash);
+        assert_eq!(
+            Scene::from_bytes(&cooked).unwrap().entities[0].position,
+            [1.0, 2.0, 3.0]
+        );
+        assert_eq!(
+            std::fs::read(out.join("res/icon.png")).unwrap(),
+            vec![1, 2, 3]
+        );
+
+        // Only the changed assets are cooked again
+        std::fs::write(src.join("icon.png"), [4u8]).unwrap();
+        let report = cook_dir(&src, "res", &out).unwrap();
+        assert_eq!((report.cooked, report.copied, report.unchanged), (0, 1, 1));
+
+        assert!(cook_dir(&src, "res", &out).is_ok());
+        std::fs::write(src.join("broken.scene.ron"), "Scene(entities: [").unwrap();
+        assert!(cook_dir(&src, "res", &out).is_err());
+    }
+}
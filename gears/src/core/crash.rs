@@ -0,0 +1,203 @@
+use crate::ecs;
+use std::backtrace::Backtrace;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{LazyLock, Mutex, Weak};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Writes a crash report when the application panics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashConfig {
+    /// The directory the reports are written to, it is created if it does not exist.
+    pub directory: PathBuf,
+    /// The number of the latest log lines kept for the report.
+    pub log_lines: usize,
+}
+
+impl Default for CrashConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("crash_reports"),
+            log_lines: 200,
+        }
+    }
+}
+
+static FRAME: AtomicU64 = AtomicU64::new(0);
+static LOG_CAPACITY: AtomicUsize = AtomicUsize::new(0);
+static LOG_LINES: LazyLock<Mutex<VecDeque<String>>> = LazyLock::new(Default::default);
+
+/// Count a started frame, the number of the frame is written to the crash report.
+pub fn frame_started() {
+    FRAME.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Get the number of the current frame.
+pub fn frame() -> u64 {
+    FRAME.load(Ordering::Relaxed)
+}
+
+/// A logger keeping the latest lines for the crash report, before passing them to env_logger.
+struct CrashLogger {
+    inner: env_logger::Logger,
+}
+
+impl log::Log for CrashLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+
+        let capacity = LOG_CAPACITY.load(Ordering::Relaxed);
+        if capacity > 0 {
+            // Never wait here, the log may be written while the panic hook holds the lines
+            if let Ok(mut lines) = LOG_LINES.try_lock() {
+                while lines.len() >= capacity {
+                    lines.pop_front();
+                }
+                lines.push_back(format!(
+                    "[{} {}] {}",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                ));
+            }
+        }
+
+        self.inner.log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Install the logger and the panic hook writing the crash reports.
+///
+/// # Arguments
+///
+/// * `config` - The crash report configuration.
+/// * `logger` - The logger the lines are passed on to.
+/// * `ecs` - The world summarized in the report, it is not kept alive by the hook.
+pub fn install(
+    config: CrashConfig,
+    logger: env_logger::Logger,
+    ecs: Weak<Mutex<ecs::Manager>>,
+) -> anyhow::Result<()> {
+    LOG_CAPACITY.store(config.log_lines, Ordering::Relaxed);
+    log::set_max_level(logger.filter());
+    log::set_boxed_logger(Box::new(CrashLogger { inner: logger }))?;
+
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let backtrace = Backtrace::force_capture();
+        let logs = LOG_LINES
+            .try_lock()
+            .map(|lines| lines.iter().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        // The panicking thread may hold the world, so the summary is best effort
+        let world = ecs.upgrade().and_then(|ecs| {
+            let ecs = ecs.try_lock().ok()?;
+            ecs.component_counts()
+        });
+
+        let report = report(
+            &info.to_string(),
+            &backtrace.to_string(),
+            frame(),
+            &logs,
+            world.as_ref(),
+        );
+        match write_report(&config.directory, &report) {
+            Ok(path) => eprintln!("Crash report written to {}", path.display()),
+            Err(e) => eprintln!("Failed to write the crash report: {}", e),
+        }
+
+        previous_hook(info);
+    }));
+
+    Ok(())
+}
+
+/// Build the text of a crash report.
+fn report(
+    message: &str,
+    backtrace: &str,
+    frame: u64,
+    logs: &[String],
+    world: Option<&BTreeMap<&'static str, usize>>,
+) -> String {
+    let mut out = String::new();
+
+    let _ = writeln!(out, "Gears crash report");
+    let _ = writeln!(out, "Version: {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(out, "Frame: {}", frame);
+    let _ = writeln!(out, "\n== Panic ==\n{}", message);
+    let _ = writeln!(out, "\n== Backtrace ==\n{}", backtrace);
+
+    let _ = writeln!(out, "\n== World ==");
+    match world {
+        Some(counts) => {
+            for (component, count) in counts {
+                let _ = writeln!(out, "{}: {}", component, count);
+            }
+        }
+        None => {
+            let _ = writeln!(out, "<unavailable, the world was locked>");
+        }
+    }
+
+    let _ = writeln!(out, "\n== Log ==");
+    for line in logs {
+        let _ = writeln!(out, "{}", line);
+    }
+
+    out
+}
+
+fn write_report(directory: &Path, report: &str) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(directory)?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = directory.join(format!("crash-{}.txt", timestamp));
+    std::fs::write(&path, report)?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let world = BTreeMap::from([("gears::ecs::components::Pos3", 12), ("Health", 3)]);
+        let logs = vec!["[INFO gears] Starting Gears...".to_string()];
+        let report = report(
+            "panicked at main.rs:1:1:\noops",
+            "0: main",
+            42,
+            &logs,
+            Some(&world),
+        );
+
+        assert!(report.contains("Frame: 42"));
+        assert!(report.contains("oops"));
+        assert!(report.contains("0: main"));
+        assert!(report.contains("gears::ecs::components::Pos3: 12"));
+        assert!(report.contains("Health: 3"));
+        assert!(report.ends_with("[INFO gears] Starting Gears...\n"));
+
+        let locked = super::report("oops", "", 0, &[], None);
+        assert!(locked.contains("<unavailable, the world was locked>"));
+    }
+}
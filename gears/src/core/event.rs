@@ -1,6 +1,7 @@
 use std::{
     collections::VecDeque,
     fmt::Debug,
+    path::PathBuf,
     sync::{Arc, Mutex},
 };
 
@@ -18,17 +19,151 @@ pub enum DeviceEvent {
     KeyboardInput,
 }
 
+/// A change of the window requested by a system, send it with `ecs::Manager::send_event`.
+/// The renderer applies the commands at the start of the next frame, they are ignored when headless.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WindowCommand {
+    SetTitle(String),
+    /// Resize the inner size of the window in physical pixels, the platform may not allow it.
+    Resize(u32, u32),
+    /// Switch to borderless fullscreen on the current monitor or back to a window.
+    SetFullscreen(bool),
+    ToggleFullscreen,
+    SetCursor(CursorIcon),
+    SetCursorVisible(bool),
+    /// Put text on the clipboard of the platform.
+    SetClipboard(String),
+    /// Read the clipboard of the platform, the text is sent as a `ClipboardText` event.
+    ReadClipboard,
+}
+
+/// The text on the clipboard, sent in reply to `WindowCommand::ReadClipboard`.
+/// It is empty if the clipboard holds no text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipboardText(pub String);
+
+/// A file dragged onto the window from the platform, e.g. a model or a scene to open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileDrop {
+    /// A file is dragged over the window, several files send an event each.
+    Hovered(PathBuf),
+    /// The files were dragged away without being dropped.
+    Cancelled,
+    Dropped(PathBuf),
+}
+
+/// The cursor shown over the window.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum CursorIcon {
+    #[default]
+    Default,
+    Pointer,
+    Crosshair,
+    Text,
+    Move,
+    Grab,
+    Grabbing,
+    Wait,
+    NotAllowed,
+}
+
 #[derive(Debug)]
 pub enum GearsEvent {
     WindowEvent(WindowEvent),
     DeviceEvent(DeviceEvent),
     CustomEvent,
     UserEvent,
+    /// Pause or resume the simulation.
+    SetPaused(bool),
     CloseRequest,
 }
 
+impl GearsEvent {
+    /// Get the priority the event is queued with by `add_event`.
+    pub fn priority(&self) -> EventPriority {
+        match self {
+            GearsEvent::CloseRequest => EventPriority::High,
+            GearsEvent::DeviceEvent(DeviceEvent::MouseMotion) => EventPriority::Low,
+            _ => EventPriority::Normal,
+        }
+    }
+
+    /// Get the kind of the event if only the latest event of its kind matters.
+    /// Queuing such an event replaces the queued one of the same kind.
+    pub fn coalesce_key(&self) -> Option<&'static str> {
+        match self {
+            GearsEvent::WindowEvent(WindowEvent::Resize(..)) => Some("resize"),
+            GearsEvent::WindowEvent(WindowEvent::Redraw) => Some("redraw"),
+            GearsEvent::DeviceEvent(DeviceEvent::MouseMotion) => Some("mouse_motion"),
+            GearsEvent::SetPaused(_) => Some("set_paused"),
+            _ => None,
+        }
+    }
+}
+
+/// The priority of a queued event, the events of a higher priority are removed first.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EventPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl EventPriority {
+    /// The priorities, the highest first.
+    const DESCENDING: [EventPriority; 3] = [
+        EventPriority::High,
+        EventPriority::Normal,
+        EventPriority::Low,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// What happens when an event is added to a full queue.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Drop the oldest event of the lowest priority, or the new event if everything queued is more important.
+    #[default]
+    DropOldest,
+    /// Drop the new event.
+    DropNewest,
+}
+
+/// The counters of an event queue.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct EventQueueStats {
+    /// The events added, including the coalesced and the dropped ones.
+    pub added: u64,
+    /// The events taken by `remove_event`.
+    pub processed: u64,
+    /// The events replaced by a newer event of the same kind.
+    pub coalesced: u64,
+    /// The events dropped because the queue was full.
+    pub dropped: u64,
+}
+
+struct Queues {
+    /// A queue per priority, indexed by `EventPriority::index`.
+    events: [VecDeque<GearsEvent>; 3],
+    stats: EventQueueStats,
+}
+
+impl Queues {
+    fn len(&self) -> usize {
+        self.events.iter().map(VecDeque::len).sum()
+    }
+}
+
+/// A bounded queue of events with priorities.
+/// The events of a kind where only the latest matters, like a resize, are coalesced.
 pub struct EventQueue {
-    events: Arc<Mutex<VecDeque<GearsEvent>>>,
+    queues: Arc<Mutex<Queues>>,
+    capacity: usize,
+    overflow: OverflowPolicy,
 }
 
 impl Default for EventQueue {
@@ -38,19 +173,144 @@ impl Default for EventQueue {
 }
 
 impl EventQueue {
+    /// The capacity of a queue created by `new`.
+    pub const DEFAULT_CAPACITY: usize = 1024;
+
     pub fn new() -> Self {
+        Self::with_capacity(Self::DEFAULT_CAPACITY, OverflowPolicy::default())
+    }
+
+    /// Create a queue holding at most `capacity` events.
+    pub fn with_capacity(capacity: usize, overflow: OverflowPolicy) -> Self {
         Self {
-            events: Arc::new(Mutex::new(VecDeque::new())),
+            queues: Arc::new(Mutex::new(Queues {
+                events: Default::default(),
+                stats: EventQueueStats::default(),
+            })),
+            capacity: capacity.max(1),
+            overflow,
         }
     }
 
-    pub fn add_event(&mut self, event: GearsEvent) {
-        let mut events = &mut self.events.lock().unwrap();
-        events.push_back(event);
+    /// Add an event with the priority of its kind.
+    pub fn add_event(&self, event: GearsEvent) {
+        let priority = event.priority();
+        self.add_event_with_priority(event, priority);
+    }
+
+    /// Add an event with a priority.
+    ///
+    /// # Returns
+    ///
+    /// False if the event was dropped because the queue is full.
+    pub fn add_event_with_priority(&self, event: GearsEvent, priority: EventPriority) -> bool {
+        let mut guard = self.queues.lock().unwrap();
+        let queues = &mut *guard;
+        queues.stats.added += 1;
+
+        if let Some(key) = event.coalesce_key() {
+            for queue in queues.events.iter_mut() {
+                if let Some(i) = queue.iter().position(|e| e.coalesce_key() == Some(key)) {
+                    queue.remove(i);
+                    queues.stats.coalesced += 1;
+                    break;
+                }
+            }
+        }
+
+        if queues.len() >= self.capacity {
+            let evicted = match self.overflow {
+                OverflowPolicy::DropNewest => false,
+                OverflowPolicy::DropOldest => EventPriority::DESCENDING
+                    .into_iter()
+                    .rev()
+                    .take_while(|lowest| *lowest <= priority)
+                    .any(|lowest| queues.events[lowest.index()].pop_front().is_some()),
+            };
+            queues.stats.dropped += 1;
+            if !evicted {
+                return false;
+            }
+        }
+
+        queues.events[priority.index()].push_back(event);
+        true
     }
 
-    pub fn remove_event(&mut self) -> Option<GearsEvent> {
-        let mut events = &mut self.events.lock().unwrap();
-        events.pop_front()
+    /// Take the oldest event of the highest priority.
+    pub fn remove_event(&self) -> Option<GearsEvent> {
+        let mut queues = self.queues.lock().unwrap();
+        let event = EventPriority::DESCENDING
+            .into_iter()
+            .find_map(|priority| queues.events[priority.index()].pop_front());
+        if event.is_some() {
+            queues.stats.processed += 1;
+        }
+        event
+    }
+
+    pub fn len(&self) -> usize {
+        self.queues.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> EventQueueStats {
+        self.queues.lock().unwrap().stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priority_and_coalescing() {
+        let queue = EventQueue::new();
+        queue.add_event(GearsEvent::WindowEvent(WindowEvent::Resize(800, 600)));
+        queue.add_event(GearsEvent::UserEvent);
+        queue.add_event(GearsEvent::WindowEvent(WindowEvent::Resize(1024, 768)));
+        queue.add_event(GearsEvent::CloseRequest);
+
+        assert!(matches!(
+            queue.remove_event(),
+            Some(GearsEvent::CloseRequest)
+        ));
+        assert!(matches!(queue.remove_event(), Some(GearsEvent::UserEvent)));
+        // Only the latest resize is kept
+        assert!(matches!(
+            queue.remove_event(),
+            Some(GearsEvent::WindowEvent(WindowEvent::Resize(1024, 768)))
+        ));
+        assert!(queue.remove_event().is_none());
+        assert_eq!(
+            queue.stats(),
+            EventQueueStats {
+                added: 4,
+                processed: 3,
+                coalesced: 1,
+                dropped: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        let queue = EventQueue::with_capacity(2, OverflowPolicy::DropOldest);
+        queue.add_event(GearsEvent::DeviceEvent(DeviceEvent::MouseMotion));
+        queue.add_event(GearsEvent::UserEvent);
+        // The low priority motion is dropped for the new event
+        assert!(queue.add_event_with_priority(GearsEvent::CustomEvent, EventPriority::Normal));
+        // Nothing less important is queued
+        assert!(!queue.add_event_with_priority(GearsEvent::UserEvent, EventPriority::Low));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.stats().dropped, 2);
+
+        let queue = EventQueue::with_capacity(1, OverflowPolicy::DropNewest);
+        queue.add_event(GearsEvent::UserEvent);
+        assert!(!queue.add_event_with_priority(GearsEvent::CloseRequest, EventPriority::High));
+        assert!(matches!(queue.remove_event(), Some(GearsEvent::UserEvent)));
     }
 }
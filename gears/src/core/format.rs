@@ -0,0 +1,32 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// The options of the RON files, `Some` can be left out around the optional values.
+fn ron_options() -> ron::Options {
+    ron::Options::default().with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+}
+
+/// Read a RON file.
+pub fn from_ron<T: DeserializeOwned>(text: &str) -> anyhow::Result<T> {
+    Ok(ron_options().from_str(text)?)
+}
+
+/// Write a RON file, with the names of the structs.
+pub fn to_ron<T: Serialize>(value: &T) -> anyhow::Result<String> {
+    let pretty = ron::ser::PrettyConfig::new().struct_names(true);
+    Ok(ron_options().to_string_pretty(value, pretty)?)
+}
+
+/// Read a JSON or a RON file, JSON files start with an object.
+pub fn from_json_or_ron<T: DeserializeOwned>(text: &str) -> anyhow::Result<T> {
+    if text.trim_start().starts_with('{') {
+        Ok(serde_json::from_str(text)?)
+    } else {
+        from_ron(text)
+    }
+}
+
+/// Write a JSON file.
+pub fn to_json<T: Serialize>(value: &T) -> anyhow::Result<String> {
+    Ok(serde_json::to_string_pretty(value)?)
+}
@@ -0,0 +1,270 @@
+use super::Dt;
+use crate::ecs;
+use crate::ecs::traits::Component;
+use instant::{Duration, Instant};
+
+/// The state of a job after a step.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Progress {
+    /// The job has more work, with the fraction of the work done from 0 to 1.
+    Running(f32),
+    Done,
+}
+
+/// A long-running piece of work, like a navmesh or a lightmap bake, done in small steps.
+/// A step should take well under the budget of the scheduler, e.g. a single chunk or a row of texels.
+pub trait Job: Send + Sync {
+    /// Do the next step of the work.
+    fn step(&mut self, ecs: &ecs::Manager) -> Progress;
+}
+
+impl<F: FnMut(&ecs::Manager) -> Progress + Send + Sync> Job for F {
+    fn step(&mut self, ecs: &ecs::Manager) -> Progress {
+        self(ecs)
+    }
+}
+
+/// Identifies a job of a `JobScheduler`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct JobId(u64);
+
+/// The progress of a scheduled job.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobInfo {
+    pub id: JobId,
+    pub name: String,
+    /// The fraction of the work done from 0 to 1.
+    pub progress: f32,
+    /// The time spent in the steps of the job.
+    pub busy: Duration,
+    pub steps: u64,
+}
+
+/// Sent when a job is done.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobFinished {
+    pub id: JobId,
+    pub name: String,
+    pub busy: Duration,
+    pub steps: u64,
+}
+
+struct ScheduledJob {
+    info: JobInfo,
+    job: Box<dyn Job>,
+}
+
+/// Runs long jobs a few steps per update within a time budget, so they do not cause frame spikes.
+/// The jobs take turns, each getting a step in order until the budget is used up.
+/// `update_jobs` runs the steps, add it as a system.
+pub struct JobScheduler {
+    /// The time the steps may take per update, at least one step runs on each update.
+    pub budget: Duration,
+    jobs: Vec<ScheduledJob>,
+    /// The job taking the next step.
+    next: usize,
+    next_id: u64,
+}
+
+impl Component for JobScheduler {}
+
+impl Default for JobScheduler {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(2))
+    }
+}
+
+impl JobScheduler {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            jobs: Vec::new(),
+            next: 0,
+            next_id: 0,
+        }
+    }
+
+    /// Add a job, it takes its first step on the next update.
+    pub fn add(&mut self, name: impl Into<String>, job: impl Job + 'static) -> JobId {
+        let id = JobId(self.next_id);
+        self.next_id += 1;
+        self.jobs.push(ScheduledJob {
+            info: JobInfo {
+                id,
+                name: name.into(),
+                progress: 0.0,
+                busy: Duration::ZERO,
+                steps: 0,
+            },
+            job: Box::new(job),
+        });
+        id
+    }
+
+    /// Stop a job without finishing it.
+    ///
+    /// # Returns
+    ///
+    /// True if the job was scheduled.
+    pub fn cancel(&mut self, id: JobId) -> bool {
+        let Some(i) = self.jobs.iter().position(|job| job.info.id == id) else {
+            return false;
+        };
+        self.jobs.remove(i);
+        if i < self.next {
+            self.next -= 1;
+        }
+        true
+    }
+
+    /// Get the progress of a job, `None` if it is done or was never scheduled.
+    pub fn progress(&self, id: JobId) -> Option<&JobInfo> {
+        self.jobs
+            .iter()
+            .map(|job| &job.info)
+            .find(|info| info.id == id)
+    }
+
+    /// Get the progress of the scheduled jobs in the order they were added.
+    pub fn jobs(&self) -> impl Iterator<Item = &JobInfo> {
+        self.jobs.iter().map(|job| &job.info)
+    }
+}
+
+/// Run the steps of the jobs in turns until the budget is used up.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `jobs` - The jobs, the finished ones are removed.
+/// * `next` - The job taking the next step.
+/// * `budget` - The time the steps may take.
+///
+/// # Returns
+///
+/// The jobs which are done.
+fn run_jobs(
+    ecs: &ecs::Manager,
+    jobs: &mut Vec<ScheduledJob>,
+    next: &mut usize,
+    budget: Duration,
+) -> Vec<JobFinished> {
+    let start = Instant::now();
+    let mut finished = Vec::new();
+
+    while !jobs.is_empty() {
+        if *next >= jobs.len() {
+            *next = 0;
+        }
+        let job = &mut jobs[*next];
+        let step_start = Instant::now();
+        let progress = job.job.step(ecs);
+        job.info.busy += step_start.elapsed();
+        job.info.steps += 1;
+
+        match progress {
+            Progress::Running(progress) => {
+                job.info.progress = progress.clamp(0.0, 1.0);
+                *next += 1;
+            }
+            Progress::Done => {
+                let job = jobs.remove(*next);
+                finished.push(JobFinished {
+                    id: job.info.id,
+                    name: job.info.name,
+                    busy: job.info.busy,
+                    steps: job.info.steps,
+                });
+            }
+        }
+
+        if start.elapsed() >= budget {
+            break;
+        }
+    }
+    finished
+}
+
+/// Run the steps of the scheduled jobs within the budget and send `JobFinished` for the finished ones.
+///
+/// The scheduler is not locked while the steps run, so the jobs can add jobs to it.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time, unused.
+pub fn update_jobs(ecs: &ecs::Manager, _dt: Dt) {
+    for (_, scheduler) in ecs.get_all_components_of_type::<JobScheduler>() {
+        let (mut jobs, mut next, budget) = {
+            let mut scheduler = scheduler.write().unwrap();
+            let jobs = std::mem::take(&mut scheduler.jobs);
+            (jobs, scheduler.next, scheduler.budget)
+        };
+        let finished = run_jobs(ecs, &mut jobs, &mut next, budget);
+
+        {
+            // The jobs added during the steps run after the ones already scheduled
+            let mut scheduler = scheduler.write().unwrap();
+            let added = std::mem::replace(&mut scheduler.jobs, jobs);
+            scheduler.jobs.extend(added);
+            scheduler.next = next;
+        }
+        for job in finished {
+            log::info!(
+                "[Jobs] {} finished in {} steps, {:.1} ms",
+                job.name,
+                job.steps,
+                job.busy.as_secs_f64() * 1000.0
+            );
+            ecs.send_event(job);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_sliced_jobs() {
+        let ecs = ecs::Manager::default();
+        let entity = ecs.create_entity();
+        // A zero budget runs a single step per update
+        let mut scheduler = JobScheduler::new(Duration::ZERO);
+        let mut chunks = 0;
+        let generate = scheduler.add("Generate chunks", move |_ecs: &ecs::Manager| {
+            chunks += 1;
+            if chunks == 3 {
+                Progress::Done
+            } else {
+                Progress::Running(chunks as f32 / 3.0)
+            }
+        });
+        let bake = scheduler.add("Bake", |_ecs: &ecs::Manager| Progress::Running(0.5));
+        ecs.add_component_to_entity(entity, scheduler);
+
+        // The jobs take turns
+        for _ in 0..4 {
+            update_jobs(&ecs, Dt::ZERO);
+        }
+        let scheduler = ecs
+            .get_component_from_entity::<JobScheduler>(entity)
+            .unwrap();
+        {
+            let scheduler = scheduler.read().unwrap();
+            let progress = scheduler.progress(generate).unwrap();
+            assert_eq!((progress.steps, progress.progress), (2, 2.0 / 3.0));
+            assert_eq!(scheduler.progress(bake).unwrap().steps, 2);
+        }
+
+        update_jobs(&ecs, Dt::ZERO);
+        let finished = ecs.drain_events::<JobFinished>();
+        assert_eq!(finished.len(), 1);
+        assert_eq!((finished[0].id, finished[0].steps), (generate, 3));
+
+        let mut scheduler = scheduler.write().unwrap();
+        assert!(scheduler.progress(generate).is_none());
+        assert!(scheduler.cancel(bake));
+        assert_eq!(scheduler.jobs().count(), 0);
+    }
+}
@@ -0,0 +1,256 @@
+use super::{format, vfs};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{LazyLock, RwLock};
+
+/// A table of translated strings of a single language.
+pub type StringTable = HashMap<String, String>;
+
+static GLOBAL: LazyLock<RwLock<Localization>> =
+    LazyLock::new(|| RwLock::new(Localization::default()));
+
+/// Translated string tables with runtime language switching.
+/// The engine uses the global instance, see `global` and the `tr!` macro.
+#[derive(Debug, Clone)]
+pub struct Localization {
+    language: String,
+    fallback: String,
+    tables: HashMap<String, StringTable>,
+}
+
+impl Default for Localization {
+    fn default() -> Self {
+        Self::new("en")
+    }
+}
+
+impl Localization {
+    pub fn new(language: impl Into<String>) -> Self {
+        let language = language.into();
+        Self {
+            fallback: language.clone(),
+            language,
+            tables: HashMap::new(),
+        }
+    }
+
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// Switch the active language, keys missing from it are looked up in the fallback language.
+    pub fn set_language(&mut self, language: impl Into<String>) {
+        self.language = language.into();
+    }
+
+    pub fn set_fallback(&mut self, language: impl Into<String>) {
+        self.fallback = language.into();
+    }
+
+    /// Get the languages that have a string table.
+    pub fn languages(&self) -> Vec<&str> {
+        let mut languages: Vec<&str> = self.tables.keys().map(String::as_str).collect();
+        languages.sort();
+        languages
+    }
+
+    /// Add strings to the table of a language, existing keys are overwritten.
+    pub fn add_table(&mut self, language: impl Into<String>, table: StringTable) {
+        self.tables
+            .entry(language.into())
+            .or_default()
+            .extend(table);
+    }
+
+    /// Load a string table in the Fluent (`.ftl`) message format.
+    /// Only simple messages are supported: `key = value`, indented continuation lines and `#` comments.
+    pub fn parse_ftl(data: &str) -> anyhow::Result<StringTable> {
+        let mut table = StringTable::new();
+        let mut last_key: Option<String> = None;
+
+        for (line_number, line) in data.lines().enumerate() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+
+            // Indented lines continue the value of the previous message
+            if line.starts_with(char::is_whitespace) {
+                let Some(key) = &last_key else {
+                    anyhow::bail!("Continuation without a message on line {}", line_number + 1);
+                };
+                let value = table.get_mut(key).unwrap();
+                if !value.is_empty() {
+                    value.push('\n');
+                }
+                value.push_str(line.trim());
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                anyhow::bail!("Expected `key = value` on line {}", line_number + 1);
+            };
+            let key = key.trim();
+            if key.is_empty() {
+                anyhow::bail!("Missing key on line {}", line_number + 1);
+            }
+
+            table.insert(key.to_string(), value.trim().to_string());
+            last_key = Some(key.to_string());
+        }
+
+        Ok(table)
+    }
+
+    /// Load a string table from a flat JSON object of `"key": "value"` pairs.
+    pub fn parse_json(data: &str) -> anyhow::Result<StringTable> {
+        format::from_json_or_ron(data)
+    }
+
+    /// Load all `<language>.ftl` and `<language>.json` files of an asset directory.
+    /// The files are read through the `vfs`, so the tables of the archives and the mods are found too.
+    pub fn load_dir(&mut self, dir: &str) -> anyhow::Result<()> {
+        self.load_tables(dir, vfs::paths(), vfs::read_to_string)
+    }
+
+    fn load_tables(
+        &mut self,
+        dir: &str,
+        paths: BTreeSet<String>,
+        read: impl Fn(&str) -> anyhow::Result<String>,
+    ) -> anyhow::Result<()> {
+        let dir = vfs::normalize(dir);
+        for path in paths {
+            let name = if dir.is_empty() {
+                Some(path.as_str())
+            } else {
+                path.strip_prefix(&dir)
+                    .and_then(|name| name.strip_prefix('/'))
+            };
+            // Only the files of the directory itself, not of the ones inside it
+            let Some(name) = name.filter(|name| !name.contains('/')) else {
+                continue;
+            };
+            let Some((language, extension)) = name.rsplit_once('.') else {
+                continue;
+            };
+            let parse = match extension {
+                "ftl" => Self::parse_ftl,
+                "json" => Self::parse_json,
+                _ => continue,
+            };
+
+            let table = parse(&read(&path)?).map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+            self.add_table(language, table);
+        }
+
+        Ok(())
+    }
+
+    /// Get the string of a key in the active or fallback language.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        [&self.language, &self.fallback]
+            .into_iter()
+            .find_map(|language| self.tables.get(language)?.get(key))
+            .map(String::as_str)
+    }
+
+    /// Translate a key and replace its `{ $name }` placeholders with the arguments.
+    /// Missing keys are returned as is, so untranslated text stays readable.
+    pub fn translate(&self, key: &str, args: &[(&str, &dyn std::fmt::Display)]) -> String {
+        let mut text = self.get(key).unwrap_or(key).to_string();
+
+        for (name, value) in args {
+            let value = value.to_string();
+            text = text
+                .replace(&format!("{{ ${} }}", name), &value)
+                .replace(&format!("{{${}}}", name), &value);
+        }
+
+        text
+    }
+}
+
+/// Get the global localization used by the engine text and the `tr!` macro.
+pub fn global() -> &'static RwLock<Localization> {
+    &GLOBAL
+}
+
+/// Translate a key with the global localization.
+pub fn tr(key: &str, args: &[(&str, &dyn std::fmt::Display)]) -> String {
+    global().read().unwrap().translate(key, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EN: &str = "
+# Menu
+menu-resume = Resume
+greeting = Hello, { $name }!
+credits =
+    Made with
+    gears
+";
+
+    #[test]
+    fn test_parse_ftl() {
+        let table = Localization::parse_ftl(EN).unwrap();
+        assert_eq!(table["menu-resume"], "Resume");
+        assert_eq!(table["credits"], "Made with\ngears");
+        assert!(Localization::parse_ftl("no separator").is_err());
+    }
+
+    #[test]
+    fn test_load_dir() {
+        const FILES: vfs::EmbeddedAssets = &[
+            ("locale/en.ftl", b"menu-resume = Resume"),
+            (
+                "locale/hu.json",
+                "{ \"menu-resume\": \"Folytatás\" }".as_bytes(),
+            ),
+            ("locale/notes.txt", b"not a table"),
+            ("locale/old/de.ftl", b"menu-resume = Fortsetzen"),
+        ];
+        let mut files = vfs::Vfs::default();
+        files.embed(FILES, 0);
+
+        let mut localization = Localization::new("en");
+        localization
+            .load_tables("locale", files.paths(), |path| files.read_to_string(path))
+            .unwrap();
+        assert_eq!(localization.languages(), ["en", "hu"]);
+        localization.set_language("hu");
+        assert_eq!(localization.translate("menu-resume", &[]), "Folytatás");
+
+        assert!(Localization::parse_json("{ \"key\": 1 }").is_err());
+    }
+
+    #[test]
+    fn test_switch_language_with_fallback() {
+        let mut localization = Localization::new("en");
+        localization.add_table("en", Localization::parse_ftl(EN).unwrap());
+        localization.add_table(
+            "hu",
+            Localization::parse_ftl("menu-resume = Folytatás").unwrap(),
+        );
+
+        localization.set_language("hu");
+        assert_eq!(localization.translate("menu-resume", &[]), "Folytatás");
+        assert_eq!(
+            localization.translate("greeting", &[("name", &"Ada")]),
+            "Hello, Ada!"
+        );
+        assert_eq!(localization.translate("missing-key", &[]), "missing-key");
+    }
+
+    #[test]
+    fn test_tr_macro() {
+        global().write().unwrap().add_table(
+            "en",
+            Localization::parse_ftl("test-items = { $count } items").unwrap(),
+        );
+
+        assert_eq!(crate::tr!("test-items", count = 3), "3 items");
+        assert_eq!(crate::tr!("test-unknown"), "test-unknown");
+    }
+}
@@ -1,6 +1,19 @@
 pub mod app;
+pub mod checksum;
+pub mod clock;
 pub mod config;
+pub mod cook;
+pub mod crash;
 pub mod event;
+pub mod format;
+pub mod jobs;
+pub mod localization;
+pub mod mods;
+pub mod pacing;
+pub mod scene;
+pub mod schedule;
+pub mod telemetry;
 pub mod threadpool;
+pub mod vfs;
 
 pub type Dt = instant::Duration;
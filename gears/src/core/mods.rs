@@ -0,0 +1,174 @@
+use super::vfs::Pak;
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+/// The manifest of a mod, `mod.txt` at the root of its directory or archive.
+pub const MANIFEST: &str = "mod.txt";
+
+/// An installed mod, a directory or a `.pak` archive in the mods directory.
+///
+/// The manifest is a list of `key = value` lines, `#` starts a comment:
+///
+/// ```text
+/// name = HD Textures
+/// version = 1.2.0
+/// author = Someone
+/// description = Replaces the textures with higher resolution ones
+/// priority = 10
+/// ```
+///
+/// Every key is optional, a mod without a manifest is named after its file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModInfo {
+    /// The name of the file or directory, unique in the mods directory.
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub author: String,
+    pub description: String,
+    /// Mods with a higher priority override the assets of the lower ones.
+    pub priority: i32,
+    pub path: PathBuf,
+    pub enabled: bool,
+}
+
+impl ModInfo {
+    /// Create the info of a mod from its manifest.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the mod, the default name.
+    /// * `path` - The directory or archive of the mod.
+    /// * `manifest` - The content of the manifest, `None` if the mod has none.
+    pub fn parse(id: &str, path: PathBuf, manifest: Option<&str>) -> anyhow::Result<Self> {
+        let mut info = Self {
+            id: id.to_string(),
+            name: id.to_string(),
+            version: String::new(),
+            author: String::new(),
+            description: String::new(),
+            priority: 0,
+            path,
+            enabled: true,
+        };
+
+        for (number, line) in manifest.unwrap_or_default().lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once('=').with_context(|| {
+                format!("{}: line {} is not a `key = value` pair", id, number + 1)
+            })?;
+            let value = value.trim().to_string();
+
+            match key.trim() {
+                "name" => info.name = value,
+                "version" => info.version = value,
+                "author" => info.author = value,
+                "description" => info.description = value,
+                "priority" => {
+                    info.priority = value
+                        .parse()
+                        .with_context(|| format!("{}: invalid priority {}", id, value))?
+                }
+                key => log::warn!("[Mods] {}: unknown manifest key {}", id, key),
+            }
+        }
+
+        Ok(info)
+    }
+
+    /// Read the info of a mod directory or archive.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let id = if path.is_dir() {
+            path.file_name()
+        } else {
+            path.file_stem()
+        }
+        .with_context(|| format!("{} is not a mod", path.display()))?
+        .to_string_lossy()
+        .into_owned();
+
+        let manifest = if path.is_dir() {
+            let manifest = path.join(MANIFEST);
+            manifest
+                .is_file()
+                .then(|| std::fs::read_to_string(manifest))
+                .transpose()?
+        } else {
+            Pak::open(path)?
+                .read(MANIFEST)
+                .transpose()?
+                .map(String::from_utf8)
+                .transpose()?
+        };
+
+        Self::parse(&id, path.to_path_buf(), manifest.as_deref())
+    }
+}
+
+/// Enumerate the mods of a directory, in load order.
+/// A mod with a higher priority is loaded later and overrides the others, mods with the same priority
+/// are ordered by their id.
+///
+/// # Arguments
+///
+/// * `dir` - The mods directory, every subdirectory and `.pak` archive in it is a mod.
+/// * `disabled` - The ids of the mods to mark disabled.
+///
+/// # Returns
+///
+/// The installed mods, an empty list if the directory does not exist.
+pub fn discover(dir: impl AsRef<Path>, disabled: &[String]) -> anyhow::Result<Vec<ModInfo>> {
+    let dir = dir.as_ref();
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut mods = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_dir() && path.extension().is_none_or(|ext| ext != "pak") {
+            continue;
+        }
+
+        match ModInfo::load(&path) {
+            Ok(mut info) => {
+                info.enabled = !disabled.contains(&info.id);
+                mods.push(info);
+            }
+            Err(err) => log::warn!("[Mods] Skipping {}: {:#}", path.display(), err),
+        }
+    }
+    mods.sort_by(|a, b| a.priority.cmp(&b.priority).then_with(|| a.id.cmp(&b.id)));
+
+    Ok(mods)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let info = ModInfo::parse(
+            "hd",
+            PathBuf::from("mods/hd"),
+            Some("# HD pack\nname = HD Textures\nversion=1.2\n\npriority = -3\nextra = 1"),
+        )
+        .unwrap();
+        assert_eq!(info.name, "HD Textures");
+        assert_eq!(info.version, "1.2");
+        assert_eq!(info.priority, -3);
+        assert!(info.enabled);
+
+        let info = ModInfo::parse("plain", PathBuf::from("mods/plain"), None).unwrap();
+        assert_eq!(info.name, "plain");
+        assert_eq!(info.priority, 0);
+
+        assert!(ModInfo::parse("bad", PathBuf::new(), Some("priority = high")).is_err());
+        assert!(ModInfo::parse("bad", PathBuf::new(), Some("name")).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use super::config::FramePacing;
+use crate::ecs::traits::Component;
+use instant::{Duration, Instant};
+
+/// The information about the display the window is on.
+/// The renderer keeps it on its own entity and updates it when the window moves to another monitor.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct DisplayInfo {
+    /// The refresh rate of the monitor in Hz, `None` if the platform does not report it.
+    pub refresh_rate: Option<f32>,
+    /// The frame rate the renderer paces to, `None` if it is unlimited or paced by vsync.
+    pub target_frame_rate: Option<f32>,
+    pub vsync: bool,
+}
+
+impl Component for DisplayInfo {}
+
+/// Sent when the detected refresh rate has changed, e.g. the window was moved to another monitor.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RefreshRateChanged {
+    pub refresh_rate: Option<f32>,
+}
+
+/// Get the frame rate to pace to.
+///
+/// # Arguments
+///
+/// * `pacing` - The pacing setting.
+/// * `refresh_rate` - The refresh rate of the display in Hz.
+/// * `vsync` - Whether the presentation is synced to the display.
+///
+/// # Returns
+///
+/// The target frame rate, `None` if the frames should not be paced by the engine.
+pub fn target_frame_rate(
+    pacing: FramePacing,
+    refresh_rate: Option<f32>,
+    vsync: bool,
+) -> Option<f32> {
+    // With vsync the presentation already waits for the display
+    if vsync {
+        return None;
+    }
+
+    match pacing {
+        FramePacing::Unlimited => None,
+        FramePacing::Display => refresh_rate,
+        FramePacing::DisplayDivisor(divisor) => {
+            refresh_rate.map(|rate| rate / divisor.max(1) as f32)
+        }
+        FramePacing::Fixed(rate) => Some(rate),
+    }
+    .filter(|rate| *rate > 0.0)
+}
+
+/// Schedules the frames at a fixed interval without drifting.
+#[derive(Debug, Clone, Default)]
+pub struct FramePacer {
+    interval: Option<Duration>,
+    next: Option<Instant>,
+}
+
+impl FramePacer {
+    pub fn new(frame_rate: Option<f32>) -> Self {
+        let mut pacer = Self::default();
+        pacer.set_frame_rate(frame_rate);
+        pacer
+    }
+
+    pub fn set_frame_rate(&mut self, frame_rate: Option<f32>) {
+        self.interval = frame_rate
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| Duration::from_secs_f64(1.0 / rate as f64));
+        self.next = None;
+    }
+
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    /// Get the time the next frame should start at, `None` if it can start immediately.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.next
+    }
+
+    /// Schedule the next frame after a frame has started.
+    /// The deadlines advance by whole intervals, so a late frame does not shift the following ones,
+    /// unless it is late by more than an interval, then the schedule restarts from now.
+    pub fn frame_started(&mut self, now: Instant) {
+        let Some(interval) = self.interval else {
+            self.next = None;
+            return;
+        };
+
+        let next = match self.next {
+            Some(next) if now < next + interval => next + interval,
+            _ => now + interval,
+        };
+        self.next = Some(next);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_frame_rate() {
+        assert_eq!(
+            target_frame_rate(FramePacing::Display, Some(144.0), false),
+            Some(144.0)
+        );
+        assert_eq!(
+            target_frame_rate(FramePacing::DisplayDivisor(2), Some(144.0), false),
+            Some(72.0)
+        );
+        assert_eq!(target_frame_rate(FramePacing::Display, None, false), None);
+        assert_eq!(
+            target_frame_rate(FramePacing::Fixed(60.0), Some(144.0), true),
+            None
+        );
+    }
+
+    #[test]
+    fn test_pacer_does_not_drift() {
+        let mut pacer = FramePacer::new(Some(100.0));
+        let start = Instant::now();
+        let interval = Duration::from_millis(10);
+
+        pacer.frame_started(start);
+        assert_eq!(pacer.deadline(), Some(start + interval));
+
+        // A slightly late frame keeps the schedule
+        pacer.frame_started(start + Duration::from_millis(12));
+        assert_eq!(pacer.deadline(), Some(start + interval * 2));
+
+        // A frame late by more than an interval restarts it
+        let late = start + Duration::from_millis(50);
+        pacer.frame_started(late);
+        assert_eq!(pacer.deadline(), Some(late + interval));
+    }
+}
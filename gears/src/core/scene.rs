@@ -0,0 +1,840 @@
+use super::{cook, format};
+use crate::ecs::components::{self, Light, Name, Pos3, Scale};
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use crate::physics::body::RigidBody;
+use cgmath::{Deg, Euler, Quaternion, Rad, Vector3};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"GSCN";
+/// Version 2 added the size of the rect lights and the registered components.
+const VERSION: u32 = 2;
+
+/// Reads the little endian numbers of a cooked scene.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| anyhow::anyhow!("The cooked scene is truncated"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn f32(&mut self) -> anyhow::Result<f32> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn vec3(&mut self) -> anyhow::Result<[f32; 3]> {
+        Ok([self.f32()?, self.f32()?, self.f32()?])
+    }
+
+    fn vec2(&mut self) -> anyhow::Result<[f32; 2]> {
+        Ok([self.f32()?, self.f32()?])
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+}
+
+/// A component saved in scenes under the `components` of an entity, with serde.
+/// The position, name, scale, model and light are saved by the scene itself,
+/// other components are saved once registered with `Manager::register_scene_component`.
+///
+/// ```ignore
+/// #[derive(Serialize, Deserialize)]
+/// struct Health(f32);
+///
+/// impl SceneComponent for Health {
+///     const NAME: &'static str = "Health";
+/// }
+/// ```
+pub trait SceneComponent: Component + Serialize + DeserializeOwned {
+    /// The name of the component in the scene files.
+    const NAME: &'static str;
+}
+
+impl SceneComponent for RigidBody {
+    const NAME: &'static str = "RigidBody";
+}
+
+/// A component type registered for the scenes.
+#[derive(Debug, Copy, Clone)]
+struct SceneComponentType {
+    name: &'static str,
+    save: fn(&ecs::Manager, Entity) -> Option<serde_json::Value>,
+    load: fn(&ecs::Manager, Entity, &serde_json::Value) -> anyhow::Result<()>,
+}
+
+/// The components saved in scenes besides the built-in ones, a resource of the ECS.
+#[derive(Debug)]
+struct SceneRegistry {
+    types: Vec<SceneComponentType>,
+}
+
+impl Default for SceneRegistry {
+    fn default() -> Self {
+        let mut registry = Self { types: Vec::new() };
+        registry.register::<RigidBody>();
+        registry
+    }
+}
+
+impl SceneRegistry {
+    fn register<T: SceneComponent>(&mut self) {
+        self.types.retain(|ty| ty.name != T::NAME);
+        self.types.push(SceneComponentType {
+            name: T::NAME,
+            save: |ecs, entity| {
+                let component = ecs.get_component_from_entity::<T>(entity)?;
+                let value = serde_json::to_value(&*component.read().unwrap());
+                value
+                    .map_err(|e| log::warn!("[Scene] Not saving the component {}: {}", T::NAME, e))
+                    .ok()
+            },
+            load: |ecs, entity, value| {
+                ecs.add_component_to_entity(entity, T::deserialize(value)?);
+                Ok(())
+            },
+        });
+    }
+
+    fn types(ecs: &ecs::Manager) -> Vec<SceneComponentType> {
+        match ecs.get_resource::<Self>() {
+            Some(registry) => registry.read().unwrap().types.clone(),
+            None => Self::default().types,
+        }
+    }
+}
+
+impl ecs::Manager {
+    /// Save a component type in the scenes, under its `SceneComponent::NAME`.
+    pub fn register_scene_component<T: SceneComponent>(&self) {
+        if !self.has_resource::<SceneRegistry>() {
+            self.insert_resource(SceneRegistry::default());
+        }
+        let registry = self.get_resource::<SceneRegistry>().unwrap();
+        registry.write().unwrap().register::<T>();
+    }
+
+    /// Save the entities to a scene file, see `Scene::capture` and `Scene::save`.
+    pub fn save_scene(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        Scene::capture(self).save(path)
+    }
+
+    /// Load a scene and spawn its entities.
+    /// The scene is read from the VFS if it has the path, from the disk otherwise, like the saved scenes.
+    ///
+    /// # Returns
+    ///
+    /// The spawned entities, in the order of the scene.
+    pub fn load_scene(&self, path: &str) -> anyhow::Result<Vec<Entity>> {
+        let scene = if super::vfs::exists(path) {
+            Scene::load(path)?
+        } else {
+            Scene::parse(&std::fs::read_to_string(path)?)?
+        };
+        Ok(scene.spawn(self))
+    }
+}
+
+/// The kind of a scene light.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LightKind {
+    #[serde(alias = "point")]
+    Point = 0,
+    #[serde(alias = "ambient")]
+    Ambient,
+    #[serde(alias = "directional")]
+    Directional,
+    #[serde(alias = "rect")]
+    Rect,
+}
+
+/// A light of a scene entity, the missing fields keep their default.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneLight {
+    #[serde(rename = "type")]
+    pub kind: LightKind,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// The radius of a point or rect light.
+    pub radius: f32,
+    /// The direction of a directional or rect light.
+    pub direction: [f32; 3],
+    /// The width and height of a rect light.
+    pub size: [f32; 2],
+}
+
+impl Default for SceneLight {
+    fn default() -> Self {
+        Self {
+            kind: LightKind::Point,
+            color: [1.0; 3],
+            intensity: 1.0,
+            radius: 10.0,
+            direction: [0.0, -1.0, 0.0],
+            size: [1.0; 2],
+        }
+    }
+}
+
+impl SceneLight {
+    /// Get the scene light of a light component.
+    pub fn from_component(light: &Light) -> Self {
+        let default = Self::default();
+        match *light {
+            Light::Point { radius, intensity } => Self {
+                radius,
+                intensity,
+                ..default
+            },
+            Light::PointColoured {
+                radius,
+                color,
+                intensity,
+            } => Self {
+                radius,
+                color,
+                intensity,
+                ..default
+            },
+            Light::Ambient { intensity } => Self {
+                kind: LightKind::Ambient,
+                intensity,
+                ..default
+            },
+            Light::AmbientColoured { color, intensity } => Self {
+                kind: LightKind::Ambient,
+                color,
+                intensity,
+                ..default
+            },
+            Light::Directional {
+                direction,
+                intensity,
+            } => Self {
+                kind: LightKind::Directional,
+                direction,
+                intensity,
+                ..default
+            },
+            Light::DirectionalColoured {
+                direction,
+                color,
+                intensity,
+            } => Self {
+                kind: LightKind::Directional,
+                direction,
+                color,
+                intensity,
+                ..default
+            },
+            Light::Rect {
+                width,
+                height,
+                direction,
+                color,
+                intensity,
+                radius,
+            } => Self {
+                kind: LightKind::Rect,
+                color,
+                intensity,
+                radius,
+                direction,
+                size: [width, height],
+            },
+        }
+    }
+
+    /// Get the light component.
+    pub fn component(&self) -> Light {
+        match self.kind {
+            LightKind::Point => Light::PointColoured {
+                radius: self.radius,
+                color: self.color,
+                intensity: self.intensity,
+            },
+            LightKind::Ambient => Light::AmbientColoured {
+                color: self.color,
+                intensity: self.intensity,
+            },
+            LightKind::Directional => Light::DirectionalColoured {
+                direction: self.direction,
+                color: self.color,
+                intensity: self.intensity,
+            },
+            LightKind::Rect => Light::Rect {
+                width: self.size[0],
+                height: self.size[1],
+                direction: self.direction,
+                color: self.color,
+                intensity: self.intensity,
+                radius: self.radius,
+            },
+        }
+    }
+}
+
+/// Read a scale of three axes, or a single number for a uniform scale.
+fn scale<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<[f32; 3]>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Scale {
+        Uniform(f32),
+        NonUniform([f32; 3]),
+    }
+
+    Ok(
+        Option::<Scale>::deserialize(deserializer)?.map(|scale| match scale {
+            Scale::Uniform(scale) => [scale; 3],
+            Scale::NonUniform(scale) => scale,
+        }),
+    )
+}
+
+/// An entity of a scene.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SceneEntity {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub position: [f32; 3],
+    /// The rotation in degrees around the x, y and z axes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<[f32; 3]>,
+    #[serde(
+        default,
+        deserialize_with = "scale",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub scale: Option<[f32; 3]>,
+    /// The path of the model in the VFS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Whether the model is static.
+    #[serde(rename = "static", default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_static: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub light: Option<SceneLight>,
+    /// The registered components by their name, see `SceneComponent`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub components: BTreeMap<String, serde_json::Value>,
+}
+
+impl SceneEntity {
+    /// Read an entity of the ECS.
+    ///
+    /// # Returns
+    ///
+    /// The scene entity, `None` if the entity has nothing saved in scenes.
+    fn capture(ecs: &ecs::Manager, entity: Entity, types: &[SceneComponentType]) -> Option<Self> {
+        let pos = ecs.get_component_from_entity::<Pos3>(entity);
+        let (position, rotation) = match &pos {
+            Some(pos) => {
+                let pos = pos.read().unwrap();
+                let rotation = pos.rot.map(|rot| {
+                    let euler = Euler::<Rad<f32>>::from(rot);
+                    [
+                        Deg::from(euler.x).0,
+                        Deg::from(euler.y).0,
+                        Deg::from(euler.z).0,
+                    ]
+                });
+                (pos.pos.into(), rotation)
+            }
+            None => ([0.0; 3], None),
+        };
+        let scale = ecs.get_component_from_entity::<Scale>(entity).map(|scale| {
+            match *scale.read().unwrap() {
+                Scale::Uniform(scale) => [scale; 3],
+                Scale::NonUniform { x, y, z } => [x, y, z],
+            }
+        });
+        let model = ecs
+            .get_component_from_entity::<components::Model<'static>>(entity)
+            .map(|model| match &*model.read().unwrap() {
+                components::Model::Static { obj_path } => (obj_path.to_string(), true),
+                components::Model::Dynamic { obj_path } => (obj_path.to_string(), false),
+            });
+
+        let scene_entity = Self {
+            name: ecs
+                .get_component_from_entity::<Name>(entity)
+                .map(|name| name.read().unwrap().0.to_string()),
+            position,
+            rotation,
+            scale,
+            is_static: model.as_ref().is_some_and(|(_, is_static)| *is_static),
+            model: model.map(|(path, _)| path),
+            light: ecs
+                .get_component_from_entity::<Light>(entity)
+                .map(|light| SceneLight::from_component(&light.read().unwrap())),
+            components: types
+                .iter()
+                .filter_map(|ty| Some((ty.name.to_string(), (ty.save)(ecs, entity)?)))
+                .collect(),
+        };
+        let is_empty = pos.is_none()
+            && scene_entity.name.is_none()
+            && scene_entity.scale.is_none()
+            && scene_entity.model.is_none()
+            && scene_entity.light.is_none()
+            && scene_entity.components.is_empty();
+        (!is_empty).then_some(scene_entity)
+    }
+}
+
+/// A scene, the entities of a level.
+///
+/// Scenes are written in JSON or RON:
+///
+/// ```json
+/// { "entities": [{ "name": "Ball", "position": [0, 1, 0], "model": "res/models/sphere/sphere.obj" }] }
+/// ```
+///
+/// ```ron
+/// Scene(entities: [(name: "Sun", light: (type: Directional, direction: (0, -1, 1)))])
+/// ```
+///
+/// `gears-cook` converts them to a binary format which release builds load instead.
+/// The registered components are under `components`, e.g. `"components": { "RigidBody": { "mass": 2 } }`.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Scene {
+    pub entities: Vec<SceneEntity>,
+}
+
+impl Scene {
+    /// Parse a JSON or RON scene.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        format::from_json_or_ron(text)
+    }
+
+    /// Read the entities of the ECS, sorted by their id.
+    /// The entities without any component saved in scenes are left out.
+    pub fn capture(ecs: &ecs::Manager) -> Self {
+        let types = SceneRegistry::types(ecs);
+        let mut entities = ecs.iter_entities().collect::<Vec<_>>();
+        entities.sort_by_key(Entity::id);
+
+        Self {
+            entities: entities
+                .into_iter()
+                .filter_map(|entity| SceneEntity::capture(ecs, entity, &types))
+                .collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        format::to_json(self)
+    }
+
+    pub fn to_ron(&self) -> anyhow::Result<String> {
+        format::to_ron(self)
+    }
+
+    /// Write the scene to a file, in RON if its extension is `.ron`, in JSON otherwise.
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let is_ron = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("ron"));
+        let text = if is_ron {
+            self.to_ron()?
+        } else {
+            self.to_json()?
+        };
+        std::fs::write(path, text)?;
+        log::info!(
+            "[Scene] Saved {} entities to {}",
+            self.entities.len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Load a scene from the VFS.
+    /// Release builds load the cooked scene when the cook manifest lists it and its hash matches,
+    /// debug builds always parse the source so edits show up without cooking.
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        if cfg!(not(debug_assertions)) {
+            match cook::load_cooked(path) {
+                Some(Ok(data)) => return Self::from_bytes(&data),
+                Some(Err(e)) => log::warn!("[Scene] Ignoring the cooked {}: {}", path, e),
+                None => {}
+            }
+        }
+
+        Self::parse(&super::vfs::read_to_string(path)?)
+    }
+
+    /// Encode the scene in the cooked binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let put_f32s = |out: &mut Vec<u8>, values: &[f32]| {
+            for value in values {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+        };
+        let put_str = |out: &mut Vec<u8>, string: &str| {
+            out.extend_from_slice(&(string.len() as u32).to_le_bytes());
+            out.extend_from_slice(string.as_bytes());
+        };
+
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.entities.len() as u32).to_le_bytes());
+        for entity in &self.entities {
+            let flags = entity.name.is_some() as u8
+                | (entity.rotation.is_some() as u8) << 1
+                | (entity.scale.is_some() as u8) << 2
+                | (entity.model.is_some() as u8) << 3
+                | (entity.is_static as u8) << 4
+                | (entity.light.is_some() as u8) << 5
+                | (!entity.components.is_empty() as u8) << 6;
+            out.push(flags);
+            put_f32s(&mut out, &entity.position);
+
+            if let Some(name) = &entity.name {
+                put_str(&mut out, name);
+            }
+            if let Some(rotation) = &entity.rotation {
+                put_f32s(&mut out, rotation);
+            }
+            if let Some(scale) = &entity.scale {
+                put_f32s(&mut out, scale);
+            }
+            if let Some(model) = &entity.model {
+                put_str(&mut out, model);
+            }
+            if let Some(light) = &entity.light {
+                out.push(light.kind as u8);
+                put_f32s(&mut out, &light.color);
+                put_f32s(&mut out, &[light.intensity, light.radius]);
+                put_f32s(&mut out, &light.direction);
+                put_f32s(&mut out, &light.size);
+            }
+            if !entity.components.is_empty() {
+                out.extend_from_slice(&(entity.components.len() as u32).to_le_bytes());
+                for (name, value) in &entity.components {
+                    put_str(&mut out, name);
+                    put_str(&mut out, &value.to_string());
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Decode a cooked scene.
+    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        let mut reader = Reader { data, pos: 0 };
+        if reader.take(4)? != MAGIC {
+            anyhow::bail!("Not a cooked scene");
+        }
+        let version = reader.u32()?;
+        if version == 0 || version > VERSION {
+            anyhow::bail!("Unsupported cooked scene version {}", version);
+        }
+
+        let count = reader.u32()?;
+        let mut entities = Vec::new();
+        for _ in 0..count {
+            let flags = reader.take(1)?[0];
+            let position = reader.vec3()?;
+            let mut entity = SceneEntity {
+                position,
+                is_static: flags & 1 << 4 != 0,
+                ..Default::default()
+            };
+
+            if flags & 1 != 0 {
+                entity.name = Some(reader.string()?);
+            }
+            if flags & 1 << 1 != 0 {
+                entity.rotation = Some(reader.vec3()?);
+            }
+            if flags & 1 << 2 != 0 {
+                entity.scale = Some(reader.vec3()?);
+            }
+            if flags & 1 << 3 != 0 {
+                entity.model = Some(reader.string()?);
+            }
+            if flags & 1 << 5 != 0 {
+                let kind = match reader.take(1)?[0] {
+                    0 => LightKind::Point,
+                    1 => LightKind::Ambient,
+                    2 => LightKind::Directional,
+                    3 => LightKind::Rect,
+                    kind => anyhow::bail!("Unknown light kind {}", kind),
+                };
+                entity.light = Some(SceneLight {
+                    kind,
+                    color: reader.vec3()?,
+                    intensity: reader.f32()?,
+                    radius: reader.f32()?,
+                    direction: reader.vec3()?,
+                    size: match version {
+                        1 => [1.0; 2],
+                        _ => reader.vec2()?,
+                    },
+                });
+            }
+            if flags & 1 << 6 != 0 {
+                let count = reader.u32()?;
+                for _ in 0..count {
+                    let name = reader.string()?;
+                    let value = serde_json::from_str(&reader.string()?)?;
+                    entity.components.insert(name, value);
+                }
+            }
+            entities.push(entity);
+        }
+
+        Ok(Self { entities })
+    }
+
+    /// Spawn the entities of the scene.
+    /// The components which are not registered, or fail to load, are skipped with a warning.
+    ///
+    /// # Returns
+    ///
+    /// The spawned entities, in the order of the scene.
+    pub fn spawn(&self, ecs: &ecs::Manager) -> Vec<Entity> {
+        let types = SceneRegistry::types(ecs);
+        self.entities
+            .iter()
+            .map(|scene_entity| {
+                let entity = ecs.create_entity();
+                let [x, y, z] = scene_entity.position;
+                let pos = match scene_entity.rotation {
+                    Some([rx, ry, rz]) => Pos3::with_rot(
+                        Vector3::new(x, y, z),
+                        Quaternion::from(Euler::new(Deg(rx), Deg(ry), Deg(rz))),
+                    ),
+                    None => Pos3::new(Vector3::new(x, y, z)),
+                };
+                ecs.add_component_to_entity(entity, pos);
+
+                // The components borrow their strings for the lifetime of the program
+                let name = scene_entity.name.clone().or_else(|| {
+                    let model = scene_entity.model.as_deref()?;
+                    let stem = std::path::Path::new(model).file_stem()?;
+                    Some(stem.to_string_lossy().into_owned())
+                });
+                if let Some(name) = name {
+                    ecs.add_component_to_entity(entity, Name(name.leak()));
+                }
+                if let Some([sx, sy, sz]) = scene_entity.scale {
+                    let scale = if sx == sy && sy == sz {
+                        Scale::Uniform(sx)
+                    } else {
+                        Scale::NonUniform {
+                            x: sx,
+                            y: sy,
+                            z: sz,
+                        }
+                    };
+                    ecs.add_component_to_entity(entity, scale);
+                }
+                if let Some(model) = &scene_entity.model {
+                    let obj_path: &'static str = model.clone().leak();
+                    let model = if scene_entity.is_static {
+                        components::Model::Static { obj_path }
+                    } else {
+                        components::Model::Dynamic { obj_path }
+                    };
+                    ecs.add_component_to_entity(entity, model);
+                }
+                if let Some(light) = &scene_entity.light {
+                    ecs.add_component_to_entity(entity, light.component());
+                }
+                for (name, value) in &scene_entity.components {
+                    let Some(ty) = types.iter().find(|ty| ty.name == name.as_str()) else {
+                        log::warn!("[Scene] The component {} is not registered", name);
+                        continue;
+                    };
+                    if let Err(e) = (ty.load)(ecs, entity, value) {
+                        log::warn!("[Scene] Skipping the component {}: {:#}", name, e);
+                    }
+                }
+
+                entity
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{
+        "entities": [
+            { "name": "Ball", "position": [0, 1, -2.5], "scale": 2, "model": "res/models/sphere/sphere.obj" },
+            { "name": "Sun", "light": { "type": "directional", "direction": [0, -1, 1], "intensity": 0.5 } }
+        ]
+    }"#;
+
+    const RON: &str = r#"Scene(
+        entities: [
+            // The ball
+            (name: "Ball", position: (0, 1, -2.5), scale: Some(2), model: Some("res/models/sphere/sphere.obj")),
+            (name: "Sun", light: (type: Directional, direction: (0, -1, 1), intensity: 0.5)),
+        ],
+    )"#;
+
+    #[test]
+    fn test_parse_json_and_ron() {
+        let scene = Scene::parse(JSON).unwrap();
+        assert_eq!(scene, Scene::parse(RON).unwrap());
+
+        assert_eq!(scene.entities.len(), 2);
+        assert_eq!(scene.entities[0].position, [0.0, 1.0, -2.5]);
+        assert_eq!(scene.entities[0].scale, Some([2.0; 3]));
+        let light = scene.entities[1].light.unwrap();
+        assert_eq!(light.kind, LightKind::Directional);
+        assert_eq!(light.direction, [0.0, -1.0, 1.0]);
+        assert_eq!(light.intensity, 0.5);
+
+        let error = Scene::parse("{ \"entities\": [\n{ \"position\": [0, 1] }\n] }").unwrap_err();
+        assert!(format!("{:#}", error).contains("line 2"), "{:#}", error);
+        assert!(Scene::parse("Scene(entities: [(light: (type: Spot))])").is_err());
+    }
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let mut scene = Scene::parse(JSON).unwrap();
+        scene.entities[0].rotation = Some([0.0, 90.0, 0.0]);
+        scene.entities[0].is_static = true;
+
+        let bytes = scene.to_bytes();
+        assert_eq!(Scene::from_bytes(&bytes).unwrap(), scene);
+        assert!(Scene::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        let ecs = ecs::Manager::default();
+        let entities = scene.spawn(&ecs);
+        assert_eq!(entities.len(), 2);
+        let name = ecs.get_component_from_entity::<Name>(entities[0]).unwrap();
+        assert_eq!(name.read().unwrap().0, "Ball");
+        assert!(ecs
+            .get_component_from_entity::<Light>(entities[1])
+            .is_some());
+    }
+
+    #[test]
+    fn test_malformed_scenes() {
+        // Every truncated or corrupted scene is an error, never a panic
+        let scene = Scene::parse(JSON).unwrap();
+        let bytes = scene.to_bytes();
+        for text in [JSON, RON] {
+            for end in (0..text.len()).filter(|end| text.is_char_boundary(*end)) {
+                let _ = Scene::parse(&text[..end]);
+            }
+        }
+        for i in 0..bytes.len() {
+            assert!(Scene::from_bytes(&bytes[..i]).is_err());
+            for bit in 0..8 {
+                let mut corrupted = bytes.clone();
+                corrupted[i] ^= 1 << bit;
+                let _ = Scene::from_bytes(&corrupted);
+            }
+        }
+    }
+
+    #[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+    struct Health(f32);
+
+    impl Component for Health {}
+
+    impl SceneComponent for Health {
+        const NAME: &'static str = "Health";
+    }
+
+    #[test]
+    fn test_capture_and_spawn() {
+        let ecs = ecs::Manager::default();
+        ecs.register_scene_component::<Health>();
+        let crate_entity = ecs.spawn_bundle((
+            Name("Crate"),
+            Pos3::with_rot(
+                Vector3::new(1.0, 2.0, 3.0),
+                Quaternion::from(Euler::new(Deg(0.0), Deg(90.0), Deg(0.0))),
+            ),
+            Scale::NonUniform {
+                x: 1.0,
+                y: 2.0,
+                z: 1.0,
+            },
+            components::Model::Static {
+                obj_path: "res/models/cube/cube.obj",
+            },
+            RigidBody::new(2.5).with_restitution(0.5),
+            Health(75.0),
+        ));
+        ecs.spawn_bundle((
+            Pos3::default(),
+            Light::Rect {
+                width: 2.0,
+                height: 0.5,
+                direction: [0.0, -1.0, 0.0],
+                color: [1.0, 0.5, 0.25],
+                intensity: 3.0,
+                radius: 8.0,
+            },
+        ));
+        // Nothing of it is saved
+        ecs.create_entity();
+
+        let scene = Scene::capture(&ecs);
+        assert_eq!(scene.entities.len(), 2);
+        // The f32 components are written in their shortest form, so compare the parsed scenes
+        let saved = Scene::parse(&scene.to_json().unwrap()).unwrap();
+        let ron = scene.to_ron().unwrap();
+        assert_eq!(Scene::parse(&ron).unwrap(), saved);
+        assert_eq!(Scene::from_bytes(&saved.to_bytes()).unwrap(), saved);
+        assert!(ron.contains("type: Rect"), "{}", ron);
+
+        let loaded = ecs::Manager::default();
+        let entities = scene.spawn(&loaded);
+        // Health is not registered in the new world
+        assert!(!loaded.has_component::<Health>(entities[0]));
+        loaded.register_scene_component::<Health>();
+        let entities = scene.spawn(&loaded);
+
+        let health = loaded.get_component_from_entity::<Health>(entities[0]);
+        assert_eq!(*health.unwrap().read().unwrap(), Health(75.0));
+        let body = loaded.get_component_from_entity::<RigidBody>(entities[0]);
+        let expected = ecs.get_component_from_entity::<RigidBody>(crate_entity);
+        assert_eq!(
+            *body.unwrap().read().unwrap(),
+            *expected.unwrap().read().unwrap()
+        );
+        let pos = loaded
+            .get_component_from_entity::<Pos3>(entities[0])
+            .unwrap();
+        let rot = pos.read().unwrap().rot.unwrap();
+        let expected = Quaternion::from(Euler::new(Deg(0.0), Deg(90.0), Deg(0.0)));
+        assert!((rot.s - expected.s).abs() < 1e-5 && (rot.v.y - expected.v.y).abs() < 1e-5);
+        assert!(matches!(
+            *loaded.get_component_from_entity::<Light>(entities[1]).unwrap().read().unwrap(),
+            Light::Rect { width, height, .. } if width == 2.0 && height == 0.5
+        ));
+    }
+}
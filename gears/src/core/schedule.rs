@@ -0,0 +1,833 @@
+use super::telemetry;
+use super::Dt;
+use crate::ecs;
+use crate::ecs::traits::Component;
+use instant::{Duration, Instant};
+use std::future::Future;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+/// A system, like the `update_*` functions of the engine.
+pub type SystemFn = fn(&ecs::Manager, Dt);
+
+/// An async system, like the update functions of `update_loop_async`.
+/// It gets the manager unlocked, so it can await between its accesses.
+pub type AsyncSystemFn = Arc<
+    dyn Fn(Arc<Mutex<ecs::Manager>>, Dt) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync,
+>;
+
+/// The most fixed steps run on a single update. The rest of a long frame is dropped,
+/// so a slow frame does not make the next ones slower.
+const MAX_FIXED_STEPS: u32 = 8;
+
+/// The stages of a frame, the systems of a stage run after all the systems of the previous stages.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stage {
+    First,
+    PreUpdate,
+    /// Runs zero or more times per update with the constant delta time of the `FixedTime`,
+    /// e.g. for the physics.
+    FixedUpdate,
+    #[default]
+    Update,
+    PostUpdate,
+    Last,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [
+        Stage::First,
+        Stage::PreUpdate,
+        Stage::FixedUpdate,
+        Stage::Update,
+        Stage::PostUpdate,
+        Stage::Last,
+    ];
+}
+
+/// The fixed timestep of the schedule. The time of the updates is accumulated
+/// and the `FixedUpdate` stage runs once for each full step in it.
+///
+/// The schedule keeps it as a resource of the manager, so the render-rate systems
+/// can interpolate between the last two fixed steps with `alpha`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FixedTime {
+    /// The delta time of a fixed step.
+    pub step: Dt,
+    accumulator: Dt,
+    /// The fixed steps run on the latest update.
+    pub steps: u32,
+    /// The time left in the accumulator as a fraction of a step.
+    pub alpha: f32,
+}
+
+impl Default for FixedTime {
+    fn default() -> Self {
+        Self::from_hz(60)
+    }
+}
+
+impl FixedTime {
+    /// Create a fixed timestep running `hz` steps per second.
+    pub fn from_hz(hz: u32) -> Self {
+        Self {
+            step: Dt::from_secs_f64(1.0 / hz.max(1) as f64),
+            accumulator: Dt::ZERO,
+            steps: 0,
+            alpha: 0.0,
+        }
+    }
+
+    /// Accumulate the delta time of an update.
+    ///
+    /// # Returns
+    ///
+    /// The fixed steps to run, at most `MAX_FIXED_STEPS`.
+    pub fn advance(&mut self, dt: Dt) -> u32 {
+        self.accumulator += dt;
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        if steps > MAX_FIXED_STEPS {
+            log::debug!(
+                "[Schedule] Dropped {} fixed steps of a long update",
+                steps - MAX_FIXED_STEPS
+            );
+            steps = MAX_FIXED_STEPS;
+        }
+
+        self.steps = steps;
+        self.alpha = self.accumulator.as_secs_f32() / self.step.as_secs_f32();
+        steps
+    }
+}
+
+/// When a scheduled system runs.
+#[derive(Clone, Default)]
+pub enum RunCriteria {
+    /// On every update.
+    #[default]
+    Always,
+    /// When at least this much time passed since the last run.
+    Every(Duration),
+    /// When an event is pending, the system is expected to drain it.
+    OnEvent {
+        name: &'static str,
+        pending: fn(&ecs::Manager) -> bool,
+    },
+    /// When the predicate returns true.
+    If(Arc<dyn Fn(&ecs::Manager) -> bool + Send + Sync>),
+}
+
+impl std::fmt::Debug for RunCriteria {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.describe())
+    }
+}
+
+impl RunCriteria {
+    /// Get a short description of the criteria for the tools.
+    pub fn describe(&self) -> String {
+        match self {
+            RunCriteria::Always => "every update".to_string(),
+            RunCriteria::Every(interval) => format!("every {} ms", interval.as_millis()),
+            RunCriteria::OnEvent { name, .. } => format!("on {}", name),
+            RunCriteria::If(_) => "if the predicate holds".to_string(),
+        }
+    }
+
+    /// Check if a system should run.
+    ///
+    /// # Arguments
+    ///
+    /// * `ecs` - The entity component system manager.
+    /// * `elapsed` - The time since the system last ran.
+    fn should_run(&self, ecs: &ecs::Manager, elapsed: Duration) -> bool {
+        match self {
+            RunCriteria::Always => true,
+            RunCriteria::Every(interval) => elapsed >= *interval,
+            RunCriteria::OnEvent { pending, .. } => pending(ecs),
+            RunCriteria::If(predicate) => predicate(ecs),
+        }
+    }
+}
+
+/// A named system with the metadata the schedule orders it by.
+/// The component access is only declared, so the tools can show which systems touch what.
+#[derive(Debug, Clone)]
+pub struct System {
+    name: String,
+    stage: Stage,
+    run: SystemFn,
+    after: Vec<String>,
+    reads: Vec<&'static str>,
+    writes: Vec<&'static str>,
+    criteria: RunCriteria,
+    /// The time since the system last ran.
+    elapsed: Duration,
+}
+
+impl System {
+    pub fn new(name: impl Into<String>, run: SystemFn) -> Self {
+        Self {
+            name: name.into(),
+            stage: Stage::default(),
+            run,
+            after: Vec::new(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            criteria: RunCriteria::Always,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn in_stage(mut self, stage: Stage) -> Self {
+        self.stage = stage;
+        self
+    }
+
+    /// Run the system after another system of the same or of an earlier stage.
+    /// The dependency is ignored while the other system is not scheduled.
+    pub fn after(mut self, system: impl Into<String>) -> Self {
+        self.after.push(system.into());
+        self
+    }
+
+    /// Declare that the system reads a component type.
+    pub fn reads<T: Component>(mut self) -> Self {
+        self.reads.push(std::any::type_name::<T>());
+        self
+    }
+
+    /// Declare that the system writes a component type.
+    pub fn writes<T: Component>(mut self) -> Self {
+        self.writes.push(std::any::type_name::<T>());
+        self
+    }
+
+    /// Set when the system runs, replacing the previous criteria.
+    /// A system which did not run on an update gets the time since its last run as the delta time.
+    pub fn with_criteria(mut self, criteria: RunCriteria) -> Self {
+        self.criteria = criteria;
+        self
+    }
+
+    /// Run the system when at least `interval` passed since its last run.
+    pub fn run_every(self, interval: Duration) -> Self {
+        self.with_criteria(RunCriteria::Every(interval))
+    }
+
+    /// Run the system when an event of a type is pending.
+    pub fn run_on_event<T: Send + Sync + 'static>(self) -> Self {
+        self.with_criteria(RunCriteria::OnEvent {
+            name: std::any::type_name::<T>(),
+            pending: ecs::Manager::has_events::<T>,
+        })
+    }
+
+    /// Run the system when a predicate returns true.
+    pub fn run_if(self, predicate: impl Fn(&ecs::Manager) -> bool + Send + Sync + 'static) -> Self {
+        self.with_criteria(RunCriteria::If(Arc::new(predicate)))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The metadata and the timings of a scheduled system.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemInfo {
+    pub name: String,
+    pub stage: Stage,
+    pub after: Vec<String>,
+    /// The type names of the components the system declared to read.
+    pub reads: Vec<&'static str>,
+    /// The type names of the components the system declared to write.
+    pub writes: Vec<&'static str>,
+    /// The description of when the system runs.
+    pub criteria: String,
+    /// The duration of the latest run.
+    pub duration: Duration,
+    /// The smoothed duration of the runs.
+    pub average: Duration,
+    pub runs: u64,
+    /// The updates the system did not run on because of its criteria.
+    pub skips: u64,
+}
+
+impl SystemInfo {
+    /// Check if the system accesses a component another system writes, so they can not run in parallel.
+    pub fn conflicts_with(&self, other: &SystemInfo) -> bool {
+        self.writes
+            .iter()
+            .any(|ty| other.reads.contains(ty) || other.writes.contains(ty))
+            || other.writes.iter().any(|ty| self.reads.contains(ty))
+    }
+}
+
+/// A named async system, run after the systems of the schedule.
+#[derive(Clone)]
+pub struct AsyncSystem {
+    name: String,
+    run: AsyncSystemFn,
+}
+
+impl std::fmt::Debug for AsyncSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncSystem")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AsyncSystem {
+    pub fn new<F>(name: impl Into<String>, run: F) -> Self
+    where
+        F: Fn(Arc<Mutex<ecs::Manager>>, Dt) -> Pin<Box<dyn Future<Output = ()> + Send>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        Self {
+            name: name.into(),
+            run: Arc::new(run),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Run the system once.
+    pub async fn run(&self, ecs: Arc<Mutex<ecs::Manager>>, dt: Dt) {
+        (self.run)(ecs, dt).await
+    }
+}
+
+/// Send it to change the systems of the running app, e.g. from a tool, a script or a scene.
+/// The commands are applied between two runs of the schedule, the errors are logged.
+#[derive(Debug, Clone)]
+pub enum ScheduleCommand {
+    Add(System),
+    AddAsync(AsyncSystem),
+    /// Remove the system or the async system with this name.
+    Remove(String),
+}
+
+/// A component the schedule copies the metadata of its systems into after each run.
+#[derive(Debug, Clone, Default)]
+pub struct ScheduleInfo {
+    /// The systems in the order they run.
+    pub systems: Vec<SystemInfo>,
+    /// The duration of the latest run of the whole schedule.
+    pub duration: Duration,
+}
+
+impl Component for ScheduleInfo {}
+
+/// Runs the systems of the app in the order of their stages and dependencies.
+/// The async systems run after them, in the order they were added.
+#[derive(Debug, Clone, Default)]
+pub struct Schedule {
+    systems: Vec<System>,
+    async_systems: Vec<AsyncSystem>,
+    /// The order the systems run in, as indices into `systems`.
+    order: Vec<usize>,
+    info: Vec<SystemInfo>,
+    fixed_time: FixedTime,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the steps per second of the `FixedUpdate` stage, 60 by default.
+    pub fn set_fixed_update_hz(&mut self, hz: u32) {
+        self.fixed_time = FixedTime::from_hz(hz);
+    }
+
+    pub fn fixed_time(&self) -> &FixedTime {
+        &self.fixed_time
+    }
+
+    /// Add a system to the schedule.
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - The system to add, its name must be unique.
+    ///
+    /// # Returns
+    ///
+    /// An error if the name is taken or the dependencies can not be ordered.
+    pub fn add(&mut self, system: System) -> anyhow::Result<()> {
+        if self.contains(&system.name) {
+            anyhow::bail!("A system named {} is already scheduled", system.name);
+        }
+
+        self.systems.push(system);
+        if let Err(e) = self.sort() {
+            self.systems.pop();
+            self.sort()?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Add an async system to the schedule.
+    ///
+    /// # Returns
+    ///
+    /// An error if the name is taken.
+    pub fn add_async(&mut self, system: AsyncSystem) -> anyhow::Result<()> {
+        if self.contains(&system.name) {
+            anyhow::bail!("A system named {} is already scheduled", system.name);
+        }
+
+        self.async_systems.push(system);
+        Ok(())
+    }
+
+    /// Remove a system or an async system.
+    /// The systems running after it keep their order.
+    ///
+    /// # Returns
+    ///
+    /// True if the system was scheduled.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let count = self.len();
+        self.systems.retain(|system| system.name != name);
+        self.async_systems.retain(|system| system.name != name);
+        if count == self.len() {
+            return false;
+        }
+
+        // Removing a system can not make the rest impossible to order
+        if let Err(e) = self.sort() {
+            log::error!("[Schedule] {}", e);
+        }
+        true
+    }
+
+    /// Check if a system or an async system has this name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.systems.iter().any(|system| system.name == name)
+            || self.async_systems.iter().any(|system| system.name == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.systems.len() + self.async_systems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the async systems in the order they run.
+    pub fn async_systems(&self) -> &[AsyncSystem] {
+        &self.async_systems
+    }
+
+    /// Apply the schedule commands sent since the last call.
+    pub fn apply_commands(&mut self, ecs: &ecs::Manager) {
+        for command in ecs.drain_events::<ScheduleCommand>() {
+            let (action, name) = match &command {
+                ScheduleCommand::Add(system) => ("Added", system.name.clone()),
+                ScheduleCommand::AddAsync(system) => ("Added", system.name.clone()),
+                ScheduleCommand::Remove(name) => ("Removed", name.clone()),
+            };
+            let result = match command {
+                ScheduleCommand::Add(system) => self.add(system),
+                ScheduleCommand::AddAsync(system) => self.add_async(system),
+                ScheduleCommand::Remove(name) if self.remove(&name) => Ok(()),
+                ScheduleCommand::Remove(name) => {
+                    Err(anyhow::anyhow!("No system named {} is scheduled", name))
+                }
+            };
+            match result {
+                Ok(()) => log::info!("[Schedule] {} {}", action, name),
+                Err(e) => log::warn!("[Schedule] {}", e),
+            }
+        }
+    }
+
+    /// Get the names of the systems in the order they run.
+    pub fn order(&self) -> Vec<&str> {
+        self.order
+            .iter()
+            .map(|i| self.systems[*i].name.as_str())
+            .collect()
+    }
+
+    /// Get the metadata and the timings of the systems in the order they run.
+    pub fn info(&self) -> &[SystemInfo] {
+        &self.info
+    }
+
+    /// Order the systems by stage, then by their dependencies, keeping the order they were added in otherwise.
+    fn sort(&mut self) -> anyhow::Result<()> {
+        let mut order = Vec::with_capacity(self.systems.len());
+
+        for stage in Stage::ALL {
+            let mut pending = (0..self.systems.len())
+                .filter(|i| self.systems[*i].stage == stage)
+                .collect::<Vec<_>>();
+
+            for i in &pending {
+                for dependency in &self.systems[*i].after {
+                    let other = self.systems.iter().find(|s| s.name == *dependency);
+                    if other.is_some_and(|other| other.stage > stage) {
+                        anyhow::bail!(
+                            "System {} runs after {}, which is in a later stage",
+                            self.systems[*i].name,
+                            dependency
+                        );
+                    }
+                    if other.is_some_and(|other| {
+                        (other.stage == Stage::FixedUpdate) != (stage == Stage::FixedUpdate)
+                    }) {
+                        anyhow::bail!(
+                            "System {} runs after {}, which runs at a different rate",
+                            self.systems[*i].name,
+                            dependency
+                        );
+                    }
+                }
+            }
+
+            while !pending.is_empty() {
+                let ready = pending.iter().position(|i| {
+                    self.systems[*i].after.iter().all(|dependency| {
+                        !pending
+                            .iter()
+                            .any(|other| self.systems[*other].name == *dependency)
+                    })
+                });
+                let Some(ready) = ready else {
+                    let names = pending
+                        .iter()
+                        .map(|i| self.systems[*i].name.as_str())
+                        .collect::<Vec<_>>();
+                    anyhow::bail!("The systems {} depend on each other", names.join(", "));
+                };
+                order.push(pending.remove(ready));
+            }
+        }
+
+        self.info = order
+            .iter()
+            .map(|i| {
+                let system = &self.systems[*i];
+                let previous = self.info.iter().find(|info| info.name == system.name);
+                SystemInfo {
+                    name: system.name.clone(),
+                    stage: system.stage,
+                    after: system.after.clone(),
+                    reads: system.reads.clone(),
+                    writes: system.writes.clone(),
+                    criteria: system.criteria.describe(),
+                    duration: previous.map_or(Duration::ZERO, |info| info.duration),
+                    average: previous.map_or(Duration::ZERO, |info| info.average),
+                    runs: previous.map_or(0, |info| info.runs),
+                    skips: previous.map_or(0, |info| info.skips),
+                }
+            })
+            .collect();
+        self.order = order;
+        Ok(())
+    }
+
+    /// Run the systems whose criteria are met in order and record their timings.
+    /// The `FixedUpdate` stage runs once for each fixed step in the accumulated time.
+    ///
+    /// # Arguments
+    ///
+    /// * `ecs` - The entity component system manager.
+    /// * `dt` - The delta time since the last update.
+    pub fn run(&mut self, ecs: &ecs::Manager, dt: Dt) {
+        let start = Instant::now();
+        let fixed_start = self
+            .order
+            .partition_point(|i| self.systems[*i].stage < Stage::FixedUpdate);
+        let fixed_end = self
+            .order
+            .partition_point(|i| self.systems[*i].stage <= Stage::FixedUpdate);
+
+        self.run_systems(ecs, 0..fixed_start, dt);
+        let steps = self.fixed_time.advance(dt);
+        // The fixed systems read the steps of this update, not of the previous one
+        match ecs.get_resource::<FixedTime>() {
+            Some(fixed_time) => *fixed_time.write().unwrap() = self.fixed_time,
+            None => ecs.insert_resource(self.fixed_time),
+        }
+        for _ in 0..steps {
+            self.run_systems(ecs, fixed_start..fixed_end, self.fixed_time.step);
+        }
+        self.run_systems(ecs, fixed_end..self.order.len(), dt);
+        let duration = start.elapsed();
+
+        for (_, schedule) in ecs.get_all_components_of_type::<ScheduleInfo>() {
+            let mut schedule = schedule.write().unwrap();
+            schedule.systems.clone_from(&self.info);
+            schedule.duration = duration;
+        }
+    }
+
+    /// Run a range of the systems in order.
+    fn run_systems(&mut self, ecs: &ecs::Manager, range: Range<usize>, dt: Dt) {
+        for (i, info) in self.order[range.clone()]
+            .iter()
+            .zip(self.info[range].iter_mut())
+        {
+            let system = &mut self.systems[*i];
+            system.elapsed += dt;
+            if !system.criteria.should_run(ecs, system.elapsed) {
+                info.skips += 1;
+                continue;
+            }
+
+            let elapsed = std::mem::take(&mut system.elapsed);
+            let system_start = Instant::now();
+            telemetry::timed(&system.name, || (system.run)(ecs, elapsed));
+
+            info.duration = system_start.elapsed();
+            info.average = if info.runs == 0 {
+                info.duration
+            } else {
+                (info.average * 15 + info.duration) / 16
+            };
+            info.runs += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::components::{Pos3, Velocity};
+
+    fn noop(_ecs: &ecs::Manager, _dt: Dt) {}
+
+    fn count(ecs: &ecs::Manager, _dt: Dt) {
+        let entity = ecs.create_entity();
+        ecs.add_component_to_entity(entity, Pos3::default());
+    }
+
+    #[test]
+    fn test_order() {
+        let mut schedule = Schedule::new();
+        schedule
+            .add(System::new("render", noop).in_stage(Stage::Last))
+            .unwrap();
+        schedule
+            .add(System::new("move", noop).after("input").writes::<Pos3>())
+            .unwrap();
+        schedule
+            .add(System::new("input", noop).writes::<Velocity>())
+            .unwrap();
+        schedule
+            .add(System::new("time", noop).in_stage(Stage::First))
+            .unwrap();
+        assert_eq!(schedule.order(), vec!["time", "input", "move", "render"]);
+
+        // A duplicate, a dependency on a later stage and a cycle are rejected
+        assert!(schedule.add(System::new("move", noop)).is_err());
+        assert!(schedule
+            .add(
+                System::new("physics", noop)
+                    .in_stage(Stage::First)
+                    .after("move")
+            )
+            .is_err());
+        schedule
+            .add(System::new("ai", noop).after("ai_setup"))
+            .unwrap();
+        assert!(schedule
+            .add(System::new("ai_setup", noop).after("ai"))
+            .is_err());
+        assert_eq!(schedule.len(), 5);
+
+        // Removing a system keeps the order of the rest
+        assert!(schedule.remove("input"));
+        assert!(!schedule.remove("input"));
+        assert_eq!(schedule.order(), vec!["time", "move", "ai", "render"]);
+    }
+
+    #[test]
+    fn test_run_criteria() {
+        struct Spawn;
+
+        let ecs = ecs::Manager::default();
+        let mut schedule = Schedule::new();
+        schedule
+            .add(System::new("slow", noop).run_every(Duration::from_millis(250)))
+            .unwrap();
+        schedule
+            .add(System::new("spawn", count).run_on_event::<Spawn>())
+            .unwrap();
+        schedule
+            .add(System::new("never", count).run_if(|ecs| ecs.entity_count() > 10))
+            .unwrap();
+
+        for _ in 0..10 {
+            schedule.run(&ecs, Dt::from_millis(50));
+        }
+        ecs.send_event(Spawn);
+        schedule.run(&ecs, Dt::from_millis(50));
+
+        let runs = schedule
+            .info()
+            .iter()
+            .map(|info| (info.runs, info.skips))
+            .collect::<Vec<_>>();
+        assert_eq!(runs, vec![(2, 9), (1, 10), (0, 11)]);
+        assert_eq!(schedule.info()[0].criteria, "every 250 ms");
+        // The event is left to the system, `count` does not drain it
+        assert!(ecs.has_events::<Spawn>());
+        assert_eq!(ecs.entity_count(), 1);
+    }
+
+    #[test]
+    fn test_commands() {
+        let ecs = ecs::Manager::default();
+        let mut schedule = Schedule::new();
+        schedule.add(System::new("spawn", count)).unwrap();
+
+        ecs.send_event(ScheduleCommand::Add(System::new("physics", noop)));
+        ecs.send_event(ScheduleCommand::AddAsync(AsyncSystem::new(
+            "load",
+            |_ecs, _dt| Box::pin(async {}),
+        )));
+        // A taken name is rejected, the other commands are applied
+        ecs.send_event(ScheduleCommand::Add(System::new("load", noop)));
+        ecs.send_event(ScheduleCommand::Remove("spawn".to_string()));
+        schedule.apply_commands(&ecs);
+
+        assert_eq!(schedule.order(), vec!["physics"]);
+        assert_eq!(schedule.async_systems()[0].name(), "load");
+        assert_eq!(schedule.len(), 2);
+        schedule.run(&ecs, Dt::from_millis(16));
+        assert!(ecs.get_entites_with_component::<Pos3>().is_empty());
+    }
+
+    #[test]
+    fn test_fixed_update() {
+        let ecs = ecs::Manager::default();
+        let mut schedule = Schedule::new();
+        schedule.set_fixed_update_hz(50);
+        schedule
+            .add(System::new("physics", count).in_stage(Stage::FixedUpdate))
+            .unwrap();
+        schedule.add(System::new("render", noop)).unwrap();
+        assert!(schedule
+            .add(System::new("collisions", noop).after("render"))
+            .is_ok());
+        assert!(schedule
+            .add(
+                System::new("forces", noop)
+                    .in_stage(Stage::FixedUpdate)
+                    .after("input")
+                    .after("render")
+            )
+            .is_err());
+
+        // 50 Hz is a step every 20 ms
+        schedule.run(&ecs, Dt::from_millis(10));
+        assert_eq!(ecs.entity_count(), 0);
+        schedule.run(&ecs, Dt::from_millis(35));
+        assert_eq!(ecs.entity_count(), 2);
+        let fixed_time = *ecs.get_resource::<FixedTime>().unwrap().read().unwrap();
+        assert_eq!(fixed_time.steps, 2);
+        assert!((fixed_time.alpha - 0.25).abs() < 1e-4);
+
+        // A long frame runs at most `MAX_FIXED_STEPS`
+        schedule.run(&ecs, Dt::from_secs(1));
+        assert_eq!(ecs.entity_count(), 2 + MAX_FIXED_STEPS as usize);
+        assert_eq!(schedule.info()[0].runs, 2 + MAX_FIXED_STEPS as u64);
+        assert_eq!(schedule.info()[1].runs, 3);
+    }
+
+    #[test]
+    fn test_fixed_time_in_fixed_update() {
+        fn record(ecs: &ecs::Manager, dt: Dt) {
+            let fixed_time = *ecs.get_resource::<FixedTime>().unwrap().read().unwrap();
+            let entity = ecs.create_entity();
+            ecs.add_component_to_entity(
+                entity,
+                Pos3::new(cgmath::vec3(
+                    fixed_time.steps as f32,
+                    fixed_time.alpha,
+                    (fixed_time.step == dt) as u8 as f32,
+                )),
+            );
+        }
+
+        let ecs = ecs::Manager::default();
+        let mut schedule = Schedule::new();
+        schedule.set_fixed_update_hz(50);
+        schedule
+            .add(System::new("record", record).in_stage(Stage::FixedUpdate))
+            .unwrap();
+
+        schedule.run(&ecs, Dt::from_millis(45));
+        let recorded = ecs.get_all_components_of_type::<Pos3>();
+        assert_eq!(recorded.len(), 2);
+        for (_, pos) in recorded {
+            let pos = pos.read().unwrap().pos;
+            assert_eq!(pos.x, 2.0);
+            assert!((pos.y - 0.25).abs() < 1e-4);
+            assert_eq!(pos.z, 1.0);
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let ecs = ecs::Manager::default();
+        let tool = ecs.create_entity();
+        ecs.add_component_to_entity(tool, ScheduleInfo::default());
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add(System::new("spawn", count).writes::<Pos3>())
+            .unwrap();
+        schedule
+            .add(System::new("physics", noop).reads::<Pos3>())
+            .unwrap();
+        schedule.run(&ecs, Dt::from_millis(16));
+        schedule.run(&ecs, Dt::from_millis(16));
+        assert_eq!(ecs.get_entites_with_component::<Pos3>().len(), 2);
+
+        let info = ecs.get_component_from_entity::<ScheduleInfo>(tool).unwrap();
+        let info = info.read().unwrap();
+        assert_eq!(info.systems.len(), 2);
+        assert_eq!(info.systems[0].runs, 2);
+        assert!(info.systems[0].conflicts_with(&info.systems[1]));
+    }
+
+    #[test]
+    fn test_async_system_macro() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let runs = Arc::new(AtomicU32::new(0));
+        let system = crate::async_system!(
+            "count",
+            [runs, step = 2u32] | ecs,
+            _dt | {
+                let ecs = ecs.lock().unwrap();
+                runs.fetch_add(step + ecs.entity_count() as u32, Ordering::Relaxed);
+            }
+        );
+        assert_eq!(system.name(), "count");
+
+        let ecs = Arc::new(Mutex::new(ecs::Manager::default()));
+        ecs.lock().unwrap().create_entity();
+        futures::executor::block_on(system.run(Arc::clone(&ecs), Dt::from_millis(16)));
+        futures::executor::block_on(system.run(ecs, Dt::from_millis(16)));
+        // The capture is shared with the system, not moved into it
+        assert_eq!(runs.load(Ordering::Relaxed), 6);
+    }
+}
@@ -0,0 +1,358 @@
+use instant::{Duration, Instant};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+/// The longest request line read, the rest of a longer one is ignored.
+const MAX_REQUEST_LINE: u64 = 1024;
+
+/// Exposes the engine metrics on a Prometheus scrape endpoint.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TelemetryConfig {
+    /// The address of the HTTP endpoint, the metrics are served on `/metrics`.
+    pub address: SocketAddr,
+    /// How long a connection may take in total to send its request and read the response,
+    /// the requests are served one at a time.
+    pub request_timeout: Duration,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            address: SocketAddr::from(([127, 0, 0, 1], 9464)),
+            request_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Metrics {
+    frames: u64,
+    frame_time: Duration,
+    entity_count: usize,
+    gpu_memory_bytes: u64,
+    system_durations: BTreeMap<String, Duration>,
+}
+
+/// The engine metrics of the latest frame.
+/// Recording is a no-op until the telemetry is enabled, so the systems can always report their timings.
+#[derive(Debug, Default)]
+pub struct Telemetry {
+    enabled: AtomicBool,
+    metrics: Mutex<Metrics>,
+}
+
+static TELEMETRY: LazyLock<Telemetry> = LazyLock::new(Telemetry::default);
+
+/// Get the telemetry of the engine.
+pub fn telemetry() -> &'static Telemetry {
+    &TELEMETRY
+}
+
+/// Run a system and record how long it took.
+///
+/// # Arguments
+///
+/// * `name` - The name of the system, used as the `system` label of the metric.
+/// * `f` - The system to run.
+///
+/// # Returns
+///
+/// The result of the system.
+pub fn timed<R>(name: &str, f: impl FnOnce() -> R) -> R {
+    if !telemetry().is_enabled() {
+        return f();
+    }
+
+    let start = Instant::now();
+    let result = f();
+    telemetry().record_system(name, start.elapsed());
+    result
+}
+
+impl Telemetry {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Record a rendered frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_time` - The time since the previous frame.
+    /// * `entity_count` - The number of entities alive.
+    /// * `gpu_memory_bytes` - The estimated size of the GPU resources owned by the renderer.
+    pub fn record_frame(&self, frame_time: Duration, entity_count: usize, gpu_memory_bytes: u64) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut metrics = self.metrics.lock().unwrap();
+        metrics.frames += 1;
+        metrics.frame_time = frame_time;
+        metrics.entity_count = entity_count;
+        metrics.gpu_memory_bytes = gpu_memory_bytes;
+    }
+
+    /// Record the duration of the latest run of a system.
+    pub fn record_system(&self, name: &str, duration: Duration) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut metrics = self.metrics.lock().unwrap();
+        match metrics.system_durations.get_mut(name) {
+            Some(latest) => *latest = duration,
+            None => {
+                metrics.system_durations.insert(name.to_string(), duration);
+            }
+        }
+    }
+
+    /// Get the metrics in the Prometheus text exposition format.
+    pub fn encode(&self) -> String {
+        let metrics = self.metrics.lock().unwrap().clone();
+        let mut out = String::new();
+
+        let mut metric = |name: &str, help: &str, kind: &str, samples: &[(String, String)]| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(out, "{}{} {}", name, labels, value);
+            }
+        };
+
+        metric(
+            "gears_frames_total",
+            "The number of rendered frames.",
+            "counter",
+            &[(String::new(), metrics.frames.to_string())],
+        );
+        metric(
+            "gears_frame_time_seconds",
+            "The duration of the latest frame.",
+            "gauge",
+            &[(String::new(), metrics.frame_time.as_secs_f64().to_string())],
+        );
+        metric(
+            "gears_entities",
+            "The number of entities alive.",
+            "gauge",
+            &[(String::new(), metrics.entity_count.to_string())],
+        );
+        metric(
+            "gears_gpu_memory_bytes",
+            "The estimated size of the GPU resources owned by the renderer.",
+            "gauge",
+            &[(String::new(), metrics.gpu_memory_bytes.to_string())],
+        );
+        metric(
+            "gears_system_duration_seconds",
+            "The duration of the latest run of a system.",
+            "gauge",
+            &metrics
+                .system_durations
+                .iter()
+                .map(|(name, duration)| {
+                    (
+                        format!("{{system=\"{}\"}}", escape_label(name)),
+                        duration.as_secs_f64().to_string(),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        out
+    }
+
+    /// Enable the telemetry and serve the metrics on a background thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The telemetry configuration.
+    ///
+    /// # Returns
+    ///
+    /// The address the endpoint is listening on.
+    pub fn serve(&'static self, config: TelemetryConfig) -> anyhow::Result<SocketAddr> {
+        let listener = TcpListener::bind(config.address)?;
+        let address = listener.local_addr()?;
+        self.set_enabled(true);
+
+        std::thread::Builder::new()
+            .name("gears-telemetry".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let result = stream
+                        .map_err(anyhow::Error::from)
+                        .and_then(|stream| self.respond(stream, config.request_timeout));
+                    if let Err(e) = result {
+                        log::warn!("[Telemetry] Failed to serve the metrics: {}", e);
+                    }
+                }
+            })?;
+
+        log::info!(
+            "[Telemetry] Serving the metrics on http://{}/metrics",
+            address
+        );
+        Ok(address)
+    }
+
+    fn respond(&self, mut stream: TcpStream, timeout: Duration) -> anyhow::Result<()> {
+        // A client which never finishes its request, or sends it a byte at a time, would block the endpoint
+        let deadline = Instant::now() + timeout;
+        let request_line = read_request_line(&mut stream, deadline)?;
+
+        let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+        let (status, body) = if path == "/metrics" {
+            ("200 OK", self.encode())
+        } else {
+            ("404 Not Found", String::new())
+        };
+
+        stream.set_write_timeout(Some(remaining(deadline)?))?;
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )?;
+        Ok(())
+    }
+}
+
+/// Get the time left until the deadline of a request, an error once it passed.
+fn remaining(deadline: Instant) -> anyhow::Result<Duration> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        anyhow::bail!("The request timed out");
+    }
+    Ok(remaining)
+}
+
+/// Read the first line of a request, at most `MAX_REQUEST_LINE` bytes of it.
+/// The timeout of each read is what is left until the deadline, so a slow client can not extend it.
+fn read_request_line(stream: &mut TcpStream, deadline: Instant) -> anyhow::Result<String> {
+    let mut line = Vec::new();
+    let mut buffer = [0u8; 256];
+    while !line.contains(&b'\n') && (line.len() as u64) < MAX_REQUEST_LINE {
+        stream.set_read_timeout(Some(remaining(deadline)?))?;
+        let len = stream.read(&mut buffer)?;
+        if len == 0 {
+            break;
+        }
+        line.extend_from_slice(&buffer[..len]);
+    }
+
+    let end = line
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .unwrap_or(line.len())
+        .min(MAX_REQUEST_LINE as usize);
+    Ok(String::from_utf8_lossy(&line[..end]).into_owned())
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_telemetry_records_nothing() {
+        let telemetry = Telemetry::default();
+        telemetry.record_frame(Duration::from_millis(16), 10, 1024);
+        assert!(telemetry.encode().contains("gears_frames_total 0"));
+    }
+
+    #[test]
+    fn test_encode() {
+        let telemetry = Telemetry::default();
+        telemetry.set_enabled(true);
+        telemetry.record_frame(Duration::from_millis(20), 42, 4096);
+        telemetry.record_system("physics", Duration::from_millis(2));
+        telemetry.record_system("ai \"brain\"", Duration::from_millis(1));
+
+        let text = telemetry.encode();
+        assert!(text.contains("gears_frames_total 1\n"));
+        assert!(text.contains("gears_frame_time_seconds 0.02\n"));
+        assert!(text.contains("gears_entities 42\n"));
+        assert!(text.contains("gears_gpu_memory_bytes 4096\n"));
+        assert!(text.contains("gears_system_duration_seconds{system=\"physics\"} 0.002\n"));
+        assert!(text.contains("{system=\"ai \\\"brain\\\"\"}"));
+        assert!(text.contains("# TYPE gears_entities gauge"));
+    }
+
+    #[test]
+    fn test_stalled_client() {
+        let telemetry = Box::leak(Box::new(Telemetry::default()));
+        let address = telemetry
+            .serve(TelemetryConfig {
+                address: SocketAddr::from(([127, 0, 0, 1], 0)),
+                request_timeout: Duration::from_millis(100),
+            })
+            .unwrap();
+
+        // The first client never sends its request, the second is still served
+        let _stalled = TcpStream::connect(address).unwrap();
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("gears_frames_total"));
+    }
+
+    #[test]
+    fn test_trickling_client() {
+        let telemetry = Box::leak(Box::new(Telemetry::default()));
+        let address = telemetry
+            .serve(TelemetryConfig {
+                address: SocketAddr::from(([127, 0, 0, 1], 0)),
+                request_timeout: Duration::from_millis(200),
+            })
+            .unwrap();
+
+        // The first client sends a byte every 50 ms, each read is quick but the whole request is not
+        let mut trickling = TcpStream::connect(address).unwrap();
+        let trickle = std::thread::spawn(move || {
+            for byte in b"GET /metrics HTTP/1.1\r\n\r\n" {
+                if trickling.write_all(&[*byte]).is_err() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        });
+
+        std::thread::sleep(Duration::from_millis(20));
+        let start = Instant::now();
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        // The trickling request takes over a second to send, it is cut off at its deadline
+        assert!(start.elapsed() < Duration::from_millis(800));
+        trickle.join().unwrap();
+    }
+}
@@ -0,0 +1,604 @@
+use super::mods::{self, ModInfo};
+use crate::ecs::Entity;
+use anyhow::Context;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{LazyLock, Mutex, RwLock};
+
+const MAGIC: &[u8; 4] = b"GPAK";
+const VERSION: u32 = 1;
+
+/// Assets compiled into the executable as `(path, data)` pairs, create them with `embed_assets!`.
+pub type EmbeddedAssets = &'static [(&'static str, &'static [u8])];
+
+/// Sent when an asset failed to load and a placeholder is used in its place.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetLoadFailed {
+    /// The entity using the asset, if any.
+    pub entity: Option<Entity>,
+    pub path: String,
+    pub error: String,
+}
+
+/// Where the assets are loaded from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetConfig {
+    /// The directory the loose assets are read from.
+    pub root: Option<PathBuf>,
+    /// The archives mounted on top of the root, a later archive overrides the earlier ones.
+    pub paks: Vec<PathBuf>,
+    /// A directory overriding every other source, e.g. for development.
+    pub override_dir: Option<PathBuf>,
+    /// The directory the mods are installed in, they are mounted on top of the archives.
+    pub mods_dir: Option<PathBuf>,
+    /// The ids of the installed mods not to mount.
+    pub disabled_mods: Vec<String>,
+    /// Assets embedded in the executable, resolved before the archives and the root directory.
+    pub embedded: EmbeddedAssets,
+}
+
+impl Default for AssetConfig {
+    fn default() -> Self {
+        Self {
+            // The build script copies res/ next to the build output
+            root: Some(PathBuf::from(env!("OUT_DIR"))),
+            paks: Vec::new(),
+            override_dir: None,
+            mods_dir: None,
+            disabled_mods: Vec::new(),
+            embedded: &[],
+        }
+    }
+}
+
+/// Normalize an asset path, so `res\models/./cube.obj` and `res/models/cube.obj` are the same asset.
+pub(crate) fn normalize(path: &str) -> String {
+    path.split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Get the file of an asset in a mounted directory, `None` if the path could leave the directory,
+/// e.g. with `..`, a root or a drive prefix.
+fn dir_file(dir: &Path, path: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(normalize(path));
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        .then(|| dir.join(path))
+}
+
+/// A read-only archive of assets.
+///
+/// The layout is the `GPAK` magic, the version, the number of entries, the table of entries
+/// (path length, path, offset, size), and then the data of the files.
+/// All numbers are little endian.
+#[derive(Debug)]
+pub struct Pak {
+    file: Mutex<File>,
+    entries: BTreeMap<String, (u64, u64)>,
+}
+
+impl Pak {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+
+        let mut header = [0u8; 12];
+        file.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            anyhow::bail!("{} is not an asset archive", path.display());
+        }
+        let version = u32::from_le_bytes(header[4..8].try_into()?);
+        if version != VERSION {
+            anyhow::bail!("{} has an unsupported version {}", path.display(), version);
+        }
+
+        let count = u32::from_le_bytes(header[8..12].try_into()?);
+        let file_len = file.metadata()?.len();
+        let mut entries = BTreeMap::new();
+        for _ in 0..count {
+            let mut len = [0u8; 2];
+            file.read_exact(&mut len)?;
+            let mut name = vec![0u8; u16::from_le_bytes(len) as usize];
+            file.read_exact(&mut name)?;
+            let mut range = [0u8; 16];
+            file.read_exact(&mut range)?;
+
+            let name = String::from_utf8(name)?;
+            let offset = u64::from_le_bytes(range[..8].try_into()?);
+            let size = u64::from_le_bytes(range[8..].try_into()?);
+            // The entries are read with buffers of their size, so they have to be inside the archive
+            if offset.checked_add(size).is_none_or(|end| end > file_len) {
+                anyhow::bail!("{} of {} is out of the archive", name, path.display());
+            }
+            entries.insert(name, (offset, size));
+        }
+
+        Ok(Self {
+            file: Mutex::new(file),
+            entries,
+        })
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(&normalize(path))
+    }
+
+    /// Get the paths of the files in the archive.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Read a file of the archive, `None` if the archive does not contain it.
+    pub fn read(&self, path: &str) -> Option<anyhow::Result<Vec<u8>>> {
+        let (offset, size) = *self.entries.get(&normalize(path))?;
+
+        let read = || {
+            let mut file = self.file.lock().unwrap();
+            file.seek(SeekFrom::Start(offset))?;
+            let mut data = vec![0u8; size as usize];
+            file.read_exact(&mut data)?;
+            Ok(data)
+        };
+        Some(read())
+    }
+}
+
+/// Pack every file of a directory into an archive.
+/// Call it from the build script of a game to ship its assets as a single file.
+///
+/// # Arguments
+///
+/// * `dir` - The directory to pack.
+/// * `prefix` - The path the files are stored under, e.g. `res` to keep the `res/models/...` paths.
+/// * `out` - The archive to write.
+///
+/// # Returns
+///
+/// The number of packed files.
+pub fn pack_dir(dir: impl AsRef<Path>, prefix: &str, out: impl Write) -> anyhow::Result<usize> {
+    fn collect(dir: &Path, name: &str, files: &mut Vec<(String, PathBuf)>) -> anyhow::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let name = normalize(&format!("{}/{}", name, file_name));
+            if path.is_dir() {
+                collect(&path, &name, files)?;
+            } else {
+                files.push((name, path));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    collect(dir.as_ref(), prefix, &mut files)?;
+    // A stable order keeps the archive reproducible
+    files.sort();
+
+    let data = files
+        .iter()
+        .map(|(_, path)| {
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let names = files.iter().map(|(name, _)| name.as_str());
+    pack(names.zip(data.iter().map(Vec::as_slice)), out)?;
+
+    Ok(files.len())
+}
+
+/// Write an archive of `(path, data)` pairs.
+pub fn pack<'a>(
+    files: impl IntoIterator<Item = (&'a str, &'a [u8])>,
+    mut out: impl Write,
+) -> anyhow::Result<()> {
+    let files = files
+        .into_iter()
+        .map(|(path, data)| (normalize(path), data))
+        .collect::<Vec<_>>();
+
+    let table_size: usize = files.iter().map(|(path, _)| 2 + path.len() + 16).sum();
+    let mut offset = (12 + table_size) as u64;
+
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    out.write_all(&(files.len() as u32).to_le_bytes())?;
+    for (path, data) in &files {
+        let len = u16::try_from(path.len()).context("The asset path is too long")?;
+        out.write_all(&len.to_le_bytes())?;
+        out.write_all(path.as_bytes())?;
+        out.write_all(&offset.to_le_bytes())?;
+        out.write_all(&(data.len() as u64).to_le_bytes())?;
+        offset += data.len() as u64;
+    }
+    for (_, data) in &files {
+        out.write_all(data)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+enum Source {
+    Dir(PathBuf),
+    Pak(Pak),
+    Embedded(BTreeMap<String, &'static [u8]>),
+}
+
+impl Source {
+    fn contains(&self, path: &str) -> bool {
+        match self {
+            Self::Dir(dir) => dir_file(dir, path).is_some_and(|file| file.is_file()),
+            Self::Pak(pak) => pak.contains(path),
+            Self::Embedded(files) => files.contains_key(&normalize(path)),
+        }
+    }
+
+    fn read(&self, path: &str) -> Option<anyhow::Result<Vec<u8>>> {
+        match self {
+            Self::Dir(dir) => {
+                let file = dir_file(dir, path)?;
+                file.is_file()
+                    .then(|| std::fs::read(&file).map_err(anyhow::Error::from))
+            }
+            Self::Pak(pak) => pak.read(path),
+            Self::Embedded(files) => files.get(&normalize(path)).map(|data| Ok(data.to_vec())),
+        }
+    }
+
+    fn collect_paths(&self, paths: &mut BTreeSet<String>) {
+        fn walk(dir: &Path, prefix: &str, paths: &mut BTreeSet<String>) {
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                return;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let name = normalize(&format!(
+                    "{}/{}",
+                    prefix,
+                    entry.file_name().to_string_lossy()
+                ));
+                if path.is_dir() {
+                    walk(&path, &name, paths);
+                } else {
+                    paths.insert(name);
+                }
+            }
+        }
+
+        match self {
+            Self::Dir(dir) => walk(dir, "", paths),
+            Self::Pak(pak) => paths.extend(pak.paths().map(str::to_string)),
+            Self::Embedded(files) => paths.extend(files.keys().cloned()),
+        }
+    }
+}
+
+/// Resolves the asset paths to a list of sources ordered by priority.
+/// A source with a higher priority overrides the lower ones, and of the sources with the same priority
+/// the one mounted last wins.
+/// By default the override directory is on top, then the mods, the embedded assets, the archives
+/// and the root directory.
+#[derive(Debug, Default)]
+pub struct Vfs {
+    sources: Vec<(i32, Source)>,
+    mods: Vec<ModInfo>,
+}
+
+impl Vfs {
+    /// The priority of the root directory.
+    pub const ROOT_PRIORITY: i32 = i32::MIN;
+    /// The priority of the archives of the config.
+    pub const PAK_PRIORITY: i32 = 0;
+    /// The priority of the embedded assets, above the archives so they are found without touching the disk.
+    pub const EMBEDDED_PRIORITY: i32 = 1 << 15;
+    /// The priority the mods start from, the priority of a mod is added to it.
+    pub const MOD_PRIORITY: i32 = 1 << 16;
+    /// The priority of the override directory.
+    pub const OVERRIDE_PRIORITY: i32 = i32::MAX;
+
+    pub fn new(config: &AssetConfig) -> anyhow::Result<Self> {
+        let mut vfs = Self::default();
+
+        if let Some(root) = &config.root {
+            vfs.mount_dir(root, Self::ROOT_PRIORITY);
+        }
+        for pak in &config.paks {
+            vfs.mount_with_priority(Pak::open(pak)?, Self::PAK_PRIORITY);
+        }
+        if !config.embedded.is_empty() {
+            vfs.embed(config.embedded, Self::EMBEDDED_PRIORITY);
+        }
+        if let Some(mods_dir) = &config.mods_dir {
+            for info in mods::discover(mods_dir, &config.disabled_mods)? {
+                vfs.mount_mod(info)?;
+            }
+        }
+        if let Some(override_dir) = &config.override_dir {
+            vfs.mount_dir(override_dir, Self::OVERRIDE_PRIORITY);
+        }
+
+        Ok(vfs)
+    }
+
+    fn insert(&mut self, priority: i32, source: Source) {
+        // Before the sources with the same priority, so the latest one wins
+        let index = self.sources.partition_point(|(p, _)| *p > priority);
+        self.sources.insert(index, (priority, source));
+    }
+
+    /// Mount an archive on top of the others.
+    pub fn mount(&mut self, pak: Pak) {
+        self.mount_with_priority(pak, Self::PAK_PRIORITY);
+    }
+
+    pub fn mount_with_priority(&mut self, pak: Pak, priority: i32) {
+        self.insert(priority, Source::Pak(pak));
+    }
+
+    /// Mount a directory of loose files.
+    pub fn mount_dir(&mut self, dir: impl Into<PathBuf>, priority: i32) {
+        self.insert(priority, Source::Dir(dir.into()));
+    }
+
+    /// Mount assets embedded in the executable.
+    pub fn embed(&mut self, assets: EmbeddedAssets, priority: i32) {
+        let files = assets
+            .iter()
+            .map(|(path, data)| (normalize(path), *data))
+            .collect();
+        self.insert(priority, Source::Embedded(files));
+    }
+
+    /// Add a mod to the installed mods, and mount it if it is enabled.
+    pub fn mount_mod(&mut self, info: ModInfo) -> anyhow::Result<()> {
+        if info.enabled {
+            let priority = Self::MOD_PRIORITY.saturating_add(info.priority);
+            if info.path.is_dir() {
+                self.mount_dir(&info.path, priority);
+            } else {
+                self.mount_with_priority(Pak::open(&info.path)?, priority);
+            }
+            log::info!("[Mods] Mounted {} {}", info.name, info.version);
+        }
+        self.mods.push(info);
+        Ok(())
+    }
+
+    /// Get the installed mods in load order, including the disabled ones.
+    pub fn mods(&self) -> &[ModInfo] {
+        &self.mods
+    }
+
+    /// Get the paths of every asset of every source, sorted.
+    pub fn paths(&self) -> BTreeSet<String> {
+        let mut paths = BTreeSet::new();
+        for (_, source) in &self.sources {
+            source.collect_paths(&mut paths);
+        }
+        paths
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.sources.iter().any(|(_, source)| source.contains(path))
+    }
+
+    pub fn read(&self, path: &str) -> anyhow::Result<Vec<u8>> {
+        self.sources
+            .iter()
+            .find_map(|(_, source)| source.read(path))
+            .unwrap_or_else(|| anyhow::bail!("The asset {} does not exist", path))
+    }
+
+    pub fn read_to_string(&self, path: &str) -> anyhow::Result<String> {
+        Ok(String::from_utf8(self.read(path)?)?)
+    }
+}
+
+static VFS: LazyLock<RwLock<Vfs>> =
+    LazyLock::new(|| RwLock::new(Vfs::new(&AssetConfig::default()).unwrap_or_default()));
+
+/// Replace the file system the assets are loaded from.
+pub fn configure(config: &AssetConfig) -> anyhow::Result<()> {
+    *VFS.write().unwrap() = Vfs::new(config)?;
+    Ok(())
+}
+
+/// Mount an archive on top of the current sources.
+pub fn mount(pak: Pak) {
+    VFS.write().unwrap().mount(pak);
+}
+
+/// Read an asset through the configured file system.
+pub fn read(path: &str) -> anyhow::Result<Vec<u8>> {
+    VFS.read().unwrap().read(path)
+}
+
+pub fn read_to_string(path: &str) -> anyhow::Result<String> {
+    VFS.read().unwrap().read_to_string(path)
+}
+
+pub fn exists(path: &str) -> bool {
+    VFS.read().unwrap().exists(path)
+}
+
+/// Get the paths of every asset, sorted.
+pub fn paths() -> BTreeSet<String> {
+    VFS.read().unwrap().paths()
+}
+
+/// Get the installed mods in load order.
+pub fn mods() -> Vec<ModInfo> {
+    VFS.read().unwrap().mods().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gears_vfs_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_pack_dir() {
+        let dir = temp_dir("pack");
+        std::fs::create_dir_all(dir.join("models/cube")).unwrap();
+        std::fs::write(dir.join("models/cube/cube.obj"), "v 0 0 0").unwrap();
+        std::fs::write(dir.join("icon.png"), [1u8, 2, 3]).unwrap();
+
+        let pak_path = dir.with_extension("pak");
+        let count = pack_dir(&dir, "res", File::create(&pak_path).unwrap()).unwrap();
+        assert_eq!(count, 2);
+
+        let pak = Pak::open(&pak_path).unwrap();
+        assert_eq!(
+            pak.paths().collect::<Vec<_>>(),
+            vec!["res/icon.png", "res/models/cube/cube.obj"]
+        );
+        assert_eq!(pak.read("res/icon.png").unwrap().unwrap(), vec![1, 2, 3]);
+        assert_eq!(
+            pak.read("res\\models/./cube/cube.obj").unwrap().unwrap(),
+            b"v 0 0 0"
+        );
+        assert!(pak.read("res/missing.png").is_none());
+
+        // An entry past the end of the archive is rejected when it is opened
+        let mut data = std::fs::read(&pak_path).unwrap();
+        let size_at = 12 + 2 + "res/icon.png".len() + 8;
+        data[size_at..size_at + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(&pak_path, &data).unwrap();
+        assert!(Pak::open(&pak_path).is_err());
+    }
+
+    #[test]
+    fn test_dir_stays_inside_root() {
+        let dir = temp_dir("escape");
+        std::fs::create_dir_all(dir.join("root/res")).unwrap();
+        std::fs::write(dir.join("root/res/a.txt"), "a").unwrap();
+        std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+
+        let root = Source::Dir(dir.join("root"));
+        assert!(!root.contains("res/../res/a.txt"));
+        assert!(root.contains("res/a.txt"));
+        assert!(root.read("../secret.txt").is_none());
+        assert!(root.read("res\\..\\..\\secret.txt").is_none());
+
+        // A leading separator is dropped, the path stays relative to the directory
+        assert_eq!(dir_file(&dir, "/res/a.txt"), Some(dir.join("res/a.txt")));
+        assert_eq!(dir_file(&dir, "res/.."), None);
+        #[cfg(windows)]
+        assert_eq!(dir_file(&dir, "C:\\secret.txt"), None);
+    }
+
+    #[test]
+    fn test_override_order() {
+        let dir = temp_dir("order");
+        let root = dir.join("root");
+        let mods = dir.join("mods");
+        std::fs::create_dir_all(root.join("res")).unwrap();
+        std::fs::create_dir_all(mods.join("res")).unwrap();
+        std::fs::write(root.join("res/a.txt"), "root a").unwrap();
+        std::fs::write(root.join("res/b.txt"), "root b").unwrap();
+        std::fs::write(mods.join("res/c.txt"), "mod c").unwrap();
+
+        let pak_path = dir.join("game.pak");
+        pack(
+            [("res/b.txt", &b"pak b"[..]), ("res/c.txt", &b"pak c"[..])],
+            File::create(&pak_path).unwrap(),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(&AssetConfig {
+            root: Some(root),
+            paks: vec![pak_path],
+            override_dir: Some(mods),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(vfs.read_to_string("res/a.txt").unwrap(), "root a");
+        assert_eq!(vfs.read_to_string("res/b.txt").unwrap(), "pak b");
+        assert_eq!(vfs.read_to_string("res/c.txt").unwrap(), "mod c");
+        assert_eq!(
+            vfs.paths().into_iter().collect::<Vec<_>>(),
+            vec!["res/a.txt", "res/b.txt", "res/c.txt"]
+        );
+        assert!(!vfs.exists("res/d.txt"));
+        assert!(vfs.read("res/d.txt").is_err());
+    }
+
+    #[test]
+    fn test_embedded_assets() {
+        let dir = temp_dir("embedded");
+        std::fs::create_dir_all(dir.join("res")).unwrap();
+        std::fs::write(dir.join("res/icon.png"), "on disk").unwrap();
+
+        let vfs = Vfs::new(&AssetConfig {
+            root: Some(dir),
+            embedded: crate::embed_assets!(dir = "..", "res/icon.png"),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let icon = vfs.read("res\\icon.png").unwrap();
+        assert_eq!(icon, include_bytes!("../../../res/icon.png"));
+        assert!(vfs.exists("res/icon.png"));
+    }
+
+    #[test]
+    fn test_mod_load_order() {
+        let dir = temp_dir("mods");
+        let root = dir.join("root");
+        let mods_dir = dir.join("mods");
+        std::fs::create_dir_all(root.join("res")).unwrap();
+        std::fs::write(root.join("res/a.txt"), "root a").unwrap();
+        std::fs::write(root.join("res/b.txt"), "root b").unwrap();
+
+        for (id, priority, files) in [("low", 0, ["a", "b"]), ("high", 5, ["b", "c"])] {
+            std::fs::create_dir_all(mods_dir.join(id).join("res")).unwrap();
+            std::fs::write(
+                mods_dir.join(id).join(mods::MANIFEST),
+                format!("name = {}\npriority = {}", id, priority),
+            )
+            .unwrap();
+            for file in files {
+                std::fs::write(mods_dir.join(id).join(format!("res/{}.txt", file)), id).unwrap();
+            }
+        }
+        pack(
+            [
+                (mods::MANIFEST, &b"priority = 10"[..]),
+                ("res/c.txt", b"archive"),
+            ],
+            File::create(mods_dir.join("off.pak")).unwrap(),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(&AssetConfig {
+            root: Some(root),
+            mods_dir: Some(mods_dir),
+            disabled_mods: vec!["off".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let mods = vfs.mods();
+        assert_eq!(
+            mods.iter().map(|info| info.id.as_str()).collect::<Vec<_>>(),
+            vec!["low", "high", "off"]
+        );
+        assert!(!mods[2].enabled);
+        assert_eq!(vfs.read_to_string("res/a.txt").unwrap(), "low");
+        assert_eq!(vfs.read_to_string("res/b.txt").unwrap(), "high");
+        assert_eq!(vfs.read_to_string("res/c.txt").unwrap(), "high");
+    }
+}
@@ -0,0 +1,219 @@
+use super::{Entity, Manager};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// The buffers of a type kept for the next frames.
+const MAX_POOLED: usize = 32;
+/// The capacity above which a returned buffer is freed instead of kept, so a single large frame
+/// does not hold on to its memory.
+const MAX_POOLED_CAPACITY: usize = 64 * 1024;
+
+/// The counters of a frame arena for the last frame.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FrameArenaStats {
+    /// The buffers allocated because none were pooled.
+    pub allocated: u64,
+    /// The buffers taken from the pool.
+    pub reused: u64,
+    /// The buffers pooled for the next frame.
+    pub pooled: usize,
+}
+
+#[derive(Default)]
+struct Pools {
+    /// The cleared buffers, a `Vec<Vec<T>>` per type.
+    buffers: HashMap<TypeId, Box<dyn Any + Send>>,
+    pooled: usize,
+    frame: FrameArenaStats,
+    last_frame: FrameArenaStats,
+}
+
+/// A scratch allocator for the short-lived data of the systems, like the obstacles near an agent
+/// or the results of a query. The buffers it hands out go back to it when dropped and are reused
+/// on the next frames, so the hot paths do not allocate once the pools are warm.
+///
+/// The app resets it after each update, the manager owns one, see `Manager::frame_arena`.
+#[derive(Default)]
+pub struct FrameArena {
+    pools: Mutex<Pools>,
+}
+
+impl FrameArena {
+    /// Get an empty buffer, pooled if possible.
+    pub fn alloc_vec<T: 'static + Send>(&self) -> FrameVec<'_, T> {
+        let mut pools = self.pools.lock().unwrap();
+        let buffer = pools
+            .buffers
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|pool| pool.downcast_mut::<Vec<Vec<T>>>())
+            .and_then(Vec::pop);
+        let buffer = match buffer {
+            Some(buffer) => {
+                pools.pooled -= 1;
+                pools.frame.reused += 1;
+                buffer
+            }
+            None => {
+                pools.frame.allocated += 1;
+                Vec::new()
+            }
+        };
+        FrameVec {
+            buffer,
+            arena: self,
+        }
+    }
+
+    /// Get a buffer filled from an iterator.
+    pub fn collect<T: 'static + Send>(&self, iter: impl IntoIterator<Item = T>) -> FrameVec<'_, T> {
+        let mut buffer = self.alloc_vec();
+        buffer.extend(iter);
+        buffer
+    }
+
+    fn release<T: 'static + Send>(&self, mut buffer: Vec<T>) {
+        if buffer.capacity() == 0 || buffer.capacity() > MAX_POOLED_CAPACITY {
+            return;
+        }
+        buffer.clear();
+
+        let mut guard = self.pools.lock().unwrap();
+        let pools = &mut *guard;
+        let pool = pools
+            .buffers
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Vec::<Vec<T>>::new()))
+            .downcast_mut::<Vec<Vec<T>>>()
+            .expect("Frame arena pool type mismatch");
+        if pool.len() < MAX_POOLED {
+            pool.push(buffer);
+            pools.pooled += 1;
+        }
+    }
+
+    /// End the frame, the counters of the frame become the ones returned by `stats`.
+    pub fn reset(&self) {
+        let mut pools = self.pools.lock().unwrap();
+        let mut frame = std::mem::take(&mut pools.frame);
+        frame.pooled = pools.pooled;
+        pools.last_frame = frame;
+    }
+
+    /// Get the counters of the last frame.
+    pub fn stats(&self) -> FrameArenaStats {
+        self.pools.lock().unwrap().last_frame
+    }
+}
+
+/// A buffer of a `FrameArena`, returned to it when dropped.
+pub struct FrameVec<'a, T: 'static + Send> {
+    buffer: Vec<T>,
+    arena: &'a FrameArena,
+}
+
+impl<T: 'static + Send> FrameVec<'_, T> {
+    /// Take the buffer out of the arena, e.g. to keep it past the frame.
+    pub fn into_vec(mut self) -> Vec<T> {
+        std::mem::take(&mut self.buffer)
+    }
+}
+
+impl<T: 'static + Send> Deref for FrameVec<'_, T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl<T: 'static + Send> DerefMut for FrameVec<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buffer
+    }
+}
+
+impl<T: 'static + Send> Drop for FrameVec<'_, T> {
+    fn drop(&mut self) {
+        self.arena.release(std::mem::take(&mut self.buffer));
+    }
+}
+
+impl<'b, T: 'static + Send> IntoIterator for &'b FrameVec<'_, T> {
+    type Item = &'b T;
+    type IntoIter = std::slice::Iter<'b, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.buffer.iter()
+    }
+}
+
+impl<T: 'static + Send + std::fmt::Debug> std::fmt::Debug for FrameVec<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.buffer.fmt(f)
+    }
+}
+
+impl Manager {
+    /// Get the scratch allocator for the data a system only needs during the frame.
+    pub fn frame_arena(&self) -> &FrameArena {
+        &self.arena
+    }
+
+    /// Get all components of a specific type, like `get_all_components_of_type`,
+    /// in a buffer of the frame arena.
+    pub fn query_components<T: 'static + Send + Sync>(
+        &self,
+    ) -> FrameVec<'_, (Entity, Arc<RwLock<T>>)> {
+        let mut result = self.arena.alloc_vec();
+        self.for_each_component::<T>(|entity, component| result.push((entity, component)));
+        result
+    }
+
+    /// Get all entities that have a specific component, like `get_entites_with_component`,
+    /// in a buffer of the frame arena.
+    pub fn query_entities<T: 'static + Send + Sync>(&self) -> FrameVec<'_, Entity> {
+        let mut result = self.arena.alloc_vec();
+        self.for_each_component::<T>(|entity, _| result.push(entity));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffers_are_reused() {
+        let ecs = Manager::default();
+        for i in 0..3 {
+            let entity = ecs.create_entity();
+            ecs.add_component_to_entity(entity, i as u32);
+        }
+
+        let arena = ecs.frame_arena();
+        {
+            let components = ecs.query_components::<u32>();
+            let mut values = arena.collect(components.iter().map(|(_, c)| *c.read().unwrap()));
+            values.sort_unstable();
+            assert_eq!(*values, vec![0, 1, 2]);
+        }
+        arena.reset();
+        assert_eq!(
+            arena.stats(),
+            FrameArenaStats {
+                allocated: 2,
+                reused: 0,
+                pooled: 2,
+            }
+        );
+
+        // The next frame takes the buffers of the last one, the entities need a new one
+        assert_eq!(ecs.query_entities::<u32>().len(), 3);
+        let kept = ecs.query_components::<u32>().into_vec();
+        assert_eq!(kept.len(), 3);
+        arena.reset();
+        assert_eq!((arena.stats().allocated, arena.stats().reused), (1, 1));
+    }
+}
@@ -1,4 +1,5 @@
 use super::traits::Component;
+#[cfg(feature = "renderer")]
 use crate::renderer;
 
 /// A component that stores the position of any object.
@@ -10,6 +11,7 @@ pub struct Pos3 {
 
 impl Component for Pos3 {}
 
+#[cfg(feature = "renderer")]
 impl renderer::traits::Pos for Pos3 {
     fn get_pos(&self) -> cgmath::Vector3<f32> {
         self.pos
@@ -38,6 +40,18 @@ impl Pos3 {
     }
 }
 
+/// A component that stores the linear velocity of an object.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Velocity(pub cgmath::Vector3<f32>);
+
+impl Component for Velocity {}
+
+impl Default for Velocity {
+    fn default() -> Self {
+        Self(cgmath::Vector3::new(0.0, 0.0, 0.0))
+    }
+}
+
 // impl From<&[f32; 3]> for cgmath::Vector3<f32> {
 //     fn from(val: &[f32; 3]) -> Self {
 //         cgmath::Vector3::new(val[0], val[1], val[2])
@@ -65,6 +79,239 @@ pub enum Camera {
 
 impl Component for Camera {}
 
+crate::impl_marker!(Camera, "is the camera of the renderer", requires[Pos3]);
+
+/// A component that stores the projection of a camera, add it to the camera entity.
+/// The camera uses the default lens without one.
+///
+/// The field of view moves towards its target over time, e.g. to zoom in when aiming down the sights.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CameraLens {
+    /// The current vertical field of view.
+    fov: cgmath::Deg<f32>,
+    target_fov: cgmath::Deg<f32>,
+    /// How quickly the field of view reaches its target, the remaining difference halves
+    /// about every `0.7 / fov_sharpness` seconds.
+    pub fov_sharpness: f32,
+    znear: f32,
+    zfar: f32,
+}
+
+impl Component for CameraLens {}
+
+impl Default for CameraLens {
+    fn default() -> Self {
+        Self::new(cgmath::Deg(45.0), 0.1, 100.0)
+    }
+}
+
+impl CameraLens {
+    /// The field of view is clamped to this range.
+    pub const FOV_RANGE: (f32, f32) = (1.0, 170.0);
+
+    pub fn new(fov: cgmath::Deg<f32>, znear: f32, zfar: f32) -> Self {
+        let mut lens = Self {
+            fov,
+            target_fov: fov,
+            fov_sharpness: 12.0,
+            znear: 0.1,
+            zfar: 100.0,
+        };
+        lens.set_fov(fov);
+        lens.set_planes(znear, zfar);
+        lens
+    }
+
+    pub fn fov(&self) -> cgmath::Deg<f32> {
+        self.fov
+    }
+
+    pub fn target_fov(&self) -> cgmath::Deg<f32> {
+        self.target_fov
+    }
+
+    /// Set the field of view at once.
+    pub fn set_fov(&mut self, fov: cgmath::Deg<f32>) {
+        self.fov = cgmath::Deg(fov.0.clamp(Self::FOV_RANGE.0, Self::FOV_RANGE.1));
+        self.target_fov = self.fov;
+    }
+
+    /// Move the field of view smoothly towards a target.
+    pub fn zoom_to(&mut self, fov: cgmath::Deg<f32>) {
+        self.target_fov = cgmath::Deg(fov.0.clamp(Self::FOV_RANGE.0, Self::FOV_RANGE.1));
+    }
+
+    pub fn znear(&self) -> f32 {
+        self.znear
+    }
+
+    pub fn zfar(&self) -> f32 {
+        self.zfar
+    }
+
+    /// Set the clipping planes, the near plane is kept in front of the camera and before the far plane.
+    pub fn set_planes(&mut self, znear: f32, zfar: f32) {
+        self.znear = znear.max(1e-4);
+        self.zfar = zfar.max(self.znear * 2.0);
+    }
+
+    /// Advance the field of view towards its target.
+    ///
+    /// # Arguments
+    ///
+    /// * `dt` - The delta time in seconds.
+    pub fn update(&mut self, dt: f32) {
+        let difference = self.target_fov.0 - self.fov.0;
+        if difference.abs() < 1e-3 {
+            self.fov = self.target_fov;
+            return;
+        }
+        self.fov.0 += difference * (1.0 - (-self.fov_sharpness * dt).exp());
+    }
+}
+
+/// A component that turns the camera around an entity, add it to the camera entity next to its `Camera`.
+/// The mouse orbits the camera around the target and the wheel zooms it, taking over the controls of the `Camera`.
+///
+/// The yaw and the pitch are the ones of the view like for the other cameras, a negative pitch looks down
+/// at the target from above. The camera is pulled in front of the colliders between it and the target
+/// when `collide` is set, so walls do not hide the target.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OrbitCameraController {
+    pub target: super::Entity,
+    /// The point orbited relative to the position of the target, e.g. above its feet.
+    pub target_offset: cgmath::Vector3<f32>,
+    /// The distance to the point orbited before it is shortened by the colliders.
+    pub distance: f32,
+    pub distance_range: (f32, f32),
+    pub yaw: cgmath::Deg<f32>,
+    pub pitch: cgmath::Deg<f32>,
+    pub pitch_range: (cgmath::Deg<f32>, cgmath::Deg<f32>),
+    /// The range of the yaw, `None` to orbit all around.
+    pub yaw_range: Option<(cgmath::Deg<f32>, cgmath::Deg<f32>)>,
+    pub sensitivity: f32,
+    pub collide: bool,
+    /// The gap kept between the camera and a collider in the way.
+    pub collision_margin: f32,
+}
+
+impl Component for OrbitCameraController {}
+
+impl OrbitCameraController {
+    pub fn new(target: super::Entity, distance: f32) -> Self {
+        let mut orbit = Self {
+            target,
+            target_offset: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            distance,
+            distance_range: (1.0, 50.0),
+            yaw: cgmath::Deg(-90.0),
+            pitch: cgmath::Deg(-20.0),
+            pitch_range: (cgmath::Deg(-80.0), cgmath::Deg(60.0)),
+            yaw_range: None,
+            sensitivity: 0.3,
+            collide: true,
+            collision_margin: 0.2,
+        };
+        orbit.clamp();
+        orbit
+    }
+
+    pub fn with_target_offset(mut self, target_offset: cgmath::Vector3<f32>) -> Self {
+        self.target_offset = target_offset;
+        self
+    }
+
+    pub fn with_distance_range(mut self, min: f32, max: f32) -> Self {
+        self.distance_range = (min, max);
+        self.clamp();
+        self
+    }
+
+    pub fn with_angles(mut self, yaw: cgmath::Deg<f32>, pitch: cgmath::Deg<f32>) -> Self {
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.clamp();
+        self
+    }
+
+    pub fn with_pitch_range(mut self, min: cgmath::Deg<f32>, max: cgmath::Deg<f32>) -> Self {
+        self.pitch_range = (min, max);
+        self.clamp();
+        self
+    }
+
+    pub fn with_yaw_range(mut self, min: cgmath::Deg<f32>, max: cgmath::Deg<f32>) -> Self {
+        self.yaw_range = Some((min, max));
+        self.clamp();
+        self
+    }
+
+    pub fn with_sensitivity(mut self, sensitivity: f32) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    pub fn with_collision(mut self, collide: bool) -> Self {
+        self.collide = collide;
+        self
+    }
+
+    /// Turn the camera around the target, the angles are kept in their ranges.
+    pub fn rotate(&mut self, yaw: cgmath::Deg<f32>, pitch: cgmath::Deg<f32>) {
+        self.yaw += yaw;
+        self.pitch += pitch;
+        self.clamp();
+    }
+
+    /// Scale the distance to the target, the distance is kept in its range.
+    pub fn zoom(&mut self, factor: f32) {
+        self.distance *= factor;
+        self.clamp();
+    }
+
+    /// Keep the angles and the distance in their ranges, a free yaw is wrapped to a turn.
+    pub fn clamp(&mut self) {
+        // The view straight up or down has no yaw
+        let pitch_limit = 89.0;
+        let (min, max) = (self.pitch_range.0 .0, self.pitch_range.1 .0);
+        self.pitch.0 = self
+            .pitch
+            .0
+            .clamp(min.min(max), max.max(min))
+            .clamp(-pitch_limit, pitch_limit);
+        self.yaw.0 = match self.yaw_range {
+            Some((min, max)) => self.yaw.0.clamp(min.0.min(max.0), max.0.max(min.0)),
+            None => self.yaw.0.rem_euclid(360.0),
+        };
+        let (min, max) = self.distance_range;
+        self.distance = self.distance.clamp(min.min(max).max(0.0), max.max(min));
+    }
+
+    /// Get the normalized direction the camera looks at the target from.
+    pub fn forward(&self) -> cgmath::Vector3<f32> {
+        let (sin_pitch, cos_pitch) = cgmath::Rad::from(self.pitch).0.sin_cos();
+        let (sin_yaw, cos_yaw) = cgmath::Rad::from(self.yaw).0.sin_cos();
+        cgmath::Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw)
+    }
+
+    /// Get the point orbited for a position of the target.
+    pub fn pivot(&self, target: cgmath::Vector3<f32>) -> cgmath::Vector3<f32> {
+        target + self.target_offset
+    }
+
+    /// Get the distance of the camera, shortened in front of a collider in the way.
+    ///
+    /// # Arguments
+    ///
+    /// * `hit` - The distance from the point orbited to the nearest collider towards the camera, if any.
+    pub fn collided_distance(&self, hit: Option<f32>) -> f32 {
+        match hit.filter(|_| self.collide) {
+            Some(hit) => (hit - self.collision_margin).clamp(0.0, self.distance),
+            None => self.distance,
+        }
+    }
+}
+
 /// A component that stores the model type.
 #[derive(Debug, Copy, Clone)]
 pub enum Model<'a> {
@@ -74,7 +321,18 @@ pub enum Model<'a> {
 
 impl Component for Model<'static> {}
 
+impl<'a> Model<'a> {
+    /// Get the path of the .obj, .gltf or .glb file, the asset the model is loaded from.
+    pub fn obj_path(&self) -> &'a str {
+        let (Self::Dynamic { obj_path } | Self::Static { obj_path }) = *self;
+        obj_path
+    }
+}
+
+crate::impl_marker!(Model<'static>, "is loaded and drawn by the renderer", requires [Name, Pos3]);
+
 /// A component that stores the name of an object.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Name(pub &'static str);
 
 impl Component for Name {}
@@ -107,10 +365,22 @@ pub enum Light {
         color: [f32; 3],
         intensity: f32,
     },
+    /// A rectangular area light that emits towards its direction, for soft studio-like lighting.
+    /// The light fades out at `radius` from the rect.
+    Rect {
+        width: f32,
+        height: f32,
+        direction: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        radius: f32,
+    },
 }
 
 impl Component for Light {}
 
+crate::impl_marker!(Light, "lights the scene", requires[Pos3]);
+
 /// A component that stores the scale of an object.
 #[derive(Debug, Copy, Clone)]
 pub enum Scale {
@@ -145,6 +415,7 @@ impl AABB {
     }
 }
 
+#[cfg(feature = "renderer")]
 impl renderer::traits::Collider for AABB {
     fn intersects(&self, other: &AABB) -> bool {
         self.min.x <= other.max.x
@@ -173,4 +444,101 @@ impl Collider {
     pub fn new(min: cgmath::Vector3<f32>, max: cgmath::Vector3<f32>) -> Self {
         Self(AABB::new(min, max))
     }
+
+    /// Get the corners of the box, relative to the position of the entity.
+    pub fn bounds(&self) -> (cgmath::Vector3<f32>, cgmath::Vector3<f32>) {
+        (self.0.min, self.0.max)
+    }
+}
+
+/// The axis-aligned bounds of the model of an entity, relative to its position.
+/// The renderer adds it when the model is loaded.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Bounds {
+    pub min: cgmath::Vector3<f32>,
+    pub max: cgmath::Vector3<f32>,
+}
+
+impl Component for Bounds {}
+
+impl Bounds {
+    pub fn new(min: cgmath::Vector3<f32>, max: cgmath::Vector3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// Get the bounds of points, or `None` if there are none.
+    pub fn from_points(points: impl IntoIterator<Item = cgmath::Vector3<f32>>) -> Option<Self> {
+        points.into_iter().fold(None, |bounds, p| match bounds {
+            None => Some(Self::new(p, p)),
+            Some(Self { min, max }) => Some(Self::new(
+                cgmath::Vector3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                cgmath::Vector3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+            )),
+        })
+    }
+
+    pub fn center(&self) -> cgmath::Vector3<f32> {
+        (self.min + self.max) / 2.0
+    }
+
+    /// Get the radius of the sphere around the box.
+    pub fn radius(&self) -> f32 {
+        use cgmath::InnerSpace;
+        (self.max - self.min).magnitude() / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+
+    #[test]
+    fn test_camera_lens_transition() {
+        let mut lens = CameraLens::new(cgmath::Deg(70.0), 0.0, -1.0);
+        assert!(lens.znear() > 0.0 && lens.zfar() > lens.znear());
+
+        lens.zoom_to(cgmath::Deg(30.0));
+        lens.update(0.05);
+        let fov = lens.fov().0;
+        assert!(fov < 70.0 && fov > 30.0);
+        for _ in 0..100 {
+            lens.update(0.05);
+        }
+        assert_eq!(lens.fov(), cgmath::Deg(30.0));
+
+        lens.set_fov(cgmath::Deg(500.0));
+        assert_eq!(lens.fov(), cgmath::Deg(CameraLens::FOV_RANGE.1));
+        assert_eq!(lens.target_fov(), lens.fov());
+    }
+
+    #[test]
+    fn test_orbit_camera_limits() {
+        let mut orbit = OrbitCameraController::new(crate::ecs::Entity(1), 100.0)
+            .with_distance_range(2.0, 10.0)
+            .with_pitch_range(cgmath::Deg(-60.0), cgmath::Deg(10.0));
+        assert_eq!(orbit.distance, 10.0);
+
+        orbit.rotate(cgmath::Deg(400.0), cgmath::Deg(-90.0));
+        assert_eq!(orbit.pitch, cgmath::Deg(-60.0));
+        assert!((orbit.yaw.0 - 310.0).abs() < 1e-4);
+        orbit.zoom(0.1);
+        assert_eq!(orbit.distance, 2.0);
+
+        // Looking down at the target from above
+        assert!(orbit.forward().y < 0.0);
+        assert!((orbit.forward().magnitude() - 1.0).abs() < 1e-5);
+
+        // A limited yaw stops at its ends
+        orbit = orbit.with_yaw_range(cgmath::Deg(-45.0), cgmath::Deg(45.0));
+        orbit.rotate(cgmath::Deg(-100.0), cgmath::Deg(0.0));
+        assert_eq!(orbit.yaw, cgmath::Deg(-45.0));
+
+        // The camera stops in front of a collider in the way
+        assert_eq!(orbit.collided_distance(None), 2.0);
+        assert!((orbit.collided_distance(Some(1.0)) - 0.8).abs() < 1e-5);
+        assert_eq!(orbit.collided_distance(Some(5.0)), 2.0);
+        orbit.collide = false;
+        assert_eq!(orbit.collided_distance(Some(1.0)), 2.0);
+    }
 }
@@ -0,0 +1,400 @@
+use super::components::Pos3;
+use super::traits::Component;
+use super::{Entity, Manager};
+use cgmath::{One, Quaternion, Rotation};
+use log::warn;
+use std::collections::{HashMap, HashSet};
+
+/// A component that makes the entity a child of another entity.
+/// The children of an entity are found with `children`, an entity without a parent is a root.
+/// The `Pos3` of a child follows its parent, see `propagate_transforms`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Parent(pub Entity);
+
+impl Component for Parent {}
+
+/// A component with the position and the rotation of a child relative to its parent.
+/// The `Pos3` of the child stays its world transform, computed from this one and the parent.
+///
+/// It is added from where the child is the first time its transform is propagated,
+/// and it is changed when something else moves the `Pos3` of the child, e.g. the editor or the physics.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LocalPos3 {
+    pub local: Pos3,
+    /// The world transform written by the last propagation.
+    world: Option<Pos3>,
+}
+
+impl Component for LocalPos3 {}
+
+impl LocalPos3 {
+    pub fn new(local: Pos3) -> Self {
+        Self { local, world: None }
+    }
+}
+
+/// A component that keeps a render-only entity, e.g. a hat, a weapon or a light, at a fixed offset
+/// from another entity, usually a physics body. Unlike a child, the attached entity can not be moved
+/// on its own, and it is left out of the physics step, so it never collides.
+///
+/// It is placed with the children by `propagate_transforms`, after the physics step moved the body,
+/// and it replaces the `Parent` of the entity if it has both.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AttachedTo {
+    pub entity: Entity,
+    /// The position and the rotation relative to the entity attached to.
+    pub offset: Pos3,
+}
+
+impl Component for AttachedTo {}
+
+impl AttachedTo {
+    pub fn new(entity: Entity, offset: Pos3) -> Self {
+        Self { entity, offset }
+    }
+}
+
+/// The entities whose parents form a cycle, so it is only logged when it changes.
+struct HierarchyCycles(Vec<Entity>);
+
+/// Get the parent of an entity.
+pub fn parent(ecs: &Manager, entity: Entity) -> Option<Entity> {
+    ecs.get_component_from_entity::<Parent>(entity)
+        .map(|parent| parent.read().unwrap().0)
+}
+
+/// Get the children of an entity in the order of the entities.
+pub fn children(ecs: &Manager, entity: Entity) -> Vec<Entity> {
+    let mut children = ecs
+        .get_all_components_of_type::<Parent>()
+        .into_iter()
+        .filter(|(_, parent)| parent.read().unwrap().0 == entity)
+        .map(|(child, _)| child)
+        .collect::<Vec<_>>();
+    children.sort_by_key(|child| child.id());
+    children
+}
+
+/// Get the entities without a parent, or whose parent was removed, in the order of the entities.
+pub fn roots(ecs: &Manager) -> Vec<Entity> {
+    let entities = ecs.iter_entities().collect::<Vec<_>>();
+    let mut roots = entities
+        .iter()
+        .copied()
+        .filter(|entity| parent(ecs, *entity).is_none_or(|parent| !entities.contains(&parent)))
+        .collect::<Vec<_>>();
+    roots.sort_by_key(|entity| entity.id());
+    roots
+}
+
+/// Get the children of an entity, their children and so on, parents before their children.
+pub fn descendants(ecs: &Manager, entity: Entity) -> Vec<Entity> {
+    let mut descendants = Vec::new();
+    let mut pending = vec![entity];
+    while let Some(next) = pending.pop() {
+        for child in children(ecs, next) {
+            // A broken hierarchy must not loop forever
+            if child != entity && !descendants.contains(&child) {
+                descendants.push(child);
+                pending.push(child);
+            }
+        }
+    }
+    descendants
+}
+
+/// Check if an entity is the parent of another entity, or the parent of one of its ancestors.
+pub fn is_ancestor(ecs: &Manager, ancestor: Entity, entity: Entity) -> bool {
+    let mut current = entity;
+    let mut visited = vec![entity];
+    while let Some(parent) = parent(ecs, current) {
+        if parent == ancestor {
+            return true;
+        }
+        if visited.contains(&parent) {
+            return false;
+        }
+        visited.push(parent);
+        current = parent;
+    }
+    false
+}
+
+/// Change the parent of an entity.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `child` - The entity to move.
+/// * `parent` - The new parent, or `None` to make the entity a root.
+///
+/// # Returns
+///
+/// An error if the parent is the entity itself or one of its descendants.
+pub fn set_parent(ecs: &Manager, child: Entity, parent: Option<Entity>) -> anyhow::Result<()> {
+    match parent {
+        Some(parent) if parent == child || is_ancestor(ecs, child, parent) => {
+            anyhow::bail!(
+                "Entity {} can not be a child of its descendant {}",
+                child.id(),
+                parent.id()
+            )
+        }
+        Some(parent) => ecs.add_component_to_entity(child, Parent(parent)),
+        None => {
+            ecs.remove_component_from_entity::<Parent>(child);
+        }
+    }
+    // The child keeps its place, it is relative to the new parent from the next propagation
+    ecs.remove_component_from_entity::<LocalPos3>(child);
+    Ok(())
+}
+
+/// Place a transform relative to a parent in the world.
+fn compose(parent: &Pos3, local: &Pos3) -> Pos3 {
+    let rotation = parent.rot.unwrap_or_else(Quaternion::one);
+    Pos3 {
+        pos: parent.pos + rotation.rotate_vector(local.pos),
+        rot: match (parent.rot, local.rot) {
+            (None, None) => None,
+            _ => Some(rotation * local.rot.unwrap_or_else(Quaternion::one)),
+        },
+    }
+}
+
+/// Get a world transform relative to a parent, the inverse of `compose`.
+fn relative(parent: &Pos3, world: &Pos3) -> Pos3 {
+    let inverse = parent.rot.unwrap_or_else(Quaternion::one).invert();
+    Pos3 {
+        pos: inverse.rotate_vector(world.pos - parent.pos),
+        rot: match (parent.rot, world.rot) {
+            (None, None) => None,
+            _ => Some(inverse * world.rot.unwrap_or_else(Quaternion::one)),
+        },
+    }
+}
+
+/// Move the children with their parents: the `Pos3` of each child becomes its `LocalPos3`
+/// placed by the `Pos3` of its parent, and the `Pos3` of each attached entity its `AttachedTo` offset,
+/// the parents before their children.
+/// The renderer runs it each frame before the instances of the models are updated.
+///
+/// A child of an entity without a `Pos3` keeps its own, and the entities whose parents form a cycle
+/// are skipped with a warning.
+pub fn propagate_transforms(ecs: &Manager) {
+    let attachments = ecs
+        .get_all_components_of_type::<AttachedTo>()
+        .into_iter()
+        .map(|(entity, attached)| (entity, *attached.read().unwrap()))
+        .collect::<HashMap<_, _>>();
+    let mut children = HashMap::<Entity, Vec<Entity>>::new();
+    for (child, parent) in ecs.get_all_components_of_type::<Parent>() {
+        if !attachments.contains_key(&child) {
+            children
+                .entry(parent.read().unwrap().0)
+                .or_default()
+                .push(child);
+        }
+    }
+    for (child, attached) in &attachments {
+        children.entry(attached.entity).or_default().push(*child);
+    }
+
+    let mut pending = children
+        .keys()
+        .copied()
+        .filter(|entity| !attachments.contains_key(entity) && parent(ecs, *entity).is_none())
+        .collect::<Vec<_>>();
+    let mut visited = HashSet::new();
+    while let Some(entity) = pending.pop() {
+        let world = ecs
+            .get_component_from_entity::<Pos3>(entity)
+            .map(|pos| *pos.read().unwrap());
+        for child in children.get(&entity).into_iter().flatten() {
+            if !visited.insert(*child) {
+                continue;
+            }
+            match (&world, attachments.get(child)) {
+                (Some(world), Some(attached)) => attach(ecs, *child, world, attached),
+                (Some(world), None) => propagate(ecs, *child, world),
+                (None, _) => {}
+            }
+            pending.push(*child);
+        }
+    }
+
+    // The children never reached from a root have a cycle above them
+    let mut cycles = children
+        .into_values()
+        .flatten()
+        .filter(|child| !visited.contains(child))
+        .collect::<Vec<_>>();
+    cycles.sort_by_key(|entity| entity.id());
+    let known = ecs
+        .get_resource::<HierarchyCycles>()
+        .is_some_and(|known| known.read().unwrap().0 == cycles);
+    if !known {
+        if !cycles.is_empty() {
+            warn!(
+                "[Hierarchy] The parents of the entities {:?} form a cycle, they are not moved with them",
+                cycles.iter().map(Entity::id).collect::<Vec<_>>()
+            );
+        }
+        ecs.insert_resource(HierarchyCycles(cycles));
+    }
+}
+
+/// Place an attached entity at its offset from the world transform of the entity it is attached to.
+fn attach(ecs: &Manager, entity: Entity, target: &Pos3, attached: &AttachedTo) {
+    let world = compose(target, &attached.offset);
+    match ecs.get_component_from_entity::<Pos3>(entity) {
+        Some(pos) => *pos.write().unwrap() = world,
+        None => ecs.add_component_to_entity(entity, world),
+    }
+}
+
+/// Place a child by the world transform of its parent.
+fn propagate(ecs: &Manager, child: Entity, parent: &Pos3) {
+    let Some(pos) = ecs.get_component_from_entity::<Pos3>(child) else {
+        return;
+    };
+    let mut pos = pos.write().unwrap();
+    let local = match ecs.get_component_from_entity::<LocalPos3>(child) {
+        Some(local) => local,
+        None => {
+            ecs.add_component_to_entity(child, LocalPos3::new(relative(parent, &pos)));
+            ecs.get_component_from_entity::<LocalPos3>(child).unwrap()
+        }
+    };
+    let mut local = local.write().unwrap();
+
+    // Moved by something else since the last propagation, it stays where it was put
+    if local.world.is_some_and(|world| world != *pos) {
+        local.local = relative(parent, &pos);
+    }
+    *pos = compose(parent, &local.local);
+    local.world = Some(*pos);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, InnerSpace, Rotation3, Vector3};
+
+    #[test]
+    fn test_hierarchy() {
+        let ecs = Manager::default();
+        let player = ecs.create_entity();
+        let arm = ecs.create_entity();
+        let weapon = ecs.create_entity();
+        set_parent(&ecs, arm, Some(player)).unwrap();
+        set_parent(&ecs, weapon, Some(arm)).unwrap();
+
+        assert_eq!(roots(&ecs), vec![player]);
+        assert_eq!(children(&ecs, player), vec![arm]);
+        assert_eq!(descendants(&ecs, player), vec![arm, weapon]);
+        assert!(is_ancestor(&ecs, player, weapon));
+
+        // Cycles are rejected
+        assert!(set_parent(&ecs, player, Some(weapon)).is_err());
+        assert!(set_parent(&ecs, player, Some(player)).is_err());
+
+        set_parent(&ecs, weapon, None).unwrap();
+        assert_eq!(roots(&ecs), vec![player, weapon]);
+        assert_eq!(parent(&ecs, arm), Some(player));
+    }
+
+    #[test]
+    fn test_propagate_transforms() {
+        let ecs = Manager::default();
+        let vehicle = ecs.create_entity();
+        ecs.add_component_to_entity(vehicle, Pos3::new(Vector3::new(10.0, 0.0, 0.0)));
+        let light = ecs.create_entity();
+        ecs.add_component_to_entity(light, Pos3::new(Vector3::new(11.0, 2.0, 0.0)));
+        set_parent(&ecs, light, Some(vehicle)).unwrap();
+        let world = |entity| {
+            *ecs.get_component_from_entity::<Pos3>(entity)
+                .unwrap()
+                .read()
+                .unwrap()
+        };
+
+        // The child keeps its place when it is parented
+        propagate_transforms(&ecs);
+        assert!((world(light).pos - Vector3::new(11.0, 2.0, 0.0)).magnitude() < 1e-5);
+
+        // And turns and moves with its parent
+        *ecs.get_component_from_entity::<Pos3>(vehicle)
+            .unwrap()
+            .write()
+            .unwrap() = Pos3::with_rot(
+            Vector3::new(0.0, 0.0, 5.0),
+            Quaternion::from_angle_y(Deg(90.0)),
+        );
+        propagate_transforms(&ecs);
+        assert!((world(light).pos - Vector3::new(0.0, 2.0, 4.0)).magnitude() < 1e-5);
+        assert!(world(light).rot.is_some());
+
+        // A child moved by something else stays there relative to the parent
+        ecs.get_component_from_entity::<Pos3>(light)
+            .unwrap()
+            .write()
+            .unwrap()
+            .pos = Vector3::new(0.0, 3.0, 5.0);
+        propagate_transforms(&ecs);
+        let local = ecs.get_component_from_entity::<LocalPos3>(light).unwrap();
+        assert!((local.read().unwrap().local.pos - Vector3::new(0.0, 3.0, 0.0)).magnitude() < 1e-5);
+
+        // The entities in a cycle are left alone
+        ecs.add_component_to_entity(vehicle, Parent(light));
+        propagate_transforms(&ecs);
+        assert!((world(vehicle).pos - Vector3::new(0.0, 0.0, 5.0)).magnitude() < 1e-5);
+        let cycles = ecs.get_resource::<HierarchyCycles>().unwrap();
+        assert_eq!(cycles.read().unwrap().0, vec![vehicle, light]);
+    }
+
+    #[test]
+    fn test_attached_to() {
+        let ecs = Manager::default();
+        let player = ecs.create_entity();
+        ecs.add_component_to_entity(
+            player,
+            Pos3::with_rot(
+                Vector3::new(2.0, 0.0, 0.0),
+                Quaternion::from_angle_y(Deg(180.0)),
+            ),
+        );
+        let weapon = ecs.create_entity();
+        ecs.add_component_to_entity(
+            weapon,
+            AttachedTo::new(player, Pos3::new(Vector3::new(0.5, 1.0, 0.0))),
+        );
+        // Attached to the weapon in turn, evaluated after it
+        let flashlight = ecs.create_entity();
+        ecs.add_component_to_entity(flashlight, Pos3::default());
+        ecs.add_component_to_entity(
+            flashlight,
+            AttachedTo::new(weapon, Pos3::new(Vector3::new(0.0, 0.0, 1.0))),
+        );
+
+        propagate_transforms(&ecs);
+        let world = |entity| {
+            ecs.get_component_from_entity::<Pos3>(entity)
+                .unwrap()
+                .read()
+                .unwrap()
+                .pos
+        };
+        assert!((world(weapon) - Vector3::new(1.5, 1.0, 0.0)).magnitude() < 1e-5);
+        assert!((world(flashlight) - Vector3::new(1.5, 1.0, -1.0)).magnitude() < 1e-5);
+
+        // Moving the weapon on its own does not detach it
+        ecs.get_component_from_entity::<Pos3>(weapon)
+            .unwrap()
+            .write()
+            .unwrap()
+            .pos = Vector3::new(9.0, 9.0, 9.0);
+        propagate_transforms(&ecs);
+        assert!((world(weapon) - Vector3::new(1.5, 1.0, 0.0)).magnitude() < 1e-5);
+    }
+}
@@ -0,0 +1,69 @@
+use super::task::TaskQueue;
+use super::Manager;
+use std::sync::Arc;
+
+/// Sends the events of the game from outside the systems, e.g. from an egui window or another thread,
+/// where the manager is not at hand or is locked.
+///
+/// An intent can be of any type, it is sent to the manager as an event of its type at the start of the
+/// next update, so a system takes it with `Manager::drain_events` like the events sent by the systems.
+/// The sender is cheap to clone and can be moved into the window closures.
+#[derive(Clone)]
+pub struct IntentSender {
+    queue: TaskQueue,
+}
+
+impl IntentSender {
+    /// Send an intent, it is delivered in the order it was sent with the intents of other types.
+    pub fn send<T: 'static + Send + Sync>(&self, intent: T) {
+        self.queue
+            .lock()
+            .unwrap()
+            .push(Box::new(move |ecs| ecs.send_event(intent)));
+    }
+}
+
+impl std::fmt::Debug for IntentSender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IntentSender").finish_non_exhaustive()
+    }
+}
+
+impl Manager {
+    /// Get a sender of intents to this manager, they are delivered with the task callbacks on each update.
+    pub fn intent_sender(&self) -> IntentSender {
+        IntentSender {
+            queue: Arc::clone(&self.tasks),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Shoot {
+        power: f32,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct OpenDoor(u32);
+
+    #[test]
+    fn test_intents_are_delivered_on_update() {
+        let ecs = Manager::default();
+        let intents = ecs.intent_sender();
+        let from_thread = intents.clone();
+        std::thread::spawn(move || from_thread.send(OpenDoor(3)))
+            .join()
+            .unwrap();
+        intents.send(Shoot { power: 0.5 });
+
+        // Nothing arrives before the update
+        assert!(!ecs.has_events::<Shoot>());
+        assert_eq!(ecs.run_task_callbacks(), 2);
+        assert_eq!(ecs.drain_events::<Shoot>(), vec![Shoot { power: 0.5 }]);
+        assert_eq!(ecs.drain_events::<OpenDoor>(), vec![OpenDoor(3)]);
+    }
+}
@@ -1,10 +1,17 @@
+pub mod arena;
 pub mod components;
+pub mod hierarchy;
+pub mod intent;
+pub mod query;
+pub mod resource;
+pub mod task;
 pub mod traits;
 pub mod utils;
+pub mod validation;
 
 use std::any::{Any, TypeId};
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -17,20 +24,61 @@ impl Entity {
 }
 
 type EntityStore = HashMap<Entity, HashMap<TypeId, Arc<RwLock<dyn Any + Send + Sync>>>>;
+type EventStore = HashMap<TypeId, Box<dyn Any + Send + Sync>>;
+
+/// The bookkeeping stored with every component besides its value: the reference counts of the `Arc`,
+/// the `RwLock` and the entry of the map of the entity.
+const COMPONENT_OVERHEAD: usize = 2 * std::mem::size_of::<usize>()
+    + std::mem::size_of::<RwLock<()>>()
+    + std::mem::size_of::<(TypeId, Arc<RwLock<dyn Any + Send + Sync>>)>();
+
+/// The name and the size of a component type.
+#[derive(Debug, Copy, Clone)]
+struct ComponentType {
+    name: &'static str,
+    size: usize,
+}
+
+/// The number and the estimated memory of the components of a type.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ComponentStats {
+    pub name: &'static str,
+    pub count: usize,
+    /// The memory of the components and their bookkeeping, without the heap memory they own.
+    pub bytes: usize,
+}
 
 // TODO add a world with scenes and scene switching
 
 /// Entity component system manager.
 pub struct Manager {
     entities: RwLock<EntityStore>,
+    events: RwLock<EventStore>,
+    component_types: RwLock<HashMap<TypeId, ComponentType>>,
     next_entity: AtomicU32,
+    spawned: AtomicU64,
+    despawned: AtomicU64,
+    tasks: task::TaskQueue,
+    arena: arena::FrameArena,
+    queries: query::QueryCache,
+    validation: RwLock<validation::Validation>,
+    resources: resource::ResourceStore,
 }
 
 impl Default for Manager {
     fn default() -> Self {
         Manager {
             entities: RwLock::new(HashMap::new()),
+            events: RwLock::new(HashMap::new()),
+            component_types: RwLock::new(HashMap::new()),
             next_entity: AtomicU32::new(0),
+            spawned: AtomicU64::new(0),
+            despawned: AtomicU64::new(0),
+            tasks: task::TaskQueue::default(),
+            arena: arena::FrameArena::default(),
+            queries: query::QueryCache::default(),
+            validation: RwLock::default(),
+            resources: resource::ResourceStore::default(),
         }
     }
 }
@@ -44,7 +92,16 @@ impl Manager {
     pub fn new(capacity: usize) -> Self {
         Manager {
             entities: RwLock::new(HashMap::with_capacity(capacity)),
+            events: RwLock::new(HashMap::new()),
+            component_types: RwLock::new(HashMap::new()),
             next_entity: AtomicU32::new(0),
+            spawned: AtomicU64::new(0),
+            despawned: AtomicU64::new(0),
+            tasks: task::TaskQueue::default(),
+            arena: arena::FrameArena::default(),
+            queries: query::QueryCache::default(),
+            validation: RwLock::default(),
+            resources: resource::ResourceStore::default(),
         }
     }
 
@@ -52,13 +109,68 @@ impl Manager {
     pub fn create_entity(&self) -> Entity {
         let id = self.next_entity.fetch_add(1, Ordering::SeqCst);
         let entity = Entity(id);
-        self.entities
-            .write()
-            .unwrap()
-            .insert(entity, HashMap::new());
+        let mut entities = self.entities.write().unwrap();
+        entities.insert(entity, HashMap::new());
+        self.queries.invalidate(&[]);
+        drop(entities);
+        self.spawned.fetch_add(1, Ordering::Relaxed);
         entity
     }
 
+    /// Create a new entity with the components of a bundle and return it.
+    /// The entity is validated, see `check_spawned`.
+    pub fn spawn_bundle(&self, bundle: impl traits::Bundle) -> Entity {
+        let entity = self.create_entity();
+        bundle.insert_into(self, entity);
+        self.check_spawned(entity);
+        entity
+    }
+
+    /// Create an entity with a specific id, e.g. when restoring a saved world.
+    /// Nothing happens if the entity exists, the components of the entity are kept.
+    pub fn create_entity_with_id(&self, entity: Entity) {
+        self.next_entity
+            .fetch_max(entity.0.saturating_add(1), Ordering::SeqCst);
+        let mut entities = self.entities.write().unwrap();
+        if let std::collections::hash_map::Entry::Vacant(entry) = entities.entry(entity) {
+            entry.insert(HashMap::new());
+            self.queries.invalidate(&[]);
+            self.spawned.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Remove an entity and all of its components.
+    ///
+    /// # Returns
+    ///
+    /// True if the entity existed.
+    pub fn remove_entity(&self, entity: Entity) -> bool {
+        let mut entities = self.entities.write().unwrap();
+        let Some(components) = entities.remove(&entity) else {
+            return false;
+        };
+        self.queries
+            .invalidate(&components.keys().copied().collect::<Vec<_>>());
+        self.despawned.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// Remove a component of a specific type from a specific entity.
+    ///
+    /// # Returns
+    ///
+    /// True if the entity had the component.
+    pub fn remove_component_from_entity<T: 'static + Send + Sync>(&self, entity: Entity) -> bool {
+        let mut entities = self.entities.write().unwrap();
+        let removed = entities
+            .get_mut(&entity)
+            .is_some_and(|components| components.remove(&TypeId::of::<T>()).is_some());
+        if removed {
+            self.queries.invalidate(&[TypeId::of::<T>()]);
+        }
+        removed
+    }
+
     /// Get the last entity created, or `None` if no entities have been created yet.
     pub fn get_last(&self) -> Option<Entity> {
         let current_idx = self.next_entity.load(Ordering::SeqCst);
@@ -75,12 +187,93 @@ impl Manager {
         self.entities.read().unwrap().len()
     }
 
+    /// Get the number of entities created and removed since the manager was created.
+    pub fn churn(&self) -> (u64, u64) {
+        (
+            self.spawned.load(Ordering::Relaxed),
+            self.despawned.load(Ordering::Relaxed),
+        )
+    }
+
     /// Add a component of a specific type to a specific entity.
     pub fn add_component_to_entity<T: 'static + Send + Sync>(&self, entity: Entity, component: T) {
         let mut entities = self.entities.write().unwrap();
         if let Some(components) = entities.get_mut(&entity) {
-            components.insert(TypeId::of::<T>(), Arc::new(RwLock::new(component)));
+            let replaced = components
+                .insert(TypeId::of::<T>(), Arc::new(RwLock::new(component)))
+                .is_some();
+            if !replaced {
+                self.queries.invalidate(&[TypeId::of::<T>()]);
+            }
+            self.component_types
+                .write()
+                .unwrap()
+                .entry(TypeId::of::<T>())
+                .or_insert_with(|| ComponentType {
+                    name: std::any::type_name::<T>(),
+                    size: std::mem::size_of::<T>(),
+                });
+        }
+    }
+
+    /// Get the number of entities having each component type, keyed by the type name.
+    /// It does not wait for the locks, so it can be used while panicking.
+    ///
+    /// # Returns
+    ///
+    /// The counts, or `None` if the entities are locked or poisoned.
+    pub fn component_counts(&self) -> Option<BTreeMap<&'static str, usize>> {
+        let entities = self.entities.try_read().ok()?;
+        let types = self.component_types.try_read().ok()?;
+
+        let mut counts = BTreeMap::new();
+        for type_id in entities.values().flat_map(|components| components.keys()) {
+            let name = types.get(type_id).map_or("<unknown>", |ty| ty.name);
+            *counts.entry(name).or_insert(0) += 1;
+        }
+        Some(counts)
+    }
+
+    /// Get the number and the estimated memory of the components of each type, sorted by name.
+    pub fn component_stats(&self) -> Vec<ComponentStats> {
+        let entities = self.entities.read().unwrap();
+        let types = self.component_types.read().unwrap();
+
+        let mut stats = HashMap::<TypeId, ComponentStats>::new();
+        for type_id in entities.values().flat_map(|components| components.keys()) {
+            let ty = types.get(type_id).copied().unwrap_or(ComponentType {
+                name: "<unknown>",
+                size: 0,
+            });
+            let entry = stats.entry(*type_id).or_insert(ComponentStats {
+                name: ty.name,
+                count: 0,
+                bytes: 0,
+            });
+            entry.count += 1;
+            entry.bytes += ty.size + COMPONENT_OVERHEAD;
         }
+
+        let mut stats = stats.into_values().collect::<Vec<_>>();
+        stats.sort_by_key(|stats| stats.name);
+        stats
+    }
+
+    /// Get the number of entities having each combination of component types, keyed by the sorted type names.
+    pub fn archetype_counts(&self) -> BTreeMap<Vec<&'static str>, usize> {
+        let entities = self.entities.read().unwrap();
+        let types = self.component_types.read().unwrap();
+
+        let mut counts = BTreeMap::new();
+        for components in entities.values() {
+            let mut archetype = components
+                .keys()
+                .map(|type_id| types.get(type_id).map_or("<unknown>", |ty| ty.name))
+                .collect::<Vec<_>>();
+            archetype.sort_unstable();
+            *counts.entry(archetype).or_insert(0) += 1;
+        }
+        counts
     }
 
     /// Get a component of a specific type for a specific entity.
@@ -101,6 +294,15 @@ impl Manager {
         })
     }
 
+    /// Check if an entity has a component of a specific type.
+    pub fn has_component<T: 'static + Send + Sync>(&self, entity: Entity) -> bool {
+        self.entities
+            .read()
+            .unwrap()
+            .get(&entity)
+            .is_some_and(|components| components.contains_key(&TypeId::of::<T>()))
+    }
+
     /// Get an iterator over the entities currently in the EntityManager.
     pub fn iter_entities(&self) -> impl Iterator<Item = Entity> + '_ {
         self.entities
@@ -112,11 +314,11 @@ impl Manager {
             .into_iter()
     }
 
-    /// Get all components of a specific type currently in the EntityManager.
-    pub fn get_all_components_of_type<T: 'static + Send + Sync>(
+    /// Call a function with each component of a specific type, the entities are locked meanwhile.
+    fn for_each_component<T: 'static + Send + Sync>(
         &self,
-    ) -> Vec<(Entity, Arc<RwLock<T>>)> {
-        let mut result: Vec<(Entity, Arc<RwLock<T>>)> = Vec::new();
+        mut f: impl FnMut(Entity, Arc<RwLock<T>>),
+    ) {
         let entities = self.entities.read().unwrap();
         for (entity, components) in entities.iter() {
             if let Some(component) = components.get(&TypeId::of::<T>()) {
@@ -124,25 +326,58 @@ impl Manager {
                 unsafe {
                     // SAFETY: We ensure that the component is of type T
                     let component_ptr = Arc::into_raw(component) as *const RwLock<T>;
-                    result.push((*entity, Arc::from_raw(component_ptr)));
+                    f(*entity, Arc::from_raw(component_ptr));
                 }
             }
         }
+    }
 
+    /// Get all components of a specific type currently in the EntityManager.
+    pub fn get_all_components_of_type<T: 'static + Send + Sync>(
+        &self,
+    ) -> Vec<(Entity, Arc<RwLock<T>>)> {
+        let mut result: Vec<(Entity, Arc<RwLock<T>>)> = Vec::new();
+        self.for_each_component::<T>(|entity, component| result.push((entity, component)));
         result
     }
 
     /// Get all entities that have a specific component.
+    /// The entities are cached until a component of the type is added or removed, see `query_filter`.
     pub fn get_entites_with_component<T: 'static + Send + Sync>(&self) -> Vec<Entity> {
-        let mut result: Vec<Entity> = Vec::new();
-        let entities = self.entities.read().unwrap();
-        for (entity, components) in entities.iter() {
-            if components.contains_key(&TypeId::of::<T>()) {
-                result.push(*entity);
-            }
-        }
+        self.query_filter(&query::ComponentFilter::of::<T>())
+            .to_vec()
+    }
 
-        result
+    /// Send an event of a specific type.
+    /// The event is stored until it is drained by a system.
+    pub fn send_event<T: 'static + Send + Sync>(&self, event: T) {
+        let mut events = self.events.write().unwrap();
+        events
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Vec::<T>::new()))
+            .downcast_mut::<Vec<T>>()
+            .expect("Event queue type mismatch")
+            .push(event);
+    }
+
+    /// Check if events of a specific type are pending, without taking them.
+    pub fn has_events<T: 'static + Send + Sync>(&self) -> bool {
+        self.events
+            .read()
+            .unwrap()
+            .get(&TypeId::of::<T>())
+            .and_then(|queue| queue.downcast_ref::<Vec<T>>())
+            .is_some_and(|queue| !queue.is_empty())
+    }
+
+    /// Take all pending events of a specific type in the order they were sent.
+    pub fn drain_events<T: 'static + Send + Sync>(&self) -> Vec<T> {
+        let mut events = self.events.write().unwrap();
+        events
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|queue| queue.downcast_mut::<Vec<T>>())
+            .map(std::mem::take)
+            .unwrap_or_default()
     }
 }
 
@@ -162,6 +397,33 @@ mod tests {
         assert_eq!(entity2, Entity(1));
     }
 
+    #[test]
+    fn test_component_counts() {
+        let manager = Manager::default();
+        let entity1 = manager.create_entity();
+        let entity2 = manager.create_entity();
+        manager.add_component_to_entity(entity1, TestComponent(1));
+        manager.add_component_to_entity(entity2, TestComponent(2));
+        manager.add_component_to_entity(entity2, 3u32);
+
+        let counts = manager.component_counts().unwrap();
+        assert_eq!(counts[std::any::type_name::<TestComponent>()], 2);
+        assert_eq!(counts["u32"], 1);
+
+        let stats = manager.component_stats();
+        let test = stats
+            .iter()
+            .find(|stats| stats.name == std::any::type_name::<TestComponent>())
+            .unwrap();
+        assert_eq!(test.count, 2);
+        assert_eq!(test.bytes, 2 * (4 + COMPONENT_OVERHEAD));
+        assert_eq!(manager.archetype_counts().len(), 2);
+
+        manager.remove_entity(entity1);
+        manager.create_entity_with_id(entity2);
+        assert_eq!(manager.churn(), (2, 1));
+    }
+
     #[test]
     fn test_add_and_get_component() {
         let manager = Manager::default();
@@ -287,6 +549,40 @@ mod tests {
         assert_eq!(manager.get_last().unwrap(), entity);
     }
 
+    #[test]
+    fn test_send_and_drain_events() {
+        let manager = Manager::default();
+        manager.send_event(TestComponent(1));
+        manager.send_event(TestComponent(2));
+
+        assert!(manager.has_events::<TestComponent>());
+        let events = manager.drain_events::<TestComponent>();
+        assert_eq!(events, vec![TestComponent(1), TestComponent(2)]);
+        assert!(manager.drain_events::<TestComponent>().is_empty());
+        assert!(!manager.has_events::<TestComponent>());
+    }
+
+    #[test]
+    fn test_remove_entity_and_component() {
+        let manager = Manager::default();
+        let entity1 = manager.create_entity();
+        let entity2 = manager.create_entity();
+        manager.add_component_to_entity(entity1, TestComponent(1));
+        manager.add_component_to_entity(entity2, TestComponent(2));
+
+        assert!(manager.remove_component_from_entity::<TestComponent>(entity1));
+        assert!(!manager.remove_component_from_entity::<TestComponent>(entity1));
+        assert!(manager.remove_entity(entity2));
+        assert!(!manager.remove_entity(entity2));
+        assert_eq!(manager.entity_count(), 1);
+        assert!(manager
+            .get_all_components_of_type::<TestComponent>()
+            .is_empty());
+
+        manager.create_entity_with_id(Entity(5));
+        assert_eq!(manager.create_entity(), Entity(6));
+    }
+
     #[test]
     fn test_get_last_multiple_entities() {
         let manager = Manager::default();
@@ -0,0 +1,108 @@
+use super::{Entity, Manager};
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// The component types an entity must have to match a query.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ComponentFilter {
+    /// The sorted types, so the same filter built in a different order is the same key.
+    types: Vec<TypeId>,
+}
+
+impl ComponentFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a filter matching the entities with a component of a specific type.
+    pub fn of<T: 'static + Send + Sync>() -> Self {
+        Self::new().with::<T>()
+    }
+
+    /// Require a component of a specific type.
+    pub fn with<T: 'static + Send + Sync>(mut self) -> Self {
+        let type_id = TypeId::of::<T>();
+        if let Err(i) = self.types.binary_search(&type_id) {
+            self.types.insert(i, type_id);
+        }
+        self
+    }
+
+    fn matches<V>(&self, components: &HashMap<TypeId, V>) -> bool {
+        self.types
+            .iter()
+            .all(|type_id| components.contains_key(type_id))
+    }
+}
+
+/// The entities matching each filter queried since the last change of its component types.
+#[derive(Debug, Default)]
+pub(crate) struct QueryCache {
+    results: RwLock<HashMap<ComponentFilter, Arc<[Entity]>>>,
+}
+
+impl QueryCache {
+    /// Drop the results of the filters requiring any of the component types, and of the empty filter
+    /// matching every entity. It is called with the entities locked for writing,
+    /// so a query can not cache an older result.
+    pub(crate) fn invalidate(&self, types: &[TypeId]) {
+        self.results.write().unwrap().retain(|filter, _| {
+            !filter.types.is_empty() && !filter.types.iter().any(|ty| types.contains(ty))
+        });
+    }
+}
+
+impl Manager {
+    /// Get the entities having all component types of a filter.
+    /// The result is cached until a component of one of the types is added or removed,
+    /// so repeated queries do not scan the entities.
+    pub fn query_filter(&self, filter: &ComponentFilter) -> Arc<[Entity]> {
+        if let Some(result) = self.queries.results.read().unwrap().get(filter) {
+            return Arc::clone(result);
+        }
+
+        // The entities stay locked until the result is cached, see `QueryCache::invalidate`
+        let entities = self.entities.read().unwrap();
+        let result = entities
+            .iter()
+            .filter(|(_, components)| filter.matches(components))
+            .map(|(entity, _)| *entity)
+            .collect::<Arc<[Entity]>>();
+        self.queries
+            .results
+            .write()
+            .unwrap()
+            .insert(filter.clone(), Arc::clone(&result));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_queries_are_invalidated() {
+        let ecs = Manager::default();
+        let a = ecs.create_entity();
+        let b = ecs.create_entity();
+        ecs.add_component_to_entity(a, 1u32);
+        ecs.add_component_to_entity(b, 2u32);
+        ecs.add_component_to_entity(b, 0.5f32);
+
+        let both = ComponentFilter::of::<f32>().with::<u32>();
+        assert_eq!(*ecs.query_filter(&both), [b]);
+        // Cached results are shared
+        let cached = ecs.query_filter(&ComponentFilter::new().with::<u32>().with::<f32>());
+        assert!(Arc::ptr_eq(&cached, &ecs.query_filter(&both)));
+
+        ecs.add_component_to_entity(a, 1.5f32);
+        assert_eq!(ecs.query_filter(&both).len(), 2);
+        ecs.remove_component_from_entity::<u32>(b);
+        assert_eq!(*ecs.query_filter(&both), [a]);
+        ecs.remove_entity(a);
+        assert!(ecs.query_filter(&both).is_empty());
+        assert!(ecs.get_entites_with_component::<u32>().is_empty());
+    }
+}
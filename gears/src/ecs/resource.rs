@@ -0,0 +1,82 @@
+use super::Manager;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// The global values shared by the systems, one per type.
+pub(crate) type ResourceStore = RwLock<HashMap<TypeId, Arc<RwLock<dyn Any + Send + Sync>>>>;
+
+impl Manager {
+    /// Store a global value, like the score or the seed of the random generator, without an entity.
+    /// It replaces the resource of the same type.
+    pub fn insert_resource<T: 'static + Send + Sync>(&self, resource: T) {
+        self.resources
+            .write()
+            .unwrap()
+            .insert(TypeId::of::<T>(), Arc::new(RwLock::new(resource)));
+    }
+
+    /// Get the resource of a specific type.
+    pub fn get_resource<T: 'static + Send + Sync>(&self) -> Option<Arc<RwLock<T>>> {
+        self.resources
+            .read()
+            .unwrap()
+            .get(&TypeId::of::<T>())
+            .map(|resource| {
+                let resource = Arc::clone(resource);
+                unsafe {
+                    // SAFETY: The resources are keyed by their type
+                    let resource_ptr = Arc::into_raw(resource) as *const RwLock<T>;
+                    Arc::from_raw(resource_ptr)
+                }
+            })
+    }
+
+    /// Check if a resource of a specific type is stored.
+    pub fn has_resource<T: 'static + Send + Sync>(&self) -> bool {
+        self.resources
+            .read()
+            .unwrap()
+            .contains_key(&TypeId::of::<T>())
+    }
+
+    /// Remove the resource of a specific type.
+    ///
+    /// # Returns
+    ///
+    /// True if the resource was stored.
+    pub fn remove_resource<T: 'static + Send + Sync>(&self) -> bool {
+        self.resources
+            .write()
+            .unwrap()
+            .remove(&TypeId::of::<T>())
+            .is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Score(u32);
+
+    #[test]
+    fn test_resources() {
+        let ecs = Manager::default();
+        assert!(ecs.get_resource::<Score>().is_none());
+
+        ecs.insert_resource(Score(10));
+        ecs.get_resource::<Score>().unwrap().write().unwrap().0 += 5;
+        assert_eq!(
+            *ecs.get_resource::<Score>().unwrap().read().unwrap(),
+            Score(15)
+        );
+        // Resources are not attached to entities
+        assert_eq!(ecs.entity_count(), 0);
+
+        assert!(ecs.remove_resource::<Score>());
+        assert!(!ecs.has_resource::<Score>());
+        assert!(!ecs.remove_resource::<Score>());
+    }
+}
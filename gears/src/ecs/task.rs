@@ -0,0 +1,107 @@
+use super::Manager;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+/// A callback of a finished task, run with the manager on the next update.
+pub(crate) type TaskCallback = Box<dyn FnOnce(&Manager) + Send>;
+
+/// The callbacks of the finished tasks, shared with the running tasks.
+pub(crate) type TaskQueue = Arc<Mutex<Vec<TaskCallback>>>;
+
+/// A task spawned with `Manager::spawn_task`.
+/// Dropping the handle does not stop the task.
+#[derive(Debug)]
+pub struct TaskHandle {
+    join: tokio::task::JoinHandle<()>,
+}
+
+impl TaskHandle {
+    /// Check if the task finished, its callback may not have run yet.
+    pub fn is_finished(&self) -> bool {
+        self.join.is_finished()
+    }
+
+    /// Stop the task, its callback will not run.
+    pub fn abort(&self) {
+        self.join.abort();
+    }
+}
+
+impl Manager {
+    /// Run a future on the async runtime, e.g. to load a file or make a request without blocking the systems.
+    /// When it finishes, its output is passed to the callback on the next update, so the callback can change the world
+    /// like a system.
+    ///
+    /// It must be called from the async runtime of the app, like the systems are.
+    ///
+    /// # Arguments
+    ///
+    /// * `future` - The work to do.
+    /// * `on_complete` - Gets the manager and the output of the future.
+    ///
+    /// # Returns
+    ///
+    /// The handle of the task.
+    pub fn spawn_task<T, F, C>(&self, future: F, on_complete: C) -> TaskHandle
+    where
+        T: Send + 'static,
+        F: Future<Output = T> + Send + 'static,
+        C: FnOnce(&Manager, T) + Send + 'static,
+    {
+        let queue = Arc::clone(&self.tasks);
+        let join = tokio::spawn(async move {
+            let output = future.await;
+            queue
+                .lock()
+                .unwrap()
+                .push(Box::new(move |ecs| on_complete(ecs, output)));
+        });
+        TaskHandle { join }
+    }
+
+    /// Run the callbacks of the tasks finished since the last call, in the order they finished.
+    /// The app calls it on each update.
+    ///
+    /// # Returns
+    ///
+    /// The number of callbacks run.
+    pub fn run_task_callbacks(&self) -> usize {
+        // The callbacks may spawn tasks, so the queue is not locked while they run
+        let callbacks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        let count = callbacks.len();
+        for callback in callbacks {
+            callback(self);
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::components::Name;
+
+    #[tokio::test]
+    async fn test_spawn_task() {
+        let ecs = Manager::default();
+        let handle = ecs.spawn_task(async { "Loaded" }, |ecs, name| {
+            let entity = ecs.create_entity();
+            ecs.add_component_to_entity(entity, Name(name));
+        });
+        while !handle.is_finished() {
+            tokio::task::yield_now().await;
+        }
+
+        // The world is changed on the next update, not by the task
+        assert_eq!(ecs.entity_count(), 0);
+        assert_eq!(ecs.run_task_callbacks(), 1);
+        assert_eq!(ecs.get_entites_with_component::<Name>().len(), 1);
+
+        let aborted = ecs.spawn_task(std::future::pending::<()>(), |ecs, _| {
+            ecs.create_entity();
+        });
+        aborted.abort();
+        tokio::task::yield_now().await;
+        assert_eq!(ecs.run_task_callbacks(), 0);
+    }
+}
@@ -1,10 +1,162 @@
-use super::Entity;
+use super::{Entity, Manager};
 
 /// A component that can be attached to an entity.
 pub trait Component: 'static + Send + Sync {}
 
+/// Get the name of a type without its path.
+fn short_type_name<T: ?Sized>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// A component type required by a marker.
+#[derive(Debug, Copy, Clone)]
+pub struct RequiredComponent {
+    pub name: &'static str,
+    present: fn(&Manager, Entity) -> bool,
+}
+
+impl RequiredComponent {
+    pub fn of<T: 'static + Send + Sync>() -> Self {
+        Self {
+            name: short_type_name::<T>(),
+            present: |ecs, entity| ecs.has_component::<T>(entity),
+        }
+    }
+
+    /// Check if an entity has the component.
+    pub fn is_present(&self, ecs: &Manager, entity: Entity) -> bool {
+        (self.present)(ecs, entity)
+    }
+}
+
+/// A component marking an entity for a system, like `Perceivable` or a `Model` for the renderer.
+/// The systems expect some other components on the marked entities, the marker lists them.
+/// Implement it with the `impl_marker!` macro.
+pub trait Marker: Component + Sized {
+    /// Describe what the marker does.
+    fn describe() -> &'static str;
+
+    /// Get the components a marked entity must have.
+    fn required() -> Vec<RequiredComponent> {
+        Vec::new()
+    }
+
+    /// Get the names of the required components an entity does not have.
+    fn missing(ecs: &Manager, entity: Entity) -> Vec<&'static str> {
+        Self::required()
+            .into_iter()
+            .filter(|required| !required.is_present(ecs, entity))
+            .map(|required| required.name)
+            .collect()
+    }
+
+    /// Check if an entity has the required components.
+    ///
+    /// # Returns
+    ///
+    /// An error naming the marker, what it does and the missing components.
+    fn validate(ecs: &Manager, entity: Entity) -> anyhow::Result<()> {
+        let missing = Self::missing(ecs, entity);
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "Entity {} is marked {} ({}), but it has no {}",
+                entity.id(),
+                short_type_name::<Self>(),
+                Self::describe(),
+                missing.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A group of components added to an entity at once, like the components of an enemy.
+/// A component and a tuple of bundles are bundles, so bundles can be nested.
+/// Implement it for a struct of bundles with the `impl_bundle!` macro.
+pub trait Bundle {
+    /// Add the components of the bundle to an entity.
+    fn insert_into(self, ecs: &Manager, entity: Entity);
+}
+
+impl<C: Component> Bundle for C {
+    fn insert_into(self, ecs: &Manager, entity: Entity) {
+        ecs.add_component_to_entity(entity, self);
+    }
+}
+
+macro_rules! impl_tuple_bundle {
+    ($($bundle:ident),*) => {
+        impl<$($bundle: Bundle),*> Bundle for ($($bundle,)*) {
+            #[allow(non_snake_case, unused_variables)]
+            fn insert_into(self, ecs: &Manager, entity: Entity) {
+                let ($($bundle,)*) = self;
+                $($bundle.insert_into(ecs, entity);)*
+            }
+        }
+    };
+}
+
+impl_tuple_bundle!();
+impl_tuple_bundle!(A);
+impl_tuple_bundle!(A, B);
+impl_tuple_bundle!(A, B, C);
+impl_tuple_bundle!(A, B, C, D);
+impl_tuple_bundle!(A, B, C, D, E);
+impl_tuple_bundle!(A, B, C, D, E, F);
+impl_tuple_bundle!(A, B, C, D, E, F, G);
+impl_tuple_bundle!(A, B, C, D, E, F, G, H);
+impl_tuple_bundle!(A, B, C, D, E, F, G, H, I);
+impl_tuple_bundle!(A, B, C, D, E, F, G, H, I, J);
+impl_tuple_bundle!(A, B, C, D, E, F, G, H, I, J, K);
+impl_tuple_bundle!(A, B, C, D, E, F, G, H, I, J, K, L);
+
 pub trait EntityBuilder {
     fn new_entity(&mut self) -> &mut Self;
     fn add_component(&mut self, component: impl Component) -> &mut Self;
+    fn add_bundle(&mut self, bundle: impl Bundle) -> &mut Self;
     fn build(&mut self) -> Entity;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::components::{Name, Pos3};
+
+    #[derive(Debug)]
+    struct Target;
+
+    impl Component for Target {}
+
+    crate::impl_marker!(Target, "can be targeted by the turrets", requires [Name, Pos3]);
+
+    #[test]
+    fn test_marker_validation() {
+        let ecs = Manager::default();
+        let entity = ecs.spawn_bundle((Target, Name("Crate")));
+        assert_eq!(Target::missing(&ecs, entity), vec!["Pos3"]);
+        assert_eq!(
+            Target::validate(&ecs, entity).unwrap_err().to_string(),
+            "Entity 0 is marked Target (can be targeted by the turrets), but it has no Pos3"
+        );
+
+        ecs.add_component_to_entity(entity, Pos3::default());
+        assert!(Target::validate(&ecs, entity).is_ok());
+    }
+
+    #[test]
+    fn test_spawn_macro() {
+        let ecs = Manager::default();
+        let named = true;
+        let entity = crate::spawn!(ecs, Pos3::default(), if named => Name("Crate"), ..(Target,));
+        assert!(ecs.has_component::<Name>(entity) && ecs.has_component::<Target>(entity));
+
+        let spawned = crate::spawn!(ecs, { name: Name("Player"), pos: Pos3::default() });
+        spawned.pos.write().unwrap().pos.x = 2.0;
+        let pos = ecs
+            .get_component_from_entity::<Pos3>(spawned.entity)
+            .unwrap();
+        assert_eq!(pos.read().unwrap().pos.x, 2.0);
+        assert_eq!(spawned.name.read().unwrap().0, "Player");
+    }
+}
@@ -2,7 +2,10 @@ use std::f64::consts::E;
 
 use log::warn;
 
-use super::{traits::Component, Entity, Manager};
+use super::{
+    traits::{Bundle, Component},
+    Entity, Manager,
+};
 
 pub struct EcsBuilder<'a> {
     ecs: &'a mut Manager,
@@ -34,14 +37,28 @@ impl super::traits::EntityBuilder for EcsBuilder<'_> {
         self
     }
 
+    fn add_bundle(&mut self, bundle: impl Bundle) -> &mut Self {
+        let entity = self.ecs.get_last().unwrap_or_else(|| {
+            warn!("No entity found, creating a new one...");
+
+            self.ecs.create_entity()
+        });
+        bundle.insert_into(self.ecs, entity);
+
+        self
+    }
+
     fn build(&mut self) -> Entity {
-        if let Some(entity) = self.ecs.get_last() {
+        let entity = if let Some(entity) = self.ecs.get_last() {
             entity
         } else {
             warn!("No entity found, creating a new one...");
 
             self.ecs.create_entity()
-        }
+        };
+        self.ecs.check_spawned(entity);
+
+        entity
     }
 }
 
@@ -96,4 +113,43 @@ mod tests {
         let component = component.read().unwrap();
         assert_eq!(*component, TestComponent { value: 100 });
     }
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+
+    impl Component for Health {}
+
+    struct EnemyBundle {
+        health: Health,
+        stats: (TestComponent, ecs::components::Name),
+    }
+
+    crate::impl_bundle!(EnemyBundle { health, stats });
+
+    #[test]
+    fn test_add_nested_bundles() {
+        let mut manager = Manager::default();
+        let entity = EcsBuilder::new(&mut manager)
+            .new_entity()
+            .add_bundle(EnemyBundle {
+                health: Health(10),
+                stats: (TestComponent { value: 1 }, ecs::components::Name("Enemy")),
+            })
+            .build();
+        assert_eq!(
+            *manager
+                .get_component_from_entity::<Health>(entity)
+                .unwrap()
+                .read()
+                .unwrap(),
+            Health(10)
+        );
+        assert!(manager
+            .get_component_from_entity::<ecs::components::Name>(entity)
+            .is_some());
+
+        let spawned = manager.spawn_bundle((Health(5), TestComponent { value: 2 }));
+        assert_ne!(spawned, entity);
+        assert_eq!(manager.query_entities::<Health>().len(), 2);
+    }
 }
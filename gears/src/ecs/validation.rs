@@ -0,0 +1,134 @@
+use super::components::{Camera, Light, Model};
+use super::traits::Marker;
+use super::{Entity, Manager};
+
+/// What happens to an entity spawned without the components its markers require.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ValidationPolicy {
+    /// Log a warning and keep the entity.
+    #[default]
+    Warn,
+    /// Log an error and remove the entity.
+    Reject,
+}
+
+/// A marker registered for validation.
+#[derive(Debug, Copy, Clone)]
+struct MarkerCheck {
+    marked: fn(&Manager, Entity) -> bool,
+    validate: fn(&Manager, Entity) -> anyhow::Result<()>,
+}
+
+/// The markers checked when an entity is spawned, see `Manager::register_marker`.
+#[derive(Debug)]
+pub(crate) struct Validation {
+    checks: Vec<MarkerCheck>,
+    policy: ValidationPolicy,
+}
+
+impl Default for Validation {
+    /// The components the renderer expects others with are registered.
+    fn default() -> Self {
+        let mut validation = Self {
+            checks: Vec::new(),
+            policy: ValidationPolicy::default(),
+        };
+        validation.register::<Model<'static>>();
+        validation.register::<Light>();
+        validation.register::<Camera>();
+        validation
+    }
+}
+
+impl Validation {
+    fn register<T: Marker>(&mut self) {
+        self.checks.push(MarkerCheck {
+            marked: |ecs, entity| ecs.has_component::<T>(entity),
+            validate: T::validate,
+        });
+    }
+}
+
+impl Manager {
+    /// Check the required components of a marker when an entity having it is spawned.
+    pub fn register_marker<T: Marker>(&self) {
+        self.validation.write().unwrap().register::<T>();
+    }
+
+    pub fn set_validation_policy(&self, policy: ValidationPolicy) {
+        self.validation.write().unwrap().policy = policy;
+    }
+
+    /// Check if an entity has the components required by its registered markers.
+    ///
+    /// # Returns
+    ///
+    /// An error listing the markers with missing components.
+    pub fn validate_entity(&self, entity: Entity) -> anyhow::Result<()> {
+        let checks = self.validation.read().unwrap().checks.clone();
+        let errors = checks
+            .iter()
+            .filter(|check| (check.marked)(self, entity))
+            .filter_map(|check| (check.validate)(self, entity).err())
+            .map(|err| err.to_string())
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            anyhow::bail!(errors.join("; "));
+        }
+        Ok(())
+    }
+
+    /// Validate a spawned entity and apply the validation policy.
+    /// It is called by `spawn_bundle` and the entity builders.
+    ///
+    /// # Returns
+    ///
+    /// False if the entity was invalid and got removed.
+    pub fn check_spawned(&self, entity: Entity) -> bool {
+        let Err(err) = self.validate_entity(entity) else {
+            return true;
+        };
+        let policy = self.validation.read().unwrap().policy;
+        match policy {
+            ValidationPolicy::Warn => {
+                log::warn!("[Validation] {}", err);
+                true
+            }
+            ValidationPolicy::Reject => {
+                log::error!("[Validation] {}, the entity is removed", err);
+                self.remove_entity(entity);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::components::{Name, Pos3};
+
+    #[test]
+    fn test_invalid_entities_are_rejected() {
+        let ecs = Manager::default();
+        let entity = ecs.spawn_bundle((
+            Model::Static {
+                obj_path: "cube.obj",
+            },
+            Name("Cube"),
+        ));
+        // The default policy keeps the entity
+        assert!(ecs.entity_count() == 1 && ecs.has_component::<Name>(entity));
+        assert!(ecs
+            .validate_entity(entity)
+            .unwrap_err()
+            .to_string()
+            .contains("it has no Pos3"));
+
+        ecs.set_validation_policy(ValidationPolicy::Reject);
+        ecs.spawn_bundle(Light::Ambient { intensity: 0.1 });
+        assert_eq!(ecs.entity_count(), 1);
+        let valid = ecs.spawn_bundle((Light::Ambient { intensity: 0.1 }, Pos3::default()));
+        assert!(ecs.check_spawned(valid));
+    }
+}
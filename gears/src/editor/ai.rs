@@ -0,0 +1,291 @@
+use super::inspector::Inspector;
+use crate::ai::blackboard::Blackboard;
+use crate::ai::crowd::CrowdAgent;
+use crate::ai::fsm::StateMachine;
+use crate::ai::patrol::{PatrolRoute, Patroller};
+use crate::ai::perception::Perception;
+use crate::ecs::components::{Name, Pos3};
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::{InnerSpace, Vector3};
+
+/// Opens the AI debug panel, showing the state machine, the blackboard, the patrol path
+/// and the perception of an agent. The state of the agent can be forced for testing.
+/// The entity selected in the inspector is followed when it is an agent.
+#[derive(Debug, Clone)]
+pub struct AiDebug {
+    pub open: bool,
+    pub selected: Option<Entity>,
+    /// The state the force button requests.
+    pub force_state: String,
+}
+
+impl Component for AiDebug {}
+
+impl Default for AiDebug {
+    fn default() -> Self {
+        Self {
+            open: true,
+            selected: None,
+            force_state: String::new(),
+        }
+    }
+}
+
+/// Get the agents of the world, the entities with any of the AI components.
+fn agents(ecs: &ecs::Manager) -> Vec<Entity> {
+    let mut agents = ecs.get_entites_with_component::<StateMachine>();
+    agents.extend(ecs.get_entites_with_component::<Patroller>());
+    agents.extend(ecs.get_entites_with_component::<Perception>());
+    agents.sort_by_key(|entity| entity.id());
+    agents.dedup();
+    agents
+}
+
+/// Format a blackboard value of a common type, the others only show that they are set.
+fn describe(blackboard: &Blackboard, key: &str) -> String {
+    macro_rules! try_types {
+        ($($ty:ty),*) => {
+            $(if let Some(value) = blackboard.get::<$ty>(key) {
+                return format!("{:?}", value);
+            })*
+        };
+    }
+    try_types!(
+        bool,
+        i32,
+        i64,
+        u32,
+        u64,
+        usize,
+        f32,
+        f64,
+        String,
+        &'static str,
+        Entity,
+        Option<Entity>,
+        Vector3<f32>
+    );
+    "(set)".to_string()
+}
+
+fn vector(v: Vector3<f32>) -> String {
+    format!("({:.2}, {:.2}, {:.2})", v.x, v.y, v.z)
+}
+
+/// Draw the state machine of an agent and the state forcing controls.
+fn show_state_machine(ui: &mut egui::Ui, debug: &mut AiDebug, machine: &mut StateMachine) {
+    egui::Grid::new("ai_state").num_columns(2).show(ui, |ui| {
+        ui.label("State");
+        ui.strong(machine.state());
+        ui.end_row();
+
+        ui.label("Sub-state");
+        ui.label(machine.sub_state().unwrap_or("-"));
+        ui.end_row();
+
+        ui.label("Time in state");
+        ui.label(format!("{:.2} s", machine.time_in_state()));
+        ui.end_row();
+    });
+
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_salt("ai_force_state")
+            .selected_text(debug.force_state.clone())
+            .show_ui(ui, |ui| {
+                for state in machine.states() {
+                    ui.selectable_value(&mut debug.force_state, state.to_string(), state);
+                }
+            });
+        ui.add(egui::TextEdit::singleline(&mut debug.force_state).desired_width(100.0));
+        let enabled = !debug.force_state.trim().is_empty();
+        if ui
+            .add_enabled(enabled, egui::Button::new("Force"))
+            .on_hover_text("Enter the state on the next update")
+            .clicked()
+        {
+            machine.request(debug.force_state.trim());
+        }
+    });
+
+    ui.collapsing("Recent transitions", |ui| {
+        for transition in machine.history().collect::<Vec<_>>().into_iter().rev() {
+            ui.label(format!(
+                "{:>7.2}s  {} → {}{}",
+                transition.time,
+                transition.from,
+                transition.to,
+                if transition.requested {
+                    " (forced)"
+                } else {
+                    ""
+                },
+            ));
+        }
+    });
+}
+
+/// Draw the route of a patroller, the waypoint it heads to is highlighted.
+fn show_path(ui: &mut egui::Ui, ecs: &ecs::Manager, entity: Entity, patroller: &Patroller) {
+    let Some(route) = ecs.get_component_from_entity::<PatrolRoute>(patroller.route) else {
+        ui.label("The route entity has no PatrolRoute");
+        return;
+    };
+    let route = route.read().unwrap();
+    let current = patroller.current_waypoint();
+
+    ui.label(format!(
+        "Route {} ({:?}){}",
+        route.name,
+        patroller.order,
+        if patroller.is_waiting() {
+            ", waiting"
+        } else {
+            ""
+        },
+    ));
+    if let Some(target) = route.waypoints.get(current) {
+        let distance = ecs
+            .get_component_from_entity::<Pos3>(entity)
+            .map(|pos| (target.pos - pos.read().unwrap().pos).magnitude());
+        ui.label(format!(
+            "Target: waypoint {} at {}, {}",
+            current,
+            vector(target.pos),
+            distance.map_or("no position".to_string(), |d| format!("{:.2} away", d)),
+        ));
+    }
+    for (i, waypoint) in route.waypoints.iter().enumerate() {
+        let text = format!("{}: {}", i, vector(waypoint.pos));
+        if i == current {
+            ui.strong(format!("▶ {}", text));
+        } else {
+            ui.label(format!("   {}", text));
+        }
+    }
+}
+
+/// Draw the AI debug panel.
+pub fn show_ai_debug(ctx: &egui::Context, ecs: &ecs::Manager) {
+    let Some((_, debug)) = ecs.get_all_components_of_type::<AiDebug>().pop() else {
+        return;
+    };
+    let mut debug = debug.write().unwrap();
+    if !debug.open {
+        return;
+    }
+
+    let agents = agents(ecs);
+    let inspected = ecs
+        .get_all_components_of_type::<Inspector>()
+        .pop()
+        .and_then(|(_, inspector)| inspector.read().unwrap().selected);
+    if let Some(entity) = inspected.filter(|entity| agents.contains(entity)) {
+        debug.selected = Some(entity);
+    }
+    if !debug
+        .selected
+        .is_some_and(|entity| agents.contains(&entity))
+    {
+        debug.selected = agents.first().copied();
+    }
+
+    let label = |entity: Entity| match ecs.get_component_from_entity::<Name>(entity) {
+        Some(name) => format!("{} ({})", name.read().unwrap().0, entity.id()),
+        None => format!("Entity {}", entity.id()),
+    };
+
+    let mut open = debug.open;
+    egui::Window::new("AI")
+        .open(&mut open)
+        .default_width(360.0)
+        .show(ctx, |ui| {
+            egui::ComboBox::from_label("Agent")
+                .selected_text(debug.selected.map(label).unwrap_or_default())
+                .show_ui(ui, |ui| {
+                    for entity in &agents {
+                        ui.selectable_value(&mut debug.selected, Some(*entity), label(*entity));
+                    }
+                });
+            let Some(entity) = debug.selected else {
+                ui.label("No entity has an AI component");
+                return;
+            };
+
+            if let Some(machine) = ecs.get_component_from_entity::<StateMachine>(entity) {
+                ui.separator();
+                show_state_machine(ui, &mut debug, &mut machine.write().unwrap());
+            }
+
+            if let Some(blackboard) = ecs.get_component_from_entity::<Blackboard>(entity) {
+                ui.separator();
+                let blackboard = blackboard.read().unwrap();
+                let mut keys = blackboard.keys().collect::<Vec<_>>();
+                keys.sort();
+                ui.collapsing(format!("Context ({})", keys.len()), |ui| {
+                    egui::Grid::new("ai_context").num_columns(2).show(ui, |ui| {
+                        for key in keys {
+                            ui.label(key);
+                            ui.label(describe(&blackboard, key));
+                            ui.end_row();
+                        }
+                    });
+                });
+            }
+
+            if let Some(patroller) = ecs.get_component_from_entity::<Patroller>(entity) {
+                ui.separator();
+                let patroller = patroller.read().unwrap().clone();
+                ui.collapsing("Path", |ui| show_path(ui, ecs, entity, &patroller));
+            }
+            if let Some(agent) = ecs.get_component_from_entity::<CrowdAgent>(entity) {
+                let velocity = agent.read().unwrap().preferred_velocity;
+                ui.label(format!("Preferred velocity {}", vector(velocity)));
+            }
+
+            if let Some(perception) = ecs.get_component_from_entity::<Perception>(entity) {
+                ui.separator();
+                let perception = perception.read().unwrap();
+                ui.collapsing("Perception", |ui| {
+                    let visible = perception.visible();
+                    if visible.is_empty() {
+                        ui.label("Sees nothing");
+                    }
+                    for target in visible {
+                        ui.label(format!("Sees {}", label(*target)));
+                    }
+                    for noise in perception.heard() {
+                        ui.label(format!("Heard a noise at {}", vector(noise.position)));
+                    }
+                });
+            }
+        });
+    debug.open = open;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_show_ai_debug() {
+        let ecs = ecs::Manager::default();
+        let agent = ecs.create_entity();
+        ecs.add_component_to_entity(agent, StateMachine::new("idle"));
+        let mut blackboard = Blackboard::new();
+        blackboard.set("alert", 0.5f32);
+        blackboard.set("cover", vec![agent]);
+        assert_eq!(describe(&blackboard, "alert"), "0.5");
+        assert_eq!(describe(&blackboard, "cover"), "(set)");
+        ecs.add_component_to_entity(agent, blackboard);
+        let editor = ecs.create_entity();
+        ecs.add_component_to_entity(editor, AiDebug::default());
+
+        let ctx = egui::Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |ctx| show_ai_debug(ctx, &ecs));
+
+        // The only agent is selected
+        let debug = ecs.get_component_from_entity::<AiDebug>(editor).unwrap();
+        assert_eq!(debug.read().unwrap().selected, Some(agent));
+    }
+}
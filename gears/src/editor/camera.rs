@@ -0,0 +1,111 @@
+use super::inspector::Inspector;
+use super::scene_tree::SceneTree;
+use crate::ecs::components::{Bounds, Collider, Pos3};
+use crate::ecs::{self, Entity};
+use cgmath::{Rad, Vector3};
+
+/// The size of the box framed around an entity without bounds.
+const DEFAULT_EXTENT: f32 = 0.5;
+
+/// Send it to move the editor camera.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CameraCommand {
+    /// Move the camera back along its view direction until the entity fills the view.
+    Frame(Entity),
+    /// Keep the camera looking at the entity, moving with it.
+    Follow(Entity),
+    /// Stop following.
+    Unfollow,
+}
+
+/// Get the entity selected in the inspector or in the scene tree.
+pub fn selected_entity(ecs: &ecs::Manager) -> Option<Entity> {
+    let inspected = ecs
+        .get_all_components_of_type::<Inspector>()
+        .pop()
+        .and_then(|(_, inspector)| inspector.read().unwrap().selected);
+    inspected.or_else(|| {
+        ecs.get_all_components_of_type::<SceneTree>()
+            .pop()
+            .and_then(|(_, tree)| tree.read().unwrap().selected)
+    })
+}
+
+/// Get the bounds of an entity in the world.
+/// The bounds of the model are used, then the collider, then a small box around the position.
+///
+/// # Returns
+///
+/// The bounds, or `None` if the entity has no position.
+pub fn entity_bounds(ecs: &ecs::Manager, entity: Entity) -> Option<Bounds> {
+    let pos = ecs
+        .get_component_from_entity::<Pos3>(entity)?
+        .read()
+        .unwrap()
+        .pos;
+    let local = ecs
+        .get_component_from_entity::<Bounds>(entity)
+        .map(|bounds| *bounds.read().unwrap())
+        .or_else(|| {
+            ecs.get_component_from_entity::<Collider>(entity)
+                .map(|collider| {
+                    let (min, max) = collider.read().unwrap().bounds();
+                    Bounds::new(min, max)
+                })
+        })
+        .unwrap_or(Bounds::new(
+            Vector3::new(-DEFAULT_EXTENT, -DEFAULT_EXTENT, -DEFAULT_EXTENT),
+            Vector3::new(DEFAULT_EXTENT, DEFAULT_EXTENT, DEFAULT_EXTENT),
+        ));
+    Some(Bounds::new(pos + local.min, pos + local.max))
+}
+
+/// Get the distance from the center of a sphere at which it fits the view.
+///
+/// # Arguments
+///
+/// * `radius` - The radius of the sphere.
+/// * `fovy` - The vertical field of view.
+/// * `aspect` - The width of the view divided by its height.
+pub fn frame_distance(radius: f32, fovy: Rad<f32>, aspect: f32) -> f32 {
+    let half_y = fovy.0 / 2.0;
+    let half_x = (half_y.tan() * aspect).atan();
+    // Fit the narrower side, with a small margin
+    let half = half_y.min(half_x).max(f32::EPSILON);
+    radius.max(f32::EPSILON) * 1.1 / half.sin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Deg;
+
+    #[test]
+    fn test_entity_bounds() {
+        let ecs = ecs::Manager::default();
+        let crate_entity = ecs.create_entity();
+        ecs.add_component_to_entity(crate_entity, Pos3::new(Vector3::new(10.0, 0.0, 0.0)));
+        ecs.add_component_to_entity(
+            crate_entity,
+            Bounds::new(Vector3::new(-1.0, 0.0, -1.0), Vector3::new(1.0, 2.0, 1.0)),
+        );
+        let bounds = entity_bounds(&ecs, crate_entity).unwrap();
+        assert_eq!(bounds.center(), Vector3::new(10.0, 1.0, 0.0));
+
+        // Without bounds a small box is framed, without a position nothing
+        let marker = ecs.create_entity();
+        ecs.add_component_to_entity(marker, Pos3::default());
+        assert_eq!(
+            entity_bounds(&ecs, marker).unwrap().center(),
+            Vector3::new(0.0, 0.0, 0.0)
+        );
+        assert!(entity_bounds(&ecs, ecs.create_entity()).is_none());
+
+        // A wider view does not bring the camera closer than the height needs
+        let fovy = Rad::from(Deg(60.0));
+        let distance = frame_distance(1.0, fovy, 1.0);
+        assert!((distance - 2.2).abs() < 1e-4, "{}", distance);
+        assert_eq!(frame_distance(1.0, fovy, 2.0), distance);
+        assert!(frame_distance(1.0, fovy, 0.5) > distance);
+    }
+}
@@ -0,0 +1,557 @@
+use crate::ecs::components::{Light, Name, Pos3, Scale, Velocity};
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use crate::gameplay::animation::{sample_keyframes, Interpolation};
+use crate::gameplay::cinematic::Keyframe;
+use crate::gameplay::health::Health;
+use cgmath::{Deg, Euler, InnerSpace, Quaternion, Vector3};
+use std::any::TypeId;
+
+/// A component with an editor in the inspector.
+pub trait Inspect {
+    /// Draw the editor of the component.
+    ///
+    /// # Arguments
+    ///
+    /// * `ui` - The ui to draw in.
+    /// * `id` - A unique id of the component, for the state of the widgets.
+    ///
+    /// # Returns
+    ///
+    /// True if the component was changed.
+    fn inspect(&mut self, ui: &mut egui::Ui, id: egui::Id) -> bool;
+}
+
+/// Edit a vector with a drag value per axis.
+pub fn drag_vec3(ui: &mut egui::Ui, value: &mut Vector3<f32>, speed: f32) -> bool {
+    ui.horizontal(|ui| {
+        let mut changed = false;
+        for (label, axis) in [
+            ("x", &mut value.x),
+            ("y", &mut value.y),
+            ("z", &mut value.z),
+        ] {
+            ui.label(label);
+            changed |= ui
+                .add(egui::DragValue::new(axis).speed(speed).max_decimals(3))
+                .changed();
+        }
+        changed
+    })
+    .inner
+}
+
+/// Get the Euler angles to show for a rotation, in degrees.
+/// The angles edited last are kept while the rotation does not change, so editing an angle
+/// close to the gimbal lock does not make the other two jump to an equivalent rotation.
+///
+/// # Arguments
+///
+/// * `rotation` - The rotation.
+/// * `cached` - The angles shown last and the rotation they were made for.
+pub fn euler_degrees(
+    rotation: Quaternion<f32>,
+    cached: Option<([f32; 3], Quaternion<f32>)>,
+) -> [f32; 3] {
+    if let Some((angles, cached_rotation)) = cached {
+        // q and -q are the same rotation
+        if rotation.dot(cached_rotation).abs() > 1.0 - 1e-6 {
+            return angles;
+        }
+    }
+
+    let euler = Euler::from(rotation);
+    [
+        Deg::from(euler.x).0,
+        Deg::from(euler.y).0,
+        Deg::from(euler.z).0,
+    ]
+}
+
+/// Edit a rotation as Euler angles in degrees, applied around x, then y, then z.
+pub fn drag_rotation(ui: &mut egui::Ui, id: egui::Id, rotation: &mut Quaternion<f32>) -> bool {
+    let cached = ui.data(|data| data.get_temp::<([f32; 3], Quaternion<f32>)>(id));
+    let mut angles = euler_degrees(*rotation, cached);
+
+    let changed = ui
+        .horizontal(|ui| {
+            let mut changed = false;
+            for (label, angle) in ["x", "y", "z"].into_iter().zip(angles.iter_mut()) {
+                ui.label(label);
+                changed |= ui
+                    .add(egui::DragValue::new(angle).speed(0.5).suffix("°"))
+                    .changed();
+            }
+            changed
+        })
+        .inner;
+
+    if angles[1].abs() > 89.0 {
+        ui.small("Gimbal lock: x and z rotate around the same axis");
+    }
+    if changed {
+        let [x, y, z] = angles;
+        *rotation = Quaternion::from(Euler::new(Deg(x), Deg(y), Deg(z))).normalize();
+    }
+    ui.data_mut(|data| data.insert_temp(id, (angles, *rotation)));
+
+    changed
+}
+
+/// Edit a linear RGB color.
+pub fn color_rgb(ui: &mut egui::Ui, color: &mut [f32; 3]) -> bool {
+    ui.color_edit_button_rgb(color).changed()
+}
+
+/// Edit a track of keyframes as a curve.
+/// Drag a keyframe to move it, double click to add one and right click one to remove it.
+///
+/// # Arguments
+///
+/// * `ui` - The ui to draw in.
+/// * `id` - A unique id of the track.
+/// * `keyframes` - The keyframes, sorted by time, they are sorted again when a drag ends.
+/// * `duration` - The time shown by the editor.
+/// * `interpolation` - How the curve is drawn between the keyframes.
+///
+/// # Returns
+///
+/// True if the keyframes were changed.
+pub fn curve_editor(
+    ui: &mut egui::Ui,
+    id: egui::Id,
+    keyframes: &mut Vec<Keyframe<f32>>,
+    duration: f32,
+    interpolation: Interpolation,
+) -> bool {
+    let (rect, background) = ui.allocate_exact_size(
+        egui::vec2(ui.available_width(), 120.0),
+        egui::Sense::click(),
+    );
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    // The value range is frozen while dragging, so the curve does not rescale under the pointer
+    let range_id = id.with("range");
+    let mut dragging = ui
+        .ctx()
+        .dragged_id()
+        .is_some_and(|dragged| (0..keyframes.len()).any(|i| dragged == id.with(i)));
+    let range = match ui.data(|data| data.get_temp::<(f32, f32)>(range_id)) {
+        Some(range) if dragging => range,
+        _ => {
+            let (min, max) = keyframes
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(min, max), k| {
+                    (min.min(k.value), max.max(k.value))
+                });
+            let (min, max) = if min > max { (0.0, 1.0) } else { (min, max) };
+            let padding = ((max - min) * 0.1).max(0.5);
+            (min - padding, max + padding)
+        }
+    };
+    ui.data_mut(|data| data.insert_temp(range_id, range));
+
+    let duration = duration.max(f32::EPSILON);
+    let to_screen = |time: f32, value: f32| {
+        egui::pos2(
+            egui::remap(time, 0.0..=duration, rect.x_range()),
+            egui::remap(value, range.0..=range.1, rect.bottom()..=rect.top()),
+        )
+    };
+    let from_screen = |pos: egui::Pos2| {
+        (
+            egui::remap(pos.x, rect.x_range(), 0.0..=duration).clamp(0.0, duration),
+            egui::remap(pos.y, rect.bottom()..=rect.top(), range.0..=range.1),
+        )
+    };
+
+    let stroke = ui.visuals().widgets.active.fg_stroke;
+    let points = keyframes
+        .iter()
+        .map(|k| to_screen(k.time, k.value))
+        .collect::<Vec<_>>();
+    if !keyframes.is_empty() {
+        let line = (0..=rect.width() as usize)
+            .filter_map(|x| {
+                let (time, _) = from_screen(egui::pos2(rect.left() + x as f32, rect.top()));
+                let value = sample_keyframes(keyframes, interpolation, time)?;
+                Some(to_screen(time, value))
+            })
+            .collect();
+        painter.add(egui::Shape::line(line, stroke));
+    }
+
+    let mut changed = false;
+    let mut removed = None;
+    for (i, point) in points.iter().enumerate() {
+        let handle = egui::Rect::from_center_size(*point, egui::vec2(10.0, 10.0));
+        let response = ui
+            .interact(handle, id.with(i), egui::Sense::click_and_drag())
+            .on_hover_text(format!(
+                "{:.2}s: {:.3}",
+                keyframes[i].time, keyframes[i].value
+            ));
+
+        if response.dragged() {
+            if let Some(pointer) = response.interact_pointer_pos() {
+                (keyframes[i].time, keyframes[i].value) = from_screen(pointer);
+                changed = true;
+            }
+        }
+        // Sorting while dragging would hand the drag over to another keyframe
+        dragging |= response.dragged();
+        changed |= response.drag_stopped();
+        if response.secondary_clicked() {
+            removed = Some(i);
+        }
+
+        let color = if response.hovered() || response.dragged() {
+            ui.visuals().selection.bg_fill
+        } else {
+            stroke.color
+        };
+        painter.circle_filled(*point, 4.0, color);
+    }
+
+    if let Some(i) = removed {
+        keyframes.remove(i);
+        changed = true;
+    }
+    if background.double_clicked() {
+        if let Some(pointer) = background.interact_pointer_pos() {
+            let (time, value) = from_screen(pointer);
+            keyframes.push(Keyframe::new(time, value));
+            changed = true;
+        }
+    }
+    if changed && !dragging {
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+
+    changed
+}
+
+impl Inspect for Pos3 {
+    fn inspect(&mut self, ui: &mut egui::Ui, id: egui::Id) -> bool {
+        let mut changed = drag_vec3(ui, &mut self.pos, 0.05);
+        let mut rotation = self.rot.unwrap_or(Quaternion::new(1.0, 0.0, 0.0, 0.0));
+        if drag_rotation(ui, id.with("rot"), &mut rotation) {
+            self.rot = Some(rotation);
+            changed = true;
+        }
+        changed
+    }
+}
+
+impl Inspect for Velocity {
+    fn inspect(&mut self, ui: &mut egui::Ui, _id: egui::Id) -> bool {
+        drag_vec3(ui, &mut self.0, 0.05)
+    }
+}
+
+impl Inspect for Scale {
+    fn inspect(&mut self, ui: &mut egui::Ui, _id: egui::Id) -> bool {
+        match self {
+            Scale::Uniform(scale) => ui
+                .add(
+                    egui::DragValue::new(scale)
+                        .speed(0.01)
+                        .range(0.0..=f32::MAX),
+                )
+                .changed(),
+            Scale::NonUniform { x, y, z } => {
+                let mut scale = Vector3::new(*x, *y, *z);
+                let changed = drag_vec3(ui, &mut scale, 0.01);
+                (*x, *y, *z) = (scale.x, scale.y, scale.z);
+                changed
+            }
+        }
+    }
+}
+
+impl Inspect for Light {
+    fn inspect(&mut self, ui: &mut egui::Ui, _id: egui::Id) -> bool {
+        let drag = |ui: &mut egui::Ui, label: &str, value: &mut f32| {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                ui.add(
+                    egui::DragValue::new(value)
+                        .speed(0.01)
+                        .range(0.0..=f32::MAX),
+                )
+                .changed()
+            })
+            .inner
+        };
+        let direction = |ui: &mut egui::Ui, value: &mut [f32; 3]| {
+            let mut direction = Vector3::from(*value);
+            let changed = drag_vec3(ui, &mut direction, 0.01);
+            *value = direction.into();
+            changed
+        };
+        let color = |ui: &mut egui::Ui, value: &mut [f32; 3]| {
+            ui.horizontal(|ui| {
+                ui.label("Color");
+                color_rgb(ui, value)
+            })
+            .inner
+        };
+
+        let mut changed = false;
+        match self {
+            Light::Point { radius, intensity } => {
+                changed |= drag(ui, "Radius", radius);
+                changed |= drag(ui, "Intensity", intensity);
+            }
+            Light::PointColoured {
+                radius,
+                color: rgb,
+                intensity,
+            } => {
+                changed |= drag(ui, "Radius", radius);
+                changed |= color(ui, rgb);
+                changed |= drag(ui, "Intensity", intensity);
+            }
+            Light::Ambient { intensity } => changed |= drag(ui, "Intensity", intensity),
+            Light::AmbientColoured {
+                color: rgb,
+                intensity,
+            } => {
+                changed |= color(ui, rgb);
+                changed |= drag(ui, "Intensity", intensity);
+            }
+            Light::Directional {
+                direction: dir,
+                intensity,
+            } => {
+                changed |= direction(ui, dir);
+                changed |= drag(ui, "Intensity", intensity);
+            }
+            Light::DirectionalColoured {
+                direction: dir,
+                color: rgb,
+                intensity,
+            } => {
+                changed |= direction(ui, dir);
+                changed |= color(ui, rgb);
+                changed |= drag(ui, "Intensity", intensity);
+            }
+            Light::Rect {
+                width,
+                height,
+                direction: dir,
+                color: rgb,
+                intensity,
+                radius,
+            } => {
+                changed |= drag(ui, "Width", width);
+                changed |= drag(ui, "Height", height);
+                changed |= direction(ui, dir);
+                changed |= color(ui, rgb);
+                changed |= drag(ui, "Intensity", intensity);
+                changed |= drag(ui, "Radius", radius);
+            }
+        }
+        changed
+    }
+}
+
+impl Inspect for Health {
+    fn inspect(&mut self, ui: &mut egui::Ui, _id: egui::Id) -> bool {
+        ui.horizontal(|ui| {
+            let max = self.max;
+            let current = ui
+                .add(egui::DragValue::new(&mut self.current).range(0.0..=max))
+                .changed();
+            ui.label("/");
+            let max = ui
+                .add(egui::DragValue::new(&mut self.max).range(0.0..=f32::MAX))
+                .changed();
+            current || max
+        })
+        .inner
+    }
+}
+
+impl Inspect for Name {
+    fn inspect(&mut self, ui: &mut egui::Ui, _id: egui::Id) -> bool {
+        ui.label(self.0);
+        false
+    }
+}
+
+type Draw = fn(&mut egui::Ui, &ecs::Manager, Entity) -> Option<bool>;
+
+fn draw<T: Inspect + Component>(
+    ui: &mut egui::Ui,
+    ecs: &ecs::Manager,
+    entity: Entity,
+) -> Option<bool> {
+    let component = ecs.get_component_from_entity::<T>(entity)?;
+    let id = egui::Id::new(("inspector", entity, TypeId::of::<T>()));
+    let changed = component.write().unwrap().inspect(ui, id);
+    Some(changed)
+}
+
+/// Opens the inspector window, editing the components of the selected entity.
+/// The components are drawn by the editors registered with `with_component`.
+pub struct Inspector {
+    pub open: bool,
+    pub selected: Option<Entity>,
+    components: Vec<(TypeId, &'static str, Draw)>,
+}
+
+impl Component for Inspector {}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inspector {
+    /// Create an inspector with the editors of the engine's components.
+    pub fn new() -> Self {
+        Self {
+            open: true,
+            selected: None,
+            components: Vec::new(),
+        }
+        .with_component::<Name>("Name")
+        .with_component::<Pos3>("Transform")
+        .with_component::<Scale>("Scale")
+        .with_component::<Velocity>("Velocity")
+        .with_component::<Light>("Light")
+        .with_component::<Health>("Health")
+    }
+
+    /// Register the editor of a component, replacing the previous one.
+    pub fn with_component<T: Inspect + Component>(mut self, name: &'static str) -> Self {
+        let type_id = TypeId::of::<T>();
+        self.components.retain(|(id, _, _)| *id != type_id);
+        self.components.push((type_id, name, draw::<T>));
+        self
+    }
+
+    /// Draw the editors of the components of an entity.
+    ///
+    /// # Returns
+    ///
+    /// The names of the edited components.
+    pub fn show_entity(
+        &self,
+        ui: &mut egui::Ui,
+        ecs: &ecs::Manager,
+        entity: Entity,
+    ) -> Vec<&'static str> {
+        let mut edited = Vec::new();
+        for (type_id, name, draw) in &self.components {
+            let id = egui::Id::new(("inspector_section", entity, type_id));
+            egui::CollapsingHeader::new(*name)
+                .id_salt(id)
+                .default_open(true)
+                .show(ui, |ui| {
+                    if draw(ui, ecs, entity) == Some(true) {
+                        edited.push(*name);
+                    }
+                });
+        }
+        edited
+    }
+}
+
+/// Draw the inspector window.
+pub fn show_inspector(ctx: &egui::Context, ecs: &ecs::Manager) {
+    let Some((_, inspector)) = ecs.get_all_components_of_type::<Inspector>().pop() else {
+        return;
+    };
+    let mut inspector = inspector.write().unwrap();
+    if !inspector.open {
+        return;
+    }
+
+    let mut entities = ecs.iter_entities().collect::<Vec<_>>();
+    entities.sort_by_key(|entity| entity.id());
+    let label = |entity: Entity| match ecs.get_component_from_entity::<Name>(entity) {
+        Some(name) => format!("{} ({})", name.read().unwrap().0, entity.id()),
+        None => format!("Entity {}", entity.id()),
+    };
+
+    let mut open = inspector.open;
+    egui::Window::new("Inspector")
+        .open(&mut open)
+        .default_width(300.0)
+        .show(ctx, |ui| {
+            let selected_text = inspector.selected.map(label).unwrap_or_default();
+            egui::ComboBox::from_label("Entity")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    for entity in &entities {
+                        ui.selectable_value(&mut inspector.selected, Some(*entity), label(*entity));
+                    }
+                });
+            ui.separator();
+
+            match inspector
+                .selected
+                .filter(|entity| entities.contains(entity))
+            {
+                Some(entity) => {
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        inspector.show_entity(ui, ecs, entity);
+                    });
+                }
+                None => {
+                    ui.label("No entity selected");
+                }
+            }
+        });
+    inspector.open = open;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Rotation3;
+
+    #[test]
+    fn test_euler_degrees() {
+        let rotation = Quaternion::from(Euler::new(Deg(10.0), Deg(20.0), Deg(30.0)));
+        let angles = euler_degrees(rotation, None);
+        for (angle, expected) in angles.iter().zip([10.0, 20.0, 30.0]) {
+            assert!((angle - expected).abs() < 1e-3, "{:?}", angles);
+        }
+
+        // At the gimbal lock the edited angles are kept instead of an equivalent decomposition
+        let edited = [40.0, 90.0, 0.0];
+        let locked = Quaternion::from(Euler::new(Deg(40.0), Deg(90.0), Deg(0.0)));
+        assert_eq!(euler_degrees(locked, Some((edited, locked))), edited);
+        assert_eq!(euler_degrees(-locked, Some((edited, locked))), edited);
+        // A rotation changed elsewhere is decomposed again
+        let turned = Quaternion::from_angle_y(Deg(45.0));
+        assert_ne!(euler_degrees(turned, Some((edited, locked))), edited);
+    }
+
+    #[test]
+    fn test_show_entity() {
+        let ecs = ecs::Manager::default();
+        let entity = ecs.create_entity();
+        ecs.add_component_to_entity(entity, Pos3::default());
+        ecs.add_component_to_entity(entity, Health::new(10.0));
+
+        let inspector = Inspector::new();
+        let ctx = egui::Context::default();
+        let mut edited = None;
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                edited = Some(inspector.show_entity(ui, &ecs, entity));
+            });
+        });
+        assert_eq!(edited, Some(vec![]));
+
+        // Drawing does not change the components
+        let pos = ecs.get_component_from_entity::<Pos3>(entity).unwrap();
+        assert_eq!(*pos.read().unwrap(), Pos3::default());
+    }
+}
@@ -0,0 +1,69 @@
+pub mod ai;
+pub mod camera;
+pub mod inspector;
+pub mod net;
+#[cfg(feature = "particles")]
+pub mod particles;
+pub mod physics;
+pub mod play;
+pub mod scene_tree;
+pub mod stats;
+pub mod stepping;
+pub mod systems;
+pub mod timeline;
+
+use crate::ecs;
+
+/// Check if the world has an editor tool to draw.
+pub fn has_editor_ui(ecs: &ecs::Manager) -> bool {
+    !ecs.get_entites_with_component::<play::PlayMode>()
+        .is_empty()
+        || !ecs
+            .get_entites_with_component::<inspector::Inspector>()
+            .is_empty()
+        || !ecs
+            .get_entites_with_component::<physics::PhysicsDebug>()
+            .is_empty()
+        || !ecs.get_entites_with_component::<ai::AiDebug>().is_empty()
+        || !ecs.get_entites_with_component::<net::NetDebug>().is_empty()
+        || !ecs
+            .get_entites_with_component::<systems::SystemGraph>()
+            .is_empty()
+        || !ecs
+            .get_entites_with_component::<stats::EcsStats>()
+            .is_empty()
+        || !ecs
+            .get_entites_with_component::<scene_tree::SceneTree>()
+            .is_empty()
+        || !ecs
+            .get_entites_with_component::<stepping::SteppingControls>()
+            .is_empty()
+        || has_particle_editor(ecs)
+}
+
+#[cfg(feature = "particles")]
+fn has_particle_editor(ecs: &ecs::Manager) -> bool {
+    !ecs.get_entites_with_component::<particles::ParticleEditor>()
+        .is_empty()
+}
+
+#[cfg(not(feature = "particles"))]
+fn has_particle_editor(_ecs: &ecs::Manager) -> bool {
+    false
+}
+
+/// Draw the editor tools of the world.
+pub fn show_editor_ui(ctx: &egui::Context, ecs: &ecs::Manager) {
+    play::show_play_controls(ctx, ecs);
+    stepping::show_stepping_controls(ctx, ecs);
+    scene_tree::show_scene_tree(ctx, ecs);
+    inspector::show_inspector(ctx, ecs);
+    timeline::show_timeline(ctx, ecs);
+    physics::show_physics_debug(ctx, ecs);
+    ai::show_ai_debug(ctx, ecs);
+    net::show_net_debug(ctx, ecs);
+    systems::show_system_graph(ctx, ecs);
+    stats::show_ecs_stats(ctx, ecs);
+    #[cfg(feature = "particles")]
+    particles::show_particle_editor(ctx, ecs);
+}
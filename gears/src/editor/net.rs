@@ -0,0 +1,331 @@
+use crate::ecs;
+use crate::ecs::traits::Component;
+use crate::net::diagnostics::{NetConditions, NetSample, NetStats, HISTORY_LEN};
+use instant::Duration;
+
+/// The height of the graphs in points.
+const GRAPH_HEIGHT: f32 = 60.0;
+
+/// Opens the network debug panel, graphing the round trip time, the packet loss and the traffic
+/// of each channel recorded by the server, with the number of replicated components.
+/// The latency and loss toggles make the server simulate a bad network.
+///
+/// The panel adds a `NetStats` and a `NetConditions` component to its entity,
+/// so the server records its activity and reads the conditions.
+#[derive(Debug, Clone)]
+pub struct NetDebug {
+    pub open: bool,
+    /// The channel graphed, all of them when empty.
+    pub channel: String,
+}
+
+impl Component for NetDebug {}
+
+impl Default for NetDebug {
+    fn default() -> Self {
+        Self {
+            open: true,
+            channel: String::new(),
+        }
+    }
+}
+
+/// Draw the history of one or more values, scaled to the largest value.
+///
+/// # Arguments
+///
+/// * `ui` - The ui to draw into.
+/// * `label` - The name of the graph, followed by the latest values.
+/// * `series` - The values of each line, the oldest first, with their name and color.
+/// * `capacity` - The number of values the graph spans, the latest value is on the right edge.
+/// * `format` - Formats a value for the labels.
+pub fn graph(
+    ui: &mut egui::Ui,
+    label: &str,
+    series: &[(&str, Vec<f32>, egui::Color32)],
+    capacity: usize,
+    format: impl Fn(f32) -> String,
+) {
+    let max = series
+        .iter()
+        .flat_map(|(_, values, _)| values.iter().copied())
+        .fold(0.0f32, f32::max);
+
+    ui.horizontal(|ui| {
+        ui.strong(label);
+        for (name, values, color) in series {
+            let latest = values.last().copied().unwrap_or_default();
+            ui.colored_label(*color, format!("{} {}", name, format(latest)));
+        }
+        ui.weak(format!("max {}", format(max)));
+    });
+
+    let (rect, _) = ui.allocate_exact_size(
+        egui::vec2(ui.available_width().max(40.0), GRAPH_HEIGHT),
+        egui::Sense::hover(),
+    );
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    for (_, values, color) in series {
+        let offset = capacity.saturating_sub(values.len());
+        let points = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let x = (offset + i) as f32 / capacity.saturating_sub(1).max(1) as f32;
+                let y = if max > 0.0 { value / max } else { 0.0 };
+                egui::pos2(
+                    egui::lerp(rect.x_range(), x),
+                    egui::lerp(rect.bottom()..=rect.top(), y),
+                )
+            })
+            .collect::<Vec<_>>();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, *color)));
+    }
+}
+
+fn bytes(value: f32) -> String {
+    if value >= 1024.0 * 1024.0 {
+        format!("{:.1} MB/s", value / (1024.0 * 1024.0))
+    } else if value >= 1024.0 {
+        format!("{:.1} kB/s", value / 1024.0)
+    } else {
+        format!("{:.0} B/s", value)
+    }
+}
+
+/// Get the names of the channels seen in the samples.
+fn channels(samples: &[NetSample]) -> Vec<String> {
+    let mut channels = samples
+        .iter()
+        .flat_map(|sample| sample.channels.keys().cloned())
+        .collect::<Vec<_>>();
+    channels.sort();
+    channels.dedup();
+    channels
+}
+
+/// Draw the latency and loss toggles.
+fn show_conditions(ui: &mut egui::Ui, conditions: &mut NetConditions) {
+    ui.checkbox(&mut conditions.enabled, "Simulate a bad network");
+    ui.add_enabled_ui(conditions.enabled, |ui| {
+        egui::Grid::new("net_conditions")
+            .num_columns(2)
+            .show(ui, |ui| {
+                let mut latency = conditions.latency.as_millis() as u64;
+                ui.label("Latency");
+                if ui
+                    .add(egui::Slider::new(&mut latency, 0..=1000).suffix(" ms"))
+                    .changed()
+                {
+                    conditions.latency = Duration::from_millis(latency);
+                }
+                ui.end_row();
+
+                let mut loss = conditions.loss * 100.0;
+                ui.label("Loss");
+                if ui
+                    .add(egui::Slider::new(&mut loss, 0.0..=100.0).suffix(" %"))
+                    .changed()
+                {
+                    conditions.loss = loss / 100.0;
+                }
+                ui.end_row();
+            });
+    });
+}
+
+/// Draw the network debug panel.
+pub fn show_net_debug(ctx: &egui::Context, ecs: &ecs::Manager) {
+    let Some((panel, debug)) = ecs.get_all_components_of_type::<NetDebug>().pop() else {
+        return;
+    };
+    if ecs.get_component_from_entity::<NetStats>(panel).is_none() {
+        ecs.add_component_to_entity(panel, NetStats::default());
+    }
+    if ecs
+        .get_component_from_entity::<NetConditions>(panel)
+        .is_none()
+    {
+        ecs.add_component_to_entity(panel, NetConditions::default());
+    }
+    let mut debug = debug.write().unwrap();
+    if !debug.open {
+        return;
+    }
+
+    let samples = ecs
+        .get_component_from_entity::<NetStats>(panel)
+        .map(|stats| stats.read().unwrap().history().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let channels = channels(&samples);
+
+    let mut open = debug.open;
+    egui::Window::new("Network")
+        .open(&mut open)
+        .default_width(420.0)
+        .show(ctx, |ui| {
+            if let Some(conditions) = ecs.get_component_from_entity::<NetConditions>(panel) {
+                show_conditions(ui, &mut conditions.write().unwrap());
+                ui.separator();
+            }
+
+            if samples.is_empty() {
+                ui.label("No network activity recorded, is the server running?");
+                return;
+            }
+            let (incoming, outgoing) = (egui::Color32::LIGHT_BLUE, egui::Color32::LIGHT_GREEN);
+
+            let rtt = samples
+                .iter()
+                .map(|sample| sample.rtt.map_or(0.0, |rtt| rtt.as_secs_f32() * 1000.0))
+                .collect();
+            graph(
+                ui,
+                "RTT",
+                &[("", rtt, egui::Color32::YELLOW)],
+                HISTORY_LEN,
+                |ms| format!("{:.0} ms", ms),
+            );
+            let loss = samples.iter().map(|sample| sample.loss * 100.0).collect();
+            let dropped = samples
+                .iter()
+                .map(|sample| sample.dropped as f32)
+                .collect::<Vec<_>>();
+            graph(
+                ui,
+                "Loss",
+                &[("", loss, egui::Color32::LIGHT_RED)],
+                HISTORY_LEN,
+                |percent| format!("{:.1} %", percent),
+            );
+            if dropped.iter().any(|dropped| *dropped > 0.0) {
+                ui.weak(format!(
+                    "{} datagrams dropped by the simulation in the last second",
+                    dropped.last().copied().unwrap_or_default()
+                ));
+            }
+
+            ui.separator();
+            egui::ComboBox::from_label("Channel")
+                .selected_text(if debug.channel.is_empty() {
+                    "All"
+                } else {
+                    debug.channel.as_str()
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut debug.channel, String::new(), "All");
+                    for channel in &channels {
+                        ui.selectable_value(&mut debug.channel, channel.clone(), channel);
+                    }
+                });
+            let traffic = samples
+                .iter()
+                .map(|sample| {
+                    if debug.channel.is_empty() {
+                        sample.total()
+                    } else {
+                        sample
+                            .channels
+                            .get(&debug.channel)
+                            .copied()
+                            .unwrap_or_default()
+                    }
+                })
+                .collect::<Vec<_>>();
+            graph(
+                ui,
+                "Bandwidth",
+                &[
+                    (
+                        "in",
+                        traffic.iter().map(|t| t.bytes_in as f32).collect(),
+                        incoming,
+                    ),
+                    (
+                        "out",
+                        traffic.iter().map(|t| t.bytes_out as f32).collect(),
+                        outgoing,
+                    ),
+                ],
+                HISTORY_LEN,
+                bytes,
+            );
+
+            let latest = samples.last().unwrap();
+            ui.collapsing("Channels", |ui| {
+                egui::Grid::new("net_channels")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (channel, traffic) in &latest.channels {
+                            ui.label(channel);
+                            ui.colored_label(
+                                incoming,
+                                format!(
+                                    "in {} ({} packets)",
+                                    bytes(traffic.bytes_in as f32),
+                                    traffic.packets_in
+                                ),
+                            );
+                            ui.colored_label(
+                                outgoing,
+                                format!(
+                                    "out {} ({} packets)",
+                                    bytes(traffic.bytes_out as f32),
+                                    traffic.packets_out
+                                ),
+                            );
+                            ui.end_row();
+                        }
+                    });
+            });
+            ui.collapsing("Replication", |ui| {
+                if latest.replicated.is_empty() {
+                    ui.label("No components replicated in the last second");
+                }
+                egui::Grid::new("net_replication")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (component, count) in &latest.replicated {
+                            ui.label(*component);
+                            ui.label(format!("{}/s", count));
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+    debug.open = open;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_show_net_debug() {
+        let ecs = ecs::Manager::default();
+        let editor = ecs.create_entity();
+        ecs.add_component_to_entity(editor, NetDebug::default());
+
+        let ctx = egui::Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |ctx| show_net_debug(ctx, &ecs));
+
+        // The server records into the panel and reads its conditions from now on
+        assert!(ecs.get_component_from_entity::<NetStats>(editor).is_some());
+        let conditions = ecs
+            .get_component_from_entity::<NetConditions>(editor)
+            .unwrap();
+        assert!(!conditions.read().unwrap().enabled);
+
+        let mut sample = NetSample::default();
+        sample
+            .channels
+            .insert("snapshot".to_string(), Default::default());
+        sample
+            .channels
+            .insert("ack".to_string(), Default::default());
+        assert_eq!(channels(&[sample.clone(), sample]), vec!["ack", "snapshot"]);
+        let _ = ctx.run(egui::RawInput::default(), |ctx| show_net_debug(ctx, &ecs));
+    }
+}
@@ -0,0 +1,305 @@
+use super::inspector::{self, Inspector};
+use crate::ecs::components::Name;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use crate::renderer::particles::{self, ParticleCollision, ParticleEmitter};
+
+const CURVE_HEIGHT: f32 = 60.0;
+
+/// Opens the particle editor window, editing the `ParticleEmitter` of an entity.
+/// The entity selected in the inspector is followed when it has an emitter.
+///
+/// The renderer uploads the emitter parameters every frame, so the edits are previewed live.
+/// The effect can be saved to and loaded from a RON preset.
+#[derive(Debug, Clone)]
+pub struct ParticleEditor {
+    pub open: bool,
+    pub selected: Option<Entity>,
+    /// The path of the preset to save and load.
+    pub preset_path: String,
+    status: Option<String>,
+}
+
+impl Component for ParticleEditor {}
+
+impl Default for ParticleEditor {
+    fn default() -> Self {
+        Self {
+            open: true,
+            selected: None,
+            preset_path: "effect.ron".to_string(),
+            status: None,
+        }
+    }
+}
+
+/// Draw a curve over the lifetime with a slider per point.
+fn curve_sliders(ui: &mut egui::Ui, label: &str, curve: &mut [f32; 4], max: f32) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        for value in curve.iter_mut() {
+            let hover = format!("{:.2}", value);
+            ui.add(
+                egui::Slider::new(value, 0.0..=max)
+                    .vertical()
+                    .show_value(false),
+            )
+            .on_hover_text(hover);
+        }
+
+        let (rect, _) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width().max(40.0), CURVE_HEIGHT),
+            egui::Sense::hover(),
+        );
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+        let points = (0..=32)
+            .map(|i| {
+                let t = i as f32 / 32.0;
+                let value = particles::sample_curve(curve, t) / max;
+                egui::pos2(
+                    egui::lerp(rect.x_range(), t),
+                    egui::lerp(rect.bottom()..=rect.top(), value.clamp(0.0, 1.0)),
+                )
+            })
+            .collect::<Vec<_>>();
+        painter.add(egui::Shape::line(
+            points,
+            ui.visuals().widgets.active.fg_stroke,
+        ));
+    });
+}
+
+/// Draw the parameters of an emitter.
+fn show_emitter(ui: &mut egui::Ui, id: egui::Id, emitter: &mut ParticleEmitter) {
+    ui.checkbox(&mut emitter.enabled, "Enabled");
+    egui::Grid::new(id.with("parameters"))
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Rate");
+            ui.add(
+                egui::DragValue::new(&mut emitter.rate)
+                    .speed(1.0)
+                    .range(0.0..=f32::MAX)
+                    .suffix(" /s"),
+            );
+            ui.end_row();
+
+            ui.label("Lifetime");
+            ui.add(
+                egui::DragValue::new(&mut emitter.lifetime)
+                    .speed(0.05)
+                    .range(0.01..=f32::MAX)
+                    .suffix(" s"),
+            );
+            ui.end_row();
+
+            ui.label("Max particles");
+            let mut max_particles = emitter.max_particles;
+            if ui
+                .add(egui::DragValue::new(&mut max_particles).range(1..=1_000_000))
+                .changed()
+            {
+                emitter.max_particles = max_particles.max(1);
+            }
+            ui.end_row();
+
+            ui.label("Direction");
+            inspector::drag_vec3(ui, &mut emitter.direction, 0.01);
+            ui.end_row();
+
+            ui.label("Speed");
+            ui.add(egui::DragValue::new(&mut emitter.speed).speed(0.05));
+            ui.end_row();
+
+            ui.label("Spread");
+            ui.add(egui::Slider::new(&mut emitter.spread.0, 0.0..=180.0).suffix("°"));
+            ui.end_row();
+
+            ui.label("Gravity");
+            inspector::drag_vec3(ui, &mut emitter.gravity, 0.05);
+            ui.end_row();
+
+            ui.label("Size");
+            ui.add(
+                egui::DragValue::new(&mut emitter.size)
+                    .speed(0.005)
+                    .range(0.0..=f32::MAX),
+            );
+            ui.end_row();
+
+            ui.label("Color");
+            ui.color_edit_button_rgba_unmultiplied(&mut emitter.color);
+            ui.end_row();
+
+            ui.label("Collision");
+            ui.horizontal(|ui| {
+                let text = match emitter.collision {
+                    ParticleCollision::None => "None",
+                    ParticleCollision::Die => "Die",
+                    ParticleCollision::Bounce { .. } => "Bounce",
+                };
+                egui::ComboBox::from_id_salt(id.with("collision"))
+                    .selected_text(text)
+                    .show_ui(ui, |ui| {
+                        let bounce = match emitter.collision {
+                            ParticleCollision::Bounce { .. } => emitter.collision,
+                            _ => ParticleCollision::Bounce { restitution: 0.5 },
+                        };
+                        let collision = &mut emitter.collision;
+                        ui.selectable_value(collision, ParticleCollision::None, "None");
+                        ui.selectable_value(collision, ParticleCollision::Die, "Die");
+                        ui.selectable_value(collision, bounce, "Bounce");
+                    });
+                if let ParticleCollision::Bounce { restitution } = &mut emitter.collision {
+                    ui.add(egui::Slider::new(restitution, 0.0..=1.0).text("Restitution"));
+                }
+            });
+            ui.end_row();
+
+            ui.label("Texture");
+            ui.horizontal(|ui| {
+                // The path is applied once it is typed, not on every key
+                let key = id.with("texture");
+                let mut path = ui
+                    .data(|data| data.get_temp::<String>(key))
+                    .unwrap_or_else(|| emitter.texture.clone().unwrap_or_default());
+                let edit = ui.add(egui::TextEdit::singleline(&mut path).hint_text("None"));
+                if edit.has_focus() {
+                    ui.data_mut(|data| data.insert_temp(key, path.clone()));
+                } else {
+                    ui.data_mut(|data| data.remove::<String>(key));
+                }
+                if edit.lost_focus() {
+                    emitter.texture = (!path.trim().is_empty()).then(|| path.trim().to_string());
+                }
+                if emitter.texture.is_some() && ui.small_button("✖").clicked() {
+                    emitter.texture = None;
+                }
+            });
+            ui.end_row();
+        });
+
+    ui.separator();
+    ui.label("Over the lifetime");
+    curve_sliders(ui, "Size ", &mut emitter.size_curve, 4.0);
+    curve_sliders(ui, "Alpha", &mut emitter.alpha_curve, 1.0);
+}
+
+/// Draw the preset path and the save and load buttons.
+fn show_presets(ui: &mut egui::Ui, editor: &mut ParticleEditor, emitter: &mut ParticleEmitter) {
+    ui.horizontal(|ui| {
+        ui.label("Preset");
+        ui.text_edit_singleline(&mut editor.preset_path);
+        if ui.button("Save").clicked() {
+            editor.status = Some(match emitter.save_preset(&editor.preset_path) {
+                Ok(()) => format!("Saved {}", editor.preset_path),
+                Err(e) => e.to_string(),
+            });
+        }
+        if ui.button("Load").clicked() {
+            editor.status = Some(match ParticleEmitter::load_preset(&editor.preset_path) {
+                Ok(preset) => {
+                    let enabled = emitter.enabled;
+                    *emitter = preset;
+                    emitter.enabled = enabled;
+                    format!("Loaded {}", editor.preset_path)
+                }
+                Err(e) => e.to_string(),
+            });
+        }
+    });
+    if let Some(status) = &editor.status {
+        ui.small(status);
+    }
+}
+
+/// Draw the particle editor window.
+pub fn show_particle_editor(ctx: &egui::Context, ecs: &ecs::Manager) {
+    let Some((_, editor)) = ecs.get_all_components_of_type::<ParticleEditor>().pop() else {
+        return;
+    };
+    let mut editor = editor.write().unwrap();
+    if !editor.open {
+        return;
+    }
+
+    let mut emitters = ecs.get_entites_with_component::<ParticleEmitter>();
+    emitters.sort_by_key(|entity| entity.id());
+    let inspected = ecs
+        .get_all_components_of_type::<Inspector>()
+        .pop()
+        .and_then(|(_, inspector)| inspector.read().unwrap().selected);
+    if let Some(entity) = inspected.filter(|entity| emitters.contains(entity)) {
+        editor.selected = Some(entity);
+    }
+    if !editor
+        .selected
+        .is_some_and(|entity| emitters.contains(&entity))
+    {
+        editor.selected = emitters.first().copied();
+    }
+
+    let label = |entity: Entity| match ecs.get_component_from_entity::<Name>(entity) {
+        Some(name) => format!("{} ({})", name.read().unwrap().0, entity.id()),
+        None => format!("Entity {}", entity.id()),
+    };
+
+    let mut open = editor.open;
+    egui::Window::new("Particles")
+        .open(&mut open)
+        .default_width(360.0)
+        .show(ctx, |ui| {
+            egui::ComboBox::from_label("Entity")
+                .selected_text(editor.selected.map(label).unwrap_or_default())
+                .show_ui(ui, |ui| {
+                    for entity in &emitters {
+                        ui.selectable_value(&mut editor.selected, Some(*entity), label(*entity));
+                    }
+                });
+
+            let Some(entity) = editor.selected else {
+                ui.label("No entity has a ParticleEmitter");
+                return;
+            };
+            let Some(emitter) = ecs.get_component_from_entity::<ParticleEmitter>(entity) else {
+                return;
+            };
+            let mut emitter = emitter.write().unwrap();
+
+            ui.separator();
+            show_emitter(ui, egui::Id::new(("particles", entity)), &mut emitter);
+            ui.separator();
+            show_presets(ui, &mut editor, &mut emitter);
+        });
+    editor.open = open;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_show_particle_editor() {
+        let ecs = ecs::Manager::default();
+        let entity = ecs.create_entity();
+        let emitter = ParticleEmitter::new(20.0, 1.0).with_texture("res/textures/spark.png");
+        ecs.add_component_to_entity(entity, emitter.clone());
+        let editor = ecs.create_entity();
+        ecs.add_component_to_entity(editor, ParticleEditor::default());
+
+        let ctx = egui::Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            show_particle_editor(ctx, &ecs)
+        });
+
+        // The only emitter is selected and drawing does not change it
+        let particle_editor = ecs.get_component_from_entity::<ParticleEditor>(editor);
+        assert_eq!(
+            particle_editor.unwrap().read().unwrap().selected,
+            Some(entity)
+        );
+        let drawn = ecs.get_component_from_entity::<ParticleEmitter>(entity);
+        assert_eq!(*drawn.unwrap().read().unwrap(), emitter);
+    }
+}
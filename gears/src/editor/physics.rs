@@ -0,0 +1,351 @@
+use super::inspector::{self, Inspector};
+use crate::ecs::components::{Name, Pos3, Velocity};
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use crate::physics::body::RigidBody;
+use crate::physics::constraint::DistanceConstraint;
+use crate::physics::settings::PhysicsSettings;
+use crate::physics::world::Contacts;
+use cgmath::{InnerSpace, Vector3, Zero};
+
+/// The speed below which a body is shown as resting.
+const RESTING_SPEED: f32 = 0.05;
+
+/// Opens the physics debug panel, listing the rigid bodies, the contacts of the last physics step
+/// and the constraints. The selected body can be frozen and teleported.
+/// The entity selected in the inspector is followed when it is a rigid body.
+///
+/// The panel adds a `Contacts` component to its entity, so the physics step records the contacts.
+#[derive(Debug, Clone)]
+pub struct PhysicsDebug {
+    pub open: bool,
+    pub selected: Option<Entity>,
+    /// Where the teleport button moves the selected body.
+    pub teleport_to: Vector3<f32>,
+}
+
+impl Component for PhysicsDebug {}
+
+impl Default for PhysicsDebug {
+    fn default() -> Self {
+        Self {
+            open: true,
+            selected: None,
+            teleport_to: Vector3::zero(),
+        }
+    }
+}
+
+/// Get the state of a body shown in the panel.
+fn body_state(body: &RigidBody, velocity: Vector3<f32>) -> &'static str {
+    if body.is_static() {
+        "static"
+    } else if body.frozen {
+        "frozen"
+    } else if body.is_sleeping() {
+        "sleeping"
+    } else if velocity.magnitude() < RESTING_SPEED {
+        "resting"
+    } else {
+        "moving"
+    }
+}
+
+/// Draw the overrides of the selected body.
+fn show_body(ui: &mut egui::Ui, ecs: &ecs::Manager, debug: &mut PhysicsDebug, entity: Entity) {
+    let Some(body) = ecs.get_component_from_entity::<RigidBody>(entity) else {
+        return;
+    };
+    let mut body = body.write().unwrap();
+
+    egui::Grid::new(("physics_body", entity))
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Mass");
+            ui.add(
+                egui::DragValue::new(&mut body.mass)
+                    .speed(0.05)
+                    .range(0.0..=f32::MAX)
+                    .suffix(" kg"),
+            );
+            ui.end_row();
+
+            ui.label("Frozen");
+            ui.checkbox(&mut body.frozen, "");
+            ui.end_row();
+
+            if let Some(velocity) = ecs.get_component_from_entity::<Velocity>(entity) {
+                ui.label("Velocity");
+                inspector::drag_vec3(ui, &mut velocity.write().unwrap().0, 0.05);
+                ui.end_row();
+            }
+
+            let force = body.applied_force();
+            ui.label("Force");
+            ui.label(format!("{:.2} {:.2} {:.2}", force.x, force.y, force.z));
+            ui.end_row();
+
+            ui.label("Gravity");
+            ui.horizontal(|ui| {
+                let mut own = body.gravity.is_some();
+                if ui
+                    .checkbox(&mut own, "")
+                    .on_hover_text("Use an own gravity instead of the world one")
+                    .changed()
+                {
+                    body.gravity = own.then(|| {
+                        ecs.get_resource::<PhysicsSettings>()
+                            .map_or(PhysicsSettings::default().gravity, |settings| {
+                                settings.read().unwrap().gravity
+                            })
+                    });
+                }
+                if let Some(gravity) = &mut body.gravity {
+                    inspector::drag_vec3(ui, gravity, 0.05);
+                }
+            });
+            ui.end_row();
+
+            ui.label("Restitution");
+            ui.add(egui::Slider::new(&mut body.restitution, 0.0..=1.0));
+            ui.end_row();
+        });
+
+    ui.horizontal(|ui| {
+        inspector::drag_vec3(ui, &mut debug.teleport_to, 0.05);
+        if ui.button("Teleport").clicked() {
+            body.wake();
+            if let Some(pos) = ecs.get_component_from_entity::<Pos3>(entity) {
+                pos.write().unwrap().pos = debug.teleport_to;
+            }
+            if let Some(velocity) = ecs.get_component_from_entity::<Velocity>(entity) {
+                velocity.write().unwrap().0 = Vector3::zero();
+            }
+        }
+        if ui
+            .button("Here")
+            .on_hover_text("Use the current position")
+            .clicked()
+        {
+            if let Some(pos) = ecs.get_component_from_entity::<Pos3>(entity) {
+                debug.teleport_to = pos.read().unwrap().pos;
+            }
+        }
+    });
+}
+
+/// Draw the settings of the physics world.
+fn show_settings(ui: &mut egui::Ui, ecs: &ecs::Manager) {
+    let Some(settings) = ecs.get_resource::<PhysicsSettings>() else {
+        ui.label("The physics has not run yet");
+        return;
+    };
+    let mut settings = settings.write().unwrap();
+
+    egui::Grid::new("physics_settings")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Gravity");
+            inspector::drag_vec3(ui, &mut settings.gravity, 0.05);
+            ui.end_row();
+
+            ui.label("Solver iterations");
+            ui.add(egui::DragValue::new(&mut settings.solver_iterations).range(1..=32));
+            ui.end_row();
+
+            ui.label("Sleep speed");
+            ui.add(
+                egui::DragValue::new(&mut settings.sleep_speed)
+                    .speed(0.005)
+                    .range(0.0..=f32::MAX),
+            );
+            ui.end_row();
+
+            ui.label("Sleep steps");
+            ui.add(egui::DragValue::new(&mut settings.sleep_steps).range(1..=u32::MAX));
+            ui.end_row();
+        });
+}
+
+/// Draw the physics debug panel.
+pub fn show_physics_debug(ctx: &egui::Context, ecs: &ecs::Manager) {
+    let Some((panel, debug)) = ecs.get_all_components_of_type::<PhysicsDebug>().pop() else {
+        return;
+    };
+    if ecs.get_component_from_entity::<Contacts>(panel).is_none() {
+        ecs.add_component_to_entity(panel, Contacts::default());
+    }
+    let mut debug = debug.write().unwrap();
+    if !debug.open {
+        return;
+    }
+
+    let mut bodies = ecs.get_entites_with_component::<RigidBody>();
+    bodies.sort_by_key(|entity| entity.id());
+    let inspected = ecs
+        .get_all_components_of_type::<Inspector>()
+        .pop()
+        .and_then(|(_, inspector)| inspector.read().unwrap().selected);
+    if let Some(entity) = inspected.filter(|entity| bodies.contains(entity)) {
+        debug.selected = Some(entity);
+    }
+    if !debug
+        .selected
+        .is_some_and(|entity| bodies.contains(&entity))
+    {
+        debug.selected = None;
+    }
+
+    let sleeping = bodies
+        .iter()
+        .filter(|entity| {
+            ecs.get_component_from_entity::<RigidBody>(**entity)
+                .is_some_and(|body| body.read().unwrap().is_sleeping())
+        })
+        .count();
+    let bodies_header = format!("Bodies ({}, {} sleeping)", bodies.len(), sleeping);
+
+    let label = |entity: Entity| match ecs.get_component_from_entity::<Name>(entity) {
+        Some(name) => format!("{} ({})", name.read().unwrap().0, entity.id()),
+        None => format!("Entity {}", entity.id()),
+    };
+    let velocity = |entity: Entity| {
+        ecs.get_component_from_entity::<Velocity>(entity)
+            .map_or(Vector3::zero(), |velocity| velocity.read().unwrap().0)
+    };
+    let position = |entity: Entity| {
+        ecs.get_component_from_entity::<Pos3>(entity)
+            .map(|pos| pos.read().unwrap().pos)
+    };
+
+    let mut open = debug.open;
+    egui::Window::new("Physics")
+        .open(&mut open)
+        .default_width(420.0)
+        .show(ctx, |ui| {
+            ui.collapsing("World", |ui| show_settings(ui, ecs));
+            ui.collapsing(bodies_header, |ui| {
+                egui::ScrollArea::vertical()
+                    .id_salt("physics_bodies")
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("physics_bodies_grid")
+                            .num_columns(4)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for entity in &bodies {
+                                    let Some(body) =
+                                        ecs.get_component_from_entity::<RigidBody>(*entity)
+                                    else {
+                                        continue;
+                                    };
+                                    let body = *body.read().unwrap();
+                                    let v = velocity(*entity);
+                                    let force = body.applied_force();
+
+                                    let selected = debug.selected == Some(*entity);
+                                    if ui.selectable_label(selected, label(*entity)).clicked() {
+                                        debug.selected = Some(*entity);
+                                        if let Some(pos) = position(*entity) {
+                                            debug.teleport_to = pos;
+                                        }
+                                    }
+                                    ui.label(body_state(&body, v));
+                                    ui.label(format!("v {:.2}", v.magnitude()));
+                                    ui.label(format!("F {:.2}", force.magnitude()));
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+
+            if let Some(entity) = debug.selected {
+                ui.separator();
+                ui.strong(label(entity));
+                show_body(ui, ecs, &mut debug, entity);
+            }
+
+            let contacts = ecs
+                .get_component_from_entity::<Contacts>(panel)
+                .map(|contacts| contacts.read().unwrap().0.clone())
+                .unwrap_or_default();
+            ui.separator();
+            ui.collapsing(format!("Contacts ({})", contacts.len()), |ui| {
+                for contact in &contacts {
+                    ui.label(format!(
+                        "{} ↔ {}: depth {:.3} at ({:.2}, {:.2}, {:.2})",
+                        label(contact.a),
+                        label(contact.b),
+                        contact.depth,
+                        contact.point.x,
+                        contact.point.y,
+                        contact.point.z,
+                    ));
+                }
+            });
+
+            let constraints = ecs.get_all_components_of_type::<DistanceConstraint>();
+            ui.collapsing(format!("Constraints ({})", constraints.len()), |ui| {
+                for (entity, constraint) in &constraints {
+                    let constraint = *constraint.read().unwrap();
+                    let length = match (position(*entity), position(constraint.other)) {
+                        (Some(a), Some(b)) => (b - a).magnitude(),
+                        _ => continue,
+                    };
+                    // Show how far the constraint is from being satisfied
+                    let error = (length - constraint.distance).abs();
+                    let color = if error > 0.01 * constraint.distance.max(1.0) {
+                        ui.visuals().warn_fg_color
+                    } else {
+                        ui.visuals().text_color()
+                    };
+                    ui.colored_label(
+                        color,
+                        format!(
+                            "{} — {}: {:.3} / {:.3}",
+                            label(*entity),
+                            label(constraint.other),
+                            length,
+                            constraint.distance,
+                        ),
+                    );
+                }
+            });
+        });
+    debug.open = open;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_show_physics_debug() {
+        let ecs = ecs::Manager::default();
+        let body = ecs.create_entity();
+        ecs.add_component_to_entity(body, Pos3::default());
+        ecs.add_component_to_entity(body, RigidBody::new(2.0));
+        let editor = ecs.create_entity();
+        ecs.add_component_to_entity(editor, PhysicsDebug::default());
+        let inspector = ecs.create_entity();
+        let mut selection = Inspector::new();
+        selection.selected = Some(body);
+        ecs.add_component_to_entity(inspector, selection);
+
+        let ctx = egui::Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            show_physics_debug(ctx, &ecs)
+        });
+
+        // The inspected body is selected and the contacts are recorded from now on
+        let debug = ecs
+            .get_component_from_entity::<PhysicsDebug>(editor)
+            .unwrap();
+        assert_eq!(debug.read().unwrap().selected, Some(body));
+        assert!(ecs.get_component_from_entity::<Contacts>(editor).is_some());
+        assert_eq!(
+            body_state(&RigidBody::new_static(), Vector3::zero()),
+            "static"
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use crate::core::Dt;
+use crate::ecs::components::{Camera, Collider, Flip, Light, Name, Pos3, Scale, Velocity};
+use crate::ecs::hierarchy::{AttachedTo, LocalPos3, Parent};
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use crate::gameplay::health::Health;
+use crate::gui::layout::HudAnchor;
+use std::any::{Any, TypeId};
+use std::collections::HashSet;
+
+type Capture = fn(&ecs::Manager) -> Box<dyn Any + Send + Sync>;
+type Restore = fn(&ecs::Manager, &(dyn Any + Send + Sync));
+
+fn capture<T: Clone + Send + Sync + 'static>(ecs: &ecs::Manager) -> Box<dyn Any + Send + Sync> {
+    let components = ecs
+        .get_all_components_of_type::<T>()
+        .into_iter()
+        .map(|(entity, component)| (entity, component.read().unwrap().clone()))
+        .collect::<Vec<_>>();
+    Box::new(components)
+}
+
+fn restore<T: Clone + Send + Sync + 'static>(ecs: &ecs::Manager, data: &(dyn Any + Send + Sync)) {
+    let Some(saved) = data.downcast_ref::<Vec<(Entity, T)>>() else {
+        return;
+    };
+
+    let saved_entities = saved
+        .iter()
+        .map(|(entity, _)| *entity)
+        .collect::<HashSet<_>>();
+    for entity in ecs.get_entites_with_component::<T>() {
+        if !saved_entities.contains(&entity) {
+            ecs.remove_component_from_entity::<T>(entity);
+        }
+    }
+    for (entity, value) in saved {
+        // Write into the existing component, so the handles held by the systems stay valid
+        match ecs.get_component_from_entity::<T>(*entity) {
+            Some(component) => *component.write().unwrap() = value.clone(),
+            None => ecs.add_component_to_entity(*entity, value.clone()),
+        }
+    }
+}
+
+/// A copy of the entities and of the registered components of the world.
+pub struct WorldSnapshot {
+    entities: HashSet<Entity>,
+    components: Vec<(Restore, Box<dyn Any + Send + Sync>)>,
+}
+
+impl WorldSnapshot {
+    pub fn entity_count(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Put the world back to the snapshot.
+    /// The entities created since are removed and the removed ones are created again,
+    /// the registered components get their saved values.
+    pub fn restore(&self, ecs: &ecs::Manager) {
+        for entity in ecs.iter_entities() {
+            if !self.entities.contains(&entity) {
+                ecs.remove_entity(entity);
+            }
+        }
+        for entity in &self.entities {
+            ecs.create_entity_with_id(*entity);
+        }
+        for (restore, data) in &self.components {
+            restore(ecs, data.as_ref());
+        }
+    }
+}
+
+/// The state of the editor's play mode.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum PlayState {
+    /// The world is edited, the game systems do not run.
+    #[default]
+    Editing,
+    Playing,
+    /// Playing, but the game systems are stopped.
+    Paused,
+}
+
+/// Send it to change the play mode.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PlayCommand {
+    /// Snapshot the world and start playing, or resume when paused.
+    Play,
+    Pause,
+    /// Stop playing and restore the snapshot taken by `Play`.
+    Stop,
+}
+
+/// Sent when the play mode starts from editing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PlayStarted;
+
+/// Sent when the play mode stops and the world is restored.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PlayStopped;
+
+/// Play-in-editor: `Play` snapshots the world and runs the game, `Stop` restores the world
+/// exactly as it was before playing, so the game can be iterated on without restarting it.
+///
+/// Only the registered component types are restored, the other components of the entities
+/// which existed before playing are kept as they are.
+pub struct PlayMode {
+    state: PlayState,
+    components: Vec<(TypeId, Capture, Restore)>,
+    snapshot: Option<WorldSnapshot>,
+}
+
+impl Component for PlayMode {}
+
+impl Default for PlayMode {
+    fn default() -> Self {
+        Self {
+            state: PlayState::Editing,
+            components: Vec::new(),
+            snapshot: None,
+        }
+    }
+}
+
+impl PlayMode {
+    /// Create a play mode restoring the engine's components.
+    pub fn new() -> Self {
+        let play_mode = Self::default()
+            .with_component::<Pos3>()
+            .with_component::<Velocity>()
+            .with_component::<Name>()
+            .with_component::<Camera>()
+            .with_component::<Light>()
+            .with_component::<Scale>()
+            .with_component::<Flip>()
+            .with_component::<Collider>()
+            .with_component::<Health>()
+            .with_component::<Parent>()
+            .with_component::<LocalPos3>()
+            .with_component::<AttachedTo>();
+
+        #[cfg(feature = "renderer")]
+        let play_mode = play_mode
+            .with_component::<crate::renderer::post::PostProcessSettings>()
+            .with_component::<crate::renderer::shadow::ShadowSettings>();
+
+        play_mode
+    }
+
+    /// Add a component type to the snapshot.
+    pub fn with_component<T: Clone + Send + Sync + 'static>(mut self) -> Self {
+        let type_id = TypeId::of::<T>();
+        if !self.components.iter().any(|(id, _, _)| *id == type_id) {
+            self.components.push((type_id, capture::<T>, restore::<T>));
+        }
+        self
+    }
+
+    pub fn state(&self) -> PlayState {
+        self.state
+    }
+
+    /// Take a snapshot of the world.
+    pub fn snapshot(&self, ecs: &ecs::Manager) -> WorldSnapshot {
+        WorldSnapshot {
+            entities: ecs.iter_entities().collect(),
+            components: self
+                .components
+                .iter()
+                .map(|(_, capture, restore)| (*restore, capture(ecs)))
+                .collect(),
+        }
+    }
+}
+
+/// Check if the game systems should run.
+/// Without a `PlayMode` the game always plays, with one only while playing.
+pub fn is_playing(ecs: &ecs::Manager) -> bool {
+    ecs.get_all_components_of_type::<PlayMode>()
+        .first()
+        .is_none_or(|(_, play_mode)| play_mode.read().unwrap().state == PlayState::Playing)
+}
+
+/// Apply the play commands.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time, unused.
+pub fn update_play_mode(ecs: &ecs::Manager, _dt: Dt) {
+    let commands = ecs.drain_events::<PlayCommand>();
+    let Some((_, play_mode)) = ecs.get_all_components_of_type::<PlayMode>().pop() else {
+        return;
+    };
+
+    for command in commands {
+        let mut play_mode = play_mode.write().unwrap();
+        match (play_mode.state, command) {
+            (PlayState::Editing, PlayCommand::Play) => {
+                play_mode.snapshot = Some(play_mode.snapshot(ecs));
+                play_mode.state = PlayState::Playing;
+                ecs.send_event(PlayStarted);
+                log::info!("[Editor] Playing");
+            }
+            (PlayState::Paused, PlayCommand::Play) => play_mode.state = PlayState::Playing,
+            (PlayState::Playing, PlayCommand::Pause) => play_mode.state = PlayState::Paused,
+            (PlayState::Playing | PlayState::Paused, PlayCommand::Stop) => {
+                if let Some(snapshot) = play_mode.snapshot.take() {
+                    snapshot.restore(ecs);
+                }
+                play_mode.state = PlayState::Editing;
+                ecs.send_event(PlayStopped);
+                log::info!("[Editor] Stopped, the world is restored");
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Draw the play, pause and stop buttons at the top of the screen.
+pub fn show_play_controls(ctx: &egui::Context, ecs: &ecs::Manager) {
+    let Some((_, play_mode)) = ecs.get_all_components_of_type::<PlayMode>().pop() else {
+        return;
+    };
+    let state = play_mode.read().unwrap().state;
+
+    HudAnchor::new(egui::Align2::CENTER_TOP)
+        .area(ctx, "play_controls")
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    let playing = state == PlayState::Playing;
+                    if ui
+                        .add_enabled(!playing, egui::Button::new("▶ Play"))
+                        .clicked()
+                    {
+                        ecs.send_event(PlayCommand::Play);
+                    }
+                    if ui
+                        .add_enabled(playing, egui::Button::new("⏸ Pause"))
+                        .clicked()
+                    {
+                        ecs.send_event(PlayCommand::Pause);
+                    }
+                    let stop = egui::Button::new("⏹ Stop");
+                    if ui.add_enabled(state != PlayState::Editing, stop).clicked() {
+                        ecs.send_event(PlayCommand::Stop);
+                    }
+                });
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector3;
+
+    #[test]
+    fn test_play_and_restore() {
+        let ecs = ecs::Manager::default();
+        let player = ecs.create_entity();
+        ecs.add_component_to_entity(player, Pos3::new(Vector3::new(1.0, 0.0, 0.0)));
+        ecs.add_component_to_entity(player, Velocity(Vector3::new(0.0, 1.0, 0.0)));
+        let editor = ecs.create_entity();
+        ecs.add_component_to_entity(editor, PlayMode::new());
+        assert!(!is_playing(&ecs));
+
+        ecs.send_event(PlayCommand::Play);
+        update_play_mode(&ecs, Dt::ZERO);
+        assert!(is_playing(&ecs));
+        assert_eq!(ecs.drain_events::<PlayStarted>(), vec![PlayStarted]);
+
+        // The game moves the player, removes its velocity and spawns a projectile
+        let pos = ecs.get_component_from_entity::<Pos3>(player).unwrap();
+        pos.write().unwrap().pos.x = 5.0;
+        ecs.remove_component_from_entity::<Velocity>(player);
+        ecs.add_component_to_entity(player, Name("Player"));
+        let projectile = ecs.create_entity();
+        ecs.add_component_to_entity(projectile, Pos3::new(Vector3::new(0.0, 0.0, 0.0)));
+
+        ecs.send_event(PlayCommand::Pause);
+        update_play_mode(&ecs, Dt::ZERO);
+        assert!(!is_playing(&ecs));
+
+        ecs.send_event(PlayCommand::Stop);
+        update_play_mode(&ecs, Dt::ZERO);
+        assert_eq!(ecs.entity_count(), 2);
+        assert_eq!(pos.read().unwrap().pos.x, 1.0);
+        assert!(ecs.get_component_from_entity::<Velocity>(player).is_some());
+        assert!(ecs.get_component_from_entity::<Name>(player).is_none());
+        assert!(ecs.get_component_from_entity::<Pos3>(projectile).is_none());
+        assert_eq!(ecs.drain_events::<PlayStopped>(), vec![PlayStopped]);
+    }
+
+    #[test]
+    fn test_without_play_mode() {
+        let ecs = ecs::Manager::default();
+        assert!(is_playing(&ecs));
+        ecs.send_event(PlayCommand::Stop);
+        update_play_mode(&ecs, Dt::ZERO);
+        assert!(is_playing(&ecs));
+    }
+}
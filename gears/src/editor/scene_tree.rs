@@ -0,0 +1,347 @@
+use super::camera::CameraCommand;
+use super::inspector::Inspector;
+use crate::ecs::components::{Camera, Collider, Flip, Light, Name, Pos3, Scale, Velocity};
+use crate::ecs::hierarchy::{self, Parent};
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use crate::gameplay::health::Health;
+use std::any::TypeId;
+
+type CopyComponent = fn(&ecs::Manager, Entity, Entity);
+
+fn copy<T: Clone + Send + Sync + 'static>(ecs: &ecs::Manager, from: Entity, to: Entity) {
+    if let Some(component) = ecs.get_component_from_entity::<T>(from) {
+        let value = component.read().unwrap().clone();
+        ecs.add_component_to_entity(to, value);
+    }
+}
+
+/// An action chosen in the tree, applied after the tree is drawn.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Action {
+    Select(Entity),
+    Reparent(Entity, Option<Entity>),
+    Delete(Entity),
+    Duplicate(Entity),
+    Camera(CameraCommand),
+}
+
+/// Opens the scene tree window, showing the entities by their parents and names.
+/// Drag an entity onto another to make it a child, or onto the bottom of the window to make it a root.
+/// Right click an entity to delete, duplicate, frame or follow it with the camera.
+///
+/// The selection is shared with the inspector. Duplicating copies the registered components.
+pub struct SceneTree {
+    pub open: bool,
+    pub selected: Option<Entity>,
+    /// The entity the camera was told to follow.
+    pub following: Option<Entity>,
+    components: Vec<(TypeId, CopyComponent)>,
+}
+
+impl Component for SceneTree {}
+
+impl Default for SceneTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneTree {
+    /// Create a scene tree duplicating the engine's components.
+    pub fn new() -> Self {
+        Self {
+            open: true,
+            selected: None,
+            following: None,
+            components: Vec::new(),
+        }
+        .with_component::<Pos3>()
+        .with_component::<Velocity>()
+        .with_component::<Name>()
+        .with_component::<Camera>()
+        .with_component::<Light>()
+        .with_component::<Scale>()
+        .with_component::<Flip>()
+        .with_component::<Collider>()
+        .with_component::<Health>()
+    }
+
+    /// Add a component type copied by duplicating.
+    pub fn with_component<T: Clone + Send + Sync + 'static>(mut self) -> Self {
+        let type_id = TypeId::of::<T>();
+        if !self.components.iter().any(|(id, _)| *id == type_id) {
+            self.components.push((type_id, copy::<T>));
+        }
+        self
+    }
+
+    /// Duplicate an entity and its descendants with the registered components.
+    /// The copy gets the same parent as the entity.
+    ///
+    /// # Returns
+    ///
+    /// The copy of the entity.
+    pub fn duplicate(&self, ecs: &ecs::Manager, entity: Entity) -> Entity {
+        let duplicate = ecs.create_entity();
+        for (_, copy) in &self.components {
+            copy(ecs, entity, duplicate);
+        }
+        if let Some(parent) = hierarchy::parent(ecs, entity) {
+            ecs.add_component_to_entity(duplicate, Parent(parent));
+        }
+        for child in hierarchy::children(ecs, entity) {
+            let child = self.duplicate(ecs, child);
+            ecs.add_component_to_entity(child, Parent(duplicate));
+        }
+        duplicate
+    }
+}
+
+/// Remove an entity and its descendants.
+pub fn delete_recursive(ecs: &ecs::Manager, entity: Entity) {
+    for descendant in hierarchy::descendants(ecs, entity) {
+        ecs.remove_entity(descendant);
+    }
+    ecs.remove_entity(entity);
+}
+
+/// Draw an entity and its children.
+fn show_node(
+    ui: &mut egui::Ui,
+    ecs: &ecs::Manager,
+    entity: Entity,
+    selected: Option<Entity>,
+    following: Option<Entity>,
+    actions: &mut Vec<Action>,
+) {
+    let label = match ecs.get_component_from_entity::<Name>(entity) {
+        Some(name) => name.read().unwrap().0.to_string(),
+        None => format!("Entity {}", entity.id()),
+    };
+    let children = hierarchy::children(ecs, entity);
+
+    let mut header = |ui: &mut egui::Ui| {
+        let id = egui::Id::new(("scene_tree_drag", entity));
+        let response = ui.dnd_drag_source(id, entity, |ui| {
+            ui.selectable_label(selected == Some(entity), &label)
+                .on_hover_text(format!("Entity {}", entity.id()))
+        });
+
+        if response.inner.clicked() {
+            actions.push(Action::Select(entity));
+        }
+        response.inner.context_menu(|ui| {
+            if ui.button("Focus camera").clicked() {
+                actions.push(Action::Camera(CameraCommand::Frame(entity)));
+                ui.close_menu();
+            }
+            if following == Some(entity) {
+                if ui.button("Stop following").clicked() {
+                    actions.push(Action::Camera(CameraCommand::Unfollow));
+                    ui.close_menu();
+                }
+            } else if ui.button("Follow").clicked() {
+                actions.push(Action::Camera(CameraCommand::Follow(entity)));
+                ui.close_menu();
+            }
+            if ui.button("Duplicate").clicked() {
+                actions.push(Action::Duplicate(entity));
+                ui.close_menu();
+            }
+            if hierarchy::parent(ecs, entity).is_some() && ui.button("Unparent").clicked() {
+                actions.push(Action::Reparent(entity, None));
+                ui.close_menu();
+            }
+            if ui.button("Delete").clicked() {
+                actions.push(Action::Delete(entity));
+                ui.close_menu();
+            }
+        });
+
+        // Highlight the entity under a dragged one
+        let hovered = response.response.dnd_hover_payload::<Entity>();
+        if hovered.is_some_and(|dragged| *dragged != entity) {
+            let stroke = ui.visuals().selection.stroke;
+            ui.painter()
+                .rect_stroke(response.response.rect, 2.0, stroke);
+        }
+        if let Some(dragged) = response.response.dnd_release_payload::<Entity>() {
+            if *dragged != entity {
+                actions.push(Action::Reparent(*dragged, Some(entity)));
+            }
+        }
+    };
+
+    if children.is_empty() {
+        // Leave the room of the collapse button, so the leaves line up with their siblings
+        ui.horizontal(|ui| {
+            ui.add_space(ui.spacing().indent);
+            header(ui);
+        });
+        return;
+    }
+
+    egui::collapsing_header::CollapsingState::load_with_default_open(
+        ui.ctx(),
+        egui::Id::new(("scene_tree_node", entity)),
+        true,
+    )
+    .show_header(ui, header)
+    .body(|ui| {
+        for child in children {
+            show_node(ui, ecs, child, selected, following, actions);
+        }
+    });
+}
+
+/// Draw the scene tree window.
+pub fn show_scene_tree(ctx: &egui::Context, ecs: &ecs::Manager) {
+    let Some((_, tree)) = ecs.get_all_components_of_type::<SceneTree>().pop() else {
+        return;
+    };
+    let mut tree = tree.write().unwrap();
+    if !tree.open {
+        return;
+    }
+
+    let inspector = ecs
+        .get_all_components_of_type::<Inspector>()
+        .pop()
+        .map(|(_, inspector)| inspector);
+    if let Some(inspector) = &inspector {
+        tree.selected = inspector.read().unwrap().selected;
+    }
+
+    let entities = ecs.iter_entities().collect::<Vec<_>>();
+    if tree
+        .following
+        .is_some_and(|entity| !entities.contains(&entity))
+    {
+        tree.following = None;
+    }
+
+    let mut actions = Vec::new();
+    let mut open = tree.open;
+    egui::Window::new("Scene")
+        .open(&mut open)
+        .default_width(250.0)
+        .show(ctx, |ui| {
+            let roots = hierarchy::roots(ecs);
+            ui.horizontal(|ui| {
+                ui.label(format!("{} entities", ecs.entity_count()));
+                if let Some(entity) = tree.following {
+                    ui.separator();
+                    ui.label(format!("Following {}", entity.id()));
+                    if ui.small_button("Stop").clicked() {
+                        actions.push(Action::Camera(CameraCommand::Unfollow));
+                    }
+                }
+            });
+            ui.separator();
+
+            egui::ScrollArea::vertical()
+                .id_salt("scene_tree")
+                .max_height(400.0)
+                .show(ui, |ui| {
+                    for entity in roots {
+                        let (selected, following) = (tree.selected, tree.following);
+                        show_node(ui, ecs, entity, selected, following, &mut actions);
+                    }
+                });
+
+            // Dropping below the tree makes the entity a root
+            let frame = egui::Frame::group(ui.style());
+            let (_, dropped) = ui.dnd_drop_zone::<Entity, ()>(frame, |ui| {
+                ui.set_min_width(ui.available_width());
+                ui.weak("Drop here to unparent");
+            });
+            if let Some(dragged) = dropped {
+                actions.push(Action::Reparent(*dragged, None));
+            }
+        });
+    tree.open = open;
+
+    for action in actions {
+        match action {
+            Action::Select(entity) => tree.selected = Some(entity),
+            Action::Reparent(entity, parent) => {
+                if let Err(e) = hierarchy::set_parent(ecs, entity, parent) {
+                    log::warn!("[Editor] {}", e);
+                }
+            }
+            Action::Delete(entity) => {
+                delete_recursive(ecs, entity);
+                if tree
+                    .selected
+                    .is_some_and(|selected| !ecs.iter_entities().any(|e| e == selected))
+                {
+                    tree.selected = None;
+                }
+            }
+            Action::Duplicate(entity) => tree.selected = Some(tree.duplicate(ecs, entity)),
+            Action::Camera(command) => {
+                tree.following = match command {
+                    CameraCommand::Follow(entity) => Some(entity),
+                    CameraCommand::Unfollow => None,
+                    CameraCommand::Frame(_) => tree.following,
+                };
+                ecs.send_event(command);
+            }
+        }
+    }
+
+    if let Some(inspector) = inspector {
+        inspector.write().unwrap().selected = tree.selected;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector3;
+
+    #[test]
+    fn test_duplicate_and_delete() {
+        let ecs = ecs::Manager::default();
+        let player = ecs.create_entity();
+        ecs.add_component_to_entity(player, Name("Player"));
+        ecs.add_component_to_entity(player, Pos3::new(Vector3::new(1.0, 0.0, 0.0)));
+        let weapon = ecs.create_entity();
+        ecs.add_component_to_entity(weapon, Name("Weapon"));
+        hierarchy::set_parent(&ecs, weapon, Some(player)).unwrap();
+
+        let tree = SceneTree::new();
+        let copy = tree.duplicate(&ecs, player);
+        assert_eq!(hierarchy::roots(&ecs), vec![player, copy]);
+        let copied_weapon = hierarchy::children(&ecs, copy);
+        assert_eq!(copied_weapon.len(), 1);
+        let name = ecs
+            .get_component_from_entity::<Name>(copied_weapon[0])
+            .unwrap();
+        assert_eq!(*name.read().unwrap(), Name("Weapon"));
+
+        delete_recursive(&ecs, player);
+        assert_eq!(ecs.entity_count(), 2);
+        assert_eq!(hierarchy::roots(&ecs), vec![copy]);
+    }
+
+    #[test]
+    fn test_show_scene_tree() {
+        let ecs = ecs::Manager::default();
+        let player = ecs.create_entity();
+        ecs.add_component_to_entity(player, Name("Player"));
+        let editor = ecs.create_entity();
+        ecs.add_component_to_entity(editor, SceneTree::new());
+        let mut inspector = Inspector::new();
+        inspector.selected = Some(player);
+        ecs.add_component_to_entity(editor, inspector);
+
+        let ctx = egui::Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |ctx| show_scene_tree(ctx, &ecs));
+
+        // The selection of the inspector is followed
+        let tree = ecs.get_component_from_entity::<SceneTree>(editor).unwrap();
+        assert_eq!(tree.read().unwrap().selected, Some(player));
+    }
+}
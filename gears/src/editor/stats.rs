@@ -0,0 +1,347 @@
+use super::net::graph;
+use crate::ecs;
+use crate::ecs::traits::Component;
+use instant::{Duration, Instant};
+use std::collections::{HashMap, VecDeque};
+
+/// The number of samples the statistics keep, one per second.
+const HISTORY_LEN: usize = 60;
+/// The number of archetypes listed, the most common first.
+const ARCHETYPES_SHOWN: usize = 20;
+
+/// The order of the component table.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum StatsOrder {
+    #[default]
+    Name,
+    Count,
+    Memory,
+    Growth,
+}
+
+/// The entity count and the churn of a second.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct StatsSample {
+    pub entities: usize,
+    /// The entities created per second.
+    pub spawned: f32,
+    /// The entities removed per second.
+    pub despawned: f32,
+}
+
+/// Opens the ECS statistics window, showing the entity count, the count and the estimated memory
+/// of each component type and the number of entities created and removed per second.
+/// The components whose count keeps growing are highlighted, to spot leaks and runaway spawners.
+#[derive(Debug, Clone)]
+pub struct EcsStats {
+    pub open: bool,
+    /// Only the component types containing this text are listed.
+    pub filter: String,
+    pub order: StatsOrder,
+    history: VecDeque<StatsSample>,
+    /// The time and the churn counters of the latest sample.
+    sampled: Option<(Instant, (u64, u64))>,
+    counts: HashMap<&'static str, usize>,
+    /// The change of the count of each component type during the latest second.
+    growth: HashMap<&'static str, isize>,
+}
+
+impl Component for EcsStats {}
+
+impl Default for EcsStats {
+    fn default() -> Self {
+        Self {
+            open: true,
+            filter: String::new(),
+            order: StatsOrder::default(),
+            history: VecDeque::new(),
+            sampled: None,
+            counts: HashMap::new(),
+            growth: HashMap::new(),
+        }
+    }
+}
+
+impl EcsStats {
+    /// Get the recorded samples, the oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &StatsSample> {
+        self.history.iter()
+    }
+
+    /// Get the change of the count of a component type during the latest second.
+    pub fn growth(&self, name: &str) -> isize {
+        self.growth.get(name).copied().unwrap_or_default()
+    }
+
+    /// Record a sample if a second passed since the latest one.
+    ///
+    /// # Arguments
+    ///
+    /// * `ecs` - The entity component system manager.
+    /// * `now` - The current time.
+    pub fn sample(&mut self, ecs: &ecs::Manager, now: Instant) {
+        let churn = ecs.churn();
+        let Some((time, (spawned, despawned))) = self.sampled else {
+            self.sampled = Some((now, churn));
+            self.counts = counts(ecs);
+            return;
+        };
+        let elapsed = now.duration_since(time);
+        if elapsed < Duration::from_secs(1) {
+            return;
+        }
+
+        let counts = counts(ecs);
+        self.growth = counts
+            .iter()
+            .map(|(name, count)| {
+                let previous = self.counts.get(name).copied().unwrap_or_default();
+                (*name, *count as isize - previous as isize)
+            })
+            .collect();
+        self.counts = counts;
+
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        let seconds = elapsed.as_secs_f32();
+        self.history.push_back(StatsSample {
+            entities: ecs.entity_count(),
+            spawned: (churn.0 - spawned) as f32 / seconds,
+            despawned: (churn.1 - despawned) as f32 / seconds,
+        });
+        self.sampled = Some((now, churn));
+    }
+}
+
+fn counts(ecs: &ecs::Manager) -> HashMap<&'static str, usize> {
+    ecs.component_stats()
+        .into_iter()
+        .map(|stats| (stats.name, stats.count))
+        .collect()
+}
+
+/// Get the name of a type without its module paths.
+fn short_type(name: &str) -> String {
+    let mut short = String::new();
+    let mut segment = String::new();
+    for c in name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            segment.push(c);
+        } else {
+            short.push_str(segment.rsplit("::").next().unwrap_or_default());
+            segment.clear();
+            short.push(c);
+        }
+    }
+    short.push_str(segment.rsplit("::").next().unwrap_or_default());
+    short
+}
+
+fn memory(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f32 / (1024.0 * 1024.0))
+    } else if bytes >= 1024 {
+        format!("{:.1} kB", bytes as f32 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+/// Draw the ECS statistics window.
+pub fn show_ecs_stats(ctx: &egui::Context, ecs: &ecs::Manager) {
+    let Some((_, stats)) = ecs.get_all_components_of_type::<EcsStats>().pop() else {
+        return;
+    };
+    let mut stats = stats.write().unwrap();
+    if !stats.open {
+        return;
+    }
+    stats.sample(ecs, Instant::now());
+    // Keep the window live when nothing else repaints
+    ctx.request_repaint_after(Duration::from_secs(1));
+
+    let mut components = ecs.component_stats();
+    let total = components.iter().map(|c| c.bytes).sum::<usize>();
+    let latest = stats.history.back().copied().unwrap_or_default();
+
+    let mut open = stats.open;
+    egui::Window::new("ECS")
+        .open(&mut open)
+        .default_width(420.0)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "{} entities, {} component types, {}",
+                ecs.entity_count(),
+                components.len(),
+                memory(total)
+            ));
+
+            let entities = stats.history.iter().map(|s| s.entities as f32).collect();
+            graph(
+                ui,
+                "Entities",
+                &[("", entities, egui::Color32::LIGHT_BLUE)],
+                HISTORY_LEN,
+                |count| format!("{:.0}", count),
+            );
+            graph(
+                ui,
+                "Churn",
+                &[
+                    (
+                        "spawned",
+                        stats.history.iter().map(|s| s.spawned).collect(),
+                        egui::Color32::LIGHT_GREEN,
+                    ),
+                    (
+                        "despawned",
+                        stats.history.iter().map(|s| s.despawned).collect(),
+                        egui::Color32::LIGHT_RED,
+                    ),
+                ],
+                HISTORY_LEN,
+                |rate| format!("{:.0}/s", rate),
+            );
+            if latest.spawned > latest.despawned * 2.0 && latest.spawned >= 10.0 {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    "Entities are created much faster than they are removed",
+                );
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut stats.filter)
+                        .hint_text("Filter")
+                        .desired_width(120.0),
+                );
+                egui::ComboBox::from_label("Order")
+                    .selected_text(format!("{:?}", stats.order))
+                    .show_ui(ui, |ui| {
+                        for order in [
+                            StatsOrder::Name,
+                            StatsOrder::Count,
+                            StatsOrder::Memory,
+                            StatsOrder::Growth,
+                        ] {
+                            ui.selectable_value(&mut stats.order, order, format!("{:?}", order));
+                        }
+                    });
+            });
+
+            let filter = stats.filter.to_lowercase();
+            components.retain(|c| c.name.to_lowercase().contains(&filter));
+            match stats.order {
+                StatsOrder::Name => {}
+                StatsOrder::Count => components.sort_by_key(|c| std::cmp::Reverse(c.count)),
+                StatsOrder::Memory => components.sort_by_key(|c| std::cmp::Reverse(c.bytes)),
+                StatsOrder::Growth => {
+                    components.sort_by_key(|c| std::cmp::Reverse(stats.growth(c.name)))
+                }
+            }
+
+            egui::ScrollArea::vertical()
+                .id_salt("ecs_components")
+                .max_height(240.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("ecs_components_grid")
+                        .num_columns(4)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Component");
+                            ui.strong("Count");
+                            ui.strong("Memory");
+                            ui.strong("Δ/s");
+                            ui.end_row();
+
+                            for component in &components {
+                                ui.label(short_type(component.name))
+                                    .on_hover_text(component.name);
+                                ui.label(component.count.to_string());
+                                ui.label(memory(component.bytes));
+                                let growth = stats.growth(component.name);
+                                if growth > 0 {
+                                    ui.colored_label(
+                                        ui.visuals().warn_fg_color,
+                                        format!("+{}", growth),
+                                    );
+                                } else {
+                                    ui.label(growth.to_string());
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+
+            ui.collapsing("Archetypes", |ui| {
+                let mut archetypes = ecs.archetype_counts().into_iter().collect::<Vec<_>>();
+                archetypes.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+                for (archetype, count) in archetypes.iter().take(ARCHETYPES_SHOWN) {
+                    let names = archetype
+                        .iter()
+                        .map(|name| short_type(name))
+                        .collect::<Vec<_>>();
+                    ui.label(format!(
+                        "{} × {}",
+                        count,
+                        if names.is_empty() {
+                            "(no components)".to_string()
+                        } else {
+                            names.join(", ")
+                        }
+                    ));
+                }
+                if archetypes.len() > ARCHETYPES_SHOWN {
+                    ui.weak(format!("and {} more", archetypes.len() - ARCHETYPES_SHOWN));
+                }
+            });
+        });
+    stats.open = open;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::components::Pos3;
+
+    #[test]
+    fn test_sample() {
+        let ecs = ecs::Manager::default();
+        let mut stats = EcsStats::default();
+        let now = Instant::now();
+        stats.sample(&ecs, now);
+
+        for _ in 0..4 {
+            let entity = ecs.create_entity();
+            ecs.add_component_to_entity(entity, Pos3::default());
+        }
+        ecs.remove_entity(ecs::Entity(0));
+        stats.sample(&ecs, now + Duration::from_millis(500));
+        assert_eq!(stats.history().count(), 0);
+
+        stats.sample(&ecs, now + Duration::from_secs(2));
+        let sample = *stats.history().last().unwrap();
+        assert_eq!(sample.entities, 3);
+        assert_eq!((sample.spawned, sample.despawned), (2.0, 0.5));
+        assert_eq!(stats.growth(std::any::type_name::<Pos3>()), 3);
+
+        assert_eq!(
+            short_type(std::any::type_name::<Option<Pos3>>()),
+            "Option<Pos3>"
+        );
+    }
+
+    #[test]
+    fn test_show_ecs_stats() {
+        let ecs = ecs::Manager::default();
+        let editor = ecs.create_entity();
+        ecs.add_component_to_entity(editor, EcsStats::default());
+
+        let ctx = egui::Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |ctx| show_ecs_stats(ctx, &ecs));
+        let stats = ecs.get_component_from_entity::<EcsStats>(editor).unwrap();
+        assert!(stats.read().unwrap().sampled.is_some());
+    }
+}
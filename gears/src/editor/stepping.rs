@@ -0,0 +1,176 @@
+use crate::core::clock::SimulationClock;
+use crate::ecs;
+use crate::ecs::traits::Component;
+
+/// The speeds offered by the controls.
+const TIME_SCALES: [f32; 4] = [1.0, 0.5, 0.25, 0.1];
+/// The slow speed toggled by the slow motion key.
+const SLOW_TIME_SCALE: f32 = 0.25;
+
+/// Opens the simulation controls, to pause the simulation, advance it one tick at a time
+/// or run it slower. The keys work while the controls are shown and no text is edited.
+///
+/// The controls add a `SimulationClock` to their entity, it holds the update loops of the app.
+#[derive(Debug, Copy, Clone)]
+pub struct SteppingControls {
+    pub open: bool,
+    pub pause_key: egui::Key,
+    pub step_key: egui::Key,
+    /// Toggles between the full speed and a quarter speed.
+    pub slow_key: egui::Key,
+}
+
+impl Component for SteppingControls {}
+
+impl Default for SteppingControls {
+    fn default() -> Self {
+        Self {
+            open: true,
+            pause_key: egui::Key::F6,
+            step_key: egui::Key::F7,
+            slow_key: egui::Key::F8,
+        }
+    }
+}
+
+/// Apply the key bindings of the controls to the clock.
+fn handle_keys(ctx: &egui::Context, controls: &SteppingControls, clock: &mut SimulationClock) {
+    if ctx.wants_keyboard_input() {
+        return;
+    }
+
+    ctx.input(|input| {
+        if input.key_pressed(controls.pause_key) {
+            clock.toggle_pause();
+        }
+        if input.key_pressed(controls.step_key) {
+            clock.step();
+        }
+        if input.key_pressed(controls.slow_key) {
+            let slow = clock.time_scale() < 1.0;
+            clock.set_time_scale(if slow { 1.0 } else { SLOW_TIME_SCALE });
+        }
+    });
+}
+
+/// Draw the simulation controls.
+pub fn show_stepping_controls(ctx: &egui::Context, ecs: &ecs::Manager) {
+    let Some((entity, controls)) = ecs.get_all_components_of_type::<SteppingControls>().pop()
+    else {
+        return;
+    };
+    let clock = match ecs.get_component_from_entity::<SimulationClock>(entity) {
+        Some(clock) => clock,
+        None => {
+            ecs.add_component_to_entity(entity, SimulationClock::default());
+            let Some(clock) = ecs.get_component_from_entity::<SimulationClock>(entity) else {
+                return;
+            };
+            clock
+        }
+    };
+    let mut controls = controls.write().unwrap();
+    let mut clock = clock.write().unwrap();
+    handle_keys(ctx, &controls, &mut clock);
+    if !controls.open {
+        return;
+    }
+
+    let mut open = controls.open;
+    egui::Window::new("Simulation")
+        .open(&mut open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let text = if clock.is_paused() {
+                    "▶ Resume"
+                } else {
+                    "⏸ Pause"
+                };
+                if ui
+                    .button(text)
+                    .on_hover_text(controls.pause_key.name())
+                    .clicked()
+                {
+                    clock.toggle_pause();
+                }
+                if ui
+                    .button("⏭ Step")
+                    .on_hover_text(format!(
+                        "Advance one tick of {:.1} ms ({})",
+                        clock.step_dt.as_secs_f64() * 1000.0,
+                        controls.step_key.name()
+                    ))
+                    .clicked()
+                {
+                    clock.step();
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Speed");
+                let mut time_scale = clock.time_scale();
+                for scale in TIME_SCALES {
+                    ui.selectable_value(&mut time_scale, scale, format!("{}×", scale));
+                }
+                clock.set_time_scale(time_scale);
+            });
+
+            let state = if clock.is_paused() {
+                "Paused"
+            } else if clock.time_scale() < 1.0 {
+                "Slow motion"
+            } else {
+                "Running"
+            };
+            ui.weak(format!(
+                "{}, {} pause, {} step, {} slow",
+                state,
+                controls.pause_key.name(),
+                controls.step_key.name(),
+                controls.slow_key.name()
+            ));
+        });
+    controls.open = open;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::clock;
+    use crate::core::Dt;
+
+    #[test]
+    fn test_stepping_keys() {
+        let ecs = ecs::Manager::default();
+        let editor = ecs.create_entity();
+        ecs.add_component_to_entity(editor, SteppingControls::default());
+
+        let ctx = egui::Context::default();
+        let press = |key: egui::Key| egui::RawInput {
+            events: vec![egui::Event::Key {
+                key,
+                physical_key: None,
+                pressed: true,
+                repeat: false,
+                modifiers: egui::Modifiers::NONE,
+            }],
+            ..Default::default()
+        };
+
+        // Pause, then step one tick
+        let _ = ctx.run(press(egui::Key::F6), |ctx| {
+            show_stepping_controls(ctx, &ecs)
+        });
+        assert_eq!(clock::simulation_dt(&ecs, Dt::from_millis(16)), None);
+        let _ = ctx.run(press(egui::Key::F7), |ctx| {
+            show_stepping_controls(ctx, &ecs)
+        });
+        let step_dt = SimulationClock::default().step_dt;
+        assert_eq!(
+            clock::simulation_dt(&ecs, Dt::from_millis(16)),
+            Some(step_dt)
+        );
+        assert_eq!(clock::simulation_dt(&ecs, Dt::from_millis(16)), None);
+    }
+}
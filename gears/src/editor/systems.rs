@@ -0,0 +1,350 @@
+use crate::core::schedule::{ScheduleInfo, Stage, SystemInfo};
+use crate::ecs;
+use crate::ecs::traits::Component;
+
+/// The size of a system node in the graph.
+const NODE_SIZE: egui::Vec2 = egui::vec2(140.0, 36.0);
+/// The space between the nodes of the graph.
+const NODE_SPACING: egui::Vec2 = egui::vec2(36.0, 12.0);
+
+/// Opens the system graph window, showing the scheduled systems by stage with their dependencies,
+/// their durations and the component types they declared access to.
+///
+/// The window adds a `ScheduleInfo` component to its entity, so the schedule reports into it.
+#[derive(Debug, Clone)]
+pub struct SystemGraph {
+    pub open: bool,
+    /// The name of the system whose details are shown.
+    pub selected: Option<String>,
+}
+
+impl Component for SystemGraph {}
+
+impl Default for SystemGraph {
+    fn default() -> Self {
+        Self {
+            open: true,
+            selected: None,
+        }
+    }
+}
+
+/// Get the name of a type without its module path.
+fn short_type(name: &str) -> &str {
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+fn millis(duration: instant::Duration) -> String {
+    format!("{:.3} ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// Get the position of each system node, a column per stage and a row per system in the run order.
+fn layout(systems: &[SystemInfo], origin: egui::Pos2) -> Vec<egui::Rect> {
+    let stages = Stage::ALL
+        .iter()
+        .filter(|stage| systems.iter().any(|system| system.stage == **stage))
+        .collect::<Vec<_>>();
+    let mut rows = vec![0; stages.len()];
+
+    systems
+        .iter()
+        .map(|system| {
+            let column = stages
+                .iter()
+                .position(|stage| **stage == system.stage)
+                .unwrap_or_default();
+            let row = rows[column];
+            rows[column] += 1;
+            egui::Rect::from_min_size(
+                origin
+                    + egui::vec2(
+                        column as f32 * (NODE_SIZE.x + NODE_SPACING.x),
+                        row as f32 * (NODE_SIZE.y + NODE_SPACING.y),
+                    ),
+                NODE_SIZE,
+            )
+        })
+        .collect()
+}
+
+/// Draw the systems as nodes with arrows to the systems running after them.
+fn show_graph(ui: &mut egui::Ui, graph: &mut SystemGraph, systems: &[SystemInfo]) {
+    let stages = Stage::ALL
+        .iter()
+        .filter(|stage| systems.iter().any(|system| system.stage == **stage))
+        .collect::<Vec<_>>();
+
+    ui.horizontal(|ui| {
+        for stage in &stages {
+            ui.add_sized(
+                egui::vec2(
+                    NODE_SIZE.x + NODE_SPACING.x - ui.spacing().item_spacing.x,
+                    0.0,
+                ),
+                egui::Label::new(egui::RichText::new(format!("{:?}", stage)).strong()),
+            );
+        }
+    });
+
+    let rows = stages
+        .iter()
+        .map(|stage| systems.iter().filter(|s| s.stage == **stage).count())
+        .max()
+        .unwrap_or_default();
+    let size = egui::vec2(
+        stages.len() as f32 * (NODE_SIZE.x + NODE_SPACING.x),
+        rows as f32 * (NODE_SIZE.y + NODE_SPACING.y),
+    );
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let rects = layout(systems, rect.min);
+    let painter = ui.painter_at(rect);
+    let visuals = ui.visuals().clone();
+    let slowest = systems
+        .iter()
+        .map(|system| system.average)
+        .max()
+        .unwrap_or_default()
+        .as_secs_f32();
+
+    for (i, system) in systems.iter().enumerate() {
+        for dependency in &system.after {
+            let Some(from) = systems.iter().position(|s| s.name == *dependency) else {
+                continue;
+            };
+            let (from, to) = (rects[from], rects[i]);
+            let (start, end) = if from.center().x < to.center().x {
+                (from.right_center(), to.left_center())
+            } else {
+                (from.center_bottom(), to.center_top())
+            };
+            painter.arrow(start, end - start, visuals.widgets.inactive.fg_stroke);
+        }
+    }
+
+    for (i, system) in systems.iter().enumerate() {
+        let response = ui.interact(
+            rects[i],
+            ui.id().with(("system", &system.name)),
+            egui::Sense::click(),
+        );
+        if response.clicked() {
+            graph.selected = Some(system.name.clone());
+        }
+
+        // The slowest systems are tinted
+        let load = if slowest > 0.0 {
+            system.average.as_secs_f32() / slowest
+        } else {
+            0.0
+        };
+        let fill = visuals
+            .faint_bg_color
+            .lerp_to_gamma(visuals.warn_fg_color, load * 0.5);
+        let stroke = if graph.selected.as_ref() == Some(&system.name) {
+            visuals.selection.stroke
+        } else if response.hovered() {
+            visuals.widgets.hovered.fg_stroke
+        } else {
+            visuals.widgets.noninteractive.bg_stroke
+        };
+        painter.rect(rects[i], 4.0, fill, stroke);
+        painter.text(
+            rects[i].center_top() + egui::vec2(0.0, 4.0),
+            egui::Align2::CENTER_TOP,
+            &system.name,
+            egui::FontId::proportional(13.0),
+            visuals.strong_text_color(),
+        );
+        painter.text(
+            rects[i].center_bottom() - egui::vec2(0.0, 4.0),
+            egui::Align2::CENTER_BOTTOM,
+            millis(system.average),
+            egui::FontId::monospace(10.0),
+            visuals.weak_text_color(),
+        );
+    }
+}
+
+/// Draw the details of a system, and the systems it can not run in parallel with.
+fn show_system(ui: &mut egui::Ui, system: &SystemInfo, systems: &[SystemInfo]) {
+    egui::Grid::new("system_details")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Stage");
+            ui.label(format!("{:?}", system.stage));
+            ui.end_row();
+
+            ui.label("After");
+            ui.label(if system.after.is_empty() {
+                "-".to_string()
+            } else {
+                system.after.join(", ")
+            });
+            ui.end_row();
+
+            ui.label("Reads");
+            ui.label(
+                system
+                    .reads
+                    .iter()
+                    .map(|ty| short_type(ty))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            );
+            ui.end_row();
+
+            ui.label("Writes");
+            ui.label(
+                system
+                    .writes
+                    .iter()
+                    .map(|ty| short_type(ty))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            );
+            ui.end_row();
+
+            ui.label("Runs");
+            ui.label(format!(
+                "{}, skipped {} updates",
+                system.criteria, system.skips
+            ));
+            ui.end_row();
+
+            ui.label("Duration");
+            ui.label(format!(
+                "{} (average {}, {} runs)",
+                millis(system.duration),
+                millis(system.average),
+                system.runs
+            ));
+            ui.end_row();
+
+            let conflicts = systems
+                .iter()
+                .filter(|other| other.name != system.name && system.conflicts_with(other))
+                .map(|other| other.name.as_str())
+                .collect::<Vec<_>>();
+            ui.label("Conflicts");
+            ui.label(if conflicts.is_empty() {
+                "-".to_string()
+            } else {
+                conflicts.join(", ")
+            })
+            .on_hover_text(
+                "The systems accessing a component this system writes, or writing one it reads",
+            );
+            ui.end_row();
+        });
+}
+
+/// Draw the system graph window.
+pub fn show_system_graph(ctx: &egui::Context, ecs: &ecs::Manager) {
+    let Some((panel, graph)) = ecs.get_all_components_of_type::<SystemGraph>().pop() else {
+        return;
+    };
+    if ecs
+        .get_component_from_entity::<ScheduleInfo>(panel)
+        .is_none()
+    {
+        ecs.add_component_to_entity(panel, ScheduleInfo::default());
+    }
+    let mut graph = graph.write().unwrap();
+    if !graph.open {
+        return;
+    }
+
+    let schedule = ecs
+        .get_component_from_entity::<ScheduleInfo>(panel)
+        .map(|info| info.read().unwrap().clone())
+        .unwrap_or_default();
+
+    let mut open = graph.open;
+    egui::Window::new("Systems")
+        .open(&mut open)
+        .default_width(560.0)
+        .show(ctx, |ui| {
+            if schedule.systems.is_empty() {
+                ui.label("No scheduled system has run yet");
+                return;
+            }
+            ui.label(format!(
+                "{} systems, {} per update",
+                schedule.systems.len(),
+                millis(schedule.duration)
+            ));
+
+            egui::ScrollArea::both()
+                .id_salt("system_graph")
+                .max_height(320.0)
+                .show(ui, |ui| show_graph(ui, &mut graph, &schedule.systems));
+
+            let selected = graph
+                .selected
+                .as_ref()
+                .and_then(|name| schedule.systems.iter().find(|s| s.name == *name));
+            if let Some(system) = selected {
+                ui.separator();
+                ui.strong(&system.name);
+                show_system(ui, system, &schedule.systems);
+            }
+        });
+    graph.open = open;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::schedule::{Schedule, System};
+    use crate::core::Dt;
+    use crate::ecs::components::Pos3;
+
+    fn noop(_ecs: &ecs::Manager, _dt: Dt) {}
+
+    #[test]
+    fn test_show_system_graph() {
+        let ecs = ecs::Manager::default();
+        let editor = ecs.create_entity();
+        ecs.add_component_to_entity(editor, SystemGraph::default());
+
+        let ctx = egui::Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            show_system_graph(ctx, &ecs)
+        });
+        assert!(ecs
+            .get_component_from_entity::<ScheduleInfo>(editor)
+            .is_some());
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add(System::new("input", noop).in_stage(Stage::PreUpdate))
+            .unwrap();
+        schedule
+            .add(System::new("move", noop).writes::<Pos3>())
+            .unwrap();
+        schedule
+            .add(System::new("collide", noop).after("move").reads::<Pos3>())
+            .unwrap();
+        schedule.run(&ecs, Dt::from_millis(16));
+
+        let info = ecs
+            .get_component_from_entity::<ScheduleInfo>(editor)
+            .unwrap();
+        let rects = layout(&info.read().unwrap().systems, egui::Pos2::ZERO);
+        // The stages are columns and the systems of a stage are stacked
+        assert!(rects[0].max.x < rects[1].min.x);
+        assert_eq!(rects[1].min.x, rects[2].min.x);
+        assert!(rects[1].max.y < rects[2].min.y);
+        assert_eq!(short_type(std::any::type_name::<Pos3>()), "Pos3");
+
+        ecs.get_component_from_entity::<SystemGraph>(editor)
+            .unwrap()
+            .write()
+            .unwrap()
+            .selected = Some("collide".to_string());
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            show_system_graph(ctx, &ecs)
+        });
+    }
+}
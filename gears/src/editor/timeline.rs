@@ -0,0 +1,378 @@
+use super::inspector::{self, Inspector};
+use super::play;
+use crate::ecs::components::Name;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use crate::gameplay::animation::{AnimationController, Interpolation, Track, TrackTarget};
+
+const LABEL_WIDTH: f32 = 110.0;
+const LANE_HEIGHT: f32 = 18.0;
+
+/// Opens the timeline window, editing the animation clips of an entity with an `AnimationController`.
+/// The entity selected in the inspector is followed when it is animated.
+///
+/// While the game is not playing, the timeline plays the clips itself, so edits can be previewed live.
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    pub open: bool,
+    pub selected: Option<Entity>,
+    track: usize,
+}
+
+impl Component for Timeline {}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            open: true,
+            selected: None,
+            track: 0,
+        }
+    }
+}
+
+/// Get the value of a new track.
+fn default_value(target: TrackTarget) -> f32 {
+    match target {
+        TrackTarget::Scale => 1.0,
+        _ => 0.0,
+    }
+}
+
+/// Draw the keyframes of a track on a lane, they can be dragged along the time.
+///
+/// # Returns
+///
+/// True if a keyframe was moved, and the time the lane was clicked at.
+fn show_lane(
+    ui: &mut egui::Ui,
+    id: egui::Id,
+    track: &mut Track,
+    duration: f32,
+    time: f32,
+) -> (bool, Option<f32>) {
+    let (rect, response) = ui.allocate_exact_size(
+        egui::vec2(ui.available_width(), LANE_HEIGHT),
+        egui::Sense::click(),
+    );
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    let to_x = |time: f32| egui::remap(time, 0.0..=duration, rect.x_range());
+    let from_x = |x: f32| egui::remap(x, rect.x_range(), 0.0..=duration).clamp(0.0, duration);
+
+    let mut changed = false;
+    let mut dragging = false;
+    for (i, keyframe) in track.keyframes.iter_mut().enumerate() {
+        let center = egui::pos2(to_x(keyframe.time), rect.center().y);
+        let handle = egui::Rect::from_center_size(center, egui::vec2(10.0, LANE_HEIGHT));
+        let key = ui
+            .interact(handle, id.with(i), egui::Sense::drag())
+            .on_hover_text(format!("{:.2}s: {:.3}", keyframe.time, keyframe.value));
+
+        if key.dragged() {
+            if let Some(pointer) = key.interact_pointer_pos() {
+                keyframe.time = from_x(pointer.x);
+                changed = true;
+            }
+        }
+        // Sorting while dragging would hand the drag over to another keyframe
+        dragging |= key.dragged();
+        changed |= key.drag_stopped();
+
+        let color = if key.hovered() || key.dragged() {
+            ui.visuals().selection.bg_fill
+        } else {
+            ui.visuals().widgets.active.fg_stroke.color
+        };
+        let diamond = [
+            center + egui::vec2(0.0, -5.0),
+            center + egui::vec2(5.0, 0.0),
+            center + egui::vec2(0.0, 5.0),
+            center + egui::vec2(-5.0, 0.0),
+        ];
+        painter.add(egui::Shape::convex_polygon(
+            diamond.to_vec(),
+            color,
+            egui::Stroke::NONE,
+        ));
+    }
+    if changed && !dragging {
+        track.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+
+    let playhead = to_x(time);
+    painter.vline(
+        playhead,
+        rect.y_range(),
+        egui::Stroke::new(1.5, egui::Color32::RED),
+    );
+
+    let clicked = response
+        .clicked()
+        .then(|| response.interact_pointer_pos())
+        .flatten()
+        .map(|pointer| from_x(pointer.x));
+    (changed, clicked)
+}
+
+/// Draw the clip editor of a controller.
+///
+/// # Returns
+///
+/// True if the pose of the controller changed.
+fn show_controller(
+    ui: &mut egui::Ui,
+    id: egui::Id,
+    selected_track: &mut usize,
+    controller: &mut AnimationController,
+) -> bool {
+    let mut changed = false;
+
+    ui.horizontal(|ui| {
+        let current = controller.current();
+        let name = controller
+            .current_clip()
+            .map(|clip| clip.name.clone())
+            .unwrap_or_default();
+        egui::ComboBox::from_id_salt(id.with("clip"))
+            .selected_text(name)
+            .show_ui(ui, |ui| {
+                for i in 0..controller.clips.len() {
+                    let name = controller.clips[i].name.clone();
+                    if ui.selectable_label(i == current, name).clicked() {
+                        controller.select(i);
+                        changed = true;
+                    }
+                }
+            });
+
+        if ui.button("⏮").on_hover_text("Rewind").clicked() {
+            controller.seek(0.0);
+            changed = true;
+        }
+        if controller.is_playing() {
+            if ui.button("⏸").on_hover_text("Pause").clicked() {
+                controller.pause();
+            }
+        } else if ui.button("▶").on_hover_text("Play").clicked() {
+            let duration = controller
+                .current_clip()
+                .map_or(0.0, |clip| clip.duration());
+            if controller.time() >= duration {
+                controller.seek(0.0);
+            }
+            controller.resume();
+        }
+        ui.add(
+            egui::DragValue::new(&mut controller.speed)
+                .speed(0.01)
+                .range(0.0..=10.0)
+                .prefix("Speed "),
+        );
+        if let Some(clip) = controller.current_clip_mut() {
+            ui.checkbox(&mut clip.looping, "Loop");
+        }
+    });
+
+    let Some(duration) = controller.current_clip().map(|clip| clip.duration()) else {
+        ui.label("The controller has no clip");
+        return changed;
+    };
+    // Leave room after the last keyframe to drag keyframes further
+    let shown = duration.max(1.0) * 1.25;
+
+    let mut time = controller.time();
+    let scrub = ui.add(
+        egui::Slider::new(&mut time, 0.0..=duration.max(f32::EPSILON))
+            .suffix(" s")
+            .text("Time"),
+    );
+    if scrub.changed() {
+        controller.seek(time);
+        changed = true;
+    }
+    ui.separator();
+
+    let mut seek = None;
+    let mut removed = None;
+    let clip = controller.current_clip_mut().unwrap();
+    for (i, track) in clip.tracks.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            let label = egui::SelectableLabel::new(*selected_track == i, track.target.name());
+            if ui.add_sized([LABEL_WIDTH, LANE_HEIGHT], label).clicked() {
+                *selected_track = i;
+            }
+            egui::ComboBox::from_id_salt(id.with(("interpolation", i)))
+                .width(70.0)
+                .selected_text(format!("{:?}", track.interpolation))
+                .show_ui(ui, |ui| {
+                    for interpolation in Interpolation::ALL {
+                        let text = format!("{:?}", interpolation);
+                        changed |= ui
+                            .selectable_value(&mut track.interpolation, interpolation, text)
+                            .changed();
+                    }
+                });
+            if ui
+                .small_button("🗑")
+                .on_hover_text("Remove the track")
+                .clicked()
+            {
+                removed = Some(i);
+            }
+
+            let (moved, clicked) = show_lane(ui, id.with(("lane", i)), track, shown, time);
+            changed |= moved;
+            seek = seek.or(clicked);
+        });
+    }
+    if let Some(i) = removed {
+        clip.tracks.remove(i);
+        changed = true;
+    }
+
+    let missing = TrackTarget::ALL
+        .into_iter()
+        .filter(|target| clip.tracks.iter().all(|track| track.target != *target))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        egui::ComboBox::from_id_salt(id.with("add_track"))
+            .selected_text("Add a track")
+            .show_ui(ui, |ui| {
+                for target in missing {
+                    if ui.selectable_label(false, target.name()).clicked() {
+                        let track = Track::new(target).with_key(time, default_value(target));
+                        clip.tracks.push(track);
+                        *selected_track = clip.tracks.len() - 1;
+                        changed = true;
+                    }
+                }
+            });
+    }
+
+    if let Some(track) = clip.tracks.get_mut(*selected_track) {
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label(track.target.name());
+            if ui.button("Key at playhead").clicked() {
+                let value = track.sample(time).unwrap_or(default_value(track.target));
+                track.insert_key(time, value);
+                changed = true;
+            }
+        });
+        let curve_id = id.with(("curve", *selected_track));
+        changed |= inspector::curve_editor(
+            ui,
+            curve_id,
+            &mut track.keyframes,
+            shown,
+            track.interpolation,
+        );
+    }
+
+    if let Some(time) = seek {
+        controller.seek(time);
+        changed = true;
+    }
+    changed
+}
+
+/// Draw the timeline window.
+pub fn show_timeline(ctx: &egui::Context, ecs: &ecs::Manager) {
+    let Some((_, timeline)) = ecs.get_all_components_of_type::<Timeline>().pop() else {
+        return;
+    };
+    let mut timeline = timeline.write().unwrap();
+    if !timeline.open {
+        return;
+    }
+
+    let mut animated = ecs.get_entites_with_component::<AnimationController>();
+    animated.sort_by_key(|entity| entity.id());
+    let inspected = ecs
+        .get_all_components_of_type::<Inspector>()
+        .pop()
+        .and_then(|(_, inspector)| inspector.read().unwrap().selected);
+    if let Some(entity) = inspected.filter(|entity| animated.contains(entity)) {
+        timeline.selected = Some(entity);
+    }
+    if !timeline
+        .selected
+        .is_some_and(|entity| animated.contains(&entity))
+    {
+        timeline.selected = animated.first().copied();
+    }
+
+    let label = |entity: Entity| match ecs.get_component_from_entity::<Name>(entity) {
+        Some(name) => format!("{} ({})", name.read().unwrap().0, entity.id()),
+        None => format!("Entity {}", entity.id()),
+    };
+
+    let mut open = timeline.open;
+    egui::Window::new("Timeline")
+        .open(&mut open)
+        .default_size([640.0, 280.0])
+        .show(ctx, |ui| {
+            egui::ComboBox::from_label("Entity")
+                .selected_text(timeline.selected.map(label).unwrap_or_default())
+                .show_ui(ui, |ui| {
+                    for entity in &animated {
+                        ui.selectable_value(&mut timeline.selected, Some(*entity), label(*entity));
+                    }
+                });
+
+            let Some(entity) = timeline.selected else {
+                ui.label("No entity has an AnimationController");
+                return;
+            };
+            let Some(controller) = ecs.get_component_from_entity::<AnimationController>(entity)
+            else {
+                return;
+            };
+            let mut controller = controller.write().unwrap();
+
+            let id = egui::Id::new(("timeline", entity));
+            let mut changed = show_controller(ui, id, &mut timeline.track, &mut controller);
+
+            // The game loop does not advance the animations while the world is edited
+            if !play::is_playing(ecs) && controller.is_playing() {
+                controller.advance(ui.input(|i| i.stable_dt));
+                ui.ctx().request_repaint();
+                changed = true;
+            }
+            if changed {
+                controller.apply(ecs, entity);
+            }
+        });
+    timeline.open = open;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::components::Pos3;
+    use crate::gameplay::animation::AnimationClip;
+
+    #[test]
+    fn test_show_timeline() {
+        let ecs = ecs::Manager::default();
+        let entity = ecs.create_entity();
+        ecs.add_component_to_entity(entity, Pos3::default());
+        let clip = AnimationClip::new("bounce").with_track(
+            Track::new(TrackTarget::PositionY)
+                .with_key(0.0, 0.0)
+                .with_key(1.0, 2.0),
+        );
+        ecs.add_component_to_entity(entity, AnimationController::new().with_clip(clip));
+        let editor = ecs.create_entity();
+        ecs.add_component_to_entity(editor, Timeline::default());
+
+        let ctx = egui::Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |ctx| show_timeline(ctx, &ecs));
+
+        // The only animated entity is selected
+        let timeline = ecs.get_component_from_entity::<Timeline>(editor).unwrap();
+        assert_eq!(timeline.read().unwrap().selected, Some(entity));
+    }
+}
@@ -0,0 +1,416 @@
+use super::cinematic::{segment, Keyframe};
+use crate::core::Dt;
+use crate::ecs::components::{Pos3, Scale};
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::{Deg, Euler, Quaternion};
+
+/// How a track blends between two keyframes.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Hold the value of a keyframe until the next one.
+    Step,
+    #[default]
+    Linear,
+    /// Ease in and out of the keyframes.
+    Smooth,
+}
+
+impl Interpolation {
+    pub const ALL: [Self; 3] = [Self::Step, Self::Linear, Self::Smooth];
+
+    /// Map the linear blend factor between two keyframes.
+    pub fn blend(self, t: f32) -> f32 {
+        match self {
+            Self::Step => 0.0,
+            Self::Linear => t,
+            Self::Smooth => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Sample a track of keyframes, `None` if it is empty.
+pub fn sample_keyframes(
+    keyframes: &[Keyframe<f32>],
+    interpolation: Interpolation,
+    time: f32,
+) -> Option<f32> {
+    let (a, b, t) = segment(keyframes, time)?;
+    Some(a + (b - a) * interpolation.blend(t))
+}
+
+/// The property of the entity a track animates.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TrackTarget {
+    PositionX,
+    PositionY,
+    PositionZ,
+    /// The rotation around an axis in degrees, the rotation is applied around x, then y, then z.
+    RotationX,
+    RotationY,
+    RotationZ,
+    /// A uniform scale.
+    Scale,
+}
+
+impl TrackTarget {
+    pub const ALL: [Self; 7] = [
+        Self::PositionX,
+        Self::PositionY,
+        Self::PositionZ,
+        Self::RotationX,
+        Self::RotationY,
+        Self::RotationZ,
+        Self::Scale,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PositionX => "Position X",
+            Self::PositionY => "Position Y",
+            Self::PositionZ => "Position Z",
+            Self::RotationX => "Rotation X",
+            Self::RotationY => "Rotation Y",
+            Self::RotationZ => "Rotation Z",
+            Self::Scale => "Scale",
+        }
+    }
+}
+
+/// The keyframes of a property.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub target: TrackTarget,
+    pub interpolation: Interpolation,
+    /// The keyframes, sorted by time.
+    pub keyframes: Vec<Keyframe<f32>>,
+}
+
+impl Track {
+    pub fn new(target: TrackTarget) -> Self {
+        Self {
+            target,
+            interpolation: Interpolation::default(),
+            keyframes: Vec::new(),
+        }
+    }
+
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    pub fn with_key(mut self, time: f32, value: f32) -> Self {
+        self.insert_key(time, value);
+        self
+    }
+
+    /// Add a keyframe, replacing the keyframe at the same time.
+    pub fn insert_key(&mut self, time: f32, value: f32) {
+        match self.keyframes.iter_mut().find(|k| k.time == time) {
+            Some(keyframe) => keyframe.value = value,
+            None => {
+                let index = self.keyframes.partition_point(|k| k.time <= time);
+                self.keyframes.insert(index, Keyframe::new(time, value));
+            }
+        }
+    }
+
+    pub fn sample(&self, time: f32) -> Option<f32> {
+        sample_keyframes(&self.keyframes, self.interpolation, time)
+    }
+}
+
+/// An animation of the transform of an entity.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnimationClip {
+    pub name: String,
+    /// Whether the clip starts over at its end.
+    pub looping: bool,
+    pub tracks: Vec<Track>,
+}
+
+impl AnimationClip {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn with_track(mut self, track: Track) -> Self {
+        self.tracks.push(track);
+        self
+    }
+
+    /// Get the duration of the clip in seconds, the time of the last keyframe.
+    pub fn duration(&self) -> f32 {
+        self.tracks
+            .iter()
+            .filter_map(|track| track.keyframes.last())
+            .fold(0.0, |duration, k| duration.max(k.time))
+    }
+
+    /// Sample the animated properties at a point in time.
+    pub fn sample(&self, time: f32) -> Vec<(TrackTarget, f32)> {
+        self.tracks
+            .iter()
+            .filter_map(|track| Some((track.target, track.sample(time)?)))
+            .collect()
+    }
+}
+
+/// A component that plays animation clips on the transform of its entity.
+#[derive(Debug, Clone)]
+pub struct AnimationController {
+    pub clips: Vec<AnimationClip>,
+    /// The playback speed, 1 is the speed of the clips.
+    pub speed: f32,
+    current: usize,
+    time: f32,
+    playing: bool,
+}
+
+impl Component for AnimationController {}
+
+impl Default for AnimationController {
+    fn default() -> Self {
+        Self {
+            clips: Vec::new(),
+            speed: 1.0,
+            current: 0,
+            time: 0.0,
+            playing: false,
+        }
+    }
+}
+
+impl AnimationController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_clip(mut self, clip: AnimationClip) -> Self {
+        self.clips.push(clip);
+        self
+    }
+
+    /// Play a clip from its start.
+    pub fn play(&mut self, index: usize) {
+        self.current = index.min(self.clips.len().saturating_sub(1));
+        self.time = 0.0;
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn resume(&mut self) {
+        self.playing = true;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Get the index of the current clip.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn current_clip(&self) -> Option<&AnimationClip> {
+        self.clips.get(self.current)
+    }
+
+    pub fn current_clip_mut(&mut self) -> Option<&mut AnimationClip> {
+        self.clips.get_mut(self.current)
+    }
+
+    /// Switch to a clip, keeping the time and the playback state.
+    pub fn select(&mut self, index: usize) {
+        self.current = index.min(self.clips.len().saturating_sub(1));
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Move the playhead of the current clip.
+    pub fn seek(&mut self, time: f32) {
+        let duration = self.current_clip().map_or(0.0, AnimationClip::duration);
+        self.time = time.clamp(0.0, duration);
+    }
+
+    /// Advance the playhead.
+    ///
+    /// # Returns
+    ///
+    /// True if a clip which does not loop reached its end, it is paused at its last frame.
+    pub fn advance(&mut self, dt: f32) -> bool {
+        let Some(clip) = self.clips.get(self.current) else {
+            return false;
+        };
+        if !self.playing {
+            return false;
+        }
+
+        let duration = clip.duration();
+        self.time += dt * self.speed;
+        if self.time <= duration {
+            return false;
+        }
+        if clip.looping && duration > 0.0 {
+            self.time %= duration;
+            return false;
+        }
+        self.time = duration;
+        self.playing = false;
+        true
+    }
+
+    /// Write the pose of the current clip at the playhead to the `Pos3` and `Scale` of an entity.
+    pub fn apply(&self, ecs: &ecs::Manager, entity: Entity) {
+        let Some(clip) = self.current_clip() else {
+            return;
+        };
+        let values = clip.sample(self.time);
+        if values.is_empty() {
+            return;
+        }
+
+        if let Some(pos) = ecs.get_component_from_entity::<Pos3>(entity) {
+            let mut pos = pos.write().unwrap();
+            let rotation = pos.rot.unwrap_or(Quaternion::new(1.0, 0.0, 0.0, 0.0));
+            let euler = Euler::from(rotation);
+            let mut angles = [
+                Deg::from(euler.x).0,
+                Deg::from(euler.y).0,
+                Deg::from(euler.z).0,
+            ];
+            let mut rotated = false;
+
+            for (target, value) in &values {
+                match target {
+                    TrackTarget::PositionX => pos.pos.x = *value,
+                    TrackTarget::PositionY => pos.pos.y = *value,
+                    TrackTarget::PositionZ => pos.pos.z = *value,
+                    TrackTarget::RotationX => (angles[0], rotated) = (*value, true),
+                    TrackTarget::RotationY => (angles[1], rotated) = (*value, true),
+                    TrackTarget::RotationZ => (angles[2], rotated) = (*value, true),
+                    TrackTarget::Scale => {}
+                }
+            }
+            if rotated {
+                let [x, y, z] = angles;
+                pos.rot = Some(Quaternion::from(Euler::new(Deg(x), Deg(y), Deg(z))));
+            }
+        }
+
+        if let Some((_, scale)) = values
+            .iter()
+            .find(|(target, _)| *target == TrackTarget::Scale)
+        {
+            match ecs.get_component_from_entity::<Scale>(entity) {
+                Some(component) => *component.write().unwrap() = Scale::Uniform(*scale),
+                None => ecs.add_component_to_entity(entity, Scale::Uniform(*scale)),
+            }
+        }
+    }
+}
+
+/// Sent when a clip which does not loop has played to its end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationFinished {
+    pub entity: Entity,
+    pub clip: String,
+}
+
+/// Advance the animation controllers and apply their poses.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_animations(ecs: &ecs::Manager, dt: Dt) {
+    for (entity, controller) in ecs.get_all_components_of_type::<AnimationController>() {
+        let mut controller = controller.write().unwrap();
+        if !controller.is_playing() {
+            continue;
+        }
+
+        if controller.advance(dt.as_secs_f32()) {
+            let clip = controller
+                .current_clip()
+                .map(|clip| clip.name.clone())
+                .unwrap_or_default();
+            ecs.send_event(AnimationFinished { entity, clip });
+        }
+        controller.apply(ecs, entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector3;
+    use instant::Duration;
+
+    #[test]
+    fn test_interpolation() {
+        let track = Track::new(TrackTarget::PositionX)
+            .with_key(1.0, 10.0)
+            .with_key(0.0, 0.0);
+        assert_eq!(track.sample(0.5), Some(5.0));
+        assert_eq!(track.sample(2.0), Some(10.0));
+
+        let step = track.clone().with_interpolation(Interpolation::Step);
+        assert_eq!(step.sample(0.9), Some(0.0));
+        let smooth = track.with_interpolation(Interpolation::Smooth);
+        assert!(smooth.sample(0.25).unwrap() < 2.5);
+        assert_eq!(smooth.sample(0.5), Some(5.0));
+    }
+
+    #[test]
+    fn test_play_clip() {
+        let manager = ecs::Manager::default();
+        let entity = manager.create_entity();
+        manager.add_component_to_entity(entity, Pos3::new(Vector3::new(0.0, 3.0, 0.0)));
+
+        let clip = AnimationClip::new("slide")
+            .with_track(
+                Track::new(TrackTarget::PositionX)
+                    .with_key(0.0, 0.0)
+                    .with_key(2.0, 4.0),
+            )
+            .with_track(
+                Track::new(TrackTarget::Scale)
+                    .with_key(0.0, 1.0)
+                    .with_key(2.0, 3.0),
+            );
+        let mut controller = AnimationController::new().with_clip(clip);
+        controller.play(0);
+        manager.add_component_to_entity(entity, controller);
+
+        update_animations(&manager, Duration::from_secs(1));
+        let pos = manager.get_component_from_entity::<Pos3>(entity).unwrap();
+        assert_eq!(pos.read().unwrap().pos, Vector3::new(2.0, 3.0, 0.0));
+        let scale = manager.get_component_from_entity::<Scale>(entity).unwrap();
+        assert!(matches!(*scale.read().unwrap(), Scale::Uniform(s) if s == 2.0));
+
+        update_animations(&manager, Duration::from_secs(5));
+        assert_eq!(pos.read().unwrap().pos.x, 4.0);
+        assert_eq!(
+            manager.drain_events::<AnimationFinished>(),
+            vec![AnimationFinished {
+                entity,
+                clip: "slide".to_string()
+            }]
+        );
+    }
+}
@@ -0,0 +1,292 @@
+use crate::core::Dt;
+use crate::ecs::components::Pos3;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::{Deg, Vector3, VectorSpace};
+use std::sync::Arc;
+
+/// A value at a point in time of a track.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Keyframe<T> {
+    pub time: f32,
+    pub value: T,
+}
+
+impl<T> Keyframe<T> {
+    pub fn new(time: f32, value: T) -> Self {
+        Self { time, value }
+    }
+}
+
+/// Find the keyframes around a point in time and the blend factor between them.
+pub(crate) fn segment<T>(keyframes: &[Keyframe<T>], time: f32) -> Option<(&T, &T, f32)> {
+    let first = keyframes.first()?;
+    let index = keyframes.partition_point(|k| k.time <= time);
+
+    let (a, b) = match index {
+        0 => (first, first),
+        i if i >= keyframes.len() => {
+            let last = keyframes.last().unwrap();
+            (last, last)
+        }
+        i => (&keyframes[i - 1], &keyframes[i]),
+    };
+
+    let span = b.time - a.time;
+    let t = if span > 0.0 {
+        ((time - a.time) / span).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    Some((&a.value, &b.value, t))
+}
+
+/// The target a camera looks at.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LookAt {
+    Point(Vector3<f32>),
+    /// The `Pos3` of an entity, resolved on each update.
+    Entity(Entity),
+}
+
+impl LookAt {
+    fn resolve(&self, ecs: &ecs::Manager) -> Option<Vector3<f32>> {
+        match self {
+            LookAt::Point(point) => Some(*point),
+            LookAt::Entity(entity) => {
+                let pos = ecs.get_component_from_entity::<Pos3>(*entity)?;
+                let pos = pos.read().unwrap().pos;
+                Some(pos)
+            }
+        }
+    }
+}
+
+/// A camera cutscene made of keyframe tracks.
+/// The position, look-at and field of view tracks are interpolated linearly, the letterbox track is stepped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cutscene {
+    pub name: String,
+    pub position: Vec<Keyframe<Vector3<f32>>>,
+    pub look_at: Vec<Keyframe<LookAt>>,
+    pub fov: Vec<Keyframe<Deg<f32>>>,
+    pub letterbox: Vec<Keyframe<bool>>,
+}
+
+impl Cutscene {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_position(mut self, time: f32, position: Vector3<f32>) -> Self {
+        insert_sorted(&mut self.position, Keyframe::new(time, position));
+        self
+    }
+
+    pub fn with_look_at(mut self, time: f32, look_at: LookAt) -> Self {
+        insert_sorted(&mut self.look_at, Keyframe::new(time, look_at));
+        self
+    }
+
+    pub fn with_fov(mut self, time: f32, fov: Deg<f32>) -> Self {
+        insert_sorted(&mut self.fov, Keyframe::new(time, fov));
+        self
+    }
+
+    pub fn with_letterbox(mut self, time: f32, letterbox: bool) -> Self {
+        insert_sorted(&mut self.letterbox, Keyframe::new(time, letterbox));
+        self
+    }
+
+    /// Get the duration of the cutscene in seconds, the time of the last keyframe.
+    pub fn duration(&self) -> f32 {
+        let last = |times: &mut dyn Iterator<Item = f32>| times.fold(0.0f32, f32::max);
+        [
+            last(&mut self.position.iter().map(|k| k.time)),
+            last(&mut self.look_at.iter().map(|k| k.time)),
+            last(&mut self.fov.iter().map(|k| k.time)),
+            last(&mut self.letterbox.iter().map(|k| k.time)),
+        ]
+        .into_iter()
+        .fold(0.0, f32::max)
+    }
+
+    /// Sample the camera shot at a point in time.
+    /// Returns `None` if the cutscene has no position track.
+    pub fn sample(&self, ecs: &ecs::Manager, time: f32) -> Option<CameraShot> {
+        let (a, b, t) = segment(&self.position, time)?;
+        let position = a.lerp(*b, t);
+
+        let target = segment(&self.look_at, time).and_then(|(a, b, t)| {
+            let a = a.resolve(ecs)?;
+            let b = b.resolve(ecs).unwrap_or(a);
+            Some(a.lerp(b, t))
+        });
+        let fov = segment(&self.fov, time).map(|(a, b, t)| Deg(a.0 + (b.0 - a.0) * t));
+        let letterbox = segment(&self.letterbox, time).is_some_and(|(a, _, _)| *a);
+
+        Some(CameraShot {
+            position,
+            target,
+            fov,
+            letterbox,
+        })
+    }
+}
+
+fn insert_sorted<T>(keyframes: &mut Vec<Keyframe<T>>, keyframe: Keyframe<T>) {
+    let index = keyframes.partition_point(|k| k.time <= keyframe.time);
+    keyframes.insert(index, keyframe);
+}
+
+/// The state of the camera at a point in a cutscene.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CameraShot {
+    pub position: Vector3<f32>,
+    /// The point to look at, the camera keeps its orientation if `None`.
+    pub target: Option<Vector3<f32>>,
+    /// The field of view, the default is used if `None`.
+    pub fov: Option<Deg<f32>>,
+    pub letterbox: bool,
+}
+
+/// A component that plays cutscenes. While a cutscene is playing, the renderer takes the camera from its shot.
+#[derive(Debug, Clone, Default)]
+pub struct CutscenePlayer {
+    cutscene: Option<Arc<Cutscene>>,
+    time: f32,
+    shot: Option<CameraShot>,
+}
+
+impl Component for CutscenePlayer {}
+
+impl CutscenePlayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn play(&mut self, cutscene: Arc<Cutscene>) {
+        self.cutscene = Some(cutscene);
+        self.time = 0.0;
+        self.shot = None;
+    }
+
+    pub fn stop(&mut self) {
+        self.cutscene = None;
+        self.shot = None;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.cutscene.is_some()
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Get the camera shot of the current frame.
+    pub fn shot(&self) -> Option<CameraShot> {
+        self.shot
+    }
+}
+
+/// Send to start a cutscene on all `CutscenePlayer`s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayCutscene(pub Arc<Cutscene>);
+
+/// Sent when a cutscene has played to its end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CutsceneFinished {
+    pub entity: Entity,
+    pub name: String,
+}
+
+/// Start the requested cutscenes, advance the players and sample their camera shots.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_cutscenes(ecs: &ecs::Manager, dt: Dt) {
+    let requests = ecs.drain_events::<PlayCutscene>();
+
+    for (entity, player) in ecs.get_all_components_of_type::<CutscenePlayer>() {
+        let mut player = player.write().unwrap();
+        if let Some(PlayCutscene(cutscene)) = requests.last() {
+            player.play(cutscene.clone());
+        } else if player.cutscene.is_some() {
+            player.time += dt.as_secs_f32();
+        }
+
+        let Some(cutscene) = player.cutscene.clone() else {
+            continue;
+        };
+
+        if player.time > cutscene.duration() {
+            player.stop();
+            ecs.send_event(CutsceneFinished {
+                entity,
+                name: cutscene.name.clone(),
+            });
+            continue;
+        }
+
+        player.shot = cutscene.sample(ecs, player.time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instant::Duration;
+
+    #[test]
+    fn test_sample_tracks() {
+        let manager = ecs::Manager::default();
+        let target = manager.create_entity();
+        manager.add_component_to_entity(target, Pos3::new(Vector3::new(0.0, 1.0, 0.0)));
+
+        let cutscene = Cutscene::new("flyby")
+            .with_position(2.0, Vector3::new(10.0, 0.0, 0.0))
+            .with_position(0.0, Vector3::new(0.0, 0.0, 0.0))
+            .with_look_at(0.0, LookAt::Entity(target))
+            .with_fov(0.0, Deg(60.0))
+            .with_fov(2.0, Deg(30.0))
+            .with_letterbox(0.0, true)
+            .with_letterbox(1.5, false);
+        assert_eq!(cutscene.duration(), 2.0);
+
+        let shot = cutscene.sample(&manager, 1.0).unwrap();
+        assert_eq!(shot.position, Vector3::new(5.0, 0.0, 0.0));
+        assert_eq!(shot.target, Some(Vector3::new(0.0, 1.0, 0.0)));
+        assert_eq!(shot.fov, Some(Deg(45.0)));
+        assert!(shot.letterbox);
+        assert!(!cutscene.sample(&manager, 1.75).unwrap().letterbox);
+    }
+
+    #[test]
+    fn test_play_via_event() {
+        let manager = ecs::Manager::default();
+        let camera = manager.create_entity();
+        manager.add_component_to_entity(camera, CutscenePlayer::new());
+
+        let cutscene = Cutscene::new("intro")
+            .with_position(0.0, Vector3::new(0.0, 0.0, 0.0))
+            .with_position(1.0, Vector3::new(0.0, 5.0, 0.0));
+        manager.send_event(PlayCutscene(Arc::new(cutscene)));
+
+        update_cutscenes(&manager, Duration::from_millis(16));
+        let player = manager
+            .get_component_from_entity::<CutscenePlayer>(camera)
+            .unwrap();
+        assert!(player.read().unwrap().shot().is_some());
+
+        update_cutscenes(&manager, Duration::from_secs(2));
+        assert!(!player.read().unwrap().is_playing());
+        assert_eq!(manager.drain_events::<CutsceneFinished>().len(), 1);
+    }
+}
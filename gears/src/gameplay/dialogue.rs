@@ -0,0 +1,377 @@
+use crate::core::Dt;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use crate::gui::layout::{AnchorSpace, HudAnchor};
+use std::collections::VecDeque;
+use std::path::Path;
+
+/// A single spoken line with its subtitle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DialogueLine {
+    pub speaker: String,
+    pub text: String,
+    /// The time in seconds the subtitle is shown.
+    pub duration: f32,
+    /// The path of the voice over, played by `update_audio_backend` while the line is shown.
+    pub audio: Option<String>,
+}
+
+impl DialogueLine {
+    pub fn new(speaker: impl Into<String>, text: impl Into<String>, duration: f32) -> Self {
+        Self {
+            speaker: speaker.into(),
+            text: text.into(),
+            duration,
+            audio: None,
+        }
+    }
+
+    pub fn with_audio(mut self, audio: impl Into<String>) -> Self {
+        self.audio = Some(audio.into());
+        self
+    }
+}
+
+/// A named sequence of dialogue lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dialogue {
+    pub name: String,
+    pub lines: Vec<DialogueLine>,
+}
+
+impl Dialogue {
+    pub fn new(name: impl Into<String>, lines: Vec<DialogueLine>) -> Self {
+        Self {
+            name: name.into(),
+            lines,
+        }
+    }
+
+    /// Parse a dialogue from text data.
+    /// Each non-empty line is `speaker | duration | text [| audio]`, lines starting with `#` are ignored.
+    pub fn parse(name: impl Into<String>, data: &str) -> anyhow::Result<Self> {
+        let mut lines = Vec::new();
+
+        for (line_number, line) in data.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+            if !(3..=4).contains(&fields.len()) {
+                anyhow::bail!(
+                    "Invalid dialogue line {}: expected `speaker | duration | text [| audio]`",
+                    line_number + 1
+                );
+            }
+
+            let duration = fields[1].parse::<f32>().map_err(|e| {
+                anyhow::anyhow!("Invalid duration on line {}: {}", line_number + 1, e)
+            })?;
+
+            lines.push(DialogueLine {
+                speaker: fields[0].to_string(),
+                text: fields[2].to_string(),
+                duration,
+                audio: fields
+                    .get(3)
+                    .filter(|a| !a.is_empty())
+                    .map(|a| a.to_string()),
+            });
+        }
+
+        Ok(Self::new(name, lines))
+    }
+
+    /// Load a dialogue from a file, the file stem is used as the name.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path)?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        Self::parse(name, &data)
+    }
+}
+
+/// The styling of the subtitles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubtitleStyle {
+    pub text_size: f32,
+    pub text_color: egui::Color32,
+    pub speaker_color: egui::Color32,
+    pub background: egui::Color32,
+    /// The distance of the subtitles from the bottom of the viewport in points.
+    pub bottom_margin: f32,
+}
+
+impl Default for SubtitleStyle {
+    fn default() -> Self {
+        Self {
+            text_size: 18.0,
+            text_color: egui::Color32::WHITE,
+            speaker_color: egui::Color32::from_rgb(255, 210, 90),
+            background: egui::Color32::from_black_alpha(160),
+            bottom_margin: 48.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct QueuedLine {
+    dialogue: Option<String>,
+    index: usize,
+    last: bool,
+    line: DialogueLine,
+}
+
+/// A component that plays queued dialogue lines one after the other.
+#[derive(Debug, Clone, Default)]
+pub struct DialoguePlayer {
+    pub style: SubtitleStyle,
+    queue: VecDeque<QueuedLine>,
+    current: Option<QueuedLine>,
+    started: bool,
+    elapsed: f32,
+    lines_started: u64,
+}
+
+impl Component for DialoguePlayer {}
+
+impl DialoguePlayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_style(mut self, style: SubtitleStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Queue a single line.
+    pub fn play_line(&mut self, line: DialogueLine) {
+        self.queue.push_back(QueuedLine {
+            dialogue: None,
+            index: 0,
+            last: true,
+            line,
+        });
+    }
+
+    /// Queue all lines of a dialogue.
+    pub fn play(&mut self, dialogue: &Dialogue) {
+        let count = dialogue.lines.len();
+        for (index, line) in dialogue.lines.iter().enumerate() {
+            self.queue.push_back(QueuedLine {
+                dialogue: Some(dialogue.name.clone()),
+                index,
+                last: index + 1 == count,
+                line: line.clone(),
+            });
+        }
+    }
+
+    /// Get the line that is being played.
+    pub fn current(&self) -> Option<&DialogueLine> {
+        self.current.as_ref().map(|q| &q.line)
+    }
+
+    /// Get the time in seconds the current line has been played for.
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    /// Get the number of lines started so far, it tells a line played again apart from the current one.
+    pub fn lines_started(&self) -> u64 {
+        self.lines_started
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.current.is_some() || !self.queue.is_empty()
+    }
+
+    /// Finish the current line on the next update.
+    pub fn skip(&mut self) {
+        if let Some(current) = &self.current {
+            self.elapsed = current.line.duration;
+        }
+    }
+
+    /// Drop the current and all queued lines without sending completion events.
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.current = None;
+        self.elapsed = 0.0;
+    }
+}
+
+/// Sent when a dialogue line starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DialogueLineStarted {
+    pub entity: Entity,
+    pub line: DialogueLine,
+}
+
+/// Sent when a dialogue line has been shown for its duration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DialogueLineFinished {
+    pub entity: Entity,
+    /// The name of the dialogue the line belongs to, `None` for single lines.
+    pub dialogue: Option<String>,
+    pub index: usize,
+}
+
+/// Sent when the last line of a dialogue has finished.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogueFinished {
+    pub entity: Entity,
+    pub dialogue: String,
+}
+
+/// Advance all dialogue players and send the line events.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_dialogue(ecs: &ecs::Manager, dt: Dt) {
+    for (entity, player) in ecs.get_all_components_of_type::<DialoguePlayer>() {
+        let mut player = player.write().unwrap();
+        let mut dt = dt.as_secs_f32();
+
+        loop {
+            if player.current.is_none() {
+                player.current = player.queue.pop_front();
+                player.started = false;
+                player.elapsed = 0.0;
+            }
+            let Some(current) = player.current.clone() else {
+                break;
+            };
+
+            if !player.started {
+                player.started = true;
+                player.lines_started += 1;
+                ecs.send_event(DialogueLineStarted {
+                    entity,
+                    line: current.line.clone(),
+                });
+            }
+
+            // Carry the remaining time over to the next line
+            let remaining = current.line.duration - player.elapsed;
+            if dt < remaining {
+                player.elapsed += dt;
+                break;
+            }
+            dt -= remaining.max(0.0);
+
+            ecs.send_event(DialogueLineFinished {
+                entity,
+                dialogue: current.dialogue.clone(),
+                index: current.index,
+            });
+            if let (Some(dialogue), true) = (current.dialogue, current.last) {
+                ecs.send_event(DialogueFinished { entity, dialogue });
+            }
+            player.current = None;
+        }
+    }
+}
+
+/// Draw the subtitle of a line at the bottom of the viewport.
+/// The speaker and the text are translated, so they can be localization keys.
+pub fn show_subtitle(
+    ctx: &egui::Context,
+    id: egui::Id,
+    line: &DialogueLine,
+    style: &SubtitleStyle,
+) {
+    HudAnchor::new(egui::Align2::CENTER_BOTTOM)
+        .with_offset([0.0, -style.bottom_margin])
+        .in_space(AnchorSpace::Viewport)
+        .area(ctx, id)
+        .show(ctx, |ui| {
+            egui::Frame::none()
+                .fill(style.background)
+                .rounding(4.0)
+                .inner_margin(egui::Margin::symmetric(12.0, 6.0))
+                .show(ui, |ui| {
+                    ui.horizontal_wrapped(|ui| {
+                        if !line.speaker.is_empty() {
+                            ui.label(
+                                egui::RichText::new(format!("{}:", crate::tr!(&line.speaker)))
+                                    .size(style.text_size)
+                                    .color(style.speaker_color)
+                                    .strong(),
+                            );
+                        }
+                        ui.label(
+                            egui::RichText::new(crate::tr!(&line.text))
+                                .size(style.text_size)
+                                .color(style.text_color),
+                        );
+                    });
+                });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instant::Duration;
+
+    #[test]
+    fn test_parse_dialogue() {
+        let dialogue = Dialogue::parse(
+            "intro",
+            "# opening\nGuard | 2.5 | Halt! | audio/halt.ogg\nPlayer | 1.0 | Easy there.\n",
+        )
+        .unwrap();
+        assert_eq!(dialogue.lines.len(), 2);
+        assert_eq!(dialogue.lines[0].audio.as_deref(), Some("audio/halt.ogg"));
+        assert_eq!(dialogue.lines[1].audio, None);
+
+        assert!(Dialogue::parse("broken", "Guard | soon | Halt!").is_err());
+    }
+
+    #[test]
+    fn test_play_sequence() {
+        let manager = ecs::Manager::default();
+        let entity = manager.create_entity();
+
+        let mut player = DialoguePlayer::new();
+        player.play(&Dialogue::new(
+            "intro",
+            vec![
+                DialogueLine::new("Guard", "Halt!", 1.0),
+                DialogueLine::new("Player", "Easy there.", 1.0),
+            ],
+        ));
+        manager.add_component_to_entity(entity, player);
+
+        update_dialogue(&manager, Duration::from_millis(500));
+        let player = manager
+            .get_component_from_entity::<DialoguePlayer>(entity)
+            .unwrap();
+        assert_eq!(player.read().unwrap().current().unwrap().speaker, "Guard");
+
+        update_dialogue(&manager, Duration::from_secs(1));
+        assert_eq!(player.read().unwrap().current().unwrap().speaker, "Player");
+        assert_eq!(manager.drain_events::<DialogueLineStarted>().len(), 2);
+
+        update_dialogue(&manager, Duration::from_secs(1));
+        assert!(!player.read().unwrap().is_playing());
+        assert_eq!(manager.drain_events::<DialogueLineFinished>().len(), 2);
+        assert_eq!(
+            manager.drain_events::<DialogueFinished>(),
+            vec![DialogueFinished {
+                entity,
+                dialogue: "intro".to_string()
+            }]
+        );
+    }
+}
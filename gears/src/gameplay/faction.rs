@@ -0,0 +1,184 @@
+use crate::ecs::components::Pos3;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::InnerSpace;
+use std::collections::HashMap;
+
+/// A component that assigns an entity to a faction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Faction(pub u32);
+
+impl Component for Faction {}
+
+/// The relationship between two factions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Relationship {
+    Ally,
+    Neutral,
+    Hostile,
+}
+
+/// A table of relationships between factions.
+/// Members of the same faction are allies, any pair without an entry uses the default relationship.
+/// Store it on an entity or share it with the systems that need it, e.g. AI targeting and damage rules.
+#[derive(Debug, Clone)]
+pub struct FactionTable {
+    relationships: HashMap<(u32, u32), Relationship>,
+    default: Relationship,
+}
+
+impl Component for FactionTable {}
+
+impl Default for FactionTable {
+    fn default() -> Self {
+        Self::new(Relationship::Neutral)
+    }
+}
+
+impl FactionTable {
+    pub fn new(default: Relationship) -> Self {
+        Self {
+            relationships: HashMap::new(),
+            default,
+        }
+    }
+
+    fn key(a: Faction, b: Faction) -> (u32, u32) {
+        (a.0.min(b.0), a.0.max(b.0))
+    }
+
+    /// Set the relationship between two factions, it is symmetric.
+    pub fn set(&mut self, a: Faction, b: Faction, relationship: Relationship) {
+        self.relationships.insert(Self::key(a, b), relationship);
+    }
+
+    /// Get the relationship between two factions.
+    pub fn get(&self, a: Faction, b: Faction) -> Relationship {
+        if a == b {
+            return Relationship::Ally;
+        }
+
+        self.relationships
+            .get(&Self::key(a, b))
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Get the relationship between two entities.
+    /// Entities without a `Faction` are neutral to everyone.
+    pub fn relationship(&self, ecs: &ecs::Manager, a: Entity, b: Entity) -> Relationship {
+        match (
+            ecs.get_component_from_entity::<Faction>(a),
+            ecs.get_component_from_entity::<Faction>(b),
+        ) {
+            (Some(fa), Some(fb)) => self.get(*fa.read().unwrap(), *fb.read().unwrap()),
+            _ => Relationship::Neutral,
+        }
+    }
+
+    pub fn is_hostile(&self, ecs: &ecs::Manager, a: Entity, b: Entity) -> bool {
+        self.relationship(ecs, a, b) == Relationship::Hostile
+    }
+
+    pub fn is_ally(&self, ecs: &ecs::Manager, a: Entity, b: Entity) -> bool {
+        self.relationship(ecs, a, b) == Relationship::Ally
+    }
+
+    /// Check if an attacker is allowed to damage a target.
+    /// Damage between allies is prevented unless friendly fire is enabled.
+    pub fn can_damage(
+        &self,
+        ecs: &ecs::Manager,
+        attacker: Entity,
+        target: Entity,
+        friendly_fire: bool,
+    ) -> bool {
+        attacker != target && (friendly_fire || !self.is_ally(ecs, attacker, target))
+    }
+
+    /// Find the closest entity that is hostile to the given entity.
+    ///
+    /// # Arguments
+    ///
+    /// * `ecs` - The entity component system manager.
+    /// * `entity` - The entity looking for a target, it needs a `Pos3`.
+    /// * `max_distance` - The maximum distance of the target.
+    pub fn nearest_hostile(
+        &self,
+        ecs: &ecs::Manager,
+        entity: Entity,
+        max_distance: f32,
+    ) -> Option<Entity> {
+        let faction = *ecs
+            .get_component_from_entity::<Faction>(entity)?
+            .read()
+            .unwrap();
+        let pos = ecs
+            .get_component_from_entity::<Pos3>(entity)?
+            .read()
+            .unwrap()
+            .pos;
+
+        ecs.get_all_components_of_type::<Faction>()
+            .into_iter()
+            .filter(|(other, other_faction)| {
+                *other != entity
+                    && self.get(faction, *other_faction.read().unwrap()) == Relationship::Hostile
+            })
+            .filter_map(|(other, _)| {
+                let other_pos = ecs.get_component_from_entity::<Pos3>(other)?;
+                let distance = (other_pos.read().unwrap().pos - pos).magnitude();
+                (distance <= max_distance).then_some((other, distance))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(other, _)| other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector3;
+
+    fn spawn(ecs: &ecs::Manager, faction: u32, x: f32) -> Entity {
+        let entity = ecs.create_entity();
+        ecs.add_component_to_entity(entity, Faction(faction));
+        ecs.add_component_to_entity(entity, Pos3::new(Vector3::new(x, 0.0, 0.0)));
+        entity
+    }
+
+    #[test]
+    fn test_relationships() {
+        let manager = ecs::Manager::default();
+        let mut table = FactionTable::default();
+        table.set(Faction(0), Faction(1), Relationship::Hostile);
+
+        let player = spawn(&manager, 0, 0.0);
+        let ally = spawn(&manager, 0, 1.0);
+        let enemy = spawn(&manager, 1, 2.0);
+        let bystander = spawn(&manager, 2, 3.0);
+
+        assert!(table.is_hostile(&manager, enemy, player));
+        assert!(table.is_ally(&manager, player, ally));
+        assert_eq!(
+            table.relationship(&manager, player, bystander),
+            Relationship::Neutral
+        );
+        assert!(!table.can_damage(&manager, player, ally, false));
+        assert!(table.can_damage(&manager, player, enemy, false));
+    }
+
+    #[test]
+    fn test_nearest_hostile() {
+        let manager = ecs::Manager::default();
+        let mut table = FactionTable::default();
+        table.set(Faction(0), Faction(1), Relationship::Hostile);
+
+        let player = spawn(&manager, 0, 0.0);
+        let _far = spawn(&manager, 1, 10.0);
+        let near = spawn(&manager, 1, -3.0);
+
+        assert_eq!(table.nearest_hostile(&manager, player, 20.0), Some(near));
+        assert_eq!(table.nearest_hostile(&manager, player, 1.0), None);
+    }
+}
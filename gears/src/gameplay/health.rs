@@ -0,0 +1,83 @@
+use super::status::StatusEffects;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+
+/// A component that stores the health of an entity.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Component for Health {}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    /// Reduce the health by the given amount.
+    ///
+    /// # Returns
+    ///
+    /// The amount of health that was actually lost.
+    pub fn damage(&mut self, amount: f32) -> f32 {
+        let lost = amount.max(0.0).min(self.current);
+        self.current -= lost;
+        lost
+    }
+
+    /// Increase the health by the given amount, up to the maximum.
+    ///
+    /// # Returns
+    ///
+    /// The amount of health that was actually restored.
+    pub fn heal(&mut self, amount: f32) -> f32 {
+        let restored = amount.max(0.0).min(self.max - self.current);
+        self.current += restored;
+        restored
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+}
+
+/// Sent when the health of an entity reaches zero.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Died {
+    pub entity: Entity,
+}
+
+/// Deal damage to an entity.
+/// Shields from the `StatusEffects` of the entity absorb the damage first.
+/// A `Died` event is sent if the health of the entity drops to zero.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `entity` - The entity to damage.
+/// * `amount` - The amount of damage.
+///
+/// # Returns
+///
+/// The amount of health that was lost.
+pub fn apply_damage(ecs: &ecs::Manager, entity: Entity, amount: f32) -> f32 {
+    let mut amount = amount;
+    if let Some(effects) = ecs.get_component_from_entity::<StatusEffects>(entity) {
+        amount = effects.write().unwrap().absorb(amount);
+    }
+
+    let Some(health) = ecs.get_component_from_entity::<Health>(entity) else {
+        return 0.0;
+    };
+    let mut health = health.write().unwrap();
+    let was_alive = !health.is_dead();
+    let lost = health.damage(amount);
+
+    if was_alive && health.is_dead() {
+        ecs.send_event(Died { entity });
+    }
+
+    lost
+}
@@ -0,0 +1,215 @@
+use crate::ecs::components::Pos3;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::{InnerSpace, Vector3};
+use winit::event::ElementState;
+use winit::keyboard::KeyCode;
+
+/// A component that allows the player to interact with an entity by looking at it.
+#[derive(Debug, Clone)]
+pub struct Interactable {
+    /// The text shown on the HUD while the entity is focused.
+    pub prompt: String,
+    /// The maximum distance from the camera the entity can be interacted from.
+    pub range: f32,
+    /// The radius of the sphere around the entity's position that is hit by the look ray.
+    pub radius: f32,
+    pub enabled: bool,
+}
+
+impl Component for Interactable {}
+
+impl Interactable {
+    pub fn new(prompt: impl Into<String>, range: f32) -> Self {
+        Self {
+            prompt: prompt.into(),
+            range,
+            radius: 0.5,
+            enabled: true,
+        }
+    }
+
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+}
+
+/// Sent when the player interacts with a focused `Interactable`.
+/// Use `ecs::Manager::drain_events::<Interacted>()` to receive it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Interacted {
+    pub entity: Entity,
+}
+
+/// Tracks the focused interactable and the state of the interact action.
+#[derive(Debug)]
+pub(crate) struct InteractionController {
+    key: KeyCode,
+    held: bool,
+    triggered: bool,
+    focused: Option<(Entity, String)>,
+}
+
+impl InteractionController {
+    pub fn new(key: KeyCode) -> Self {
+        Self {
+            key,
+            held: false,
+            triggered: false,
+            focused: None,
+        }
+    }
+
+    /// Get the prompt of the currently focused interactable, it is shown by the UI.
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub fn prompt(&self) -> Option<&str> {
+        self.focused.as_ref().map(|(_, prompt)| prompt.as_str())
+    }
+
+    pub fn process_keyboard(&mut self, key: KeyCode, state: ElementState) -> bool {
+        if key != self.key {
+            return false;
+        }
+
+        // Only trigger once per key press, ignore the key repeat
+        if state == ElementState::Pressed && !self.held {
+            self.triggered = true;
+        }
+        self.held = state == ElementState::Pressed;
+
+        true
+    }
+
+    /// Update the focused interactable and send an `Interacted` event if the action fired.
+    ///
+    /// # Arguments
+    ///
+    /// * `ecs` - The entity component system manager.
+    /// * `origin` - The origin of the look ray, usually the camera position.
+    /// * `direction` - The direction of the look ray.
+    pub fn update(&mut self, ecs: &ecs::Manager, origin: Vector3<f32>, direction: Vector3<f32>) {
+        let previous = self.focused.as_ref().map(|(entity, _)| *entity);
+        self.focused = find_focused(ecs, origin, direction).and_then(|entity| {
+            ecs.get_component_from_entity::<Interactable>(entity)
+                .map(|i| (entity, i.read().unwrap().prompt.clone()))
+        });
+
+        if self.triggered {
+            // Consume the press so holding the key doesn't interact every frame
+            self.triggered = false;
+
+            if let Some((entity, _)) = &self.focused {
+                // Interact only with entities that were focused before the press
+                if previous == Some(*entity) {
+                    ecs.send_event(Interacted { entity: *entity });
+                }
+            }
+        }
+    }
+}
+
+/// Find the closest enabled interactable hit by a ray.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `origin` - The origin of the ray.
+/// * `direction` - The direction of the ray.
+///
+/// # Returns
+///
+/// The closest interactable in range, or `None` if nothing is hit.
+pub fn find_focused(
+    ecs: &ecs::Manager,
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+) -> Option<Entity> {
+    let direction = direction.normalize();
+    let mut closest: Option<(Entity, f32)> = None;
+
+    for (entity, interactable) in ecs.get_all_components_of_type::<Interactable>() {
+        let interactable = interactable.read().unwrap();
+        if !interactable.enabled {
+            continue;
+        }
+
+        let Some(pos) = ecs.get_component_from_entity::<Pos3>(entity) else {
+            continue;
+        };
+
+        let to_center = pos.read().unwrap().pos - origin;
+        let t = to_center.dot(direction);
+        if t < 0.0 || t > interactable.range {
+            continue;
+        }
+
+        let distance2 = to_center.magnitude2() - t * t;
+        if distance2 > interactable.radius * interactable.radius {
+            continue;
+        }
+
+        if closest.is_none_or(|(_, closest_t)| t < closest_t) {
+            closest = Some((entity, t));
+        }
+    }
+
+    closest.map(|(entity, _)| entity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add_interactable(ecs: &ecs::Manager, pos: Vector3<f32>, range: f32) -> Entity {
+        let entity = ecs.create_entity();
+        ecs.add_component_to_entity(entity, Pos3::new(pos));
+        ecs.add_component_to_entity(entity, Interactable::new("Open", range));
+        entity
+    }
+
+    #[test]
+    fn test_find_focused_closest() {
+        let manager = ecs::Manager::default();
+        let near = add_interactable(&manager, Vector3::new(0.0, 0.0, -2.0), 5.0);
+        let _far = add_interactable(&manager, Vector3::new(0.0, 0.0, -4.0), 5.0);
+
+        let focused = find_focused(
+            &manager,
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -1.0),
+        );
+        assert_eq!(focused, Some(near));
+    }
+
+    #[test]
+    fn test_find_focused_out_of_range() {
+        let manager = ecs::Manager::default();
+        add_interactable(&manager, Vector3::new(0.0, 0.0, -10.0), 5.0);
+
+        let focused = find_focused(
+            &manager,
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, -1.0),
+        );
+        assert!(focused.is_none());
+    }
+
+    #[test]
+    fn test_interact_sends_event() {
+        let manager = ecs::Manager::default();
+        let entity = add_interactable(&manager, Vector3::new(0.0, 0.0, -2.0), 5.0);
+        let mut controller = InteractionController::new(KeyCode::KeyE);
+        let origin = Vector3::new(0.0, 0.0, 0.0);
+        let direction = Vector3::new(0.0, 0.0, -1.0);
+
+        controller.update(&manager, origin, direction);
+        assert_eq!(controller.prompt(), Some("Open"));
+
+        controller.process_keyboard(KeyCode::KeyE, ElementState::Pressed);
+        controller.update(&manager, origin, direction);
+
+        let events = manager.drain_events::<Interacted>();
+        assert_eq!(events, vec![Interacted { entity }]);
+    }
+}
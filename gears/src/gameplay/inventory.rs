@@ -0,0 +1,285 @@
+use crate::ecs::components::Pos3;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::InnerSpace;
+
+/// A component that describes an item type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    /// The unique identifier of the item type, items with the same id stack together.
+    pub id: String,
+    pub name: String,
+    /// The maximum number of items in a single inventory slot, at least 1.
+    pub max_stack: u32,
+}
+
+impl Component for Item {}
+
+impl Item {
+    /// Create an item type, a `max_stack` of 0 is raised to 1.
+    pub fn new(id: impl Into<String>, name: impl Into<String>, max_stack: u32) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            max_stack: max_stack.max(1),
+        }
+    }
+}
+
+/// A component that makes an `Item` entity collectable by walking into it.
+#[derive(Debug, Copy, Clone)]
+pub struct Pickup {
+    /// The number of items collected at once.
+    pub amount: u32,
+    /// The distance from the item an inventory owner has to be to collect it.
+    pub radius: f32,
+    pub enabled: bool,
+}
+
+impl Component for Pickup {}
+
+impl Pickup {
+    pub fn new(amount: u32, radius: f32) -> Self {
+        Self {
+            amount,
+            radius,
+            enabled: true,
+        }
+    }
+}
+
+/// A stack of items of the same type in an inventory slot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemStack {
+    pub item: Item,
+    pub amount: u32,
+}
+
+/// A component that stores the items collected by an entity.
+#[derive(Debug, Clone, Default)]
+pub struct Inventory {
+    slots: Vec<ItemStack>,
+    capacity: usize,
+}
+
+impl Component for Inventory {}
+
+impl Inventory {
+    /// Create a new inventory.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The maximum number of slots.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Add items to the inventory, filling up existing stacks first.
+    ///
+    /// # Returns
+    ///
+    /// The number of items that did not fit into the inventory.
+    pub fn add(&mut self, item: &Item, amount: u32) -> u32 {
+        let mut remaining = amount;
+
+        for stack in self.slots.iter_mut().filter(|s| s.item.id == item.id) {
+            let added = remaining.min(stack.item.max_stack.saturating_sub(stack.amount));
+            stack.amount += added;
+            remaining -= added;
+        }
+
+        while remaining > 0 && self.slots.len() < self.capacity {
+            let added = remaining.min(item.max_stack.max(1));
+            self.slots.push(ItemStack {
+                item: item.clone(),
+                amount: added,
+            });
+            remaining -= added;
+        }
+
+        remaining
+    }
+
+    /// Remove items of a specific type, emptying the last stacks first.
+    ///
+    /// # Returns
+    ///
+    /// The number of items that were removed.
+    pub fn remove(&mut self, id: &str, amount: u32) -> u32 {
+        let mut removed = 0;
+
+        for stack in self.slots.iter_mut().rev().filter(|s| s.item.id == id) {
+            let taken = (amount - removed).min(stack.amount);
+            stack.amount -= taken;
+            removed += taken;
+
+            if removed == amount {
+                break;
+            }
+        }
+        self.slots.retain(|s| s.amount > 0);
+
+        removed
+    }
+
+    /// Get the total number of items of a specific type.
+    pub fn count(&self, id: &str) -> u32 {
+        self.slots
+            .iter()
+            .filter(|s| s.item.id == id)
+            .map(|s| s.amount)
+            .sum()
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.count(id) > 0
+    }
+
+    pub fn slots(&self) -> &[ItemStack] {
+        &self.slots
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.slots.len() >= self.capacity && self.slots.iter().all(|s| s.amount >= s.item.max_stack)
+    }
+}
+
+/// Sent when an entity collects a `Pickup`.
+/// Use `ecs::Manager::drain_events::<ItemPickedUp>()` to receive it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemPickedUp {
+    /// The entity that owns the inventory.
+    pub collector: Entity,
+    /// The item entity that was collected.
+    pub pickup: Entity,
+    pub item_id: String,
+    pub amount: u32,
+}
+
+/// Collect all enabled pickups that are in range of an inventory owner.
+/// A pickup that only partially fits into the inventory keeps the rest of its items.
+/// Fully collected pickups are disabled, and an `ItemPickedUp` event is sent for each collection.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+pub fn update_pickups(ecs: &ecs::Manager) {
+    let collectors = ecs.get_all_components_of_type::<Inventory>();
+
+    for (pickup_entity, pickup) in ecs.get_all_components_of_type::<Pickup>() {
+        let mut pickup = pickup.write().unwrap();
+        if !pickup.enabled || pickup.amount == 0 {
+            continue;
+        }
+
+        let (Some(item), Some(item_pos)) = (
+            ecs.get_component_from_entity::<Item>(pickup_entity),
+            ecs.get_component_from_entity::<Pos3>(pickup_entity),
+        ) else {
+            continue;
+        };
+        let item = item.read().unwrap();
+        let item_pos = item_pos.read().unwrap().pos;
+
+        for (collector, inventory) in collectors.iter() {
+            let Some(pos) = ecs.get_component_from_entity::<Pos3>(*collector) else {
+                continue;
+            };
+            if (pos.read().unwrap().pos - item_pos).magnitude() > pickup.radius {
+                continue;
+            }
+
+            let leftover = inventory.write().unwrap().add(&item, pickup.amount);
+            let collected = pickup.amount - leftover;
+            if collected == 0 {
+                continue;
+            }
+
+            pickup.amount = leftover;
+            ecs.send_event(ItemPickedUp {
+                collector: *collector,
+                pickup: pickup_entity,
+                item_id: item.id.clone(),
+                amount: collected,
+            });
+
+            if pickup.amount == 0 {
+                pickup.enabled = false;
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector3;
+
+    #[test]
+    fn test_inventory_stacking() {
+        let coin = Item::new("coin", "Coin", 10);
+        let mut inventory = Inventory::new(2);
+
+        assert_eq!(inventory.add(&coin, 15), 0);
+        assert_eq!(inventory.slots().len(), 2);
+        assert_eq!(inventory.count("coin"), 15);
+
+        // Only 5 more fit into the second slot
+        assert_eq!(inventory.add(&coin, 8), 3);
+        assert!(inventory.is_full());
+
+        assert_eq!(inventory.remove("coin", 12), 12);
+        assert_eq!(inventory.count("coin"), 8);
+        assert_eq!(inventory.slots().len(), 1);
+    }
+
+    #[test]
+    fn test_inventory_without_max_stack() {
+        assert_eq!(Item::new("key", "Key", 0).max_stack, 1);
+
+        // An item built without a max stack fills the slots one by one
+        let key = Item {
+            max_stack: 0,
+            ..Item::new("key", "Key", 1)
+        };
+        let mut inventory = Inventory::new(2);
+        assert_eq!(inventory.add(&key, 3), 1);
+        assert_eq!(inventory.add(&key, 1), 1);
+        assert_eq!(inventory.count("key"), 2);
+    }
+
+    #[test]
+    fn test_update_pickups() {
+        let manager = ecs::Manager::default();
+        let player = manager.create_entity();
+        manager.add_component_to_entity(player, Pos3::default());
+        manager.add_component_to_entity(player, Inventory::new(4));
+
+        let potion = manager.create_entity();
+        manager.add_component_to_entity(potion, Pos3::new(Vector3::new(0.5, 0.0, 0.0)));
+        manager.add_component_to_entity(potion, Item::new("potion", "Potion", 5));
+        manager.add_component_to_entity(potion, Pickup::new(2, 1.0));
+
+        update_pickups(&manager);
+
+        let events = manager.drain_events::<ItemPickedUp>();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].amount, 2);
+
+        let inventory = manager
+            .get_component_from_entity::<Inventory>(player)
+            .unwrap();
+        assert_eq!(inventory.read().unwrap().count("potion"), 2);
+
+        let pickup = manager.get_component_from_entity::<Pickup>(potion).unwrap();
+        assert!(!pickup.read().unwrap().enabled);
+    }
+}
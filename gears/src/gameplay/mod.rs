@@ -0,0 +1,13 @@
+pub mod animation;
+pub mod cinematic;
+pub mod dialogue;
+pub mod faction;
+pub mod health;
+#[cfg(feature = "renderer")]
+pub mod interaction;
+pub mod inventory;
+pub mod replay;
+pub mod skinning;
+pub mod spawner;
+pub mod status;
+pub mod teleporter;
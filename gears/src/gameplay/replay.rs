@@ -0,0 +1,341 @@
+use crate::core::Dt;
+use crate::ecs::components::{self, Pos3};
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::{InnerSpace, One, Quaternion, Vector3, VectorSpace};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A single recorded transform.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TransformSample {
+    pub time: f32,
+    pub pos: [f32; 3],
+    pub rot: [f32; 4],
+}
+
+impl TransformSample {
+    fn new(time: f32, pos: &Pos3) -> Self {
+        let rot = pos.rot.unwrap_or(Quaternion::one());
+        Self {
+            time,
+            pos: pos.pos.into(),
+            rot: [rot.v.x, rot.v.y, rot.v.z, rot.s],
+        }
+    }
+
+    fn rot(&self) -> Quaternion<f32> {
+        Quaternion::new(self.rot[3], self.rot[0], self.rot[1], self.rot[2])
+    }
+
+    fn same_transform(&self, other: &Self, epsilon: f32) -> bool {
+        let pos = self.pos.iter().zip(other.pos.iter());
+        let rot = self.rot.iter().zip(other.rot.iter());
+        pos.chain(rot).all(|(a, b)| (a - b).abs() <= epsilon)
+    }
+}
+
+/// A compact track of transforms sampled over time.
+/// Samples that do not change the transform are merged, so an entity at rest only costs two samples.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TransformTrack {
+    samples: Vec<TransformSample>,
+}
+
+impl TransformTrack {
+    /// The smallest change in a transform that is stored as a new sample.
+    const EPSILON: f32 = 1e-4;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a sample to the end of the track.
+    pub fn push(&mut self, time: f32, pos: &Pos3) {
+        let sample = TransformSample::new(time, pos);
+
+        // If the last two samples and the new one are the same, only move the end of the hold
+        if let [.., before, last] = self.samples.as_mut_slice() {
+            if before.same_transform(last, Self::EPSILON)
+                && last.same_transform(&sample, Self::EPSILON)
+            {
+                last.time = time;
+                return;
+            }
+        }
+
+        self.samples.push(sample);
+    }
+
+    pub fn samples(&self) -> &[TransformSample] {
+        &self.samples
+    }
+
+    /// Get the duration of the track in seconds.
+    pub fn duration(&self) -> f32 {
+        self.samples.last().map_or(0.0, |s| s.time)
+    }
+
+    /// Get the interpolated transform at a point in time.
+    ///
+    /// # Returns
+    ///
+    /// The transform, or `None` if the track is empty.
+    pub fn sample(&self, time: f32) -> Option<Pos3> {
+        let first = self.samples.first()?;
+        let index = self.samples.partition_point(|s| s.time <= time);
+
+        let (a, b) = match index {
+            0 => (first, first),
+            i if i >= self.samples.len() => {
+                let last = self.samples.last().unwrap();
+                (last, last)
+            }
+            i => (&self.samples[i - 1], &self.samples[i]),
+        };
+
+        let span = b.time - a.time;
+        let t = if span > 0.0 {
+            ((time - a.time) / span).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        let pos = Vector3::from(a.pos).lerp(Vector3::from(b.pos), t);
+        let rot = a.rot().nlerp(b.rot(), t);
+
+        Some(Pos3::with_rot(pos, rot.normalize()))
+    }
+}
+
+/// A component that records the transforms of selected entities.
+/// Attach it to any entity and call `update_recorders` on each update.
+#[derive(Debug, Clone)]
+pub struct TransformRecorder {
+    /// The time in seconds between two samples.
+    pub interval: f32,
+    pub recording: bool,
+    targets: Vec<Entity>,
+    tracks: HashMap<Entity, TransformTrack>,
+    time: f32,
+    timer: f32,
+}
+
+impl Component for TransformRecorder {}
+
+impl TransformRecorder {
+    pub fn new(targets: Vec<Entity>, interval: f32) -> Self {
+        Self {
+            interval,
+            recording: true,
+            targets,
+            tracks: HashMap::new(),
+            time: 0.0,
+            timer: 0.0,
+        }
+    }
+
+    /// Get the recorded track of an entity.
+    pub fn track(&self, entity: Entity) -> Option<&TransformTrack> {
+        self.tracks.get(&entity)
+    }
+
+    /// Take the recorded track of an entity and remove it from the recorder.
+    pub fn take_track(&mut self, entity: Entity) -> Option<TransformTrack> {
+        self.tracks.remove(&entity)
+    }
+
+    /// Clear all tracks and restart the recording time.
+    pub fn reset(&mut self) {
+        self.tracks.clear();
+        self.time = 0.0;
+        self.timer = 0.0;
+    }
+}
+
+/// A component that replays a recorded track on a visual-only entity.
+#[derive(Debug, Clone)]
+pub struct Ghost {
+    pub track: Arc<TransformTrack>,
+    pub speed: f32,
+    pub looping: bool,
+    pub playing: bool,
+    time: f32,
+}
+
+impl Component for Ghost {}
+
+impl Ghost {
+    pub fn new(track: Arc<TransformTrack>) -> Self {
+        Self {
+            track,
+            speed: 1.0,
+            looping: false,
+            playing: true,
+            time: 0.0,
+        }
+    }
+
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Get the current playback time in seconds.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.clamp(0.0, self.track.duration());
+    }
+}
+
+/// Spawn a ghost that replays a track with the visuals of a source entity.
+/// Only the `Model`, `Scale` and `Flip` components are copied, so the ghost takes no part in gameplay.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `source` - The entity whose visuals are duplicated.
+/// * `ghost` - The ghost playback settings.
+///
+/// # Returns
+///
+/// The ghost entity.
+pub fn spawn_ghost(ecs: &ecs::Manager, source: Entity, ghost: Ghost) -> Entity {
+    let entity = ecs.create_entity();
+    let start = ghost.track.sample(0.0).unwrap_or_default();
+
+    ecs.add_component_to_entity(entity, components::Name("Ghost"));
+    ecs.add_component_to_entity(entity, start);
+    if let Some(model) = ecs.get_component_from_entity::<components::Model>(source) {
+        ecs.add_component_to_entity(entity, *model.read().unwrap());
+    }
+    if let Some(scale) = ecs.get_component_from_entity::<components::Scale>(source) {
+        ecs.add_component_to_entity(entity, *scale.read().unwrap());
+    }
+    if let Some(flip) = ecs.get_component_from_entity::<components::Flip>(source) {
+        ecs.add_component_to_entity(entity, *flip.read().unwrap());
+    }
+    ecs.add_component_to_entity(entity, ghost);
+
+    entity
+}
+
+/// Sample the targets of all active transform recorders.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_recorders(ecs: &ecs::Manager, dt: Dt) {
+    for (_, recorder) in ecs.get_all_components_of_type::<TransformRecorder>() {
+        let mut recorder = recorder.write().unwrap();
+        if !recorder.recording {
+            continue;
+        }
+
+        // Always take the first sample right away
+        let due = recorder.tracks.is_empty() || recorder.timer >= recorder.interval;
+        if due {
+            recorder.timer = 0.0;
+
+            let time = recorder.time;
+            let targets = recorder.targets.clone();
+            for target in targets {
+                if let Some(pos) = ecs.get_component_from_entity::<Pos3>(target) {
+                    let pos = *pos.read().unwrap();
+                    recorder.tracks.entry(target).or_default().push(time, &pos);
+                }
+            }
+        }
+
+        recorder.time += dt.as_secs_f32();
+        recorder.timer += dt.as_secs_f32();
+    }
+}
+
+/// Advance all ghosts and move them along their tracks.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_ghosts(ecs: &ecs::Manager, dt: Dt) {
+    for (entity, ghost) in ecs.get_all_components_of_type::<Ghost>() {
+        let mut ghost = ghost.write().unwrap();
+        if !ghost.playing {
+            continue;
+        }
+
+        let duration = ghost.track.duration();
+        ghost.time += dt.as_secs_f32() * ghost.speed;
+        if ghost.time > duration {
+            if ghost.looping && duration > 0.0 {
+                ghost.time %= duration;
+            } else {
+                ghost.time = duration;
+                ghost.playing = false;
+            }
+        }
+
+        if let (Some(sample), Some(pos)) = (
+            ghost.track.sample(ghost.time),
+            ecs.get_component_from_entity::<Pos3>(entity),
+        ) {
+            *pos.write().unwrap() = sample;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instant::Duration;
+
+    #[test]
+    fn test_track_compacts_and_interpolates() {
+        let mut track = TransformTrack::new();
+        let rest = Pos3::default();
+        for i in 0..10 {
+            track.push(i as f32, &rest);
+        }
+        assert_eq!(track.samples().len(), 2);
+        assert_eq!(track.duration(), 9.0);
+
+        track.push(10.0, &Pos3::new(Vector3::new(10.0, 0.0, 0.0)));
+        let sample = track.sample(9.5).unwrap();
+        assert!((sample.pos.x - 5.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let manager = ecs::Manager::default();
+        let runner = manager.create_entity();
+        manager.add_component_to_entity(runner, Pos3::default());
+
+        let recorder = manager.create_entity();
+        manager.add_component_to_entity(recorder, TransformRecorder::new(vec![runner], 0.1));
+
+        let pos = manager.get_component_from_entity::<Pos3>(runner).unwrap();
+        for _ in 0..10 {
+            update_recorders(&manager, Duration::from_millis(100));
+            pos.write().unwrap().pos.x += 1.0;
+        }
+
+        let track = manager
+            .get_component_from_entity::<TransformRecorder>(recorder)
+            .unwrap()
+            .write()
+            .unwrap()
+            .take_track(runner)
+            .unwrap();
+
+        let ghost = spawn_ghost(&manager, runner, Ghost::new(Arc::new(track)));
+        update_ghosts(&manager, Duration::from_millis(500));
+
+        let ghost_pos = manager.get_component_from_entity::<Pos3>(ghost).unwrap();
+        assert!((ghost_pos.read().unwrap().pos.x - 5.0).abs() < 1e-3);
+    }
+}
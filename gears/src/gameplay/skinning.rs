@@ -0,0 +1,403 @@
+use crate::ecs::traits::Component;
+use crate::ecs::Entity;
+use cgmath::{InnerSpace, Matrix4, One, Quaternion, SquareMatrix, Vector3, VectorSpace};
+use std::sync::Arc;
+
+/// The largest width and height of an animation texture, every GPU supports it.
+pub const MAX_ANIMATION_TEXTURE_SIZE: u32 = 8192;
+/// The texels of a bone matrix in an animation texture, a row of its affine part each.
+pub const TEXELS_PER_BONE: u32 = 3;
+
+/// A bone of a skeleton.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bone {
+    pub name: String,
+    /// The index of the parent bone, it comes before its children.
+    pub parent: Option<usize>,
+    /// The transform from the model space of the bind pose into the space of the bone.
+    pub inverse_bind: Matrix4<f32>,
+}
+
+/// The bones of a skinned mesh, parents before their children.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Skeleton {
+    pub bones: Vec<Bone>,
+}
+
+impl Skeleton {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_bone(
+        mut self,
+        name: impl Into<String>,
+        parent: Option<usize>,
+        inverse_bind: Matrix4<f32>,
+    ) -> Self {
+        self.bones.push(Bone {
+            name: name.into(),
+            parent,
+            inverse_bind,
+        });
+        self
+    }
+
+    pub fn bone_index(&self, name: &str) -> Option<usize> {
+        self.bones.iter().position(|bone| bone.name == name)
+    }
+}
+
+/// The transform of a bone relative to its parent.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BonePose {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: f32,
+}
+
+impl Default for BonePose {
+    fn default() -> Self {
+        Self {
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Quaternion::one(),
+            scale: 1.0,
+        }
+    }
+}
+
+impl BonePose {
+    pub fn new(translation: Vector3<f32>, rotation: Quaternion<f32>) -> Self {
+        Self {
+            translation,
+            rotation,
+            scale: 1.0,
+        }
+    }
+
+    pub fn to_matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation.normalize())
+            * Matrix4::from_scale(self.scale)
+    }
+}
+
+/// The poses of the bones of a skeleton sampled at a fixed rate.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkeletalClip {
+    pub name: String,
+    /// The frames per second.
+    pub frame_rate: f32,
+    /// The local pose of each bone per frame, the missing bones keep their default pose.
+    pub frames: Vec<Vec<BonePose>>,
+}
+
+impl SkeletalClip {
+    pub fn new(name: impl Into<String>, frame_rate: f32) -> Self {
+        Self {
+            name: name.into(),
+            frame_rate,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn with_frame(mut self, poses: Vec<BonePose>) -> Self {
+        self.frames.push(poses);
+        self
+    }
+
+    /// Get the length of the clip in seconds.
+    pub fn duration(&self) -> f32 {
+        self.frames.len() as f32 / self.frame_rate
+    }
+}
+
+/// The rows of a clip in an animation texture.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipRange {
+    pub name: String,
+    pub first_frame: u32,
+    pub frames: u32,
+    pub frame_rate: f32,
+}
+
+/// The skinning matrices of every frame of the clips of a skeleton, baked into the texels of an
+/// `Rgba32Float` texture. A row is a frame and a bone takes `TEXELS_PER_BONE` texels,
+/// so the vertex shader skins the crowd members without the CPU posing each of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationTexture {
+    pub width: u32,
+    pub height: u32,
+    pub bones: u32,
+    /// The texels row by row.
+    pub texels: Vec<[f32; 4]>,
+    pub clips: Vec<ClipRange>,
+}
+
+impl AnimationTexture {
+    /// Bake the clips of a skeleton.
+    ///
+    /// # Returns
+    ///
+    /// An error if a bone comes before its parent, a clip is empty or the texture would be too large.
+    pub fn bake(skeleton: &Skeleton, clips: &[SkeletalClip]) -> anyhow::Result<Self> {
+        for (i, bone) in skeleton.bones.iter().enumerate() {
+            if bone.parent.is_some_and(|parent| parent >= i) {
+                anyhow::bail!("The bone {} comes before its parent", bone.name);
+            }
+        }
+        if let Some(clip) = clips
+            .iter()
+            .find(|clip| clip.frames.is_empty() || clip.frame_rate <= 0.0)
+        {
+            anyhow::bail!("The clip {} has no frames", clip.name);
+        }
+
+        let bones = skeleton.bones.len().max(1) as u32;
+        let width = bones * TEXELS_PER_BONE;
+        let height = clips
+            .iter()
+            .map(|clip| clip.frames.len() as u32)
+            .sum::<u32>();
+        if width > MAX_ANIMATION_TEXTURE_SIZE || height > MAX_ANIMATION_TEXTURE_SIZE {
+            anyhow::bail!(
+                "The animation texture of {} bones and {} frames is larger than {}x{}",
+                bones,
+                height,
+                MAX_ANIMATION_TEXTURE_SIZE,
+                MAX_ANIMATION_TEXTURE_SIZE
+            );
+        }
+
+        let mut texels = Vec::with_capacity((width * height.max(1)) as usize);
+        let mut ranges = Vec::with_capacity(clips.len());
+        let mut global = vec![Matrix4::identity(); skeleton.bones.len()];
+        for clip in clips {
+            ranges.push(ClipRange {
+                name: clip.name.clone(),
+                first_frame: (texels.len() / width as usize) as u32,
+                frames: clip.frames.len() as u32,
+                frame_rate: clip.frame_rate,
+            });
+
+            for poses in &clip.frames {
+                for (i, bone) in skeleton.bones.iter().enumerate() {
+                    let local = poses.get(i).copied().unwrap_or_default().to_matrix();
+                    global[i] = bone.parent.map_or(local, |parent| global[parent] * local);
+                }
+                let skins = skeleton
+                    .bones
+                    .iter()
+                    .zip(&global)
+                    .map(|(bone, global)| global * bone.inverse_bind);
+                // A skeleton without bones gets one identity bone
+                for skin in skins.chain(skeleton.bones.is_empty().then(Matrix4::identity)) {
+                    // The columns are the axes, a texel holds a row of the matrix
+                    for row in 0..TEXELS_PER_BONE as usize {
+                        texels.push([skin.x[row], skin.y[row], skin.z[row], skin.w[row]]);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            bones,
+            texels,
+            clips: ranges,
+        })
+    }
+
+    pub fn clip_index(&self, name: &str) -> Option<usize> {
+        self.clips.iter().position(|clip| clip.name == name)
+    }
+
+    /// Get a skinning matrix, like the vertex shader reads it.
+    pub fn bone_matrix(&self, frame: u32, bone: u32) -> Option<Matrix4<f32>> {
+        if frame >= self.height || bone >= self.bones {
+            return None;
+        }
+        let start = (frame * self.width + bone * TEXELS_PER_BONE) as usize;
+        let rows = &self.texels[start..start + TEXELS_PER_BONE as usize];
+        Some(Matrix4::new(
+            rows[0][0], rows[1][0], rows[2][0], 0.0, //
+            rows[0][1], rows[1][1], rows[2][1], 0.0, //
+            rows[0][2], rows[1][2], rows[2][2], 0.0, //
+            rows[0][3], rows[1][3], rows[2][3], 1.0,
+        ))
+    }
+}
+
+/// A vertex of a skinned mesh, weighted to up to four bones.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "renderer", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct SkinnedVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub normal: [f32; 3],
+    pub joints: [u32; 4],
+    /// The weights of the joints, they should sum to 1.
+    pub weights: [f32; 4],
+}
+
+/// The geometry of the crowd members.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SkinnedMesh {
+    pub vertices: Vec<SkinnedVertex>,
+    pub indices: Vec<u32>,
+}
+
+/// A component drawing the entities with a `CrowdAnimation` pointing to it as instances
+/// of a single skinned mesh, animated on the GPU with the baked clips.
+#[derive(Debug, Clone)]
+pub struct SkinnedCrowd {
+    pub mesh: Arc<SkinnedMesh>,
+    pub animations: Arc<AnimationTexture>,
+    /// The path of the diffuse texture, the mesh is white without one.
+    pub texture: Option<String>,
+}
+
+impl Component for SkinnedCrowd {}
+
+impl SkinnedCrowd {
+    pub fn new(mesh: SkinnedMesh, animations: AnimationTexture) -> Self {
+        Self {
+            mesh: Arc::new(mesh),
+            animations: Arc::new(animations),
+            texture: None,
+        }
+    }
+
+    pub fn with_texture(mut self, texture: impl Into<String>) -> Self {
+        self.texture = Some(texture.into());
+        self
+    }
+}
+
+/// A component making an entity with a `Pos3` a member of a `SkinnedCrowd`.
+/// The clips loop, the time offset keeps the members sharing a clip out of step.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CrowdAnimation {
+    /// The entity with the `SkinnedCrowd`.
+    pub crowd: Entity,
+    /// The index of the clip in the animation texture.
+    pub clip: usize,
+    /// The seconds the member is ahead of the others in its clip.
+    pub time_offset: f32,
+    /// The playback speed.
+    pub speed: f32,
+}
+
+impl Component for CrowdAnimation {}
+
+impl CrowdAnimation {
+    pub fn new(crowd: Entity, clip: usize) -> Self {
+        Self {
+            crowd,
+            clip,
+            time_offset: 0.0,
+            speed: 1.0,
+        }
+    }
+
+    pub fn with_time_offset(mut self, time_offset: f32) -> Self {
+        self.time_offset = time_offset;
+        self
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Get the frames the member blends between at a time of the crowd, like the vertex shader.
+    ///
+    /// # Returns
+    ///
+    /// The rows of the two frames in the animation texture and the blend factor,
+    /// `None` if the clip does not exist.
+    pub fn frames(&self, animations: &AnimationTexture, time: f32) -> Option<(u32, u32, f32)> {
+        let clip = animations.clips.get(self.clip)?;
+        let position = ((time * self.speed + self.time_offset) * clip.frame_rate)
+            .rem_euclid(clip.frames as f32);
+        let frame = (position as u32).min(clip.frames - 1);
+        Some((
+            clip.first_frame + frame,
+            clip.first_frame + (frame + 1) % clip.frames,
+            position - frame as f32,
+        ))
+    }
+
+    /// Get the skinning matrix of a bone of the member at a time of the crowd.
+    pub fn bone_matrix(
+        &self,
+        animations: &AnimationTexture,
+        time: f32,
+        bone: u32,
+    ) -> Option<Matrix4<f32>> {
+        let (a, b, t) = self.frames(animations, time)?;
+        let a = animations.bone_matrix(a, bone)?;
+        let b = animations.bone_matrix(b, bone)?;
+        Some(Matrix4::from_cols(
+            a.x.lerp(b.x, t),
+            a.y.lerp(b.y, t),
+            a.z.lerp(b.z, t),
+            a.w.lerp(b.w, t),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Rotation3, Transform};
+
+    #[test]
+    fn test_bake_and_sample() {
+        // An arm bent at the elbow, the elbow is a unit above the shoulder
+        let skeleton = Skeleton::new()
+            .with_bone("shoulder", None, Matrix4::identity())
+            .with_bone(
+                "elbow",
+                Some(0),
+                Matrix4::from_translation(Vector3::new(0.0, -1.0, 0.0)),
+            );
+        let elbow = BonePose::new(Vector3::unit_y(), Quaternion::one());
+        let bent = BonePose::new(Vector3::unit_y(), Quaternion::from_angle_z(Deg(90.0)));
+        let idle = SkeletalClip::new("idle", 10.0).with_frame(vec![BonePose::default(), elbow]);
+        let wave = SkeletalClip::new("wave", 2.0)
+            .with_frame(vec![BonePose::default(), elbow])
+            .with_frame(vec![BonePose::default(), bent]);
+
+        let animations = AnimationTexture::bake(&skeleton, &[idle, wave.clone()]).unwrap();
+        assert_eq!((animations.width, animations.height), (6, 3));
+        assert_eq!(animations.clips[1].first_frame, 1);
+        assert_eq!(animations.clip_index("wave"), Some(1));
+
+        // The bind pose does not move the vertices, the bent elbow rotates them around it
+        let hand = cgmath::Point3::new(0.0, 2.0, 0.0);
+        let matrix = animations.bone_matrix(1, 1).unwrap();
+        assert!((matrix.transform_point(hand) - hand).magnitude() < 1e-5);
+        let matrix = animations.bone_matrix(2, 1).unwrap();
+        let bent_hand = matrix.transform_point(hand);
+        assert!((bent_hand - cgmath::Point3::new(-1.0, 1.0, 0.0)).magnitude() < 1e-5);
+
+        // The offset member is half a frame ahead and blends between the frames
+        let member = CrowdAnimation::new(Entity(0), 1).with_time_offset(0.25);
+        assert_eq!(member.frames(&animations, 0.0), Some((1, 2, 0.5)));
+        assert_eq!(member.frames(&animations, 0.5), Some((2, 1, 0.5)));
+        let halfway = member.bone_matrix(&animations, 0.0, 1).unwrap();
+        assert!(
+            (halfway.transform_point(hand) - cgmath::Point3::new(-0.5, 1.5, 0.0)).magnitude()
+                < 1e-5
+        );
+        assert!(CrowdAnimation::new(Entity(0), 2)
+            .frames(&animations, 0.0)
+            .is_none());
+
+        let unordered = Skeleton::new().with_bone("child", Some(0), Matrix4::identity());
+        assert!(AnimationTexture::bake(&unordered, &[wave]).is_err());
+    }
+}
@@ -0,0 +1,328 @@
+use super::health::Health;
+use crate::core::Dt;
+use crate::ecs::components::Pos3;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::Vector3;
+use rand::Rng;
+use std::fmt;
+use std::sync::Arc;
+
+/// A function that spawns a new entity at the given position and returns it.
+pub type Prefab = Arc<dyn Fn(&ecs::Manager, Vector3<f32>) -> Entity + Send + Sync>;
+
+/// A component that marks a location where a `WaveSpawner` of the same group can spawn entities.
+/// The location is taken from the `Pos3` of the same entity.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SpawnPoint {
+    pub group: u32,
+}
+
+impl Component for SpawnPoint {}
+
+/// A single wave of a `WaveSpawner`.
+#[derive(Clone)]
+pub struct Wave {
+    pub prefab: Prefab,
+    /// The number of entities spawned in the wave.
+    pub count: usize,
+    /// The time in seconds between two spawns.
+    pub interval: f32,
+    /// The time in seconds to wait before the wave starts.
+    pub delay: f32,
+}
+
+impl fmt::Debug for Wave {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Wave")
+            .field("count", &self.count)
+            .field("interval", &self.interval)
+            .field("delay", &self.delay)
+            .finish()
+    }
+}
+
+impl Wave {
+    pub fn new(prefab: Prefab, count: usize, interval: f32) -> Self {
+        Self {
+            prefab,
+            count,
+            interval,
+            delay: 0.0,
+        }
+    }
+
+    pub fn with_delay(mut self, delay: f32) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum SpawnerState {
+    Waiting,
+    Spawning,
+    Clearing,
+    Finished,
+}
+
+/// A component that spawns timed waves of prefabs at the `SpawnPoint`s of its group.
+/// If the group has no spawn points, the `Pos3` of the spawner entity is used.
+/// A wave is completed when all of its entities have been spawned and killed.
+#[derive(Debug, Clone)]
+pub struct WaveSpawner {
+    pub waves: Vec<Wave>,
+    pub group: u32,
+    /// The radius of the random offset applied to the spawn positions on the XZ plane.
+    pub spawn_radius: f32,
+    /// The maximum number of entities spawned by the spawner that can be alive at once.
+    pub max_alive: usize,
+    current_wave: usize,
+    spawned: usize,
+    timer: f32,
+    next_point: usize,
+    alive: Vec<Entity>,
+    state: SpawnerState,
+}
+
+impl Component for WaveSpawner {}
+
+impl WaveSpawner {
+    pub fn new(waves: Vec<Wave>) -> Self {
+        Self {
+            waves,
+            group: 0,
+            spawn_radius: 0.0,
+            max_alive: usize::MAX,
+            current_wave: 0,
+            spawned: 0,
+            timer: 0.0,
+            next_point: 0,
+            alive: Vec::new(),
+            state: SpawnerState::Waiting,
+        }
+    }
+
+    pub fn with_group(mut self, group: u32) -> Self {
+        self.group = group;
+        self
+    }
+
+    pub fn with_spawn_radius(mut self, spawn_radius: f32) -> Self {
+        self.spawn_radius = spawn_radius;
+        self
+    }
+
+    pub fn with_max_alive(mut self, max_alive: usize) -> Self {
+        self.max_alive = max_alive;
+        self
+    }
+
+    /// Get the index of the current wave.
+    pub fn current_wave(&self) -> usize {
+        self.current_wave
+    }
+
+    /// Get the entities spawned by the spawner that are still alive.
+    pub fn alive(&self) -> &[Entity] {
+        &self.alive
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state == SpawnerState::Finished
+    }
+}
+
+/// Sent when a wave starts spawning.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WaveStarted {
+    pub spawner: Entity,
+    pub wave: usize,
+}
+
+/// Sent when all entities of a wave have been spawned and killed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WaveCompleted {
+    pub spawner: Entity,
+    pub wave: usize,
+}
+
+/// Sent when the last wave of a spawner has been completed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SpawnerFinished {
+    pub spawner: Entity,
+}
+
+fn is_alive(ecs: &ecs::Manager, entity: Entity) -> bool {
+    ecs.get_component_from_entity::<Health>(entity)
+        .is_none_or(|h| !h.read().unwrap().is_dead())
+}
+
+fn spawn_position(
+    ecs: &ecs::Manager,
+    spawner_entity: Entity,
+    spawner: &mut WaveSpawner,
+) -> Vector3<f32> {
+    let mut points: Vec<Vector3<f32>> = ecs
+        .get_all_components_of_type::<SpawnPoint>()
+        .into_iter()
+        .filter(|(_, point)| point.read().unwrap().group == spawner.group)
+        .filter_map(|(entity, _)| {
+            let pos = ecs.get_component_from_entity::<Pos3>(entity)?;
+            let pos = pos.read().unwrap().pos;
+            Some(pos)
+        })
+        .collect();
+
+    if points.is_empty() {
+        if let Some(pos) = ecs.get_component_from_entity::<Pos3>(spawner_entity) {
+            points.push(pos.read().unwrap().pos);
+        } else {
+            points.push(Vector3::new(0.0, 0.0, 0.0));
+        }
+    }
+
+    // Cycle through the spawn points so the spawns are spread out
+    let mut pos = points[spawner.next_point % points.len()];
+    spawner.next_point = spawner.next_point.wrapping_add(1);
+
+    if spawner.spawn_radius > 0.0 {
+        let mut rng = rand::thread_rng();
+        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+        let distance = spawner.spawn_radius * rng.gen::<f32>().sqrt();
+        pos.x += angle.cos() * distance;
+        pos.z += angle.sin() * distance;
+    }
+
+    pos
+}
+
+/// Advance all wave spawners and spawn the entities that are due.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_wave_spawners(ecs: &ecs::Manager, dt: Dt) {
+    let dt = dt.as_secs_f32();
+
+    for (spawner_entity, spawner) in ecs.get_all_components_of_type::<WaveSpawner>() {
+        let mut spawner = spawner.write().unwrap();
+        spawner.alive.retain(|e| is_alive(ecs, *e));
+
+        if spawner.current_wave >= spawner.waves.len() {
+            if spawner.state != SpawnerState::Finished {
+                spawner.state = SpawnerState::Finished;
+                ecs.send_event(SpawnerFinished {
+                    spawner: spawner_entity,
+                });
+            }
+            continue;
+        }
+
+        spawner.timer += dt;
+        let wave = spawner.waves[spawner.current_wave].clone();
+
+        match spawner.state {
+            SpawnerState::Waiting => {
+                if spawner.timer >= wave.delay {
+                    spawner.state = SpawnerState::Spawning;
+                    // Spawn the first entity of the wave right away
+                    spawner.timer = wave.interval;
+                    ecs.send_event(WaveStarted {
+                        spawner: spawner_entity,
+                        wave: spawner.current_wave,
+                    });
+                }
+            }
+            SpawnerState::Spawning => {
+                while spawner.timer >= wave.interval
+                    && spawner.spawned < wave.count
+                    && spawner.alive.len() < spawner.max_alive
+                {
+                    spawner.timer -= wave.interval;
+                    let pos = spawn_position(ecs, spawner_entity, &mut spawner);
+                    let entity = (wave.prefab)(ecs, pos);
+                    spawner.alive.push(entity);
+                    spawner.spawned += 1;
+                }
+
+                if spawner.spawned >= wave.count {
+                    spawner.state = SpawnerState::Clearing;
+                } else if spawner.alive.len() >= spawner.max_alive {
+                    // Do not accumulate time while the spawner is capped
+                    spawner.timer = spawner.timer.min(wave.interval);
+                }
+            }
+            SpawnerState::Clearing => {
+                if spawner.alive.is_empty() {
+                    ecs.send_event(WaveCompleted {
+                        spawner: spawner_entity,
+                        wave: spawner.current_wave,
+                    });
+                    spawner.current_wave += 1;
+                    spawner.spawned = 0;
+                    spawner.timer = 0.0;
+                    spawner.state = SpawnerState::Waiting;
+                }
+            }
+            SpawnerState::Finished => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instant::Duration;
+
+    fn enemy_prefab() -> Prefab {
+        Arc::new(|ecs: &ecs::Manager, pos: Vector3<f32>| {
+            let entity = ecs.create_entity();
+            ecs.add_component_to_entity(entity, Pos3::new(pos));
+            ecs.add_component_to_entity(entity, Health::new(10.0));
+            entity
+        })
+    }
+
+    #[test]
+    fn test_waves_spawn_and_complete() {
+        let manager = ecs::Manager::default();
+        let point = manager.create_entity();
+        manager.add_component_to_entity(point, Pos3::new(Vector3::new(5.0, 0.0, 0.0)));
+        manager.add_component_to_entity(point, SpawnPoint::default());
+
+        let spawner_entity = manager.create_entity();
+        manager.add_component_to_entity(
+            spawner_entity,
+            WaveSpawner::new(vec![Wave::new(enemy_prefab(), 3, 1.0)]).with_max_alive(2),
+        );
+
+        for _ in 0..5 {
+            update_wave_spawners(&manager, Duration::from_secs(1));
+        }
+
+        let spawner = manager
+            .get_component_from_entity::<WaveSpawner>(spawner_entity)
+            .unwrap();
+        let alive = spawner.read().unwrap().alive().to_vec();
+        assert_eq!(alive.len(), 2);
+        assert_eq!(manager.drain_events::<WaveStarted>().len(), 1);
+
+        // Kill the spawned entities until the wave is completed
+        for _ in 0..3 {
+            let alive = spawner.read().unwrap().alive().to_vec();
+            for entity in alive {
+                let health = manager.get_component_from_entity::<Health>(entity).unwrap();
+                health.write().unwrap().damage(10.0);
+            }
+            update_wave_spawners(&manager, Duration::from_secs(1));
+            update_wave_spawners(&manager, Duration::from_secs(1));
+        }
+
+        assert_eq!(manager.drain_events::<WaveCompleted>().len(), 1);
+        update_wave_spawners(&manager, Duration::from_secs(1));
+        assert_eq!(manager.drain_events::<SpawnerFinished>().len(), 1);
+        assert!(spawner.read().unwrap().is_finished());
+    }
+}
@@ -0,0 +1,308 @@
+use super::health::{self, Health};
+use crate::core::Dt;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use std::fmt;
+use std::sync::Arc;
+
+/// A callback that is run on each tick of a status effect.
+pub type TickCallback = Arc<dyn Fn(&ecs::Manager, Entity) + Send + Sync>;
+
+/// The behavior of a status effect.
+#[derive(Clone)]
+pub enum StatusKind {
+    /// Deals damage on each tick.
+    DamageOverTime { damage_per_tick: f32 },
+    /// Restores health on each tick.
+    HealOverTime { heal_per_tick: f32 },
+    /// Multiplies the movement speed while active, see `StatusEffects::speed_multiplier`.
+    Slow { factor: f32 },
+    /// Absorbs incoming damage until depleted.
+    Shield { remaining: f32 },
+    /// Runs a custom callback on each tick.
+    Custom(TickCallback),
+}
+
+impl fmt::Debug for StatusKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatusKind::DamageOverTime { damage_per_tick } => f
+                .debug_struct("DamageOverTime")
+                .field("damage_per_tick", damage_per_tick)
+                .finish(),
+            StatusKind::HealOverTime { heal_per_tick } => f
+                .debug_struct("HealOverTime")
+                .field("heal_per_tick", heal_per_tick)
+                .finish(),
+            StatusKind::Slow { factor } => f.debug_struct("Slow").field("factor", factor).finish(),
+            StatusKind::Shield { remaining } => f
+                .debug_struct("Shield")
+                .field("remaining", remaining)
+                .finish(),
+            StatusKind::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+/// A single timed effect applied to an entity.
+#[derive(Debug, Clone)]
+pub struct StatusEffect {
+    /// The name of the effect, applying an effect with the same name refreshes it.
+    pub name: String,
+    pub kind: StatusKind,
+    /// The duration in seconds, `None` if the effect never expires.
+    pub duration: Option<f32>,
+    /// The time in seconds between two ticks, kept above 0 by `with_tick_interval`.
+    tick_interval: f32,
+    elapsed: f32,
+    tick_timer: f32,
+}
+
+impl StatusEffect {
+    pub fn new(name: impl Into<String>, kind: StatusKind, duration: Option<f32>) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            duration,
+            tick_interval: 1.0,
+            elapsed: 0.0,
+            tick_timer: 0.0,
+        }
+    }
+
+    pub fn with_tick_interval(mut self, tick_interval: f32) -> Self {
+        assert!(tick_interval > 0.0);
+        self.tick_interval = tick_interval;
+        self
+    }
+
+    /// Get the time in seconds between two ticks.
+    pub fn tick_interval(&self) -> f32 {
+        self.tick_interval
+    }
+
+    /// Get the remaining time in seconds, `None` if the effect never expires.
+    pub fn remaining(&self) -> Option<f32> {
+        self.duration.map(|d| (d - self.elapsed).max(0.0))
+    }
+
+    fn is_expired(&self) -> bool {
+        match self.kind {
+            StatusKind::Shield { remaining } if remaining <= 0.0 => true,
+            _ => self.remaining().is_some_and(|r| r <= 0.0),
+        }
+    }
+}
+
+/// A component that stores the status effects active on an entity.
+#[derive(Debug, Clone, Default)]
+pub struct StatusEffects {
+    effects: Vec<StatusEffect>,
+    applied: Vec<String>,
+}
+
+impl Component for StatusEffects {}
+
+impl StatusEffects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply an effect. If an effect with the same name is active it is replaced.
+    pub fn apply(&mut self, effect: StatusEffect) {
+        self.applied.push(effect.name.clone());
+
+        if let Some(existing) = self.effects.iter_mut().find(|e| e.name == effect.name) {
+            *existing = effect;
+        } else {
+            self.effects.push(effect);
+        }
+    }
+
+    /// Remove an effect by name.
+    ///
+    /// # Returns
+    ///
+    /// The removed effect, if it was active.
+    pub fn remove(&mut self, name: &str) -> Option<StatusEffect> {
+        let index = self.effects.iter().position(|e| e.name == name)?;
+        Some(self.effects.remove(index))
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.effects.iter().any(|e| e.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &StatusEffect> {
+        self.effects.iter()
+    }
+
+    /// Get the combined speed multiplier of all active slow effects.
+    pub fn speed_multiplier(&self) -> f32 {
+        self.effects
+            .iter()
+            .filter_map(|e| match e.kind {
+                StatusKind::Slow { factor } => Some(factor),
+                _ => None,
+            })
+            .product()
+    }
+
+    /// Absorb damage with the active shields.
+    ///
+    /// # Returns
+    ///
+    /// The damage that was not absorbed.
+    pub fn absorb(&mut self, damage: f32) -> f32 {
+        let mut damage = damage;
+
+        for effect in self.effects.iter_mut() {
+            if let StatusKind::Shield { remaining } = &mut effect.kind {
+                let absorbed = damage.min(*remaining);
+                *remaining -= absorbed;
+                damage -= absorbed;
+            }
+        }
+
+        damage
+    }
+}
+
+/// Sent when a status effect is applied to an entity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusApplied {
+    pub entity: Entity,
+    pub name: String,
+}
+
+/// Sent when a status effect expires on an entity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusExpired {
+    pub entity: Entity,
+    pub name: String,
+}
+
+/// Advance all status effects, run their ticks and remove the expired ones.
+/// Damage and healing ticks are applied to the `Health` of the entity.
+/// `StatusApplied` and `StatusExpired` events are sent for the changes since the last update.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_status_effects(ecs: &ecs::Manager, dt: Dt) {
+    let dt = dt.as_secs_f32();
+
+    for (entity, effects) in ecs.get_all_components_of_type::<StatusEffects>() {
+        let mut ticks: Vec<StatusKind> = Vec::new();
+        let mut expired: Vec<String> = Vec::new();
+
+        // Collect the ticks first, so the lock is not held while they are applied
+        {
+            let mut effects = effects.write().unwrap();
+
+            for name in effects.applied.drain(..) {
+                ecs.send_event(StatusApplied { entity, name });
+            }
+
+            for effect in effects.effects.iter_mut() {
+                let elapsed = match effect.duration {
+                    Some(duration) => dt.min((duration - effect.elapsed).max(0.0)),
+                    None => dt,
+                };
+                effect.elapsed += dt;
+                effect.tick_timer += elapsed;
+
+                while effect.tick_timer >= effect.tick_interval {
+                    effect.tick_timer -= effect.tick_interval;
+                    ticks.push(effect.kind.clone());
+                }
+            }
+
+            effects.effects.retain(|e| {
+                if e.is_expired() {
+                    expired.push(e.name.clone());
+                }
+                !e.is_expired()
+            });
+        }
+
+        for tick in ticks {
+            match tick {
+                StatusKind::DamageOverTime { damage_per_tick } => {
+                    health::apply_damage(ecs, entity, damage_per_tick);
+                }
+                StatusKind::HealOverTime { heal_per_tick } => {
+                    if let Some(health) = ecs.get_component_from_entity::<Health>(entity) {
+                        health.write().unwrap().heal(heal_per_tick);
+                    }
+                }
+                StatusKind::Custom(callback) => callback(ecs, entity),
+                StatusKind::Slow { .. } | StatusKind::Shield { .. } => {}
+            }
+        }
+
+        for name in expired {
+            ecs.send_event(StatusExpired { entity, name });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instant::Duration;
+
+    #[test]
+    fn test_damage_over_time() {
+        let manager = ecs::Manager::default();
+        let entity = manager.create_entity();
+        manager.add_component_to_entity(entity, Health::new(100.0));
+
+        let mut effects = StatusEffects::new();
+        effects.apply(StatusEffect::new(
+            "burning",
+            StatusKind::DamageOverTime {
+                damage_per_tick: 5.0,
+            },
+            Some(3.0),
+        ));
+        manager.add_component_to_entity(entity, effects);
+
+        for _ in 0..4 {
+            update_status_effects(&manager, Duration::from_secs(1));
+        }
+
+        let health = manager.get_component_from_entity::<Health>(entity).unwrap();
+        assert_eq!(health.read().unwrap().current, 85.0);
+
+        let applied = manager.drain_events::<StatusApplied>();
+        assert_eq!(applied.len(), 1);
+        let expired = manager.drain_events::<StatusExpired>();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].name, "burning");
+    }
+
+    #[test]
+    fn test_shield_absorbs_damage() {
+        let manager = ecs::Manager::default();
+        let entity = manager.create_entity();
+        manager.add_component_to_entity(entity, Health::new(100.0));
+
+        let mut effects = StatusEffects::new();
+        effects.apply(StatusEffect::new(
+            "shield",
+            StatusKind::Shield { remaining: 30.0 },
+            None,
+        ));
+        manager.add_component_to_entity(entity, effects);
+
+        assert_eq!(health::apply_damage(&manager, entity, 50.0), 20.0);
+
+        update_status_effects(&manager, Duration::from_millis(16));
+        let effects = manager
+            .get_component_from_entity::<StatusEffects>(entity)
+            .unwrap();
+        assert!(!effects.read().unwrap().has("shield"));
+    }
+}
@@ -0,0 +1,352 @@
+use crate::core::Dt;
+use crate::ecs::components::{Pos3, Velocity};
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::{InnerSpace, One, Quaternion, Vector3};
+
+/// A component that turns an entity into a teleporter entrance.
+/// Any `Teleportable` entity entering the trigger volume is moved to the linked exit.
+#[derive(Debug, Copy, Clone)]
+pub struct Teleporter {
+    /// The entity holding the matching `TeleporterExit` component.
+    pub exit: Entity,
+    /// Half extents of the trigger volume in the local frame of the teleporter.
+    pub half_extents: Vector3<f32>,
+    pub enabled: bool,
+}
+
+impl Component for Teleporter {}
+
+impl Teleporter {
+    pub fn new(exit: Entity, half_extents: Vector3<f32>) -> Self {
+        Self {
+            exit,
+            half_extents,
+            enabled: true,
+        }
+    }
+
+    /// Check if a world space point is inside the trigger volume.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The position and rotation of the teleporter.
+    /// * `point` - The point to check.
+    pub fn contains(&self, frame: &Pos3, point: Vector3<f32>) -> bool {
+        let inv_rot = frame.rot.unwrap_or(Quaternion::one()).conjugate();
+        let local = inv_rot * (point - frame.pos);
+
+        local.x.abs() <= self.half_extents.x
+            && local.y.abs() <= self.half_extents.y
+            && local.z.abs() <= self.half_extents.z
+    }
+}
+
+/// A component that marks the exit side of a teleporter pair.
+/// The exit frame is taken from the `Pos3` of the same entity.
+#[derive(Debug, Copy, Clone)]
+pub struct TeleporterExit {
+    /// Offset applied in the exit frame after the transform handoff.
+    pub offset: Vector3<f32>,
+}
+
+impl Component for TeleporterExit {}
+
+impl Default for TeleporterExit {
+    fn default() -> Self {
+        Self {
+            offset: Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// A component that shows the view through a teleporter on its model, add it next to the `Teleporter`.
+/// Each frame the renderer draws the scene into a texture as the main camera would see it after going
+/// through, and draws every mesh of the model of the teleporter with that texture.
+/// The meshes are lit like the other models and the portals are not seen in each other.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PortalView {
+    /// The size of the texture in pixels.
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Component for PortalView {}
+
+impl Default for PortalView {
+    fn default() -> Self {
+        Self::new(512, 512)
+    }
+}
+
+impl PortalView {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width: width.max(1),
+            height: height.max(1),
+        }
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+}
+
+/// A component that allows an entity to use teleporters.
+#[derive(Debug, Copy, Clone)]
+pub struct Teleportable {
+    /// The time in seconds before the entity can be teleported again.
+    pub cooldown: f32,
+    remaining: f32,
+}
+
+impl Component for Teleportable {}
+
+impl Default for Teleportable {
+    fn default() -> Self {
+        Self::new(0.5)
+    }
+}
+
+impl Teleportable {
+    pub fn new(cooldown: f32) -> Self {
+        Self {
+            cooldown,
+            remaining: 0.0,
+        }
+    }
+
+    /// Check if the entity is still cooling down after a teleport.
+    pub fn is_cooling_down(&self) -> bool {
+        self.remaining > 0.0
+    }
+}
+
+/// Emitted when an entity has been moved through a teleporter.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TeleportEvent {
+    pub entity: Entity,
+    pub teleporter: Entity,
+    pub exit: Entity,
+}
+
+/// Map a transform from the entrance frame into the exit frame.
+/// The relative offset, rotation and velocity of the entity are preserved,
+/// so the handoff is seamless from the point of view of the entity.
+///
+/// # Arguments
+///
+/// * `entrance` - The frame of the teleporter entrance.
+/// * `exit` - The frame of the teleporter exit.
+/// * `pos` - The transform of the entity.
+/// * `velocity` - The velocity of the entity, if any.
+///
+/// # Returns
+///
+/// The transform and the velocity in the exit frame.
+pub fn handoff(
+    entrance: &Pos3,
+    exit: &Pos3,
+    pos: &Pos3,
+    velocity: Option<Vector3<f32>>,
+) -> (Pos3, Option<Vector3<f32>>) {
+    let entrance_rot = entrance.rot.unwrap_or(Quaternion::one());
+    let exit_rot = exit.rot.unwrap_or(Quaternion::one());
+    let delta = (exit_rot * entrance_rot.conjugate()).normalize();
+
+    let new_pos = exit.pos + delta * (pos.pos - entrance.pos);
+    let new_rot = pos.rot.map(|rot| (delta * rot).normalize());
+
+    (
+        Pos3 {
+            pos: new_pos,
+            rot: new_rot,
+        },
+        velocity.map(|v| delta * v),
+    )
+}
+
+/// Get where a camera would be after going through a teleporter, the view of a `PortalView`.
+///
+/// # Arguments
+///
+/// * `entrance` - The frame of the teleporter entrance.
+/// * `exit` - The frame of the teleporter exit.
+/// * `exit_offset` - The `TeleporterExit::offset` of the exit.
+/// * `eye` - The position of the camera.
+/// * `forward` - The direction the camera looks at.
+///
+/// # Returns
+///
+/// The position and the direction in the exit frame.
+pub fn view_through(
+    entrance: &Pos3,
+    exit: &Pos3,
+    exit_offset: Vector3<f32>,
+    eye: Vector3<f32>,
+    forward: Vector3<f32>,
+) -> (Vector3<f32>, Vector3<f32>) {
+    let (pos, forward) = handoff(entrance, exit, &Pos3::new(eye), Some(forward));
+    let eye = pos.pos + exit.rot.unwrap_or(Quaternion::one()) * exit_offset;
+    (eye, forward.unwrap_or(Vector3::unit_z()))
+}
+
+/// Move all teleportable entities that are inside an enabled teleporter to its exit.
+/// The default schedule runs it on each update and sends the teleports as events.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+///
+/// # Returns
+///
+/// The teleports that happened during this update.
+pub fn update_teleporters(ecs: &ecs::Manager, dt: Dt) -> Vec<TeleportEvent> {
+    let mut events = Vec::new();
+    let teleporters = ecs.get_all_components_of_type::<Teleporter>();
+    let dt = dt.as_secs_f32();
+
+    for (entity, teleportable) in ecs.get_all_components_of_type::<Teleportable>() {
+        let mut teleportable = teleportable.write().unwrap();
+        if teleportable.is_cooling_down() {
+            teleportable.remaining = (teleportable.remaining - dt).max(0.0);
+            continue;
+        }
+
+        let Some(pos) = ecs.get_component_from_entity::<Pos3>(entity) else {
+            continue;
+        };
+
+        for (teleporter_entity, teleporter) in teleporters.iter() {
+            let teleporter = teleporter.read().unwrap();
+            if !teleporter.enabled || *teleporter_entity == entity {
+                continue;
+            }
+
+            let (Some(entrance), Some(exit_pos), Some(exit)) = (
+                ecs.get_component_from_entity::<Pos3>(*teleporter_entity),
+                ecs.get_component_from_entity::<Pos3>(teleporter.exit),
+                ecs.get_component_from_entity::<TeleporterExit>(teleporter.exit),
+            ) else {
+                log::warn!(
+                    "Teleporter {:?} has no valid exit, skipping...",
+                    teleporter_entity
+                );
+                continue;
+            };
+
+            let entrance = *entrance.read().unwrap();
+            if !teleporter.contains(&entrance, pos.read().unwrap().pos) {
+                continue;
+            }
+
+            let exit_frame = *exit_pos.read().unwrap();
+            let velocity = ecs.get_component_from_entity::<Velocity>(entity);
+            let (mut new_pos, new_velocity) = handoff(
+                &entrance,
+                &exit_frame,
+                &pos.read().unwrap(),
+                velocity.as_ref().map(|v| v.read().unwrap().0),
+            );
+            new_pos.pos +=
+                exit_frame.rot.unwrap_or(Quaternion::one()) * exit.read().unwrap().offset;
+
+            *pos.write().unwrap() = new_pos;
+            if let (Some(velocity), Some(new_velocity)) = (velocity, new_velocity) {
+                velocity.write().unwrap().0 = new_velocity;
+            }
+
+            teleportable.remaining = teleportable.cooldown;
+            events.push(TeleportEvent {
+                entity,
+                teleporter: *teleporter_entity,
+                exit: teleporter.exit,
+            });
+            break;
+        }
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Rad, Rotation3};
+    use instant::Duration;
+
+    fn approx_eq(a: Vector3<f32>, b: Vector3<f32>) -> bool {
+        (a - b).magnitude() < 1e-4
+    }
+
+    #[test]
+    fn test_handoff_rotates_into_exit_frame() {
+        let entrance = Pos3::new(Vector3::new(0.0, 0.0, 0.0));
+        let exit = Pos3::with_rot(
+            Vector3::new(10.0, 0.0, 0.0),
+            Quaternion::from_angle_y(Rad(std::f32::consts::FRAC_PI_2)),
+        );
+        let pos = Pos3::new(Vector3::new(0.0, 0.0, 1.0));
+
+        let (new_pos, velocity) =
+            handoff(&entrance, &exit, &pos, Some(Vector3::new(0.0, 0.0, 2.0)));
+
+        assert!(approx_eq(new_pos.pos, Vector3::new(11.0, 0.0, 0.0)));
+        assert!(approx_eq(velocity.unwrap(), Vector3::new(2.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_view_through() {
+        let entrance = Pos3::new(Vector3::new(0.0, 0.0, 0.0));
+        let exit = Pos3::with_rot(
+            Vector3::new(10.0, 0.0, 0.0),
+            Quaternion::from_angle_y(Rad(std::f32::consts::FRAC_PI_2)),
+        );
+
+        // A camera behind the entrance looking into it looks out of the exit the same way
+        let (eye, forward) = view_through(
+            &entrance,
+            &exit,
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, -3.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        );
+        assert!(approx_eq(eye, Vector3::new(7.0, 1.0, 0.0)));
+        assert!(approx_eq(forward, Vector3::new(1.0, 0.0, 0.0)));
+        assert_eq!(PortalView::new(0, 256), PortalView::new(1, 256));
+    }
+
+    #[test]
+    fn test_update_teleporters() {
+        let manager = ecs::Manager::default();
+        let exit = manager.create_entity();
+        manager.add_component_to_entity(exit, Pos3::new(Vector3::new(20.0, 0.0, 0.0)));
+        manager.add_component_to_entity(exit, TeleporterExit::default());
+
+        let teleporter = manager.create_entity();
+        manager.add_component_to_entity(teleporter, Pos3::default());
+        manager.add_component_to_entity(
+            teleporter,
+            Teleporter::new(exit, Vector3::new(1.0, 1.0, 1.0)),
+        );
+
+        let player = manager.create_entity();
+        manager.add_component_to_entity(player, Pos3::new(Vector3::new(0.5, 0.0, 0.0)));
+        manager.add_component_to_entity(player, Teleportable::default());
+
+        let events = update_teleporters(&manager, Duration::from_millis(16));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].entity, player);
+
+        let pos = manager.get_component_from_entity::<Pos3>(player).unwrap();
+        assert!(approx_eq(
+            pos.read().unwrap().pos,
+            Vector3::new(20.5, 0.0, 0.0)
+        ));
+
+        // The entity is cooling down, so it is not teleported again
+        let events = update_teleporters(&manager, Duration::from_millis(16));
+        assert!(events.is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+use crate::core::vfs;
+use crate::ecs::traits::Component;
+use crate::renderer::thumbnail::Thumbnails;
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector3, Vector4};
+use std::path::Path;
+
+/// Opens the engine's asset browser window, listing the assets of the VFS.
+/// Dragging a model out of the window onto the viewport spawns it on the ground.
+#[derive(Debug, Clone)]
+pub struct AssetBrowser {
+    pub open: bool,
+    /// Only the assets containing this text are listed.
+    pub filter: String,
+    /// The listed extensions, lowercase.
+    pub extensions: Vec<&'static str>,
+    /// The size of the thumbnails in points.
+    pub thumbnail_size: f32,
+    assets: Option<Vec<String>>,
+}
+
+impl Component for AssetBrowser {}
+
+impl Default for AssetBrowser {
+    fn default() -> Self {
+        Self {
+            open: true,
+            filter: String::new(),
+            extensions: vec!["obj", "png", "jpg", "jpeg"],
+            thumbnail_size: 72.0,
+            assets: None,
+        }
+    }
+}
+
+impl AssetBrowser {
+    /// List the assets again, e.g. after mounting an archive.
+    pub fn refresh(&mut self) {
+        self.assets = None;
+    }
+
+    fn is_listed(&self, path: &str) -> bool {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        self.extensions.contains(&extension.as_str())
+            && path.to_lowercase().contains(&self.filter.to_lowercase())
+    }
+}
+
+/// Sent when a model is dropped from the asset browser and spawned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetSpawned {
+    pub entity: crate::ecs::Entity,
+    pub path: String,
+    pub position: Vector3<f32>,
+}
+
+/// The payload of an asset dragged out of the browser.
+#[derive(Debug, Clone)]
+struct AssetDrag(String);
+
+/// Draw the asset browser window.
+///
+/// # Returns
+///
+/// The path of a model dropped onto the viewport and the position it was dropped at.
+pub fn show_asset_browser(
+    ctx: &egui::Context,
+    browser: &mut AssetBrowser,
+    thumbnails: &mut Thumbnails,
+) -> Option<(String, egui::Pos2)> {
+    if browser.assets.is_none() {
+        browser.assets = Some(vfs::paths().into_iter().collect());
+    }
+
+    let mut open = browser.open;
+    let window = egui::Window::new("Assets")
+        .open(&mut open)
+        .default_size([420.0, 360.0])
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Filter");
+                ui.text_edit_singleline(&mut browser.filter);
+                if ui.button("Refresh").clicked() {
+                    browser.refresh();
+                }
+            });
+            ui.separator();
+
+            let listed = browser
+                .assets
+                .iter()
+                .flatten()
+                .filter(|path| browser.is_listed(path))
+                .cloned()
+                .collect::<Vec<_>>();
+            let size = egui::vec2(browser.thumbnail_size, browser.thumbnail_size);
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for path in listed {
+                        let id = egui::Id::new(("asset_browser", &path));
+                        let thumbnail = thumbnails.get(ctx, &path);
+                        let name = Path::new(&path)
+                            .file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_default();
+
+                        ui.dnd_drag_source(id, AssetDrag(path.clone()), |ui| {
+                            ui.vertical(|ui| {
+                                ui.set_width(size.x);
+                                match &thumbnail {
+                                    Some(texture) => {
+                                        ui.add(egui::Image::new(texture).fit_to_exact_size(size));
+                                    }
+                                    None => {
+                                        ui.add_sized(size, egui::Spinner::new());
+                                    }
+                                }
+                                ui.add(
+                                    egui::Label::new(egui::RichText::new(name).small()).truncate(),
+                                );
+                            });
+                        })
+                        .response
+                        .on_hover_text(&path);
+                    }
+                });
+            });
+        });
+    browser.open = open;
+
+    // A model released outside of the window is dropped onto the viewport
+    let window_rect = window.map(|window| window.response.rect);
+    let (released, pointer) = ctx.input(|i| (i.pointer.any_released(), i.pointer.latest_pos()));
+    let pointer = pointer?;
+    if !released || window_rect.is_some_and(|rect| rect.contains(pointer)) {
+        return None;
+    }
+    let payload = egui::DragAndDrop::take_payload::<AssetDrag>(ctx)?;
+    let is_model = Path::new(&payload.0)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("obj"));
+
+    is_model.then(|| (payload.0.clone(), pointer))
+}
+
+/// Pick the point of the ground plane (y = 0) under a point of the screen.
+///
+/// # Arguments
+///
+/// * `view_proj` - The view projection matrix of the camera.
+/// * `ndc` - The point of the screen in normalized device coordinates.
+///
+/// # Returns
+///
+/// The picked point, `None` if the ground is not under the point.
+pub fn pick_ground(view_proj: Matrix4<f32>, ndc: [f32; 2]) -> Option<Vector3<f32>> {
+    let inverse = view_proj.invert()?;
+    let unproject = |depth: f32| {
+        let point = inverse * Vector4::new(ndc[0], ndc[1], depth, 1.0);
+        point.truncate() / point.w
+    };
+    let near = unproject(0.0);
+    let direction = (unproject(1.0) - near).normalize();
+
+    if direction.y >= -f32::EPSILON {
+        return None;
+    }
+    Some(near + direction * (-near.y / direction.y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{perspective, Deg, Point3};
+
+    #[test]
+    fn test_pick_ground() {
+        let view = Matrix4::look_at_rh(
+            Point3::new(0.0, 10.0, 10.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::unit_y(),
+        );
+        let view_proj = perspective(Deg(60.0), 1.0, 0.1, 100.0) * view;
+
+        let center = pick_ground(view_proj, [0.0, 0.0]).unwrap();
+        assert!(center.magnitude() < 1e-3, "{:?}", center);
+
+        // Looking at the horizon, only the lower half of the screen sees the ground
+        let view = Matrix4::look_at_rh(
+            Point3::new(0.0, 10.0, 0.0),
+            Point3::new(0.0, 10.0, -1.0),
+            Vector3::unit_y(),
+        );
+        let view_proj = perspective(Deg(60.0), 1.0, 0.1, 100.0) * view;
+        assert!(pick_ground(view_proj, [0.0, 0.5]).is_none());
+        let below = pick_ground(view_proj, [0.0, -0.5]).unwrap();
+        assert!(below.y.abs() < 1e-3 && below.z < 0.0, "{:?}", below);
+    }
+
+    #[test]
+    fn test_is_listed() {
+        let browser = AssetBrowser {
+            filter: "Cube".to_string(),
+            ..Default::default()
+        };
+        assert!(browser.is_listed("res/models/cube/cube.obj"));
+        assert!(browser.is_listed("res/models/cube/cube-normal.PNG"));
+        assert!(!browser.is_listed("res/models/cube/cube.mtl"));
+        assert!(!browser.is_listed("res/models/sphere/sphere.obj"));
+    }
+}
@@ -0,0 +1,225 @@
+use crate::core::config::{AspectMode, DisplayConfig};
+use cgmath::Rad;
+use egui::{Align2, Context, Id, Pos2, Rect, Vec2};
+
+/// Get the largest rect of the target aspect ratio centered in the screen.
+/// The rest of the screen is covered by letterbox (top and bottom) or pillarbox (left and right) bars.
+///
+/// # Arguments
+///
+/// * `screen` - The rect of the whole screen.
+/// * `target_aspect` - The width divided by the height of the rect.
+///
+/// # Returns
+///
+/// The fitted rect.
+pub fn fit_rect(screen: Rect, target_aspect: f32) -> Rect {
+    if target_aspect <= 0.0 || screen.height() <= 0.0 {
+        return screen;
+    }
+
+    let aspect = screen.width() / screen.height();
+    let size = if aspect > target_aspect {
+        // Wider than the target, pillarbox
+        Vec2::new(screen.height() * target_aspect, screen.height())
+    } else {
+        // Taller than the target, letterbox
+        Vec2::new(screen.width(), screen.width() / target_aspect)
+    };
+
+    Rect::from_center_size(screen.center(), size)
+}
+
+/// Get the vertical field of view that keeps the framing of the target aspect ratio.
+/// When letterboxing, the visible part of the screen is shorter than the screen,
+/// so the field of view of the whole screen has to be wider.
+///
+/// # Arguments
+///
+/// * `fovy` - The vertical field of view of the visible part.
+/// * `width` - The width of the screen.
+/// * `height` - The height of the screen.
+/// * `display` - The display configuration.
+///
+/// # Returns
+///
+/// The vertical field of view of the whole screen.
+pub fn fit_fovy(fovy: Rad<f32>, width: f32, height: f32, display: &DisplayConfig) -> Rad<f32> {
+    let (AspectMode::Fit, Some(target_aspect)) = (display.aspect_mode, display.target_aspect)
+    else {
+        return fovy;
+    };
+
+    let screen = Rect::from_min_size(Pos2::ZERO, Vec2::new(width, height));
+    let visible = fit_rect(screen, target_aspect);
+    if visible.height() <= 0.0 {
+        return fovy;
+    }
+
+    let scale = screen.height() / visible.height();
+    Rad(2.0 * ((fovy.0 * 0.5).tan() * scale).atan())
+}
+
+/// The rect a HUD element is anchored in.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum AnchorSpace {
+    /// The whole window.
+    Screen,
+    /// The part of the window inside of the letterbox or pillarbox bars.
+    Viewport,
+    /// The viewport shrunk by the safe area margin, the default for HUD elements.
+    #[default]
+    Safe,
+}
+
+/// The screen areas of the current frame, stored in the egui context by the renderer.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SafeArea {
+    pub screen: Rect,
+    pub viewport: Rect,
+    pub safe: Rect,
+}
+
+impl SafeArea {
+    pub fn new(screen: Rect, display: &DisplayConfig) -> Self {
+        let viewport = match (display.aspect_mode, display.target_aspect) {
+            (AspectMode::Fit, Some(target_aspect)) => fit_rect(screen, target_aspect),
+            _ => screen,
+        };
+        let margin = viewport.size() * display.safe_area_margin.clamp(0.0, 0.5);
+        let safe = viewport.shrink2(margin);
+
+        Self {
+            screen,
+            viewport,
+            safe,
+        }
+    }
+
+    pub fn rect(&self, space: AnchorSpace) -> Rect {
+        match space {
+            AnchorSpace::Screen => self.screen,
+            AnchorSpace::Viewport => self.viewport,
+            AnchorSpace::Safe => self.safe,
+        }
+    }
+
+    /// Store the safe area in the context, so the UI code of the frame can anchor to it.
+    pub fn store(&self, ctx: &Context) {
+        ctx.data_mut(|data| data.insert_temp(Self::id(), *self));
+    }
+
+    /// Get the safe area of the frame, the whole screen is used if none was stored.
+    pub fn get(ctx: &Context) -> Self {
+        ctx.data(|data| data.get_temp(Self::id()))
+            .unwrap_or_else(|| {
+                let screen = ctx.screen_rect();
+                Self {
+                    screen,
+                    viewport: screen,
+                    safe: screen,
+                }
+            })
+    }
+
+    fn id() -> Id {
+        Id::new("gears_safe_area")
+    }
+}
+
+/// The anchoring rule of a HUD element.
+/// The element is aligned to a point of the anchor space, e.g. `RIGHT_TOP` puts the top right corner
+/// of the element into the top right corner of the space, and then moved by the offset.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HudAnchor {
+    pub align: Align2,
+    pub offset: Vec2,
+    pub space: AnchorSpace,
+}
+
+impl HudAnchor {
+    pub fn new(align: Align2) -> Self {
+        Self {
+            align,
+            offset: Vec2::ZERO,
+            space: AnchorSpace::default(),
+        }
+    }
+
+    pub fn with_offset(mut self, offset: impl Into<Vec2>) -> Self {
+        self.offset = offset.into();
+        self
+    }
+
+    pub fn in_space(mut self, space: AnchorSpace) -> Self {
+        self.space = space;
+        self
+    }
+
+    /// Get the position of the pivot of the element.
+    pub fn position(&self, safe_area: &SafeArea) -> Pos2 {
+        self.align.pos_in_rect(&safe_area.rect(self.space)) + self.offset
+    }
+
+    /// Create an area placed by this rule in the safe area of the frame.
+    pub fn area(&self, ctx: &Context, id: impl Into<Id>) -> egui::Area {
+        egui::Area::new(id.into())
+            .pivot(self.align)
+            .fixed_pos(self.position(&SafeArea::get(ctx)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_rect() {
+        let ultrawide = Rect::from_min_size(Pos2::ZERO, Vec2::new(3440.0, 1440.0));
+        let pillarbox = fit_rect(ultrawide, 16.0 / 9.0);
+        assert_eq!(pillarbox.height(), 1440.0);
+        assert_eq!(pillarbox.width(), 2560.0);
+        assert_eq!(pillarbox.center(), ultrawide.center());
+
+        let tall = Rect::from_min_size(Pos2::ZERO, Vec2::new(1600.0, 1200.0));
+        let letterbox = fit_rect(tall, 16.0 / 9.0);
+        assert_eq!(letterbox.width(), 1600.0);
+        assert_eq!(letterbox.height(), 900.0);
+
+        let display = DisplayConfig {
+            target_aspect: Some(16.0 / 9.0),
+            aspect_mode: AspectMode::Fit,
+            safe_area_margin: 0.0,
+            ..Default::default()
+        };
+        let fovy = fit_fovy(Rad(1.0), 1600.0, 1200.0, &display);
+        assert!(((fovy.0 * 0.5).tan() - (0.5f32).tan() * 1200.0 / 900.0).abs() < 1e-5);
+        assert_eq!(fit_fovy(Rad(1.0), 3440.0, 1440.0, &display), Rad(1.0));
+    }
+
+    #[test]
+    fn test_anchor_in_safe_area() {
+        let screen = Rect::from_min_size(Pos2::ZERO, Vec2::new(3440.0, 1440.0));
+        let safe_area = SafeArea::new(
+            screen,
+            &DisplayConfig {
+                target_aspect: Some(16.0 / 9.0),
+                aspect_mode: AspectMode::Fit,
+                safe_area_margin: 0.05,
+                ..Default::default()
+            },
+        );
+
+        let crosshair = HudAnchor::new(Align2::CENTER_CENTER);
+        assert_eq!(crosshair.position(&safe_area), screen.center());
+
+        let minimap = HudAnchor::new(Align2::RIGHT_TOP).with_offset([-10.0, 10.0]);
+        assert_eq!(
+            minimap.position(&safe_area),
+            Pos2::new(440.0 + 2560.0 * 0.95 - 10.0, 1440.0 * 0.05 + 10.0)
+        );
+
+        let corner = HudAnchor::new(Align2::LEFT_TOP).in_space(AnchorSpace::Screen);
+        assert_eq!(corner.position(&safe_area), Pos2::ZERO);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::ecs::traits::Component;
+use crate::renderer::pipelines::PipelineWarmUp;
+
+/// The loading screen covering the frame while the renderer warms up its pipelines.
+/// Add it to any entity to change it, the first one found is used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadingScreen {
+    pub enabled: bool,
+    pub title: String,
+    pub background: egui::Color32,
+}
+
+impl Default for LoadingScreen {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            title: "Loading".to_string(),
+            background: egui::Color32::from_rgb(16, 16, 20),
+        }
+    }
+}
+
+impl Component for LoadingScreen {}
+
+/// Get the line under the progress bar of the loading screen.
+fn progress_label(progress: &PipelineWarmUp) -> String {
+    format!(
+        "Compiling shaders {}/{}",
+        progress.compiled.min(progress.total),
+        progress.total
+    )
+}
+
+/// Show the loading screen over the whole frame: the title and the progress of the warm-up.
+///
+/// # Arguments
+///
+/// * `ctx` - The egui context.
+/// * `screen` - The loading screen settings.
+/// * `progress` - The progress of the pipeline warm-up.
+pub fn show_loading_screen(ctx: &egui::Context, screen: &LoadingScreen, progress: &PipelineWarmUp) {
+    let rect = ctx.screen_rect();
+    egui::Area::new(egui::Id::new("loading_screen"))
+        .order(egui::Order::Foreground)
+        .fixed_pos(rect.min)
+        .interactable(false)
+        .show(ctx, |ui| {
+            ui.painter().rect_filled(rect, 0.0, screen.background);
+            ui.allocate_new_ui(egui::UiBuilder::new().max_rect(rect), |ui| {
+                ui.with_layout(
+                    egui::Layout::top_down(egui::Align::Center)
+                        .with_main_align(egui::Align::Center),
+                    |ui| {
+                        ui.set_max_width((rect.width() * 0.4).max(160.0));
+                        ui.heading(&screen.title);
+                        ui.add(egui::ProgressBar::new(progress.progress()).animate(true));
+                        ui.label(progress_label(progress));
+                    },
+                );
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_label() {
+        let progress = PipelineWarmUp {
+            compiled: 1,
+            total: 4,
+        };
+        assert_eq!(progress_label(&progress), "Compiling shaders 1/4");
+        assert_eq!(progress.progress(), 0.25);
+    }
+}
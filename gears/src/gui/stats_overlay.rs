@@ -0,0 +1,166 @@
+use crate::core::schedule::ScheduleInfo;
+use instant::Duration;
+use std::collections::VecDeque;
+
+/// The number of frames the frame time graph shows.
+const HISTORY_LEN: usize = 120;
+/// The number of the slowest systems listed.
+const SYSTEMS_SHOWN: usize = 8;
+/// The frame time the graph is scaled to at least, the height of a 30 FPS frame.
+const GRAPH_MIN_SCALE: Duration = Duration::from_micros(33_333);
+
+/// The statistics of the rendered frames shown by the stats overlay.
+#[derive(Debug, Clone, Default)]
+pub struct FrameStats {
+    frame_times: VecDeque<Duration>,
+    /// The draw calls of the models in the latest frame.
+    pub draw_calls: usize,
+    /// The lights in the latest frame.
+    pub lights: usize,
+    pub entities: usize,
+}
+
+impl FrameStats {
+    /// Record the time of a frame, the oldest one is forgotten once the history is full.
+    pub fn record_frame(&mut self, frame_time: Duration) {
+        if self.frame_times.len() == HISTORY_LEN {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+    }
+
+    /// Get the recorded frame times, the oldest first.
+    pub fn frame_times(&self) -> impl Iterator<Item = &Duration> {
+        self.frame_times.iter()
+    }
+
+    /// Get the average frame time of the recorded frames.
+    pub fn average_frame_time(&self) -> Duration {
+        if self.frame_times.is_empty() {
+            return Duration::ZERO;
+        }
+        self.frame_times.iter().sum::<Duration>() / self.frame_times.len() as u32
+    }
+
+    /// Get the frames per second of the average frame time, 0 before the first frame.
+    pub fn fps(&self) -> f32 {
+        let average = self.average_frame_time().as_secs_f32();
+        if average > 0.0 {
+            1.0 / average
+        } else {
+            0.0
+        }
+    }
+
+    /// Get the longest recorded frame time.
+    pub fn max_frame_time(&self) -> Duration {
+        self.frame_times.iter().max().copied().unwrap_or_default()
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.2} ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// Draw the frame times as bars, the newest on the right.
+fn frame_graph(ui: &mut egui::Ui, stats: &FrameStats) {
+    let (rect, _) = ui.allocate_exact_size(
+        egui::vec2(HISTORY_LEN as f32 * 2.0, 48.0),
+        egui::Sense::hover(),
+    );
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, egui::Color32::from_black_alpha(96));
+
+    let scale = stats.max_frame_time().max(GRAPH_MIN_SCALE).as_secs_f32();
+    let width = rect.width() / HISTORY_LEN as f32;
+    let offset = HISTORY_LEN - stats.frame_times.len();
+    for (i, frame_time) in stats.frame_times().enumerate() {
+        let height = rect.height() * (frame_time.as_secs_f32() / scale).min(1.0);
+        let x = rect.left() + (offset + i) as f32 * width;
+        // The frames slower than 30 FPS stand out
+        let color = if *frame_time > GRAPH_MIN_SCALE {
+            egui::Color32::from_rgb(230, 90, 70)
+        } else {
+            egui::Color32::from_rgb(110, 200, 120)
+        };
+        painter.rect_filled(
+            egui::Rect::from_min_max(
+                egui::pos2(x, rect.bottom() - height),
+                egui::pos2(x + width, rect.bottom()),
+            ),
+            0.0,
+            color,
+        );
+    }
+}
+
+/// Show the built-in stats overlay in the top left corner: the FPS, a frame time graph, the draw calls,
+/// the entity and the light counts and the slowest systems of the schedule.
+///
+/// # Arguments
+///
+/// * `ctx` - The egui context.
+/// * `stats` - The statistics of the frames.
+/// * `schedule` - The timings of the systems, `None` to leave them out.
+pub fn show_stats_overlay(
+    ctx: &egui::Context,
+    stats: &FrameStats,
+    schedule: Option<&ScheduleInfo>,
+) {
+    egui::Area::new(egui::Id::new("stats_overlay"))
+        .anchor(egui::Align2::LEFT_TOP, egui::vec2(8.0, 8.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(format!(
+                    "{:.0} FPS, {} (max {})",
+                    stats.fps(),
+                    millis(stats.average_frame_time()),
+                    millis(stats.max_frame_time())
+                ));
+                frame_graph(ui, stats);
+                ui.label(format!(
+                    "Draw calls: {}, entities: {}, lights: {}",
+                    stats.draw_calls, stats.entities, stats.lights
+                ));
+
+                let Some(schedule) = schedule.filter(|schedule| !schedule.systems.is_empty())
+                else {
+                    return;
+                };
+                ui.separator();
+                ui.label(format!("Systems: {}", millis(schedule.duration)));
+                let mut systems = schedule.systems.iter().collect::<Vec<_>>();
+                systems.sort_by_key(|system| std::cmp::Reverse(system.average));
+                egui::Grid::new("stats_overlay_systems").show(ui, |ui| {
+                    for system in systems.into_iter().take(SYSTEMS_SHOWN) {
+                        ui.label(&system.name);
+                        ui.label(millis(system.average));
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_stats() {
+        let mut stats = FrameStats::default();
+        assert_eq!(stats.fps(), 0.0);
+
+        for _ in 0..HISTORY_LEN {
+            stats.record_frame(Duration::from_millis(40));
+        }
+        for _ in 0..HISTORY_LEN {
+            stats.record_frame(Duration::from_millis(10));
+        }
+        // Only the latest frames are kept
+        assert_eq!(stats.frame_times().count(), HISTORY_LEN);
+        assert_eq!(stats.max_frame_time(), Duration::from_millis(10));
+        assert!((stats.fps() - 100.0).abs() < 0.01);
+    }
+}
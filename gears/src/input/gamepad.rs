@@ -0,0 +1,332 @@
+use cgmath::Vector2;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// A button of a gamepad, named by its place on the controller rather than its label.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// An analog input of a gamepad, the sticks are in -1..1 with up being positive,
+/// the triggers in 0..1.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+/// The state of a connected gamepad.
+#[derive(Debug, Clone, Default)]
+pub struct Gamepad {
+    pub name: String,
+    pressed: HashSet<GamepadButton>,
+    just_pressed: HashSet<GamepadButton>,
+    axes: HashMap<GamepadAxis, f32>,
+}
+
+impl Gamepad {
+    pub fn is_pressed(&self, button: GamepadButton) -> bool {
+        self.pressed.contains(&button)
+    }
+
+    /// Check if the button was pressed since the last poll.
+    pub fn just_pressed(&self, button: GamepadButton) -> bool {
+        self.just_pressed.contains(&button)
+    }
+
+    /// Get the raw value of an axis, without the dead zone.
+    pub fn axis(&self, axis: GamepadAxis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or(0.0)
+    }
+
+    /// Check if any button was pressed since the last poll.
+    pub fn has_just_pressed(&self) -> bool {
+        !self.just_pressed.is_empty()
+    }
+}
+
+/// The buttons and axes of the connected gamepads, a resource of the world.
+/// The renderer polls the gamepads into it each frame when the `gamepad` feature is enabled,
+/// other backends can feed it with `connect`, `set_button` and `set_axis`.
+///
+/// The queries without a gamepad id combine all the gamepads, so any of them can control the game.
+#[derive(Debug, Clone)]
+pub struct GamepadState {
+    gamepads: BTreeMap<usize, Gamepad>,
+    /// The axis values below it are read as 0, the rest is scaled to start from 0.
+    pub dead_zone: f32,
+}
+
+impl Default for GamepadState {
+    fn default() -> Self {
+        Self {
+            gamepads: BTreeMap::new(),
+            dead_zone: 0.15,
+        }
+    }
+}
+
+impl GamepadState {
+    /// Get the connected gamepads by their ids.
+    pub fn gamepads(&self) -> impl Iterator<Item = (usize, &Gamepad)> {
+        self.gamepads.iter().map(|(id, gamepad)| (*id, gamepad))
+    }
+
+    pub fn gamepad(&self, id: usize) -> Option<&Gamepad> {
+        self.gamepads.get(&id)
+    }
+
+    /// Check if the button is held on any gamepad.
+    pub fn is_pressed(&self, button: GamepadButton) -> bool {
+        self.gamepads
+            .values()
+            .any(|gamepad| gamepad.is_pressed(button))
+    }
+
+    /// Check if the button was pressed on any gamepad since the last poll.
+    pub fn just_pressed(&self, button: GamepadButton) -> bool {
+        self.gamepads
+            .values()
+            .any(|gamepad| gamepad.just_pressed(button))
+    }
+
+    /// Get the value of an axis after the dead zone, the one moved the most of all the gamepads.
+    pub fn axis(&self, axis: GamepadAxis) -> f32 {
+        self.gamepads
+            .values()
+            .map(|gamepad| self.apply_dead_zone(gamepad.axis(axis)))
+            .fold(0.0, |value: f32, other| {
+                if other.abs() > value.abs() {
+                    other
+                } else {
+                    value
+                }
+            })
+    }
+
+    /// Get the value of an axis of one gamepad after the dead zone.
+    pub fn axis_of(&self, id: usize, axis: GamepadAxis) -> f32 {
+        self.gamepad(id)
+            .map_or(0.0, |gamepad| self.apply_dead_zone(gamepad.axis(axis)))
+    }
+
+    pub fn left_stick(&self) -> Vector2<f32> {
+        Vector2::new(
+            self.axis(GamepadAxis::LeftStickX),
+            self.axis(GamepadAxis::LeftStickY),
+        )
+    }
+
+    pub fn right_stick(&self) -> Vector2<f32> {
+        Vector2::new(
+            self.axis(GamepadAxis::RightStickX),
+            self.axis(GamepadAxis::RightStickY),
+        )
+    }
+
+    fn apply_dead_zone(&self, value: f32) -> f32 {
+        if value.abs() <= self.dead_zone {
+            0.0
+        } else {
+            value.signum() * (value.abs() - self.dead_zone) / (1.0 - self.dead_zone)
+        }
+    }
+
+    pub fn connect(&mut self, id: usize, name: impl Into<String>) {
+        self.gamepads.insert(
+            id,
+            Gamepad {
+                name: name.into(),
+                ..Default::default()
+            },
+        );
+    }
+
+    pub fn disconnect(&mut self, id: usize) {
+        self.gamepads.remove(&id);
+    }
+
+    /// Set a button of a gamepad, an unknown gamepad is connected first.
+    pub fn set_button(&mut self, id: usize, button: GamepadButton, pressed: bool) {
+        let gamepad = self.gamepads.entry(id).or_default();
+        if pressed {
+            if gamepad.pressed.insert(button) {
+                gamepad.just_pressed.insert(button);
+            }
+        } else {
+            gamepad.pressed.remove(&button);
+        }
+    }
+
+    /// Set an axis of a gamepad, an unknown gamepad is connected first.
+    pub fn set_axis(&mut self, id: usize, axis: GamepadAxis, value: f32) {
+        self.gamepads
+            .entry(id)
+            .or_default()
+            .axes
+            .insert(axis, value.clamp(-1.0, 1.0));
+    }
+
+    /// Forget the buttons pressed since the last poll, called before polling the new events.
+    pub fn clear_just_pressed(&mut self) {
+        for gamepad in self.gamepads.values_mut() {
+            gamepad.just_pressed.clear();
+        }
+    }
+}
+
+/// Reads the gamepads of the platform with gilrs.
+#[cfg(feature = "gamepad")]
+pub(crate) struct GamepadPoller {
+    gilrs: gilrs::Gilrs,
+}
+
+#[cfg(feature = "gamepad")]
+impl GamepadPoller {
+    /// # Returns
+    ///
+    /// `None` if the gamepads of the platform can not be read.
+    pub fn new() -> Option<Self> {
+        match gilrs::Gilrs::new() {
+            Ok(gilrs) => Some(Self { gilrs }),
+            Err(err) => {
+                log::warn!("[Gamepad] The gamepads can not be read: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Apply the events since the last poll to the state.
+    pub fn poll(&mut self, state: &mut GamepadState) {
+        use gilrs::EventType;
+
+        state.clear_just_pressed();
+        for (id, gamepad) in self.gilrs.gamepads() {
+            if state.gamepad(id.into()).is_none() {
+                state.connect(id.into(), gamepad.name());
+            }
+        }
+        while let Some(gilrs::Event {
+            id: gilrs_id,
+            event,
+            ..
+        }) = self.gilrs.next_event()
+        {
+            let id = usize::from(gilrs_id);
+            match event {
+                EventType::Connected => {
+                    let name = self.gilrs.gamepad(gilrs_id).name().to_string();
+                    log::info!("[Gamepad] {} connected", name);
+                    state.connect(id, name);
+                }
+                EventType::Disconnected => {
+                    log::info!("[Gamepad] Gamepad {} disconnected", id);
+                    state.disconnect(id);
+                }
+                EventType::ButtonPressed(button, _) => {
+                    if let Some(button) = map_button(button) {
+                        state.set_button(id, button, true);
+                    }
+                }
+                EventType::ButtonReleased(button, _) => {
+                    if let Some(button) = map_button(button) {
+                        state.set_button(id, button, false);
+                    }
+                }
+                EventType::ButtonChanged(gilrs::Button::LeftTrigger2, value, _) => {
+                    state.set_axis(id, GamepadAxis::LeftTrigger, value);
+                }
+                EventType::ButtonChanged(gilrs::Button::RightTrigger2, value, _) => {
+                    state.set_axis(id, GamepadAxis::RightTrigger, value);
+                }
+                EventType::AxisChanged(axis, value, _) => {
+                    let axis = match axis {
+                        gilrs::Axis::LeftStickX => GamepadAxis::LeftStickX,
+                        gilrs::Axis::LeftStickY => GamepadAxis::LeftStickY,
+                        gilrs::Axis::RightStickX => GamepadAxis::RightStickX,
+                        gilrs::Axis::RightStickY => GamepadAxis::RightStickY,
+                        _ => continue,
+                    };
+                    state.set_axis(id, axis, value);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(feature = "gamepad")]
+fn map_button(button: gilrs::Button) -> Option<GamepadButton> {
+    use gilrs::Button;
+
+    Some(match button {
+        Button::South => GamepadButton::South,
+        Button::East => GamepadButton::East,
+        Button::North => GamepadButton::North,
+        Button::West => GamepadButton::West,
+        Button::LeftTrigger => GamepadButton::LeftBumper,
+        Button::RightTrigger => GamepadButton::RightBumper,
+        Button::LeftTrigger2 => GamepadButton::LeftTrigger,
+        Button::RightTrigger2 => GamepadButton::RightTrigger,
+        Button::Select => GamepadButton::Select,
+        Button::Start => GamepadButton::Start,
+        Button::LeftThumb => GamepadButton::LeftStick,
+        Button::RightThumb => GamepadButton::RightStick,
+        Button::DPadUp => GamepadButton::DPadUp,
+        Button::DPadDown => GamepadButton::DPadDown,
+        Button::DPadLeft => GamepadButton::DPadLeft,
+        Button::DPadRight => GamepadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buttons_and_axes() {
+        let mut state = GamepadState::default();
+        state.connect(0, "Pad");
+        state.set_button(0, GamepadButton::South, true);
+        state.set_button(1, GamepadButton::East, true);
+        assert!(state.just_pressed(GamepadButton::South) && state.is_pressed(GamepadButton::East));
+        assert_eq!(state.gamepads().count(), 2);
+
+        // Held buttons are not pressed again
+        state.clear_just_pressed();
+        state.set_button(0, GamepadButton::South, true);
+        assert!(
+            state.is_pressed(GamepadButton::South) && !state.just_pressed(GamepadButton::South)
+        );
+
+        // The dead zone is cut off, the stick moved the most wins
+        state.set_axis(0, GamepadAxis::LeftStickX, 0.1);
+        assert_eq!(state.left_stick().x, 0.0);
+        state.set_axis(1, GamepadAxis::LeftStickX, -1.0);
+        assert_eq!(state.left_stick().x, -1.0);
+
+        state.disconnect(1);
+        assert_eq!(state.axis(GamepadAxis::LeftStickX), 0.0);
+        assert!(!state.is_pressed(GamepadButton::East));
+    }
+}
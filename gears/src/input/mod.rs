@@ -0,0 +1,3 @@
+pub mod gamepad;
+#[cfg(feature = "renderer")]
+pub mod players;
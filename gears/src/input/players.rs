@@ -0,0 +1,493 @@
+use cgmath::Vector2;
+use std::collections::{HashMap, HashSet};
+use winit::keyboard::KeyCode;
+
+use super::gamepad::{GamepadAxis, GamepadButton, GamepadState};
+use crate::ecs::{traits::Component, Entity};
+
+/// A device a local player reads the input from.
+///
+/// The keyboards are numbered in the order they were first used, the platforms that report all
+/// the keyboards as one device have a single keyboard. Several players can share a keyboard with
+/// different input maps, e.g. one with WASD and one with the arrows.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum InputDevice {
+    Keyboard(usize),
+    Gamepad(usize),
+}
+
+/// An input triggering an action.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Button(GamepadButton),
+}
+
+impl From<KeyCode> for Binding {
+    fn from(key: KeyCode) -> Self {
+        Binding::Key(key)
+    }
+}
+
+impl From<GamepadButton> for Binding {
+    fn from(button: GamepadButton) -> Self {
+        Binding::Button(button)
+    }
+}
+
+/// An input driving an axis in -1..1.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AxisBinding {
+    /// Two keys, the axis is -1 or 1 while one of them is held.
+    Keys {
+        negative: KeyCode,
+        positive: KeyCode,
+    },
+    /// Two gamepad buttons, e.g. the directions of the d-pad.
+    Buttons {
+        negative: GamepadButton,
+        positive: GamepadButton,
+    },
+    Gamepad(GamepadAxis),
+}
+
+/// The actions and the axes of a player by name, and the inputs bound to them.
+/// Only the inputs of the device of the player are read.
+#[derive(Debug, Clone, Default)]
+pub struct InputMap {
+    actions: HashMap<String, Vec<Binding>>,
+    axes: HashMap<String, Vec<AxisBinding>>,
+}
+
+impl InputMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind an input to an action, an action can have several.
+    pub fn bind(mut self, action: impl Into<String>, binding: impl Into<Binding>) -> Self {
+        self.actions
+            .entry(action.into())
+            .or_default()
+            .push(binding.into());
+        self
+    }
+
+    /// Bind an input to an axis, the one moved the most is used.
+    pub fn bind_axis(mut self, axis: impl Into<String>, binding: AxisBinding) -> Self {
+        self.axes.entry(axis.into()).or_default().push(binding);
+        self
+    }
+
+    /// The `move_x` and `move_y` axes on WASD and the `jump` action on space.
+    pub fn wasd() -> Self {
+        Self::new()
+            .bind_axis(
+                "move_x",
+                AxisBinding::Keys {
+                    negative: KeyCode::KeyA,
+                    positive: KeyCode::KeyD,
+                },
+            )
+            .bind_axis(
+                "move_y",
+                AxisBinding::Keys {
+                    negative: KeyCode::KeyS,
+                    positive: KeyCode::KeyW,
+                },
+            )
+            .bind("jump", KeyCode::Space)
+    }
+
+    /// The `move_x` and `move_y` axes on the arrows and the `jump` action on the right control.
+    pub fn arrows() -> Self {
+        Self::new()
+            .bind_axis(
+                "move_x",
+                AxisBinding::Keys {
+                    negative: KeyCode::ArrowLeft,
+                    positive: KeyCode::ArrowRight,
+                },
+            )
+            .bind_axis(
+                "move_y",
+                AxisBinding::Keys {
+                    negative: KeyCode::ArrowDown,
+                    positive: KeyCode::ArrowUp,
+                },
+            )
+            .bind("jump", KeyCode::ControlRight)
+    }
+
+    /// The `move_x` and `move_y` axes on the left stick and the d-pad, the `look_x` and `look_y`
+    /// axes on the right stick and the `jump` action on the south button.
+    pub fn gamepad() -> Self {
+        Self::new()
+            .bind_axis("move_x", AxisBinding::Gamepad(GamepadAxis::LeftStickX))
+            .bind_axis(
+                "move_x",
+                AxisBinding::Buttons {
+                    negative: GamepadButton::DPadLeft,
+                    positive: GamepadButton::DPadRight,
+                },
+            )
+            .bind_axis("move_y", AxisBinding::Gamepad(GamepadAxis::LeftStickY))
+            .bind_axis(
+                "move_y",
+                AxisBinding::Buttons {
+                    negative: GamepadButton::DPadDown,
+                    positive: GamepadButton::DPadUp,
+                },
+            )
+            .bind_axis("look_x", AxisBinding::Gamepad(GamepadAxis::RightStickX))
+            .bind_axis("look_y", AxisBinding::Gamepad(GamepadAxis::RightStickY))
+            .bind("jump", GamepadButton::South)
+    }
+}
+
+/// The point of view of a player in split-screen, add it with a `Pos3` to the entity
+/// set as the camera of the player.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PlayerCamera {
+    pub yaw: cgmath::Rad<f32>,
+    pub pitch: cgmath::Rad<f32>,
+}
+
+impl Component for PlayerCamera {}
+
+impl PlayerCamera {
+    /// Look from a position at a target.
+    pub fn looking_at(from: cgmath::Vector3<f32>, to: cgmath::Vector3<f32>) -> Self {
+        use cgmath::InnerSpace;
+
+        let direction = (to - from).normalize();
+        Self {
+            yaw: cgmath::Rad(direction.z.atan2(direction.x)),
+            pitch: cgmath::Rad(direction.y.asin()),
+        }
+    }
+}
+
+/// How the screen is divided between two players, three or four get a quarter each.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum SplitLayout {
+    /// The players are side by side.
+    #[default]
+    Vertical,
+    /// The players are above each other.
+    Horizontal,
+}
+
+impl SplitLayout {
+    /// Get the viewports of a number of players as the x, y, width and height in 0..1,
+    /// from the top left corner. At most four players get a viewport.
+    pub fn viewports(&self, players: usize) -> Vec<[f32; 4]> {
+        match players {
+            0 => Vec::new(),
+            1 => vec![[0.0, 0.0, 1.0, 1.0]],
+            2 => match self {
+                SplitLayout::Vertical => vec![[0.0, 0.0, 0.5, 1.0], [0.5, 0.0, 0.5, 1.0]],
+                SplitLayout::Horizontal => vec![[0.0, 0.0, 1.0, 0.5], [0.0, 0.5, 1.0, 0.5]],
+            },
+            _ => [[0.0, 0.0], [0.5, 0.0], [0.0, 0.5], [0.5, 0.5]]
+                .into_iter()
+                .take(players)
+                .map(|[x, y]| [x, y, 0.5, 0.5])
+                .collect(),
+        }
+    }
+}
+
+/// A player playing on this machine.
+#[derive(Debug, Clone)]
+pub struct LocalPlayer {
+    /// The device the player reads the input from, `None` until one is assigned or joins.
+    pub device: Option<InputDevice>,
+    pub map: InputMap,
+    /// The entity the player sees the world from, with a `Pos3` and a `PlayerCamera`.
+    pub camera: Option<Entity>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct KeyboardState {
+    pressed: HashSet<KeyCode>,
+    just_pressed: HashSet<KeyCode>,
+    /// The keys pressed since the last frame started, they become the just pressed ones.
+    pending: HashSet<KeyCode>,
+}
+
+/// The local players and the input routed to them by device, a resource of the world.
+/// The renderer feeds it the keys and the gamepads each frame, insert it to enable local co-op.
+///
+/// The players with a camera entity get a viewport each and the frame is split between them.
+#[derive(Debug, Clone)]
+pub struct LocalPlayers {
+    players: Vec<LocalPlayer>,
+    keyboards: HashMap<usize, KeyboardState>,
+    gamepads: GamepadState,
+    /// Assign a device without a player to the first player without a device when it is used.
+    pub join_on_input: bool,
+    pub layout: SplitLayout,
+}
+
+impl Default for LocalPlayers {
+    fn default() -> Self {
+        Self {
+            players: Vec::new(),
+            keyboards: HashMap::new(),
+            gamepads: GamepadState::default(),
+            join_on_input: true,
+            layout: SplitLayout::default(),
+        }
+    }
+}
+
+impl LocalPlayers {
+    /// Add a player without a device.
+    ///
+    /// # Returns
+    ///
+    /// The index of the player.
+    pub fn add_player(&mut self, map: InputMap) -> usize {
+        self.players.push(LocalPlayer {
+            device: None,
+            map,
+            camera: None,
+        });
+        self.players.len() - 1
+    }
+
+    pub fn players(&self) -> &[LocalPlayer] {
+        &self.players
+    }
+
+    pub fn player(&self, player: usize) -> Option<&LocalPlayer> {
+        self.players.get(player)
+    }
+
+    pub fn player_mut(&mut self, player: usize) -> Option<&mut LocalPlayer> {
+        self.players.get_mut(player)
+    }
+
+    /// Set the device of a player.
+    pub fn assign(&mut self, player: usize, device: InputDevice) {
+        if let Some(player) = self.players.get_mut(player) {
+            player.device = Some(device);
+        }
+    }
+
+    /// Set the entity a player sees the world from.
+    pub fn set_camera(&mut self, player: usize, camera: Entity) {
+        if let Some(player) = self.players.get_mut(player) {
+            player.camera = Some(camera);
+        }
+    }
+
+    /// Get the player using a device, the first one if it is shared.
+    pub fn player_of(&self, device: InputDevice) -> Option<usize> {
+        self.players
+            .iter()
+            .position(|player| player.device == Some(device))
+    }
+
+    /// Get the viewport of a player as the x, y, width and height in 0..1,
+    /// `None` if the player has no camera or the screen is already split four ways.
+    pub fn viewport(&self, player: usize) -> Option<[f32; 4]> {
+        let with_camera = self
+            .players
+            .iter()
+            .take(player + 1)
+            .filter(|player| player.camera.is_some())
+            .count();
+        self.players.get(player)?.camera?;
+        let viewports = self.layout.viewports(self.cameras().count());
+        viewports.get(with_camera - 1).copied()
+    }
+
+    /// Get the players with a camera and their cameras, in the order of their viewports.
+    pub fn cameras(&self) -> impl Iterator<Item = (usize, Entity)> + '_ {
+        self.players
+            .iter()
+            .enumerate()
+            .filter_map(|(i, player)| Some((i, player.camera?)))
+    }
+
+    /// Apply a key of a keyboard, called by the renderer for each key event.
+    pub fn press_key(&mut self, keyboard: usize, key: KeyCode, pressed: bool) {
+        let state = self.keyboards.entry(keyboard).or_default();
+        if pressed {
+            if state.pressed.insert(key) {
+                state.pending.insert(key);
+            }
+        } else {
+            state.pressed.remove(&key);
+        }
+        if pressed {
+            self.join(InputDevice::Keyboard(keyboard));
+        }
+    }
+
+    /// Start a frame, the keys pressed since the last one become the just pressed ones
+    /// and the gamepads are copied from the polled state.
+    pub fn begin_frame(&mut self, gamepads: &GamepadState) {
+        for keyboard in self.keyboards.values_mut() {
+            keyboard.just_pressed = std::mem::take(&mut keyboard.pending);
+        }
+        self.gamepads = gamepads.clone();
+        let used = self
+            .gamepads
+            .gamepads()
+            .filter(|(_, gamepad)| gamepad.has_just_pressed())
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for id in used {
+            self.join(InputDevice::Gamepad(id));
+        }
+    }
+
+    fn join(&mut self, device: InputDevice) {
+        if !self.join_on_input || self.player_of(device).is_some() {
+            return;
+        }
+        if let Some((i, player)) = self
+            .players
+            .iter_mut()
+            .enumerate()
+            .find(|(_, player)| player.device.is_none())
+        {
+            log::info!("[Input] Player {} joined with {:?}", i + 1, device);
+            player.device = Some(device);
+        }
+    }
+
+    /// Check if an action of a player is held.
+    pub fn pressed(&self, player: usize, action: &str) -> bool {
+        self.bindings(player, action)
+            .any(|(device, binding)| self.binding_pressed(device, binding, false))
+    }
+
+    /// Check if an action of a player was triggered this frame.
+    pub fn just_pressed(&self, player: usize, action: &str) -> bool {
+        self.bindings(player, action)
+            .any(|(device, binding)| self.binding_pressed(device, binding, true))
+    }
+
+    /// Get an axis of a player in -1..1, the binding moved the most of it.
+    pub fn axis(&self, player: usize, axis: &str) -> f32 {
+        let Some((device, bindings)) = self
+            .players
+            .get(player)
+            .and_then(|player| Some((player.device?, player.map.axes.get(axis)?)))
+        else {
+            return 0.0;
+        };
+        let held = |key| self.binding_pressed(device, &Binding::Key(key), false);
+        let button = |button| self.binding_pressed(device, &Binding::Button(button), false);
+        bindings
+            .iter()
+            .map(|binding| match (*binding, device) {
+                (AxisBinding::Keys { negative, positive }, InputDevice::Keyboard(_)) => {
+                    f32::from(held(positive)) - f32::from(held(negative))
+                }
+                (AxisBinding::Buttons { negative, positive }, InputDevice::Gamepad(_)) => {
+                    f32::from(button(positive)) - f32::from(button(negative))
+                }
+                (AxisBinding::Gamepad(axis), InputDevice::Gamepad(id)) => {
+                    self.gamepads.axis_of(id, axis)
+                }
+                _ => 0.0,
+            })
+            .fold(0.0, |value: f32, other| {
+                if other.abs() > value.abs() {
+                    other
+                } else {
+                    value
+                }
+            })
+    }
+
+    /// Get two axes of a player as a vector, e.g. `stick(0, "move_x", "move_y")`.
+    pub fn stick(&self, player: usize, x: &str, y: &str) -> Vector2<f32> {
+        Vector2::new(self.axis(player, x), self.axis(player, y))
+    }
+
+    fn bindings<'a>(
+        &'a self,
+        player: usize,
+        action: &str,
+    ) -> impl Iterator<Item = (InputDevice, &'a Binding)> + 'a {
+        self.players
+            .get(player)
+            .and_then(|player| Some((player.device?, player.map.actions.get(action)?)))
+            .into_iter()
+            .flat_map(|(device, bindings)| bindings.iter().map(move |binding| (device, binding)))
+    }
+
+    fn binding_pressed(&self, device: InputDevice, binding: &Binding, just: bool) -> bool {
+        match (device, binding) {
+            (InputDevice::Keyboard(keyboard), Binding::Key(key)) => {
+                self.keyboards.get(&keyboard).is_some_and(|state| {
+                    if just {
+                        state.just_pressed.contains(key)
+                    } else {
+                        state.pressed.contains(key)
+                    }
+                })
+            }
+            (InputDevice::Gamepad(id), Binding::Button(button)) => {
+                self.gamepads.gamepad(id).is_some_and(|gamepad| {
+                    if just {
+                        gamepad.just_pressed(*button)
+                    } else {
+                        gamepad.is_pressed(*button)
+                    }
+                })
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_is_routed_by_device() {
+        let mut players = LocalPlayers::default();
+        let first = players.add_player(InputMap::wasd());
+        let second = players.add_player(InputMap::arrows());
+        let third = players.add_player(InputMap::gamepad());
+        players.assign(second, InputDevice::Keyboard(0));
+
+        // The first keyboard is taken, a second one joins as the first player
+        players.press_key(1, KeyCode::KeyW, true);
+        players.press_key(0, KeyCode::ArrowLeft, true);
+        players.press_key(0, KeyCode::KeyD, true);
+        let mut gamepads = GamepadState::default();
+        gamepads.set_button(3, GamepadButton::South, true);
+        gamepads.set_axis(3, GamepadAxis::LeftStickX, -1.0);
+        players.begin_frame(&gamepads);
+
+        assert_eq!(players.player_of(InputDevice::Keyboard(1)), Some(first));
+        assert_eq!(players.player_of(InputDevice::Gamepad(3)), Some(third));
+        assert_eq!(
+            players.stick(first, "move_x", "move_y"),
+            Vector2::new(0.0, 1.0)
+        );
+        // The keys of the shared keyboard are read through the map of each player
+        assert_eq!(players.axis(second, "move_x"), -1.0);
+        assert!(players.just_pressed(third, "jump") && !players.pressed(first, "jump"));
+        assert_eq!(players.axis(third, "move_x"), -1.0);
+
+        players.begin_frame(&GamepadState::default());
+        assert!(!players.just_pressed(third, "jump"));
+
+        players.layout = SplitLayout::Horizontal;
+        players.set_camera(first, Entity(1));
+        players.set_camera(third, Entity(2));
+        assert_eq!(players.viewport(first), Some([0.0, 0.0, 1.0, 0.5]));
+        assert_eq!(players.viewport(second), None);
+        assert_eq!(players.viewport(third), Some([0.0, 0.5, 1.0, 0.5]));
+    }
+}
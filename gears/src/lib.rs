@@ -1,6 +1,16 @@
+pub mod ai;
+pub mod audio;
 pub mod core;
 pub mod ecs;
+pub mod editor;
+pub mod gameplay;
 pub mod gui;
+pub mod input;
 pub mod macros;
+pub mod net;
+pub mod physics;
 pub mod prelude;
+#[cfg(feature = "renderer")]
 pub mod renderer;
+
+pub use gears_math as math;
@@ -1,11 +1,212 @@
 /// A macro to create a new entity and add multiple components when using the EntityBuilder trait.
+/// A component can be added only if a condition holds with `if cond => component`,
+/// and the components of a bundle are added with `..bundle`.
+///
+/// ```ignore
+/// let entity = new_entity!(app, Name("Crate"), if debug => DebugDraw, ..physics_bundle);
+/// ```
 #[macro_export]
 macro_rules! new_entity {
-    ($app:expr, $($component:expr),* $(,)?) => {{
-        let mut entity_builder = $app.new_entity();
-        $(
-            entity_builder = entity_builder.add_component($component);
-        )*
+    (@add $builder:ident;) => {};
+    (@add $builder:ident; if $cond:expr => $component:expr $(, $($rest:tt)*)?) => {
+        if $cond {
+            $builder.add_component($component);
+        }
+        $crate::new_entity!(@add $builder; $($($rest)*)?);
+    };
+    (@add $builder:ident; ..$bundle:expr $(, $($rest:tt)*)?) => {
+        $builder.add_bundle($bundle);
+        $crate::new_entity!(@add $builder; $($($rest)*)?);
+    };
+    (@add $builder:ident; $component:expr $(, $($rest:tt)*)?) => {
+        $builder.add_component($component);
+        $crate::new_entity!(@add $builder; $($($rest)*)?);
+    };
+    ($app:expr, $($rest:tt)*) => {{
+        let entity_builder = $app.new_entity();
+        $crate::new_entity!(@add entity_builder; $($rest)*);
         entity_builder.build()
     }};
 }
+
+/// A macro to spawn an entity into an `ecs::Manager`, the entity is validated like with `spawn_bundle`.
+/// It takes the same list as `new_entity!` and returns the entity.
+///
+/// With named components in braces it returns a struct with the `entity` and a handle
+/// to each component under its name instead.
+///
+/// ```ignore
+/// let entity = spawn!(ecs, Name("Crate"), if debug => DebugDraw, ..physics_bundle);
+///
+/// let player = spawn!(ecs, { name: Name("Player"), health: Health::new(100.0) });
+/// player.health.write().unwrap().damage(10.0);
+/// ```
+#[macro_export]
+macro_rules! spawn {
+    (@insert $ecs:ident, $entity:ident;) => {};
+    (@insert $ecs:ident, $entity:ident; if $cond:expr => $bundle:expr $(, $($rest:tt)*)?) => {
+        if $cond {
+            $crate::ecs::traits::Bundle::insert_into($bundle, $ecs, $entity);
+        }
+        $crate::spawn!(@insert $ecs, $entity; $($($rest)*)?);
+    };
+    (@insert $ecs:ident, $entity:ident; ..$bundle:expr $(, $($rest:tt)*)?) => {
+        $crate::ecs::traits::Bundle::insert_into($bundle, $ecs, $entity);
+        $crate::spawn!(@insert $ecs, $entity; $($($rest)*)?);
+    };
+    (@insert $ecs:ident, $entity:ident; $bundle:expr $(, $($rest:tt)*)?) => {
+        $crate::ecs::traits::Bundle::insert_into($bundle, $ecs, $entity);
+        $crate::spawn!(@insert $ecs, $entity; $($($rest)*)?);
+    };
+    ($ecs:expr, { $($field:ident : $component:expr),* $(,)? }) => {{
+        fn insert<T: $crate::ecs::traits::Component>(
+            ecs: &$crate::ecs::Manager,
+            entity: $crate::ecs::Entity,
+            component: T,
+        ) -> ::std::sync::Arc<::std::sync::RwLock<T>> {
+            ecs.add_component_to_entity(entity, component);
+            ecs.get_component_from_entity::<T>(entity).unwrap()
+        }
+
+        #[allow(non_camel_case_types, dead_code)]
+        struct Spawned<$($field),*> {
+            entity: $crate::ecs::Entity,
+            $($field: ::std::sync::Arc<::std::sync::RwLock<$field>>,)*
+        }
+
+        let ecs: &$crate::ecs::Manager = &$ecs;
+        let entity = ecs.create_entity();
+        let spawned = Spawned {
+            entity,
+            $($field: insert(ecs, entity, $component),)*
+        };
+        ecs.check_spawned(entity);
+        spawned
+    }};
+    ($ecs:expr, $($rest:tt)*) => {{
+        let ecs: &$crate::ecs::Manager = &$ecs;
+        let entity = ecs.create_entity();
+        $crate::spawn!(@insert ecs, entity; $($rest)*);
+        ecs.check_spawned(entity);
+        entity
+    }};
+}
+
+/// A macro to create an `AsyncSystem` from an async block, without boxing and pinning the future by hand.
+/// The captures in brackets are cloned into the system once and into every run of it again,
+/// so an `Arc` is shared instead of moved. A capture is a variable or a named expression.
+///
+/// ```ignore
+/// let score = Arc::new(AtomicU32::new(0));
+/// let system = async_system!("count", [score, tx = events.sender()] |ecs, dt| {
+///     let ecs = ecs.lock().unwrap();
+///     score.fetch_add(ecs.entity_count() as u32, Ordering::Relaxed);
+/// });
+/// app.add_async(system)?;
+/// ```
+#[macro_export]
+macro_rules! async_system {
+    (@capture $capture:ident) => {
+        let $capture = ::std::clone::Clone::clone(&$capture);
+    };
+    (@capture $capture:ident = $init:expr) => {
+        let $capture = $init;
+    };
+    ($name:expr, $([$($capture:ident $(= $init:expr)?),* $(,)?])? |$ecs:pat_param, $dt:pat_param| $body:block) => {{
+        $($($crate::async_system!(@capture $capture $(= $init)?);)*)?
+        $crate::core::schedule::AsyncSystem::new(
+            $name,
+            move |ecs: ::std::sync::Arc<::std::sync::Mutex<$crate::ecs::Manager>>, dt: $crate::core::Dt| {
+                $($(let $capture = ::std::clone::Clone::clone(&$capture);)*)?
+                ::std::boxed::Box::pin(async move {
+                    let $ecs = ecs;
+                    let $dt = dt;
+                    $body
+                })
+            },
+        )
+    }};
+    ($($tokens:tt)*) => {
+        compile_error!(
+            "expected `async_system!(\"name\", [captures] |ecs, dt| { ... })`, the captures are optional \
+             and written as `name` or `name = expr`"
+        );
+    };
+}
+
+/// A macro to implement the `Bundle` trait for a struct, listing the fields to add.
+/// The fields are components or bundles themselves.
+///
+/// ```ignore
+/// struct EnemyBundle {
+///     name: components::Name,
+///     pos: components::Pos3,
+///     ai: AiBundle,
+/// }
+///
+/// impl_bundle!(EnemyBundle { name, pos, ai });
+/// ```
+#[macro_export]
+macro_rules! impl_bundle {
+    ($bundle:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::ecs::traits::Bundle for $bundle {
+            fn insert_into(self, ecs: &$crate::ecs::Manager, entity: $crate::ecs::Entity) {
+                $($crate::ecs::traits::Bundle::insert_into(self.$field, ecs, entity);)*
+            }
+        }
+    };
+}
+
+/// A macro to implement the `Marker` trait for a component, with its description
+/// and the components a marked entity must have.
+///
+/// ```ignore
+/// impl_marker!(Perceivable, "can be seen by AI agents", requires [Pos3]);
+/// ```
+#[macro_export]
+macro_rules! impl_marker {
+    ($marker:ty, $description:literal $(, requires [$($required:ty),* $(,)?])? $(,)?) => {
+        impl $crate::ecs::traits::Marker for $marker {
+            fn describe() -> &'static str {
+                $description
+            }
+
+            fn required() -> Vec<$crate::ecs::traits::RequiredComponent> {
+                vec![$($($crate::ecs::traits::RequiredComponent::of::<$required>()),*)?]
+            }
+        }
+    };
+}
+
+/// A macro to translate a key with the global localization, with optional `name = value` placeholder arguments.
+#[macro_export]
+macro_rules! tr {
+    ($key:expr $(,)?) => {
+        $crate::core::localization::tr($key, &[])
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::core::localization::tr($key, &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),+])
+    };
+}
+
+/// A macro to embed assets into the executable, for an `AssetConfig::embedded`.
+/// The paths are relative to the directory of the crate's `Cargo.toml`, or to `dir` in it,
+/// and the assets keep the path they are embedded with.
+///
+/// ```ignore
+/// let embedded = embed_assets!(dir = "..", "res/models/cube/cube.obj", "res/models/cube/cube.mtl");
+/// ```
+#[macro_export]
+macro_rules! embed_assets {
+    ($($path:literal),* $(,)?) => {
+        $crate::embed_assets!(dir = ".", $($path),*)
+    };
+    (dir = $dir:literal, $($path:literal),* $(,)?) => {
+        &[$(
+            (
+                $path,
+                include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $dir, "/", $path)) as &[u8],
+            )
+        ),*]
+    };
+}
@@ -0,0 +1,588 @@
+use super::protocol::Message;
+use super::server::ClientId;
+use crate::core::Dt;
+use crate::ecs;
+use crate::ecs::traits::Component;
+use crate::gui::layout::HudAnchor;
+use instant::{Duration, Instant};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// The channel the chat messages are sent on.
+pub const CHAT_CHANNEL: u8 = 0;
+
+/// A reliable, ordered stream of small messages on top of the datagrams.
+/// Each message is resent until the peer acknowledges it, and the received messages are delivered
+/// in the order they were sent, so a channel has the ordering guarantees of a TCP stream.
+#[derive(Debug, Clone)]
+pub struct ReliableChannel {
+    id: u8,
+    next_send: u32,
+    pending: BTreeMap<u32, Pending>,
+    next_receive: u32,
+    received: BTreeMap<u32, Vec<u8>>,
+    /// The bytes of the messages in `received`.
+    buffered: usize,
+    sent: u64,
+    resent: u64,
+}
+
+/// A message waiting for an acknowledgement.
+#[derive(Debug, Clone)]
+struct Pending {
+    data: Vec<u8>,
+    sent: Option<Instant>,
+    resent: bool,
+}
+
+impl ReliableChannel {
+    /// The largest number of messages waiting for an acknowledgement.
+    pub const MAX_PENDING: usize = 256;
+    /// How far ahead of the next message to deliver a received message may be.
+    /// A peer never has more messages in flight, and the window caps the messages buffered out of order.
+    pub const RECEIVE_WINDOW: u32 = Self::MAX_PENDING as u32;
+
+    pub fn new(id: u8) -> Self {
+        Self {
+            id,
+            next_send: 0,
+            pending: BTreeMap::new(),
+            next_receive: 0,
+            received: BTreeMap::new(),
+            buffered: 0,
+            sent: 0,
+            resent: 0,
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Get the number of messages not acknowledged yet.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Get the number of messages sent, and how many of them were resends.
+    pub fn counters(&self) -> (u64, u64) {
+        (self.sent, self.resent)
+    }
+
+    /// Get the bytes of the received messages waiting for an earlier one.
+    pub fn buffered(&self) -> usize {
+        self.buffered
+    }
+
+    /// Queue a message, it is sent by the next `poll`.
+    ///
+    /// # Returns
+    ///
+    /// The sequence number of the message, or an error if it is too large or the peer stopped acknowledging.
+    pub fn send(&mut self, data: Vec<u8>) -> anyhow::Result<u32> {
+        if data.len() > Message::MAX_RELIABLE_SIZE {
+            anyhow::bail!(
+                "The message is {} bytes, the limit is {}",
+                data.len(),
+                Message::MAX_RELIABLE_SIZE
+            );
+        }
+        if self.pending.len() >= Self::MAX_PENDING {
+            anyhow::bail!("Too many messages are waiting for an acknowledgement");
+        }
+
+        let sequence = self.next_send;
+        self.next_send = self.next_send.wrapping_add(1);
+        self.pending.insert(
+            sequence,
+            Pending {
+                data,
+                sent: None,
+                resent: false,
+            },
+        );
+        Ok(sequence)
+    }
+
+    /// Get the messages to send now, the new ones and the ones not acknowledged in time.
+    pub fn poll(&mut self, now: Instant, resend_interval: Duration) -> Vec<Message> {
+        let mut messages = Vec::new();
+        for (sequence, pending) in self.pending.iter_mut() {
+            if pending
+                .sent
+                .is_some_and(|sent| now < sent + resend_interval)
+            {
+                continue;
+            }
+            pending.resent |= pending.sent.is_some();
+            self.resent += pending.sent.is_some() as u64;
+            self.sent += 1;
+            pending.sent = Some(now);
+            messages.push(Message::Reliable {
+                channel: self.id,
+                sequence: *sequence,
+                data: pending.data.clone(),
+            });
+        }
+        messages
+    }
+
+    /// Remove an acknowledged message.
+    ///
+    /// # Returns
+    ///
+    /// The time the message was sent, if it was sent only once, to measure the round trip.
+    pub fn ack(&mut self, sequence: u32) -> Option<Instant> {
+        let pending = self.pending.remove(&sequence)?;
+        pending.sent.filter(|_| !pending.resent)
+    }
+
+    /// Accept a received message.
+    /// A message further ahead than the `RECEIVE_WINDOW` is dropped without an acknowledgement,
+    /// so a peer can not make the channel buffer an unbounded number of messages.
+    ///
+    /// # Returns
+    ///
+    /// The acknowledgement to send back, `None` for a dropped message,
+    /// and the messages which can be delivered in order.
+    pub fn receive(&mut self, sequence: u32, data: Vec<u8>) -> (Option<Message>, Vec<Vec<u8>>) {
+        let ack = Message::Ack {
+            channel: self.id,
+            sequence,
+        };
+
+        // Messages already delivered are only acknowledged again, the previous ack was lost
+        let behind = self.next_receive.wrapping_sub(sequence);
+        if behind > 0 && behind <= u32::MAX / 2 {
+            return (Some(ack), Vec::new());
+        }
+        let ahead = sequence.wrapping_sub(self.next_receive);
+        if ahead >= Self::RECEIVE_WINDOW {
+            log::debug!(
+                "[Net] Dropping the message {} of channel {}, {} ahead of the next one",
+                sequence,
+                self.id,
+                ahead
+            );
+            return (None, Vec::new());
+        }
+        let len = data.len();
+        if let Some(previous) = self.received.insert(sequence, data) {
+            self.buffered -= previous.len();
+        }
+        self.buffered += len;
+
+        let mut delivered = Vec::new();
+        while let Some(data) = self.received.remove(&self.next_receive) {
+            self.buffered -= data.len();
+            delivered.push(data);
+            self.next_receive = self.next_receive.wrapping_add(1);
+        }
+        (Some(ack), delivered)
+    }
+}
+
+/// The acknowledgement to send back and the `(channel, data)` pairs delivered by a handled message.
+pub type Handled = (Option<Message>, Vec<(u8, Vec<u8>)>);
+
+/// The reliable channels to a single peer.
+/// The channels are opened by the first message on them, so the peer decides how many there are.
+#[derive(Debug, Clone)]
+pub struct Channels {
+    channels: HashMap<u8, ReliableChannel>,
+    pub resend_interval: Duration,
+    /// The most bytes buffered out of order on all the channels together,
+    /// a peer going over it is misbehaving and `handle` fails.
+    pub max_buffered: usize,
+    rtt: Option<Duration>,
+}
+
+impl Default for Channels {
+    fn default() -> Self {
+        Self {
+            channels: HashMap::new(),
+            resend_interval: Duration::from_millis(200),
+            // A single channel never buffers more than its window
+            max_buffered: ReliableChannel::RECEIVE_WINDOW as usize * Message::MAX_RELIABLE_SIZE,
+            rtt: None,
+        }
+    }
+}
+
+impl Channels {
+    fn channel(&mut self, id: u8) -> &mut ReliableChannel {
+        self.channels
+            .entry(id)
+            .or_insert_with(|| ReliableChannel::new(id))
+    }
+
+    pub fn send(&mut self, channel: u8, data: Vec<u8>) -> anyhow::Result<u32> {
+        self.channel(channel).send(data)
+    }
+
+    /// Get the smoothed round trip time, measured from the acknowledgements of the messages sent once.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
+    /// Get the number of messages sent on every channel, and how many of them were resends.
+    pub fn counters(&self) -> (u64, u64) {
+        self.channels
+            .values()
+            .map(ReliableChannel::counters)
+            .fold((0, 0), |(sent, resent), (s, r)| (sent + s, resent + r))
+    }
+
+    /// Get the bytes buffered out of order on all the channels.
+    pub fn buffered(&self) -> usize {
+        self.channels.values().map(ReliableChannel::buffered).sum()
+    }
+
+    /// Handle a reliable message or an acknowledgement.
+    ///
+    /// # Returns
+    ///
+    /// The acknowledgement to send back, and the `(channel, data)` pairs delivered in order,
+    /// or an error if the peer made the channels buffer more than `max_buffered`.
+    pub fn handle(&mut self, message: Message) -> anyhow::Result<Handled> {
+        Ok(match message {
+            Message::Reliable {
+                channel,
+                sequence,
+                data,
+            } => {
+                let (ack, delivered) = self.channel(channel).receive(sequence, data);
+                let buffered = self.buffered();
+                if buffered > self.max_buffered {
+                    anyhow::bail!(
+                        "The channels buffer {} bytes, the limit is {}",
+                        buffered,
+                        self.max_buffered
+                    );
+                }
+                let delivered = delivered.into_iter().map(|data| (channel, data)).collect();
+                (ack, delivered)
+            }
+            Message::Ack { channel, sequence } => {
+                // Nothing was sent on a channel which does not exist yet
+                let sent = self
+                    .channels
+                    .get_mut(&channel)
+                    .and_then(|channel| channel.ack(sequence));
+                if let Some(sent) = sent {
+                    let sample = Instant::now().duration_since(sent);
+                    self.rtt = Some(match self.rtt {
+                        Some(rtt) => (rtt * 7 + sample) / 8,
+                        None => sample,
+                    });
+                }
+                (None, Vec::new())
+            }
+            _ => (None, Vec::new()),
+        })
+    }
+
+    /// Get the messages of every channel to send now.
+    pub fn poll(&mut self, now: Instant) -> Vec<Message> {
+        let resend_interval = self.resend_interval;
+        self.channels
+            .values_mut()
+            .flat_map(|channel| channel.poll(now, resend_interval))
+            .collect()
+    }
+}
+
+/// Sent for each message received on a reliable channel, in the order of the channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetMessage {
+    pub client: ClientId,
+    pub channel: u8,
+    pub data: Vec<u8>,
+}
+
+/// Send it to queue a message on a reliable channel, `None` sends it to every client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SendNetMessage {
+    pub to: Option<ClientId>,
+    pub channel: u8,
+    pub data: Vec<u8>,
+}
+
+/// A chat line, sent on the `CHAT_CHANNEL`.
+/// The server relays the chat of a client to the other clients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub sender: String,
+    pub text: String,
+}
+
+impl ChatMessage {
+    pub fn new(sender: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            sender: sender.into(),
+            text: text.into(),
+        }
+    }
+
+    /// Encode the message, a sender longer than 255 bytes is cut at the last character fitting.
+    pub fn encode(&self) -> Vec<u8> {
+        let sender = truncate(&self.sender, u8::MAX as usize);
+        let mut out = vec![sender.len() as u8];
+        out.extend_from_slice(sender.as_bytes());
+        out.extend_from_slice(self.text.as_bytes());
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        let (len, rest) = bytes
+            .split_first()
+            .ok_or_else(|| anyhow::anyhow!("The chat message is empty"))?;
+        if rest.len() < *len as usize {
+            anyhow::bail!("The chat message is truncated");
+        }
+        let (sender, text) = rest.split_at(*len as usize);
+
+        Ok(Self {
+            sender: String::from_utf8_lossy(sender).into_owned(),
+            text: String::from_utf8_lossy(text).into_owned(),
+        })
+    }
+}
+
+/// Cut a text to at most `max` bytes without splitting a character.
+pub(crate) fn truncate(text: &str, max: usize) -> &str {
+    let end = (0..=max.min(text.len()))
+        .rev()
+        .find(|end| text.is_char_boundary(*end))
+        .unwrap_or(0);
+    &text[..end]
+}
+
+/// Keeps the latest chat lines for display.
+#[derive(Debug, Clone)]
+pub struct ChatLog {
+    pub max_lines: usize,
+    /// Lines older than this are hidden, `None` to always show them.
+    pub fade_after: Option<f32>,
+    lines: VecDeque<(ChatMessage, f32)>,
+}
+
+impl Component for ChatLog {}
+
+impl Default for ChatLog {
+    fn default() -> Self {
+        Self {
+            max_lines: 8,
+            fade_after: Some(10.0),
+            lines: VecDeque::new(),
+        }
+    }
+}
+
+impl ChatLog {
+    pub fn push(&mut self, message: ChatMessage) {
+        while self.lines.len() >= self.max_lines.max(1) {
+            self.lines.pop_front();
+        }
+        self.lines.push_back((message, 0.0));
+    }
+
+    /// Get the visible lines, oldest first.
+    pub fn lines(&self) -> impl Iterator<Item = &ChatMessage> {
+        self.lines
+            .iter()
+            .filter(|(_, age)| self.fade_after.is_none_or(|fade_after| *age < fade_after))
+            .map(|(message, _)| message)
+    }
+}
+
+/// Add the received chat messages to every chat log.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time.
+pub fn update_chat_log(ecs: &ecs::Manager, dt: Dt) {
+    let messages = ecs.drain_events::<ChatMessage>();
+
+    for (_, log) in ecs.get_all_components_of_type::<ChatLog>() {
+        let mut log = log.write().unwrap();
+        for (_, age) in log.lines.iter_mut() {
+            *age += dt.as_secs_f32();
+        }
+        for message in &messages {
+            log.push(message.clone());
+        }
+    }
+}
+
+/// Draw the visible lines of a chat log in the bottom left corner of the safe area.
+pub fn show_chat_log(ctx: &egui::Context, id: egui::Id, log: &ChatLog) {
+    HudAnchor::new(egui::Align2::LEFT_BOTTOM)
+        .area(ctx, id)
+        .show(ctx, |ui| {
+            for message in log.lines() {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label(
+                        egui::RichText::new(format!("{}:", message.sender))
+                            .color(egui::Color32::from_rgb(255, 210, 90))
+                            .strong(),
+                    );
+                    ui.label(&message.text);
+                });
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deliver(
+        from: &mut ReliableChannel,
+        to: &mut ReliableChannel,
+        messages: Vec<Message>,
+    ) -> Vec<Vec<u8>> {
+        let mut delivered = Vec::new();
+        for message in messages {
+            if let Message::Reliable { sequence, data, .. } = message {
+                let (ack, data) = to.receive(sequence, data);
+                if let Some(Message::Ack { sequence, .. }) = ack {
+                    from.ack(sequence);
+                }
+                delivered.extend(data);
+            }
+        }
+        delivered
+    }
+
+    #[test]
+    fn test_reliable_ordering() {
+        let mut sender = ReliableChannel::new(1);
+        let mut receiver = ReliableChannel::new(1);
+        let now = Instant::now();
+        let resend = Duration::from_millis(100);
+
+        for text in ["a", "b", "c"] {
+            sender.send(text.as_bytes().to_vec()).unwrap();
+        }
+        let mut messages = sender.poll(now, resend);
+        assert_eq!(messages.len(), 3);
+
+        // "b" is lost and "c" arrives first, nothing after "a" is delivered until "b" arrives
+        let c = messages.pop().unwrap();
+        messages.pop();
+        assert_eq!(
+            deliver(&mut sender, &mut receiver, vec![c]),
+            Vec::<Vec<u8>>::new()
+        );
+        assert_eq!(
+            deliver(&mut sender, &mut receiver, messages),
+            vec![b"a".to_vec()]
+        );
+        assert_eq!(sender.pending(), 1);
+
+        // Only the lost message is resent, after the interval
+        assert!(sender.poll(now + resend / 2, resend).is_empty());
+        let resent = sender.poll(now + resend, resend);
+        assert_eq!(
+            deliver(&mut sender, &mut receiver, resent),
+            vec![b"b".to_vec(), b"c".to_vec()]
+        );
+        assert_eq!(sender.pending(), 0);
+
+        // A duplicate is acknowledged but not delivered again
+        let (ack, delivered) = receiver.receive(0, b"a".to_vec());
+        assert_eq!(
+            ack,
+            Some(Message::Ack {
+                channel: 1,
+                sequence: 0
+            })
+        );
+        assert!(delivered.is_empty());
+    }
+
+    #[test]
+    fn test_receive_window() {
+        let mut receiver = ReliableChannel::new(1);
+
+        // Far ahead of the next message, neither buffered nor acknowledged
+        let window = ReliableChannel::RECEIVE_WINDOW;
+        let (ack, delivered) = receiver.receive(window, b"far".to_vec());
+        assert!(ack.is_none() && delivered.is_empty());
+        assert!(receiver.received.is_empty());
+
+        // The whole window is buffered until the first message arrives
+        for sequence in 1..window {
+            let (ack, delivered) = receiver.receive(sequence, vec![]);
+            assert!(ack.is_some() && delivered.is_empty());
+        }
+        assert_eq!(receiver.received.len(), window as usize - 1);
+        let (_, delivered) = receiver.receive(0, vec![]);
+        assert_eq!(delivered.len(), window as usize);
+        assert!(receiver.received.is_empty());
+    }
+
+    #[test]
+    fn test_buffered_limit() {
+        let mut channels = Channels {
+            max_buffered: 1000,
+            ..Default::default()
+        };
+        let message = |channel, sequence, len| Message::Reliable {
+            channel,
+            sequence,
+            data: vec![0; len],
+        };
+
+        // Delivered messages are not buffered, and acks of unknown channels open none
+        assert!(channels.handle(message(1, 0, 800)).is_ok());
+        assert!(channels
+            .handle(Message::Ack {
+                channel: 9,
+                sequence: 0
+            })
+            .is_ok());
+        assert_eq!(channels.channels.len(), 1);
+        assert_eq!(channels.buffered(), 0);
+
+        // Gaps on many channels add up
+        for channel in 0..3 {
+            assert!(channels.handle(message(channel, 2, 300)).is_ok());
+        }
+        assert_eq!(channels.buffered(), 900);
+        assert!(channels.handle(message(3, 2, 200)).is_err());
+
+        // A duplicate replaces the buffered message instead of adding to it
+        let mut channel = ReliableChannel::new(1);
+        channel.receive(1, vec![0; 10]);
+        channel.receive(1, vec![0; 10]);
+        assert_eq!(channel.buffered(), 10);
+        channel.receive(0, vec![]);
+        assert_eq!(channel.buffered(), 0);
+    }
+
+    #[test]
+    fn test_chat_message() {
+        let message = ChatMessage::new("Player", "gg");
+        assert_eq!(ChatMessage::decode(&message.encode()).unwrap(), message);
+        assert!(ChatMessage::decode(&[5, b'a']).is_err());
+
+        // A long sender is cut before the character crossing the limit, not inside it
+        let long = ChatMessage::new("é".repeat(200), "hi");
+        let decoded = ChatMessage::decode(&long.encode()).unwrap();
+        assert_eq!(decoded.sender, "é".repeat(127));
+        assert_eq!(decoded.text, "hi");
+
+        let ecs = ecs::Manager::default();
+        let entity = ecs.create_entity();
+        ecs.add_component_to_entity(entity, ChatLog::default());
+        ecs.send_event(message.clone());
+        update_chat_log(&ecs, Dt::from_secs(1));
+        update_chat_log(&ecs, Dt::from_secs(11));
+
+        let log = ecs.get_component_from_entity::<ChatLog>(entity).unwrap();
+        assert_eq!(log.read().unwrap().lines.len(), 1);
+        assert_eq!(log.read().unwrap().lines().count(), 0);
+    }
+}
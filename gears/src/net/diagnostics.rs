@@ -0,0 +1,223 @@
+use super::protocol::{EntityState, Message};
+use crate::ecs::traits::Component;
+use instant::Duration;
+use rand::Rng;
+use std::collections::{BTreeMap, VecDeque};
+
+/// The number of samples the network stats keep, one per second.
+pub const HISTORY_LEN: usize = 60;
+
+/// A component that makes the server simulate a bad network, for testing.
+/// The datagrams it sends are delayed by the latency, and the datagrams it sends and receives
+/// are dropped with the loss probability.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NetConditions {
+    pub enabled: bool,
+    /// The delay added to every datagram the server sends.
+    pub latency: Duration,
+    /// The probability of dropping a datagram, from 0 to 1.
+    pub loss: f32,
+}
+
+impl Component for NetConditions {}
+
+impl Default for NetConditions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            latency: Duration::from_millis(100),
+            loss: 0.05,
+        }
+    }
+}
+
+impl NetConditions {
+    /// Get the delay of an outgoing datagram.
+    pub fn delay(&self) -> Duration {
+        if self.enabled {
+            self.latency
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    /// Decide whether a datagram is lost.
+    pub fn drops(&self) -> bool {
+        self.enabled && self.loss > 0.0 && rand::thread_rng().gen::<f32>() < self.loss
+    }
+}
+
+/// The traffic of a channel.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Traffic {
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub packets_in: u64,
+    pub packets_out: u64,
+}
+
+/// The network activity of the server during a second.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetSample {
+    /// The average smoothed round trip time of the clients.
+    pub rtt: Option<Duration>,
+    /// The fraction of the reliable messages that had to be resent, an estimate of the packet loss.
+    pub loss: f32,
+    /// The datagrams dropped by the `NetConditions`.
+    pub dropped: u64,
+    /// The traffic of each channel, see `channel_name`.
+    pub channels: BTreeMap<String, Traffic>,
+    /// The number of components sent in the snapshots, by component type.
+    pub replicated: BTreeMap<&'static str, u64>,
+}
+
+impl NetSample {
+    /// Get the total traffic of the channels.
+    pub fn total(&self) -> Traffic {
+        self.channels
+            .values()
+            .fold(Traffic::default(), |total, traffic| Traffic {
+                bytes_in: total.bytes_in + traffic.bytes_in,
+                bytes_out: total.bytes_out + traffic.bytes_out,
+                packets_in: total.packets_in + traffic.packets_in,
+                packets_out: total.packets_out + traffic.packets_out,
+            })
+    }
+
+    pub(crate) fn record_in(&mut self, message: &Message, bytes: usize) {
+        let traffic = self.channels.entry(channel_name(message)).or_default();
+        traffic.bytes_in += bytes as u64;
+        traffic.packets_in += 1;
+    }
+
+    pub(crate) fn record_out(&mut self, message: &Message, bytes: usize) {
+        let traffic = self.channels.entry(channel_name(message)).or_default();
+        traffic.bytes_out += bytes as u64;
+        traffic.packets_out += 1;
+
+        if let Message::Snapshot { entities, .. } = message {
+            self.record_replicated(entities);
+        }
+    }
+
+    fn record_replicated(&mut self, entities: &[EntityState]) {
+        for state in entities {
+            *self.replicated.entry("Pos3").or_default() += 1;
+            if state.velocity.is_some() {
+                *self.replicated.entry("Velocity").or_default() += 1;
+            }
+        }
+    }
+}
+
+/// Get the name of the channel a message is counted on.
+pub fn channel_name(message: &Message) -> String {
+    match message {
+        Message::Connect
+        | Message::Challenge { .. }
+        | Message::Response { .. }
+        | Message::Disconnect => "control".to_string(),
+        Message::Intent { .. } => "intent".to_string(),
+        Message::Snapshot { .. } => "snapshot".to_string(),
+        Message::Reliable { channel, .. } => format!("reliable {}", channel),
+        Message::Ack { .. } => "ack".to_string(),
+    }
+}
+
+/// A component the server records its network activity into, once per second.
+#[derive(Debug, Clone, Default)]
+pub struct NetStats {
+    history: VecDeque<NetSample>,
+}
+
+impl Component for NetStats {}
+
+impl NetStats {
+    pub fn push(&mut self, sample: NetSample) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(sample);
+    }
+
+    /// Get the recorded samples, the oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &NetSample> {
+        self.history.iter()
+    }
+
+    pub fn latest(&self) -> Option<&NetSample> {
+        self.history.back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample() {
+        let mut sample = NetSample::default();
+        let snapshot = Message::Snapshot {
+            tick: 1,
+            entities: vec![
+                EntityState {
+                    entity: 0,
+                    pos: [0.0; 3],
+                    rot: None,
+                    velocity: Some([1.0, 0.0, 0.0]),
+                },
+                EntityState {
+                    entity: 1,
+                    pos: [0.0; 3],
+                    rot: None,
+                    velocity: None,
+                },
+            ],
+        };
+        sample.record_out(&snapshot, 100);
+        sample.record_in(
+            &Message::Ack {
+                channel: 1,
+                sequence: 0,
+            },
+            6,
+        );
+        sample.record_in(
+            &Message::Intent {
+                tick: 1,
+                data: vec![0],
+            },
+            10,
+        );
+
+        assert_eq!(sample.channels["snapshot"].bytes_out, 100);
+        assert_eq!(sample.channels["ack"].packets_in, 1);
+        assert_eq!(sample.replicated["Pos3"], 2);
+        assert_eq!(sample.replicated["Velocity"], 1);
+        let total = sample.total();
+        assert_eq!((total.bytes_in, total.bytes_out), (16, 100));
+
+        let mut stats = NetStats::default();
+        for _ in 0..HISTORY_LEN + 5 {
+            stats.push(sample.clone());
+        }
+        assert_eq!(stats.history().count(), HISTORY_LEN);
+    }
+
+    #[test]
+    fn test_conditions() {
+        let mut conditions = NetConditions {
+            enabled: false,
+            latency: Duration::from_millis(50),
+            loss: 1.0,
+        };
+        assert_eq!(conditions.delay(), Duration::ZERO);
+        assert!(!conditions.drops());
+
+        conditions.enabled = true;
+        assert_eq!(conditions.delay(), Duration::from_millis(50));
+        assert!(conditions.drops());
+        conditions.loss = 0.0;
+        assert!(!conditions.drops());
+    }
+}
@@ -0,0 +1,5 @@
+pub mod channel;
+pub mod diagnostics;
+pub mod prediction;
+pub mod protocol;
+pub mod server;
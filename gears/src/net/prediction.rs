@@ -0,0 +1,361 @@
+use super::protocol::EntityState;
+use crate::ecs::components::Pos3;
+use cgmath::{InnerSpace, MetricSpace, Quaternion, Vector3, VectorSpace};
+use std::collections::VecDeque;
+
+/// A state that can be blended for prediction and interpolation.
+pub trait NetState: Clone {
+    /// Blend towards another state, `t` is in `0.0..=1.0`.
+    fn lerp(&self, other: &Self, t: f32) -> Self;
+    /// The size of the difference between two states, used to detect mispredictions.
+    fn error(&self, other: &Self) -> f32;
+}
+
+fn nlerp(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    let a = Quaternion::new(a[3], a[0], a[1], a[2]);
+    let b = Quaternion::new(b[3], b[0], b[1], b[2]);
+    let rot = a.nlerp(b, t);
+    [rot.v.x, rot.v.y, rot.v.z, rot.s]
+}
+
+impl NetState for Pos3 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            pos: self.pos.lerp(other.pos, t),
+            rot: match (self.rot, other.rot) {
+                (Some(a), Some(b)) => Some(a.nlerp(b, t)),
+                _ => other.rot,
+            },
+        }
+    }
+
+    fn error(&self, other: &Self) -> f32 {
+        self.pos.distance(other.pos)
+    }
+}
+
+impl NetState for EntityState {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            entity: other.entity,
+            pos: Vector3::from(self.pos)
+                .lerp(Vector3::from(other.pos), t)
+                .into(),
+            rot: match (self.rot, other.rot) {
+                (Some(a), Some(b)) => Some(nlerp(a, b, t)),
+                _ => other.rot,
+            },
+            velocity: match (self.velocity, other.velocity) {
+                (Some(a), Some(b)) => Some(Vector3::from(a).lerp(Vector3::from(b), t).into()),
+                _ => other.velocity,
+            },
+        }
+    }
+
+    fn error(&self, other: &Self) -> f32 {
+        (Vector3::from(self.pos) - Vector3::from(other.pos)).magnitude()
+    }
+}
+
+/// The inputs of the local player and the states predicted from them, keyed by the input sequence number.
+/// When the server acknowledges an input, the prediction is checked against the authoritative state,
+/// and on a misprediction the inputs not yet acknowledged are replayed on top of it.
+#[derive(Debug, Clone)]
+pub struct PredictionBuffer<S, I> {
+    entries: VecDeque<(u64, I, S)>,
+    capacity: usize,
+    /// The sequence number of the last input reconciled.
+    acknowledged: Option<u64>,
+    /// The largest error accepted without a correction.
+    pub tolerance: f32,
+}
+
+impl<S: NetState, I> PredictionBuffer<S, I> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            acknowledged: None,
+            tolerance: 0.01,
+        }
+    }
+
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Store an input and the state predicted after applying it, the oldest entry is dropped when full.
+    pub fn push(&mut self, sequence: u64, input: I, state: S) {
+        while self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((sequence, input, state));
+    }
+
+    /// Get the number of inputs not acknowledged by the server yet.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get the latest predicted state.
+    pub fn latest(&self) -> Option<&S> {
+        self.entries.back().map(|(_, _, state)| state)
+    }
+
+    /// Apply the authoritative state of an acknowledged input.
+    ///
+    /// # Arguments
+    ///
+    /// * `sequence` - The sequence number of the last input the server applied.
+    /// * `server_state` - The state of the server after applying that input.
+    /// * `simulate` - Applies an input to a state, the same way the prediction did.
+    ///
+    /// # Returns
+    ///
+    /// The corrected current state if the prediction was wrong, `None` if it was right.
+    /// `None` is also returned for an acknowledgement older than the pending inputs, e.g. a reordered or
+    /// a duplicated snapshot, which is ignored.
+    pub fn reconcile(
+        &mut self,
+        sequence: u64,
+        server_state: &S,
+        mut simulate: impl FnMut(&S, &I) -> S,
+    ) -> Option<S> {
+        let stale = self
+            .acknowledged
+            .is_some_and(|acknowledged| sequence <= acknowledged)
+            || self
+                .entries
+                .front()
+                .is_some_and(|(oldest, _, _)| sequence < *oldest);
+        if stale {
+            return None;
+        }
+        self.acknowledged = Some(sequence);
+
+        let predicted = self
+            .entries
+            .iter()
+            .find(|(entry_sequence, _, _)| *entry_sequence == sequence)
+            .map(|(_, _, state)| state.clone());
+        self.entries
+            .retain(|(entry_sequence, _, _)| *entry_sequence > sequence);
+
+        if predicted.is_some_and(|predicted| predicted.error(server_state) <= self.tolerance) {
+            return None;
+        }
+
+        // Replay the pending inputs from the authoritative state
+        let mut state = server_state.clone();
+        for (_, input, predicted) in self.entries.iter_mut() {
+            state = simulate(&state, input);
+            *predicted = state.clone();
+        }
+        Some(state)
+    }
+}
+
+/// Hides a correction by blending from the previously displayed state to the corrected one over time.
+#[derive(Debug, Clone)]
+pub struct CorrectionSmoother<S> {
+    /// The time to blend over in seconds.
+    pub duration: f32,
+    /// Errors larger than this are applied immediately, e.g. after a teleport.
+    pub snap_distance: f32,
+    from: Option<S>,
+    elapsed: f32,
+}
+
+impl<S: NetState> CorrectionSmoother<S> {
+    pub fn new(duration: f32, snap_distance: f32) -> Self {
+        Self {
+            duration,
+            snap_distance,
+            from: None,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Start blending away from the state displayed before the correction.
+    pub fn correct(&mut self, displayed: &S, corrected: &S) {
+        if displayed.error(corrected) > self.snap_distance {
+            self.from = None;
+            return;
+        }
+        self.from = Some(displayed.clone());
+        self.elapsed = 0.0;
+    }
+
+    pub fn is_smoothing(&self) -> bool {
+        self.from.is_some()
+    }
+
+    /// Get the state to display this frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `current` - The current (corrected and predicted) state.
+    /// * `dt` - The delta time in seconds.
+    pub fn smooth(&mut self, current: &S, dt: f32) -> S {
+        let Some(from) = &self.from else {
+            return current.clone();
+        };
+
+        self.elapsed += dt;
+        let t = if self.duration > 0.0 {
+            (self.elapsed / self.duration).min(1.0)
+        } else {
+            1.0
+        };
+        let state = from.lerp(current, t);
+        if t >= 1.0 {
+            self.from = None;
+        }
+        state
+    }
+}
+
+/// Buffers the snapshots of a remote entity and samples it slightly in the past,
+/// so it moves smoothly between the snapshots.
+/// The server can keep one per entity too, to rewind the world to the time a client saw it (lag compensation).
+#[derive(Debug, Clone)]
+pub struct InterpolationBuffer<S> {
+    snapshots: VecDeque<(f64, S)>,
+    capacity: usize,
+}
+
+impl<S: NetState> InterpolationBuffer<S> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(2),
+        }
+    }
+
+    /// Add the state of a point in time, out of order snapshots older than the latest are dropped.
+    pub fn push(&mut self, time: f64, state: S) {
+        if self.snapshots.back().is_some_and(|(last, _)| time <= *last) {
+            return;
+        }
+        while self.snapshots.len() >= self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back((time, state));
+    }
+
+    /// Get the time of the latest snapshot.
+    pub fn latest_time(&self) -> Option<f64> {
+        self.snapshots.back().map(|(time, _)| *time)
+    }
+
+    /// Get the state at a point in time.
+    /// Before the first snapshot the first one is held, after the last one the last one is held.
+    pub fn sample(&self, time: f64) -> Option<S> {
+        let index = self.snapshots.partition_point(|(t, _)| *t <= time);
+        match (
+            index.checked_sub(1).and_then(|i| self.snapshots.get(i)),
+            self.snapshots.get(index),
+        ) {
+            (Some((t0, a)), Some((t1, b))) => Some(a.lerp(b, ((time - t0) / (t1 - t0)) as f32)),
+            (Some((_, a)), None) => Some(a.clone()),
+            (None, Some((_, b))) => Some(b.clone()),
+            (None, None) => None,
+        }
+    }
+
+    /// Get the state at the latest snapshot time minus a delay.
+    /// A delay of two or three snapshot intervals keeps a snapshot to interpolate to when one is lost.
+    pub fn sample_delayed(&self, delay: f64) -> Option<S> {
+        self.sample(self.latest_time()? - delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector3;
+
+    fn pos(x: f32) -> Pos3 {
+        Pos3::new(Vector3::new(x, 0.0, 0.0))
+    }
+
+    fn step(state: &Pos3, input: &f32) -> Pos3 {
+        pos(state.pos.x + input)
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let mut buffer = PredictionBuffer::new(64);
+        let mut state = pos(0.0);
+        for sequence in 1..=4 {
+            state = step(&state, &1.0);
+            buffer.push(sequence, 1.0, state);
+        }
+
+        // The server agrees with the prediction
+        assert!(buffer.reconcile(1, &pos(1.0), step).is_none());
+        assert_eq!(buffer.len(), 3);
+
+        // The server was blocked at input 2, the remaining inputs are replayed on its state
+        let corrected = buffer.reconcile(2, &pos(1.5), step).unwrap();
+        assert_eq!(corrected.pos.x, 3.5);
+        assert_eq!(buffer.latest().unwrap().pos.x, 3.5);
+        assert_eq!(buffer.len(), 2);
+    }
+
+    #[test]
+    fn test_reconcile_stale_acknowledgement() {
+        let mut buffer = PredictionBuffer::new(64);
+        let mut state = pos(0.0);
+        for sequence in 1..=4 {
+            state = step(&state, &1.0);
+            buffer.push(sequence, 1.0, state);
+        }
+        assert!(buffer.reconcile(2, &pos(2.0), step).is_none());
+
+        // A reordered snapshot neither replays nor drops the pending inputs
+        assert!(buffer.reconcile(1, &pos(0.0), step).is_none());
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.latest().unwrap().pos.x, 4.0);
+
+        // A duplicated one is ignored as well, even with nothing pending
+        assert!(buffer.reconcile(4, &pos(4.0), step).is_none());
+        assert!(buffer.is_empty());
+        assert!(buffer.reconcile(4, &pos(0.0), step).is_none());
+
+        // The inputs evicted from a full buffer leave the oldest pending one as the limit
+        let mut buffer = PredictionBuffer::new(2);
+        for sequence in 1..=3 {
+            buffer.push(sequence, 1.0, pos(sequence as f32));
+        }
+        assert!(buffer.reconcile(1, &pos(0.0), step).is_none());
+        assert_eq!(buffer.len(), 2);
+    }
+
+    #[test]
+    fn test_smoothing_and_interpolation() {
+        let mut smoother = CorrectionSmoother::new(0.1, 5.0);
+        smoother.correct(&pos(0.0), &pos(1.0));
+        assert!((smoother.smooth(&pos(1.0), 0.05).pos.x - 0.5).abs() < 1e-5);
+        assert_eq!(smoother.smooth(&pos(1.0), 0.05).pos.x, 1.0);
+        assert!(!smoother.is_smoothing());
+        smoother.correct(&pos(0.0), &pos(10.0));
+        assert!(!smoother.is_smoothing());
+
+        let mut buffer = InterpolationBuffer::new(8);
+        buffer.push(0.0, pos(0.0));
+        buffer.push(0.1, pos(1.0));
+        buffer.push(0.05, pos(9.0));
+        buffer.push(0.2, pos(3.0));
+
+        assert_eq!(buffer.sample(-1.0).unwrap().pos.x, 0.0);
+        assert!((buffer.sample(0.15).unwrap().pos.x - 2.0).abs() < 1e-5);
+        assert!((buffer.sample_delayed(0.15).unwrap().pos.x - 0.5).abs() < 1e-5);
+        assert_eq!(buffer.sample(1.0).unwrap().pos.x, 3.0);
+    }
+}
@@ -0,0 +1,304 @@
+use anyhow::Context;
+
+/// The largest datagram the server sends, kept under the common MTU to avoid fragmentation.
+pub const MAX_DATAGRAM_SIZE: usize = 1200;
+
+/// The replicated state of an entity.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EntityState {
+    pub entity: u32,
+    pub pos: [f32; 3],
+    pub rot: Option<[f32; 4]>,
+    pub velocity: Option<[f32; 3]>,
+}
+
+impl EntityState {
+    /// The largest encoded size of an entity state.
+    const MAX_SIZE: usize = 4 + 12 + 1 + 16 + 1 + 12;
+}
+
+/// A message between the server and its clients.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// Sent by a client to join the server, padded to the size of the `Challenge` it is answered with,
+    /// so a spoofed address can not be sent more than it was sent.
+    Connect,
+    /// The answer of the server to a `Connect`, the client sends the token back to prove its address.
+    Challenge { token: u64 },
+    /// Sent by a client with the token of its `Challenge` and the name to join with,
+    /// the server only then accepts the client.
+    Response { token: u64, name: String },
+    /// Sent by a client to leave the server.
+    Disconnect,
+    /// The input of a client for a tick, the payload is defined by the game.
+    Intent { tick: u64, data: Vec<u8> },
+    /// A part of the world state of a tick, large snapshots are split into several messages.
+    Snapshot {
+        tick: u64,
+        entities: Vec<EntityState>,
+    },
+    /// A message of a reliable channel, resent until it is acknowledged.
+    Reliable {
+        channel: u8,
+        sequence: u32,
+        data: Vec<u8>,
+    },
+    /// Acknowledges a reliable message.
+    Ack { channel: u8, sequence: u32 },
+}
+
+impl Message {
+    const CONNECT: u8 = 0;
+    const DISCONNECT: u8 = 1;
+    const INTENT: u8 = 2;
+    const SNAPSHOT: u8 = 3;
+    const RELIABLE: u8 = 4;
+    const ACK: u8 = 5;
+    const CHALLENGE: u8 = 6;
+    const RESPONSE: u8 = 7;
+
+    /// The size of an encoded `Connect`, the server ignores the smaller ones.
+    pub const CONNECT_SIZE: usize = 1 + 8;
+
+    /// The number of entity states fitting into a single snapshot datagram.
+    pub const SNAPSHOT_CHUNK: usize = (MAX_DATAGRAM_SIZE - 1 - 8 - 2) / EntityState::MAX_SIZE;
+    /// The largest payload of a reliable message.
+    pub const MAX_RELIABLE_SIZE: usize = MAX_DATAGRAM_SIZE - 1 - 1 - 4;
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            Self::Connect => {
+                out.push(Self::CONNECT);
+                out.resize(Self::CONNECT_SIZE, 0);
+            }
+            Self::Challenge { token } => {
+                out.push(Self::CHALLENGE);
+                out.extend_from_slice(&token.to_le_bytes());
+            }
+            Self::Response { token, name } => {
+                out.push(Self::RESPONSE);
+                out.extend_from_slice(&token.to_le_bytes());
+                out.extend_from_slice(name.as_bytes());
+            }
+            Self::Disconnect => out.push(Self::DISCONNECT),
+            Self::Intent { tick, data } => {
+                out.push(Self::INTENT);
+                out.extend_from_slice(&tick.to_le_bytes());
+                out.extend_from_slice(data);
+            }
+            Self::Snapshot { tick, entities } => {
+                out.push(Self::SNAPSHOT);
+                out.extend_from_slice(&tick.to_le_bytes());
+                out.extend_from_slice(&(entities.len() as u16).to_le_bytes());
+                for state in entities {
+                    out.extend_from_slice(&state.entity.to_le_bytes());
+                    write_floats(&mut out, &state.pos);
+                    write_optional_floats(&mut out, state.rot.as_ref().map(|rot| rot.as_slice()));
+                    write_optional_floats(
+                        &mut out,
+                        state.velocity.as_ref().map(|velocity| velocity.as_slice()),
+                    );
+                }
+            }
+            Self::Reliable {
+                channel,
+                sequence,
+                data,
+            } => {
+                out.push(Self::RELIABLE);
+                out.push(*channel);
+                out.extend_from_slice(&sequence.to_le_bytes());
+                out.extend_from_slice(data);
+            }
+            Self::Ack { channel, sequence } => {
+                out.push(Self::ACK);
+                out.push(*channel);
+                out.extend_from_slice(&sequence.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut reader = Reader(bytes);
+        let message = match reader.u8()? {
+            Self::CONNECT => Self::Connect,
+            Self::CHALLENGE => Self::Challenge {
+                token: reader.u64()?,
+            },
+            Self::RESPONSE => Self::Response {
+                token: reader.u64()?,
+                name: String::from_utf8_lossy(reader.rest()).into_owned(),
+            },
+            Self::DISCONNECT => Self::Disconnect,
+            Self::INTENT => Self::Intent {
+                tick: reader.u64()?,
+                data: reader.rest().to_vec(),
+            },
+            Self::SNAPSHOT => {
+                let tick = reader.u64()?;
+                let count = reader.u16()?;
+                let entities = (0..count)
+                    .map(|_| {
+                        Ok(EntityState {
+                            entity: reader.u32()?,
+                            pos: reader.floats()?,
+                            rot: reader.optional_floats()?,
+                            velocity: reader.optional_floats()?,
+                        })
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                Self::Snapshot { tick, entities }
+            }
+            Self::RELIABLE => Self::Reliable {
+                channel: reader.u8()?,
+                sequence: reader.u32()?,
+                data: reader.rest().to_vec(),
+            },
+            Self::ACK => Self::Ack {
+                channel: reader.u8()?,
+                sequence: reader.u32()?,
+            },
+            tag => anyhow::bail!("Unknown message type {}", tag),
+        };
+        Ok(message)
+    }
+}
+
+fn write_floats(out: &mut Vec<u8>, values: &[f32]) {
+    for value in values {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn write_optional_floats(out: &mut Vec<u8>, values: Option<&[f32]>) {
+    match values {
+        Some(values) => {
+            out.push(1);
+            write_floats(out, values);
+        }
+        None => out.push(0),
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        let (head, rest) = self
+            .0
+            .split_first_chunk::<N>()
+            .context("The message is truncated")?;
+        self.0 = rest;
+        Ok(*head)
+    }
+
+    fn rest(&mut self) -> &[u8] {
+        std::mem::take(&mut self.0)
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn u64(&mut self) -> anyhow::Result<u64> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn floats<const N: usize>(&mut self) -> anyhow::Result<[f32; N]> {
+        let mut values = [0.0; N];
+        for value in &mut values {
+            *value = f32::from_le_bytes(self.take()?);
+        }
+        Ok(values)
+    }
+
+    fn optional_floats<const N: usize>(&mut self) -> anyhow::Result<Option<[f32; N]>> {
+        match self.u8()? {
+            0 => Ok(None),
+            _ => self.floats().map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let messages = [
+            Message::Connect,
+            Message::Challenge { token: u64::MAX },
+            Message::Response {
+                token: 12,
+                name: "Player".to_string(),
+            },
+            Message::Disconnect,
+            Message::Intent {
+                tick: 7,
+                data: vec![1, 2, 3],
+            },
+            Message::Snapshot {
+                tick: 42,
+                entities: vec![
+                    EntityState {
+                        entity: 3,
+                        pos: [1.0, 2.0, 3.0],
+                        rot: Some([0.0, 0.0, 0.0, 1.0]),
+                        velocity: None,
+                    },
+                    EntityState {
+                        entity: 9,
+                        pos: [-1.0, 0.5, 0.0],
+                        rot: None,
+                        velocity: Some([0.0, -9.81, 0.0]),
+                    },
+                ],
+            },
+            Message::Reliable {
+                channel: 1,
+                sequence: 300,
+                data: b"hello".to_vec(),
+            },
+            Message::Ack {
+                channel: 1,
+                sequence: 300,
+            },
+        ];
+
+        for message in messages {
+            assert_eq!(Message::decode(&message.encode()).unwrap(), message);
+        }
+        assert!(Message::decode(&[Message::INTENT, 1, 2]).is_err());
+        assert!(Message::decode(&[200]).is_err());
+        assert_eq!(
+            Message::Connect.encode().len(),
+            Message::Challenge { token: 0 }.encode().len()
+        );
+    }
+
+    #[test]
+    fn test_snapshot_chunk_fits_a_datagram() {
+        let state = EntityState {
+            entity: u32::MAX,
+            pos: [0.0; 3],
+            rot: Some([0.0; 4]),
+            velocity: Some([0.0; 3]),
+        };
+        let message = Message::Snapshot {
+            tick: u64::MAX,
+            entities: vec![state; Message::SNAPSHOT_CHUNK],
+        };
+        assert!(message.encode().len() <= MAX_DATAGRAM_SIZE);
+    }
+}
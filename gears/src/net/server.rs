@@ -0,0 +1,812 @@
+use super::channel::{self, Channels, ChatMessage, NetMessage, SendNetMessage, CHAT_CHANNEL};
+use super::diagnostics::{NetConditions, NetSample, NetStats};
+use super::protocol::{EntityState, Message, MAX_DATAGRAM_SIZE};
+use crate::core::Dt;
+use crate::ecs::components::{Pos3, Velocity};
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use instant::{Duration, Instant};
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::hash::BuildHasher;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::net::UdpSocket;
+use tokio::sync::broadcast;
+
+/// How long the token of a challenge is accepted, it is accepted for up to twice as long.
+const CHALLENGE_LIFETIME: Duration = Duration::from_secs(10);
+/// The longest name of a client in bytes, a longer one is cut.
+const MAX_NAME_LEN: usize = 32;
+
+/// Runs the app as a dedicated server, see `Server`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ServerConfig {
+    pub address: SocketAddr,
+    /// The number of simulation ticks per second.
+    pub tick_rate: f32,
+    /// Broadcast a snapshot every this many ticks.
+    pub snapshot_interval: u32,
+    /// Drop the clients which have not sent anything for this long.
+    pub client_timeout: Duration,
+    /// The most clients joined at once, the others are refused until one leaves.
+    pub max_clients: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: SocketAddr::from(([0, 0, 0, 0], 7777)),
+            tick_rate: 60.0,
+            snapshot_interval: 3,
+            client_timeout: Duration::from_secs(10),
+            max_clients: 32,
+        }
+    }
+}
+
+/// Marks an entity to be sent to the clients in the snapshots.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Replicated;
+
+impl Component for Replicated {}
+
+crate::impl_marker!(Replicated, "is sent to the clients in the snapshots");
+
+/// A client of the server, identified by its address.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ClientId(pub SocketAddr);
+
+/// Sent when a client joins the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientConnected {
+    pub client: ClientId,
+    /// The name the chat of the client is sent with, unique among the joined clients.
+    pub name: String,
+}
+
+/// Sent when a client leaves the server or times out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ClientDisconnected {
+    pub client: ClientId,
+}
+
+/// Sent for each input received from a client, the systems decide how to apply it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientIntent {
+    pub client: ClientId,
+    /// The tick of the client the input was made on.
+    pub tick: u64,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+struct ClientState {
+    name: String,
+    last_seen: Instant,
+    channels: Channels,
+    /// The counters of the channels at the last sample.
+    sampled: (u64, u64),
+}
+
+impl ClientState {
+    fn new(name: String, now: Instant) -> Self {
+        Self {
+            name,
+            last_seen: now,
+            channels: Channels::default(),
+            sampled: (0, 0),
+        }
+    }
+}
+
+/// The server side of the network layer.
+/// Each tick it turns the received messages into events, advances the systems by a fixed step
+/// and broadcasts the state of the `Replicated` entities to the clients.
+/// The reliable channels are flushed every tick, the chat is relayed to the other clients.
+///
+/// A client joins with a handshake: it sends a `Connect`, the server answers with a `Challenge`,
+/// and the client is only accepted once it sends the token of the challenge back in a `Response`.
+/// Until then nothing else it sends is read, so a spoofed address can not join or be sent the snapshots.
+///
+/// The network activity is recorded into the `NetStats` components every second,
+/// and a `NetConditions` component adds latency and packet loss.
+pub struct Server {
+    config: ServerConfig,
+    socket: UdpSocket,
+    clients: HashMap<ClientId, ClientState>,
+    /// The acknowledgements and the challenges to send on the next flush.
+    replies: Vec<(ClientId, Message)>,
+    /// The key of the challenge tokens, so they can be checked without remembering them.
+    secret: RandomState,
+    started: Instant,
+    tick: u64,
+    conditions: NetConditions,
+    /// The datagrams held back by the latency, in the order they are due.
+    delayed: VecDeque<(Instant, ClientId, Vec<u8>)>,
+    sample: NetSample,
+    sampled: Instant,
+}
+
+impl Server {
+    pub async fn bind(config: ServerConfig) -> anyhow::Result<Self> {
+        let socket = UdpSocket::bind(config.address).await?;
+        log::info!("[Server] Listening on {}", socket.local_addr()?);
+
+        Ok(Self {
+            config,
+            socket,
+            clients: HashMap::new(),
+            replies: Vec::new(),
+            secret: RandomState::new(),
+            started: Instant::now(),
+            tick: 0,
+            conditions: NetConditions::default(),
+            delayed: VecDeque::new(),
+            sample: NetSample::default(),
+            sampled: Instant::now(),
+        })
+    }
+
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    pub fn clients(&self) -> impl Iterator<Item = ClientId> + '_ {
+        self.clients.keys().copied()
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Run the server loop until the app is stopped.
+    ///
+    /// # Arguments
+    ///
+    /// * `ecs` - The entity component system manager.
+    /// * `tx_dt` - The delta time channel driving the update loops of the app.
+    /// * `is_running` - The flag stopping the loop.
+    pub async fn run(
+        mut self,
+        ecs: Arc<Mutex<ecs::Manager>>,
+        tx_dt: broadcast::Sender<Dt>,
+        is_running: Arc<AtomicBool>,
+    ) -> anyhow::Result<()> {
+        // The simulation always advances by the same step, regardless of how late a tick is
+        let dt = Duration::from_secs_f64(1.0 / self.config.tick_rate.max(1.0) as f64);
+        let mut interval = tokio::time::interval(dt);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        while is_running.load(Ordering::Relaxed) {
+            interval.tick().await;
+
+            self.conditions = ecs
+                .lock()
+                .unwrap()
+                .get_all_components_of_type::<NetConditions>()
+                .pop()
+                .map(|(_, conditions)| *conditions.read().unwrap())
+                .unwrap_or_default();
+            self.receive(&ecs.lock().unwrap(), Instant::now());
+
+            if let Err(e) = tx_dt.send(dt) {
+                log::warn!("Failed to send delta time: {:?}", e);
+            }
+            self.tick += 1;
+
+            if self
+                .tick
+                .is_multiple_of(self.config.snapshot_interval.max(1) as u64)
+            {
+                let entities = snapshot(&ecs.lock().unwrap());
+                self.broadcast(entities).await;
+            }
+
+            let outgoing = ecs.lock().unwrap().drain_events::<SendNetMessage>();
+            self.queue(outgoing);
+            self.flush(Instant::now()).await;
+            self.record(&ecs.lock().unwrap(), Instant::now());
+        }
+
+        log::info!("[Server] Stopped at tick {}", self.tick);
+        Ok(())
+    }
+
+    /// Read the pending messages without waiting and send them to the systems as events.
+    fn receive(&mut self, ecs: &ecs::Manager, now: Instant) {
+        let mut buffer = [0u8; MAX_DATAGRAM_SIZE];
+
+        loop {
+            let (len, address) = match self.socket.try_recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    log::warn!("[Server] Failed to receive: {}", e);
+                    break;
+                }
+            };
+            let client = ClientId(address);
+
+            let message = match Message::decode(&buffer[..len]) {
+                Ok(message) => message,
+                Err(e) => {
+                    log::warn!("[Server] Invalid message from {}: {}", address, e);
+                    continue;
+                }
+            };
+            if self.conditions.drops() {
+                self.sample.dropped += 1;
+                continue;
+            }
+            self.sample.record_in(&message, len);
+
+            let Some(state) = self.clients.get_mut(&client) else {
+                self.handshake(ecs, client, message, len, now);
+                continue;
+            };
+            state.last_seen = now;
+
+            match message {
+                Message::Disconnect => {
+                    self.clients.remove(&client);
+                    ecs.send_event(ClientDisconnected { client });
+                }
+                Message::Intent { tick, data } => {
+                    ecs.send_event(ClientIntent { client, tick, data });
+                }
+                Message::Reliable { .. } | Message::Ack { .. } => {
+                    let (ack, delivered) = match state.channels.handle(message) {
+                        Ok(handled) => handled,
+                        Err(e) => {
+                            log::warn!("[Server] Dropping the client {}: {}", client.0, e);
+                            self.clients.remove(&client);
+                            ecs.send_event(ClientDisconnected { client });
+                            continue;
+                        }
+                    };
+                    self.replies.extend(ack.map(|ack| (client, ack)));
+                    for (channel, data) in delivered {
+                        if channel == CHAT_CHANNEL {
+                            self.relay_chat(ecs, client, &data);
+                        }
+                        ecs.send_event(NetMessage {
+                            client,
+                            channel,
+                            data,
+                        });
+                    }
+                }
+                // Clients only receive the snapshots, and a joined client has no handshake left
+                _ => {}
+            }
+        }
+
+        let timeout = self.config.client_timeout;
+        self.clients.retain(|client, state| {
+            let alive = now.duration_since(state.last_seen) < timeout;
+            if !alive {
+                log::info!("[Server] Client {} timed out", client.0);
+                ecs.send_event(ClientDisconnected { client: *client });
+            }
+            alive
+        });
+    }
+
+    /// Answer the handshake of an address which has not joined, the other messages of it are ignored.
+    fn handshake(
+        &mut self,
+        ecs: &ecs::Manager,
+        client: ClientId,
+        message: Message,
+        len: usize,
+        now: Instant,
+    ) {
+        match message {
+            // The padding keeps the challenge from being larger than the request
+            Message::Connect if len >= Message::CONNECT_SIZE => {
+                let token = self.token(client, now, 0);
+                self.replies.push((client, Message::Challenge { token }));
+            }
+            Message::Response { token, name } => {
+                if !(0..2).any(|age| self.token(client, now, age) == token) {
+                    log::debug!("[Server] Invalid challenge response from {}", client.0);
+                    return;
+                }
+                if self.clients.len() >= self.config.max_clients {
+                    log::warn!("[Server] Refusing {}, the server is full", client.0);
+                    return;
+                }
+                let name = self.unique_name(&name);
+                log::info!("[Server] Client {} connected as {}", client.0, name);
+                ecs.send_event(ClientConnected {
+                    client,
+                    name: name.clone(),
+                });
+                self.clients.insert(client, ClientState::new(name, now));
+            }
+            _ => {}
+        }
+    }
+
+    /// Get the name a client joins with, cut to `MAX_NAME_LEN` and numbered if another client has it.
+    fn unique_name(&self, requested: &str) -> String {
+        let requested = channel::truncate(requested.trim(), MAX_NAME_LEN).trim_end();
+        let requested = if requested.is_empty() {
+            "Player"
+        } else {
+            requested
+        };
+        let taken = |name: &str| self.clients.values().any(|state| state.name == name);
+
+        let mut name = requested.to_string();
+        let mut number = 1;
+        while taken(&name) {
+            number += 1;
+            name = format!("{} ({})", requested, number);
+        }
+        name
+    }
+
+    /// Get the challenge token of an address, it changes every `CHALLENGE_LIFETIME`.
+    /// An `age` of 1 gets the token of the previous period, which is still accepted.
+    fn token(&self, client: ClientId, now: Instant, age: u64) -> u64 {
+        let period = now.duration_since(self.started).as_secs() / CHALLENGE_LIFETIME.as_secs();
+        self.secret.hash_one((client, period.wrapping_sub(age)))
+    }
+
+    /// Send a chat message to the systems and to the other clients.
+    /// The sender is replaced with the name of the client, so a client can not speak as another one.
+    fn relay_chat(&mut self, ecs: &ecs::Manager, from: ClientId, data: &[u8]) {
+        let mut message = match ChatMessage::decode(data) {
+            Ok(message) => message,
+            Err(e) => {
+                log::warn!("[Server] Invalid chat message from {}: {}", from.0, e);
+                return;
+            }
+        };
+        let Some(sender) = self.clients.get(&from) else {
+            return;
+        };
+        message.sender.clone_from(&sender.name);
+        let data = message.encode();
+
+        for (client, state) in self.clients.iter_mut() {
+            if *client != from {
+                if let Err(e) = state.channels.send(CHAT_CHANNEL, data.clone()) {
+                    log::warn!("[Server] Failed to relay the chat to {}: {}", client.0, e);
+                }
+            }
+        }
+        ecs.send_event(message);
+    }
+
+    /// Queue the messages of the systems on the reliable channels.
+    fn queue(&mut self, messages: Vec<SendNetMessage>) {
+        for message in messages {
+            for (client, state) in self.clients.iter_mut() {
+                if message.to.is_none_or(|to| to == *client) {
+                    if let Err(e) = state.channels.send(message.channel, message.data.clone()) {
+                        log::warn!("[Server] Failed to queue a message to {}: {}", client.0, e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Send the acknowledgements, the new or resent reliable messages and the delayed datagrams which are due.
+    async fn flush(&mut self, now: Instant) {
+        let mut messages = std::mem::take(&mut self.replies);
+        for (client, state) in self.clients.iter_mut() {
+            messages.extend(
+                state
+                    .channels
+                    .poll(now)
+                    .into_iter()
+                    .map(|message| (*client, message)),
+            );
+        }
+
+        for (client, message) in messages {
+            self.send(client, &message, now).await;
+        }
+
+        while self.delayed.front().is_some_and(|(due, _, _)| *due <= now) {
+            let (_, client, datagram) = self.delayed.pop_front().unwrap();
+            if let Err(e) = self.socket.send_to(&datagram, client.0).await {
+                log::warn!("[Server] Failed to send to {}: {}", client.0, e);
+            }
+        }
+    }
+
+    /// Send a message to a client, through the simulated network conditions.
+    async fn send(&mut self, client: ClientId, message: &Message, now: Instant) {
+        let datagram = message.encode();
+        self.sample.record_out(message, datagram.len());
+        if self.conditions.drops() {
+            self.sample.dropped += 1;
+            return;
+        }
+
+        let delay = self.conditions.delay();
+        // Keep the order of the datagrams when the latency is turned off
+        if delay.is_zero() && self.delayed.is_empty() {
+            if let Err(e) = self.socket.send_to(&datagram, client.0).await {
+                log::warn!("[Server] Failed to send to {}: {}", client.0, e);
+            }
+        } else {
+            self.delayed.push_back((now + delay, client, datagram));
+        }
+    }
+
+    /// Push the network activity to the `NetStats` components once per second.
+    fn record(&mut self, ecs: &ecs::Manager, now: Instant) {
+        if now.duration_since(self.sampled) < Duration::from_secs(1) {
+            return;
+        }
+        self.sampled = now;
+
+        let (mut sent, mut resent) = (0, 0);
+        let mut rtts = Vec::new();
+        for state in self.clients.values_mut() {
+            let counters = state.channels.counters();
+            sent += counters.0 - state.sampled.0;
+            resent += counters.1 - state.sampled.1;
+            state.sampled = counters;
+            rtts.extend(state.channels.rtt());
+        }
+        let mut sample = std::mem::take(&mut self.sample);
+        if sent > 0 {
+            sample.loss = resent as f32 / sent as f32;
+        }
+        if !rtts.is_empty() {
+            sample.rtt = Some(rtts.iter().sum::<Duration>() / rtts.len() as u32);
+        }
+
+        for (_, stats) in ecs.get_all_components_of_type::<NetStats>() {
+            stats.write().unwrap().push(sample.clone());
+        }
+    }
+
+    /// Send the snapshot of the current tick to every client.
+    async fn broadcast(&mut self, entities: Vec<EntityState>) {
+        // An empty world still sends a message, so the clients know the tick
+        let chunks = entities
+            .chunks(Message::SNAPSHOT_CHUNK)
+            .map(<[EntityState]>::to_vec)
+            .collect::<Vec<_>>();
+        let chunks = if chunks.is_empty() {
+            vec![Vec::new()]
+        } else {
+            chunks
+        };
+
+        let now = Instant::now();
+        let clients = self.clients.keys().copied().collect::<Vec<_>>();
+        for entities in chunks {
+            let message = Message::Snapshot {
+                tick: self.tick,
+                entities,
+            };
+            for client in &clients {
+                self.send(*client, &message, now).await;
+            }
+        }
+    }
+}
+
+/// Get the state of the `Replicated` entities in the order of the entities.
+pub fn snapshot(ecs: &ecs::Manager) -> Vec<EntityState> {
+    let mut entities: Vec<Entity> = ecs.get_entites_with_component::<Replicated>();
+    entities.sort_by_key(|entity| entity.id());
+
+    entities
+        .into_iter()
+        .filter_map(|entity| {
+            let pos = *ecs
+                .get_component_from_entity::<Pos3>(entity)?
+                .read()
+                .unwrap();
+            let velocity = ecs
+                .get_component_from_entity::<Velocity>(entity)
+                .map(|velocity| velocity.read().unwrap().0.into());
+
+            Some(EntityState {
+                entity: entity.id(),
+                pos: pos.pos.into(),
+                rot: pos.rot.map(|rot| [rot.v.x, rot.v.y, rot.v.z, rot.s]),
+                velocity,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector3;
+
+    /// Receive on the server until the condition holds.
+    async fn receive_until(
+        server: &mut Server,
+        ecs: &ecs::Manager,
+        done: impl Fn(&Server) -> bool,
+    ) {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !done(server) {
+            assert!(Instant::now() < deadline);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            server.receive(ecs, Instant::now());
+        }
+    }
+
+    /// Send a connect and answer the challenge of the server.
+    async fn join(server: &mut Server, ecs: &ecs::Manager, client: &UdpSocket, name: &str) {
+        let server_addr = server.local_addr().unwrap();
+        let connect = Message::Connect.encode();
+        assert_eq!(connect.len(), Message::CONNECT_SIZE);
+        client.send_to(&connect, server_addr).await.unwrap();
+        receive_until(server, ecs, |server| !server.replies.is_empty()).await;
+        server.flush(Instant::now()).await;
+
+        let mut buffer = [0u8; MAX_DATAGRAM_SIZE];
+        let len = client.recv(&mut buffer).await.unwrap();
+        assert_eq!(len, connect.len());
+        let token = match Message::decode(&buffer[..len]).unwrap() {
+            Message::Challenge { token } => token,
+            message => panic!("Expected a challenge, got {:?}", message),
+        };
+        let joined = server.clients.len() + 1;
+        let response = Message::Response {
+            token,
+            name: name.to_string(),
+        };
+        client
+            .send_to(&response.encode(), server_addr)
+            .await
+            .unwrap();
+        receive_until(server, ecs, |server| server.clients.len() == joined).await;
+    }
+
+    #[tokio::test]
+    async fn test_intents_and_snapshots() {
+        let ecs = ecs::Manager::default();
+        let player = ecs.create_entity();
+        ecs.add_component_to_entity(player, Pos3::new(Vector3::new(1.0, 2.0, 3.0)));
+        ecs.add_component_to_entity(player, Replicated);
+        let hidden = ecs.create_entity();
+        ecs.add_component_to_entity(hidden, Pos3::default());
+
+        let mut server = Server::bind(ServerConfig {
+            address: SocketAddr::from(([127, 0, 0, 1], 0)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_id = ClientId(client.local_addr().unwrap());
+        let server_addr = server.local_addr().unwrap();
+
+        join(&mut server, &ecs, &client, "Player").await;
+        let intent = Message::Intent {
+            tick: 5,
+            data: vec![1, 0],
+        };
+        client.send_to(&intent.encode(), server_addr).await.unwrap();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        loop {
+            assert!(Instant::now() < deadline);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            server.receive(&ecs, Instant::now());
+            if ecs.has_events::<ClientIntent>() {
+                break;
+            }
+        }
+
+        assert_eq!(server.clients().collect::<Vec<_>>(), vec![client_id]);
+        assert_eq!(
+            ecs.drain_events::<ClientConnected>(),
+            vec![ClientConnected {
+                client: client_id,
+                name: "Player".to_string(),
+            }]
+        );
+        assert_eq!(
+            ecs.drain_events::<ClientIntent>(),
+            vec![ClientIntent {
+                client: client_id,
+                tick: 5,
+                data: vec![1, 0],
+            }]
+        );
+
+        server.broadcast(snapshot(&ecs)).await;
+        let mut buffer = [0u8; MAX_DATAGRAM_SIZE];
+        let len = client.recv(&mut buffer).await.unwrap();
+        match Message::decode(&buffer[..len]).unwrap() {
+            Message::Snapshot { entities, .. } => {
+                assert_eq!(entities.len(), 1);
+                assert_eq!(entities[0].entity, player.id());
+                assert_eq!(entities[0].pos, [1.0, 2.0, 3.0]);
+            }
+            message => panic!("Expected a snapshot, got {:?}", message),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_timeout() {
+        let mut server = Server::bind(ServerConfig {
+            address: SocketAddr::from(([127, 0, 0, 1], 0)),
+            client_timeout: Duration::from_secs(1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let ecs = ecs::Manager::default();
+        let client = ClientId(SocketAddr::from(([127, 0, 0, 1], 1234)));
+        let now = Instant::now();
+        server
+            .clients
+            .insert(client, ClientState::new("Player".to_string(), now));
+
+        server.receive(&ecs, now + Duration::from_millis(500));
+        assert!(ecs.drain_events::<ClientDisconnected>().is_empty());
+
+        server.receive(&ecs, now + Duration::from_secs(2));
+        assert_eq!(
+            ecs.drain_events::<ClientDisconnected>(),
+            vec![ClientDisconnected { client }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handshake() {
+        let ecs = ecs::Manager::default();
+        let mut server = Server::bind(ServerConfig {
+            address: SocketAddr::from(([127, 0, 0, 1], 0)),
+            max_clients: 1,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let first = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let second = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let second_id = ClientId(second.local_addr().unwrap());
+
+        // An address which has not answered a challenge is not registered
+        let intent = Message::Intent {
+            tick: 1,
+            data: vec![],
+        };
+        second.send_to(&intent.encode(), server_addr).await.unwrap();
+        let guess = Message::Response {
+            token: 0,
+            name: String::new(),
+        };
+        second.send_to(&guess.encode(), server_addr).await.unwrap();
+        // A connect without the padding gets no challenge
+        second.send_to(&[0], server_addr).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        server.receive(&ecs, Instant::now());
+        assert_eq!(server.clients.len(), 0);
+        assert!(server.replies.is_empty());
+        assert!(!ecs.has_events::<ClientIntent>());
+
+        join(&mut server, &ecs, &first, "Player").await;
+        assert_eq!(ecs.drain_events::<ClientConnected>().len(), 1);
+
+        // The token of another address is refused
+        let token = server.token(ClientId(first.local_addr().unwrap()), Instant::now(), 0);
+        let stolen = Message::Response {
+            token,
+            name: String::new(),
+        };
+        second.send_to(&stolen.encode(), server_addr).await.unwrap();
+        // A valid response is refused while the server is full
+        let token = server.token(second_id, Instant::now(), 0);
+        let response = Message::Response {
+            token,
+            name: String::new(),
+        };
+        second
+            .send_to(&response.encode(), server_addr)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        server.receive(&ecs, Instant::now());
+        assert_eq!(server.clients.len(), 1);
+        assert!(!server.clients.contains_key(&second_id));
+        assert!(!ecs.has_events::<ClientConnected>());
+    }
+
+    #[tokio::test]
+    async fn test_chat_sender() {
+        let ecs = ecs::Manager::default();
+        let mut server = Server::bind(ServerConfig {
+            address: SocketAddr::from(([127, 0, 0, 1], 0)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let first = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let second = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        // A taken name is numbered, a long one is cut
+        join(&mut server, &ecs, &first, "Player").await;
+        join(&mut server, &ecs, &second, " Player ").await;
+        let names = ecs
+            .drain_events::<ClientConnected>()
+            .into_iter()
+            .map(|connected| connected.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Player", "Player (2)"]);
+        assert_eq!(server.unique_name(&"é".repeat(40)), "é".repeat(16));
+
+        // The second client pretends to be the first one
+        server.flush(Instant::now()).await;
+        let chat = Message::Reliable {
+            channel: CHAT_CHANNEL,
+            sequence: 0,
+            data: ChatMessage::new("Player", "gg").encode(),
+        };
+        second.send_to(&chat.encode(), server_addr).await.unwrap();
+        receive_until(&mut server, &ecs, |server| !server.replies.is_empty()).await;
+        assert_eq!(
+            ecs.drain_events::<ChatMessage>(),
+            vec![ChatMessage::new("Player (2)", "gg")]
+        );
+
+        server.flush(Instant::now()).await;
+        let mut buffer = [0u8; MAX_DATAGRAM_SIZE];
+        let len = first.recv(&mut buffer).await.unwrap();
+        match Message::decode(&buffer[..len]).unwrap() {
+            Message::Reliable { data, .. } => assert_eq!(
+                ChatMessage::decode(&data).unwrap(),
+                ChatMessage::new("Player (2)", "gg")
+            ),
+            message => panic!("Expected the relayed chat, got {:?}", message),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_buffering_client_is_dropped() {
+        let ecs = ecs::Manager::default();
+        let mut server = Server::bind(ServerConfig {
+            address: SocketAddr::from(([127, 0, 0, 1], 0)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_id = ClientId(client.local_addr().unwrap());
+        join(&mut server, &ecs, &client, "Player").await;
+        server
+            .clients
+            .get_mut(&client_id)
+            .unwrap()
+            .channels
+            .max_buffered = 100;
+
+        // Messages after a gap are buffered until the limit is crossed
+        for channel in 1..=2 {
+            let message = Message::Reliable {
+                channel,
+                sequence: 1,
+                data: vec![0; 60],
+            };
+            client
+                .send_to(&message.encode(), server_addr)
+                .await
+                .unwrap();
+        }
+        receive_until(&mut server, &ecs, |server| server.clients.is_empty()).await;
+        assert_eq!(
+            ecs.drain_events::<ClientDisconnected>(),
+            vec![ClientDisconnected { client: client_id }]
+        );
+    }
+}
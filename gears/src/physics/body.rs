@@ -0,0 +1,150 @@
+use crate::ecs::traits::Component;
+use cgmath::{Vector3, Zero};
+use serde::{Deserialize, Serialize};
+
+/// A component that moves an entity with forces, gravity and collisions.
+/// The entity needs a `Pos3`, its `Velocity` is added by the physics step if it is missing,
+/// and it only collides with the other bodies if it has a `Collider`.
+///
+/// A body moving slower than the sleep speed of the `PhysicsSettings` for enough steps falls asleep.
+/// A sleeping body is not integrated and its contacts with the other resting bodies are not tested,
+/// until a force or an impulse is applied to it or a body runs into it.
+/// Only the parameters are saved in scenes, the missing ones keep their default.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RigidBody {
+    /// The mass in kilograms, a body without mass is static and never moves.
+    pub mass: f32,
+    /// The gravity of the body instead of the one of the `PhysicsSettings`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gravity: Option<Vector3<f32>>,
+    /// The fraction of the speed kept when bouncing off another body.
+    pub restitution: f32,
+    /// The fraction of the velocity lost per second.
+    pub damping: f32,
+    /// A frozen body is not simulated and collides like a static body, for debugging.
+    pub frozen: bool,
+    #[serde(skip)]
+    force: Vector3<f32>,
+    #[serde(skip)]
+    applied: Vector3<f32>,
+    #[serde(skip)]
+    impulse: Vector3<f32>,
+    /// The steps the body has been slower than the sleep speed.
+    #[serde(skip)]
+    still_steps: u32,
+    #[serde(skip)]
+    sleeping: bool,
+}
+
+impl Component for RigidBody {}
+
+impl Default for RigidBody {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl RigidBody {
+    pub fn new(mass: f32) -> Self {
+        Self {
+            mass: mass.max(0.0),
+            gravity: None,
+            restitution: 0.2,
+            damping: 0.0,
+            frozen: false,
+            force: Vector3::zero(),
+            applied: Vector3::zero(),
+            impulse: Vector3::zero(),
+            still_steps: 0,
+            sleeping: false,
+        }
+    }
+
+    /// Create a body that is not moved by the simulation, e.g. the ground.
+    pub fn new_static() -> Self {
+        Self::new(0.0)
+    }
+
+    pub fn with_gravity(mut self, gravity: Vector3<f32>) -> Self {
+        self.gravity = Some(gravity);
+        self
+    }
+
+    pub fn with_restitution(mut self, restitution: f32) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn with_damping(mut self, damping: f32) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.mass <= 0.0
+    }
+
+    /// Get the inverse of the mass, 0 for the bodies the simulation does not move.
+    pub fn inverse_mass(&self) -> f32 {
+        if self.is_static() || self.frozen {
+            0.0
+        } else {
+            1.0 / self.mass
+        }
+    }
+
+    /// Push the body during the next physics step, the forces are cleared after each step.
+    pub fn apply_force(&mut self, force: Vector3<f32>) {
+        self.force += force;
+        self.wake();
+    }
+
+    /// Change the velocity of the body at once during the next physics step, e.g. for a jump or a hit.
+    pub fn apply_impulse(&mut self, impulse: Vector3<f32>) {
+        self.impulse += impulse;
+        self.wake();
+    }
+
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
+    }
+
+    /// Wake the body up, so the next physics step moves it again.
+    pub fn wake(&mut self) {
+        self.still_steps = 0;
+        self.sleeping = false;
+    }
+
+    /// Get the forces applied to the body during the last physics step, without the gravity.
+    pub fn applied_force(&self) -> Vector3<f32> {
+        self.applied
+    }
+
+    /// Take the accumulated forces for a physics step.
+    pub(crate) fn take_force(&mut self) -> Vector3<f32> {
+        self.applied = self.force;
+        self.force = Vector3::zero();
+        self.applied
+    }
+
+    /// Take the accumulated impulses for a physics step.
+    pub(crate) fn take_impulse(&mut self) -> Vector3<f32> {
+        std::mem::replace(&mut self.impulse, Vector3::zero())
+    }
+
+    /// Count the steps the body has been slower than the sleep speed and put it to sleep.
+    ///
+    /// # Returns
+    ///
+    /// If the body is asleep.
+    pub(crate) fn update_sleep(&mut self, speed: f32, sleep_speed: f32, sleep_steps: u32) -> bool {
+        if speed < sleep_speed {
+            self.still_steps = self.still_steps.saturating_add(1);
+        } else {
+            self.still_steps = 0;
+        }
+        self.sleeping = sleep_speed > 0.0 && self.still_steps >= sleep_steps.max(1);
+        self.sleeping
+    }
+}
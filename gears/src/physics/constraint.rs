@@ -0,0 +1,30 @@
+use crate::ecs::traits::Component;
+use crate::ecs::Entity;
+
+/// A component that keeps the rigid body of the entity at a distance from another body, like a rod.
+/// The constraint is solved after the collisions of each physics step.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DistanceConstraint {
+    pub other: Entity,
+    /// The distance in world units between the positions of the two entities.
+    pub distance: f32,
+    /// The fraction of the error corrected per step, 1 is a rigid rod.
+    pub stiffness: f32,
+}
+
+impl Component for DistanceConstraint {}
+
+impl DistanceConstraint {
+    pub fn new(other: Entity, distance: f32) -> Self {
+        Self {
+            other,
+            distance,
+            stiffness: 1.0,
+        }
+    }
+
+    pub fn with_stiffness(mut self, stiffness: f32) -> Self {
+        self.stiffness = stiffness.clamp(0.0, 1.0);
+        self
+    }
+}
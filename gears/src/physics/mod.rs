@@ -0,0 +1,5 @@
+pub mod body;
+pub mod constraint;
+pub mod settings;
+pub mod spatial;
+pub mod world;
@@ -0,0 +1,102 @@
+use crate::core::Dt;
+use cgmath::Vector3;
+
+/// The settings of the physics world, a resource of the ECS read by `update_physics` on each step,
+/// so they can be changed while the app runs. The defaults are inserted on the first step
+/// if the resource is missing.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PhysicsSettings {
+    /// The gravity of the bodies without their own one.
+    pub gravity: Vector3<f32>,
+    /// The passes over the collisions and the constraints in a step,
+    /// more passes settle stacks and chains of bodies better.
+    pub solver_iterations: u32,
+    /// The speed below which a body starts to fall asleep, 0 to keep every body awake.
+    pub sleep_speed: f32,
+    /// The steps a body has to stay below the sleep speed to fall asleep.
+    pub sleep_steps: u32,
+    /// The time step the bodies are simulated with, the delta time of the system is split into
+    /// steps of it and the rest is kept for the next update.
+    /// `None` simulates a single step of the delta time, e.g. when the system runs in the `FixedUpdate` stage.
+    pub fixed_step: Option<Dt>,
+    accumulator: Dt,
+}
+
+impl Default for PhysicsSettings {
+    fn default() -> Self {
+        Self {
+            gravity: Vector3::new(0.0, -9.81, 0.0),
+            solver_iterations: 1,
+            sleep_speed: 0.05,
+            sleep_steps: 30,
+            fixed_step: None,
+            accumulator: Dt::ZERO,
+        }
+    }
+}
+
+impl PhysicsSettings {
+    pub fn with_gravity(mut self, gravity: Vector3<f32>) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn with_solver_iterations(mut self, solver_iterations: u32) -> Self {
+        self.solver_iterations = solver_iterations;
+        self
+    }
+
+    pub fn with_sleep(mut self, sleep_speed: f32, sleep_steps: u32) -> Self {
+        self.sleep_speed = sleep_speed;
+        self.sleep_steps = sleep_steps;
+        self
+    }
+
+    pub fn with_fixed_step(mut self, fixed_step: Dt) -> Self {
+        self.fixed_step = Some(fixed_step);
+        self
+    }
+
+    /// Split the delta time of an update into the steps to simulate.
+    ///
+    /// # Returns
+    ///
+    /// The delta time of a step and the number of steps, at most `max_steps`.
+    pub(crate) fn steps(&mut self, dt: Dt, max_steps: u32) -> (Dt, u32) {
+        let Some(step) = self.fixed_step.filter(|step| !step.is_zero()) else {
+            return (dt, 1);
+        };
+        self.accumulator += dt;
+        let mut steps = 0;
+        while self.accumulator >= step {
+            self.accumulator -= step;
+            steps += 1;
+        }
+        if steps > max_steps {
+            // Falling behind, the time of the skipped steps is dropped instead of piling up
+            steps = max_steps;
+        }
+        (step, steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_step() {
+        let mut settings = PhysicsSettings::default();
+        let dt = Dt::from_millis(25);
+        assert_eq!(settings.steps(dt, 4), (dt, 1));
+
+        settings = settings.with_fixed_step(Dt::from_millis(10));
+        assert_eq!(settings.steps(dt, 4), (Dt::from_millis(10), 2));
+        // The rest of the previous update is kept
+        assert_eq!(settings.steps(dt, 4), (Dt::from_millis(10), 3));
+        assert_eq!(
+            settings.steps(Dt::from_secs(1), 4),
+            (Dt::from_millis(10), 4)
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use crate::ecs::Entity;
+use cgmath::{InnerSpace, Vector3};
+use std::collections::{HashMap, HashSet};
+
+/// A box spanning more cells than this is kept out of the grid and tested against every query instead.
+const MAX_CELLS_PER_BOX: i64 = 64;
+
+/// A box in the index.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Entry {
+    entity: Entity,
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+}
+
+/// A uniform grid of axis-aligned boxes, for finding the boxes near a point, a box or a ray
+/// without testing all of them.
+///
+/// The physics step rebuilds it from the colliders each update and inserts it as a resource,
+/// so other systems can query the colliders of the last step without building their own.
+#[derive(Debug, Clone)]
+pub struct SpatialIndex {
+    cell_size: f32,
+    entries: Vec<Entry>,
+    cells: HashMap<[i32; 3], Vec<usize>>,
+    /// The boxes too large for the grid, e.g. the ground.
+    large: Vec<usize>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new(4.0)
+    }
+}
+
+impl SpatialIndex {
+    /// Create an empty index, the cells are cubes of `cell_size`.
+    /// A cell about twice the size of the common boxes keeps the queries short.
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size: cell_size.max(f32::EPSILON),
+            entries: Vec::new(),
+            cells: HashMap::new(),
+            large: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.cells.clear();
+        self.large.clear();
+    }
+
+    fn cell(&self, point: Vector3<f32>) -> [i32; 3] {
+        [
+            (point.x / self.cell_size).floor() as i32,
+            (point.y / self.cell_size).floor() as i32,
+            (point.z / self.cell_size).floor() as i32,
+        ]
+    }
+
+    /// Get the range of cells a box spans, `None` if it spans too many for the grid.
+    fn cell_range(&self, min: Vector3<f32>, max: Vector3<f32>) -> Option<([i32; 3], [i32; 3])> {
+        let (low, high) = (self.cell(min), self.cell(max));
+        // The extents of a huge box overflow i32 and their product overflows i64
+        let count = (0..3)
+            .map(|axis| high[axis] as i64 - low[axis] as i64 + 1)
+            .try_fold(1i64, i64::checked_mul);
+        count
+            .is_some_and(|count| count <= MAX_CELLS_PER_BOX)
+            .then_some((low, high))
+    }
+
+    fn cells_in(low: [i32; 3], high: [i32; 3]) -> impl Iterator<Item = [i32; 3]> {
+        (low[0]..=high[0]).flat_map(move |x| {
+            (low[1]..=high[1]).flat_map(move |y| (low[2]..=high[2]).map(move |z| [x, y, z]))
+        })
+    }
+
+    /// Add the world space box of an entity.
+    ///
+    /// # Returns
+    ///
+    /// The index of the box, in the order the boxes were inserted.
+    pub fn insert(&mut self, entity: Entity, min: Vector3<f32>, max: Vector3<f32>) -> usize {
+        let index = self.entries.len();
+        self.entries.push(Entry { entity, min, max });
+        match self.cell_range(min, max) {
+            Some((low, high)) => {
+                for cell in Self::cells_in(low, high) {
+                    self.cells.entry(cell).or_default().push(index);
+                }
+            }
+            None => self.large.push(index),
+        }
+        index
+    }
+
+    /// Get the entity of a box by its index.
+    pub fn entity(&self, index: usize) -> Entity {
+        self.entries[index].entity
+    }
+
+    /// Get the indices of the boxes that may overlap a box, each once and in insertion order.
+    fn candidates(&self, min: Vector3<f32>, max: Vector3<f32>) -> Vec<usize> {
+        let mut found = self.large.iter().copied().collect::<HashSet<_>>();
+        match self.cell_range(min, max) {
+            Some((low, high)) => {
+                for cell in Self::cells_in(low, high) {
+                    if let Some(indices) = self.cells.get(&cell) {
+                        found.extend(indices);
+                    }
+                }
+            }
+            // A query too large for the grid tests all the boxes
+            None => found.extend(0..self.entries.len()),
+        }
+        let mut found = found.into_iter().collect::<Vec<_>>();
+        found.sort_unstable();
+        found
+    }
+
+    /// Get the indices of the boxes overlapping a box.
+    pub fn query_box(&self, min: Vector3<f32>, max: Vector3<f32>) -> Vec<usize> {
+        self.candidates(min, max)
+            .into_iter()
+            .filter(|&i| {
+                let entry = &self.entries[i];
+                (0..3).all(|axis| entry.min[axis] <= max[axis] && min[axis] <= entry.max[axis])
+            })
+            .collect()
+    }
+
+    /// Get the entities whose boxes are within a radius of a point.
+    pub fn query_radius(&self, center: Vector3<f32>, radius: f32) -> Vec<Entity> {
+        let extent = Vector3::new(radius, radius, radius);
+        self.candidates(center - extent, center + extent)
+            .into_iter()
+            .filter(|&i| {
+                let entry = &self.entries[i];
+                let closest = Vector3::new(
+                    center.x.clamp(entry.min.x, entry.max.x),
+                    center.y.clamp(entry.min.y, entry.max.y),
+                    center.z.clamp(entry.min.z, entry.max.z),
+                );
+                (closest - center).magnitude2() <= radius * radius
+            })
+            .map(|i| self.entries[i].entity)
+            .collect()
+    }
+
+    /// Find the first box hit by a ray.
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - The origin of the ray.
+    /// * `direction` - The direction of the ray, it does not have to be normalized.
+    /// * `max_distance` - The length of the ray.
+    /// * `ignore` - The entities whose boxes are skipped, e.g. the one casting the ray.
+    ///
+    /// # Returns
+    ///
+    /// The entity hit and the distance to it.
+    pub fn raycast(
+        &self,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+        max_distance: f32,
+        ignore: &[Entity],
+    ) -> Option<(Entity, f32)> {
+        if direction.magnitude2() <= f32::EPSILON {
+            return None;
+        }
+        let direction = direction.normalize();
+        let end = origin + direction * max_distance;
+        let min = Vector3::new(
+            origin.x.min(end.x),
+            origin.y.min(end.y),
+            origin.z.min(end.z),
+        );
+        let max = Vector3::new(
+            origin.x.max(end.x),
+            origin.y.max(end.y),
+            origin.z.max(end.z),
+        );
+
+        self.candidates(min, max)
+            .into_iter()
+            .map(|i| &self.entries[i])
+            .filter(|entry| !ignore.contains(&entry.entity))
+            .filter_map(|entry| {
+                // The slab test, the ray enters the box after it entered all three slabs
+                let (mut near, mut far) = (0.0f32, max_distance);
+                for axis in 0..3 {
+                    if direction[axis].abs() <= f32::EPSILON {
+                        if origin[axis] < entry.min[axis] || origin[axis] > entry.max[axis] {
+                            return None;
+                        }
+                        continue;
+                    }
+                    let t0 = (entry.min[axis] - origin[axis]) / direction[axis];
+                    let t1 = (entry.max[axis] - origin[axis]) / direction[axis];
+                    near = near.max(t0.min(t1));
+                    far = far.min(t0.max(t1));
+                }
+                (near <= far).then_some((entry.entity, near))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Get the pairs of boxes that overlap, the broadphase of the collisions.
+    ///
+    /// # Returns
+    ///
+    /// The indices of the boxes of each pair, the lower one first, sorted.
+    pub fn pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = HashSet::new();
+        let overlaps = |a: usize, b: usize| {
+            let (a, b) = (&self.entries[a], &self.entries[b]);
+            (0..3).all(|axis| a.min[axis] <= b.max[axis] && b.min[axis] <= a.max[axis])
+        };
+        for indices in self.cells.values() {
+            for (n, &a) in indices.iter().enumerate() {
+                for &b in &indices[n + 1..] {
+                    if overlaps(a, b) {
+                        pairs.insert((a.min(b), a.max(b)));
+                    }
+                }
+            }
+        }
+        for &a in &self.large {
+            for b in 0..self.entries.len() {
+                if a != b && overlaps(a, b) {
+                    pairs.insert((a.min(b), a.max(b)));
+                }
+            }
+        }
+        let mut pairs = pairs.into_iter().collect::<Vec<_>>();
+        pairs.sort_unstable();
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pairs_and_queries() {
+        let mut index = SpatialIndex::new(1.0);
+        let unit = Vector3::new(0.5, 0.5, 0.5);
+        let ground = index.insert(
+            Entity(0),
+            Vector3::new(-100.0, -1.0, -100.0),
+            Vector3::new(100.0, 0.0, 100.0),
+        );
+        let a = index.insert(
+            Entity(1),
+            Vector3::new(0.0, 0.5, 0.0) - unit,
+            Vector3::new(0.0, 0.5, 0.0) + unit,
+        );
+        let b = index.insert(
+            Entity(2),
+            Vector3::new(0.8, 0.5, 0.0) - unit,
+            Vector3::new(0.8, 0.5, 0.0) + unit,
+        );
+        let far = index.insert(
+            Entity(3),
+            Vector3::new(50.0, 5.0, 0.0) - unit,
+            Vector3::new(50.0, 5.0, 0.0) + unit,
+        );
+
+        // The far box only overlaps nothing, the ground is too large for the grid but still paired
+        assert_eq!(index.pairs(), vec![(ground, a), (ground, b), (a, b)]);
+        assert_eq!(
+            index.query_box(Vector3::new(49.0, 4.0, -1.0), Vector3::new(51.0, 6.0, 1.0)),
+            vec![far]
+        );
+        assert_eq!(
+            index.query_radius(Vector3::new(0.0, 3.0, 0.0), 2.1),
+            vec![Entity(1), Entity(2)]
+        );
+
+        let hit = index.raycast(
+            Vector3::new(-5.0, 0.5, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            10.0,
+            &[],
+        );
+        assert_eq!(hit, Some((Entity(1), 4.5)));
+        let hit = index.raycast(
+            Vector3::new(-5.0, 0.5, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            10.0,
+            &[Entity(1)],
+        );
+        assert_eq!(hit.map(|(entity, _)| entity), Some(Entity(2)));
+        assert_eq!(
+            index.raycast(
+                Vector3::new(0.0, 3.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+                10.0,
+                &[]
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_huge_boxes() {
+        let mut index = SpatialIndex::new(0.001);
+        let small = index.insert(
+            Entity(0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 1.0),
+        );
+        // The cells of these span the whole i32 range, the counts must not overflow into a small number
+        let huge = index.insert(
+            Entity(1),
+            Vector3::new(-f32::MAX, -f32::MAX, -f32::MAX),
+            Vector3::new(f32::MAX, f32::MAX, f32::MAX),
+        );
+        let wide = index.insert(
+            Entity(2),
+            Vector3::new(-1e7, 0.0, 0.0),
+            Vector3::new(1e7, 1e7, 1e7),
+        );
+
+        assert_eq!(
+            index.pairs(),
+            vec![(small, huge), (small, wide), (huge, wide)]
+        );
+        assert_eq!(
+            index.query_box(Vector3::new(0.5, 0.5, 0.5), Vector3::new(0.6, 0.6, 0.6)),
+            vec![small, huge, wide]
+        );
+    }
+}
@@ -0,0 +1,481 @@
+use super::body::RigidBody;
+use super::constraint::DistanceConstraint;
+use super::settings::PhysicsSettings;
+use super::spatial::SpatialIndex;
+use crate::core::Dt;
+use crate::ecs::components::{Collider, Pos3, Velocity};
+use crate::ecs::hierarchy::AttachedTo;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::{InnerSpace, Vector3, Zero};
+
+/// The most steps simulated in an update with a `PhysicsSettings::fixed_step`.
+const MAX_STEPS: u32 = 8;
+
+/// A contact between two colliding bodies.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Contact {
+    pub a: Entity,
+    pub b: Entity,
+    /// The center of the overlap of the two colliders.
+    pub point: Vector3<f32>,
+    /// The direction from `a` to `b` the bodies were pushed apart along.
+    pub normal: Vector3<f32>,
+    /// How far the colliders overlapped.
+    pub depth: f32,
+}
+
+/// A component that records the contacts of the last physics step, e.g. for debugging.
+#[derive(Debug, Clone, Default)]
+pub struct Contacts(pub Vec<Contact>);
+
+impl Component for Contacts {}
+
+/// A collider of a body in world space.
+struct Shape {
+    entity: Entity,
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+    inverse_mass: f32,
+    restitution: f32,
+    sleeping: bool,
+}
+
+impl Shape {
+    /// Check if the body is not moving on its own, its contacts with the other resting bodies are skipped.
+    fn is_resting(&self) -> bool {
+        self.sleeping || self.inverse_mass <= 0.0
+    }
+}
+
+/// Find the overlap of two boxes along the axis it is the smallest on.
+fn overlap(a: &Shape, b: &Shape) -> Option<(Vector3<f32>, Vector3<f32>, f32)> {
+    let mut normal = Vector3::zero();
+    let mut depth = f32::MAX;
+    for axis in 0..3 {
+        let amount = a.max[axis].min(b.max[axis]) - a.min[axis].max(b.min[axis]);
+        if amount <= 0.0 {
+            return None;
+        }
+        if amount < depth {
+            depth = amount;
+            normal = Vector3::zero();
+            let a_center = a.min[axis] + a.max[axis];
+            let b_center = b.min[axis] + b.max[axis];
+            normal[axis] = if b_center >= a_center { 1.0 } else { -1.0 };
+        }
+    }
+
+    let low = Vector3::new(
+        a.min.x.max(b.min.x),
+        a.min.y.max(b.min.y),
+        a.min.z.max(b.min.z),
+    );
+    let high = Vector3::new(
+        a.max.x.min(b.max.x),
+        a.max.y.min(b.max.y),
+        a.max.z.min(b.max.z),
+    );
+    Some(((low + high) * 0.5, normal, depth))
+}
+
+fn velocity_of(ecs: &ecs::Manager, entity: Entity) -> Vector3<f32> {
+    ecs.get_component_from_entity::<Velocity>(entity)
+        .map_or(Vector3::zero(), |velocity| velocity.read().unwrap().0)
+}
+
+/// Move an entity and change its velocity, if it has the components.
+fn push(ecs: &ecs::Manager, entity: Entity, offset: Vector3<f32>, impulse: Vector3<f32>) {
+    if let Some(pos) = ecs.get_component_from_entity::<Pos3>(entity) {
+        pos.write().unwrap().pos += offset;
+    }
+    if let Some(velocity) = ecs.get_component_from_entity::<Velocity>(entity) {
+        velocity.write().unwrap().0 += impulse;
+    }
+}
+
+fn wake(ecs: &ecs::Manager, entity: Entity) {
+    if let Some(body) = ecs.get_component_from_entity::<RigidBody>(entity) {
+        body.write().unwrap().wake();
+    }
+}
+
+/// Build the spatial index of the shapes, the index of a shape in it is its index in `shapes`.
+fn index_shapes(shapes: &[Shape]) -> SpatialIndex {
+    // Cells about twice the common size of a collider keep a collider in a few cells
+    let sizes = shapes
+        .iter()
+        .map(|shape| {
+            let extent = shape.max - shape.min;
+            extent.x.max(extent.y).max(extent.z)
+        })
+        .filter(|size| size.is_finite())
+        .collect::<Vec<_>>();
+    let cell_size = if sizes.is_empty() {
+        1.0
+    } else {
+        2.0 * sizes.iter().sum::<f32>() / sizes.len() as f32
+    };
+
+    let mut index = SpatialIndex::new(cell_size);
+    for shape in shapes {
+        index.insert(shape.entity, shape.min, shape.max);
+    }
+    index
+}
+
+/// Separate the overlapping colliders and bounce the bodies off each other.
+/// Only the pairs the spatial index finds overlapping are tested, instead of every pair,
+/// and the pairs of the resting bodies are skipped. A sleeping body hit faster than `wake_speed` wakes up.
+fn collide(ecs: &ecs::Manager, shapes: &mut [Shape], wake_speed: f32) -> Vec<Contact> {
+    let mut contacts = Vec::new();
+    for (i, j) in index_shapes(shapes).pairs() {
+        let (a, b) = (&shapes[i], &shapes[j]);
+        if a.is_resting() && b.is_resting() {
+            continue;
+        }
+        let total = a.inverse_mass + b.inverse_mass;
+        let Some((point, normal, depth)) = overlap(a, b) else {
+            continue;
+        };
+
+        let relative = velocity_of(ecs, b.entity) - velocity_of(ecs, a.entity);
+        let approach = relative.dot(normal);
+        if approach < -wake_speed {
+            for k in [i, j] {
+                if shapes[k].sleeping {
+                    wake(ecs, shapes[k].entity);
+                    shapes[k].sleeping = false;
+                }
+            }
+        }
+        let (a, b) = (&shapes[i], &shapes[j]);
+        let impulse = if approach < 0.0 {
+            let restitution = a.restitution.min(b.restitution);
+            normal * (-(1.0 + restitution) * approach / total)
+        } else {
+            Vector3::zero()
+        };
+
+        let correction = normal * (depth / total);
+        let (a_offset, b_offset) = (-correction * a.inverse_mass, correction * b.inverse_mass);
+        push(ecs, a.entity, a_offset, -impulse * a.inverse_mass);
+        push(ecs, b.entity, b_offset, impulse * b.inverse_mass);
+
+        contacts.push(Contact {
+            a: a.entity,
+            b: b.entity,
+            point,
+            normal,
+            depth,
+        });
+        shapes[i].min += a_offset;
+        shapes[i].max += a_offset;
+        shapes[j].min += b_offset;
+        shapes[j].max += b_offset;
+    }
+    contacts
+}
+
+/// Pull or push the constrained bodies to their distance.
+fn solve_constraints(ecs: &ecs::Manager) {
+    let inverse_mass = |entity| {
+        ecs.get_component_from_entity::<RigidBody>(entity)
+            .map_or(0.0, |body| body.read().unwrap().inverse_mass())
+    };
+    let position = |entity| {
+        ecs.get_component_from_entity::<Pos3>(entity)
+            .map(|pos| pos.read().unwrap().pos)
+    };
+
+    for (entity, constraint) in ecs.get_all_components_of_type::<DistanceConstraint>() {
+        let constraint = *constraint.read().unwrap();
+        let (a, b) = (entity, constraint.other);
+        let (a_inverse, b_inverse) = (inverse_mass(a), inverse_mass(b));
+        let total = a_inverse + b_inverse;
+        if a == b || total <= 0.0 {
+            continue;
+        }
+        let (Some(a_pos), Some(b_pos)) = (position(a), position(b)) else {
+            continue;
+        };
+
+        let delta = b_pos - a_pos;
+        let length = delta.magnitude();
+        if length < f32::EPSILON {
+            continue;
+        }
+        let normal = delta / length;
+        let error = (length - constraint.distance) * constraint.stiffness / total;
+        // The velocity along the rod is removed, so the bodies do not drift apart again
+        let stretch = (velocity_of(ecs, b) - velocity_of(ecs, a)).dot(normal) / total;
+
+        push(
+            ecs,
+            a,
+            normal * error * a_inverse,
+            normal * stretch * a_inverse,
+        );
+        push(
+            ecs,
+            b,
+            -normal * error * b_inverse,
+            -normal * stretch * b_inverse,
+        );
+    }
+}
+
+/// Step the rigid bodies: apply the gravity and the forces, resolve the collisions
+/// between the bodies with a `Collider`, and solve the constraints.
+/// The gravity, the solver iterations, the sleeping and the time step are set by the `PhysicsSettings` resource.
+/// The contacts are recorded into every `Contacts` component, and the colliders after the step
+/// into the `SpatialIndex` resource, for the systems querying the bodies near a point or along a ray.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_physics(ecs: &ecs::Manager, dt: Dt) {
+    let settings = match ecs.get_resource::<PhysicsSettings>() {
+        Some(settings) => settings,
+        None => {
+            ecs.insert_resource(PhysicsSettings::default());
+            ecs.get_resource::<PhysicsSettings>().unwrap()
+        }
+    };
+    let (step, steps) = settings.write().unwrap().steps(dt, MAX_STEPS);
+    let settings = *settings.read().unwrap();
+
+    for _ in 0..steps {
+        step_physics(ecs, &settings, step.as_secs_f32());
+    }
+}
+
+/// Simulate a single step of the rigid bodies.
+fn step_physics(ecs: &ecs::Manager, settings: &PhysicsSettings, dt: f32) {
+    let mut shapes = Vec::new();
+
+    for (entity, body) in ecs.get_all_components_of_type::<RigidBody>() {
+        // An attached entity follows another one, it is neither moved nor collided with
+        if ecs
+            .get_component_from_entity::<AttachedTo>(entity)
+            .is_some()
+        {
+            continue;
+        }
+        let Some(pos) = ecs.get_component_from_entity::<Pos3>(entity) else {
+            continue;
+        };
+        let mut body = body.write().unwrap();
+        let force = body.take_force();
+        let impulse = body.take_impulse();
+
+        let inverse_mass = body.inverse_mass();
+        if inverse_mass > 0.0 {
+            let velocity = match ecs.get_component_from_entity::<Velocity>(entity) {
+                Some(velocity) => velocity,
+                None => {
+                    ecs.add_component_to_entity(entity, Velocity::default());
+                    ecs.get_component_from_entity::<Velocity>(entity).unwrap()
+                }
+            };
+            let mut velocity = velocity.write().unwrap();
+            velocity.0 += impulse * inverse_mass;
+            let speed = velocity.0.magnitude();
+            if body.update_sleep(speed, settings.sleep_speed, settings.sleep_steps) {
+                velocity.0 = Vector3::zero();
+            } else {
+                let gravity = body.gravity.unwrap_or(settings.gravity);
+                velocity.0 += (gravity + force * inverse_mass) * dt;
+                velocity.0 *= (1.0 - body.damping * dt).max(0.0);
+                pos.write().unwrap().pos += velocity.0 * dt;
+            }
+        }
+
+        if let Some(collider) = ecs.get_component_from_entity::<Collider>(entity) {
+            let (min, max) = collider.read().unwrap().bounds();
+            let origin = pos.read().unwrap().pos;
+            shapes.push(Shape {
+                entity,
+                min: origin + min,
+                max: origin + max,
+                inverse_mass,
+                restitution: body.restitution,
+                sleeping: body.is_sleeping(),
+            });
+        }
+    }
+
+    // The contacts are the ones found by the first pass, the later ones only settle the bodies
+    let contacts = collide(ecs, &mut shapes, settings.sleep_speed);
+    solve_constraints(ecs);
+    for _ in 1..settings.solver_iterations {
+        collide(ecs, &mut shapes, settings.sleep_speed);
+        solve_constraints(ecs);
+    }
+    ecs.insert_resource(index_shapes(&shapes));
+
+    for (_, recorded) in ecs.get_all_components_of_type::<Contacts>() {
+        recorded.write().unwrap().0 = contacts.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instant::Duration;
+
+    #[test]
+    fn test_body_lands_on_ground() {
+        let ecs = ecs::Manager::default();
+        let ground = ecs.create_entity();
+        ecs.add_component_to_entity(ground, Pos3::default());
+        ecs.add_component_to_entity(ground, RigidBody::new_static());
+        ecs.add_component_to_entity(
+            ground,
+            Collider::new(Vector3::new(-5.0, -1.0, -5.0), Vector3::new(5.0, 0.0, 5.0)),
+        );
+        let ball = ecs.create_entity();
+        ecs.add_component_to_entity(ball, Pos3::new(Vector3::new(0.0, 2.0, 0.0)));
+        ecs.add_component_to_entity(ball, RigidBody::new(1.0).with_restitution(0.0));
+        ecs.add_component_to_entity(
+            ball,
+            Collider::new(Vector3::new(-0.5, -0.5, -0.5), Vector3::new(0.5, 0.5, 0.5)),
+        );
+        let recorder = ecs.create_entity();
+        ecs.add_component_to_entity(recorder, Contacts::default());
+
+        let mut touched = false;
+        for _ in 0..120 {
+            update_physics(&ecs, Duration::from_millis(16));
+            let contacts = ecs.get_component_from_entity::<Contacts>(recorder).unwrap();
+            touched |= contacts
+                .read()
+                .unwrap()
+                .0
+                .iter()
+                .any(|c| c.a == ball || c.b == ball);
+        }
+        assert!(touched);
+
+        // The ball rests on the ground, the ground did not move
+        let ball_pos = ecs.get_component_from_entity::<Pos3>(ball).unwrap();
+        assert!((ball_pos.read().unwrap().pos.y - 0.5).abs() < 0.05);
+        let ground_pos = ecs.get_component_from_entity::<Pos3>(ground).unwrap();
+        assert_eq!(ground_pos.read().unwrap().pos, Vector3::zero());
+    }
+
+    #[test]
+    fn test_distance_constraint_and_freeze() {
+        let ecs = ecs::Manager::default();
+        let anchor = ecs.create_entity();
+        ecs.add_component_to_entity(anchor, Pos3::default());
+        ecs.add_component_to_entity(anchor, RigidBody::new_static());
+        let bob = ecs.create_entity();
+        ecs.add_component_to_entity(bob, Pos3::new(Vector3::new(2.0, 0.0, 0.0)));
+        ecs.add_component_to_entity(bob, RigidBody::new(1.0));
+        ecs.add_component_to_entity(bob, DistanceConstraint::new(anchor, 2.0));
+
+        for _ in 0..60 {
+            update_physics(&ecs, Duration::from_millis(16));
+        }
+        let pos = ecs.get_component_from_entity::<Pos3>(bob).unwrap();
+        let swung = pos.read().unwrap().pos;
+        assert!((swung.magnitude() - 2.0).abs() < 1e-3);
+        assert!(swung.y < -0.1);
+
+        // A frozen body keeps its place even with a force applied
+        let body = ecs.get_component_from_entity::<RigidBody>(bob).unwrap();
+        body.write().unwrap().frozen = true;
+        body.write()
+            .unwrap()
+            .apply_force(Vector3::new(100.0, 0.0, 0.0));
+        update_physics(&ecs, Duration::from_millis(16));
+        assert_eq!(pos.read().unwrap().pos, swung);
+        assert_eq!(
+            body.read().unwrap().applied_force(),
+            Vector3::new(100.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_settings_gravity_and_sleep() {
+        let ecs = ecs::Manager::default();
+        ecs.insert_resource(
+            PhysicsSettings::default()
+                .with_gravity(Vector3::new(1.0, 0.0, 0.0))
+                .with_sleep(0.05, 16),
+        );
+        let drifting = ecs.create_entity();
+        ecs.add_component_to_entity(drifting, Pos3::default());
+        ecs.add_component_to_entity(drifting, RigidBody::new(1.0));
+        let falling = ecs.create_entity();
+        ecs.add_component_to_entity(falling, Pos3::default());
+        ecs.add_component_to_entity(
+            falling,
+            RigidBody::new(1.0).with_gravity(Vector3::new(0.0, -1.0, 0.0)),
+        );
+        let resting = ecs.create_entity();
+        ecs.add_component_to_entity(resting, Pos3::default());
+        ecs.add_component_to_entity(resting, RigidBody::new(1.0).with_gravity(Vector3::zero()));
+
+        for _ in 0..30 {
+            update_physics(&ecs, Duration::from_millis(16));
+        }
+        let pos = |entity| {
+            ecs.get_component_from_entity::<Pos3>(entity)
+                .unwrap()
+                .read()
+                .unwrap()
+                .pos
+        };
+        assert!(pos(drifting).x > 0.1 && pos(drifting).y == 0.0);
+        assert!(pos(falling).y < -0.1 && pos(falling).x == 0.0);
+
+        // A body at rest falls asleep, a force wakes it up
+        let body = ecs.get_component_from_entity::<RigidBody>(resting).unwrap();
+        assert!(body.read().unwrap().is_sleeping());
+        body.write()
+            .unwrap()
+            .apply_force(Vector3::new(0.0, 0.0, 60.0));
+        update_physics(&ecs, Duration::from_millis(16));
+        assert!(!body.read().unwrap().is_sleeping());
+        assert!(pos(resting).z > 0.0);
+    }
+
+    #[test]
+    fn test_sleeping_body_wakes_on_impact() {
+        let ecs = ecs::Manager::default();
+        ecs.insert_resource(
+            PhysicsSettings::default()
+                .with_gravity(Vector3::zero())
+                .with_sleep(0.05, 5),
+        );
+        let cube = || Collider::new(Vector3::new(-0.5, -0.5, -0.5), Vector3::new(0.5, 0.5, 0.5));
+        let crate_entity = ecs.create_entity();
+        ecs.add_component_to_entity(crate_entity, Pos3::default());
+        ecs.add_component_to_entity(crate_entity, RigidBody::new(1.0));
+        ecs.add_component_to_entity(crate_entity, cube());
+        let ball = ecs.create_entity();
+        ecs.add_component_to_entity(ball, Pos3::new(Vector3::new(-3.0, 0.0, 0.0)));
+        ecs.add_component_to_entity(ball, RigidBody::new(1.0));
+        ecs.add_component_to_entity(ball, Velocity(Vector3::new(5.0, 0.0, 0.0)));
+        ecs.add_component_to_entity(ball, cube());
+
+        for _ in 0..5 {
+            update_physics(&ecs, Duration::from_millis(16));
+        }
+        let body = ecs
+            .get_component_from_entity::<RigidBody>(crate_entity)
+            .unwrap();
+        assert!(body.read().unwrap().is_sleeping());
+
+        // The ball hits the crate after about 25 steps
+        for _ in 0..40 {
+            update_physics(&ecs, Duration::from_millis(16));
+        }
+        assert!(!body.read().unwrap().is_sleeping());
+        let pos = ecs.get_component_from_entity::<Pos3>(crate_entity).unwrap();
+        assert!(pos.read().unwrap().pos.x > 0.2);
+    }
+}
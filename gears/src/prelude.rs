@@ -1,8 +1,14 @@
-#[allow(unused)]
+//! The items most apps need, `use gears::prelude::*` is the only import the examples use from the engine.
 pub use crate::{
+    async_system,
     core::app::{self, App, GearsApp},
-    ecs,
-    ecs::components,
-    ecs::traits::{Component, EntityBuilder},
-    macros,
+    core::config::{Config, ConfigPreset},
+    core::schedule::{AsyncSystem, RunCriteria, Stage, System},
+    core::vfs::AssetConfig,
+    core::Dt,
+    ecs::traits::{Bundle, Component, EntityBuilder, Marker},
+    ecs::{self, components, Entity, Manager},
+    embed_assets, impl_bundle, impl_marker, macros, new_entity, spawn, tr,
 };
+/// The engine returns `anyhow::Result`s.
+pub use anyhow;
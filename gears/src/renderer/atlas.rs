@@ -0,0 +1,159 @@
+use image::RgbaImage;
+
+/// The region of a texture in an atlas, in UV units.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AtlasRect {
+    pub offset: [f32; 2],
+    pub scale: [f32; 2],
+}
+
+impl AtlasRect {
+    /// Map a UV coordinate of the texture to the atlas.
+    pub fn map_uv(&self, uv: [f32; 2]) -> [f32; 2] {
+        [
+            self.offset[0] + uv[0] * self.scale[0],
+            self.offset[1] + uv[1] * self.scale[1],
+        ]
+    }
+}
+
+/// Textures packed into a single image.
+#[derive(Debug, Clone)]
+pub struct Atlas {
+    pub image: RgbaImage,
+    /// The regions of the textures in the order they were added.
+    pub rects: Vec<AtlasRect>,
+}
+
+/// Packs small textures into an atlas, so the meshes using them share a bind group.
+/// The textures are placed on shelves, the tallest first, and their edges are extended into the padding
+/// around them, so the filtering does not bleed the neighbours in.
+#[derive(Debug, Clone)]
+pub struct AtlasBuilder {
+    size: u32,
+    padding: u32,
+    images: Vec<RgbaImage>,
+}
+
+impl AtlasBuilder {
+    /// Create a builder for an atlas of at most `size` by `size` pixels.
+    pub fn new(size: u32, padding: u32) -> Self {
+        Self {
+            size,
+            padding,
+            images: Vec::new(),
+        }
+    }
+
+    /// Add a texture.
+    ///
+    /// # Returns
+    ///
+    /// The index of its region in the atlas.
+    pub fn add(&mut self, image: RgbaImage) -> usize {
+        self.images.push(image);
+        self.images.len() - 1
+    }
+
+    pub fn images(&self) -> &[RgbaImage] {
+        &self.images
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// Pack the textures, the atlas is only as tall as the shelves used.
+    ///
+    /// # Returns
+    ///
+    /// The atlas, `None` if the textures do not fit.
+    pub fn build(&self) -> Option<Atlas> {
+        let padded = |image: &RgbaImage| {
+            (
+                image.width() + 2 * self.padding,
+                image.height() + 2 * self.padding,
+            )
+        };
+        let mut order = (0..self.images.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| std::cmp::Reverse(self.images[i].height()));
+
+        let mut positions = vec![(0, 0); self.images.len()];
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+        for &i in &order {
+            let (width, height) = padded(&self.images[i]);
+            if x + width > self.size {
+                y += shelf_height;
+                x = 0;
+                shelf_height = 0;
+            }
+            if x + width > self.size || y + height > self.size {
+                return None;
+            }
+            positions[i] = (x, y);
+            x += width;
+            shelf_height = shelf_height.max(height);
+        }
+        let atlas_height = (y + shelf_height).max(1);
+
+        let mut atlas = RgbaImage::new(self.size, atlas_height);
+        let mut rects = Vec::with_capacity(self.images.len());
+        for (image, &(x, y)) in self.images.iter().zip(&positions) {
+            let (width, height) = padded(image);
+            for py in 0..height {
+                for px in 0..width {
+                    let sx = px.saturating_sub(self.padding).min(image.width() - 1);
+                    let sy = py.saturating_sub(self.padding).min(image.height() - 1);
+                    atlas.put_pixel(x + px, y + py, *image.get_pixel(sx, sy));
+                }
+            }
+            rects.push(AtlasRect {
+                offset: [
+                    (x + self.padding) as f32 / self.size as f32,
+                    (y + self.padding) as f32 / atlas_height as f32,
+                ],
+                scale: [
+                    image.width() as f32 / self.size as f32,
+                    image.height() as f32 / atlas_height as f32,
+                ],
+            });
+        }
+
+        Some(Atlas {
+            image: atlas,
+            rects,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_with_padding() {
+        let red = image::Rgba([255, 0, 0, 255]);
+        let blue = image::Rgba([0, 0, 255, 255]);
+        let mut builder = AtlasBuilder::new(16, 1);
+        let small = builder.add(RgbaImage::from_pixel(2, 2, red));
+        let tall = builder.add(RgbaImage::from_pixel(4, 6, blue));
+
+        let atlas = builder.build().unwrap();
+        // The tall texture is on the first shelf, the small one next to it
+        assert_eq!(atlas.image.dimensions(), (16, 8));
+        assert_eq!(
+            atlas.rects[tall].map_uv([0.0, 0.0]),
+            [1.0 / 16.0, 1.0 / 8.0]
+        );
+        let [u, v] = atlas.rects[small].map_uv([0.5, 0.5]);
+        assert_eq!(
+            *atlas.image.get_pixel((u * 16.0) as u32, (v * 8.0) as u32),
+            red
+        );
+        // The padding repeats the edge
+        assert_eq!(*atlas.image.get_pixel(0, 0), blue);
+
+        builder.add(RgbaImage::from_pixel(15, 15, red));
+        assert!(builder.build().is_none());
+    }
+}
@@ -0,0 +1,167 @@
+/// The smallest buffer allocated, in bytes.
+const MIN_SIZE: u64 = 256;
+/// The frames in a row a buffer has to use under a quarter of its size before it shrinks.
+const SHRINK_AFTER_FRAMES: u32 = 300;
+
+/// The growth strategy of a buffer, separate from the GPU so it can be tested.
+///
+/// The size doubles to the next power of two when the contents do not fit, so growing by one
+/// instance at a time reallocates rarely. It halves only after the contents stayed small for a while,
+/// so a count going back and forth around a power of two does not reallocate every frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Capacity {
+    size: u64,
+    underused_frames: u32,
+}
+
+impl Capacity {
+    fn new(size: u64) -> Self {
+        Self {
+            size: size.max(MIN_SIZE).next_power_of_two(),
+            underused_frames: 0,
+        }
+    }
+
+    /// Fit the size to the bytes needed this frame.
+    ///
+    /// # Returns
+    ///
+    /// True if the size changed.
+    fn fit(&mut self, needed: u64) -> bool {
+        if needed > self.size {
+            *self = Self::new(needed);
+            return true;
+        }
+        if needed.saturating_mul(4) >= self.size || self.size == MIN_SIZE {
+            self.underused_frames = 0;
+            return false;
+        }
+        self.underused_frames += 1;
+        if self.underused_frames < SHRINK_AFTER_FRAMES {
+            return false;
+        }
+        // The contents keep room to grow twice before reallocating again
+        *self = Self::new(needed.saturating_mul(2));
+        true
+    }
+}
+
+/// A GPU buffer rewritten each frame that grows with its contents and shrinks when they stay small.
+///
+/// The particle buffers are only rewritten when their capacity changes, in between single particles
+/// are written into `buffer()` directly.
+///
+/// The contents are uploaded with `wgpu::Queue::write_buffer`, which stages them in memory
+/// wgpu keeps mapped, wgpu has no persistently mapped vertex or storage buffers to write into directly.
+#[derive(Debug)]
+pub(crate) struct GrowableBuffer {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    buffer: wgpu::Buffer,
+    capacity: Capacity,
+    /// The bytes written by the last `write`.
+    len: u64,
+}
+
+impl GrowableBuffer {
+    /// Create a buffer with room for `size` bytes, `COPY_DST` is added to the usage.
+    pub fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        size: u64,
+    ) -> Self {
+        let usage = usage | wgpu::BufferUsages::COPY_DST;
+        let capacity = Capacity::new(size);
+        Self {
+            label,
+            usage,
+            buffer: Self::create(device, label, usage, capacity.size),
+            capacity,
+            len: 0,
+        }
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        size: u64,
+    ) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size,
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// Get the written part of the buffer, e.g. to bind it as a vertex buffer.
+    pub fn slice(&self) -> wgpu::BufferSlice<'_> {
+        self.buffer
+            .slice(..self.len.max(wgpu::COPY_BUFFER_ALIGNMENT))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the size of the buffer in bytes.
+    pub fn size(&self) -> u64 {
+        self.buffer.size()
+    }
+
+    /// Replace the contents, the buffer is reallocated if they do not fit or stayed small for a while.
+    ///
+    /// # Returns
+    ///
+    /// True if the buffer was reallocated, the bind groups using it have to be created again.
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, contents: &[u8]) -> bool {
+        self.len = contents.len() as u64;
+        let reallocated = self.capacity.fit(self.len);
+        if reallocated {
+            log::debug!(
+                "[Renderer] {} reallocated with {} bytes",
+                self.label,
+                self.capacity.size
+            );
+            self.buffer = Self::create(device, self.label, self.usage, self.capacity.size);
+        }
+        if !contents.is_empty() {
+            queue.write_buffer(&self.buffer, 0, contents);
+        }
+        reallocated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capacity_grows_and_shrinks() {
+        let mut capacity = Capacity::new(0);
+        assert_eq!(capacity.size, MIN_SIZE);
+        assert!(!capacity.fit(200));
+
+        // Growing doubles to the next power of two
+        assert!(capacity.fit(1000));
+        assert_eq!(capacity.size, 1024);
+        assert!(!capacity.fit(1024));
+
+        // A short dip keeps the size, only a long one shrinks it
+        for _ in 0..SHRINK_AFTER_FRAMES - 1 {
+            assert!(!capacity.fit(100));
+        }
+        assert!(!capacity.fit(600));
+        for _ in 0..SHRINK_AFTER_FRAMES - 1 {
+            assert!(!capacity.fit(100));
+        }
+        assert!(capacity.fit(100));
+        assert_eq!(capacity.size, MIN_SIZE);
+    }
+}
@@ -1,17 +1,33 @@
-use cgmath::{perspective, InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Vector3};
+use cgmath::{perspective, InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Vector2, Vector3};
 use winit::{
     dpi::PhysicalPosition,
     event::{ElementState, KeyEvent, MouseScrollDelta, WindowEvent},
     keyboard::{KeyCode, PhysicalKey},
 };
 
-use super::{OPENGL_TO_WGPU_MATRIX, SAFE_FRAC_PI_2};
+use crate::ecs::components::OrbitCameraController;
+use crate::input::gamepad::{GamepadButton, GamepadState};
+use crate::math::{OPENGL_TO_WGPU_MATRIX, SAFE_FRAC_PI_2};
+
+/// The turn of a fully tilted stick, like a mouse moved this much each frame.
+const GAMEPAD_LOOK_SPEED: f32 = 10.0;
+/// The pixels of a touchpad scroll counted as a line of a mouse wheel.
+const PIXELS_PER_LINE: f32 = 100.0;
+/// The distance to a followed target and the field of view change by this factor per line.
+const ZOOM_STEP: f32 = 1.1;
+/// The distance the free camera moves per line.
+const DOLLY_PER_LINE: f32 = 1.0;
+/// The closest the camera zooms to a followed target.
+const MIN_FOLLOW_DISTANCE: f32 = 0.5;
+/// The narrowest field of view of the fixed camera, a fraction of its own.
+const MIN_FOV_ZOOM: f32 = 0.2;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub(crate) struct CameraUniform {
     pub view_pos: [f32; 4],
     pub view_proj: [[f32; 4]; 4],
+    pub prev_view_proj: [[f32; 4]; 4],
 }
 
 impl CameraUniform {
@@ -19,10 +35,13 @@ impl CameraUniform {
         Self {
             view_pos: [0.0; 4],
             view_proj: cgmath::Matrix4::identity().into(),
+            prev_view_proj: cgmath::Matrix4::identity().into(),
         }
     }
 
+    /// Update the matrices, the current view projection becomes the previous one.
     pub fn update_view_proj(&mut self, camera: &Camera, projection: &Projection) {
+        self.prev_view_proj = self.view_proj;
         self.view_pos = camera.position.to_homogeneous().into();
         self.view_proj = (projection.calc_matrix() * camera.calc_matrix()).into();
     }
@@ -62,15 +81,33 @@ impl Camera {
         }
     }
 
-    pub fn calc_matrix(&self) -> Matrix4<f32> {
+    /// Get the normalized direction the camera is looking at.
+    pub fn forward(&self) -> Vector3<f32> {
         let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
         let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
 
-        Matrix4::look_to_rh(
-            self.position,
-            Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize(),
-            Vector3::unit_y(),
-        )
+        Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize()
+    }
+
+    /// Turn the camera towards a point.
+    pub fn look_at(&mut self, target: Point3<f32>) {
+        let direction = target - self.position;
+        if direction.magnitude2() <= f32::EPSILON {
+            return;
+        }
+
+        let direction = direction.normalize();
+        self.pitch = Rad(direction.y.asin());
+        self.yaw = Rad(direction.z.atan2(direction.x));
+    }
+
+    /// Move the camera back along its view direction, so it looks at a point from a distance.
+    pub fn frame(&mut self, target: Point3<f32>, distance: f32) {
+        self.position = target - self.forward() * distance;
+    }
+
+    pub fn calc_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_to_rh(self.position, self.forward(), Vector3::unit_y())
     }
 }
 
@@ -95,11 +132,45 @@ impl Projection {
         self.aspect = width as f32 / height as f32;
     }
 
+    pub fn znear(&self) -> f32 {
+        self.znear
+    }
+
+    pub fn zfar(&self) -> f32 {
+        self.zfar
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    pub fn fovy(&self) -> Rad<f32> {
+        self.fovy
+    }
+
+    pub fn set_fovy<F: Into<Rad<f32>>>(&mut self, fovy: F) {
+        self.fovy = fovy.into();
+    }
+
+    pub fn set_planes(&mut self, znear: f32, zfar: f32) {
+        self.znear = znear;
+        self.zfar = zfar;
+    }
+
     pub fn calc_matrix(&self) -> Matrix4<f32> {
         OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, self.znear, self.zfar)
     }
 }
 
+/// What the mouse wheel does to the camera, a followed target is always zoomed to by its distance.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ScrollZoom {
+    /// Move the camera along its view.
+    Dolly,
+    /// Narrow the field of view, for a camera which does not move.
+    FieldOfView,
+}
+
 #[derive(Debug)]
 pub(crate) struct CameraController {
     amount_left: f32,
@@ -110,9 +181,21 @@ pub(crate) struct CameraController {
     amount_down: f32,
     rotate_horizontal: f32,
     rotate_vertical: f32,
+    /// The lines scrolled since the last update, positive to zoom in.
     scroll: f32,
+    scroll_zoom: ScrollZoom,
+    scroll_sensitivity: f32,
+    /// The field of view is scaled by it, from `MIN_FOV_ZOOM` to 1.
+    fov_zoom: f32,
+    /// The left stick, the right stick and the bumpers of the gamepads, added to the keys and the mouse.
+    gamepad_move: Vector2<f32>,
+    gamepad_look: Vector2<f32>,
+    gamepad_lift: f32,
     speed: f32,
     sensitivity: f32,
+    frame_requested: bool,
+    /// The position of the followed target in the last frame.
+    follow_target: Option<Point3<f32>>,
 }
 
 impl CameraController {
@@ -127,11 +210,39 @@ impl CameraController {
             rotate_horizontal: 0.0,
             rotate_vertical: 0.0,
             scroll: 0.0,
+            scroll_zoom: ScrollZoom::Dolly,
+            scroll_sensitivity: 1.0,
+            fov_zoom: 1.0,
+            gamepad_move: Vector2::new(0.0, 0.0),
+            gamepad_look: Vector2::new(0.0, 0.0),
+            gamepad_lift: 0.0,
             speed,
             sensitivity,
+            frame_requested: false,
+            follow_target: None,
         }
     }
 
+    pub fn with_scroll_zoom(mut self, scroll_zoom: ScrollZoom) -> Self {
+        self.scroll_zoom = scroll_zoom;
+        self
+    }
+
+    /// Scale the zoom of the mouse wheel, a negative sensitivity inverts it.
+    pub fn set_scroll_sensitivity(&mut self, sensitivity: f32) {
+        self.scroll_sensitivity = sensitivity;
+    }
+
+    /// Get the scale of the field of view zoomed with the mouse wheel, 1 when not zoomed.
+    pub fn fov_zoom(&self) -> f32 {
+        self.fov_zoom
+    }
+
+    /// Check if framing the selection was requested since the last call.
+    pub fn take_frame_request(&mut self) -> bool {
+        std::mem::take(&mut self.frame_requested)
+    }
+
     pub fn process_keyboard(&mut self, key: KeyCode, state: ElementState) -> bool {
         let amount = if state == ElementState::Pressed {
             1.0
@@ -163,6 +274,10 @@ impl CameraController {
                 self.amount_down = amount;
                 true
             }
+            KeyCode::KeyF => {
+                self.frame_requested |= state == ElementState::Pressed;
+                true
+            }
             _ => false,
         }
     }
@@ -172,12 +287,46 @@ impl CameraController {
         self.rotate_vertical = mouse_dy as f32;
     }
 
+    /// Move with the left stick, look around with the right one and rise or sink with the bumpers.
+    pub fn process_gamepad(&mut self, gamepads: &GamepadState) {
+        self.gamepad_move = gamepads.left_stick();
+        self.gamepad_look = gamepads.right_stick();
+        let held = |button| f32::from(gamepads.is_pressed(button));
+        self.gamepad_lift = held(GamepadButton::RightBumper) - held(GamepadButton::LeftBumper);
+        self.frame_requested |= gamepads.just_pressed(GamepadButton::North);
+    }
+
+    /// Add a scroll of the mouse wheel or the touchpad, it is applied on the next update.
     pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
-        self.scroll = -match delta {
-            // Assuming a line is about 100 pixels
-            MouseScrollDelta::LineDelta(_, scroll) => scroll * 100.0,
-            MouseScrollDelta::PixelDelta(PhysicalPosition { y: scroll, .. }) => *scroll as f32,
-        };
+        self.scroll += match delta {
+            MouseScrollDelta::LineDelta(_, lines) => *lines,
+            MouseScrollDelta::PixelDelta(PhysicalPosition { y: pixels, .. }) => {
+                *pixels as f32 / PIXELS_PER_LINE
+            }
+        } * self.scroll_sensitivity;
+    }
+
+    /// Zoom by the lines scrolled since the last update.
+    fn apply_scroll(&mut self, camera: &mut Camera) {
+        let lines = std::mem::take(&mut self.scroll);
+        if lines == 0.0 {
+            return;
+        }
+        let factor = ZOOM_STEP.powf(-lines);
+        if let Some(target) = self.follow_target {
+            let offset = camera.position - target;
+            let distance = (offset.magnitude() * factor).max(MIN_FOLLOW_DISTANCE);
+            if offset.magnitude2() > 0.0 {
+                camera.position = target + offset.normalize() * distance;
+            }
+            return;
+        }
+        match self.scroll_zoom {
+            ScrollZoom::Dolly => camera.position += camera.forward() * lines * DOLLY_PER_LINE,
+            ScrollZoom::FieldOfView => {
+                self.fov_zoom = (self.fov_zoom * factor).clamp(MIN_FOV_ZOOM, 1.0);
+            }
+        }
     }
 
     pub fn update_camera(&mut self, camera: &mut Camera, dt: instant::Duration) {
@@ -187,26 +336,25 @@ impl CameraController {
         let (yaw_sin, yaw_cos) = camera.yaw.0.sin_cos();
         let forward = Vector3::new(yaw_cos, 0.0, yaw_sin).normalize();
         let right = Vector3::new(-yaw_sin, 0.0, yaw_cos).normalize();
-        camera.position += forward * (self.amount_forward - self.amount_backward) * self.speed * dt;
-        camera.position += right * (self.amount_right - self.amount_left) * self.speed * dt;
-
-        // Move in/out (aka. "zoom")
-        // Note: this isn't an actual zoom. The camera's position
-        // changes when zooming. I've added this to make it easier
-        // to get closer to an object you want to focus on.
-        let (pitch_sin, pitch_cos) = camera.pitch.0.sin_cos();
-        let scrollward =
-            Vector3::new(pitch_cos * yaw_cos, pitch_sin, pitch_cos * yaw_sin).normalize();
-        camera.position += scrollward * self.scroll * self.speed * self.sensitivity * dt;
-        self.scroll = 0.0;
+        camera.position += forward
+            * (self.amount_forward - self.amount_backward + self.gamepad_move.y)
+            * self.speed
+            * dt;
+        camera.position +=
+            right * (self.amount_right - self.amount_left + self.gamepad_move.x) * self.speed * dt;
+
+        // Zoom with the mouse wheel
+        self.apply_scroll(camera);
 
         // Move up/down. Since we don't use roll, we can just
         // modify the y coordinate directly.
-        camera.position.y += (self.amount_up - self.amount_down) * self.speed * dt;
+        camera.position.y +=
+            (self.amount_up - self.amount_down + self.gamepad_lift) * self.speed * dt;
 
-        // Rotate
-        camera.yaw += Rad(self.rotate_horizontal) * self.sensitivity * dt;
-        camera.pitch += Rad(-self.rotate_vertical) * self.sensitivity * dt;
+        // Rotate, the stick up is looking up while the mouse moving down is
+        let look = self.gamepad_look * GAMEPAD_LOOK_SPEED;
+        camera.yaw += Rad(self.rotate_horizontal + look.x) * self.sensitivity * dt;
+        camera.pitch += Rad(look.y - self.rotate_vertical) * self.sensitivity * dt;
 
         // If process_mouse isn't called every frame, these values
         // will not get set to zero, and the camera will rotate
@@ -221,4 +369,123 @@ impl CameraController {
             camera.pitch = Rad(SAFE_FRAC_PI_2);
         }
     }
+
+    /// Move the camera with a followed target and turn it towards the target.
+    /// Call it after `update_camera`, so the movement keys still move the camera around the target.
+    pub fn follow(&mut self, camera: &mut Camera, target: Point3<f32>) {
+        if let Some(last) = self.follow_target.replace(target) {
+            camera.position += target - last;
+        }
+        camera.look_at(target);
+    }
+
+    /// Orbit the camera around a point with the mouse and the right stick, and zoom it with the wheel.
+    /// It replaces `update_camera` for a camera entity with an `OrbitCameraController`.
+    ///
+    /// # Arguments
+    ///
+    /// * `camera` - The camera to move.
+    /// * `orbit` - The orbit of the camera, the input changes its angles and distance.
+    /// * `pivot` - The point orbited.
+    /// * `obstacle` - Find the distance to the nearest collider along a ray from a point, if any.
+    /// * `dt` - The delta time since the last update.
+    pub fn update_orbit(
+        &mut self,
+        camera: &mut Camera,
+        orbit: &mut OrbitCameraController,
+        pivot: Point3<f32>,
+        obstacle: impl FnOnce(Point3<f32>, Vector3<f32>, f32) -> Option<f32>,
+        dt: instant::Duration,
+    ) {
+        let turn = orbit.sensitivity * dt.as_secs_f32();
+        let look = self.gamepad_look * GAMEPAD_LOOK_SPEED;
+        let yaw = Rad((std::mem::take(&mut self.rotate_horizontal) + look.x) * turn);
+        let pitch = Rad((look.y - std::mem::take(&mut self.rotate_vertical)) * turn);
+        orbit.rotate(yaw.into(), pitch.into());
+
+        let lines = std::mem::take(&mut self.scroll);
+        if lines != 0.0 {
+            orbit.zoom(ZOOM_STEP.powf(-lines));
+        }
+
+        let back = -orbit.forward();
+        let distance = orbit.collided_distance(obstacle(pivot, back, orbit.distance));
+        camera.position = pivot + back * distance;
+        camera.yaw = orbit.yaw.into();
+        camera.pitch = orbit.pitch.into();
+    }
+
+    /// Forget the followed target, the next `follow` starts from the current position.
+    pub fn stop_following(&mut self) {
+        self.follow_target = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instant::Duration;
+
+    #[test]
+    fn test_scroll_zoom() {
+        let mut camera = Camera::new((0.0, 0.0, 10.0), cgmath::Deg(-90.0), cgmath::Deg(0.0));
+        let mut controller =
+            CameraController::new(1.0, 1.0).with_scroll_zoom(ScrollZoom::FieldOfView);
+
+        // The full field of view is the widest
+        controller.process_scroll(&MouseScrollDelta::LineDelta(0.0, -3.0));
+        controller.update_camera(&mut camera, Duration::ZERO);
+        assert_eq!(controller.fov_zoom(), 1.0);
+
+        // A line of the wheel and the pixels of a line of the touchpad
+        controller.process_scroll(&MouseScrollDelta::LineDelta(0.0, 1.0));
+        controller.process_scroll(&MouseScrollDelta::PixelDelta(PhysicalPosition::new(
+            0.0, 100.0,
+        )));
+        controller.update_camera(&mut camera, Duration::ZERO);
+        assert!((controller.fov_zoom() - ZOOM_STEP.powi(-2)).abs() < 1e-5);
+
+        // A followed target is zoomed to by the distance instead
+        let target = Point3::new(0.0, 0.0, 0.0);
+        controller.follow(&mut camera, target);
+        controller.set_scroll_sensitivity(2.0);
+        controller.process_scroll(&MouseScrollDelta::LineDelta(0.0, 1.0));
+        controller.update_camera(&mut camera, Duration::ZERO);
+        let distance = (camera.position - target).magnitude();
+        assert!((distance - 10.0 * ZOOM_STEP.powi(-2)).abs() < 1e-4);
+        assert!((controller.fov_zoom() - ZOOM_STEP.powi(-2)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_orbit() {
+        let mut camera = Camera::new((0.0, 0.0, 0.0), cgmath::Deg(0.0), cgmath::Deg(0.0));
+        let mut controller = CameraController::new(1.0, 1.0);
+        let mut orbit = OrbitCameraController::new(crate::ecs::Entity(1), 4.0)
+            .with_angles(cgmath::Deg(-90.0), cgmath::Deg(0.0))
+            .with_distance_range(1.0, 10.0);
+        let pivot = Point3::new(0.0, 1.0, 0.0);
+
+        // Behind the target, looking at it along -z
+        controller.update_orbit(
+            &mut camera,
+            &mut orbit,
+            pivot,
+            |_, _, _| None,
+            Duration::ZERO,
+        );
+        assert!((camera.position - Point3::new(0.0, 1.0, 4.0)).magnitude() < 1e-4);
+        assert!((camera.forward() - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-4);
+
+        // Zoomed out, but in front of a wall between the camera and the target
+        controller.process_scroll(&MouseScrollDelta::LineDelta(0.0, -2.0));
+        controller.update_orbit(
+            &mut camera,
+            &mut orbit,
+            pivot,
+            |_, _, _| Some(3.0),
+            Duration::ZERO,
+        );
+        assert!((orbit.distance - 4.0 * ZOOM_STEP.powi(2)).abs() < 1e-4);
+        assert!(((camera.position - pivot).magnitude() - 2.8).abs() < 1e-4);
+    }
 }
@@ -0,0 +1,7 @@
+// The camera uniform of the passes drawn from the camera.
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+    prev_view_proj: mat4x4<f32>,
+}
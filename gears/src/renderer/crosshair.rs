@@ -0,0 +1,228 @@
+use super::buffer::GrowableBuffer;
+use crate::ecs::traits::Component;
+
+/// The style of the crosshair in the middle of the views, shown with the `RuntimeConfig`.
+/// Add it to any entity to change it, the first one found is used.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Crosshair {
+    pub color: [f32; 4],
+    /// The length of each arm in pixels.
+    pub size: f32,
+    /// The width of the arms in pixels.
+    pub thickness: f32,
+    /// The empty space between the center and the arms in pixels.
+    pub gap: f32,
+}
+
+impl Default for Crosshair {
+    fn default() -> Self {
+        Self {
+            color: [1.0, 1.0, 1.0, 0.8],
+            size: 8.0,
+            thickness: 2.0,
+            gap: 4.0,
+        }
+    }
+}
+
+impl Component for Crosshair {}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct CrosshairVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl CrosshairVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+            wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<CrosshairVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Get the triangles of the four arms of a crosshair.
+///
+/// # Arguments
+///
+/// * `style` - The crosshair.
+/// * `center` - The center in pixels from the top left corner of the frame.
+/// * `frame` - The width and the height of the frame in pixels.
+///
+/// # Returns
+///
+/// Six vertices in clip space for each arm.
+pub(crate) fn crosshair_vertices(
+    style: &Crosshair,
+    center: [f32; 2],
+    frame: (u32, u32),
+) -> Vec<CrosshairVertex> {
+    let (width, height) = (frame.0.max(1) as f32, frame.1.max(1) as f32);
+    let half = style.thickness * 0.5;
+    let (near, far) = (style.gap, style.gap + style.size);
+    // The rectangles of the arms around the center as min x, min y, max x, max y
+    let arms = [
+        [near, -half, far, half],
+        [-far, -half, -near, half],
+        [-half, near, half, far],
+        [-half, -far, half, -near],
+    ];
+
+    let to_clip = |x: f32, y: f32| {
+        [
+            (center[0] + x) / width * 2.0 - 1.0,
+            1.0 - (center[1] + y) / height * 2.0,
+        ]
+    };
+    arms.iter()
+        .flat_map(|&[x0, y0, x1, y1]| [(x0, y0), (x1, y0), (x1, y1), (x0, y0), (x1, y1), (x0, y1)])
+        .map(|(x, y)| CrosshairVertex {
+            position: to_clip(x, y),
+            color: style.color,
+        })
+        .collect()
+}
+
+/// The crosshair pass, drawn over the post-processed frame at the center of each view.
+pub(crate) struct CrosshairRenderer {
+    pipeline: wgpu::RenderPipeline,
+    vertices: GrowableBuffer,
+    count: u32,
+}
+
+impl CrosshairRenderer {
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Crosshair Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("crosshair.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Crosshair Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Crosshair Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[CrosshairVertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            vertices: GrowableBuffer::new(
+                device,
+                "Crosshair Vertex Buffer",
+                wgpu::BufferUsages::VERTEX,
+                24 * std::mem::size_of::<CrosshairVertex>() as u64,
+            ),
+            count: 0,
+        }
+    }
+
+    /// Update the crosshairs of the views.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - The device.
+    /// * `queue` - The queue.
+    /// * `style` - The crosshair.
+    /// * `centers` - The centers of the views in pixels from the top left corner.
+    /// * `frame` - The width and the height of the frame in pixels.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        style: &Crosshair,
+        centers: &[[f32; 2]],
+        frame: (u32, u32),
+    ) {
+        let vertices = centers
+            .iter()
+            .flat_map(|&center| crosshair_vertices(style, center, frame))
+            .collect::<Vec<_>>();
+        self.count = vertices.len() as u32;
+        if !vertices.is_empty() {
+            self.vertices
+                .write(device, queue, bytemuck::cast_slice(&vertices));
+        }
+    }
+
+    /// Draw the crosshairs over the frame.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        if self.count == 0 {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Crosshair Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, self.vertices.slice());
+        render_pass.draw(0..self.count, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crosshair_vertices() {
+        let style = Crosshair {
+            color: [1.0; 4],
+            size: 10.0,
+            thickness: 2.0,
+            gap: 0.0,
+        };
+        let vertices = crosshair_vertices(&style, [50.0, 50.0], (100, 100));
+        assert_eq!(vertices.len(), 24);
+        // The right arm reaches 10 pixels from the center of the frame
+        let max_x = vertices
+            .iter()
+            .map(|vertex| vertex.position[0])
+            .fold(f32::MIN, f32::max);
+        assert!((max_x - 0.2).abs() < 1e-6);
+        // The pixels grow down, the clip space up
+        let min_y = vertices
+            .iter()
+            .map(|vertex| vertex.position[1])
+            .fold(f32::MAX, f32::min);
+        assert!((min_y + 0.2).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,581 @@
+use super::buffer::GrowableBuffer;
+use super::{model, preprocess, resources, texture};
+use crate::ecs::components::Pos3;
+use crate::ecs::{self, Entity};
+use crate::gameplay::skinning::{
+    AnimationTexture, CrowdAnimation, SkinnedCrowd, SkinnedMesh, SkinnedVertex,
+};
+use cgmath::{Matrix4, Quaternion};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
+
+impl model::Vertex for SkinnedVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<SkinnedVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Uint32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct CrowdInstanceRaw {
+    pub model: [[f32; 4]; 4],
+    /// The first frame of the clip, its frames, the frames per second and the offset in frames.
+    pub animation: [f32; 4],
+}
+
+impl CrowdInstanceRaw {
+    /// Get the instance of a crowd member.
+    ///
+    /// # Returns
+    ///
+    /// The instance, `None` if the clip of the member does not exist.
+    pub fn new(pos: &Pos3, member: &CrowdAnimation, animations: &AnimationTexture) -> Option<Self> {
+        let clip = animations.clips.get(member.clip)?;
+        let rotation = pos.rot.unwrap_or(Quaternion::new(1.0, 0.0, 0.0, 0.0));
+        let model = Matrix4::from_translation(pos.pos) * Matrix4::from(rotation);
+
+        Some(Self {
+            model: model.into(),
+            animation: [
+                clip.first_frame as f32,
+                clip.frames as f32,
+                clip.frame_rate * member.speed,
+                member.time_offset * clip.frame_rate,
+            ],
+        })
+    }
+}
+
+impl model::Vertex for CrowdInstanceRaw {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<CrowdInstanceRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct CrowdGlobals {
+    /// The seconds the crowds have been animated for.
+    pub time: f32,
+    pub _padding: [f32; 3],
+}
+
+struct GpuCrowd {
+    mesh: Arc<SkinnedMesh>,
+    animations: Arc<AnimationTexture>,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    num_indices: u32,
+    animation_texture: wgpu::Texture,
+    animation_bind_group: wgpu::BindGroup,
+    instance_buffer: GrowableBuffer,
+    instances: u32,
+    texture: Option<String>,
+}
+
+/// Draws the skinned crowds as a single instanced draw each.
+/// The clips are baked into an animation texture, so the vertex shader poses every member
+/// from its clip, time offset and speed, and the CPU only writes their transforms.
+pub(crate) struct CrowdRenderer {
+    globals: CrowdGlobals,
+    globals_buffer: wgpu::Buffer,
+    animation_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    crowds: HashMap<Entity, GpuCrowd>,
+    /// Bound for the crowds without a texture.
+    white_texture: wgpu::BindGroup,
+    textures: HashMap<String, wgpu::BindGroup>,
+    failed: HashSet<String>,
+}
+
+impl CrowdRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        light_bind_group_layout: &wgpu::BindGroupLayout,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        velocity_format: wgpu::TextureFormat,
+    ) -> Self {
+        let animation_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("crowd_animation_layout"),
+        });
+
+        let globals = CrowdGlobals {
+            time: 0.0,
+            _padding: [0.0; 3],
+        };
+        let globals_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Crowd Globals Buffer"),
+            contents: bytemuck::cast_slice(&[globals]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // The crowds are lit like the models, without the specular highlights
+        let shader = device.create_shader_module(preprocess::shader_module(
+            "Crowd Shader",
+            "crowd",
+            &preprocess::ShaderDefines::new(),
+        ));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Crowd Pipeline Layout"),
+            bind_group_layouts: &[
+                camera_bind_group_layout,
+                light_bind_group_layout,
+                texture_bind_group_layout,
+                &animation_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Crowd Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    <SkinnedVertex as model::Vertex>::desc(),
+                    <CrowdInstanceRaw as model::Vertex>::desc(),
+                ],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    Some(wgpu::ColorTargetState {
+                        format: velocity_format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                ],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let white = image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4]));
+        let white = texture::Texture::from_mips(device, queue, &[white], Some("Crowd White"))
+            .expect("A 1x1 texture can always be created");
+        let white_texture =
+            model::Material::create_bind_group(device, texture_bind_group_layout, &white);
+
+        Self {
+            globals,
+            globals_buffer,
+            animation_layout,
+            pipeline,
+            crowds: HashMap::new(),
+            white_texture,
+            textures: HashMap::new(),
+            failed: HashSet::new(),
+        }
+    }
+
+    fn create_crowd(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        crowd: &SkinnedCrowd,
+    ) -> GpuCrowd {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Crowd Vertex Buffer"),
+            contents: bytemuck::cast_slice(&crowd.mesh.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Crowd Index Buffer"),
+            contents: bytemuck::cast_slice(&crowd.mesh.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let animations = &crowd.animations;
+        let size = wgpu::Extent3d {
+            width: animations.width,
+            height: animations.height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let animation_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Crowd Animation Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        if !animations.texels.is_empty() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &animation_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                bytemuck::cast_slice(&animations.texels),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(16 * animations.width),
+                    rows_per_image: Some(animations.height),
+                },
+                size,
+            );
+        }
+        let view = animation_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let animation_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.animation_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.globals_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("crowd_animation_bind_group"),
+        });
+
+        GpuCrowd {
+            mesh: Arc::clone(&crowd.mesh),
+            animations: Arc::clone(&crowd.animations),
+            vertex_buffer,
+            index_buffer,
+            num_indices: crowd.mesh.indices.len() as u32,
+            animation_texture,
+            animation_bind_group,
+            instance_buffer: GrowableBuffer::new(
+                device,
+                "Crowd Instance Buffer",
+                wgpu::BufferUsages::VERTEX,
+                std::mem::size_of::<CrowdInstanceRaw>() as u64,
+            ),
+            instances: 0,
+            texture: None,
+        }
+    }
+
+    /// Get the size of the crowd buffers and animation textures in bytes.
+    pub fn gpu_memory(&self) -> u64 {
+        self.crowds
+            .values()
+            .map(|gpu| {
+                let texture = gpu.animation_texture.size();
+                gpu.vertex_buffer.size()
+                    + gpu.index_buffer.size()
+                    + gpu.instance_buffer.size()
+                    + texture.width as u64 * texture.height as u64 * 16
+            })
+            .sum()
+    }
+
+    /// Get the textures of the crowds which are not loaded yet.
+    pub fn missing_textures(&self, ecs: &ecs::Manager) -> HashSet<String> {
+        ecs.get_all_components_of_type::<SkinnedCrowd>()
+            .iter()
+            .filter_map(|(_, component)| component.read().unwrap().texture.clone())
+            .filter(|path| !self.textures.contains_key(path) && !self.failed.contains(path))
+            .collect()
+    }
+
+    /// Load the textures returned by `missing_textures`, the ECS does not have to stay locked meanwhile.
+    pub async fn load_textures(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        paths: HashSet<String>,
+    ) {
+        for path in paths {
+            match resources::load_texture(&path, device, queue).await {
+                Ok(texture) => {
+                    let bind_group = model::Material::create_bind_group(
+                        device,
+                        texture_bind_group_layout,
+                        &texture,
+                    );
+                    self.textures.insert(path, bind_group);
+                }
+                Err(e) => {
+                    log::warn!("[Crowd] Failed to load the texture {}: {}", path, e);
+                    self.failed.insert(path);
+                }
+            }
+        }
+    }
+
+    /// Sync the crowds with the ECS and write the instances of their members.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        ecs: &ecs::Manager,
+        dt: f32,
+    ) {
+        let crowds = ecs.get_all_components_of_type::<SkinnedCrowd>();
+        self.crowds
+            .retain(|entity, _| crowds.iter().any(|(e, _)| e == entity));
+        if crowds.is_empty() {
+            return;
+        }
+
+        self.globals.time += dt;
+        queue.write_buffer(
+            &self.globals_buffer,
+            0,
+            bytemuck::cast_slice(&[self.globals]),
+        );
+
+        // The members of each crowd
+        let mut instances = HashMap::<Entity, Vec<CrowdInstanceRaw>>::new();
+        for (entity, crowd) in &crowds {
+            let crowd = crowd.read().unwrap();
+            let recreate = self.crowds.get(entity).is_none_or(|gpu| {
+                !Arc::ptr_eq(&gpu.mesh, &crowd.mesh)
+                    || !Arc::ptr_eq(&gpu.animations, &crowd.animations)
+            });
+            if recreate {
+                let gpu = self.create_crowd(device, queue, &crowd);
+                self.crowds.insert(*entity, gpu);
+            }
+            let gpu = self.crowds.get_mut(entity).unwrap();
+            gpu.texture = crowd
+                .texture
+                .clone()
+                .filter(|path| self.textures.contains_key(path));
+            instances.insert(*entity, Vec::new());
+        }
+        for (entity, member) in ecs.get_all_components_of_type::<CrowdAnimation>() {
+            let member = *member.read().unwrap();
+            let (Some(gpu), Some(instances)) = (
+                self.crowds.get(&member.crowd),
+                instances.get_mut(&member.crowd),
+            ) else {
+                continue;
+            };
+            let Some(pos) = ecs.get_component_from_entity::<Pos3>(entity) else {
+                continue;
+            };
+            let pos = *pos.read().unwrap();
+            if let Some(instance) = CrowdInstanceRaw::new(&pos, &member, &gpu.animations) {
+                instances.push(instance);
+            }
+        }
+
+        for (entity, instances) in instances {
+            let gpu = self.crowds.get_mut(&entity).unwrap();
+            gpu.instance_buffer
+                .write(device, queue, bytemuck::cast_slice(&instances));
+            gpu.instances = instances.len() as u32;
+        }
+    }
+
+    /// Draw the crowds into the scene after the base pass, they write the depth like the models.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        velocity_view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        views: &[(&wgpu::BindGroup, Option<[f32; 4]>)],
+        light_bind_group: &wgpu::BindGroup,
+    ) {
+        if self.crowds.values().all(|gpu| gpu.instances == 0) {
+            return;
+        }
+
+        let load = |view| {
+            Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })
+        };
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Crowd Render Pass"),
+            color_attachments: &[load(color_view), load(velocity_view)],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(1, light_bind_group, &[]);
+
+        // Each camera with the viewport in pixels it is drawn to, `None` for the whole frame
+        for &(camera_bind_group, viewport) in views {
+            if let Some([x, y, width, height]) = viewport {
+                render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+            }
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            for gpu in self.crowds.values().filter(|gpu| gpu.instances > 0) {
+                let texture = gpu
+                    .texture
+                    .as_ref()
+                    .and_then(|path| self.textures.get(path));
+                render_pass.set_bind_group(2, texture.unwrap_or(&self.white_texture), &[]);
+                render_pass.set_bind_group(3, &gpu.animation_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, gpu.vertex_buffer.slice(..));
+                render_pass.set_vertex_buffer(1, gpu.instance_buffer.slice());
+                render_pass.set_index_buffer(gpu.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..gpu.num_indices, 0, 0..gpu.instances);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::skinning::{BonePose, SkeletalClip, Skeleton};
+    use cgmath::SquareMatrix;
+
+    #[test]
+    fn test_instance_matches_cpu_frames() {
+        let skeleton = Skeleton::new().with_bone("root", None, Matrix4::identity());
+        let clips = [
+            SkeletalClip::new("idle", 30.0).with_frame(vec![BonePose::default()]),
+            SkeletalClip::new("walk", 24.0)
+                .with_frame(vec![])
+                .with_frame(vec![]),
+        ];
+        let animations = AnimationTexture::bake(&skeleton, &clips).unwrap();
+        let member = CrowdAnimation::new(Entity(3), 1)
+            .with_speed(2.0)
+            .with_time_offset(0.125);
+
+        let pos = Pos3::new(cgmath::Vector3::new(1.0, 2.0, 3.0));
+        let instance = CrowdInstanceRaw::new(&pos, &member, &animations).unwrap();
+        assert_eq!(instance.model[3], [1.0, 2.0, 3.0, 1.0]);
+        assert_eq!(instance.animation, [1.0, 2.0, 48.0, 3.0]);
+
+        // The shader computes the frame like the CPU reference
+        let time = 0.01;
+        let position = time * instance.animation[2] + instance.animation[3];
+        let (a, _, blend) = member.frames(&animations, time).unwrap();
+        let wrapped = position.rem_euclid(instance.animation[1]);
+        assert_eq!(a, instance.animation[0] as u32 + wrapped as u32);
+        assert!((blend - wrapped.fract()).abs() < 1e-5);
+        assert!(
+            CrowdInstanceRaw::new(&pos, &CrowdAnimation::new(Entity(3), 5), &animations).is_none()
+        );
+    }
+}
@@ -0,0 +1,135 @@
+// Crowd pass: instanced skinned meshes posed from a baked animation texture
+
+#include "camera"
+#include "lighting"
+
+struct Globals {
+    time: f32,
+    _padding_0: f32,
+    _padding_1: f32,
+    _padding_2: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var<storage, read> lights: array<Light>;
+@group(1) @binding(1)
+var<uniform> light_grid: LightGrid;
+// The offset and the count of the light indices of every tile, then the indices
+@group(1) @binding(2)
+var<storage, read> light_tiles: array<u32>;
+
+@group(2) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(2) @binding(1)
+var s_diffuse: sampler;
+
+// A row per frame, three texels per bone holding the rows of its skinning matrix
+@group(3) @binding(0)
+var t_animation: texture_2d<f32>;
+@group(3) @binding(1)
+var<uniform> globals: Globals;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) joints: vec4<u32>,
+    @location(4) weights: vec4<f32>,
+}
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    // The first frame of the clip, its frames, the frames per second and the offset in frames
+    @location(9) animation: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) current_clip: vec4<f32>,
+    @location(4) previous_clip: vec4<f32>,
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) velocity: vec2<f32>,
+}
+
+fn bone_matrix(frame: u32, bone: u32) -> mat4x4<f32> {
+    let x = i32(bone * 3u);
+    let y = i32(frame);
+    let r0 = textureLoad(t_animation, vec2<i32>(x, y), 0);
+    let r1 = textureLoad(t_animation, vec2<i32>(x + 1, y), 0);
+    let r2 = textureLoad(t_animation, vec2<i32>(x + 2, y), 0);
+    return transpose(mat4x4<f32>(r0, r1, r2, vec4<f32>(0.0, 0.0, 0.0, 1.0)));
+}
+
+fn skin_matrix(frame: u32, joints: vec4<u32>, weights: vec4<f32>) -> mat4x4<f32> {
+    return bone_matrix(frame, joints.x) * weights.x
+        + bone_matrix(frame, joints.y) * weights.y
+        + bone_matrix(frame, joints.z) * weights.z
+        + bone_matrix(frame, joints.w) * weights.w;
+}
+
+@vertex
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    // The clips loop, the members blend between the two frames around their time
+    let first_frame = u32(instance.animation.x);
+    let frames = max(u32(instance.animation.y), 1u);
+    let position = globals.time * instance.animation.z + instance.animation.w;
+    let wrapped = position - floor(position / f32(frames)) * f32(frames);
+    let frame = min(u32(wrapped), frames - 1u);
+    let blend = wrapped - f32(frame);
+    let skin = skin_matrix(first_frame + frame, model.joints, model.weights) * (1.0 - blend)
+        + skin_matrix(first_frame + (frame + 1u) % frames, model.joints, model.weights) * blend;
+
+    let world_position = model_matrix * skin * vec4<f32>(model.position, 1.0);
+    let world_normal = (model_matrix * skin * vec4<f32>(model.normal, 0.0)).xyz;
+
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.world_normal = normalize(world_normal);
+    out.world_position = world_position.xyz;
+    out.clip_position = camera.view_proj * world_position;
+    // Only the camera motion is in the velocity, the poses of the last frame are not kept
+    out.current_clip = out.clip_position;
+    out.previous_clip = camera.prev_view_proj * world_position;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let object_color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+
+    // The lights of the base pass without the specular highlights
+    let result_color = shade(
+        in.clip_position.xy,
+        object_color.xyz,
+        in.world_position,
+        in.world_normal,
+        camera.view_pos.xyz,
+    );
+
+    let current = in.current_clip.xy / in.current_clip.w;
+    let previous = in.previous_clip.xy / in.previous_clip.w;
+
+    var out: FragmentOutput;
+    out.color = vec4<f32>(result_color, object_color.a);
+    out.velocity = (current - previous) * vec2<f32>(0.5, -0.5);
+    return out;
+}
@@ -0,0 +1,507 @@
+use super::buffer::GrowableBuffer;
+use super::camera::{Camera, Projection};
+use super::{resources, texture};
+use crate::core::Dt;
+use crate::ecs::components::Pos3;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector3};
+use std::collections::{HashMap, HashSet};
+use wgpu::util::DeviceExt;
+
+/// A component that projects a texture onto the geometry around the position of the entity,
+/// e.g. bullet holes and blood splatters.
+/// The texture is projected along the direction through a box of width x height x depth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decal {
+    /// The path of the texture, relative to the resources like the models.
+    pub texture: String,
+    pub width: f32,
+    pub height: f32,
+    /// The depth of the projection box, geometry outside of it is not affected.
+    pub depth: f32,
+    pub direction: Vector3<f32>,
+    /// The tint of the texture, the alpha scales the opacity.
+    pub color: [f32; 4],
+    /// The time in seconds after which the decal expires, `None` for permanent decals.
+    pub lifetime: Option<f32>,
+    /// The time in seconds the decal fades out before it expires.
+    pub fade_time: f32,
+    age: f32,
+}
+
+impl Component for Decal {}
+
+impl Decal {
+    pub fn new(texture: impl Into<String>, width: f32, height: f32) -> Self {
+        Self {
+            texture: texture.into(),
+            width,
+            height,
+            depth: 0.5,
+            direction: -Vector3::unit_y(),
+            color: [1.0; 4],
+            lifetime: None,
+            fade_time: 1.0,
+            age: 0.0,
+        }
+    }
+
+    pub fn with_direction(mut self, direction: Vector3<f32>) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_lifetime(mut self, lifetime: f32, fade_time: f32) -> Self {
+        self.lifetime = Some(lifetime);
+        self.fade_time = fade_time;
+        self
+    }
+
+    pub fn age(&self) -> f32 {
+        self.age
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.lifetime.is_some_and(|lifetime| self.age >= lifetime)
+    }
+
+    /// Get the opacity of the decal, it fades out linearly over the fade time before it expires.
+    pub fn opacity(&self) -> f32 {
+        match self.lifetime {
+            Some(lifetime) if self.fade_time > 0.0 => {
+                ((lifetime - self.age) / self.fade_time).clamp(0.0, 1.0)
+            }
+            Some(lifetime) if self.age >= lifetime => 0.0,
+            _ => 1.0,
+        }
+    }
+
+    /// Get the transform of the unit projection box into the world.
+    fn model_matrix(&self, position: Vector3<f32>) -> Matrix4<f32> {
+        let forward = if self.direction.magnitude2() > 0.0 {
+            self.direction.normalize()
+        } else {
+            -Vector3::unit_y()
+        };
+        let helper = if forward.y.abs() > 0.99 {
+            Vector3::unit_z()
+        } else {
+            Vector3::unit_y()
+        };
+        let right = helper.cross(forward).normalize();
+        let up = forward.cross(right);
+
+        let rotation = Matrix4::from_cols(
+            right.extend(0.0),
+            up.extend(0.0),
+            forward.extend(0.0),
+            cgmath::Vector4::unit_w(),
+        );
+
+        Matrix4::from_translation(position)
+            * rotation
+            * Matrix4::from_nonuniform_scale(self.width, self.height, self.depth)
+    }
+}
+
+/// Sent when a decal has expired, the owner can remove or reuse the entity.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DecalExpired {
+    pub entity: Entity,
+}
+
+/// Age the decals and send the expiry events.
+///
+/// # Arguments
+///
+/// * `ecs` - The entity component system manager.
+/// * `dt` - The delta time since the last update.
+pub fn update_decals(ecs: &ecs::Manager, dt: Dt) {
+    for (entity, decal) in ecs.get_all_components_of_type::<Decal>() {
+        let mut decal = decal.write().unwrap();
+        if decal.is_expired() {
+            continue;
+        }
+
+        decal.age += dt.as_secs_f32();
+        if decal.is_expired() {
+            ecs.send_event(DecalExpired { entity });
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct DecalInstance {
+    pub model: [[f32; 4]; 4],
+    pub inv_model: [[f32; 4]; 4],
+    pub color: [f32; 4],
+}
+
+impl DecalInstance {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 9] = wgpu::vertex_attr_array![
+            0 => Float32x4, 1 => Float32x4, 2 => Float32x4, 3 => Float32x4,
+            4 => Float32x4, 5 => Float32x4, 6 => Float32x4, 7 => Float32x4,
+            8 => Float32x4,
+        ];
+
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<DecalInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct DecalGlobals {
+    pub view_proj: [[f32; 4]; 4],
+    pub inv_view_proj: [[f32; 4]; 4],
+}
+
+/// The decal pass. It runs after the base pass and draws the projection box of each decal,
+/// reconstructing the position of the geometry inside it from the depth buffer.
+pub(crate) struct DecalRenderer {
+    globals_buffer: wgpu::Buffer,
+    globals_layout: wgpu::BindGroupLayout,
+    globals: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    textures: HashMap<String, wgpu::BindGroup>,
+    failed: HashSet<String>,
+    instances: GrowableBuffer,
+    batches: Vec<(String, std::ops::Range<u32>)>,
+}
+
+impl DecalRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        depth_view: &wgpu::TextureView,
+    ) -> Self {
+        let globals_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("decal_globals_layout"),
+        });
+
+        let globals_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Decal Globals Buffer"),
+            contents: bytemuck::cast_slice(&[<DecalGlobals as bytemuck::Zeroable>::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let globals = Self::create_globals(device, &globals_layout, &globals_buffer, depth_view);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Decal Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("decals.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Decal Pipeline Layout"),
+            bind_group_layouts: &[&globals_layout, texture_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Decal Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[DecalInstance::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            // The back faces are drawn, so the decal is still visible with the camera inside of the box
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Front),
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            globals_buffer,
+            globals_layout,
+            globals,
+            pipeline,
+            textures: HashMap::new(),
+            failed: HashSet::new(),
+            instances: GrowableBuffer::new(
+                device,
+                "Decal Instance Buffer",
+                wgpu::BufferUsages::VERTEX,
+                64 * std::mem::size_of::<DecalInstance>() as u64,
+            ),
+            batches: Vec::new(),
+        }
+    }
+
+    fn create_globals(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        globals_buffer: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: globals_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+            ],
+            label: Some("decal_globals"),
+        })
+    }
+
+    /// Rebind the depth texture after it has been recreated.
+    pub fn resize(&mut self, device: &wgpu::Device, depth_view: &wgpu::TextureView) {
+        self.globals = Self::create_globals(
+            device,
+            &self.globals_layout,
+            &self.globals_buffer,
+            depth_view,
+        );
+    }
+
+    /// Get the textures of the decals which are not loaded yet.
+    pub fn missing_textures(&self, ecs: &ecs::Manager) -> HashSet<String> {
+        ecs.get_all_components_of_type::<Decal>()
+            .iter()
+            .map(|(_, component)| component.read().unwrap().texture.clone())
+            .filter(|path| !self.textures.contains_key(path) && !self.failed.contains(path))
+            .collect()
+    }
+
+    /// Load the textures returned by `missing_textures`, the ECS does not have to stay locked meanwhile.
+    pub async fn load_textures(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        paths: HashSet<String>,
+    ) {
+        for path in paths {
+            match resources::load_texture(&path, device, queue).await {
+                Ok(texture) => {
+                    let bind_group = Self::create_texture_bind_group(
+                        device,
+                        texture_bind_group_layout,
+                        &texture,
+                    );
+                    self.textures.insert(path, bind_group);
+                }
+                Err(e) => {
+                    log::warn!("[Decal] Failed to load the texture {}: {}", path, e);
+                    self.failed.insert(path);
+                }
+            }
+        }
+    }
+
+    /// Collect the visible decals into batches per texture, the decals without a loaded texture are skipped.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        ecs: &ecs::Manager,
+        camera: &Camera,
+        projection: &Projection,
+    ) {
+        let view_proj = projection.calc_matrix() * camera.calc_matrix();
+        let globals = DecalGlobals {
+            view_proj: view_proj.into(),
+            inv_view_proj: view_proj.invert().unwrap_or(Matrix4::identity()).into(),
+        };
+        queue.write_buffer(&self.globals_buffer, 0, bytemuck::cast_slice(&[globals]));
+
+        let mut decals: Vec<(String, DecalInstance)> = Vec::new();
+        for (entity, decal) in ecs.get_all_components_of_type::<Decal>() {
+            let Some(pos) = ecs.get_component_from_entity::<Pos3>(entity) else {
+                continue;
+            };
+            let position = pos.read().unwrap().pos;
+            let decal = decal.read().unwrap();
+
+            let opacity = decal.opacity();
+            if opacity <= 0.0 {
+                continue;
+            }
+
+            let model = decal.model_matrix(position);
+            let Some(inv_model) = model.invert() else {
+                continue;
+            };
+            let mut color = decal.color;
+            color[3] *= opacity;
+
+            decals.push((
+                decal.texture.clone(),
+                DecalInstance {
+                    model: model.into(),
+                    inv_model: inv_model.into(),
+                    color,
+                },
+            ));
+        }
+
+        decals.retain(|(path, _)| self.textures.contains_key(path));
+        decals.sort_by(|a, b| a.0.cmp(&b.0));
+
+        self.batches.clear();
+        for (index, (path, _)) in decals.iter().enumerate() {
+            let index = index as u32;
+            match self.batches.last_mut() {
+                Some((last, range)) if last == path => range.end = index + 1,
+                _ => self.batches.push((path.clone(), index..index + 1)),
+            }
+        }
+
+        let instances: Vec<DecalInstance> = decals.into_iter().map(|(_, i)| i).collect();
+        self.instances
+            .write(device, queue, bytemuck::cast_slice(&instances));
+    }
+
+    fn create_texture_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        texture: &texture::Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: Some("decal_texture_bind_group"),
+        })
+    }
+
+    /// Draw the decals over the scene color.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        if self.instances.is_empty() {
+            return;
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Decal Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.globals, &[]);
+        render_pass.set_vertex_buffer(0, self.instances.slice());
+
+        for (path, range) in &self.batches {
+            render_pass.set_bind_group(1, &self.textures[path], &[]);
+            render_pass.draw(0..36, range.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Transform;
+    use instant::Duration;
+
+    #[test]
+    fn test_fade_and_expire() {
+        let manager = ecs::Manager::default();
+        let entity = manager.create_entity();
+        manager.add_component_to_entity(
+            entity,
+            Decal::new("decals/hole.png", 0.2, 0.2).with_lifetime(2.0, 1.0),
+        );
+
+        update_decals(&manager, Duration::from_millis(1500));
+        let decal = manager.get_component_from_entity::<Decal>(entity).unwrap();
+        assert!((decal.read().unwrap().opacity() - 0.5).abs() < 1e-5);
+        assert!(manager.drain_events::<DecalExpired>().is_empty());
+
+        update_decals(&manager, Duration::from_secs(1));
+        assert!(decal.read().unwrap().is_expired());
+        assert_eq!(decal.read().unwrap().opacity(), 0.0);
+        assert_eq!(
+            manager.drain_events::<DecalExpired>(),
+            vec![DecalExpired { entity }]
+        );
+    }
+
+    #[test]
+    fn test_projection_box() {
+        let decal = Decal::new("decals/hole.png", 2.0, 1.0)
+            .with_direction(Vector3::unit_x())
+            .with_depth(4.0);
+        let model = decal.model_matrix(Vector3::new(0.0, 1.0, 0.0));
+
+        // The local z axis of the box follows the projection direction
+        let tip = model.transform_point(cgmath::Point3::new(0.0, 0.0, 0.5));
+        assert!((tip - cgmath::Point3::new(2.0, 1.0, 0.0)).magnitude() < 1e-5);
+    }
+}
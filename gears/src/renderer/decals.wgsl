@@ -0,0 +1,100 @@
+// Decal pass: projects textures onto the geometry inside of a box
+
+struct Globals {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+@group(0) @binding(1)
+var t_depth: texture_depth_2d;
+
+@group(1) @binding(0)
+var t_decal: texture_2d<f32>;
+@group(1) @binding(1)
+var s_decal: sampler;
+
+struct InstanceInput {
+    @location(0) model_matrix_0: vec4<f32>,
+    @location(1) model_matrix_1: vec4<f32>,
+    @location(2) model_matrix_2: vec4<f32>,
+    @location(3) model_matrix_3: vec4<f32>,
+    @location(4) inv_model_matrix_0: vec4<f32>,
+    @location(5) inv_model_matrix_1: vec4<f32>,
+    @location(6) inv_model_matrix_2: vec4<f32>,
+    @location(7) inv_model_matrix_3: vec4<f32>,
+    @location(8) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) inv_model_matrix_0: vec4<f32>,
+    @location(1) inv_model_matrix_1: vec4<f32>,
+    @location(2) inv_model_matrix_2: vec4<f32>,
+    @location(3) inv_model_matrix_3: vec4<f32>,
+    @location(4) color: vec4<f32>,
+}
+
+// The corners of a unit cube centered at the origin
+fn cube_corner(index: u32) -> vec3<f32> {
+    var indices = array<u32, 36>(
+        0u, 2u, 1u, 1u, 2u, 3u, // -z
+        4u, 5u, 6u, 5u, 7u, 6u, // +z
+        0u, 1u, 4u, 1u, 5u, 4u, // -y
+        2u, 6u, 3u, 3u, 6u, 7u, // +y
+        0u, 4u, 2u, 2u, 4u, 6u, // -x
+        1u, 3u, 5u, 3u, 7u, 5u, // +x
+    );
+    let corner = indices[index];
+    return vec3<f32>(f32(corner & 1u), f32((corner >> 1u) & 1u), f32((corner >> 2u) & 1u)) - 0.5;
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, instance: InstanceInput) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    var out: VertexOutput;
+    out.clip_position = globals.view_proj * model_matrix * vec4<f32>(cube_corner(index), 1.0);
+    out.inv_model_matrix_0 = instance.inv_model_matrix_0;
+    out.inv_model_matrix_1 = instance.inv_model_matrix_1;
+    out.inv_model_matrix_2 = instance.inv_model_matrix_2;
+    out.inv_model_matrix_3 = instance.inv_model_matrix_3;
+    out.color = instance.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Reconstruct the position of the geometry behind this pixel
+    let size = vec2<f32>(textureDimensions(t_depth));
+    let coord = vec2<i32>(in.clip_position.xy);
+    let depth = textureLoad(t_depth, coord, 0);
+    if depth >= 1.0 {
+        discard;
+    }
+
+    let uv = in.clip_position.xy / size;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = globals.inv_view_proj * ndc;
+
+    let inv_model_matrix = mat4x4<f32>(
+        in.inv_model_matrix_0,
+        in.inv_model_matrix_1,
+        in.inv_model_matrix_2,
+        in.inv_model_matrix_3,
+    );
+    let local = (inv_model_matrix * vec4<f32>(world.xyz / world.w, 1.0)).xyz;
+    if any(abs(local) > vec3<f32>(0.5)) {
+        discard;
+    }
+
+    let decal_uv = vec2<f32>(local.x + 0.5, 0.5 - local.y);
+    let color = textureSampleLevel(t_decal, s_decal, decal_uv, 0.0) * in.color;
+    return color;
+}
@@ -0,0 +1,89 @@
+use super::overrides::DrawMaterial;
+use super::pipelines::MaterialFeatures;
+
+/// A mesh of a model in the opaque draw list of a frame.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct OpaqueDraw {
+    /// The index of the model in the models of the frame.
+    pub model: usize,
+    /// The index of the mesh in the model.
+    pub mesh: usize,
+    /// The features of the material, they choose the pipeline.
+    pub features: MaterialFeatures,
+    /// The material of the mesh, after the overrides of the entity.
+    pub slot: DrawMaterial,
+    /// Identifies the bind group of the material, the draws with the same one share it.
+    pub material: usize,
+    /// The squared distance of the model from the camera.
+    pub depth: f32,
+}
+
+/// Add the draws of the meshes of a model.
+///
+/// # Arguments
+///
+/// * `draws` - The draw list of the frame.
+/// * `index` - The index of the model in the models of the frame.
+/// * `meshes` - The material, the features and the material bind group of each mesh.
+/// * `depth` - The squared distance of the model from the camera.
+pub(crate) fn add_model<'a>(
+    draws: &mut Vec<OpaqueDraw>,
+    index: usize,
+    meshes: impl IntoIterator<Item = (DrawMaterial, MaterialFeatures, &'a wgpu::BindGroup)>,
+    depth: f32,
+) {
+    draws.extend(
+        meshes
+            .into_iter()
+            .enumerate()
+            .map(|(mesh, (slot, features, bind_group))| OpaqueDraw {
+                model: index,
+                mesh,
+                features,
+                slot,
+                material: std::ptr::from_ref(bind_group) as usize,
+                depth,
+            }),
+    );
+}
+
+/// Sort the opaque draws by their pipeline, then by their material and then front to back.
+/// The pipelines and the bind groups are switched as rarely as possible,
+/// and the near meshes fill the depth buffer first so the hidden fragments of the rest are rejected early.
+pub(crate) fn sort_opaque(draws: &mut [OpaqueDraw]) {
+    draws.sort_by(|a, b| {
+        a.features
+            .cmp(&b.features)
+            .then(a.material.cmp(&b.material))
+            .then(a.depth.total_cmp(&b.depth))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_opaque() {
+        let draw = |model, specular, material, depth| OpaqueDraw {
+            model,
+            mesh: 0,
+            features: MaterialFeatures { specular },
+            slot: DrawMaterial::Slot(0),
+            material,
+            depth,
+        };
+        let mut draws = vec![
+            draw(0, true, 2, 1.0),
+            draw(1, false, 1, 9.0),
+            draw(2, true, 1, 4.0),
+            draw(3, true, 2, 0.5),
+            draw(4, true, 1, 2.0),
+        ];
+        sort_opaque(&mut draws);
+
+        // Grouped by the pipeline and the material, the nearest first within a group
+        let order = draws.iter().map(|draw| draw.model).collect::<Vec<_>>();
+        assert_eq!(order, vec![1, 4, 2, 3, 0]);
+    }
+}
@@ -0,0 +1,733 @@
+//! Loading the meshes and the materials of glTF models, .gltf files with their buffers and
+//! .glb files with an embedded binary chunk.
+//!
+//! The buffer views compressed with `EXT_meshopt_compression` are decoded when the file is
+//! loaded and the quantized attributes of `KHR_mesh_quantization` are converted to floats.
+//! Draco compressed primitives (`KHR_draco_mesh_compression`) are not supported, those
+//! files fail to load with an error asking for another export.
+
+use super::resources::{self, LoadFailure};
+use super::streaming::StreamedTexture;
+use super::{mesh_optimizer, meshopt_codec, model, texture};
+use crate::core::vfs;
+use crate::ecs::components::Bounds;
+use anyhow::{bail, Context};
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, Point3, SquareMatrix, Transform, Vector3};
+use gltf::accessor::DataType;
+use gltf::json::validation::Validate;
+use std::borrow::Cow;
+use std::path::Path;
+use wgpu::util::DeviceExt;
+
+/// The required extensions the loader can decode.
+const SUPPORTED_EXTENSIONS: [&str; 2] = ["EXT_meshopt_compression", "KHR_mesh_quantization"];
+
+/// A primitive of a glTF mesh, with the transform of its node applied.
+pub(crate) struct GltfMesh {
+    pub name: String,
+    /// The index of the material in `GltfScene::materials`.
+    pub material: usize,
+    pub vertices: Vec<model::ModelVertex>,
+    pub indices: Vec<u32>,
+}
+
+/// Where the base color texture of a material is read from.
+pub(crate) enum TextureSource {
+    /// A path relative to the model or a data URI.
+    Uri(String),
+    /// An image stored in a buffer view.
+    Embedded(Vec<u8>),
+}
+
+pub(crate) struct GltfMaterial {
+    pub name: String,
+    /// The linear base color factor, only used when there is no texture.
+    pub base_color: [f32; 4],
+    pub texture: Option<TextureSource>,
+}
+
+/// The meshes of the default scene of a glTF file and the materials they use.
+pub(crate) struct GltfScene {
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<GltfMaterial>,
+}
+
+/// Parse a .gltf or .glb file and read the meshes of its default scene.
+///
+/// # Arguments
+///
+/// * `file_path` - The path of the file, the external buffers are read relative to it.
+/// * `data` - The content of the file.
+///
+/// # Returns
+///
+/// The meshes and the materials, or an error if the file is invalid or uses an unsupported extension.
+pub(crate) fn parse(file_path: &str, data: &[u8]) -> anyhow::Result<GltfScene> {
+    let mut gltf = gltf::Gltf::from_slice_without_validation(data)?;
+    for extension in gltf.extensions_required() {
+        match extension {
+            "KHR_draco_mesh_compression" => bail!(
+                "The model is Draco compressed, which is not supported, export it with meshopt compression or without compression"
+            ),
+            extension if !SUPPORTED_EXTENSIONS.contains(&extension) => {
+                bail!("The model requires the unsupported extension {}", extension)
+            }
+            _ => {}
+        }
+    }
+    // The glTF crate rejects the extensions it can't decode itself, they were checked above
+    let root = gltf.document.as_json();
+    let mut errors = Vec::new();
+    root.validate(root, gltf::json::Path::new, &mut |path, error| {
+        let path = path();
+        if !path.as_str().starts_with("extensionsRequired") {
+            errors.push(format!("{}: {}", path.as_str(), error));
+        }
+    });
+    if !errors.is_empty() {
+        bail!("The model is invalid: {}", errors.join(", "));
+    }
+
+    let root_dir = Path::new(file_path).parent().unwrap_or(Path::new(""));
+    let mut blob = gltf.blob.take();
+    let buffers = gltf
+        .buffers()
+        .map(|buffer| {
+            let fallback = buffer
+                .extension_value("EXT_meshopt_compression")
+                .and_then(|meshopt| meshopt.get("fallback"))
+                .and_then(|fallback| fallback.as_bool())
+                .unwrap_or(false);
+            match buffer.source() {
+                gltf::buffer::Source::Uri(uri) => read_uri(uri, root_dir),
+                // Only compressed views use the fallback buffers, so they don't need any data
+                gltf::buffer::Source::Bin if fallback => Ok(Vec::new()),
+                gltf::buffer::Source::Bin => blob
+                    .take()
+                    .context("The buffer of the binary chunk is missing"),
+            }
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let views = gltf
+        .views()
+        .map(|view| view_data(&view, &buffers))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut materials = gltf
+        .materials()
+        .map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+            GltfMaterial {
+                name: material
+                    .name()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("Material {}", material.index().unwrap_or(0))),
+                base_color: pbr.base_color_factor(),
+                texture: pbr.base_color_texture().map(|info| {
+                    match info.texture().source().source() {
+                        gltf::image::Source::Uri { uri, .. } => TextureSource::Uri(uri.to_string()),
+                        gltf::image::Source::View { view, .. } => {
+                            TextureSource::Embedded(views[view.index()].to_vec())
+                        }
+                    }
+                }),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut meshes = Vec::new();
+    let mut reader = MeshReader {
+        views: &views,
+        default_material: materials.len(),
+        meshes: &mut meshes,
+    };
+    match gltf.default_scene().or_else(|| gltf.scenes().next()) {
+        Some(scene) => {
+            for node in scene.nodes() {
+                reader.read_node(&node, Matrix4::identity())?;
+            }
+        }
+        // A file without scenes, e.g. a library of meshes
+        None => {
+            for mesh in gltf.meshes() {
+                reader.read_mesh(&mesh, None, Matrix4::identity())?;
+            }
+        }
+    }
+
+    // The primitives without a material use the default material of glTF, plain white
+    if meshes.iter().any(|mesh| mesh.material == materials.len()) {
+        materials.push(GltfMaterial {
+            name: "Default material".to_string(),
+            base_color: [1.0; 4],
+            texture: None,
+        });
+    }
+    Ok(GltfScene { meshes, materials })
+}
+
+/// Load a glTF model with its materials.
+/// The textures which fail to load are replaced by the missing texture and added to `failures`.
+/// The materials without a texture are drawn with their base color.
+///
+/// # Returns
+///
+/// The model, or an error if the file itself can not be loaded.
+pub(crate) async fn load_model(
+    file_path: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    stream_textures: bool,
+    failures: &mut Vec<LoadFailure>,
+) -> anyhow::Result<model::Model> {
+    let root_dir = Path::new(file_path).parent().unwrap_or(Path::new(""));
+    let data = resources::load_binary(file_path)
+        .await
+        .with_context(|| format!("Failed to load the model {}", file_path))?;
+    let scene = parse(file_path, &data)
+        .with_context(|| format!("Failed to parse the model {}", file_path))?;
+
+    let mut materials = Vec::with_capacity(scene.materials.len());
+    for m in &scene.materials {
+        let (texture_path, data) = match &m.texture {
+            Some(TextureSource::Uri(uri)) => {
+                let path = if uri.starts_with("data:") {
+                    format!("{} ({})", file_path, m.name)
+                } else {
+                    root_dir
+                        .join(decode_percent(uri))
+                        .to_string_lossy()
+                        .into_owned()
+                };
+                (path, Some(read_uri(uri, root_dir)))
+            }
+            Some(TextureSource::Embedded(data)) => (
+                format!("{} ({})", file_path, m.name),
+                Some(Ok(data.clone())),
+            ),
+            None => (file_path.to_string(), None),
+        };
+
+        let loaded = match data {
+            Some(Ok(data)) if stream_textures => Ok((
+                StreamedTexture::placeholder(device, queue),
+                Some(StreamedTexture::load(&texture_path, data)),
+            )),
+            Some(Ok(data)) => texture::Texture::from_bytes(device, queue, &data, &texture_path)
+                .map(|texture| (texture, None)),
+            Some(Err(error)) => Err(error),
+            None => {
+                // The factor is linear, the texture is sRGB
+                let [r, g, b, a] = m.base_color.map(|c| c.clamp(0.0, 1.0));
+                let srgb = |c: f32| (c.powf(1.0 / 2.2) * 255.0).round() as u8;
+                let color = [srgb(r), srgb(g), srgb(b), (a * 255.0).round() as u8];
+                let image = image::RgbaImage::from_pixel(1, 1, image::Rgba(color));
+                texture::Texture::from_mips(device, queue, &[image], Some(&m.name))
+                    .map(|texture| (texture, None))
+            }
+        };
+        let (diffuse_texture, stream) = match loaded {
+            Ok(loaded) => loaded,
+            Err(error) => {
+                failures.push(LoadFailure {
+                    path: texture_path,
+                    error,
+                });
+                (texture::Texture::missing(device, queue), None)
+            }
+        };
+        let bind_group = model::Material::create_bind_group(device, layout, &diffuse_texture);
+        materials.push(model::Material {
+            name: m.name.clone(),
+            diffuse_texture,
+            bind_group,
+            stream,
+        });
+    }
+
+    let bounds = Bounds::from_points(
+        scene
+            .meshes
+            .iter()
+            .flat_map(|m| m.vertices.iter().map(|v| Vector3::from(v.position))),
+    );
+
+    let meshes = scene
+        .meshes
+        .into_iter()
+        .map(|m| {
+            let bounds = Bounds::from_points(m.vertices.iter().map(|v| Vector3::from(v.position)));
+
+            // Reorder the triangles for the vertex cache, then the vertices in the order they are used
+            let mut vertices = m.vertices;
+            let mut indices = mesh_optimizer::optimize_vertex_cache(&m.indices, vertices.len());
+            mesh_optimizer::optimize_vertex_fetch(&mut vertices, &mut indices);
+
+            let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{:?} Vertex Buffer", m.name)),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+            let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{:?} Index Buffer", m.name)),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            });
+
+            log::info!("Mesh: {}", m.name);
+            model::Mesh {
+                name: m.name,
+                vertex_buffer,
+                index_buffer,
+                num_elements: indices.len() as u32,
+                material: m.material,
+                material_name: scene.materials[m.material].name.clone(),
+                bounds,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Ok(model::Model {
+        meshes,
+        materials,
+        bounds,
+    })
+}
+
+/// Read the primitives of the nodes of a scene.
+struct MeshReader<'a> {
+    views: &'a [Cow<'a, [u8]>],
+    /// The material index of the primitives without a material.
+    default_material: usize,
+    meshes: &'a mut Vec<GltfMesh>,
+}
+
+impl MeshReader<'_> {
+    fn read_node(&mut self, node: &gltf::Node, parent: Matrix4<f32>) -> anyhow::Result<()> {
+        let transform = parent * Matrix4::from(node.transform().matrix());
+        if let Some(mesh) = node.mesh() {
+            self.read_mesh(&mesh, node.name(), transform)?;
+        }
+        for child in node.children() {
+            self.read_node(&child, transform)?;
+        }
+        Ok(())
+    }
+
+    fn read_mesh(
+        &mut self,
+        mesh: &gltf::Mesh,
+        node_name: Option<&str>,
+        transform: Matrix4<f32>,
+    ) -> anyhow::Result<()> {
+        let name = mesh
+            .name()
+            .or(node_name)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("Mesh {}", mesh.index()));
+        let linear = Matrix3::from_cols(
+            transform.x.truncate(),
+            transform.y.truncate(),
+            transform.z.truncate(),
+        );
+        let normal_matrix = linear
+            .invert()
+            .map_or(linear, |inverse| inverse.transpose());
+
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                log::warn!(
+                    "[Renderer] A primitive of {} is drawn as {:?}, only triangles are supported",
+                    name,
+                    primitive.mode()
+                );
+                continue;
+            }
+
+            let attribute = |semantic: gltf::Semantic| {
+                primitive
+                    .get(&semantic)
+                    .map(|accessor| read_accessor(&accessor, self.views, float_reader(&accessor)))
+                    .transpose()
+            };
+            let positions = attribute(gltf::Semantic::Positions)?
+                .with_context(|| format!("A primitive of {} has no positions", name))?;
+            let normals = attribute(gltf::Semantic::Normals)?;
+            let tex_coords = attribute(gltf::Semantic::TexCoords(0))?;
+            let count = positions.len() / 3;
+
+            let mut indices = match primitive.indices() {
+                Some(accessor) => {
+                    let read: fn(&[u8]) -> u32 = match accessor.data_type() {
+                        DataType::U8 => |bytes: &[u8]| bytes[0] as u32,
+                        DataType::U16 => {
+                            |bytes: &[u8]| u16::from_le_bytes([bytes[0], bytes[1]]) as u32
+                        }
+                        DataType::U32 => |bytes: &[u8]| {
+                            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+                        },
+                        data_type => bail!("The indices of {} are {:?}", name, data_type),
+                    };
+                    read_accessor(&accessor, self.views, read)?
+                }
+                None => (0..count as u32).collect(),
+            };
+            if indices.iter().any(|index| *index as usize >= count) {
+                bail!("The indices of {} are out of bounds", name);
+            }
+            indices.truncate(indices.len() / 3 * 3);
+            // A mirroring transform flips the winding of the triangles
+            if linear.determinant() < 0.0 {
+                for triangle in indices.chunks_exact_mut(3) {
+                    triangle.swap(1, 2);
+                }
+            }
+
+            let vertices = (0..count)
+                .map(|i| {
+                    let position =
+                        Point3::new(positions[i * 3], positions[i * 3 + 1], positions[i * 3 + 2]);
+                    let normal = normals.as_ref().map_or(Vector3::new(0.0, 0.0, 0.0), |n| {
+                        let normal =
+                            normal_matrix * Vector3::new(n[i * 3], n[i * 3 + 1], n[i * 3 + 2]);
+                        if normal.magnitude2() > 0.0 {
+                            normal.normalize()
+                        } else {
+                            normal
+                        }
+                    });
+                    model::ModelVertex {
+                        position: transform.transform_point(position).into(),
+                        tex_coords: tex_coords
+                            .as_ref()
+                            .map_or([0.0, 0.0], |t| [t[i * 2], t[i * 2 + 1]]),
+                        normal: normal.into(),
+                    }
+                })
+                .collect();
+
+            self.meshes.push(GltfMesh {
+                name: name.clone(),
+                material: primitive
+                    .material()
+                    .index()
+                    .unwrap_or(self.default_material),
+                vertices,
+                indices,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Get the data of a buffer view, decoding it if it is meshopt compressed.
+fn view_data<'a>(
+    view: &gltf::buffer::View,
+    buffers: &'a [Vec<u8>],
+) -> anyhow::Result<Cow<'a, [u8]>> {
+    let slice = |buffer: usize, offset: usize, length: usize| {
+        buffers
+            .get(buffer)
+            .and_then(|data| data.get(offset..offset.checked_add(length)?))
+            .with_context(|| format!("The buffer view {} is out of bounds", view.index()))
+    };
+
+    let Some(meshopt) = view.extension_value("EXT_meshopt_compression") else {
+        return slice(view.buffer().index(), view.offset(), view.length()).map(Cow::Borrowed);
+    };
+    let field = |name: &str| {
+        meshopt
+            .get(name)
+            .and_then(|value| value.as_u64())
+            .map(|value| value as usize)
+    };
+    let missing = |name: &str| {
+        format!(
+            "The compressed buffer view {} has no {}",
+            view.index(),
+            name
+        )
+    };
+    let data = slice(
+        field("buffer").with_context(|| missing("buffer"))?,
+        field("byteOffset").unwrap_or(0),
+        field("byteLength").with_context(|| missing("byteLength"))?,
+    )?;
+    let mode = meshopt_codec::Mode::parse(
+        meshopt
+            .get("mode")
+            .and_then(|mode| mode.as_str())
+            .unwrap_or_default(),
+    )?;
+    let filter = meshopt
+        .get("filter")
+        .and_then(|filter| filter.as_str())
+        .map(meshopt_codec::Filter::parse)
+        .transpose()?
+        .unwrap_or_default();
+
+    meshopt_codec::decode(
+        data,
+        field("count").with_context(|| missing("count"))?,
+        field("byteStride").with_context(|| missing("byteStride"))?,
+        mode,
+        filter,
+    )
+    .map(Cow::Owned)
+    .with_context(|| format!("Failed to decode the buffer view {}", view.index()))
+}
+
+/// Read the components of the elements of an accessor.
+fn read_accessor<T>(
+    accessor: &gltf::Accessor,
+    views: &[Cow<[u8]>],
+    read: impl Fn(&[u8]) -> T,
+) -> anyhow::Result<Vec<T>> {
+    let components = accessor.dimensions().multiplicity();
+    let size = accessor.data_type().size();
+    let count = accessor.count();
+    if accessor.sparse().is_some() {
+        bail!(
+            "The accessor {} is sparse, which is not supported",
+            accessor.index()
+        );
+    }
+    let Some(view) = accessor.view() else {
+        // An accessor without a buffer view is all zeros
+        return Ok((0..count * components).map(|_| read(&[0; 4])).collect());
+    };
+
+    let data = &views[view.index()];
+    let stride = view.stride().unwrap_or(components * size);
+    let end = accessor.offset() + stride * count.saturating_sub(1) + components * size;
+    if count > 0 && end > data.len() {
+        bail!("The accessor {} is out of bounds", accessor.index());
+    }
+    Ok((0..count)
+        .flat_map(|i| (0..components).map(move |c| accessor.offset() + i * stride + c * size))
+        .map(|at| read(&data[at..at + size]))
+        .collect())
+}
+
+/// Read the components of an accessor as floats, the quantized ones are either normalized or
+/// converted as they are.
+fn float_reader(accessor: &gltf::Accessor) -> impl Fn(&[u8]) -> f32 {
+    let normalized = accessor.normalized();
+    let data_type = accessor.data_type();
+    move |bytes| {
+        let (value, max) = match data_type {
+            DataType::I8 => (bytes[0] as i8 as f32, i8::MAX as f32),
+            DataType::U8 => (bytes[0] as f32, u8::MAX as f32),
+            DataType::I16 => (
+                i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+                i16::MAX as f32,
+            ),
+            DataType::U16 => (
+                u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+                u16::MAX as f32,
+            ),
+            DataType::U32 => (
+                u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+                1.0,
+            ),
+            DataType::F32 => (
+                f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+                1.0,
+            ),
+        };
+        if normalized {
+            (value / max).max(-1.0)
+        } else {
+            value
+        }
+    }
+}
+
+/// Read a file next to the model or the content of a base64 data URI.
+fn read_uri(uri: &str, root_dir: &Path) -> anyhow::Result<Vec<u8>> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, payload) = data
+            .split_once(";base64,")
+            .context("Only base64 data URIs are supported")?;
+        return decode_base64(payload);
+    }
+    vfs::read(&root_dir.join(decode_percent(uri)).to_string_lossy())
+}
+
+/// Decode the escaped characters of a relative URI, e.g. `%20` for a space.
+fn decode_percent(uri: &str) -> String {
+    let mut bytes = Vec::with_capacity(uri.len());
+    let mut i = 0;
+    while i < uri.len() {
+        let escaped = (uri.as_bytes()[i] == b'%')
+            .then(|| uri.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                bytes.push(byte);
+                i += 3;
+            }
+            None => {
+                bytes.push(uri.as_bytes()[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn decode_base64(text: &str) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => bail!("Invalid base64 character {:?}", c as char),
+        };
+        bits = (bits << 6) | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::meshopt_codec::tests::{INDEX_BUFFER, VERTEX_BUFFER};
+
+    /// Pack a JSON chunk and a binary chunk into a .glb file.
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut bin = bin.to_vec();
+        bin.resize(bin.len().next_multiple_of(4), 0);
+
+        let mut data = Vec::new();
+        data.extend(b"glTF");
+        data.extend(2u32.to_le_bytes());
+        data.extend(((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        data.extend((json.len() as u32).to_le_bytes());
+        data.extend(b"JSON");
+        data.extend(json);
+        data.extend((bin.len() as u32).to_le_bytes());
+        data.extend(b"BIN\0");
+        data.extend(bin);
+        data
+    }
+
+    #[test]
+    fn test_parse_glb() {
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "buffers": [{"byteLength": 44}],
+            "bufferViews": [
+                {"buffer": 0, "byteOffset": 0, "byteLength": 36},
+                {"buffer": 0, "byteOffset": 36, "byteLength": 6}
+            ],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0]},
+                {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}
+            ],
+            "meshes": [{"name": "Triangle", "primitives": [{"attributes": {"POSITION": 0}, "indices": 1}]}],
+            "nodes": [{"mesh": 0, "translation": [0, 2, 0]}],
+            "scenes": [{"nodes": [0]}],
+            "scene": 0
+        }"#;
+        let mut bin = [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]
+            .into_iter()
+            .flat_map(f32::to_le_bytes)
+            .collect::<Vec<_>>();
+        bin.extend([0u16, 1, 2].into_iter().flat_map(u16::to_le_bytes));
+
+        let scene = parse("models/triangle.glb", &glb(json, &bin)).unwrap();
+        assert_eq!(scene.meshes.len(), 1);
+        let mesh = &scene.meshes[0];
+        assert_eq!(mesh.name, "Triangle");
+        assert_eq!(mesh.indices, [0, 1, 2]);
+        let positions = mesh.vertices.iter().map(|v| v.position).collect::<Vec<_>>();
+        assert_eq!(
+            positions,
+            [[0.0, 2.0, 0.0], [1.0, 2.0, 0.0], [0.0, 3.0, 0.0]]
+        );
+        // The primitive has no material, so it gets the default one
+        assert_eq!(scene.materials[mesh.material].name, "Default material");
+    }
+
+    #[test]
+    fn test_parse_meshopt_compressed() {
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "extensionsUsed": ["EXT_meshopt_compression", "KHR_mesh_quantization"],
+            "extensionsRequired": ["EXT_meshopt_compression", "KHR_mesh_quantization"],
+            "buffers": [
+                {"byteLength": 180},
+                {"byteLength": 372, "extensions": {"EXT_meshopt_compression": {"fallback": true}}}
+            ],
+            "bufferViews": [
+                {"buffer": 1, "byteOffset": 0, "byteLength": 144, "byteStride": 8, "extensions": {"EXT_meshopt_compression":
+                    {"buffer": 0, "byteOffset": 0, "byteLength": 126, "byteStride": 8, "mode": "ATTRIBUTES", "count": 18}}},
+                {"buffer": 1, "byteOffset": 144, "byteLength": 228, "extensions": {"EXT_meshopt_compression":
+                    {"buffer": 0, "byteOffset": 128, "byteLength": 51, "byteStride": 4, "mode": "TRIANGLES", "count": 57}}}
+            ],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5123, "count": 18, "type": "VEC3", "min": [0, 0, 0], "max": [1700, 14, 60000]},
+                {"bufferView": 1, "componentType": 5125, "count": 57, "type": "SCALAR"}
+            ],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "indices": 1}]}],
+            "nodes": [{"name": "Grid", "mesh": 0, "scale": [0.01, 0.01, 0.01]}],
+            "scenes": [{"nodes": [0]}],
+            "scene": 0
+        }"#;
+        let mut bin = VERTEX_BUFFER.to_vec();
+        bin.extend([0, 0]);
+        bin.extend(INDEX_BUFFER);
+
+        let scene = parse("models/grid.glb", &glb(json, &bin)).unwrap();
+        let mesh = &scene.meshes[0];
+        assert_eq!(mesh.name, "Grid");
+        assert_eq!(mesh.vertices.len(), 18);
+        assert_eq!(mesh.indices.len(), 57);
+        assert_eq!(mesh.indices.iter().max(), Some(&15));
+        // The positions are quantized, the scale of the node restores them
+        let position = mesh.vertices[1].position;
+        for (actual, expected) in position.iter().zip([1.0, 0.07, 599.63]) {
+            assert!((actual - expected).abs() < 1e-3, "{:?}", position);
+        }
+    }
+
+    #[test]
+    fn test_parse_draco_compressed() {
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "extensionsUsed": ["KHR_draco_mesh_compression"],
+            "extensionsRequired": ["KHR_draco_mesh_compression"]
+        }"#;
+
+        let Err(error) = parse("models/draco.gltf", json.as_bytes()) else {
+            panic!("A Draco compressed model was parsed");
+        };
+        assert!(error.to_string().contains("Draco"));
+    }
+
+    #[test]
+    fn test_decode_uri() {
+        assert_eq!(decode_base64("Z2VhcnM=").unwrap(), b"gears");
+        assert_eq!(decode_base64("AAEC/w==").unwrap(), [0, 1, 2, 255]);
+        assert!(decode_base64("a*b").is_err());
+        assert_eq!(decode_percent("my%20model.bin"), "my model.bin");
+        assert_eq!(decode_percent("100%"), "100%");
+    }
+}
@@ -0,0 +1,232 @@
+use super::{camera, optional_features, recorder, wgpu_backends, State, REQUIRED_FEATURES};
+use crate::core::config::{DisplayConfig, RendererConfig};
+use crate::core::Dt;
+use crate::ecs;
+use cgmath::{EuclideanSpace, Point3, Rad, Vector3};
+use std::iter;
+use std::sync::{Arc, Mutex};
+
+/// The format of the headless frames, they are read back as RGBA.
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+impl State<'static> {
+    /// Set up the renderer without a window, the frames are rendered into a texture.
+    async fn new_headless(
+        ecs: Arc<Mutex<ecs::Manager>>,
+        settings: RendererConfig,
+    ) -> anyhow::Result<Self> {
+        let (width, height) = settings.window.size.unwrap_or((1280, 720));
+        let backend = settings.backend;
+        if width == 0 || height == 0 {
+            anyhow::bail!("The headless frame size {}x{} is empty", width, height);
+        }
+
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu_backends(backend),
+            ..Default::default()
+        });
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            })
+            .await
+            .ok_or_else(|| anyhow::anyhow!("No GPU adapter found for the {:?} backend", backend))?;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    required_features: REQUIRED_FEATURES | optional_features(&adapter),
+                    required_limits: wgpu::Limits::default(),
+                    memory_hints: Default::default(),
+                },
+                None,
+            )
+            .await?;
+        log::info!(
+            "[Headless] Rendering {}x{} with {}",
+            width,
+            height,
+            adapter.get_info().name
+        );
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: FORMAT,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 1,
+        };
+        let offscreen = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Headless Frame"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: config.usage,
+            view_formats: &[],
+        });
+
+        // The textures are loaded fully, so the frames do not depend on the streaming
+        let settings = RendererConfig {
+            display: DisplayConfig::default(),
+            texture_streaming: None,
+            ..settings
+        };
+        let mut state = Self::with_device(device, queue, config, ecs, &settings, None);
+        state.offscreen = Some(offscreen);
+        state.init_components().await?;
+        Ok(state)
+    }
+
+    /// Render a frame into the offscreen texture.
+    fn render_offscreen(&mut self) {
+        let Some(offscreen) = &self.offscreen else {
+            return;
+        };
+        let view = offscreen.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Headless Encoder"),
+            });
+
+        self.render_scene(&mut encoder, &view);
+        self.queue.submit(iter::once(encoder.finish()));
+    }
+
+    /// Copy the offscreen texture back, waiting for the GPU.
+    fn read_pixels(&self) -> anyhow::Result<Vec<u8>> {
+        let offscreen = self
+            .offscreen
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("The renderer is not headless"))?;
+        let (width, height) = (self.config.width, self.config.height);
+        let padded_bytes_per_row = recorder::padded_bytes_per_row(width);
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Headless Readback Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Headless Readback Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            offscreen.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            offscreen.size(),
+        );
+        self.queue.submit(iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        rx.recv()??;
+
+        let pixels = recorder::unpad_rows(
+            &slice.get_mapped_range(),
+            width,
+            height,
+            padded_bytes_per_row,
+        );
+        buffer.unmap();
+        Ok(pixels)
+    }
+}
+
+/// Renders the entities without a window, for golden image tests and thumbnails on a server.
+/// The frames only advance when `render_frame` is called, so the same updates give the same frames.
+/// Only the scene is drawn, the UI needs a window.
+pub struct HeadlessRenderer {
+    state: State<'static>,
+}
+
+impl HeadlessRenderer {
+    /// Set up the renderer and load the lights and the models of the entities.
+    /// The frames have the window size of the settings or 1280x720, the texture streaming
+    /// and the display settings are not used.
+    ///
+    /// # Returns
+    ///
+    /// An error if the size is empty or there is no GPU for the backend.
+    pub async fn new(
+        ecs: Arc<Mutex<ecs::Manager>>,
+        settings: RendererConfig,
+    ) -> anyhow::Result<Self> {
+        let state = State::new_headless(ecs, settings).await?;
+        Ok(Self { state })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.state.config.width, self.state.config.height)
+    }
+
+    /// Point the camera at a target, e.g. to frame a model for a thumbnail.
+    /// The camera keeps its place until the next call, there is no input to move it.
+    pub fn look_at(&mut self, eye: Vector3<f32>, target: Vector3<f32>) {
+        self.state.camera =
+            camera::Camera::new_look_at(Point3::from_vec(eye), Point3::from_vec(target));
+    }
+
+    /// Get the vertical field of view of the camera.
+    pub fn fovy(&self) -> Rad<f32> {
+        self.state.camera_projection.fovy()
+    }
+
+    /// Update the camera, the lights and the models by a delta time and render a frame.
+    pub async fn render_frame(&mut self, dt: Dt) {
+        self.state.update(dt).await;
+        self.state.render_offscreen();
+    }
+
+    /// Read the last frame.
+    ///
+    /// # Returns
+    ///
+    /// The rows of the frame from the top, 4 bytes per pixel in RGBA.
+    pub fn read_pixels(&self) -> anyhow::Result<Vec<u8>> {
+        self.state.read_pixels()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::Config;
+
+    #[test]
+    fn test_empty_size_is_rejected() {
+        let ecs = Arc::new(Mutex::new(ecs::Manager::default()));
+        let mut settings = Config::default().renderer();
+        settings.window.size = Some((0, 720));
+        let renderer = futures::executor::block_on(HeadlessRenderer::new(ecs, settings));
+        assert!(renderer
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("size 0x720 is empty"));
+    }
+}
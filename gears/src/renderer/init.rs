@@ -0,0 +1,436 @@
+use super::{camera, instance, light, model, post, report_load_failures, resources, State};
+use crate::ecs;
+use crate::ecs::components::{self, Flip};
+use crate::ecs::traits::Marker;
+use cgmath::{EuclideanSpace, Point3, Rotation3};
+use log::{info, warn};
+use std::sync::{Arc, Mutex};
+use wgpu::util::DeviceExt;
+
+impl State<'_> {
+    /// Load the lights and the models of the entities and detect the display, once the renderer is set up.
+    pub(super) async fn init_components(&mut self) -> anyhow::Result<()> {
+        self.init_lights().await;
+        self.init_models().await;
+        self.detect_refresh_rate();
+
+        Ok(())
+    }
+
+    /// Create the camera of the camera entity and its controller, or the default ones without a camera entity.
+    pub(super) fn init_camera(
+        ecs: Arc<Mutex<ecs::Manager>>,
+    ) -> (camera::Camera, camera::CameraController) {
+        let ecs_lock = ecs.lock().unwrap();
+        let mut camera_entity = ecs_lock.get_entites_with_component::<components::Camera>();
+        assert!(
+            camera_entity.len() <= 1,
+            "There should be only one camera entity"
+        );
+
+        // If there is no camera entity provide a default implementation
+        if camera_entity.is_empty() {
+            let camera =
+                camera::Camera::new((0.0, 5.0, 10.0), cgmath::Deg(-90.0), cgmath::Deg(-20.0));
+            let controller = camera::CameraController::new(0.5, 0.2);
+
+            return (camera, controller);
+        }
+
+        let camera_entity = camera_entity.pop().unwrap();
+
+        let (Some(camera_pos), Some(camera)) = (
+            ecs_lock.get_component_from_entity::<components::Pos3>(camera_entity),
+            ecs_lock.get_component_from_entity::<components::Camera>(camera_entity),
+        ) else {
+            if let Err(err) = components::Camera::validate(&ecs_lock, camera_entity) {
+                warn!("[Renderer] {}, using the default camera", err);
+            }
+            let camera =
+                camera::Camera::new((0.0, 5.0, 10.0), cgmath::Deg(-90.0), cgmath::Deg(-20.0));
+            let controller = camera::CameraController::new(0.5, 0.2);
+
+            return (camera, controller);
+        };
+
+        let camera_pos = camera_pos.read().unwrap();
+        let camera = camera.read().unwrap();
+
+        let (mut camera, controller) = match *camera {
+            components::Camera::FPS {
+                look_at,
+                speed,
+                sensitivity,
+            } => {
+                let pos_point = cgmath::Point3::from_vec(camera_pos.pos);
+                let look_at_point = look_at;
+                let camera = camera::Camera::new_look_at(pos_point, look_at_point);
+                let controller = camera::CameraController::new(speed, sensitivity);
+
+                (camera, controller)
+            }
+            components::Camera::Fixed { look_at } => {
+                let pos_point = cgmath::Point3::from_vec(camera_pos.pos);
+                let look_at_point = look_at;
+                let camera = camera::Camera::new_look_at(pos_point, look_at_point);
+                let controller = camera::CameraController::new(0.0, 0.0)
+                    .with_scroll_zoom(camera::ScrollZoom::FieldOfView);
+
+                (camera, controller)
+            }
+        };
+
+        Self::init_orbit_camera(&ecs_lock, camera_entity, &mut camera);
+
+        (camera, controller)
+    }
+
+    /// Place an orbiting camera at its distance and angles around the target, so it does not
+    /// swing in from the place of its `Pos3` in the first frame. `update_orbit_camera` follows it after.
+    ///
+    /// # Arguments
+    ///
+    /// * `ecs` - The ECS of the camera.
+    /// * `camera_entity` - The camera entity, nothing changes without an `OrbitCameraController`.
+    /// * `camera` - The camera to place.
+    fn init_orbit_camera(
+        ecs: &ecs::Manager,
+        camera_entity: ecs::Entity,
+        camera: &mut camera::Camera,
+    ) {
+        let Some(orbit) = ecs
+            .get_component_from_entity::<components::OrbitCameraController>(camera_entity)
+            .map(|orbit| *orbit.read().unwrap())
+        else {
+            return;
+        };
+        let Some(target) = ecs.get_component_from_entity::<components::Pos3>(orbit.target) else {
+            warn!(
+                "[Renderer] The orbit camera target {:?} has no Pos3, the camera stays in place",
+                orbit.target
+            );
+            return;
+        };
+        let pivot = Point3::from_vec(orbit.pivot(target.read().unwrap().pos));
+        camera.position = pivot - orbit.forward() * orbit.distance;
+        camera.look_at(pivot);
+    }
+
+    /// Create the uniforms of the valid light entities, the lights are only collected once.
+    async fn init_lights(&mut self) {
+        let ecs_lock = self.ecs.lock().unwrap();
+        let light_entities = ecs_lock
+            .get_entites_with_component::<components::Light>()
+            .into_iter()
+            .filter(
+                |entity| match components::Light::validate(&ecs_lock, *entity) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        warn!("[Renderer] {}, the light is skipped", err);
+                        false
+                    }
+                },
+            )
+            .collect::<Vec<_>>();
+
+        for entity in light_entities.iter() {
+            let pos = ecs_lock
+                .get_component_from_entity::<components::Pos3>(*entity)
+                .unwrap();
+
+            let light = ecs_lock
+                .get_component_from_entity::<components::Light>(*entity)
+                .unwrap();
+
+            let light_uniform = {
+                let rlock_pos = pos.read().unwrap();
+                let rlock_light = light.read().unwrap();
+
+                match *rlock_light {
+                    components::Light::Point { radius, intensity } => light::LightUniform {
+                        position: [rlock_pos.pos.x, rlock_pos.pos.y, rlock_pos.pos.z],
+                        light_type: light::LightType::Point as u32,
+                        color: [1.0, 1.0, 1.0],
+                        radius,
+                        direction: [0.0; 3],
+                        intensity,
+                        ..Default::default()
+                    },
+                    components::Light::PointColoured {
+                        radius,
+                        color,
+                        intensity,
+                    } => light::LightUniform {
+                        position: [rlock_pos.pos.x, rlock_pos.pos.y, rlock_pos.pos.z],
+                        light_type: light::LightType::Point as u32,
+                        color,
+                        radius,
+                        direction: [0.0; 3],
+                        intensity,
+                        ..Default::default()
+                    },
+                    components::Light::Ambient { intensity } => light::LightUniform {
+                        position: [rlock_pos.pos.x, rlock_pos.pos.y, rlock_pos.pos.z],
+                        light_type: light::LightType::Ambient as u32,
+                        color: [1.0, 1.0, 1.0],
+                        radius: 0.0,
+                        direction: [0.0; 3],
+                        intensity,
+                        ..Default::default()
+                    },
+                    components::Light::AmbientColoured { color, intensity } => {
+                        light::LightUniform {
+                            position: [rlock_pos.pos.x, rlock_pos.pos.y, rlock_pos.pos.z],
+                            light_type: light::LightType::Ambient as u32,
+                            color,
+                            radius: 0.0,
+                            direction: [0.0; 3],
+                            intensity,
+                            ..Default::default()
+                        }
+                    }
+                    components::Light::Directional {
+                        direction,
+                        intensity,
+                    } => light::LightUniform {
+                        position: [rlock_pos.pos.x, rlock_pos.pos.y, rlock_pos.pos.z],
+                        light_type: light::LightType::Directional as u32,
+                        color: [1.0, 1.0, 1.0],
+                        radius: 0.0,
+                        direction,
+                        intensity,
+                        ..Default::default()
+                    },
+                    components::Light::DirectionalColoured {
+                        direction,
+                        color,
+                        intensity,
+                    } => light::LightUniform {
+                        position: [rlock_pos.pos.x, rlock_pos.pos.y, rlock_pos.pos.z],
+                        light_type: light::LightType::Directional as u32,
+                        color,
+                        radius: 0.0,
+                        direction,
+                        intensity,
+                        ..Default::default()
+                    },
+                    components::Light::Rect {
+                        width,
+                        height,
+                        direction,
+                        color,
+                        intensity,
+                        radius,
+                    } => light::LightUniform {
+                        position: [rlock_pos.pos.x, rlock_pos.pos.y, rlock_pos.pos.z],
+                        light_type: light::LightType::Rect as u32,
+                        color,
+                        radius,
+                        direction,
+                        intensity,
+                        size: [width, height],
+                        ..Default::default()
+                    },
+                }
+            };
+            ecs_lock.add_component_to_entity(*entity, light_uniform);
+        }
+
+        if light_entities.len() > self.light_culling_config.max_lights as usize {
+            info!(
+                "[Renderer] {} lights, the {} most important are drawn each frame",
+                light_entities.len(),
+                self.light_culling_config.max_lights
+            );
+        }
+
+        self.light_entities = Some(light_entities);
+    }
+
+    /// Load the models of the entities which have none loaded yet, so models can be spawned at runtime.
+    /// The entities missing the components required by `Model` are skipped, they were reported when spawned.
+    /// The ECS is not locked while a model loads.
+    pub(super) async fn init_models(&mut self) {
+        let model_entities = {
+            let ecs_lock = self.ecs.lock().unwrap();
+            ecs_lock
+                .get_entites_with_component::<components::Model>()
+                .into_iter()
+                .filter(|entity| {
+                    ecs_lock
+                        .get_component_from_entity::<model::Model>(*entity)
+                        .is_none()
+                        && components::Model::missing(&ecs_lock, *entity).is_empty()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for entity in model_entities.iter() {
+            let Some(obj_path) = self
+                .ecs
+                .lock()
+                .unwrap()
+                .get_component_from_entity::<components::Model>(*entity)
+                .map(|model| model.read().unwrap().obj_path())
+            else {
+                continue;
+            };
+
+            let mut failures = Vec::new();
+            let obj_model = match resources::load_model(
+                obj_path,
+                &self.device,
+                &self.queue,
+                &self.texture_bind_group_layout,
+                self.texture_streamer.is_some(),
+                self.texture_atlas.as_ref(),
+                &mut failures,
+            )
+            .await
+            {
+                Ok(obj_model) => obj_model,
+                Err(error) => {
+                    failures.push(resources::LoadFailure {
+                        path: obj_path.to_string(),
+                        error,
+                    });
+                    resources::missing_model(
+                        &self.device,
+                        &self.queue,
+                        &self.texture_bind_group_layout,
+                    )
+                }
+            };
+
+            // The entity may have been despawned while its model loaded
+            let ecs_lock = self.ecs.lock().unwrap();
+            let (Some(name), Some(pos)) = (
+                ecs_lock.get_component_from_entity::<components::Name>(*entity),
+                ecs_lock.get_component_from_entity::<components::Pos3>(*entity),
+            ) else {
+                continue;
+            };
+
+            let flip = ecs_lock.get_component_from_entity::<components::Flip>(*entity);
+
+            let scale = ecs_lock.get_component_from_entity::<components::Scale>(*entity);
+
+            report_load_failures(&ecs_lock, *entity, failures);
+            if let Some(bounds) = obj_model.bounds {
+                if ecs_lock
+                    .get_component_from_entity::<components::Bounds>(*entity)
+                    .is_none()
+                {
+                    ecs_lock.add_component_to_entity(*entity, bounds);
+                }
+            }
+            ecs_lock.add_component_to_entity(*entity, obj_model);
+
+            // TODO rename instance to model::ModelUniform
+            let mut instance = {
+                let rlock_pos = pos.read().unwrap();
+                instance::Instance::new(
+                    rlock_pos.pos,
+                    rlock_pos
+                        .rot
+                        .unwrap_or(cgmath::Quaternion::from_angle_y(cgmath::Rad(0.0))),
+                )
+            };
+            instance.motion_blur = ecs_lock
+                .get_component_from_entity::<post::NoMotionBlur>(*entity)
+                .is_none();
+
+            if let Some(flip) = flip {
+                let rlock_flip = flip.read().unwrap();
+
+                match *rlock_flip {
+                    Flip::Horizontal => {
+                        instance.rotation =
+                            cgmath::Quaternion::from_angle_y(cgmath::Rad(std::f32::consts::PI));
+                    }
+                    Flip::Vertical => {
+                        instance.rotation =
+                            cgmath::Quaternion::from_angle_x(cgmath::Rad(std::f32::consts::PI));
+                    }
+                    Flip::Both => {
+                        instance.rotation =
+                            cgmath::Quaternion::from_angle_y(cgmath::Rad(std::f32::consts::PI));
+                        instance.rotation =
+                            cgmath::Quaternion::from_angle_x(cgmath::Rad(std::f32::consts::PI));
+                    }
+                }
+            }
+
+            // if let Some(scale) = scale {
+            //     let rlock_scale = scale.read().unwrap();
+
+            //     match *rlock_scale {
+            //         Scale::Uniform(s) => {
+            //             instance.scale = cgmath::Vector3::new(s, s, s);
+            //         }
+            //         Scale::NonUniform { x, y, z } => {
+            //             instance.scale = cgmath::Vector3::new(x, y, z);
+            //         }
+            //     }
+            // }
+
+            let instance_raw = instance.to_raw();
+            let instance_buffer =
+                self.device
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some(format!("{} Instance Buffer", name.read().unwrap().0).as_str()),
+                        contents: bytemuck::cast_slice(&[instance_raw]),
+                        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                    });
+            ecs_lock.add_component_to_entity(*entity, instance);
+            ecs_lock.add_component_to_entity(*entity, instance_buffer);
+        }
+
+        // The entities despawned since, e.g. when the play mode stops, are not drawn anymore
+        let ecs_lock = self.ecs.lock().unwrap();
+        let entities = self.model_entities.get_or_insert_with(Vec::new);
+        entities.extend(model_entities);
+        entities.retain(|entity| {
+            ecs_lock
+                .get_component_from_entity::<model::Model>(*entity)
+                .is_some()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{InnerSpace, Vector3};
+
+    #[test]
+    fn test_init_orbit_camera() {
+        let ecs = Arc::new(Mutex::new(ecs::Manager::default()));
+        let target_pos = Vector3::new(2.0, 0.0, 0.0);
+        let orbit = {
+            let ecs = ecs.lock().unwrap();
+            let target = ecs.create_entity();
+            ecs.add_component_to_entity(target, components::Pos3::new(target_pos));
+            let orbit = components::OrbitCameraController::new(target, 5.0);
+            let camera = ecs.create_entity();
+            ecs.add_component_to_entity(
+                camera,
+                components::Pos3::new(Vector3::new(0.0, 50.0, 0.0)),
+            );
+            ecs.add_component_to_entity(
+                camera,
+                components::Camera::Fixed {
+                    look_at: Point3::new(0.0, 0.0, 0.0),
+                },
+            );
+            ecs.add_component_to_entity(camera, orbit);
+            orbit
+        };
+
+        // The camera starts around the target instead of at its Pos3
+        let (camera, _) = State::init_camera(Arc::clone(&ecs));
+        let offset = orbit.pivot(target_pos) - camera.position.to_vec();
+        assert!((offset.magnitude() - 5.0).abs() < 1e-4);
+        assert!(camera.forward().dot(offset.normalize()) > 0.999);
+    }
+}
@@ -2,15 +2,32 @@ use super::model;
 pub(crate) struct Instance {
     pub position: cgmath::Vector3<f32>,
     pub rotation: cgmath::Quaternion<f32>,
+    /// The model matrix of the previous frame, used for the velocity buffer.
+    pub previous: Option<cgmath::Matrix4<f32>>,
+    pub motion_blur: bool,
 }
 
 impl Instance {
+    pub fn new(position: cgmath::Vector3<f32>, rotation: cgmath::Quaternion<f32>) -> Self {
+        Self {
+            position,
+            rotation,
+            previous: None,
+            motion_blur: true,
+        }
+    }
+
+    pub fn model_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.position) * cgmath::Matrix4::from(self.rotation)
+    }
+
     pub fn to_raw(&self) -> InstanceRaw {
-        let model =
-            cgmath::Matrix4::from_translation(self.position) * cgmath::Matrix4::from(self.rotation);
+        let model = self.model_matrix();
         InstanceRaw {
             model: model.into(),
             normal: cgmath::Matrix3::from(self.rotation).into(),
+            previous_model: self.previous.unwrap_or(model).into(),
+            motion_blur: if self.motion_blur { 1.0 } else { 0.0 },
         }
     }
 }
@@ -21,6 +38,8 @@ impl Instance {
 pub(crate) struct InstanceRaw {
     pub model: [[f32; 4]; 4],
     pub normal: [[f32; 3]; 3],
+    pub previous_model: [[f32; 4]; 4],
+    pub motion_blur: f32,
 }
 
 impl model::Vertex for InstanceRaw {
@@ -66,6 +85,31 @@ impl model::Vertex for InstanceRaw {
                     shader_location: 11,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 25]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 29]>() as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 33]>() as wgpu::BufferAddress,
+                    shader_location: 14,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 37]>() as wgpu::BufferAddress,
+                    shader_location: 15,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 41]>() as wgpu::BufferAddress,
+                    shader_location: 16,
+                    format: wgpu::VertexFormat::Float32,
+                },
             ],
         }
     }
@@ -1,15 +1,24 @@
+use std::collections::HashMap;
 use std::default;
 
-use cgmath::{Point3, Vector3};
+use cgmath::{InnerSpace, Matrix4, Point3, Vector3};
 use wgpu::util::DeviceExt;
 
-pub(crate) const NUM_MAX_LIGHTS: u32 = 20;
+use super::buffer::GrowableBuffer;
+use crate::core::config::LightCullingConfig;
+use crate::ecs::Entity;
+
+/// The size of the screen tiles the lights are binned into, in pixels.
+pub(crate) const TILE_SIZE: u32 = 32;
+/// The most lights a tile lists, the least important lights over a crowded tile are dropped.
+pub(crate) const MAX_LIGHTS_PER_TILE: usize = 64;
 
 #[repr(u32)]
 pub(crate) enum LightType {
     Point = 0,
     Ambient = 1,
     Directional = 2,
+    Rect = 3,
 }
 
 #[repr(C)]
@@ -21,6 +30,9 @@ pub(crate) struct LightUniform {
     pub radius: f32,
     pub direction: [f32; 3],
     pub intensity: f32,
+    /// The width and height of rect lights.
+    pub size: [f32; 2],
+    pub _padding: [f32; 2],
 }
 
 impl Default for LightUniform {
@@ -32,24 +44,471 @@ impl Default for LightUniform {
             radius: 0.0,
             direction: [0.0; 3],
             intensity: 0.1,
+            size: [0.0; 2],
+            _padding: [0.0; 2],
         }
     }
 }
 
+impl LightUniform {
+    /// Check if the light reaches everything, the ambient and directional lights are not binned.
+    fn is_global(&self) -> bool {
+        self.light_type == LightType::Ambient as u32
+            || self.light_type == LightType::Directional as u32
+    }
+}
+
+/// The screen tiles of the frame, the global lights come first in the light buffer.
 #[repr(C)]
-#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub(crate) struct LightData {
-    pub lights: [LightUniform; NUM_MAX_LIGHTS as usize],
-    pub num_lights: u32,
-    pub _padding: [u32; 3], // Padding to align to 16 bytes
+#[derive(Debug, Copy, Clone, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct LightGrid {
+    pub tile_size: u32,
+    pub tiles_x: u32,
+    pub tiles_y: u32,
+    pub num_global: u32,
 }
 
-impl Default for LightData {
-    fn default() -> Self {
+/// The lights binned into the screen tiles.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LightTiles {
+    pub grid: LightGrid,
+    /// The offset into `data` and the count of the light indices of every tile, row by row,
+    /// followed by the light indices.
+    pub data: Vec<u32>,
+}
+
+impl LightTiles {
+    /// A single tile every light is global in, for frames not drawn from one camera.
+    pub fn untiled(num_lights: usize) -> Self {
+        Self {
+            grid: LightGrid {
+                tile_size: TILE_SIZE,
+                tiles_x: 1,
+                tiles_y: 1,
+                num_global: num_lights as u32,
+            },
+            data: vec![2, 0],
+        }
+    }
+}
+
+/// Get the part of the screen a local light reaches, in normalized device coordinates.
+///
+/// # Returns
+///
+/// The minimum and maximum corners, `None` if the light is off screen.
+fn screen_bounds(
+    light: &LightUniform,
+    view_proj: Matrix4<f32>,
+    eye: Vector3<f32>,
+) -> Option<([f32; 2], [f32; 2])> {
+    let full = Some(([-1.0, -1.0], [1.0, 1.0]));
+    let center = Vector3::from(light.position);
+    if (center - eye).magnitude() <= light.radius {
+        return full;
+    }
+
+    let (mut min, mut max) = ([f32::MAX; 2], [f32::MIN; 2]);
+    let mut behind = 0;
+    for corner in 0..8 {
+        let sign = |bit: u32| if corner & bit == 0 { -1.0 } else { 1.0 };
+        let offset = Vector3::new(sign(1), sign(2), sign(4)) * light.radius;
+        let clip = view_proj * (center + offset).extend(1.0);
+        if clip.w <= f32::EPSILON {
+            behind += 1;
+            continue;
+        }
+        let (x, y) = (clip.x / clip.w, clip.y / clip.w);
+        min = [min[0].min(x), min[1].min(y)];
+        max = [max[0].max(x), max[1].max(y)];
+    }
+    // The corners behind the camera project to the wrong side, so a range crossing it covers everything
+    match behind {
+        8 => return None,
+        1.. => return full,
+        _ => {}
+    }
+    if max[0] < -1.0 || max[1] < -1.0 || min[0] > 1.0 || min[1] > 1.0 {
+        return None;
+    }
+    Some((min, max))
+}
+
+/// Bin the selected lights into screen tiles of `TILE_SIZE` pixels.
+///
+/// # Arguments
+///
+/// * `lights` - The selected lights, the global ones first and the rest the most important first.
+/// * `view_proj` - The view projection matrix of the camera.
+/// * `eye` - The position of the camera.
+/// * `width` - The width of the frame in pixels.
+/// * `height` - The height of the frame in pixels.
+pub(crate) fn bin_lights(
+    lights: &[LightUniform],
+    view_proj: Matrix4<f32>,
+    eye: Vector3<f32>,
+    width: u32,
+    height: u32,
+) -> LightTiles {
+    let tiles_x = width.div_ceil(TILE_SIZE).max(1);
+    let tiles_y = height.div_ceil(TILE_SIZE).max(1);
+    let num_global = lights.iter().take_while(|light| light.is_global()).count();
+
+    let mut tiles = vec![Vec::new(); (tiles_x * tiles_y) as usize];
+    for (i, light) in lights.iter().enumerate().skip(num_global) {
+        if light.is_global() {
+            continue;
+        }
+        let Some((min, max)) = screen_bounds(light, view_proj, eye) else {
+            continue;
+        };
+        // The y of the normalized device coordinates points up, the pixel rows down
+        let tile = |pixel: f32, tiles: u32| ((pixel / TILE_SIZE as f32) as u32).min(tiles - 1);
+        let column = |ndc: f32| tile((ndc.clamp(-1.0, 1.0) * 0.5 + 0.5) * width as f32, tiles_x);
+        let row = |ndc: f32| tile((0.5 - ndc.clamp(-1.0, 1.0) * 0.5) * height as f32, tiles_y);
+        let (x0, x1) = (column(min[0]), column(max[0]));
+        let (y0, y1) = (row(max[1]), row(min[1]));
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let tile = &mut tiles[(y * tiles_x + x) as usize];
+                if tile.len() < MAX_LIGHTS_PER_TILE {
+                    tile.push(i as u32);
+                }
+            }
+        }
+    }
+
+    let mut data = Vec::with_capacity(tiles.len() * 2 + tiles.iter().map(Vec::len).sum::<usize>());
+    let mut offset = tiles.len() as u32 * 2;
+    for tile in &tiles {
+        data.extend([offset, tile.len() as u32]);
+        offset += tile.len() as u32;
+    }
+    for tile in tiles {
+        data.extend(tile);
+    }
+
+    LightTiles {
+        grid: LightGrid {
+            tile_size: TILE_SIZE,
+            tiles_x,
+            tiles_y,
+            num_global: num_global as u32,
+        },
+        data,
+    }
+}
+
+/// The storage buffers of the lights and their tiles, they grow when a frame needs more.
+pub(crate) struct LightBuffers {
+    lights: GrowableBuffer,
+    grid: wgpu::Buffer,
+    tiles: GrowableBuffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl LightBuffers {
+    /// The lights the buffers have room for at first.
+    const INITIAL_LIGHTS: u64 = 64;
+    const INITIAL_TILE_DATA: u64 = 4096;
+
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let storage = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                storage(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(2),
+            ],
+            label: Some("light_bind_group_layout"),
+        })
+    }
+
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> Self {
+        let lights = GrowableBuffer::new(
+            device,
+            "Light Buffer",
+            wgpu::BufferUsages::STORAGE,
+            Self::INITIAL_LIGHTS * std::mem::size_of::<LightUniform>() as u64,
+        );
+        let tiles = GrowableBuffer::new(
+            device,
+            "Light Tile Buffer",
+            wgpu::BufferUsages::STORAGE,
+            Self::INITIAL_TILE_DATA * 4,
+        );
+        let grid = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Light Grid Buffer"),
+            // A single empty tile until the first frame bins the lights
+            contents: bytemuck::cast_slice(&[LightGrid {
+                tile_size: TILE_SIZE,
+                tiles_x: 1,
+                tiles_y: 1,
+                num_global: 0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group =
+            Self::create_bind_group(device, layout, lights.buffer(), &grid, tiles.buffer());
         Self {
-            lights: [LightUniform::default(); NUM_MAX_LIGHTS as usize],
-            num_lights: 0,
-            _padding: [0; 3],
+            lights,
+            grid,
+            tiles,
+            bind_group,
         }
     }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        lights: &wgpu::Buffer,
+        grid: &wgpu::Buffer,
+        tiles: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: lights.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: grid.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: tiles.as_entire_binding(),
+                },
+            ],
+            label: Some("light_bind_group"),
+        })
+    }
+
+    /// Upload the lights and their tiles, the bind group is created again if a buffer was reallocated.
+    pub fn write(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        lights: &[LightUniform],
+        tiles: &LightTiles,
+    ) {
+        let lights_reallocated = self
+            .lights
+            .write(device, queue, bytemuck::cast_slice(lights));
+        let tiles_reallocated = self
+            .tiles
+            .write(device, queue, bytemuck::cast_slice(&tiles.data));
+        if lights_reallocated || tiles_reallocated {
+            self.bind_group = Self::create_bind_group(
+                device,
+                layout,
+                self.lights.buffer(),
+                &self.grid,
+                self.tiles.buffer(),
+            );
+        }
+        queue.write_buffer(&self.grid, 0, bytemuck::cast_slice(&[tiles.grid]));
+    }
+
+    /// Get the size of the light buffers in bytes.
+    pub fn gpu_memory(&self) -> u64 {
+        self.lights.size() + self.grid.size() + self.tiles.size()
+    }
+}
+
+/// Score how much a light adds to the frame, its brightness times the part of the view its range covers.
+/// The ambient and directional lights light everything, so they are the most important.
+pub(crate) fn importance(light: &LightUniform, eye: Vector3<f32>) -> f32 {
+    if light.light_type == LightType::Ambient as u32
+        || light.light_type == LightType::Directional as u32
+    {
+        return f32::INFINITY;
+    }
+    let distance = (Vector3::from(light.position) - eye).magnitude();
+    // The whole view is covered when the camera is in the range
+    let coverage = (light.radius / distance.max(f32::EPSILON)).min(1.0).powi(2);
+    let brightness = light.color.iter().copied().fold(0.0, f32::max) * light.intensity;
+    brightness * coverage
+}
+
+/// Selects the most important lights each frame, see `LightCullingConfig`.
+/// The lights entering or leaving the selection are faded by scaling their intensity, so they do not pop.
+#[derive(Debug, Default)]
+pub(crate) struct LightCulling {
+    /// The fade of every light seen, from 0 when culled to 1 when selected.
+    fades: HashMap<Entity, f32>,
+}
+
+impl LightCulling {
+    /// Select the lights of this frame, at most `max_lights` of the config and the ones fading out.
+    ///
+    /// # Returns
+    ///
+    /// The selected lights, the global ones and then the most important first, with their intensity faded.
+    pub fn select(
+        &mut self,
+        lights: &[(Entity, LightUniform)],
+        eye: Vector3<f32>,
+        config: &LightCullingConfig,
+        dt: f32,
+    ) -> Vec<LightUniform> {
+        let mut scored = lights
+            .iter()
+            .map(|(entity, light)| (*entity, *light, importance(light, eye)))
+            .collect::<Vec<_>>();
+        scored.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+        let step = if config.fade_time > 0.0 {
+            dt / config.fade_time
+        } else {
+            1.0
+        };
+        let budget = config.max_lights as usize;
+        let mut fades = HashMap::with_capacity(scored.len());
+        let (mut selected, mut fading) = (Vec::new(), Vec::new());
+        for (i, (entity, light, score)) in scored.into_iter().enumerate() {
+            let wanted = i < budget && score >= config.min_importance;
+            // The lights seen for the first time, e.g. when the scene starts, are not faded in
+            let fade = match self.fades.get(&entity) {
+                Some(fade) if wanted => (fade + step).min(1.0),
+                Some(fade) => (fade - step).max(0.0),
+                None => wanted as u8 as f32,
+            };
+            fades.insert(entity, fade);
+            if fade <= 0.0 {
+                continue;
+            }
+
+            let light = LightUniform {
+                intensity: light.intensity * fade,
+                ..light
+            };
+            if wanted {
+                selected.push(light);
+            } else {
+                fading.push(light);
+            }
+        }
+        self.fades = fades;
+
+        // The global lights fading out stay in front, they are not binned
+        selected.extend(fading);
+        selected.sort_by_key(|light| !light.is_global());
+        selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f32, intensity: f32) -> LightUniform {
+        LightUniform {
+            position: [x, 0.0, 0.0],
+            light_type: LightType::Point as u32,
+            radius: 5.0,
+            intensity,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_select_and_fade() {
+        let config = LightCullingConfig {
+            max_lights: 2,
+            min_importance: 0.01,
+            fade_time: 0.5,
+        };
+        let eye = Vector3::new(0.0, 0.0, 0.0);
+        let mut lights = vec![
+            (Entity(0), LightUniform::default()),
+            (Entity(1), point(2.0, 1.0)),
+            (Entity(2), point(20.0, 1.0)),
+            // Too far and dim to matter
+            (Entity(3), point(500.0, 0.1)),
+        ];
+        assert!(importance(&lights[1].1, eye) > importance(&lights[2].1, eye));
+
+        let mut culling = LightCulling::default();
+        let selected = culling.select(&lights, eye, &config, 0.1);
+        assert_eq!(selected.len(), 2);
+        assert_eq!(selected[0].light_type, LightType::Ambient as u32);
+        assert_eq!(selected[1].position, [2.0, 0.0, 0.0]);
+
+        // The close light moves away, the other one fades in while it fades out
+        lights[1].1.position = [40.0, 0.0, 0.0];
+        let selected = culling.select(&lights, eye, &config, 0.1);
+        assert_eq!(selected.len(), 3);
+        assert_eq!(selected[1].position, [20.0, 0.0, 0.0]);
+        assert!((selected[1].intensity - 0.2).abs() < 1e-5);
+        assert!((selected[2].intensity - 0.8).abs() < 1e-5);
+
+        for _ in 0..10 {
+            culling.select(&lights, eye, &config, 0.1);
+        }
+        let selected = culling.select(&lights, eye, &config, 0.1);
+        assert_eq!(selected.len(), 2);
+        assert_eq!(selected[1].intensity, 1.0);
+    }
+
+    /// Get the indices of the local lights over a tile.
+    fn tile(tiles: &LightTiles, x: u32, y: u32) -> &[u32] {
+        let tile = (y * tiles.grid.tiles_x + x) as usize;
+        let offset = tiles.data[tile * 2] as usize;
+        &tiles.data[offset..offset + tiles.data[tile * 2 + 1] as usize]
+    }
+
+    #[test]
+    fn test_bin_lights() {
+        let eye = Vector3::new(0.0, 0.0, 0.0);
+        let view_proj = cgmath::perspective(cgmath::Deg(90.0), 1.0, 0.1, 100.0)
+            * Matrix4::look_at_rh(
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(0.0, 0.0, -1.0),
+                Vector3::unit_y(),
+            );
+        let at = |position: [f32; 3], radius: f32| LightUniform {
+            position,
+            radius,
+            ..point(0.0, 1.0)
+        };
+        let lights = [
+            LightUniform::default(),
+            // Over the top left tile of the 2x2
+            at([-5.0, 5.0, -10.0], 1.0),
+            // Behind the camera and off to the side
+            at([0.0, 0.0, 10.0], 1.0),
+            at([50.0, 0.0, -10.0], 1.0),
+            // The camera is in its range
+            at([0.0, 0.0, -1.0], 5.0),
+        ];
+
+        let tiles = bin_lights(&lights, view_proj, eye, 64, 64);
+        assert_eq!((tiles.grid.tiles_x, tiles.grid.tiles_y), (2, 2));
+        assert_eq!(tiles.grid.num_global, 1);
+        assert_eq!(tile(&tiles, 0, 0), [1, 4]);
+        assert_eq!(tile(&tiles, 1, 0), [4]);
+        assert_eq!(tile(&tiles, 0, 1), [4]);
+        assert_eq!(tile(&tiles, 1, 1), [4]);
+    }
 }
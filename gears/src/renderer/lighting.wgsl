@@ -0,0 +1,152 @@
+// The lights of the scene, included by the passes drawing lit geometry.
+// The including shader declares the light bindings at its own group:
+//   var<storage, read> lights: array<Light>;
+//   var<uniform> light_grid: LightGrid;
+//   var<storage, read> light_tiles: array<u32>;
+// Define SPECULAR for the specular highlights, without it the lights are diffuse only.
+// Define SHADOWS for the cascaded shadows of a directional light and the shadows of the point lights, see `shadows`.
+
+#ifdef SHADOWS
+#include "shadows"
+#endif
+
+struct Light {
+    position: vec3<f32>,
+    light_type: u32,
+    color: vec3<f32>,
+    radius: f32,
+    direction: vec3<f32>,
+    intensity: f32,
+    size: vec2<f32>,
+    _padding: vec2<f32>,
+}
+
+// The screen tiles the local lights are binned into, the global lights come first and light every tile
+struct LightGrid {
+    tile_size: u32,
+    tiles_x: u32,
+    tiles_y: u32,
+    num_global: u32,
+}
+
+// The closest point of a rect light to a point, the rect faces along the light direction
+fn closest_point_on_rect(light: Light, right: vec3<f32>, up: vec3<f32>, point: vec3<f32>) -> vec3<f32> {
+    let offset = point - light.position;
+    let half_size = light.size * 0.5;
+    let x = clamp(dot(offset, right), -half_size.x, half_size.x);
+    let y = clamp(dot(offset, up), -half_size.y, half_size.y);
+    return light.position + right * x + up * y;
+}
+
+// Get the index of the n-th light lighting a fragment, the global lights and then the ones of its tile
+fn light_index(tile: u32, n: u32) -> u32 {
+    if (n < light_grid.num_global) {
+        return n;
+    }
+    return light_tiles[light_tiles[tile * 2u] + n - light_grid.num_global];
+}
+
+// Get the number of lights lighting a fragment and its tile
+fn tile_lights(frag_coord: vec2<f32>) -> vec2<u32> {
+    let tile_xy = min(
+        vec2<u32>(frag_coord) / light_grid.tile_size,
+        vec2<u32>(light_grid.tiles_x, light_grid.tiles_y) - 1u,
+    );
+    let tile = tile_xy.y * light_grid.tiles_x + tile_xy.x;
+    return vec2<u32>(light_grid.num_global + light_tiles[tile * 2u + 1u], tile);
+}
+
+// The strength of the highlight of a light, 0 without specular highlights
+fn specular(normal: vec3<f32>, view_dir: vec3<f32>, light_dir: vec3<f32>) -> f32 {
+#ifdef SPECULAR
+    let half_dir = normalize(view_dir + light_dir);
+    return pow(max(dot(normal, half_dir), 0.0), 32.0);
+#else
+    return 0.0;
+#endif
+}
+
+// The color of a fragment lit by a light
+fn shade_light(light: Light, albedo: vec3<f32>, position: vec3<f32>, normal: vec3<f32>, view_pos: vec3<f32>) -> vec3<f32> {
+    let view_dir = normalize(view_pos - position);
+
+    if (light.light_type == 0u) { // Point light
+        let distance = length(light.position - position);
+        let attenuation = clamp(1.0 - (distance / light.radius) * (distance / light.radius), 0.0, 1.0);
+        if (attenuation <= 0.0) {
+            return vec3<f32>(0.0);
+        }
+        let light_dir = normalize(light.position - position);
+        let diffuse_strength = max(dot(normal, light_dir), 0.0);
+        let specular_strength = specular(normal, view_dir, light_dir);
+        // Blending object color and light color for more balance
+        return light.color * light.intensity * (diffuse_strength + specular_strength) * attenuation
+            * mix(albedo, light.color, 0.3);
+    } else if (light.light_type == 1u) { // Ambient light
+        return light.color * light.intensity * albedo;
+    } else if (light.light_type == 2u) { // Directional light
+        let light_dir = normalize(light.position + light.direction); // Calculate the direction from the light's position
+        let diffuse_strength = max(dot(normal, light_dir), 0.0);
+        let specular_strength = specular(normal, view_dir, light_dir);
+        return light.color * light.intensity * (diffuse_strength + specular_strength) * albedo;
+    }
+
+    // Rect light, approximated with representative points: the closest point of the rect for the diffuse
+    // and the point closest to the reflected view ray for the specular
+    let forward = normalize(light.direction);
+    var world_up = vec3<f32>(0.0, 1.0, 0.0);
+    if (abs(forward.y) > 0.99) {
+        world_up = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let right = normalize(cross(forward, world_up));
+    let up = cross(right, forward);
+
+    // Only the front side of the rect emits light
+    if (dot(position - light.position, forward) <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+    let diffuse_point = closest_point_on_rect(light, right, up, position);
+    let distance = length(diffuse_point - position);
+    let attenuation = clamp(1.0 - (distance / light.radius) * (distance / light.radius), 0.0, 1.0);
+    let light_dir = normalize(diffuse_point - position);
+    let facing = max(dot(-light_dir, forward), 0.0);
+    let diffuse_strength = max(dot(normal, light_dir), 0.0);
+
+    // Intersect the reflected view ray with the plane of the rect
+    let reflected = reflect(-view_dir, normal);
+    let denominator = dot(reflected, forward);
+    var specular_point = diffuse_point;
+    if (denominator < -0.0001) {
+        let t = dot(light.position - position, forward) / denominator;
+        specular_point = closest_point_on_rect(light, right, up, position + reflected * t);
+    }
+    let specular_strength = specular(normal, view_dir, normalize(specular_point - position));
+
+    return light.color * light.intensity * (diffuse_strength + specular_strength) * attenuation * facing * albedo;
+}
+
+// The color of a fragment lit by the global lights and the lights of its tile
+fn shade(frag_coord: vec2<f32>, albedo: vec3<f32>, position: vec3<f32>, normal: vec3<f32>, view_pos: vec3<f32>) -> vec3<f32> {
+    var color = vec3<f32>(0.0, 0.0, 0.0);
+    let tile = tile_lights(frag_coord);
+    for (var n = 0u; n < tile.x; n = n + 1u) {
+        let index = light_index(tile.y, n);
+        var light_color = shade_light(lights[index], albedo, position, normal, view_pos);
+#ifdef SHADOWS
+        if (shadow.enabled != 0u && index == shadow.light) {
+            light_color = light_color * shadow_factor(position, normal);
+        }
+        let slot = point_shadow_slot(index);
+        if (slot < shadow.point_count) {
+            light_color = light_color * point_shadow_factor(slot, lights[index].position, position, normal);
+        }
+#endif
+        color = color + light_color;
+    }
+#ifdef SHADOWS
+    if (shadow.enabled != 0u && shadow.debug != 0u) {
+        color = color * cascade_color(position);
+    }
+#endif
+    return color;
+}
@@ -0,0 +1,240 @@
+//! The optimizations applied to the meshes after they are loaded.
+//!
+//! The meshopt compressed buffers of glTF files are decoded by `meshopt_codec` before,
+//! Draco compressed primitives are not supported.
+
+/// The size of the simulated post-transform vertex cache.
+const CACHE_SIZE: usize = 32;
+
+/// The score of a vertex in Tom Forsyth's linear-speed vertex cache optimization.
+/// Recently used vertices and vertices with few remaining triangles score higher.
+fn vertex_score(cache_position: Option<usize>, remaining: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+
+    let cache_score = match cache_position {
+        // The vertices of the last triangle score the same, so the order inside a triangle does not matter
+        Some(position) if position < 3 => 0.75,
+        Some(position) => (1.0 - (position - 3) as f32 / (CACHE_SIZE - 3) as f32)
+            .max(0.0)
+            .powf(1.5),
+        None => 0.0,
+    };
+    cache_score + 2.0 * (remaining as f32).powf(-0.5)
+}
+
+/// Reorder the triangles of an indexed triangle list to reuse the post-transform vertex cache.
+///
+/// # Arguments
+///
+/// * `indices` - The triangle list.
+/// * `vertex_count` - The number of vertices the indices refer to.
+///
+/// # Returns
+///
+/// The same triangles in a cache friendly order.
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+
+    // The triangles of each vertex, the first `remaining[v]` of a vertex are not emitted yet
+    let mut remaining = vec![0usize; vertex_count];
+    for index in &indices[..triangle_count * 3] {
+        remaining[*index as usize] += 1;
+    }
+    let mut offsets = vec![0usize; vertex_count + 1];
+    for vertex in 0..vertex_count {
+        offsets[vertex + 1] = offsets[vertex] + remaining[vertex];
+    }
+    let mut adjacency = vec![0usize; triangle_count * 3];
+    let mut filled = offsets.clone();
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        for index in corners {
+            adjacency[filled[*index as usize]] = triangle;
+            filled[*index as usize] += 1;
+        }
+    }
+
+    let mut cache_position = vec![None; vertex_count];
+    let mut vertex_scores = (0..vertex_count)
+        .map(|vertex| vertex_score(None, remaining[vertex]))
+        .collect::<Vec<_>>();
+    let triangle_vertices = |triangle: usize| &indices[triangle * 3..triangle * 3 + 3];
+    let mut triangle_scores = (0..triangle_count)
+        .map(|triangle| {
+            triangle_vertices(triangle)
+                .iter()
+                .map(|vertex| vertex_scores[*vertex as usize])
+                .sum::<f32>()
+        })
+        .collect::<Vec<_>>();
+    let mut emitted = vec![false; triangle_count];
+    // The triangles before it are all emitted
+    let mut next_unemitted = 0;
+
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut out = Vec::with_capacity(triangle_count * 3);
+    let mut best = None;
+
+    while out.len() < triangle_count * 3 {
+        // Fall back to the next triangle in the input order when no triangle touching the cache is left,
+        // the cursor only moves forward so the fallbacks take linear time in total
+        let triangle = best.take().unwrap_or_else(|| {
+            while emitted[next_unemitted] {
+                next_unemitted += 1;
+            }
+            next_unemitted
+        });
+        emitted[triangle] = true;
+
+        let corners = triangle_vertices(triangle);
+        out.extend_from_slice(corners);
+        for vertex in corners {
+            let vertex = *vertex as usize;
+            let live = offsets[vertex]..offsets[vertex] + remaining[vertex];
+            if let Some(i) = adjacency[live.clone()].iter().position(|t| *t == triangle) {
+                adjacency.swap(live.start + i, live.end - 1);
+                remaining[vertex] -= 1;
+            }
+        }
+
+        // Move the vertices of the triangle to the front of the cache
+        let mut new_cache = corners.to_vec();
+        new_cache.extend(cache.iter().filter(|vertex| !corners.contains(vertex)));
+        let evicted = new_cache.split_off(new_cache.len().min(CACHE_SIZE));
+        for vertex in &evicted {
+            cache_position[*vertex as usize] = None;
+        }
+        cache = new_cache;
+
+        let mut best_score = f32::MIN;
+        for (position, vertex) in cache.iter().chain(&evicted).enumerate() {
+            let vertex = *vertex as usize;
+            if position < cache.len() {
+                cache_position[vertex] = Some(position);
+            }
+            let score = vertex_score(cache_position[vertex], remaining[vertex]);
+            let delta = score - vertex_scores[vertex];
+            vertex_scores[vertex] = score;
+
+            for adjacent in &adjacency[offsets[vertex]..offsets[vertex] + remaining[vertex]] {
+                triangle_scores[*adjacent] += delta;
+                if triangle_scores[*adjacent] > best_score {
+                    best_score = triangle_scores[*adjacent];
+                    best = Some(*adjacent);
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// Reorder the vertices in the order the triangles use them, so the vertex buffer is read
+/// sequentially, and drop the vertices no triangle uses.
+pub fn optimize_vertex_fetch<T: Copy>(vertices: &mut Vec<T>, indices: &mut [u32]) {
+    let mut remap = vec![u32::MAX; vertices.len()];
+    let mut reordered = Vec::with_capacity(vertices.len());
+
+    for index in indices.iter_mut() {
+        let new_index = &mut remap[*index as usize];
+        if *new_index == u32::MAX {
+            *new_index = reordered.len() as u32;
+            reordered.push(vertices[*index as usize]);
+        }
+        *index = *new_index;
+    }
+
+    *vertices = reordered;
+}
+
+/// Get the average number of vertices transformed per triangle with a FIFO cache, lower is better.
+/// A value near 0.5 is ideal for a regular grid, 3.0 means no vertex is reused.
+pub fn average_cache_miss_ratio(indices: &[u32], cache_size: usize) -> f32 {
+    let mut cache = std::collections::VecDeque::with_capacity(cache_size);
+    let mut misses = 0;
+
+    for index in indices {
+        if !cache.contains(index) {
+            misses += 1;
+            if cache.len() >= cache_size {
+                cache.pop_front();
+            }
+            cache.push_back(*index);
+        }
+    }
+
+    misses as f32 / (indices.len() / 3).max(1) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A grid of quads with the triangles in a scrambled order.
+    fn scrambled_grid(size: u32) -> Vec<u32> {
+        let mut triangles = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let i = y * (size + 1) + x;
+                triangles.push([i, i + 1, i + size + 1]);
+                triangles.push([i + 1, i + size + 2, i + size + 1]);
+            }
+        }
+        let mut scrambled = Vec::new();
+        let mut i = 0;
+        for _ in 0..triangles.len() {
+            i = (i + 97) % triangles.len();
+            scrambled.extend(triangles[i]);
+        }
+        scrambled
+    }
+
+    fn sorted_triangles(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles = indices
+            .chunks_exact(3)
+            .map(|t| {
+                // Rotate the smallest index first, keeping the winding
+                let first = (0..3).min_by_key(|i| t[*i]).unwrap();
+                [t[first], t[(first + 1) % 3], t[(first + 2) % 3]]
+            })
+            .collect::<Vec<_>>();
+        triangles.sort();
+        triangles
+    }
+
+    #[test]
+    fn test_optimize_vertex_cache() {
+        let indices = scrambled_grid(32);
+        let vertex_count = 33 * 33;
+        let optimized = optimize_vertex_cache(&indices, vertex_count);
+
+        assert_eq!(sorted_triangles(&optimized), sorted_triangles(&indices));
+        let before = average_cache_miss_ratio(&indices, 16);
+        let after = average_cache_miss_ratio(&optimized, 16);
+        assert!(
+            after < 0.8 && after < before / 2.0,
+            "{} -> {}",
+            before,
+            after
+        );
+    }
+
+    #[test]
+    fn test_disconnected_triangles() {
+        // No triangle shares a vertex, so every triangle after the first is a fallback
+        let indices = (0..3 * 20_000).collect::<Vec<u32>>();
+        let optimized = optimize_vertex_cache(&indices, indices.len());
+        assert_eq!(optimized, indices);
+    }
+
+    #[test]
+    fn test_optimize_vertex_fetch() {
+        let mut vertices = vec!['a', 'b', 'c', 'd', 'e'];
+        let mut indices = vec![3, 1, 0, 1, 3, 2];
+        optimize_vertex_fetch(&mut vertices, &mut indices);
+
+        assert_eq!(vertices, vec!['d', 'b', 'a', 'c']);
+        assert_eq!(indices, vec![0, 1, 2, 1, 0, 3]);
+    }
+}
@@ -0,0 +1,628 @@
+//! The decoders of the `EXT_meshopt_compression` glTF extension.
+//!
+//! The buffer views of a glTF file can be compressed with the vertex and index codecs of
+//! meshoptimizer, optionally after a filter that quantizes the normals, the rotations or
+//! the floats further. The decoded bytes replace the data of the buffer view, so the
+//! accessors read them as if they were never compressed.
+
+use anyhow::{anyhow, bail};
+
+const VERTEX_HEADER: u8 = 0xa0;
+const INDEX_HEADER: u8 = 0xe0;
+const SEQUENCE_HEADER: u8 = 0xd0;
+
+/// The vertices of a block are limited so their bytes fit into 8 KiB.
+const VERTEX_BLOCK_SIZE_BYTES: usize = 8192;
+const VERTEX_BLOCK_MAX_SIZE: usize = 256;
+const BYTE_GROUP_SIZE: usize = 16;
+/// The vertex data ends with at least this many bytes, which holds the first vertex.
+const TAIL_MAX_SIZE: usize = 32;
+
+/// How the data of a compressed buffer view was encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// The vertex attributes, encoded with the vertex codec.
+    Attributes,
+    /// A triangle list, encoded with the index codec.
+    Triangles,
+    /// Any other indices, encoded with the index sequence codec.
+    Indices,
+}
+
+impl Mode {
+    pub fn parse(name: &str) -> anyhow::Result<Self> {
+        match name {
+            "ATTRIBUTES" => Ok(Self::Attributes),
+            "TRIANGLES" => Ok(Self::Triangles),
+            "INDICES" => Ok(Self::Indices),
+            _ => Err(anyhow!("Unknown meshopt compression mode {}", name)),
+        }
+    }
+}
+
+/// The filter applied to the decoded vertex attributes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Filter {
+    #[default]
+    None,
+    /// Unit vectors stored in two octahedral components, as 8 or 16 bit normalized integers.
+    Octahedral,
+    /// Unit quaternions stored in three components and the index of the largest one.
+    Quaternion,
+    /// Floats stored as a 24 bit mantissa and an 8 bit exponent.
+    Exponential,
+}
+
+impl Filter {
+    pub fn parse(name: &str) -> anyhow::Result<Self> {
+        match name {
+            "NONE" => Ok(Self::None),
+            "OCTAHEDRAL" => Ok(Self::Octahedral),
+            "QUATERNION" => Ok(Self::Quaternion),
+            "EXPONENTIAL" => Ok(Self::Exponential),
+            _ => Err(anyhow!("Unknown meshopt compression filter {}", name)),
+        }
+    }
+
+    /// Undo the filter on decoded vertex attributes in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The decoded attributes.
+    /// * `stride` - The size of an element in bytes.
+    pub fn apply(self, data: &mut [u8], stride: usize) -> anyhow::Result<()> {
+        match (self, stride) {
+            (Self::None, _) => {}
+            (Self::Octahedral, 4) => {
+                for element in data.chunks_exact_mut(4) {
+                    let normal = octahedral(
+                        [element[0], element[1], element[2]].map(|c| c as i8 as f32),
+                        i8::MAX as f32,
+                    );
+                    for (c, value) in element.iter_mut().zip(normal) {
+                        *c = value as i8 as u8;
+                    }
+                }
+            }
+            (Self::Octahedral, 8) => {
+                for element in data.chunks_exact_mut(8) {
+                    let [x, y, z, w] = read_i16s(element);
+                    let normal = octahedral([x, y, z].map(f32::from), i16::MAX as f32);
+                    let [x, y, z] = normal.map(|value| value as i16);
+                    write_i16s(element, [x, y, z, w]);
+                }
+            }
+            (Self::Quaternion, 8) => {
+                for element in data.chunks_exact_mut(8) {
+                    let [x, y, z, w] = read_i16s(element);
+                    let rotation = quaternion([x, y, z], w);
+                    write_i16s(element, rotation);
+                }
+            }
+            (Self::Exponential, stride) if stride.is_multiple_of(4) => {
+                for value in data.chunks_exact_mut(4) {
+                    let bits = u32::from_le_bytes([value[0], value[1], value[2], value[3]]);
+                    value.copy_from_slice(&exponential(bits).to_le_bytes());
+                }
+            }
+            (filter, stride) => bail!(
+                "The {:?} filter can't be used with a stride of {}",
+                filter,
+                stride
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// Decode the data of a compressed buffer view.
+///
+/// # Arguments
+///
+/// * `data` - The compressed bytes.
+/// * `count` - The number of elements.
+/// * `stride` - The size of an element in bytes, 2 or 4 for the indices.
+/// * `mode` - How the data was encoded.
+/// * `filter` - The filter applied to the attributes.
+///
+/// # Returns
+///
+/// The `count * stride` decoded bytes.
+pub fn decode(
+    data: &[u8],
+    count: usize,
+    stride: usize,
+    mode: Mode,
+    filter: Filter,
+) -> anyhow::Result<Vec<u8>> {
+    let mut decoded = match mode {
+        Mode::Attributes => decode_vertex_buffer(data, count, stride)?,
+        Mode::Triangles => index_bytes(decode_index_buffer(data, count)?, stride)?,
+        Mode::Indices => index_bytes(decode_index_sequence(data, count)?, stride)?,
+    };
+    filter.apply(&mut decoded, stride)?;
+    Ok(decoded)
+}
+
+fn index_bytes(indices: Vec<u32>, stride: usize) -> anyhow::Result<Vec<u8>> {
+    match stride {
+        2 => Ok(indices
+            .into_iter()
+            .flat_map(|index| (index as u16).to_le_bytes())
+            .collect()),
+        4 => Ok(indices.into_iter().flat_map(u32::to_le_bytes).collect()),
+        _ => bail!("Invalid index stride {}", stride),
+    }
+}
+
+/// Decode vertices encoded with the vertex codec.
+///
+/// Each byte of a vertex is delta encoded against the same byte of the previous vertex and
+/// the deltas of a block are packed in groups of 16 with 0, 2, 4 or 8 bits each.
+///
+/// # Arguments
+///
+/// * `data` - The compressed bytes.
+/// * `count` - The number of vertices.
+/// * `stride` - The size of a vertex in bytes, a multiple of 4 up to 256.
+pub fn decode_vertex_buffer(data: &[u8], count: usize, stride: usize) -> anyhow::Result<Vec<u8>> {
+    if stride == 0 || stride > 256 || !stride.is_multiple_of(4) {
+        bail!("Invalid vertex stride {}", stride);
+    }
+    if data.len() < 1 + stride {
+        bail!("The vertex data is truncated");
+    }
+    if data[0] != VERTEX_HEADER {
+        bail!("Unsupported vertex codec version {:#x}", data[0]);
+    }
+
+    let block_size =
+        ((VERTEX_BLOCK_SIZE_BYTES / stride) & !(BYTE_GROUP_SIZE - 1)).min(VERTEX_BLOCK_MAX_SIZE);
+    let mut last = data[data.len() - stride..].to_vec();
+    let mut vertices = vec![0; count * stride];
+    let mut deltas = [0; VERTEX_BLOCK_MAX_SIZE];
+    let mut position = 1;
+
+    for block in (0..count).step_by(block_size.max(1)) {
+        let block_count = block_size.min(count - block);
+        let aligned = block_count.next_multiple_of(BYTE_GROUP_SIZE);
+
+        for (k, previous) in last.iter_mut().enumerate() {
+            position = decode_bytes(data, position, &mut deltas[..aligned])?;
+            for (i, delta) in deltas[..block_count].iter().enumerate() {
+                // The deltas are zigzag encoded to keep the small negative ones small
+                *previous = previous.wrapping_add((delta >> 1) ^ (delta & 1).wrapping_neg());
+                vertices[(block + i) * stride + k] = *previous;
+            }
+        }
+    }
+
+    if data.len() - position != stride.max(TAIL_MAX_SIZE) {
+        bail!(
+            "The vertex data has {} unexpected bytes at the end",
+            data.len() - position
+        );
+    }
+    Ok(vertices)
+}
+
+/// Decode the byte groups of a byte of each vertex of a block.
+fn decode_bytes(data: &[u8], mut position: usize, deltas: &mut [u8]) -> anyhow::Result<usize> {
+    // A 2 bit header for each group
+    let header_size = (deltas.len() / BYTE_GROUP_SIZE).div_ceil(4);
+    if data.len() - position < header_size {
+        bail!("The vertex data is truncated");
+    }
+    let header = position;
+    position += header_size;
+
+    for (group, deltas) in deltas.chunks_exact_mut(BYTE_GROUP_SIZE).enumerate() {
+        // The tail is always there, so a group never reads past the end
+        if data.len() - position < TAIL_MAX_SIZE {
+            bail!("The vertex data is truncated");
+        }
+        let bits_log2 = (data[header + group / 4] >> ((group % 4) * 2)) & 3;
+        position = match bits_log2 {
+            0 => {
+                deltas.fill(0);
+                position
+            }
+            3 => {
+                deltas.copy_from_slice(&data[position..position + BYTE_GROUP_SIZE]);
+                position + BYTE_GROUP_SIZE
+            }
+            _ => {
+                let bits = 1 << bits_log2;
+                let sentinel = (1u8 << bits) - 1;
+                // The values that don't fit follow the packed ones as whole bytes
+                let mut extra = position + BYTE_GROUP_SIZE * bits / 8;
+                for (i, delta) in deltas.iter_mut().enumerate() {
+                    let shift = 8 - bits - (i * bits) % 8;
+                    let value = (data[position + i * bits / 8] >> shift) & sentinel;
+                    *delta = if value == sentinel {
+                        extra += 1;
+                        data[extra - 1]
+                    } else {
+                        value
+                    };
+                }
+                extra
+            }
+        };
+    }
+    Ok(position)
+}
+
+/// Decode a triangle list encoded with the index codec, version 0 or 1.
+///
+/// The triangles are rebuilt from a FIFO of the recent edges and one of the recent
+/// vertices, the new vertices are either the next unused index or delta encoded.
+///
+/// # Arguments
+///
+/// * `data` - The compressed bytes.
+/// * `count` - The number of indices, a multiple of 3.
+pub fn decode_index_buffer(data: &[u8], count: usize) -> anyhow::Result<Vec<u32>> {
+    if !count.is_multiple_of(3) {
+        bail!("The index count {} is not a multiple of 3", count);
+    }
+    // A code for each triangle and the 16 byte table of the auxiliary codes
+    let triangles = count / 3;
+    if data.len() < 1 + triangles + 16 {
+        bail!("The index data is truncated");
+    }
+    if data[0] & 0xf0 != INDEX_HEADER || data[0] & 0x0f > 1 {
+        bail!("Unsupported index codec version {:#x}", data[0]);
+    }
+    // Version 1 uses the codes 13 and 14 for the last free index plus or minus one
+    let fec_max = if data[0] & 0x0f >= 1 { 13 } else { 15 };
+
+    let mut edges = [[u32::MAX; 2]; 16];
+    let mut edge_offset = 0;
+    let mut vertices = [u32::MAX; 16];
+    let mut vertex_offset = 0;
+
+    let mut next = 0u32;
+    let mut last = 0u32;
+    let codes = &data[1..1 + triangles];
+    let mut position = 1 + triangles;
+    let safe_end = data.len() - 16;
+    let table = &data[safe_end..];
+    let mut indices = Vec::with_capacity(count);
+
+    for &code in codes {
+        // A triangle reads at most 16 bytes, which the table leaves room for
+        if position > safe_end {
+            bail!("The index data is truncated");
+        }
+
+        if code < 0xf0 {
+            // An edge from the FIFO and a new, recent or free vertex
+            let fe = (code >> 4) as usize;
+            let [a, b] = edges[(edge_offset + 15 - fe) & 15];
+            let fec = (code & 15) as usize;
+            let c = if fec < fec_max {
+                let c = if fec == 0 {
+                    next
+                } else {
+                    vertices[(vertex_offset + 15 - fec) & 15]
+                };
+                push_vertex(&mut vertices, &mut vertex_offset, c, fec == 0);
+                next += (fec == 0) as u32;
+                c
+            } else {
+                last = match fec {
+                    13 => last.wrapping_sub(1),
+                    14 => last.wrapping_add(1),
+                    _ => decode_index(data, &mut position, last),
+                };
+                push_vertex(&mut vertices, &mut vertex_offset, last, true);
+                last
+            };
+            push_edge(&mut edges, &mut edge_offset, c, b);
+            push_edge(&mut edges, &mut edge_offset, a, c);
+            indices.extend([a, b, c]);
+        } else {
+            // Three vertices that aren't in the edge FIFO
+            let (feb, fec, a) = if code < 0xfe {
+                let aux = table[(code & 15) as usize];
+                next += 1;
+                ((aux >> 4) as usize, (aux & 15) as usize, next - 1)
+            } else {
+                let aux = data[position];
+                position += 1;
+                // A zero code that is not from the table restarts the indices
+                if aux == 0 {
+                    next = 0;
+                }
+                let a = if code == 0xfe {
+                    next += 1;
+                    next - 1
+                } else {
+                    0
+                };
+                ((aux >> 4) as usize, (aux & 15) as usize, a)
+            };
+
+            let vertex = |fe: usize, next: &mut u32| match fe {
+                0 => {
+                    *next += 1;
+                    *next - 1
+                }
+                15 => u32::MAX,
+                _ => vertices[(vertex_offset + 16 - fe) & 15],
+            };
+            let b = vertex(feb, &mut next);
+            let c = vertex(fec, &mut next);
+
+            // The free vertices are decoded after the new ones are numbered
+            let mut free = |v: u32, is_free: bool| {
+                if is_free {
+                    last = decode_index(data, &mut position, last);
+                    last
+                } else {
+                    v
+                }
+            };
+            let a = free(a, code == 0xff);
+            let b = free(b, feb == 15);
+            let c = free(c, fec == 15);
+
+            push_vertex(&mut vertices, &mut vertex_offset, a, true);
+            push_vertex(&mut vertices, &mut vertex_offset, b, feb == 0 || feb == 15);
+            push_vertex(&mut vertices, &mut vertex_offset, c, fec == 0 || fec == 15);
+            push_edge(&mut edges, &mut edge_offset, b, a);
+            push_edge(&mut edges, &mut edge_offset, c, b);
+            push_edge(&mut edges, &mut edge_offset, a, c);
+            indices.extend([a, b, c]);
+        }
+    }
+
+    if position != safe_end {
+        bail!("The index data has unexpected bytes before the code table");
+    }
+    Ok(indices)
+}
+
+/// Decode indices encoded with the index sequence codec.
+///
+/// Each index is delta encoded against one of two baselines, which keeps two interleaved
+/// runs of indices small.
+///
+/// # Arguments
+///
+/// * `data` - The compressed bytes.
+/// * `count` - The number of indices.
+pub fn decode_index_sequence(data: &[u8], count: usize) -> anyhow::Result<Vec<u32>> {
+    // At least a byte for each index and a 4 byte tail
+    if data.len() < 1 + count + 4 {
+        bail!("The index data is truncated");
+    }
+    if data[0] & 0xf0 != SEQUENCE_HEADER || data[0] & 0x0f > 1 {
+        bail!("Unsupported index sequence codec version {:#x}", data[0]);
+    }
+
+    let mut baselines = [0u32; 2];
+    let mut position = 1;
+    let safe_end = data.len() - 4;
+    let mut indices = Vec::with_capacity(count);
+    for _ in 0..count {
+        if position >= safe_end {
+            bail!("The index data is truncated");
+        }
+        let value = decode_vbyte(data, &mut position);
+        let baseline = &mut baselines[(value & 1) as usize];
+        *baseline = baseline.wrapping_add(unzigzag(value >> 1));
+        indices.push(*baseline);
+    }
+
+    if position != safe_end {
+        bail!("The index data has unexpected bytes before the tail");
+    }
+    Ok(indices)
+}
+
+fn push_edge(edges: &mut [[u32; 2]; 16], offset: &mut usize, a: u32, b: u32) {
+    edges[*offset] = [a, b];
+    *offset = (*offset + 1) & 15;
+}
+
+/// Add a vertex to the FIFO, it is only kept when `push` is set.
+fn push_vertex(vertices: &mut [u32; 16], offset: &mut usize, v: u32, push: bool) {
+    vertices[*offset] = v;
+    *offset = (*offset + push as usize) & 15;
+}
+
+fn decode_index(data: &[u8], position: &mut usize, last: u32) -> u32 {
+    last.wrapping_add(unzigzag(decode_vbyte(data, position)))
+}
+
+fn unzigzag(value: u32) -> u32 {
+    (value >> 1) ^ (value & 1).wrapping_neg()
+}
+
+/// Read a value of up to 5 groups of 7 bits.
+fn decode_vbyte(data: &[u8], position: &mut usize) -> u32 {
+    let mut value = 0;
+    for group in 0..5 {
+        let byte = data[*position];
+        *position += 1;
+        value |= ((byte & 127) as u32) << (7 * group);
+        if byte < 128 {
+            break;
+        }
+    }
+    value
+}
+
+fn read_i16s(element: &[u8]) -> [i16; 4] {
+    [0, 1, 2, 3].map(|i| i16::from_le_bytes([element[i * 2], element[i * 2 + 1]]))
+}
+
+fn write_i16s(element: &mut [u8], values: [i16; 4]) {
+    for (bytes, value) in element.chunks_exact_mut(2).zip(values) {
+        bytes.copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Round to the nearest integer, halfway cases away from zero.
+fn round(value: f32) -> i32 {
+    (value + if value >= 0.0 { 0.5 } else { -0.5 }) as i32
+}
+
+/// Unfold an octahedral unit vector, the third component holds the encoding of 1.
+fn octahedral([x, y, one]: [f32; 3], max: f32) -> [i32; 3] {
+    let z = one - x.abs() - y.abs();
+    // The lower hemisphere is folded over the diagonals
+    let t = z.min(0.0);
+    let x = x + if x >= 0.0 { t } else { -t };
+    let y = y + if y >= 0.0 { t } else { -t };
+    let scale = max / (x * x + y * y + z * z).sqrt();
+    [round(x * scale), round(y * scale), round(z * scale)]
+}
+
+/// Rebuild a unit quaternion from its three smallest components.
+///
+/// The low 2 bits of `w` hold the index of the largest component and the rest its scale.
+fn quaternion(components: [i16; 3], w: i16) -> [i16; 4] {
+    let scale = std::f32::consts::FRAC_1_SQRT_2 / (w | 3) as f32;
+    let [x, y, z] = components.map(|c| c as f32 * scale);
+    let largest = (1.0 - x * x - y * y - z * z).max(0.0).sqrt();
+
+    let index = (w & 3) as usize;
+    let mut rotation = [0; 4];
+    for (i, value) in [largest, x, y, z].into_iter().enumerate() {
+        rotation[(index + i) & 3] = round(value * i16::MAX as f32) as i16;
+    }
+    rotation
+}
+
+/// Rebuild a float from a 24 bit signed mantissa and an 8 bit signed exponent.
+fn exponential(bits: u32) -> f32 {
+    let mantissa = ((bits << 8) as i32) >> 8;
+    let exponent = (bits as i32) >> 24;
+    f32::from_bits(((exponent + 127) as u32) << 23) * mantissa as f32
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// 18 vertices of 4 u16, `[i * 100, (i % 3) * 7, 60000 - i * i * 37, 0]`, encoded with meshoptimizer.
+    pub(crate) const VERTEX_BUFFER: [u8; 126] = [
+        0xa0, 0x07, 0x00, 0xc8, 0xc8, 0xc8, 0xc8, 0xc8, 0xc8, 0xc8, 0xc8, 0xc8, 0xc8, 0xc8, 0xc8,
+        0xc8, 0xc8, 0xc8, 0xf0, 0x00, 0x00, 0x00, 0xc8, 0xc8, 0x05, 0x02, 0x08, 0x82, 0x20, 0x80,
+        0x00, 0x00, 0x00, 0x06, 0x0e, 0xef, 0xee, 0xfe, 0xef, 0xee, 0xfe, 0xef, 0x1b, 0x1b, 0x1b,
+        0x1b, 0x1b, 0xf0, 0x00, 0x00, 0x00, 0x0e, 0x0e, 0x00, 0x07, 0x00, 0x49, 0xdd, 0x8e, 0x05,
+        0x99, 0xd2, 0x3e, 0x55, 0xe9, 0x82, 0x11, 0xa5, 0xc6, 0x32, 0x61, 0xf0, 0x00, 0x00, 0x00,
+        0xf5, 0x76, 0x06, 0x00, 0x10, 0x13, 0x13, 0x35, 0x55, 0x57, 0x59, 0xf0, 0x00, 0x00, 0x00,
+        0x07, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x60, 0xea, 0x00, 0x00,
+    ];
+
+    /// A grid of 3 by 3 quads and a triangle of its corners, encoded with meshoptimizer.
+    pub(crate) const INDEX_BUFFER: [u8; 51] = [
+        0xe0, 0xfe, 0x1f, 0x00, 0x1f, 0x00, 0x1f, 0x9f, 0x0f, 0x04, 0x1f, 0x03, 0x1f, 0x9f, 0x0f,
+        0x03, 0x1f, 0x03, 0x1f, 0xff, 0xf0, 0x08, 0x02, 0x02, 0x02, 0x02, 0x02, 0x02, 0x02, 0x02,
+        0x02, 0x02, 0x02, 0xa4, 0x00, 0x00, 0x76, 0x87, 0x56, 0x67, 0x78, 0xa9, 0x86, 0x65, 0x89,
+        0x68, 0x98, 0x01, 0x69, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_decode_vertex_buffer() {
+        let data = VERTEX_BUFFER;
+        let expected = (0..18u16)
+            .flat_map(|i| [i * 100, (i % 3) * 7, 60000 - i * i * 37, 0])
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+
+        assert_eq!(decode_vertex_buffer(&data, 18, 8).unwrap(), expected);
+        assert!(decode_vertex_buffer(&data[..data.len() - 1], 18, 8).is_err());
+        assert!(decode_vertex_buffer(&data, 18, 6).is_err());
+    }
+
+    #[test]
+    fn test_decode_index_buffer() {
+        let data = INDEX_BUFFER;
+        let mut expected = Vec::new();
+        for y in 0..3 {
+            for x in 0..3 {
+                let a = y * 4 + x;
+                expected.extend([a, a + 4, a + 1, a + 1, a + 4, a + 5]);
+            }
+        }
+        expected.extend([15, 3, 12]);
+
+        let indices = decode_index_buffer(&data, expected.len()).unwrap();
+        // The encoder may rotate the triangles
+        for (triangle, expected) in indices.chunks(3).zip(expected.chunks(3)) {
+            let mut triangle = triangle.to_vec();
+            let start = triangle.iter().position(|v| *v == expected[0]).unwrap();
+            triangle.rotate_left(start);
+            assert_eq!(triangle, expected);
+        }
+        assert!(decode_index_buffer(&data[..data.len() - 1], expected.len()).is_err());
+    }
+
+    #[test]
+    fn test_decode_index_buffer_version_1() {
+        let table = [
+            0x00, 0x76, 0x87, 0x56, 0x67, 0x78, 0xa9, 0x86, 0x65, 0x89, 0x68, 0x98, 0x01, 0x69, 0,
+            0,
+        ];
+        // Three new vertices, an edge and a new vertex, two free vertices, the last one minus one
+        let mut data = vec![0xe1, 0xf0, 0x10, 0xfe, 0x0d, 0xff, 18, 1];
+        data.extend(table);
+
+        assert_eq!(
+            decode_index_buffer(&data, 12).unwrap(),
+            [0, 1, 2, 2, 1, 3, 4, 9, 8, 4, 8, 7]
+        );
+        data[0] = 0xe2;
+        assert!(decode_index_buffer(&data, 12).is_err());
+    }
+
+    #[test]
+    fn test_decode_index_sequence() {
+        let data = [0xd1, 20, 4, 4, 145, 3, 5, 4, 0, 0, 0, 0];
+
+        assert_eq!(
+            decode_index_sequence(&data, 6).unwrap(),
+            [5, 6, 7, 100, 101, 8]
+        );
+        assert_eq!(
+            decode(&data, 6, 2, Mode::Indices, Filter::None).unwrap(),
+            [5, 0, 6, 0, 7, 0, 100, 0, 101, 0, 8, 0]
+        );
+    }
+
+    #[test]
+    fn test_filters() {
+        let mut floats = [0xff000003u32, 0x02fffffe]
+            .into_iter()
+            .flat_map(u32::to_le_bytes)
+            .collect::<Vec<_>>();
+        Filter::Exponential.apply(&mut floats, 8).unwrap();
+        assert_eq!(floats[..4], 1.5f32.to_le_bytes());
+        assert_eq!(floats[4..], (-8.0f32).to_le_bytes());
+
+        // +Z, +X and -Z
+        let mut normals = [0, 0, 127, 0, 127, 0, 127, 0, 127, 127, 127, 0];
+        Filter::Octahedral.apply(&mut normals, 4).unwrap();
+        assert_eq!(
+            normals,
+            [0, 0, 127, 0, 127, 0, 0, 0, 0, 0, (-127i8) as u8, 0]
+        );
+
+        // A quarter turn around X, the largest component is the first one
+        let mut rotation = [0i16, 0, i16::MAX, 0x7ffc]
+            .into_iter()
+            .flat_map(i16::to_le_bytes)
+            .collect::<Vec<_>>();
+        Filter::Quaternion.apply(&mut rotation, 8).unwrap();
+        assert_eq!(read_i16s(&rotation), [23170, 0, 0, 23170]);
+
+        assert!(Filter::Quaternion.apply(&mut normals, 4).is_err());
+    }
+}
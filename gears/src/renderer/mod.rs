@@ -1,24 +1,76 @@
+pub mod atlas;
+pub(crate) mod buffer;
 pub mod camera;
+#[cfg(feature = "crosshair")]
+pub mod crosshair;
+#[cfg(feature = "crowds")]
+pub mod crowd;
+#[cfg(feature = "decals")]
+pub mod decals;
+pub(crate) mod draw_list;
+pub(crate) mod gltf_loader;
+pub mod headless;
+mod init;
 pub mod instance;
 pub mod light;
+pub mod mesh_optimizer;
+pub mod meshopt_codec;
 pub mod model;
+pub mod overrides;
+#[cfg(feature = "particles")]
+pub mod particles;
+pub mod pipelines;
+pub(crate) mod portal;
+pub mod post;
+pub(crate) mod preprocess;
+pub mod recorder;
 pub mod resources;
+pub mod shadow;
+pub(crate) mod split;
+pub(crate) mod streaming;
 pub mod texture;
+pub mod thumbnail;
 pub mod traits;
-
+#[cfg(feature = "ui")]
+mod ui;
+#[cfg(feature = "wireframe")]
+pub(crate) mod wireframe;
+
+use crate::core::checksum::StableHasher;
+use crate::core::config::{
+    Backend, DisplayConfig, LightCullingConfig, RecordingConfig, RenderPassesConfig,
+    RendererConfig, RuntimeConfig, TextureAtlasConfig,
+};
+use crate::core::event::{ClipboardText, CursorIcon, FileDrop, WindowCommand};
+use crate::core::pacing::{self, DisplayInfo, FramePacer, RefreshRateChanged};
+use crate::core::schedule::ScheduleInfo;
+use crate::core::telemetry;
+use crate::core::vfs::AssetLoadFailed;
 use crate::core::Dt;
-use crate::ecs::components::{Flip, Name, Scale};
+use crate::core::{clock, crash};
+use crate::ecs::components::Scale;
 use crate::ecs::{self, components};
+use crate::editor::camera::{self as editor_camera, CameraCommand};
+use crate::editor::play;
+use crate::gameplay::cinematic::CutscenePlayer;
+use crate::gameplay::interaction::InteractionController;
+use crate::gui::layout;
+use crate::gui::stats_overlay::FrameStats;
+#[cfg(feature = "ui")]
 use crate::gui::EguiRenderer;
+#[cfg(feature = "gamepad")]
+use crate::input::gamepad::GamepadPoller;
+use crate::input::gamepad::GamepadState;
+use crate::input::players::LocalPlayers;
+use crate::physics::spatial::SpatialIndex;
 use cgmath::prelude::*;
 use cgmath::*;
-use egui_wgpu::ScreenDescriptor;
 use instant::Duration;
 use log::{info, warn};
-use model::{DrawModel, Vertex};
-use std::f32::consts::FRAC_PI_2;
+use model::DrawModel;
+use std::hash::Hasher;
 use std::num::NonZero;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use std::{any, iter};
 use tokio::sync::{broadcast, Mutex as TokioMutex};
 use wgpu::util::DeviceExt;
@@ -32,35 +84,135 @@ use winit::{
     window::Window,
 };
 
-#[rustfmt::skip]
-const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
-    1.0, 0.0, 0.0, 0.0,
-    0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.5,
-    0.0, 0.0, 0.0, 1.0,
-);
-const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
+const REQUIRED_FEATURES: wgpu::Features = wgpu::Features::BUFFER_BINDING_ARRAY;
+
+/// Get the device features of the optional passes the adapter supports, the passes without them are skipped.
+fn optional_features(adapter: &wgpu::Adapter) -> wgpu::Features {
+    #[allow(unused_mut)]
+    let mut features = wgpu::Features::empty();
+    #[cfg(feature = "wireframe")]
+    {
+        features |= wireframe::FEATURES;
+    }
+    adapter.features() & features
+}
+
+/// Get the wgpu backends of a backend, `Backend::Auto` tries the native ones of the platform.
+fn wgpu_backends(backend: Backend) -> wgpu::Backends {
+    match backend {
+        Backend::Auto if cfg!(windows) => wgpu::Backends::DX12 | wgpu::Backends::VULKAN,
+        Backend::Auto if cfg!(any(target_os = "macos", target_os = "ios")) => wgpu::Backends::METAL,
+        Backend::Auto if cfg!(target_arch = "wasm32") => {
+            wgpu::Backends::BROWSER_WEBGPU | wgpu::Backends::GL
+        }
+        Backend::Auto => wgpu::Backends::VULKAN | wgpu::Backends::GL,
+        Backend::Vulkan => wgpu::Backends::VULKAN,
+        Backend::Dx12 => wgpu::Backends::DX12,
+        Backend::Metal => wgpu::Backends::METAL,
+        Backend::Gl => wgpu::Backends::GL,
+    }
+}
+
+/// Get the winit cursor of a cursor icon.
+fn winit_cursor(icon: CursorIcon) -> winit::window::CursorIcon {
+    match icon {
+        CursorIcon::Default => winit::window::CursorIcon::Default,
+        CursorIcon::Pointer => winit::window::CursorIcon::Pointer,
+        CursorIcon::Crosshair => winit::window::CursorIcon::Crosshair,
+        CursorIcon::Text => winit::window::CursorIcon::Text,
+        CursorIcon::Move => winit::window::CursorIcon::Move,
+        CursorIcon::Grab => winit::window::CursorIcon::Grab,
+        CursorIcon::Grabbing => winit::window::CursorIcon::Grabbing,
+        CursorIcon::Wait => winit::window::CursorIcon::Wait,
+        CursorIcon::NotAllowed => winit::window::CursorIcon::NotAllowed,
+    }
+}
+
+/// Log the optional render passes which are turned off by the config or left out of the build.
+fn log_render_passes(render_passes: RenderPassesConfig) {
+    let passes = [
+        (
+            "particles",
+            render_passes.particles,
+            cfg!(feature = "particles"),
+        ),
+        ("crowds", render_passes.crowds, cfg!(feature = "crowds")),
+        ("decals", render_passes.decals, cfg!(feature = "decals")),
+        ("hdr", render_passes.hdr, cfg!(feature = "hdr")),
+        (
+            "crosshair",
+            render_passes.crosshair,
+            cfg!(feature = "crosshair"),
+        ),
+        (
+            "wireframe",
+            render_passes.wireframe,
+            cfg!(feature = "wireframe"),
+        ),
+        ("ui", render_passes.ui, cfg!(feature = "ui")),
+    ];
+    for (name, enabled, compiled) in passes {
+        if enabled && !compiled {
+            log::warn!(
+                "[State] The {} pass is enabled but the {} feature is not, it is skipped",
+                name,
+                name
+            );
+        } else if !enabled {
+            log::info!("[State] The {} pass is disabled", name);
+        }
+    }
+}
+
+/// Log the assets of an entity which failed to load and pass them to the systems.
+fn report_load_failures(
+    ecs: &ecs::Manager,
+    entity: ecs::Entity,
+    failures: Vec<resources::LoadFailure>,
+) {
+    for failure in failures {
+        log::error!(
+            "[Renderer] {:#}, a placeholder is used for {}",
+            failure.error,
+            failure.path
+        );
+        ecs.send_event(AssetLoadFailed {
+            entity: Some(entity),
+            path: failure.path,
+            error: format!("{:#}", failure.error),
+        });
+    }
+}
 
 /// The main event loop of the application
 ///
 /// # Returns
 ///
 /// A future which can be awaited.
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
     ecs: Arc<Mutex<ecs::Manager>>,
     tx_dt: broadcast::Sender<Dt>,
     egui_windows: Option<Vec<Box<dyn FnMut(&egui::Context)>>>,
+    settings: RendererConfig,
 ) -> anyhow::Result<()> {
     // * Window creation
     let event_loop = EventLoop::new()?;
-    let window_attributes = WindowAttributes::default()
-        .with_title("Winit window")
+    let mut window_attributes = WindowAttributes::default()
+        .with_title(settings.window.title.clone())
         .with_transparent(true)
         .with_window_icon(None);
+    if let Some((width, height)) = settings.window.size {
+        window_attributes =
+            window_attributes.with_inner_size(winit::dpi::PhysicalSize::new(width, height));
+    }
 
     let window = event_loop.create_window(window_attributes)?;
-    let mut state = State::new(&window, ecs).await;
+    let mut state = State::new(&window, ecs, &settings).await;
     state.init_components().await?;
+    if let Some(recording) = settings.recording {
+        state.start_recording(recording);
+    }
 
     if let Some(egui_windows) = egui_windows {
         state.egui_windows = egui_windows;
@@ -88,6 +240,11 @@ pub async fn run(
                 } => if state.mouse_pressed {
                     state.camera_controller.process_mouse(delta.0, delta.1)
                 },
+                // The wheel is read from the device while dragging, the cursor may leave the window then
+                Event::DeviceEvent {
+                    event: DeviceEvent::MouseWheel { delta },
+                    ..
+                } if state.mouse_pressed => state.camera_controller.process_scroll(&delta),
                 Event::WindowEvent {
                     ref event,
                     window_id,
@@ -102,7 +259,14 @@ pub async fn run(
                                     ..
                                 },
                             ..
-                        } => ewlt.exit(),
+                        } => {
+                            state.stop_recording();
+                            ewlt.exit()
+                        }
+                        // The window may have moved to a monitor with another refresh rate
+                        WindowEvent::Moved(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                            state.detect_refresh_rate();
+                        }
                         WindowEvent::Resized(physical_size) => {
                             state.resize(*physical_size);
                         }
@@ -111,7 +275,13 @@ pub async fn run(
                         // }
                         WindowEvent::RedrawRequested => {
                             let now = instant::Instant::now();
-                            let dt = now - last_render_time;
+                            state.frame_pacer.frame_started(now);
+                            crash::frame_started();
+                            // While recording the simulation runs at the frame rate of the video
+                            let dt = state
+                                .recorder
+                                .as_ref()
+                                .map_or(now - last_render_time, |recorder| recorder.frame_time());
                             last_render_time = now;
 
                             info!(
@@ -120,14 +290,28 @@ pub async fn run(
                                 &dt.as_millis()
                             );
 
-                            // Send the delta time using the broadcast channel
-                            if let Err(e) = tx_dt.send(dt) {
-                                log::warn!("Failed to send delta time: {:?}", e);
+                            // Send the delta time using the broadcast channel,
+                            // a paused simulation clock holds the update loops while rendering goes on
+                            let sim_dt = clock::simulation_dt(&state.ecs.lock().unwrap(), dt);
+                            if let Some(sim_dt) = sim_dt {
+                                if let Err(e) = tx_dt.send(sim_dt) {
+                                    log::warn!("Failed to send delta time: {:?}", e);
+                                }
                             }
 
-                            futures::executor::block_on(state.update(dt));
+                            telemetry::timed("renderer.update", || {
+                                futures::executor::block_on(state.update(dt))
+                            });
+                            if telemetry::telemetry().is_enabled() {
+                                let entity_count = state.ecs.lock().unwrap().entity_count();
+                                telemetry::telemetry().record_frame(
+                                    dt,
+                                    entity_count,
+                                    state.gpu_memory_estimate(),
+                                );
+                            }
 
-                            match state.render() {
+                            match telemetry::timed("renderer.render", || state.render()) {
                                 Ok(_) => {}
                                 // Reconfigure the surface if it's lost or outdated
                                 Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
@@ -143,8 +327,16 @@ pub async fn run(
                     };
                 }
                 Event::AboutToWait => {
-                    // RedrawRequested will only trigger once unless manually requested.
-                    state.window().request_redraw();
+                    // Wait for the next paced frame, RedrawRequested will only trigger once unless manually requested.
+                    match state.frame_pacer.deadline() {
+                        Some(deadline) if instant::Instant::now() < deadline => {
+                            ewlt.set_control_flow(ControlFlow::WaitUntil(deadline));
+                        }
+                        _ => {
+                            ewlt.set_control_flow(ControlFlow::Poll);
+                            state.window().request_redraw();
+                        }
+                    }
                 }
                 _ => {}
             }
@@ -154,42 +346,105 @@ pub async fn run(
     Ok(())
 }
 
-struct State<'a> {
+/// The window the frames are presented to, with its surface and UI.
+struct WindowTarget<'a> {
+    window: &'a Window,
     surface: wgpu::Surface<'a>,
+    /// The UI renderer, `None` if the UI is disabled in the config.
+    #[cfg(feature = "ui")]
+    egui_renderer: Option<EguiRenderer>,
+}
+
+struct State<'a> {
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
-    render_pipeline: wgpu::RenderPipeline,
+    model_pipelines: pipelines::ModelPipelines,
     camera: camera::Camera,
     camera_projection: camera::Projection,
+    default_fovy: Rad<f32>,
+    letterbox: bool,
     camera_controller: camera::CameraController,
+    /// The entity the camera follows.
+    camera_follow: Option<ecs::Entity>,
     camera_uniform: camera::CameraUniform,
     camera_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
+    /// The cameras of the local players when the frame is split between them.
+    player_views: split::PlayerViews,
+    /// The views through the teleporters with a `PortalView`, drawn before the frame.
+    portals: portal::Portals,
+    /// The keyboards in the order they were first used, their index routes the keys to the local players.
+    keyboards: Vec<event::DeviceId>,
     light_entities: Option<Vec<ecs::Entity>>,
-    light_buffer: wgpu::Buffer,
-    light_bind_group: wgpu::BindGroup,
+    light_culling: light::LightCulling,
+    light_culling_config: LightCullingConfig,
+    light_buffers: light::LightBuffers,
+    shadows: shadow::ShadowMaps,
+    /// The materials of the texture and the color overrides of the models.
+    override_materials: overrides::OverrideMaterials,
     model_entities: Option<Vec<ecs::Entity>>,
     texture_bind_group_layout: wgpu::BindGroupLayout,
     light_bind_group_layout: wgpu::BindGroupLayout,
     depth_texture: texture::Texture,
-    window: &'a Window,
+    post_process: post::PostProcess,
+    /// The optional passes, `None` if disabled in the config.
+    #[cfg(feature = "particles")]
+    particles: Option<particles::ParticleSystem>,
+    #[cfg(feature = "crowds")]
+    crowds: Option<crowd::CrowdRenderer>,
+    #[cfg(feature = "decals")]
+    decals: Option<decals::DecalRenderer>,
+    #[cfg(feature = "crosshair")]
+    crosshair: Option<crosshair::CrosshairRenderer>,
+    #[cfg(feature = "wireframe")]
+    wireframe: Option<wireframe::WireframeRenderer>,
+    /// Whether the crosshair and the wireframe are shown, toggled with the `RuntimeConfig`.
+    #[cfg(feature = "crosshair")]
+    show_crosshair: bool,
+    #[cfg(feature = "wireframe")]
+    show_wireframe: bool,
+    /// The window, `None` when rendering headless.
+    window: Option<WindowTarget<'a>>,
+    /// The texture the frames are rendered into when headless.
+    offscreen: Option<wgpu::Texture>,
     ecs: Arc<Mutex<ecs::Manager>>,
     mouse_pressed: bool,
     draw_colliders: bool,
-    egui_renderer: EguiRenderer,
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
     egui_windows: Vec<Box<dyn FnMut(&egui::Context)>>,
+    interaction: InteractionController,
+    /// Polls the gamepads into the `GamepadState` resource, `None` when headless.
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<GamepadPoller>,
+    display: DisplayConfig,
+    display_entity: Option<ecs::Entity>,
+    frame_pacer: FramePacer,
+    recorder: Option<recorder::FrameRecorder>,
+    texture_streamer: Option<streaming::TextureStreamer>,
+    texture_atlas: Option<TextureAtlasConfig>,
+    #[cfg(feature = "ui")]
+    thumbnails: Option<thumbnail::Thumbnails>,
+    stats_overlay: bool,
+    frame_stats: FrameStats,
 }
 
 impl<'a> State<'a> {
-    async fn new(window: &'a Window, ecs: Arc<Mutex<ecs::Manager>>) -> State<'a> {
+    #[allow(clippy::too_many_arguments)]
+    async fn new(
+        window: &'a Window,
+        ecs: Arc<Mutex<ecs::Manager>>,
+        settings: &RendererConfig,
+    ) -> State<'a> {
+        let backend = settings.backend;
         log::warn!("[State] Setup starting...");
         let size = window.inner_size();
 
-        // The instance is a handle to the GPU. BackendBit::PRIMARY => Vulkan + Metal + DX12 + Browser WebGPU.
+        // The instance is a handle to the GPU
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
+            backends: wgpu_backends(backend),
             ..Default::default()
         });
         let surface = instance.create_surface(window).unwrap();
@@ -202,15 +457,19 @@ impl<'a> State<'a> {
                 force_fallback_adapter: false,
             })
             .await
-            .unwrap();
+            .unwrap_or_else(|| panic!("No GPU adapter found for the {:?} backend", backend));
+        let adapter_info = adapter.get_info();
+        info!(
+            "[State] Using {} with {:?}",
+            adapter_info.name, adapter_info.backend
+        );
 
         log::warn!("[State] Device and Queue");
-        let required_features = wgpu::Features::BUFFER_BINDING_ARRAY;
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    required_features,
+                    required_features: REQUIRED_FEATURES | optional_features(&adapter),
                     required_limits: wgpu::Limits::default(),
                     memory_hints: Default::default(),
                 },
@@ -227,16 +486,61 @@ impl<'a> State<'a> {
             .copied()
             .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
+        // The frames are copied out of the surface when recording
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC),
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            present_mode: if settings.display.vsync {
+                wgpu::PresentMode::AutoVsync
+            } else {
+                wgpu::PresentMode::AutoNoVsync
+            },
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
         };
+        #[cfg(feature = "ui")]
+        let egui_renderer = settings
+            .render_passes
+            .ui
+            .then(|| EguiRenderer::new(&device, surface_format, None, 1, window));
+
+        Self::with_device(
+            device,
+            queue,
+            config,
+            ecs,
+            settings,
+            Some(WindowTarget {
+                window,
+                surface,
+                #[cfg(feature = "ui")]
+                egui_renderer,
+            }),
+        )
+    }
+
+    /// Set up the renderer on a device, presenting to a window or headless.
+    fn with_device(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+        ecs: Arc<Mutex<ecs::Manager>>,
+        settings: &RendererConfig,
+        window: Option<WindowTarget<'a>>,
+    ) -> State<'a> {
+        let RendererConfig {
+            display,
+            texture_streaming,
+            texture_atlas,
+            light_culling,
+            render_passes,
+            ..
+        } = *settings;
+        let size = winit::dpi::PhysicalSize::new(config.width, config.height);
 
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -261,20 +565,7 @@ impl<'a> State<'a> {
                 label: Some("texture_bind_group_layout"),
             });
 
-        let light_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: Some("light_bind_group_layout"),
-            });
+        let light_bind_group_layout = light::LightBuffers::bind_group_layout(&device);
 
         let camera_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -295,27 +586,22 @@ impl<'a> State<'a> {
         // ! CAMERA COMPONENT
         let (state_camera, state_camera_controller) = Self::init_camera(Arc::clone(&ecs));
 
-        let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Light Buffer"),
-            contents: &[0; std::mem::size_of::<light::LightData>()], // ! Initialize the buffer for the maximum number of lights
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &light_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: light_buffer.as_entire_binding(),
-            }],
-            label: Some("light_bind_group"),
-        });
+        // ! The light buffers grow with the lights of the scene
+        let light_buffers = light::LightBuffers::new(&device, &light_bind_group_layout);
         // ! MODELS -> init_models()
         // * INITIALIZING STATE COMPONENTS
 
         /* CAMERA */
-        let camera_projection =
-            camera::Projection::new(config.width, config.height, cgmath::Deg(45.0), 0.1, 100.0);
-        let camera_uniform = camera::CameraUniform::new();
+        let lens = Self::update_camera_lens(&ecs.lock().unwrap(), 0.0);
+        let camera_projection = camera::Projection::new(
+            config.width,
+            config.height,
+            lens.fov(),
+            lens.znear(),
+            lens.zfar(),
+        );
+        let mut camera_uniform = camera::CameraUniform::new();
+        camera_uniform.update_view_proj(&state_camera, &camera_projection);
 
         let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Camera Buffer"),
@@ -336,29 +622,76 @@ impl<'a> State<'a> {
 
         let depth_texture =
             texture::Texture::create_depth_texture(&device, &config, "depth_texture");
+        // ! The scene is rendered into a floating point target and tone mapped by the post-process pass with HDR
+        let scene_format = if cfg!(feature = "hdr") && render_passes.hdr {
+            post::HDR_FORMAT
+        } else {
+            config.format
+        };
+        let post_process =
+            post::PostProcess::new(&device, &config, scene_format, &depth_texture.view);
+        #[cfg(feature = "particles")]
+        let particles = render_passes.particles.then(|| {
+            particles::ParticleSystem::new(
+                &device,
+                &queue,
+                &texture_bind_group_layout,
+                scene_format,
+                post::VELOCITY_FORMAT,
+                texture::Texture::DEPTH_FORMAT,
+                &depth_texture.view,
+            )
+        });
+        #[cfg(feature = "crowds")]
+        let crowds = render_passes.crowds.then(|| {
+            crowd::CrowdRenderer::new(
+                &device,
+                &queue,
+                &camera_bind_group_layout,
+                &light_bind_group_layout,
+                &texture_bind_group_layout,
+                scene_format,
+                post::VELOCITY_FORMAT,
+            )
+        });
+        #[cfg(feature = "decals")]
+        let decals = render_passes.decals.then(|| {
+            decals::DecalRenderer::new(
+                &device,
+                scene_format,
+                &texture_bind_group_layout,
+                &depth_texture.view,
+            )
+        });
+        // ! The crosshair is drawn into the frame after the post-process pass
+        #[cfg(feature = "crosshair")]
+        let crosshair = render_passes
+            .crosshair
+            .then(|| crosshair::CrosshairRenderer::new(&device, config.format));
+        #[cfg(feature = "wireframe")]
+        let wireframe = render_passes.wireframe.then(|| {
+            let wireframe =
+                wireframe::WireframeRenderer::new(&device, &camera_bind_group_layout, scene_format);
+            if wireframe.is_none() {
+                log::warn!("[State] The GPU cannot draw lines, the wireframe pass is skipped");
+            }
+            wireframe
+        });
+        log_render_passes(render_passes);
 
-        let render_pipeline = {
+        let shadows = shadow::ShadowMaps::new(&device);
+        let model_pipelines = {
             let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
                 bind_group_layouts: &[
                     &texture_bind_group_layout,
                     &camera_bind_group_layout,
                     &light_bind_group_layout,
+                    &shadows.bind_group_layout,
                 ],
                 push_constant_ranges: &[],
             });
-            let shader = wgpu::ShaderModuleDescriptor {
-                label: Some("Normal Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
-            };
-            Self::create_render_pipeline(
-                &device,
-                &layout,
-                config.format,
-                Some(texture::Texture::DEPTH_FORMAT),
-                &[model::ModelVertex::desc(), instance::InstanceRaw::desc()],
-                shader,
-            )
+            pipelines::ModelPipelines::new(&device, layout, scene_format)
         };
 
         // let light_render_pipeline = {
@@ -381,35 +714,82 @@ impl<'a> State<'a> {
         //     )
         // };
 
-        let egui_renderer = EguiRenderer::new(&device, surface_format, None, 1, window);
         let egui_windows = vec![];
 
+        // ! GAMEPADS, the headless frames do not depend on them
+        {
+            let ecs_lock = ecs.lock().unwrap();
+            ecs_lock.insert_resource(model_pipelines.progress());
+            if !ecs_lock.has_resource::<GamepadState>() {
+                ecs_lock.insert_resource(GamepadState::default());
+            }
+        }
+        #[cfg(feature = "gamepad")]
+        let gamepads = window.as_ref().and_then(|_| GamepadPoller::new());
+
         Self {
-            surface,
             device,
             queue,
             config,
             size,
-            render_pipeline,
+            model_pipelines,
             camera: state_camera,
+            default_fovy: camera_projection.fovy(),
+            letterbox: false,
             camera_projection,
             texture_bind_group_layout,
             camera_controller: state_camera_controller,
+            camera_follow: None,
             camera_buffer,
             camera_bind_group,
+            camera_bind_group_layout,
+            player_views: split::PlayerViews::default(),
+            portals: portal::Portals::default(),
+            keyboards: Vec::new(),
             camera_uniform,
             light_entities: None,
-            light_buffer,
-            light_bind_group,
+            light_culling: light::LightCulling::default(),
+            light_culling_config: light_culling,
+            light_buffers,
+            shadows,
+            override_materials: overrides::OverrideMaterials::default(),
             model_entities: None,
             light_bind_group_layout,
             depth_texture,
+            post_process,
+            #[cfg(feature = "particles")]
+            particles,
+            #[cfg(feature = "crowds")]
+            crowds,
+            #[cfg(feature = "decals")]
+            decals,
+            #[cfg(feature = "crosshair")]
+            crosshair,
+            #[cfg(feature = "wireframe")]
+            wireframe: wireframe.flatten(),
+            #[cfg(feature = "crosshair")]
+            show_crosshair: false,
+            #[cfg(feature = "wireframe")]
+            show_wireframe: false,
             window,
+            offscreen: None,
             ecs,
             mouse_pressed: false,
             draw_colliders: true,
-            egui_renderer,
             egui_windows,
+            interaction: InteractionController::new(KeyCode::KeyE),
+            #[cfg(feature = "gamepad")]
+            gamepads,
+            display,
+            display_entity: None,
+            frame_pacer: FramePacer::default(),
+            recorder: None,
+            texture_streamer: texture_streaming.map(streaming::TextureStreamer::new),
+            texture_atlas,
+            #[cfg(feature = "ui")]
+            thumbnails: None,
+            stats_overlay: false,
+            frame_stats: FrameStats::default(),
         }
     }
 
@@ -435,14 +815,22 @@ impl<'a> State<'a> {
             fragment: Some(wgpu::FragmentState {
                 module: &shader,
                 entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: color_format,
-                    blend: Some(wgpu::BlendState {
-                        alpha: wgpu::BlendComponent::REPLACE,
-                        color: wgpu::BlendComponent::REPLACE,
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState {
+                            alpha: wgpu::BlendComponent::REPLACE,
+                            color: wgpu::BlendComponent::REPLACE,
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    // The screen-space velocity for the motion blur
+                    Some(wgpu::ColorTargetState {
+                        format: post::VELOCITY_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
                     }),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
+                ],
                 compilation_options: Default::default(),
             }),
             primitive: wgpu::PrimitiveState {
@@ -474,264 +862,168 @@ impl<'a> State<'a> {
         })
     }
 
-    async fn init_components(&mut self) -> anyhow::Result<()> {
-        self.init_lights().await;
-        self.init_models().await;
-
-        Ok(())
-    }
-
-    fn init_camera(ecs: Arc<Mutex<ecs::Manager>>) -> (camera::Camera, camera::CameraController) {
-        let ecs_lock = ecs.lock().unwrap();
-        let mut camera_entity = ecs_lock.get_entites_with_component::<components::Camera>();
-        assert!(
-            camera_entity.len() <= 1,
-            "There should be only one camera entity"
-        );
-
-        // If there is no camera entity provide a default implementation
-        if camera_entity.is_empty() {
-            let camera =
-                camera::Camera::new((0.0, 5.0, 10.0), cgmath::Deg(-90.0), cgmath::Deg(-20.0));
-            let controller = camera::CameraController::new(0.5, 0.2);
-
-            return (camera, controller);
-        }
-
-        let camera_entity = camera_entity.pop().unwrap();
-
-        let camera_pos = ecs_lock
-            .get_component_from_entity::<components::Pos3>(camera_entity)
-            .expect("No position provided for the camera!");
-        let camera = ecs_lock
-            .get_component_from_entity::<components::Camera>(camera_entity)
-            .expect("No camera component provided for the camera!");
-
-        let camera_pos = camera_pos.read().unwrap();
-        let camera = camera.read().unwrap();
-
-        match *camera {
-            components::Camera::FPS {
-                look_at,
-                speed,
-                sensitivity,
-            } => {
-                let pos_point = cgmath::Point3::from_vec(camera_pos.pos);
-                let look_at_point = look_at;
-                let camera = camera::Camera::new_look_at(pos_point, look_at_point);
-                let controller = camera::CameraController::new(speed, sensitivity);
-
-                (camera, controller)
+    /// Apply the display settings of the `RuntimeConfig` if they changed.
+    fn apply_runtime_config(&mut self) {
+        let runtime = {
+            let ecs_lock = self.ecs.lock().unwrap();
+            let Some((entity, runtime)) =
+                ecs_lock.get_all_components_of_type::<RuntimeConfig>().pop()
+            else {
+                return;
+            };
+            let mut runtime = runtime.write().unwrap();
+            if runtime.display.vsync != self.display.vsync {
+                warn!("[Renderer] The vsync can only be set at startup");
+                runtime.display.vsync = self.display.vsync;
             }
-            components::Camera::Fixed { look_at } => {
-                let pos_point = cgmath::Point3::from_vec(camera_pos.pos);
-                let look_at_point = look_at;
-                let camera = camera::Camera::new_look_at(pos_point, look_at_point);
-                let controller = camera::CameraController::new(0.0, 0.0);
-
-                (camera, controller)
+            self.camera_controller
+                .set_scroll_sensitivity(runtime.scroll_sensitivity);
+            #[cfg(feature = "crosshair")]
+            {
+                self.show_crosshair = runtime.crosshair;
             }
+            #[cfg(feature = "wireframe")]
+            {
+                self.show_wireframe = runtime.wireframe;
+            }
+            // The schedule reports the timings of the systems to the overlay
+            self.stats_overlay = runtime.stats_overlay;
+            if self.stats_overlay
+                && ecs_lock
+                    .get_component_from_entity::<ScheduleInfo>(entity)
+                    .is_none()
+            {
+                ecs_lock.add_component_to_entity(entity, ScheduleInfo::default());
+            }
+            runtime.display
+        };
+        if runtime != self.display {
+            self.display = runtime;
+            // The frame pacing may have changed
+            self.detect_refresh_rate();
         }
     }
 
-    async fn init_lights(&mut self) {
+    /// Detect the refresh rate of the monitor the window is on and pace the frames to it.
+    /// The detected rate is exposed to the systems with the `DisplayInfo` component.
+    fn detect_refresh_rate(&mut self) {
+        let refresh_rate = self
+            .window
+            .as_ref()
+            .and_then(|target| target.window.current_monitor())
+            .and_then(|monitor| monitor.refresh_rate_millihertz())
+            .map(|millihertz| millihertz as f32 / 1000.0);
+        let info = DisplayInfo {
+            refresh_rate,
+            target_frame_rate: pacing::target_frame_rate(
+                self.display.frame_pacing,
+                refresh_rate,
+                self.display.vsync,
+            ),
+            vsync: self.display.vsync,
+        };
+
         let ecs_lock = self.ecs.lock().unwrap();
-        let light_entities = ecs_lock.get_entites_with_component::<components::Light>();
-
-        for entity in light_entities.iter() {
-            let pos = ecs_lock
-                .get_component_from_entity::<components::Pos3>(*entity)
-                .expect("No position provided for the light!");
-
-            let light = ecs_lock
-                .get_component_from_entity::<components::Light>(*entity)
-                .unwrap();
-
-            let light_uniform = {
-                let rlock_pos = pos.read().unwrap();
-                let rlock_light = light.read().unwrap();
-
-                match *rlock_light {
-                    components::Light::Point { radius, intensity } => light::LightUniform {
-                        position: [rlock_pos.pos.x, rlock_pos.pos.y, rlock_pos.pos.z],
-                        light_type: light::LightType::Point as u32,
-                        color: [1.0, 1.0, 1.0],
-                        radius,
-                        direction: [0.0; 3],
-                        intensity,
-                    },
-                    components::Light::PointColoured {
-                        radius,
-                        color,
-                        intensity,
-                    } => light::LightUniform {
-                        position: [rlock_pos.pos.x, rlock_pos.pos.y, rlock_pos.pos.z],
-                        light_type: light::LightType::Point as u32,
-                        color,
-                        radius,
-                        direction: [0.0; 3],
-                        intensity,
-                    },
-                    components::Light::Ambient { intensity } => light::LightUniform {
-                        position: [rlock_pos.pos.x, rlock_pos.pos.y, rlock_pos.pos.z],
-                        light_type: light::LightType::Ambient as u32,
-                        color: [1.0, 1.0, 1.0],
-                        radius: 0.0,
-                        direction: [0.0; 3],
-                        intensity,
-                    },
-                    components::Light::AmbientColoured { color, intensity } => {
-                        light::LightUniform {
-                            position: [rlock_pos.pos.x, rlock_pos.pos.y, rlock_pos.pos.z],
-                            light_type: light::LightType::Ambient as u32,
-                            color,
-                            radius: 0.0,
-                            direction: [0.0; 3],
-                            intensity,
-                        }
-                    }
-                    components::Light::Directional {
-                        direction,
-                        intensity,
-                    } => light::LightUniform {
-                        position: [rlock_pos.pos.x, rlock_pos.pos.y, rlock_pos.pos.z],
-                        light_type: light::LightType::Directional as u32,
-                        color: [1.0, 1.0, 1.0],
-                        radius: 0.0,
-                        direction,
-                        intensity,
-                    },
-                    components::Light::DirectionalColoured {
-                        direction,
-                        color,
-                        intensity,
-                    } => light::LightUniform {
-                        position: [rlock_pos.pos.x, rlock_pos.pos.y, rlock_pos.pos.z],
-                        light_type: light::LightType::Directional as u32,
-                        color,
-                        radius: 0.0,
-                        direction,
-                        intensity,
-                    },
-                }
-            };
-            ecs_lock.add_component_to_entity(*entity, light_uniform);
+        let entity = *self
+            .display_entity
+            .get_or_insert_with(|| ecs_lock.create_entity());
+        let previous = ecs_lock
+            .get_component_from_entity::<DisplayInfo>(entity)
+            .map(|info| *info.read().unwrap());
+        if previous == Some(info) {
+            return;
         }
 
-        if light_entities.len() > light::NUM_MAX_LIGHTS as usize {
-            panic!("The number of lights exceeds the maximum number of lights supported by the renderer!");
+        info!(
+            "Refresh rate: {:?} Hz, target frame rate: {:?}",
+            refresh_rate, info.target_frame_rate
+        );
+        if previous.is_some_and(|previous| previous.refresh_rate != refresh_rate) {
+            ecs_lock.send_event(RefreshRateChanged { refresh_rate });
         }
-
-        self.light_entities = Some(light_entities);
+        ecs_lock.add_component_to_entity(entity, info);
+        self.frame_pacer.set_frame_rate(info.target_frame_rate);
     }
 
-    async fn init_models(&mut self) {
-        let ecs_lock = self.ecs.lock().unwrap();
-        let model_entities = ecs_lock.get_entites_with_component::<components::Model>();
-
-        for entity in model_entities.iter() {
-            let name = ecs_lock
-                .get_component_from_entity::<components::Name>(*entity)
-                .expect("No name provided for the Model!");
-
-            let pos = ecs_lock
-                .get_component_from_entity::<components::Pos3>(*entity)
-                .expect("No position provided for the Model!");
-
-            let model = ecs_lock
-                .get_component_from_entity::<components::Model>(*entity)
-                .unwrap();
-
-            let flip = ecs_lock.get_component_from_entity::<components::Flip>(*entity);
-
-            let scale = ecs_lock.get_component_from_entity::<components::Scale>(*entity);
-
-            let obj_model = {
-                let model = model.read().unwrap();
-
-                match *model {
-                    components::Model::Dynamic { obj_path } => resources::load_model(
-                        obj_path,
-                        &self.device,
-                        &self.queue,
-                        &self.texture_bind_group_layout,
-                    )
-                    .await
-                    .unwrap(),
-                    components::Model::Static { obj_path } => resources::load_model(
-                        obj_path,
-                        &self.device,
-                        &self.queue,
-                        &self.texture_bind_group_layout,
-                    )
-                    .await
-                    .unwrap(),
-                }
-            };
-            ecs_lock.add_component_to_entity(*entity, obj_model);
-
-            // TODO rename instance to model::ModelUniform
-            let mut instance = {
-                let rlock_pos = pos.read().unwrap();
-                instance::Instance {
-                    position: rlock_pos.pos,
-                    rotation: rlock_pos
-                        .rot
-                        .unwrap_or(cgmath::Quaternion::from_angle_y(cgmath::Rad(0.0))),
-                }
-            };
+    /// Get the lens of the camera entity and advance its field of view transition.
+    fn update_camera_lens(ecs: &ecs::Manager, dt: f32) -> components::CameraLens {
+        let lens = ecs
+            .get_entites_with_component::<components::Camera>()
+            .first()
+            .and_then(|entity| ecs.get_component_from_entity::<components::CameraLens>(*entity));
+        match lens {
+            Some(lens) => {
+                let mut lens = lens.write().unwrap();
+                lens.update(dt);
+                *lens
+            }
+            None => components::CameraLens::default(),
+        }
+    }
 
-            if let Some(flip) = flip {
-                let rlock_flip = flip.read().unwrap();
+    pub fn window(&self) -> &Window {
+        self.window
+            .as_ref()
+            .expect("The headless renderer has no window")
+            .window
+    }
 
-                match *rlock_flip {
-                    Flip::Horizontal => {
-                        instance.rotation =
-                            cgmath::Quaternion::from_angle_y(cgmath::Rad(std::f32::consts::PI));
-                    }
-                    Flip::Vertical => {
-                        instance.rotation =
-                            cgmath::Quaternion::from_angle_x(cgmath::Rad(std::f32::consts::PI));
+    /// Apply the window commands sent by the systems, the surface is reconfigured on a resize.
+    /// The fullscreen switches and the resizes the platform delays reconfigure it on their `Resized` event.
+    fn apply_window_commands(&mut self, commands: Vec<WindowCommand>) {
+        let Some(window) = self.window.as_ref().map(|target| target.window) else {
+            return;
+        };
+        for command in commands {
+            match command {
+                WindowCommand::SetTitle(title) => window.set_title(&title),
+                WindowCommand::Resize(width, height) => {
+                    let size = winit::dpi::PhysicalSize::new(width, height);
+                    if let Some(size) = window.request_inner_size(size) {
+                        self.resize(size);
                     }
-                    Flip::Both => {
-                        instance.rotation =
-                            cgmath::Quaternion::from_angle_y(cgmath::Rad(std::f32::consts::PI));
-                        instance.rotation =
-                            cgmath::Quaternion::from_angle_x(cgmath::Rad(std::f32::consts::PI));
+                }
+                WindowCommand::SetFullscreen(fullscreen) => window.set_fullscreen(
+                    fullscreen.then_some(winit::window::Fullscreen::Borderless(None)),
+                ),
+                WindowCommand::ToggleFullscreen => window.set_fullscreen(
+                    window
+                        .fullscreen()
+                        .is_none()
+                        .then_some(winit::window::Fullscreen::Borderless(None)),
+                ),
+                WindowCommand::SetCursor(icon) => window.set_cursor(winit_cursor(icon)),
+                WindowCommand::SetCursorVisible(visible) => window.set_cursor_visible(visible),
+                // The clipboard is shared with the UI, without it the text is dropped
+                WindowCommand::SetClipboard(text) => {
+                    #[cfg(feature = "ui")]
+                    if let Some(egui_renderer) = self
+                        .window
+                        .as_mut()
+                        .and_then(|target| target.egui_renderer.as_mut())
+                    {
+                        egui_renderer.set_clipboard_text(text);
                     }
+                    #[cfg(not(feature = "ui"))]
+                    warn!(
+                        "[State] The clipboard needs the ui feature, {} bytes dropped",
+                        text.len()
+                    );
+                }
+                WindowCommand::ReadClipboard => {
+                    #[cfg(feature = "ui")]
+                    let text = self
+                        .window
+                        .as_mut()
+                        .and_then(|target| target.egui_renderer.as_mut())
+                        .and_then(|egui_renderer| egui_renderer.clipboard_text())
+                        .unwrap_or_default();
+                    #[cfg(not(feature = "ui"))]
+                    let text = String::new();
+                    self.ecs.lock().unwrap().send_event(ClipboardText(text));
                 }
             }
-
-            // if let Some(scale) = scale {
-            //     let rlock_scale = scale.read().unwrap();
-
-            //     match *rlock_scale {
-            //         Scale::Uniform(s) => {
-            //             instance.scale = cgmath::Vector3::new(s, s, s);
-            //         }
-            //         Scale::NonUniform { x, y, z } => {
-            //             instance.scale = cgmath::Vector3::new(x, y, z);
-            //         }
-            //     }
-            // }
-
-            let instance_raw = instance.to_raw();
-            let instance_buffer =
-                self.device
-                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                        label: Some(format!("{} Instance Buffer", name.read().unwrap().0).as_str()),
-                        contents: bytemuck::cast_slice(&[instance_raw]),
-                        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                    });
-            ecs_lock.add_component_to_entity(*entity, instance);
-            ecs_lock.add_component_to_entity(*entity, instance_buffer);
         }
-
-        self.model_entities = Some(model_entities);
-    }
-
-    pub fn window(&self) -> &Window {
-        self.window
     }
 
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
@@ -739,27 +1031,104 @@ impl<'a> State<'a> {
             .resize(new_size.width, new_size.height);
 
         if new_size.width > 0 && new_size.height > 0 {
+            // The video has a fixed size
+            if self
+                .recorder
+                .as_ref()
+                .is_some_and(|recorder| recorder.size() != (new_size.width, new_size.height))
+            {
+                log::warn!("[Recorder] The window was resized, stopping the recording");
+                self.stop_recording();
+            }
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.size = new_size;
             //self.camera.aspect = self.config.width as f32 / self.config.height as f32;
-            self.surface.configure(&self.device, &self.config);
+            if let Some(target) = &self.window {
+                target.surface.configure(&self.device, &self.config);
+            }
             self.depth_texture =
                 texture::Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
+            self.post_process
+                .resize(&self.device, &self.config, &self.depth_texture.view);
+            #[cfg(feature = "particles")]
+            if let Some(particles) = &mut self.particles {
+                particles.resize(&self.device, &self.depth_texture.view);
+            }
+            #[cfg(feature = "decals")]
+            if let Some(decals) = &mut self.decals {
+                decals.resize(&self.device, &self.depth_texture.view);
+            }
+        }
+    }
+    /// Estimate the size of the GPU resources owned by the renderer in bytes.
+    /// Only the screen sized targets, the light, particle and crowd buffers and the streamed textures are counted,
+    /// the meshes and the other textures are not.
+    fn gpu_memory_estimate(&self) -> u64 {
+        let pixels = self.config.width as u64 * self.config.height as u64;
+        let bytes_per_pixel =
+            |format: wgpu::TextureFormat| format.block_copy_size(None).unwrap_or(4) as u64;
+        // The swapchain images, the post process color target, the velocity and the depth
+        let screen = pixels
+            * ((self.config.desired_maximum_frame_latency as u64 + 1)
+                * bytes_per_pixel(self.config.format)
+                + bytes_per_pixel(self.post_process.scene_format())
+                + bytes_per_pixel(post::VELOCITY_FORMAT)
+                + bytes_per_pixel(texture::Texture::DEPTH_FORMAT));
+
+        let textures = self
+            .texture_streamer
+            .as_ref()
+            .map_or(0, |streamer| streamer.resident_bytes());
+
+        #[allow(unused_mut)]
+        let mut passes = self.light_buffers.gpu_memory() + self.shadows.gpu_memory();
+        #[cfg(feature = "particles")]
+        {
+            passes += self.particles.as_ref().map_or(0, |p| p.gpu_memory());
+        }
+        #[cfg(feature = "crowds")]
+        {
+            passes += self.crowds.as_ref().map_or(0, |c| c.gpu_memory());
+        }
+
+        screen + passes + textures
+    }
+
+    fn start_recording(&mut self, recording: RecordingConfig) {
+        self.stop_recording();
+        match recorder::FrameRecorder::start(&self.device, &self.config, recording) {
+            Ok(recorder) => self.recorder = Some(recorder),
+            Err(e) => log::error!("[Recorder] Failed to start the recording: {}", e),
         }
     }
+
+    fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            if let Err(e) = recorder.finish() {
+                log::error!("[Recorder] Failed to finish the recording: {}", e);
+            }
+        }
+    }
+
     fn input(&mut self, event: &WindowEvent) -> bool {
         // TODO is this important? chek perf on DGPU
         //self.window.request_redraw();
 
         // * Capture the input for the custom windows
-        if self.egui_renderer.handle_input(self.window, event) {
-            // If a window consumed the event return true since no other component should handle it again
-            return true;
+        #[cfg(feature = "ui")]
+        if let Some(target) = &mut self.window {
+            if let Some(egui_renderer) = &mut target.egui_renderer {
+                if egui_renderer.handle_input(target.window, event) {
+                    // If a window consumed the event return true since no other component should handle it again
+                    return true;
+                }
+            }
         }
 
         match event {
             WindowEvent::KeyboardInput {
+                device_id,
                 event:
                     KeyEvent {
                         physical_key: PhysicalKey::Code(key),
@@ -767,9 +1136,35 @@ impl<'a> State<'a> {
                         ..
                     },
                 ..
-            } => self.camera_controller.process_keyboard(*key, *state),
+            } => {
+                self.route_key(*device_id, *key, *state);
+                self.camera_controller.process_keyboard(*key, *state)
+                    || self.interaction.process_keyboard(*key, *state)
+            }
+            // The files dragged onto the window are passed to the systems
+            WindowEvent::HoveredFile(path) => {
+                self.ecs
+                    .lock()
+                    .unwrap()
+                    .send_event(FileDrop::Hovered(path.clone()));
+                true
+            }
+            WindowEvent::HoveredFileCancelled => {
+                self.ecs.lock().unwrap().send_event(FileDrop::Cancelled);
+                true
+            }
+            WindowEvent::DroppedFile(path) => {
+                self.ecs
+                    .lock()
+                    .unwrap()
+                    .send_event(FileDrop::Dropped(path.clone()));
+                true
+            }
+            // The device events scroll while dragging, so a scroll is not counted twice
             WindowEvent::MouseWheel { delta, .. } => {
-                self.camera_controller.process_scroll(delta);
+                if !self.mouse_pressed {
+                    self.camera_controller.process_scroll(delta);
+                }
                 true
             }
             WindowEvent::MouseInput {
@@ -784,9 +1179,100 @@ impl<'a> State<'a> {
         }
     }
 
+    /// Pass a key to the local players by the index of its keyboard.
+    fn route_key(&mut self, device_id: event::DeviceId, key: KeyCode, state: ElementState) {
+        let ecs_lock = self.ecs.lock().unwrap();
+        let Some(players) = ecs_lock.get_resource::<LocalPlayers>() else {
+            return;
+        };
+        let keyboard = match self.keyboards.iter().position(|id| *id == device_id) {
+            Some(keyboard) => keyboard,
+            None => {
+                self.keyboards.push(device_id);
+                self.keyboards.len() - 1
+            }
+        };
+        players
+            .write()
+            .unwrap()
+            .press_key(keyboard, key, state == ElementState::Pressed);
+    }
+
     async fn update(&mut self, dt: instant::Duration) {
-        // Update camera
-        self.camera_controller.update_camera(&mut self.camera, dt);
+        play::update_play_mode(&self.ecs.lock().unwrap(), dt);
+        // The tools spawn tasks while the game is not playing too
+        self.ecs.lock().unwrap().run_task_callbacks();
+        self.apply_runtime_config();
+
+        // Start or stop the recording on request
+        let (start, stop) = {
+            let ecs_lock = self.ecs.lock().unwrap();
+            (
+                ecs_lock.drain_events::<recorder::StartRecording>(),
+                ecs_lock.drain_events::<recorder::StopRecording>(),
+            )
+        };
+        if !stop.is_empty() {
+            self.stop_recording();
+        }
+        if let Some(recorder::StartRecording(recording)) = start.into_iter().last() {
+            self.start_recording(recording);
+        }
+        if self.stats_overlay {
+            self.frame_stats.record_frame(dt);
+            self.frame_stats.entities = self.ecs.lock().unwrap().entity_count();
+            self.frame_stats.lights = self.light_entities.as_ref().map_or(0, Vec::len);
+        }
+        let window_commands = self.ecs.lock().unwrap().drain_events::<WindowCommand>();
+        self.apply_window_commands(window_commands);
+
+        // Poll the gamepads and move the camera with them
+        {
+            let ecs_lock = self.ecs.lock().unwrap();
+            if let Some(gamepads) = ecs_lock.get_resource::<GamepadState>() {
+                #[cfg(feature = "gamepad")]
+                if let Some(poller) = &mut self.gamepads {
+                    poller.poll(&mut gamepads.write().unwrap());
+                }
+                self.camera_controller
+                    .process_gamepad(&gamepads.read().unwrap());
+                if let Some(players) = ecs_lock.get_resource::<LocalPlayers>() {
+                    players
+                        .write()
+                        .unwrap()
+                        .begin_frame(&gamepads.read().unwrap());
+                }
+            }
+        }
+
+        // Update camera, a playing cutscene takes over the controller
+        let (shot, lens) = {
+            let ecs_lock = self.ecs.lock().unwrap();
+            let shot = ecs_lock
+                .get_all_components_of_type::<CutscenePlayer>()
+                .into_iter()
+                .find_map(|(_, player)| player.read().unwrap().shot());
+            (shot, Self::update_camera_lens(&ecs_lock, dt.as_secs_f32()))
+        };
+        self.default_fovy = Rad::from(lens.fov());
+        self.camera_projection.set_planes(lens.znear(), lens.zfar());
+        if let Some(shot) = shot {
+            self.camera.position = Point3::from_vec(shot.position);
+            if let Some(target) = shot.target {
+                self.camera.look_at(Point3::from_vec(target));
+            }
+            self.camera_projection
+                .set_fovy(self.fit_fovy(shot.fov.map_or(self.default_fovy, Rad::from)));
+            self.letterbox = shot.letterbox;
+        } else {
+            if !self.update_orbit_camera(dt) {
+                self.camera_controller.update_camera(&mut self.camera, dt);
+                self.update_editor_camera();
+            }
+            let fovy = self.default_fovy * self.camera_controller.fov_zoom();
+            self.camera_projection.set_fovy(self.fit_fovy(fovy));
+            self.letterbox = false;
+        }
         self.camera_uniform
             .update_view_proj(&self.camera, &self.camera_projection);
 
@@ -795,15 +1281,245 @@ impl<'a> State<'a> {
             0,
             bytemuck::cast_slice(&[self.camera_uniform]),
         );
+        self.player_views.update(
+            &self.device,
+            &self.queue,
+            &self.camera_bind_group_layout,
+            &self.ecs.lock().unwrap(),
+            self.config.width,
+            self.config.height,
+            self.camera_projection.fovy(),
+            self.camera_projection.znear(),
+            self.camera_projection.zfar(),
+        );
+        self.portals.update(
+            &self.device,
+            &self.queue,
+            (
+                &self.camera_bind_group_layout,
+                &self.texture_bind_group_layout,
+            ),
+            &mut self.override_materials,
+            &self.ecs.lock().unwrap(),
+            &self.camera,
+            &self.camera_projection,
+            self.config.format,
+        );
 
-        self.update_lights();
+        // The ECS is not locked while the new textures load
+        #[cfg(feature = "particles")]
+        if let Some(particles) = &mut self.particles {
+            let paths = particles.missing_textures(&self.ecs.lock().unwrap());
+            particles
+                .load_textures(
+                    &self.device,
+                    &self.queue,
+                    &self.texture_bind_group_layout,
+                    paths,
+                )
+                .await;
+            particles.update(
+                &self.device,
+                &self.queue,
+                &self.ecs.lock().unwrap(),
+                &self.camera,
+                &self.camera_projection,
+                dt.as_secs_f32(),
+            );
+        }
+        #[cfg(feature = "crowds")]
+        if let Some(crowds) = &mut self.crowds {
+            let paths = crowds.missing_textures(&self.ecs.lock().unwrap());
+            crowds
+                .load_textures(
+                    &self.device,
+                    &self.queue,
+                    &self.texture_bind_group_layout,
+                    paths,
+                )
+                .await;
+            crowds.update(
+                &self.device,
+                &self.queue,
+                &self.ecs.lock().unwrap(),
+                dt.as_secs_f32(),
+            );
+        }
+        #[cfg(feature = "decals")]
+        if let Some(decals) = &mut self.decals {
+            let paths = decals.missing_textures(&self.ecs.lock().unwrap());
+            decals
+                .load_textures(
+                    &self.device,
+                    &self.queue,
+                    &self.texture_bind_group_layout,
+                    paths,
+                )
+                .await;
+            decals.update(
+                &self.device,
+                &self.queue,
+                &self.ecs.lock().unwrap(),
+                &self.camera,
+                &self.camera_projection,
+            );
+        }
+
+        if let Some(streamer) = &mut self.texture_streamer {
+            let ecs_lock = self.ecs.lock().unwrap();
+            streamer.update(
+                &self.device,
+                &self.queue,
+                &self.texture_bind_group_layout,
+                &ecs_lock,
+                self.camera.position.to_vec(),
+            );
+        }
+
+        {
+            let ecs_lock = self.ecs.lock().unwrap();
+            let origin = self.camera.position.to_vec();
+            self.interaction
+                .update(&ecs_lock, origin, self.camera.forward());
+        }
+
+        // Update the post-process settings
+        let settings = {
+            let ecs_lock = self.ecs.lock().unwrap();
+            ecs_lock
+                .get_all_components_of_type::<post::PostProcessSettings>()
+                .first()
+                .map(|(_, settings)| *settings.read().unwrap())
+                .unwrap_or_default()
+        };
+        self.post_process.update(
+            &self.queue,
+            &self.config,
+            &settings,
+            self.camera_projection.znear(),
+            self.camera_projection.zfar(),
+        );
+
+        // Compile the pipeline permutations ahead of the models needing them
+        if let Some(progress) = self.model_pipelines.warm_up(&self.device) {
+            self.ecs.lock().unwrap().insert_resource(progress);
+        }
+
+        // The schedule moves the children after the physics, but it is paused while editing,
+        // so they are moved with their parents here too before the lights and the models are drawn
+        ecs::hierarchy::propagate_transforms(&self.ecs.lock().unwrap());
+        self.update_lights(dt.as_secs_f32());
+        self.init_models().await;
         self.update_models();
         //self.update_colliders();
     }
 
-    fn update_lights(&mut self) {
+    /// Widen the field of view when letterboxing, so the visible part keeps the target framing.
+    /// Apply the camera commands and frame the selected entity when F is pressed,
+    /// then move the camera with the followed entity.
+    fn update_editor_camera(&mut self) {
+        let ecs_lock = self.ecs.lock().unwrap();
+        let mut commands = ecs_lock.drain_events::<CameraCommand>();
+        if self.camera_controller.take_frame_request() {
+            if let Some(entity) = editor_camera::selected_entity(&ecs_lock) {
+                commands.push(CameraCommand::Frame(entity));
+            }
+        }
+
+        for command in commands {
+            match command {
+                CameraCommand::Frame(entity) => {
+                    let Some(bounds) = editor_camera::entity_bounds(&ecs_lock, entity) else {
+                        continue;
+                    };
+                    let distance = editor_camera::frame_distance(
+                        bounds.radius(),
+                        self.camera_projection.fovy(),
+                        self.camera_projection.aspect(),
+                    );
+                    self.camera
+                        .frame(Point3::from_vec(bounds.center()), distance);
+                    // Continue following from the framed position
+                    self.camera_controller.stop_following();
+                }
+                CameraCommand::Follow(entity) => {
+                    self.camera_follow = Some(entity);
+                    self.camera_controller.stop_following();
+                }
+                CameraCommand::Unfollow => {
+                    self.camera_follow = None;
+                    self.camera_controller.stop_following();
+                }
+            }
+        }
+
+        let Some(entity) = self.camera_follow else {
+            return;
+        };
+        match editor_camera::entity_bounds(&ecs_lock, entity) {
+            Some(bounds) => self
+                .camera_controller
+                .follow(&mut self.camera, Point3::from_vec(bounds.center())),
+            None => {
+                // The followed entity was removed
+                self.camera_follow = None;
+                self.camera_controller.stop_following();
+            }
+        }
+    }
+
+    /// Orbit the camera around the target of the `OrbitCameraController` of the camera entity.
+    /// The colliders of the last physics step between the camera and the target pull the camera closer.
+    ///
+    /// # Returns
+    ///
+    /// If the camera orbits, without an orbit or a target it is moved by the controls of its `Camera`.
+    fn update_orbit_camera(&mut self, dt: Dt) -> bool {
+        let ecs_lock = self.ecs.lock().unwrap();
+        let Some((camera_entity, orbit)) = ecs_lock
+            .get_entites_with_component::<components::Camera>()
+            .first()
+            .and_then(|entity| {
+                ecs_lock
+                    .get_component_from_entity::<components::OrbitCameraController>(*entity)
+                    .map(|orbit| (*entity, orbit))
+            })
+        else {
+            return false;
+        };
+        let mut orbit = orbit.write().unwrap();
+        let Some(target) = ecs_lock.get_component_from_entity::<components::Pos3>(orbit.target)
+        else {
+            return false;
+        };
+        let pivot = Point3::from_vec(orbit.pivot(target.read().unwrap().pos));
+
+        let index = ecs_lock.get_resource::<SpatialIndex>();
+        let ignore = [orbit.target, camera_entity];
+        let obstacle = |origin: Point3<f32>, direction, max_distance| {
+            index?
+                .read()
+                .unwrap()
+                .raycast(origin.to_vec(), direction, max_distance, &ignore)
+                .map(|(_, distance)| distance)
+        };
+        self.camera_controller
+            .update_orbit(&mut self.camera, &mut orbit, pivot, obstacle, dt);
+        true
+    }
+
+    fn fit_fovy(&self, fovy: Rad<f32>) -> Rad<f32> {
+        layout::fit_fovy(
+            fovy,
+            self.config.width as f32,
+            self.config.height as f32,
+            &self.display,
+        )
+    }
+
+    fn update_lights(&mut self, dt: f32) {
         if let Some(light_entities) = &self.light_entities {
-            let mut light_uniforms: Vec<light::LightUniform> = Vec::new();
+            let mut light_uniforms = Vec::with_capacity(light_entities.len());
 
             for entity in light_entities {
                 let ecs_lock = self.ecs.lock().unwrap();
@@ -825,26 +1541,74 @@ impl<'a> State<'a> {
 
                 let rlock_light_uniform = light_uniform.read().unwrap();
 
-                light_uniforms.push(*rlock_light_uniform);
+                light_uniforms.push((*entity, *rlock_light_uniform));
             }
 
-            let num_lights = light_uniforms.len() as u32;
-
-            let light_data = light::LightData {
-                lights: {
-                    let mut array =
-                        [light::LightUniform::default(); light::NUM_MAX_LIGHTS as usize];
-                    for (i, light) in light_uniforms.iter().enumerate() {
-                        array[i] = *light;
-                    }
-                    array
-                },
-                num_lights,
-                _padding: [0; 3],
+            let eye = self.camera.position.to_vec();
+            let light_uniforms =
+                self.light_culling
+                    .select(&light_uniforms, eye, &self.light_culling_config, dt);
+            // The tiles are of the main camera, the split and the portal views light every fragment with every light
+            let tiles = if self.player_views.is_split() || self.portals.is_active() {
+                light::LightTiles::untiled(light_uniforms.len())
+            } else {
+                light::bin_lights(
+                    &light_uniforms,
+                    Matrix4::from(self.camera_uniform.view_proj),
+                    eye,
+                    self.config.width,
+                    self.config.height,
+                )
             };
-
-            self.queue
-                .write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&[light_data]));
+            self.light_buffers.write(
+                &self.device,
+                &self.queue,
+                &self.light_bind_group_layout,
+                &light_uniforms,
+                &tiles,
+            );
+
+            // The first directional light casts the shadows, they are fitted to the main camera only
+            let caster = light_uniforms
+                .iter()
+                .enumerate()
+                .find(|(_, light)| light.light_type == light::LightType::Directional as u32)
+                .map(|(index, light)| {
+                    let to_light = Vector3::from(light.position) + Vector3::from(light.direction);
+                    (index as u32, to_light)
+                })
+                .filter(|(_, to_light)| to_light.magnitude2() > 0.0);
+            let frustum = (!self.player_views.is_split()).then(|| shadow::CameraFrustum {
+                eye,
+                forward: self.camera.forward(),
+                fovy: self.camera_projection.fovy(),
+                aspect: self.camera_projection.aspect(),
+                znear: self.camera_projection.znear(),
+            });
+            // The most important point lights cast shadows, the selection is sorted by importance
+            let points = light_uniforms
+                .iter()
+                .enumerate()
+                .filter(|(_, light)| {
+                    light.light_type == light::LightType::Point as u32 && light.radius > 0.0
+                })
+                .map(|(index, light)| shadow::PointCaster {
+                    light: index as u32,
+                    position: Vector3::from(light.position),
+                    radius: light.radius,
+                })
+                .take(shadow::MAX_POINT_SHADOWS)
+                .collect::<Vec<_>>();
+            let settings = {
+                let ecs_lock = self.ecs.lock().unwrap();
+                ecs_lock
+                    .get_all_components_of_type::<shadow::ShadowSettings>()
+                    .first()
+                    .map(|(_, settings)| *settings.read().unwrap())
+                    .unwrap_or_default()
+            };
+            self.shadows
+                .update(&self.queue, frustum.as_ref(), caster, &points, &settings);
         }
     }
 
@@ -877,6 +1641,10 @@ impl<'a> State<'a> {
                     let mut wlock_instance = instance.write().unwrap();
                     let rlock_pos3 = pos.read().unwrap();
 
+                    wlock_instance.previous = Some(wlock_instance.model_matrix());
+                    wlock_instance.motion_blur = ecs_lock
+                        .get_component_from_entity::<post::NoMotionBlur>(*entity)
+                        .is_none();
                     wlock_instance.position = rlock_pos3.pos;
                     wlock_instance.rotation = rlock_pos3
                         .rot
@@ -910,8 +1678,13 @@ impl<'a> State<'a> {
     //     }
     // }
 
+    /// Render the frame into the surface of the window and draw the UI over it.
+    /// A headless renderer draws with `render_offscreen` instead.
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
+        let Some(target) = &self.window else {
+            return Ok(());
+        };
+        let output = target.surface.get_current_texture()?;
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
@@ -921,23 +1694,259 @@ impl<'a> State<'a> {
                 label: Some("Render Encoder"),
             });
 
+        self.render_scene(&mut encoder, &view);
+
+        // ! The UI over the frame
+        #[cfg(feature = "ui")]
+        self.render_ui(&mut encoder, &view);
+
+        if let Some(recorder) = &self.recorder {
+            recorder.copy_frame(&mut encoder, &output.texture);
+        }
+
+        self.queue.submit(iter::once(encoder.finish()));
+
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.write_frame(&self.device) {
+                log::error!("[Recorder] Failed to record the frame: {}", e);
+                self.stop_recording();
+            }
+        }
+
+        output.present();
+
+        Ok(())
+    }
+
+    /// Render the scene and post-process it into a view, without the UI.
+    fn render_scene(&mut self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        // ! Particle simulation, collides with the depth buffer of the last frame
+        #[cfg(feature = "particles")]
+        if let Some(particles) = &self.particles {
+            particles.simulate(encoder);
+        }
+
+        // ! The opaque draw list, sorted so the pipelines and the materials change rarely
+        let (models, draws, casters, portal_models) = {
+            let ecs_lock = self.ecs.lock().unwrap();
+            let eye = self.camera.position.to_vec();
+            let mut models = Vec::new();
+            let mut draws = Vec::new();
+            // The models of the teleporters with a portal view, they are not drawn into the views
+            let mut portal_models = Vec::new();
+            // The models and the transforms drawn, the cached shadow cascades are drawn again once they change
+            let mut casters = StableHasher::default();
+            for entity in self.model_entities.iter().flatten() {
+                let (Some(model), Some(instance_buffer)) = (
+                    ecs_lock.get_component_from_entity::<model::Model>(*entity),
+                    ecs_lock.get_component_from_entity::<wgpu::Buffer>(*entity),
+                ) else {
+                    continue;
+                };
+                let features = ecs_lock
+                    .get_component_from_entity::<pipelines::MaterialFeatures>(*entity)
+                    .map_or_else(Default::default, |features| *features.read().unwrap());
+                let overrides =
+                    ecs_lock.get_component_from_entity::<overrides::MaterialOverrides>(*entity);
+                let depth = ecs_lock
+                    .get_component_from_entity::<components::Pos3>(*entity)
+                    .map_or(0.0, |pos| (pos.read().unwrap().pos - eye).magnitude2());
+
+                let model: &model::Model = unsafe { &*(&*model.read().unwrap() as *const _) };
+                let overrides = overrides
+                    .as_ref()
+                    .map(|overrides| overrides.read().unwrap());
+                let mut failures = Vec::new();
+                let portal = self.portals.get(*entity);
+                if portal.is_some() {
+                    portal_models.push(models.len());
+                }
+                let materials = (0..model.meshes.len())
+                    .map(|mesh| match portal {
+                        // A teleporter with a portal view shows the texture of its view without highlights
+                        Some(portal) => (
+                            overrides::DrawMaterial::Override(portal.material),
+                            pipelines::MaterialFeatures { specular: false },
+                        ),
+                        None => self.override_materials.resolve(
+                            &self.device,
+                            &self.queue,
+                            &self.texture_bind_group_layout,
+                            model,
+                            mesh,
+                            overrides.as_deref(),
+                            features,
+                            &mut failures,
+                        ),
+                    })
+                    .collect::<Vec<_>>();
+                report_load_failures(&ecs_lock, *entity, failures);
+                draw_list::add_model(
+                    &mut draws,
+                    models.len(),
+                    materials.into_iter().map(|(material, features)| {
+                        let bind_group = self.override_materials.bind_group(model, material);
+                        (material, features, bind_group)
+                    }),
+                    depth,
+                );
+                shadow::hash_caster(
+                    &mut casters,
+                    *entity,
+                    model.meshes.iter().map(|mesh| {
+                        (
+                            mesh.vertex_buffer.global_id(),
+                            mesh.index_buffer.global_id(),
+                            mesh.num_elements,
+                        )
+                    }),
+                    ecs_lock
+                        .get_component_from_entity::<instance::Instance>(*entity)
+                        .map(|instance| instance.read().unwrap().model_matrix()),
+                );
+                models.push((model, instance_buffer));
+            }
+            draw_list::sort_opaque(&mut draws);
+            (models, draws, casters.finish(), portal_models)
+        };
+        // The pipelines the warm-up did not reach yet are compiled now
+        for draw in &draws {
+            self.model_pipelines.prepare(&self.device, draw.features);
+        }
+
+        // ! The frame is split between the cameras of the local players, or drawn from the main camera
+        let views = if self.player_views.is_split() {
+            self.player_views
+                .active()
+                .iter()
+                .map(|view| {
+                    let viewport = view.pixels(self.config.width, self.config.height);
+                    (&view.bind_group, Some(viewport))
+                })
+                .collect::<Vec<_>>()
+        } else {
+            vec![(&self.camera_bind_group, None)]
+        };
+
+        // ! The views through the portals are drawn first, without the portals themselves
+        let portal_draws = draws
+            .iter()
+            .filter(|draw| !portal_models.contains(&draw.model))
+            .copied()
+            .collect::<Vec<_>>();
+        let portals = self.portals.active();
+        self.frame_stats.draw_calls =
+            draws.len() * views.len() + portal_draws.len() * portals.len();
+
+        // ! The shadow cascades of the directional light and the cubes of the point lights, drawn from the models of the frame
+        if self.shadows.is_active() {
+            self.shadows.render(encoder, &models, casters);
+        }
+
+        for portal in portals {
+            let target = &self
+                .override_materials
+                .get(portal.material)
+                .diffuse_texture
+                .view;
+            self.draw_views(
+                encoder,
+                &[(&portal.bind_group, None)],
+                &models,
+                &portal_draws,
+                false,
+            );
+            self.post_process.render(encoder, target);
+        }
+        self.draw_views(encoder, &views, &models, &draws, true);
+
+        // ! The edges of the models over the frame, not drawn into the portals
+        #[cfg(feature = "wireframe")]
+        if let Some(wireframe) = self.wireframe.as_ref().filter(|_| self.show_wireframe) {
+            wireframe.render(
+                encoder,
+                self.post_process.color_view(),
+                &self.depth_texture.view,
+                &views,
+                &models,
+                &draws,
+            );
+        }
+
+        // ! Post-process pass into the frame
+        self.post_process.render(encoder, view);
+
+        // ! The crosshair in the middle of each view
+        #[cfg(feature = "crosshair")]
+        if let Some(crosshair) = self.crosshair.as_mut().filter(|_| self.show_crosshair) {
+            let frame = (self.config.width, self.config.height);
+            let centers = views
+                .iter()
+                .map(|(_, viewport)| match viewport {
+                    Some([x, y, width, height]) => [x + width * 0.5, y + height * 0.5],
+                    None => [frame.0 as f32 * 0.5, frame.1 as f32 * 0.5],
+                })
+                .collect::<Vec<_>>();
+            let style = self
+                .ecs
+                .lock()
+                .unwrap()
+                .get_all_components_of_type::<crosshair::Crosshair>()
+                .first()
+                .map_or_else(Default::default, |(_, style)| *style.read().unwrap());
+            crosshair.update(&self.device, &self.queue, &style, &centers, frame);
+            crosshair.render(encoder, view);
+        }
+    }
+
+    /// Draw the opaque models, the crowds and the effects from cameras into the post-process targets.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder` - The encoder of the frame.
+    /// * `views` - The camera bind groups and the viewports in pixels, `None` for the whole frame.
+    /// * `models` - The models of the frame with their instance buffers.
+    /// * `draws` - The sorted opaque draws.
+    /// * `main` - Whether the views are of the frame, the decals and the particles are only drawn into it.
+    #[cfg_attr(
+        not(any(feature = "decals", feature = "particles")),
+        allow(unused_variables)
+    )]
+    fn draw_views(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        views: &[(&wgpu::BindGroup, Option<[f32; 4]>)],
+        models: &[(&model::Model, Arc<RwLock<wgpu::Buffer>>)],
+        draws: &[draw_list::OpaqueDraw],
+        main: bool,
+    ) {
         // ! Graphical render pass
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
+                color_attachments: &[
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: self.post_process.color_view(),
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color {
+                                r: 0.1,
+                                g: 0.2,
+                                b: 0.3,
+                                a: 1.0,
+                            }),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: self.post_process.velocity_view(),
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                ],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                     view: &self.depth_texture.view,
                     depth_ops: Some(wgpu::Operations {
@@ -950,55 +1959,65 @@ impl<'a> State<'a> {
                 timestamp_writes: None,
             });
 
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-            render_pass.set_bind_group(2, &self.light_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.light_buffers.bind_group, &[]);
+            render_pass.set_bind_group(3, &self.shadows.bind_group, &[]);
+            let (mut bound_features, mut bound_material, mut bound_model) = (None, None, None);
 
-            if let Some(model_entities) = &self.model_entities {
-                for entity in model_entities {
-                    let ecs_lock = self.ecs.lock().unwrap();
-
-                    let model = ecs_lock
-                        .get_component_from_entity::<model::Model>(*entity)
-                        .unwrap();
-                    let instance_buffer = ecs_lock
-                        .get_component_from_entity::<wgpu::Buffer>(*entity)
-                        .unwrap();
-
-                    let model: &model::Model = unsafe { &*(&*model.read().unwrap() as *const _) };
-
-                    render_pass.set_vertex_buffer(1, instance_buffer.read().unwrap().slice(..));
-
-                    // Draw model
-                    render_pass.draw_model(model, &self.camera_bind_group, &self.light_bind_group);
+            for &(camera_bind_group, viewport) in views {
+                if let Some([x, y, width, height]) = viewport {
+                    render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+                }
+                render_pass.set_bind_group(1, camera_bind_group, &[]);
+                for draw in draws {
+                    let (model, instance_buffer) = &models[draw.model];
+                    let mesh = &model.meshes[draw.mesh];
+                    if bound_features != Some(draw.features) {
+                        render_pass.set_pipeline(self.model_pipelines.get(draw.features));
+                        bound_features = Some(draw.features);
+                    }
+                    if bound_material != Some(draw.material) {
+                        let bind_group = self.override_materials.bind_group(model, draw.slot);
+                        render_pass.set_bind_group(0, bind_group, &[]);
+                        bound_material = Some(draw.material);
+                    }
+                    if bound_model != Some(draw.model) {
+                        render_pass.set_vertex_buffer(1, instance_buffer.read().unwrap().slice(..));
+                        bound_model = Some(draw.model);
+                    }
+                    render_pass.draw_mesh_geometry(mesh, 0..1);
                 }
             }
         }
 
-        // ! Egui render pass for the custom UI windows
-        if !self.egui_windows.is_empty() {
-            // * if a custom ui is present
-            let screen_descriptor = ScreenDescriptor {
-                size_in_pixels: [self.config.width, self.config.height],
-                pixels_per_point: self.window.scale_factor() as f32,
-            };
-
-            for window in self.egui_windows.iter_mut() {
-                self.egui_renderer.draw_ui_full(
-                    &self.device,
-                    &self.queue,
-                    &mut encoder,
-                    self.window,
-                    &view,
-                    &screen_descriptor,
-                    window,
-                );
-            }
+        // ! Skinned crowds, drawn like the models before anything is projected onto them
+        #[cfg(feature = "crowds")]
+        if let Some(crowds) = &self.crowds {
+            crowds.render(
+                encoder,
+                self.post_process.color_view(),
+                self.post_process.velocity_view(),
+                &self.depth_texture.view,
+                views,
+                &self.light_buffers.bind_group,
+            );
         }
 
-        self.queue.submit(iter::once(encoder.finish()));
-        output.present();
-
-        Ok(())
+        // ! Decals are projected onto the opaque geometry, then the particles are drawn over them
+        // ! They are set up for the main camera, so a split frame and the portals are drawn without them
+        #[cfg(any(feature = "decals", feature = "particles"))]
+        let main_camera = main && !self.player_views.is_split();
+        #[cfg(feature = "decals")]
+        if let Some(decals) = self.decals.as_ref().filter(|_| main_camera) {
+            decals.render(encoder, self.post_process.color_view());
+        }
+        #[cfg(feature = "particles")]
+        if let Some(particles) = self.particles.as_ref().filter(|_| main_camera) {
+            particles.render(
+                encoder,
+                self.post_process.color_view(),
+                self.post_process.velocity_view(),
+                &self.depth_texture.view,
+            );
+        }
     }
 }
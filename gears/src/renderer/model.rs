@@ -1,4 +1,6 @@
+use super::streaming::StreamedTexture;
 use super::texture;
+use crate::ecs::components::Bounds;
 use std::{clone, ops::Range};
 
 pub(crate) trait Vertex {
@@ -40,115 +42,130 @@ impl Vertex for ModelVertex {
     }
 }
 
-pub(crate) struct Material {
+/// A material slot of a model, named after the material of the .mtl file.
+pub struct Material {
+    pub(crate) name: String,
     #[allow(unused)]
-    pub name: String,
-    #[allow(unused)]
-    pub diffuse_texture: texture::Texture,
-    pub bind_group: wgpu::BindGroup,
+    pub(crate) diffuse_texture: texture::Texture,
+    pub(crate) bind_group: wgpu::BindGroup,
+    /// The full texture when it is streamed, the diffuse texture holds its resident mip levels.
+    pub(crate) stream: Option<StreamedTexture>,
 }
 
-pub(crate) struct Mesh {
-    #[allow(unused)]
-    pub name: String,
-    pub vertex_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer,
-    pub num_elements: u32,
-    pub material: usize,
+impl Material {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(crate) fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        texture: &texture::Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: None,
+        })
+    }
+}
+
+/// A part of a model drawn with a single material.
+pub struct Mesh {
+    pub(crate) name: String,
+    pub(crate) vertex_buffer: wgpu::Buffer,
+    pub(crate) index_buffer: wgpu::Buffer,
+    pub(crate) num_elements: u32,
+    pub(crate) material: usize,
+    /// The name of the material of the .mtl file, empty if the mesh has none.
+    pub(crate) material_name: String,
+    /// The bounds of the vertices, `None` if the mesh has none.
+    pub(crate) bounds: Option<Bounds>,
 }
 
-pub(crate) struct Model {
-    pub meshes: Vec<Mesh>,
-    pub materials: Vec<Material>,
+impl Mesh {
+    /// Get the name of the object or the group of the .obj file.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the material slot of the mesh in its model, without the overrides.
+    pub fn material(&self) -> usize {
+        self.material
+    }
+
+    /// Get the name of the material of the .mtl file, it stays the same when the texture is packed into an atlas.
+    pub fn material_name(&self) -> &str {
+        &self.material_name
+    }
+
+    pub fn triangle_count(&self) -> u32 {
+        self.num_elements / 3
+    }
+
+    /// Get the bounds of the mesh relative to the origin of the model.
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
+}
+
+/// A loaded model. The renderer adds it to an entity with a `components::Model` once the file is loaded,
+/// so the systems can look into it with `Manager::get_component_from_entity::<Model>`.
+pub struct Model {
+    pub(crate) meshes: Vec<Mesh>,
+    pub(crate) materials: Vec<Material>,
+    /// The bounds of the vertices, `None` if the model has none.
+    pub(crate) bounds: Option<Bounds>,
+}
+
+impl Model {
+    pub fn meshes(&self) -> &[Mesh] {
+        &self.meshes
+    }
+
+    pub fn materials(&self) -> &[Material] {
+        &self.materials
+    }
+
+    /// Get the bounds of the model relative to its origin, `None` if it has no vertices.
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
+
+    /// Find the index of the first mesh with a name.
+    pub fn find_mesh(&self, name: &str) -> Option<usize> {
+        self.meshes.iter().position(|mesh| mesh.name == name)
+    }
+
+    /// Find the first material slot with a name.
+    pub fn find_material(&self, name: &str) -> Option<usize> {
+        self.materials
+            .iter()
+            .position(|material| material.name == name)
+    }
 }
 
 pub(crate) trait DrawModel<'a> {
-    fn draw_mesh(
-        &mut self,
-        mesh: &'a Mesh,
-        material: &'a Material,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    );
-    fn draw_mesh_instanced(
-        &mut self,
-        mesh: &'a Mesh,
-        material: &'a Material,
-        instances: Range<u32>,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    );
-
-    fn draw_model(
-        &mut self,
-        model: &'a Model,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    );
-    fn draw_model_instanced(
-        &mut self,
-        model: &'a Model,
-        instances: Range<u32>,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    );
+    /// Draw the geometry of a mesh with the bind groups already set, e.g. when consecutive meshes share them.
+    fn draw_mesh_geometry(&mut self, mesh: &'a Mesh, instances: Range<u32>);
 }
 
 impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a>
 where
     'b: 'a,
 {
-    fn draw_mesh(
-        &mut self,
-        mesh: &'b Mesh,
-        material: &'b Material,
-        camera_bind_group: &'b wgpu::BindGroup,
-        light_bind_group: &'b wgpu::BindGroup,
-    ) {
-        self.draw_mesh_instanced(mesh, material, 0..1, camera_bind_group, light_bind_group);
-    }
-
-    fn draw_mesh_instanced(
-        &mut self,
-        mesh: &'b Mesh,
-        material: &'b Material,
-        instances: Range<u32>,
-        camera_bind_group: &'b wgpu::BindGroup,
-        light_bind_group: &'b wgpu::BindGroup,
-    ) {
+    fn draw_mesh_geometry(&mut self, mesh: &'b Mesh, instances: Range<u32>) {
         self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
         self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        self.set_bind_group(0, &material.bind_group, &[]);
-        self.set_bind_group(1, camera_bind_group, &[]);
-        self.set_bind_group(2, light_bind_group, &[]);
         self.draw_indexed(0..mesh.num_elements, 0, instances);
     }
-
-    fn draw_model(
-        &mut self,
-        model: &'b Model,
-        camera_bind_group: &'b wgpu::BindGroup,
-        light_bind_group: &'b wgpu::BindGroup,
-    ) {
-        self.draw_model_instanced(model, 0..1, camera_bind_group, light_bind_group);
-    }
-
-    fn draw_model_instanced(
-        &mut self,
-        model: &'b Model,
-        instances: Range<u32>,
-        camera_bind_group: &'b wgpu::BindGroup,
-        light_bind_group: &'b wgpu::BindGroup,
-    ) {
-        for mesh in &model.meshes {
-            let material = &model.materials[mesh.material];
-            self.draw_mesh_instanced(
-                mesh,
-                material,
-                instances.clone(),
-                camera_bind_group,
-                light_bind_group,
-            );
-        }
-    }
 }
@@ -0,0 +1,309 @@
+use super::model::{Material, Model};
+use super::pipelines::MaterialFeatures;
+use super::resources::LoadFailure;
+use super::texture;
+use crate::core::vfs;
+use crate::ecs::traits::Component;
+use std::collections::HashMap;
+
+/// The meshes of a model an override applies to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MeshSelector {
+    /// The mesh at an index of `Model::meshes`.
+    Index(usize),
+    /// The meshes with a name, the objects or the groups of the .obj file.
+    Name(String),
+    /// The meshes with a material of the .mtl file by its name.
+    Material(String),
+}
+
+impl MeshSelector {
+    fn matches(&self, index: usize, name: &str, material: &str) -> bool {
+        match self {
+            Self::Index(selected) => *selected == index,
+            Self::Name(selected) => selected == name,
+            Self::Material(selected) => selected == material,
+        }
+    }
+}
+
+/// The material a mesh is drawn with instead of its own.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MaterialSource {
+    /// Another material slot of the same model, see `Model::find_material`.
+    Slot(usize),
+    /// A texture file, loaded the first time a mesh is drawn with it.
+    Texture(String),
+    /// A flat sRGB color with alpha.
+    Color([u8; 4]),
+}
+
+/// Replaces the material and the shader features of the selected meshes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MaterialOverride {
+    /// The material, `None` keeps the own one.
+    pub material: Option<MaterialSource>,
+    /// The shader features, `None` keeps the ones of the entity.
+    pub features: Option<MaterialFeatures>,
+}
+
+impl MaterialOverride {
+    pub fn slot(slot: usize) -> Self {
+        Self {
+            material: Some(MaterialSource::Slot(slot)),
+            features: None,
+        }
+    }
+
+    pub fn texture(path: impl Into<String>) -> Self {
+        Self {
+            material: Some(MaterialSource::Texture(path.into())),
+            features: None,
+        }
+    }
+
+    pub fn color(color: [u8; 4]) -> Self {
+        Self {
+            material: Some(MaterialSource::Color(color)),
+            features: None,
+        }
+    }
+
+    pub fn with_features(mut self, features: MaterialFeatures) -> Self {
+        self.features = Some(features);
+        self
+    }
+}
+
+/// Draws the meshes of the model of an entity with other materials and shader features,
+/// e.g. for team colors or damage states without a copy of the model for each.
+///
+/// The overrides are applied when the model is drawn, the last one added which selects a mesh is used.
+/// The texture coordinates of a mesh packed into an atlas point into its region, so the texture
+/// overrides of such meshes need `Config::texture_atlas` turned off.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MaterialOverrides {
+    overrides: Vec<(MeshSelector, MaterialOverride)>,
+}
+
+impl Component for MaterialOverrides {}
+
+impl MaterialOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, selector: MeshSelector, material_override: MaterialOverride) -> Self {
+        self.set(selector, material_override);
+        self
+    }
+
+    /// Override the selected meshes, an override with the same selector is replaced.
+    pub fn set(&mut self, selector: MeshSelector, material_override: MaterialOverride) {
+        self.remove(&selector);
+        self.overrides.push((selector, material_override));
+    }
+
+    /// Draw the selected meshes with their own material again.
+    pub fn remove(&mut self, selector: &MeshSelector) {
+        self.overrides.retain(|(other, _)| other != selector);
+    }
+
+    /// Get the override of a mesh.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index of the mesh in the model.
+    /// * `name` - The name of the mesh.
+    /// * `material` - The name of the material of the mesh.
+    pub fn get(&self, index: usize, name: &str, material: &str) -> Option<&MaterialOverride> {
+        self.overrides
+            .iter()
+            .rev()
+            .find(|(selector, _)| selector.matches(index, name, material))
+            .map(|(_, material_override)| material_override)
+    }
+}
+
+/// The material a mesh is drawn with, a slot of its model or one created for an override.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum DrawMaterial {
+    Slot(usize),
+    Override(usize),
+}
+
+/// The materials of the texture and the color overrides, shared by the entities using the same one.
+#[derive(Default)]
+pub(crate) struct OverrideMaterials {
+    indices: HashMap<MaterialSource, usize>,
+    materials: Vec<Material>,
+}
+
+impl OverrideMaterials {
+    /// Resolve the material and the features a mesh is drawn with,
+    /// the material of a texture or a color is created the first time it is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - The device the materials are created on.
+    /// * `queue` - The queue the textures are uploaded with.
+    /// * `layout` - The layout of the material bind groups.
+    /// * `model` - The model of the mesh.
+    /// * `mesh` - The index of the mesh.
+    /// * `overrides` - The overrides of the entity, if it has any.
+    /// * `features` - The features of the entity.
+    /// * `failures` - The textures which failed to load, they are drawn with the missing texture.
+    #[allow(clippy::too_many_arguments)]
+    pub fn resolve(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        model: &Model,
+        mesh: usize,
+        overrides: Option<&MaterialOverrides>,
+        features: MaterialFeatures,
+        failures: &mut Vec<LoadFailure>,
+    ) -> (DrawMaterial, MaterialFeatures) {
+        let data = &model.meshes[mesh];
+        let own = DrawMaterial::Slot(data.material);
+        let Some(material_override) =
+            overrides.and_then(|overrides| overrides.get(mesh, &data.name, &data.material_name))
+        else {
+            return (own, features);
+        };
+
+        let material = match &material_override.material {
+            None => own,
+            // A slot the model does not have is ignored
+            Some(MaterialSource::Slot(slot)) if *slot < model.materials.len() => {
+                DrawMaterial::Slot(*slot)
+            }
+            Some(MaterialSource::Slot(_)) => own,
+            Some(source) => {
+                DrawMaterial::Override(self.create(device, queue, layout, source, failures))
+            }
+        };
+        (material, material_override.features.unwrap_or(features))
+    }
+
+    /// Get the material of a texture or a color, it is created the first time.
+    fn create(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        source: &MaterialSource,
+        failures: &mut Vec<LoadFailure>,
+    ) -> usize {
+        if let Some(&index) = self.indices.get(source) {
+            return index;
+        }
+
+        let (name, loaded) = match source {
+            MaterialSource::Texture(path) => (
+                path.clone(),
+                vfs::read(path)
+                    .and_then(|data| texture::Texture::from_bytes(device, queue, &data, path)),
+            ),
+            MaterialSource::Color(color) => (
+                format!("{:?}", color),
+                texture::Texture::from_mips(
+                    device,
+                    queue,
+                    &[image::RgbaImage::from_pixel(1, 1, image::Rgba(*color))],
+                    Some("Override color"),
+                ),
+            ),
+            MaterialSource::Slot(_) => unreachable!("The slots are materials of the model"),
+        };
+        let diffuse_texture = loaded.unwrap_or_else(|error| {
+            failures.push(LoadFailure {
+                path: name.clone(),
+                error,
+            });
+            texture::Texture::missing(device, queue)
+        });
+        let bind_group = Material::create_bind_group(device, layout, &diffuse_texture);
+
+        self.materials.push(Material {
+            name,
+            diffuse_texture,
+            bind_group,
+            stream: None,
+        });
+        let index = self.materials.len() - 1;
+        self.indices.insert(source.clone(), index);
+        index
+    }
+
+    /// Add a material the renderer draws into, e.g. the texture of a portal.
+    ///
+    /// # Returns
+    ///
+    /// The index of the material, for `DrawMaterial::Override`.
+    pub fn add(&mut self, material: Material) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    /// Replace a material added with `add`, e.g. once its texture is resized.
+    pub fn replace(&mut self, index: usize, material: Material) {
+        self.materials[index] = material;
+    }
+
+    pub fn get(&self, index: usize) -> &Material {
+        &self.materials[index]
+    }
+
+    /// Get the bind group of the material a mesh of a model is drawn with.
+    pub fn bind_group<'a>(
+        &'a self,
+        model: &'a Model,
+        material: DrawMaterial,
+    ) -> &'a wgpu::BindGroup {
+        match material {
+            DrawMaterial::Slot(slot) => &model.materials[slot].bind_group,
+            DrawMaterial::Override(index) => &self.materials[index].bind_group,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_material_overrides() {
+        let mut overrides = MaterialOverrides::new()
+            .with(
+                MeshSelector::Material("paint".to_string()),
+                MaterialOverride::color([200, 30, 30, 255]),
+            )
+            .with(MeshSelector::Index(2), MaterialOverride::slot(1));
+
+        assert_eq!(overrides.get(0, "body", "metal"), None);
+        assert_eq!(
+            overrides.get(0, "body", "paint"),
+            Some(&MaterialOverride::color([200, 30, 30, 255]))
+        );
+        // The latest override selecting a mesh wins
+        assert_eq!(
+            overrides.get(2, "door", "paint"),
+            Some(&MaterialOverride::slot(1))
+        );
+
+        // Setting a selector again replaces its override
+        overrides.set(
+            MeshSelector::Material("paint".to_string()),
+            MaterialOverride::texture("textures/burnt.png"),
+        );
+        assert_eq!(
+            overrides.get(2, "door", "paint"),
+            Some(&MaterialOverride::texture("textures/burnt.png"))
+        );
+        overrides.remove(&MeshSelector::Material("paint".to_string()));
+        overrides.remove(&MeshSelector::Index(2));
+        assert_eq!(overrides.get(2, "door", "paint"), None);
+    }
+}
@@ -0,0 +1,948 @@
+use super::buffer::GrowableBuffer;
+use super::camera::{Camera, Projection};
+use super::{resources, texture};
+use crate::core::{format, vfs};
+use crate::ecs::components::Pos3;
+use crate::ecs::traits::Component;
+use crate::ecs::{self, Entity};
+use cgmath::{Deg, InnerSpace, Matrix4, Rad, SquareMatrix, Vector3};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use wgpu::util::DeviceExt;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+/// What happens to a particle when it hits the scene geometry.
+/// The collision is tested against the depth buffer, so only visible surfaces collide.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum ParticleCollision {
+    #[default]
+    None,
+    /// The particle dies on contact, e.g. rain drops.
+    Die,
+    /// The particle bounces off the surface and keeps the given fraction of its speed, e.g. sparks.
+    Bounce { restitution: f32 },
+}
+
+/// A component that emits camera-facing particles from the position of the entity.
+/// The particles are simulated on the GPU.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParticleEmitter {
+    pub enabled: bool,
+    /// The number of particles spawned per second.
+    pub rate: f32,
+    /// The lifetime of a particle in seconds.
+    pub lifetime: f32,
+    /// The initial direction of the particles.
+    pub direction: Vector3<f32>,
+    pub speed: f32,
+    /// The angle of the cone around the direction the particles are spawned in.
+    pub spread: Deg<f32>,
+    pub gravity: Vector3<f32>,
+    /// The half size of a particle in world units.
+    pub size: f32,
+    pub color: [f32; 4],
+    /// The size multiplier over the lifetime, at 0, 1/3, 2/3 and the end of the lifetime.
+    pub size_curve: [f32; 4],
+    /// The alpha multiplier over the lifetime, sampled like the size curve.
+    pub alpha_curve: [f32; 4],
+    /// The path of the texture drawn on the particles, a soft dot is drawn without one.
+    pub texture: Option<String>,
+    pub collision: ParticleCollision,
+    /// The maximum number of living particles, the oldest ones are replaced first.
+    pub max_particles: u32,
+    accumulator: f32,
+    cursor: u32,
+}
+
+impl Component for ParticleEmitter {}
+
+impl Default for ParticleEmitter {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rate: 50.0,
+            lifetime: 2.0,
+            direction: Vector3::unit_y(),
+            speed: 2.0,
+            spread: Deg(20.0),
+            gravity: Vector3::new(0.0, -9.81, 0.0),
+            size: 0.05,
+            color: [1.0; 4],
+            size_curve: [1.0; 4],
+            alpha_curve: [1.0, 2.0 / 3.0, 1.0 / 3.0, 0.0],
+            texture: None,
+            collision: ParticleCollision::None,
+            max_particles: 1024,
+            accumulator: 0.0,
+            cursor: 0,
+        }
+    }
+}
+
+/// The parameters of an emitter in an effect preset.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "ParticleEmitter", default)]
+struct Preset {
+    rate: f32,
+    lifetime: f32,
+    direction: [f32; 3],
+    speed: f32,
+    /// The spread in degrees.
+    spread: f32,
+    gravity: [f32; 3],
+    size: f32,
+    color: [f32; 4],
+    size_curve: [f32; 4],
+    alpha_curve: [f32; 4],
+    texture: Option<String>,
+    collision: ParticleCollision,
+    max_particles: u32,
+}
+
+impl Default for Preset {
+    fn default() -> Self {
+        Self::from(&ParticleEmitter::default())
+    }
+}
+
+impl From<&ParticleEmitter> for Preset {
+    fn from(emitter: &ParticleEmitter) -> Self {
+        Self {
+            rate: emitter.rate,
+            lifetime: emitter.lifetime,
+            direction: emitter.direction.into(),
+            speed: emitter.speed,
+            spread: emitter.spread.0,
+            gravity: emitter.gravity.into(),
+            size: emitter.size,
+            color: emitter.color,
+            size_curve: emitter.size_curve,
+            alpha_curve: emitter.alpha_curve,
+            texture: emitter.texture.clone(),
+            collision: emitter.collision,
+            max_particles: emitter.max_particles,
+        }
+    }
+}
+
+impl ParticleEmitter {
+    pub fn new(rate: f32, lifetime: f32) -> Self {
+        Self {
+            rate,
+            lifetime,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_velocity(mut self, direction: Vector3<f32>, speed: f32, spread: Deg<f32>) -> Self {
+        self.direction = direction;
+        self.speed = speed;
+        self.spread = spread;
+        self
+    }
+
+    pub fn with_gravity(mut self, gravity: Vector3<f32>) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_size_curve(mut self, size_curve: [f32; 4]) -> Self {
+        self.size_curve = size_curve;
+        self
+    }
+
+    pub fn with_alpha_curve(mut self, alpha_curve: [f32; 4]) -> Self {
+        self.alpha_curve = alpha_curve;
+        self
+    }
+
+    pub fn with_texture(mut self, texture: impl Into<String>) -> Self {
+        self.texture = Some(texture.into());
+        self
+    }
+
+    pub fn with_collision(mut self, collision: ParticleCollision) -> Self {
+        self.collision = collision;
+        self
+    }
+
+    pub fn with_max_particles(mut self, max_particles: u32) -> Self {
+        self.max_particles = max_particles.max(1);
+        self
+    }
+
+    /// Write the parameters of the emitter as a RON effect preset.
+    pub fn to_ron(&self) -> anyhow::Result<String> {
+        format::to_ron(&Preset::from(self))
+    }
+
+    /// Read an effect preset written by `to_ron`, the missing parameters keep their default.
+    pub fn from_ron(text: &str) -> anyhow::Result<Self> {
+        let preset: Preset = format::from_ron(text)?;
+        Ok(Self {
+            rate: preset.rate,
+            lifetime: preset.lifetime,
+            direction: preset.direction.into(),
+            speed: preset.speed,
+            spread: Deg(preset.spread),
+            gravity: preset.gravity.into(),
+            size: preset.size,
+            color: preset.color,
+            size_curve: preset.size_curve,
+            alpha_curve: preset.alpha_curve,
+            texture: preset.texture,
+            collision: preset.collision,
+            max_particles: preset.max_particles.max(1),
+            ..Default::default()
+        })
+    }
+
+    /// Load an effect preset from the VFS, or from the file system if the VFS does not have it.
+    pub fn load_preset(path: &str) -> anyhow::Result<Self> {
+        let text = if vfs::exists(path) {
+            vfs::read_to_string(path)?
+        } else {
+            std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?
+        };
+        Self::from_ron(&text)
+    }
+
+    /// Save the parameters of the emitter as an effect preset.
+    pub fn save_preset(&self, path: &str) -> anyhow::Result<()> {
+        std::fs::write(path, self.to_ron()?)
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", path, e))
+    }
+
+    /// Advance the spawn timer and get the particle slots to spawn into.
+    /// The slots wrap around the capacity, so the oldest particles are replaced first.
+    pub(crate) fn emit(&mut self, dt: f32) -> Vec<u32> {
+        if !self.enabled || self.rate <= 0.0 {
+            self.accumulator = 0.0;
+            return Vec::new();
+        }
+
+        // The capacity can change between the calls, the cursor has to stay inside it
+        let capacity = self.max_particles.max(1);
+        self.cursor %= capacity;
+        self.accumulator += dt * self.rate;
+        let count = (self.accumulator.floor() as u32).min(capacity);
+        self.accumulator -= self.accumulator.floor();
+
+        (0..count)
+            .map(|_| {
+                let slot = self.cursor;
+                self.cursor = (self.cursor + 1) % capacity;
+                slot
+            })
+            .collect()
+    }
+
+    /// Get a random initial velocity inside the spread cone.
+    pub(crate) fn random_velocity(&self, rng: &mut impl Rng) -> Vector3<f32> {
+        let forward = if self.direction.magnitude2() > 0.0 {
+            self.direction.normalize()
+        } else {
+            Vector3::unit_y()
+        };
+        let helper = if forward.y.abs() > 0.99 {
+            Vector3::unit_x()
+        } else {
+            Vector3::unit_y()
+        };
+        let right = forward.cross(helper).normalize();
+        let up = right.cross(forward);
+
+        // Uniform over the spherical cap of the cone
+        let cos_spread = Rad::from(self.spread).0.cos();
+        let cos_theta = 1.0 - rng.gen::<f32>() * (1.0 - cos_spread);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = rng.gen::<f32>() * std::f32::consts::TAU;
+
+        (forward * cos_theta + (right * phi.cos() + up * phi.sin()) * sin_theta) * self.speed
+    }
+}
+
+/// Sample a curve over the lifetime of a particle like the particle shader does.
+///
+/// # Arguments
+///
+/// * `curve` - The values at 0, 1/3, 2/3 and the end of the lifetime.
+/// * `t` - The age of the particle divided by its lifetime.
+pub fn sample_curve(curve: &[f32; 4], t: f32) -> f32 {
+    let x = t.clamp(0.0, 1.0) * 3.0;
+    let i = (x as usize).min(2);
+    let f = x - i as f32;
+    curve[i] + (curve[i + 1] - curve[i]) * f
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct ParticleRaw {
+    pub position: [f32; 3],
+    pub age: f32,
+    pub velocity: [f32; 3],
+    pub lifetime: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct EmitterUniform {
+    pub color: [f32; 4],
+    pub gravity: [f32; 3],
+    pub size: f32,
+    pub collision: u32,
+    pub restitution: f32,
+    pub textured: u32,
+    pub _padding: f32,
+    pub size_curve: [f32; 4],
+    pub alpha_curve: [f32; 4],
+}
+
+impl EmitterUniform {
+    fn new(emitter: &ParticleEmitter) -> Self {
+        let (collision, restitution) = match emitter.collision {
+            ParticleCollision::None => (0, 0.0),
+            ParticleCollision::Die => (1, 0.0),
+            ParticleCollision::Bounce { restitution } => (2, restitution),
+        };
+
+        Self {
+            color: emitter.color,
+            gravity: emitter.gravity.into(),
+            size: emitter.size,
+            collision,
+            restitution,
+            textured: emitter.texture.is_some() as u32,
+            _padding: 0.0,
+            size_curve: emitter.size_curve,
+            alpha_curve: emitter.alpha_curve,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct ParticleGlobals {
+    pub view_proj: [[f32; 4]; 4],
+    pub inv_view_proj: [[f32; 4]; 4],
+    pub camera_position: [f32; 3],
+    pub dt: f32,
+    pub camera_right: [f32; 3],
+    /// How far behind a surface a particle still collides with it.
+    pub thickness: f32,
+    pub camera_up: [f32; 3],
+    pub _padding: f32,
+}
+
+struct GpuEmitter {
+    capacity: u32,
+    /// Sized to at least the capacity, the particles past it are never simulated or drawn.
+    particles: GrowableBuffer,
+    uniform: wgpu::Buffer,
+    compute_bind_group: wgpu::BindGroup,
+    render_bind_group: wgpu::BindGroup,
+    texture: Option<String>,
+}
+
+/// The GPU particle simulation and renderer.
+/// Each frame the particles are updated in a compute pass, which collides them with the depth buffer
+/// of the previous frame, and then drawn as billboards after the base pass.
+pub(crate) struct ParticleSystem {
+    globals_buffer: wgpu::Buffer,
+    compute_globals_layout: wgpu::BindGroupLayout,
+    compute_globals: wgpu::BindGroup,
+    render_globals: wgpu::BindGroup,
+    compute_emitter_layout: wgpu::BindGroupLayout,
+    render_emitter_layout: wgpu::BindGroupLayout,
+    compute_pipeline: wgpu::ComputePipeline,
+    render_pipeline: wgpu::RenderPipeline,
+    emitters: HashMap<Entity, GpuEmitter>,
+    /// Bound for the emitters without a texture.
+    white_texture: wgpu::BindGroup,
+    textures: HashMap<String, wgpu::BindGroup>,
+    failed: HashSet<String>,
+}
+
+impl ParticleSystem {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+        velocity_format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        depth_view: &wgpu::TextureView,
+    ) -> Self {
+        let uniform_entry = |binding, visibility| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage_entry = |binding, visibility, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        // The depth texture is an attachment of the base pass, so it is only bound for the compute pass
+        let compute_globals_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    uniform_entry(0, wgpu::ShaderStages::COMPUTE),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("particle_compute_globals_layout"),
+            });
+        let render_globals_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[uniform_entry(0, wgpu::ShaderStages::VERTEX)],
+                label: Some("particle_render_globals_layout"),
+            });
+        let compute_emitter_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    storage_entry(0, wgpu::ShaderStages::COMPUTE, false),
+                    uniform_entry(1, wgpu::ShaderStages::COMPUTE),
+                ],
+                label: Some("particle_compute_emitter_layout"),
+            });
+        let render_emitter_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    storage_entry(0, wgpu::ShaderStages::VERTEX, true),
+                    uniform_entry(1, wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT),
+                ],
+                label: Some("particle_render_emitter_layout"),
+            });
+
+        let globals_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Globals Buffer"),
+            contents: bytemuck::cast_slice(&[<ParticleGlobals as bytemuck::Zeroable>::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let compute_globals = Self::create_compute_globals(
+            device,
+            &compute_globals_layout,
+            &globals_buffer,
+            depth_view,
+        );
+        let render_globals = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &render_globals_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: globals_buffer.as_entire_binding(),
+            }],
+            label: Some("particle_render_globals"),
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Particle Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("particles.wgsl").into()),
+        });
+
+        let compute_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Particle Compute Pipeline Layout"),
+            bind_group_layouts: &[&compute_globals_layout, &compute_emitter_layout],
+            push_constant_ranges: &[],
+        });
+        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Particle Compute Pipeline"),
+            layout: Some(&compute_layout),
+            module: &shader,
+            entry_point: "cs_main",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let render_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Particle Render Pipeline Layout"),
+            bind_group_layouts: &[
+                &render_globals_layout,
+                &render_emitter_layout,
+                texture_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Particle Render Pipeline"),
+            layout: Some(&render_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    // Particles keep the velocity of the geometry behind them
+                    Some(wgpu::ColorTargetState {
+                        format: velocity_format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::empty(),
+                    }),
+                ],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let white = image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4]));
+        let white = texture::Texture::from_mips(device, queue, &[white], Some("Particle White"))
+            .expect("A 1x1 texture can always be created");
+        let white_texture =
+            Self::create_texture_bind_group(device, texture_bind_group_layout, &white);
+
+        Self {
+            globals_buffer,
+            compute_globals_layout,
+            compute_globals,
+            render_globals,
+            compute_emitter_layout,
+            render_emitter_layout,
+            compute_pipeline,
+            render_pipeline,
+            emitters: HashMap::new(),
+            white_texture,
+            textures: HashMap::new(),
+            failed: HashSet::new(),
+        }
+    }
+
+    fn create_texture_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        texture: &texture::Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: Some("particle_texture_bind_group"),
+        })
+    }
+
+    fn create_compute_globals(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        globals_buffer: &wgpu::Buffer,
+        depth_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: globals_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+            ],
+            label: Some("particle_compute_globals"),
+        })
+    }
+
+    fn create_emitter(&self, device: &wgpu::Device, emitter: &ParticleEmitter) -> GpuEmitter {
+        let capacity = emitter.max_particles.max(1);
+        // New buffers are zeroed, so all the particles start dead
+        let particles = GrowableBuffer::new(
+            device,
+            "Particle Buffer",
+            wgpu::BufferUsages::STORAGE,
+            Self::particles_size(capacity),
+        );
+        let uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Emitter Buffer"),
+            contents: bytemuck::cast_slice(&[EmitterUniform::new(emitter)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let (compute_bind_group, render_bind_group) =
+            self.create_emitter_bind_groups(device, &particles, &uniform);
+
+        GpuEmitter {
+            capacity,
+            particles,
+            uniform,
+            compute_bind_group,
+            render_bind_group,
+            texture: None,
+        }
+    }
+
+    fn particles_size(capacity: u32) -> u64 {
+        capacity as u64 * std::mem::size_of::<ParticleRaw>() as u64
+    }
+
+    /// Create the compute and the render bind groups of an emitter.
+    fn create_emitter_bind_groups(
+        &self,
+        device: &wgpu::Device,
+        particles: &GrowableBuffer,
+        uniform: &wgpu::Buffer,
+    ) -> (wgpu::BindGroup, wgpu::BindGroup) {
+        let create_bind_group = |layout, label| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: particles.buffer().as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: uniform.as_entire_binding(),
+                    },
+                ],
+                label: Some(label),
+            })
+        };
+
+        (
+            create_bind_group(&self.compute_emitter_layout, "particle_compute_emitter"),
+            create_bind_group(&self.render_emitter_layout, "particle_render_emitter"),
+        )
+    }
+
+    /// Clear the particles of an emitter whose capacity changed.
+    fn resize_emitter(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        gpu: &mut GpuEmitter,
+        capacity: u32,
+    ) {
+        gpu.capacity = capacity;
+        let particles = vec![<ParticleRaw as bytemuck::Zeroable>::zeroed(); capacity as usize];
+        if gpu
+            .particles
+            .write(device, queue, bytemuck::cast_slice(&particles))
+        {
+            (gpu.compute_bind_group, gpu.render_bind_group) =
+                self.create_emitter_bind_groups(device, &gpu.particles, &gpu.uniform);
+        }
+    }
+
+    /// Get the size of the particle buffers in bytes.
+    pub fn gpu_memory(&self) -> u64 {
+        self.globals_buffer.size()
+            + self
+                .emitters
+                .values()
+                .map(|gpu| gpu.particles.size() + gpu.uniform.size())
+                .sum::<u64>()
+    }
+
+    /// Rebind the depth texture after it has been recreated.
+    pub fn resize(&mut self, device: &wgpu::Device, depth_view: &wgpu::TextureView) {
+        self.compute_globals = Self::create_compute_globals(
+            device,
+            &self.compute_globals_layout,
+            &self.globals_buffer,
+            depth_view,
+        );
+    }
+
+    /// Get the textures of the emitters which are not loaded yet.
+    pub fn missing_textures(&self, ecs: &ecs::Manager) -> HashSet<String> {
+        ecs.get_all_components_of_type::<ParticleEmitter>()
+            .iter()
+            .filter_map(|(_, emitter)| emitter.read().unwrap().texture.clone())
+            .filter(|path| !self.textures.contains_key(path) && !self.failed.contains(path))
+            .collect()
+    }
+
+    /// Load the textures returned by `missing_textures`, the ECS does not have to stay locked meanwhile.
+    pub async fn load_textures(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        paths: HashSet<String>,
+    ) {
+        for path in paths {
+            match resources::load_texture(&path, device, queue).await {
+                Ok(texture) => {
+                    let bind_group = Self::create_texture_bind_group(
+                        device,
+                        texture_bind_group_layout,
+                        &texture,
+                    );
+                    self.textures.insert(path, bind_group);
+                }
+                Err(e) => {
+                    log::warn!("[Particles] Failed to load the texture {}: {}", path, e);
+                    self.failed.insert(path);
+                }
+            }
+        }
+    }
+
+    /// Sync the emitters with the ECS and spawn the new particles.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        ecs: &ecs::Manager,
+        camera: &Camera,
+        projection: &Projection,
+        dt: f32,
+    ) {
+        let view = camera.calc_matrix();
+        let view_proj = projection.calc_matrix() * view;
+        let globals = ParticleGlobals {
+            view_proj: view_proj.into(),
+            inv_view_proj: view_proj.invert().unwrap_or(Matrix4::identity()).into(),
+            camera_position: camera.position.into(),
+            dt,
+            camera_right: [view.x.x, view.y.x, view.z.x],
+            thickness: 0.5,
+            camera_up: [view.x.y, view.y.y, view.z.y],
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.globals_buffer, 0, bytemuck::cast_slice(&[globals]));
+
+        let emitters = ecs.get_all_components_of_type::<ParticleEmitter>();
+        self.emitters
+            .retain(|entity, _| emitters.iter().any(|(e, _)| e == entity));
+
+        let mut rng = rand::thread_rng();
+        for (entity, emitter) in emitters {
+            let Some(pos) = ecs.get_component_from_entity::<Pos3>(entity) else {
+                continue;
+            };
+            let origin = pos.read().unwrap().pos;
+            let mut emitter = emitter.write().unwrap();
+
+            let mut gpu = match self.emitters.remove(&entity) {
+                Some(gpu) => gpu,
+                None => self.create_emitter(device, &emitter),
+            };
+            let capacity = emitter.max_particles.max(1);
+            if gpu.capacity != capacity {
+                self.resize_emitter(device, queue, &mut gpu, capacity);
+            }
+            gpu.texture = emitter
+                .texture
+                .clone()
+                .filter(|path| self.textures.contains_key(path));
+            // A texture that failed to load falls back to the soft dot
+            let mut uniform = EmitterUniform::new(&emitter);
+            uniform.textured = gpu.texture.is_some() as u32;
+            queue.write_buffer(&gpu.uniform, 0, bytemuck::cast_slice(&[uniform]));
+
+            for slot in emitter.emit(dt) {
+                let particle = ParticleRaw {
+                    position: origin.into(),
+                    age: 0.0,
+                    velocity: emitter.random_velocity(&mut rng).into(),
+                    lifetime: emitter.lifetime,
+                };
+                queue.write_buffer(
+                    gpu.particles.buffer(),
+                    Self::particles_size(slot),
+                    bytemuck::cast_slice(&[particle]),
+                );
+            }
+            self.emitters.insert(entity, gpu);
+        }
+    }
+
+    /// Simulate the particles, must be recorded before the base pass writes the depth buffer.
+    pub fn simulate(&self, encoder: &mut wgpu::CommandEncoder) {
+        if self.emitters.is_empty() {
+            return;
+        }
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Particle Compute Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.compute_pipeline);
+        compute_pass.set_bind_group(0, &self.compute_globals, &[]);
+
+        for gpu in self.emitters.values() {
+            compute_pass.set_bind_group(1, &gpu.compute_bind_group, &[]);
+            compute_pass.dispatch_workgroups(gpu.capacity.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+    }
+
+    /// Draw the particles over the scene, they are depth tested against but do not write the depth.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        velocity_view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+    ) {
+        if self.emitters.is_empty() {
+            return;
+        }
+
+        let load = |view| {
+            Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })
+        };
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Particle Render Pass"),
+            color_attachments: &[load(color_view), load(velocity_view)],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.render_globals, &[]);
+
+        for gpu in self.emitters.values() {
+            let texture = gpu
+                .texture
+                .as_ref()
+                .and_then(|path| self.textures.get(path));
+            render_pass.set_bind_group(1, &gpu.render_bind_group, &[]);
+            render_pass.set_bind_group(2, texture.unwrap_or(&self.white_texture), &[]);
+            render_pass.draw(0..6, 0..gpu.capacity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emit_wraps_around_capacity() {
+        let mut emitter = ParticleEmitter::new(10.0, 1.0).with_max_particles(4);
+
+        assert_eq!(emitter.emit(0.25), vec![0, 1]);
+        assert_eq!(emitter.emit(0.25), vec![2, 3, 0]);
+
+        emitter.enabled = false;
+        assert!(emitter.emit(1.0).is_empty());
+    }
+
+    #[test]
+    fn test_emit_after_capacity_change() {
+        let mut emitter = ParticleEmitter::new(10.0, 1.0).with_max_particles(8);
+        assert_eq!(emitter.emit(0.5), vec![0, 1, 2, 3, 4]);
+
+        // The slots stay inside the smaller capacity
+        emitter.max_particles = 2;
+        assert_eq!(emitter.emit(0.3), vec![1, 0]);
+        emitter.max_particles = 0;
+        assert_eq!(emitter.emit(0.1), vec![0]);
+    }
+
+    #[test]
+    fn test_velocity_inside_spread() {
+        let emitter = ParticleEmitter::default().with_velocity(Vector3::unit_x(), 3.0, Deg(30.0));
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..100 {
+            let velocity = emitter.random_velocity(&mut rng);
+            assert!((velocity.magnitude() - 3.0).abs() < 1e-4);
+            let angle = Deg::from(velocity.angle(Vector3::unit_x()));
+            assert!(angle.0 <= 30.0 + 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_preset_roundtrip() {
+        let emitter = ParticleEmitter::new(120.0, 0.75)
+            .with_velocity(Vector3::new(0.0, 0.5, 1.0), 4.5, Deg(35.0))
+            .with_color([1.0, 0.5, 0.25, 0.8])
+            .with_size_curve([0.5, 1.5, 1.0, 0.0])
+            .with_texture("res/textures/spark.png")
+            .with_collision(ParticleCollision::Bounce { restitution: 0.3 })
+            .with_max_particles(256);
+        let preset = ParticleEmitter::from_ron(&emitter.to_ron().unwrap()).unwrap();
+        assert_eq!(preset, emitter);
+
+        // The missing parameters keep their default
+        let preset = ParticleEmitter::from_ron("ParticleEmitter(rate: 5, collision: Die)").unwrap();
+        assert_eq!(preset.rate, 5.0);
+        assert_eq!(preset.collision, ParticleCollision::Die);
+        assert_eq!(preset.alpha_curve, ParticleEmitter::default().alpha_curve);
+        assert!(ParticleEmitter::from_ron("ParticleEmitter(color: (1, 1))").is_err());
+    }
+
+    #[test]
+    fn test_sample_curve() {
+        let curve = [0.0, 3.0, 3.0, 6.0];
+        assert_eq!(sample_curve(&curve, 0.0), 0.0);
+        assert_eq!(sample_curve(&curve, 1.0 / 6.0), 1.5);
+        assert_eq!(sample_curve(&curve, 0.5), 3.0);
+        assert_eq!(sample_curve(&curve, 1.0), 6.0);
+        assert_eq!(sample_curve(&curve, 2.0), 6.0);
+    }
+}
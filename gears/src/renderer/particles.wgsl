@@ -0,0 +1,191 @@
+// GPU particles: simulation with depth buffer collision and billboard rendering
+
+struct Particle {
+    position: vec3<f32>,
+    age: f32,
+    velocity: vec3<f32>,
+    lifetime: f32,
+}
+
+struct Emitter {
+    color: vec4<f32>,
+    gravity: vec3<f32>,
+    size: f32,
+    collision: u32,
+    restitution: f32,
+    textured: u32,
+    _padding: f32,
+    // Multipliers at 0, 1/3, 2/3 and the end of the lifetime
+    size_curve: vec4<f32>,
+    alpha_curve: vec4<f32>,
+}
+
+struct Globals {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    dt: f32,
+    camera_right: vec3<f32>,
+    thickness: f32,
+    camera_up: vec3<f32>,
+    _padding: f32,
+}
+
+const COLLISION_DIE: u32 = 1u;
+const COLLISION_BOUNCE: u32 = 2u;
+
+// Compute shader
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+@group(0) @binding(1)
+var t_depth: texture_depth_2d;
+
+@group(1) @binding(0)
+var<storage, read_write> particles: array<Particle>;
+@group(1) @binding(1)
+var<uniform> emitter: Emitter;
+
+fn world_position(coord: vec2<i32>, size: vec2<i32>) -> vec3<f32> {
+    let depth = textureLoad(t_depth, clamp(coord, vec2<i32>(0), size - 1), 0);
+    let uv = (vec2<f32>(coord) + 0.5) / vec2<f32>(size);
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = globals.inv_view_proj * ndc;
+    return world.xyz / world.w;
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= arrayLength(&particles) {
+        return;
+    }
+
+    var particle = particles[index];
+    if particle.age >= particle.lifetime {
+        return;
+    }
+
+    particle.age += globals.dt;
+    particle.velocity += emitter.gravity * globals.dt;
+    let previous = particle.position;
+    particle.position += particle.velocity * globals.dt;
+
+    // Screen-space collision: a particle hits the scene if it is just behind the visible surface
+    let clip = globals.view_proj * vec4<f32>(particle.position, 1.0);
+    let ndc = clip.xyz / clip.w;
+    if emitter.collision != 0u && clip.w > 0.0 && all(abs(ndc.xy) < vec2<f32>(1.0)) {
+        let size = vec2<i32>(textureDimensions(t_depth));
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        let coord = vec2<i32>(uv * vec2<f32>(size));
+        let surface = world_position(coord, size);
+
+        let particle_distance = distance(particle.position, globals.camera_position);
+        let surface_distance = distance(surface, globals.camera_position);
+        let penetration = particle_distance - surface_distance;
+
+        if penetration > 0.0 && penetration < globals.thickness {
+            if emitter.collision == COLLISION_DIE {
+                particle.age = particle.lifetime;
+            } else if emitter.collision == COLLISION_BOUNCE {
+                // Reconstruct the surface normal from the neighbouring depth samples
+                let right = world_position(coord + vec2<i32>(1, 0), size) - surface;
+                let down = world_position(coord + vec2<i32>(0, 1), size) - surface;
+                var normal = normalize(cross(right, down));
+                if dot(normal, globals.camera_position - surface) < 0.0 {
+                    normal = -normal;
+                }
+
+                particle.position = previous;
+                if dot(particle.velocity, normal) < 0.0 {
+                    particle.velocity = reflect(particle.velocity, normal) * emitter.restitution;
+                }
+            }
+        }
+    }
+
+    particles[index] = particle;
+}
+
+// Render shader
+
+@group(0) @binding(0)
+var<uniform> render_globals: Globals;
+
+@group(1) @binding(0)
+var<storage, read> render_particles: array<Particle>;
+@group(1) @binding(1)
+var<uniform> render_emitter: Emitter;
+
+@group(2) @binding(0)
+var t_particle: texture_2d<f32>;
+@group(2) @binding(1)
+var s_particle: sampler;
+
+fn sample_curve(curve: vec4<f32>, t: f32) -> f32 {
+    let x = clamp(t, 0.0, 1.0) * 3.0;
+    let i = min(u32(x), 2u);
+    return mix(curve[i], curve[i + 1u], x - f32(i));
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) corner: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) velocity: vec2<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+    let particle = render_particles[instance_index];
+
+    var out: VertexOutput;
+    out.corner = corner;
+
+    // Dead particles are collapsed outside of the clip volume
+    if particle.age >= particle.lifetime {
+        out.clip_position = vec4<f32>(0.0, 0.0, -1.0, 1.0);
+        out.color = vec4<f32>(0.0);
+        return out;
+    }
+
+    let t = particle.age / max(particle.lifetime, 0.0001);
+    let size = render_emitter.size * sample_curve(render_emitter.size_curve, t);
+    let offset = (render_globals.camera_right * corner.x + render_globals.camera_up * corner.y) * size;
+    out.clip_position = render_globals.view_proj * vec4<f32>(particle.position + offset, 1.0);
+
+    let alpha = sample_curve(render_emitter.alpha_curve, t);
+    out.color = vec4<f32>(render_emitter.color.rgb, render_emitter.color.a * alpha);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let uv = vec2<f32>(in.corner.x, -in.corner.y) * 0.5 + 0.5;
+    let texel = textureSample(t_particle, s_particle, uv);
+    var falloff = 1.0 - smoothstep(0.6, 1.0, length(in.corner));
+    if render_emitter.textured != 0u {
+        falloff = 1.0;
+    }
+
+    var out: FragmentOutput;
+    out.color = in.color * texel * vec4<f32>(1.0, 1.0, 1.0, falloff);
+    out.velocity = vec2<f32>(0.0);
+    return out;
+}
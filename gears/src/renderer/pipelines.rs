@@ -0,0 +1,195 @@
+use super::preprocess::{self, ShaderDefines};
+use super::{instance, model, texture, State};
+use crate::ecs::traits::Component;
+use instant::{Duration, Instant};
+use model::Vertex;
+use std::collections::HashMap;
+
+/// The time the warm-up may take per frame, at least one pipeline is compiled each frame.
+const WARM_UP_BUDGET: Duration = Duration::from_millis(8);
+
+/// The shader features a model is drawn with, each combination is a separate pipeline.
+/// A model without the component is drawn with the default features.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MaterialFeatures {
+    /// The highlights of the lights, without them the model is lit diffusely, e.g. for cloth or terrain.
+    pub specular: bool,
+}
+
+impl Component for MaterialFeatures {}
+
+impl Default for MaterialFeatures {
+    fn default() -> Self {
+        Self { specular: true }
+    }
+}
+
+impl MaterialFeatures {
+    /// Get every combination of the features, the permutations compiled by the warm-up.
+    pub fn all() -> Vec<Self> {
+        [true, false]
+            .into_iter()
+            .map(|specular| Self { specular })
+            .collect()
+    }
+
+    fn defines(&self) -> ShaderDefines {
+        let defines = ShaderDefines::new().with("SHADOWS");
+        if self.specular {
+            defines.with("SPECULAR")
+        } else {
+            defines
+        }
+    }
+}
+
+/// The progress of the pipeline warm-up, a resource updated while the permutations are compiled
+/// in the first frames, the `LoadingScreen` covers the frame until it is done.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PipelineWarmUp {
+    pub compiled: usize,
+    pub total: usize,
+}
+
+impl PipelineWarmUp {
+    pub fn is_done(&self) -> bool {
+        self.compiled >= self.total
+    }
+
+    /// Get the fraction of the pipelines compiled from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        self.compiled as f32 / self.total as f32
+    }
+}
+
+/// The pipelines of the models by their material features.
+///
+/// Compiling a pipeline the first time a model needs it stalls the frame, so every permutation
+/// is compiled ahead by the warm-up, a few per frame while loading.
+pub(crate) struct ModelPipelines {
+    layout: wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    pipelines: HashMap<MaterialFeatures, wgpu::RenderPipeline>,
+    /// The permutations the warm-up did not compile yet.
+    pending: Vec<MaterialFeatures>,
+    total: usize,
+    /// The time spent compiling by the warm-up.
+    busy: Duration,
+}
+
+impl ModelPipelines {
+    /// Create the pipeline of the default features, the rest are compiled by `warm_up`.
+    pub fn new(
+        device: &wgpu::Device,
+        layout: wgpu::PipelineLayout,
+        color_format: wgpu::TextureFormat,
+    ) -> Self {
+        let pending = MaterialFeatures::all();
+        let mut pipelines = Self {
+            layout,
+            color_format,
+            pipelines: HashMap::new(),
+            total: pending.len(),
+            pending,
+            busy: Duration::ZERO,
+        };
+        pipelines.compile(device, MaterialFeatures::default());
+        pipelines
+    }
+
+    fn compile(&mut self, device: &wgpu::Device, features: MaterialFeatures) {
+        self.pending.retain(|pending| *pending != features);
+        if self.pipelines.contains_key(&features) {
+            return;
+        }
+        let shader = preprocess::shader_module("Normal Shader", "shader", &features.defines());
+        let pipeline = State::create_render_pipeline(
+            device,
+            &self.layout,
+            self.color_format,
+            Some(texture::Texture::DEPTH_FORMAT),
+            &[model::ModelVertex::desc(), instance::InstanceRaw::desc()],
+            shader,
+        );
+        self.pipelines.insert(features, pipeline);
+    }
+
+    pub fn progress(&self) -> PipelineWarmUp {
+        PipelineWarmUp {
+            compiled: self.total - self.pending.len(),
+            total: self.total,
+        }
+    }
+
+    /// Compile the pending permutations until the budget of the frame is used up.
+    ///
+    /// # Returns
+    ///
+    /// The progress after the frame, `None` if the warm-up was already done.
+    pub fn warm_up(&mut self, device: &wgpu::Device) -> Option<PipelineWarmUp> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let start = Instant::now();
+        while let Some(&features) = self.pending.first() {
+            self.compile(device, features);
+            if start.elapsed() >= WARM_UP_BUDGET {
+                break;
+            }
+        }
+        self.busy += start.elapsed();
+
+        let progress = self.progress();
+        if progress.is_done() {
+            log::info!(
+                "[Renderer] Warmed up {} model pipelines in {:?}",
+                progress.total,
+                self.busy
+            );
+        }
+        Some(progress)
+    }
+
+    /// Make sure the pipeline of a permutation exists before drawing with it,
+    /// it is compiled now if the warm-up did not reach it yet.
+    pub fn prepare(&mut self, device: &wgpu::Device, features: MaterialFeatures) {
+        if !self.pipelines.contains_key(&features) {
+            log::debug!(
+                "[Renderer] Compiling the {:?} pipeline on first use, ahead of the warm-up",
+                features
+            );
+            self.compile(device, features);
+        }
+    }
+
+    /// Get the pipeline of a permutation, `prepare` has to be called for it first.
+    pub fn get(&self, features: MaterialFeatures) -> &wgpu::RenderPipeline {
+        &self.pipelines[&features]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permutations() {
+        let all = MaterialFeatures::all();
+        assert!(all.contains(&MaterialFeatures::default()));
+        for (i, features) in all.iter().enumerate() {
+            assert!(!all[i + 1..].contains(features));
+            // Every permutation expands, so the warm-up does not fail on one
+            assert!(preprocess::shader_source("shader", &features.defines()).is_ok());
+        }
+
+        let progress = PipelineWarmUp {
+            compiled: 1,
+            total: 2,
+        };
+        assert!(!progress.is_done());
+        assert_eq!(progress.progress(), 0.5);
+    }
+}
@@ -0,0 +1,213 @@
+use cgmath::{EuclideanSpace, Point3};
+use wgpu::util::DeviceExt;
+
+use super::camera::{Camera, CameraUniform, Projection};
+use super::model::Material;
+use super::overrides::OverrideMaterials;
+use super::texture;
+use crate::ecs::{self, components, Entity};
+use crate::gameplay::teleporter::{self, PortalView, Teleporter, TeleporterExit};
+
+/// The view through a teleporter with a `PortalView`, drawn into a texture before the frame.
+pub(crate) struct Portal {
+    /// The teleporter the view is drawn on.
+    pub entity: Entity,
+    size: (u32, u32),
+    uniform: CameraUniform,
+    buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    /// The index of the material of the texture in the override materials.
+    pub material: usize,
+}
+
+/// The views of the enabled teleporters with a `PortalView` and an exit.
+#[derive(Default)]
+pub(crate) struct Portals {
+    portals: Vec<Portal>,
+    /// The number of views drawn this frame, the textures of the rest are kept for later.
+    active: usize,
+}
+
+impl Portals {
+    pub fn active(&self) -> &[Portal] {
+        &self.portals[..self.active]
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active > 0
+    }
+
+    /// Get the view drawn on a teleporter this frame.
+    pub fn get(&self, entity: Entity) -> Option<&Portal> {
+        self.active().iter().find(|portal| portal.entity == entity)
+    }
+
+    /// Update the cameras of the views from the main camera, a texture is created for each new view.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - The device the textures are created on.
+    /// * `queue` - The queue the cameras are written with.
+    /// * `layouts` - The layouts of the camera and the material bind groups.
+    /// * `materials` - The override materials the textures are added to.
+    /// * `ecs` - The entity component system manager.
+    /// * `camera` - The main camera.
+    /// * `projection` - The projection of the main camera, the views keep its field of view.
+    /// * `format` - The format of the frames.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        (camera_layout, material_layout): (&wgpu::BindGroupLayout, &wgpu::BindGroupLayout),
+        materials: &mut OverrideMaterials,
+        ecs: &ecs::Manager,
+        camera: &Camera,
+        projection: &Projection,
+        format: wgpu::TextureFormat,
+    ) {
+        let eye = camera.position.to_vec();
+        let views = ecs
+            .get_all_components_of_type::<PortalView>()
+            .into_iter()
+            .filter_map(|(entity, view)| {
+                let teleporter = *ecs
+                    .get_component_from_entity::<Teleporter>(entity)?
+                    .read()
+                    .unwrap();
+                if !teleporter.enabled {
+                    return None;
+                }
+                let entrance = ecs.get_component_from_entity::<components::Pos3>(entity)?;
+                let exit = ecs.get_component_from_entity::<components::Pos3>(teleporter.exit)?;
+                let offset = ecs
+                    .get_component_from_entity::<TeleporterExit>(teleporter.exit)?
+                    .read()
+                    .unwrap()
+                    .offset;
+                let (eye, forward) = teleporter::view_through(
+                    &entrance.read().unwrap(),
+                    &exit.read().unwrap(),
+                    offset,
+                    eye,
+                    camera.forward(),
+                );
+                let camera =
+                    Camera::new_look_at(Point3::from_vec(eye), Point3::from_vec(eye + forward));
+                // The fields are public, so the size is checked again
+                let view = *view.read().unwrap();
+                Some((entity, PortalView::new(view.width, view.height), camera))
+            })
+            .collect::<Vec<_>>();
+        self.active = views.len();
+
+        for (i, (entity, view, camera)) in views.iter().enumerate() {
+            if i == self.portals.len() {
+                let portal = Self::create_portal(
+                    device,
+                    (camera_layout, material_layout),
+                    materials,
+                    *entity,
+                    *view,
+                    format,
+                );
+                self.portals.push(portal);
+            }
+            let portal = &mut self.portals[i];
+            if portal.size != (view.width, view.height) {
+                let target = Self::create_target(device, material_layout, *view, format);
+                materials.replace(portal.material, target);
+                portal.size = (view.width, view.height);
+            }
+            portal.entity = *entity;
+
+            let projection = Projection::new(
+                view.width,
+                view.height,
+                projection.fovy(),
+                projection.znear(),
+                projection.zfar(),
+            );
+            portal.uniform.update_view_proj(camera, &projection);
+            queue.write_buffer(&portal.buffer, 0, bytemuck::cast_slice(&[portal.uniform]));
+        }
+    }
+
+    fn create_portal(
+        device: &wgpu::Device,
+        (camera_layout, material_layout): (&wgpu::BindGroupLayout, &wgpu::BindGroupLayout),
+        materials: &mut OverrideMaterials,
+        entity: Entity,
+        view: PortalView,
+        format: wgpu::TextureFormat,
+    ) -> Portal {
+        let uniform = CameraUniform::new();
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Portal Camera Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("portal_camera_bind_group"),
+        });
+        let material = materials.add(Self::create_target(device, material_layout, view, format));
+
+        Portal {
+            entity,
+            size: (view.width, view.height),
+            uniform,
+            buffer,
+            bind_group,
+            material,
+        }
+    }
+
+    /// Create the texture a view is drawn into, as the material its teleporter is drawn with.
+    fn create_target(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        view: PortalView,
+        format: wgpu::TextureFormat,
+    ) -> Material {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Portal Texture"),
+            size: wgpu::Extent3d {
+                width: view.width,
+                height: view.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let diffuse_texture = texture::Texture {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            texture,
+            sampler,
+        };
+        let bind_group = Material::create_bind_group(device, layout, &diffuse_texture);
+
+        Material {
+            name: "Portal".to_string(),
+            diffuse_texture,
+            bind_group,
+            stream: None,
+        }
+    }
+}
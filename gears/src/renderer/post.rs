@@ -0,0 +1,424 @@
+use crate::ecs::traits::Component;
+use wgpu::util::DeviceExt;
+
+/// The depth of field effect, blurs everything outside of the focus distance.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DepthOfField {
+    pub enabled: bool,
+    /// The distance from the camera that is in focus.
+    pub focus_distance: f32,
+    /// The distance from the focus at which the blur reaches its maximum.
+    pub focus_range: f32,
+    /// Scales the blur, a larger aperture gives a shallower depth of field.
+    pub aperture: f32,
+    /// The maximum blur radius in pixels.
+    pub max_blur: f32,
+}
+
+impl Default for DepthOfField {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            focus_distance: 10.0,
+            focus_range: 10.0,
+            aperture: 1.0,
+            max_blur: 8.0,
+        }
+    }
+}
+
+/// The motion blur effect, smears pixels along their screen-space velocity.
+/// Both camera and object motion are blurred, see `NoMotionBlur` to exclude an entity.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MotionBlur {
+    pub enabled: bool,
+    /// The fraction of the frame's motion that is blurred, like the shutter angle of a camera.
+    pub strength: f32,
+    /// The number of samples taken along the velocity.
+    pub samples: u32,
+    /// The maximum blur length in pixels.
+    pub max_blur: f32,
+}
+
+impl Default for MotionBlur {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: 0.5,
+            samples: 8,
+            max_blur: 32.0,
+        }
+    }
+}
+
+/// The tone mapping of the HDR scene into the range of the display, an ACES filmic curve.
+/// It is only used when the scene is rendered with the `hdr` pass.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tonemapping {
+    /// Scales the scene color before it is mapped, a higher exposure brightens the frame.
+    pub exposure: f32,
+}
+
+impl Default for Tonemapping {
+    fn default() -> Self {
+        Self { exposure: 1.0 }
+    }
+}
+
+/// The settings of the post-process chain.
+/// Add it to any entity to adjust the effects at runtime, the first one found is used.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct PostProcessSettings {
+    pub depth_of_field: DepthOfField,
+    pub motion_blur: MotionBlur,
+    pub tonemapping: Tonemapping,
+}
+
+impl Component for PostProcessSettings {}
+
+/// A marker component that excludes an entity from the motion blur, e.g. a first-person weapon.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct NoMotionBlur;
+
+impl Component for NoMotionBlur {}
+
+crate::impl_marker!(NoMotionBlur, "is excluded from the motion blur");
+
+/// The format of the velocity buffer written by the base pass.
+pub(crate) const VELOCITY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+/// The format of the scene color with the `hdr` pass, the colors above 1 are kept for the tone mapping.
+pub(crate) const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct PostUniform {
+    pub focus_distance: f32,
+    pub focus_range: f32,
+    pub max_blur: f32,
+    pub dof_enabled: f32,
+    pub znear: f32,
+    pub zfar: f32,
+    pub texel_size: [f32; 2],
+    pub motion_blur_enabled: f32,
+    pub motion_blur_strength: f32,
+    pub motion_blur_samples: f32,
+    pub motion_blur_max: f32,
+    pub exposure: f32,
+    pub tonemap_enabled: f32,
+    pub _padding: [f32; 2],
+}
+
+/// The post-process chain. The scene is rendered into an offscreen color target,
+/// which is then processed into the surface.
+pub(crate) struct PostProcess {
+    /// The format of the scene color, `HDR_FORMAT` if it is tone mapped.
+    scene_format: wgpu::TextureFormat,
+    color_view: wgpu::TextureView,
+    velocity_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl PostProcess {
+    /// Set up the chain and its targets.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - The device.
+    /// * `config` - The surface configuration, the chain writes its format.
+    /// * `scene_format` - The format the scene is rendered in, `HDR_FORMAT` to tone map it.
+    /// * `depth_view` - The depth of the scene.
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        scene_format: wgpu::TextureFormat,
+        depth_view: &wgpu::TextureView,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("post_bind_group_layout"),
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[<PostUniform as bytemuck::Zeroable>::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Post Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("post.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Post Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let color_view = Self::create_target_view(device, config, scene_format, "Post Color");
+        let velocity_view =
+            Self::create_target_view(device, config, VELOCITY_FORMAT, "Post Velocity");
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            &color_view,
+            &velocity_view,
+            &sampler,
+            depth_view,
+            &uniform_buffer,
+        );
+
+        Self {
+            scene_format,
+            color_view,
+            velocity_view,
+            sampler,
+            bind_group_layout,
+            bind_group,
+            uniform_buffer,
+            pipeline,
+        }
+    }
+
+    fn create_target_view(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> wgpu::TextureView {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width.max(1),
+                height: config.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        color_view: &wgpu::TextureView,
+        velocity_view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        depth_view: &wgpu::TextureView,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(color_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(velocity_view),
+                },
+            ],
+            label: Some("post_bind_group"),
+        })
+    }
+
+    /// Recreate the offscreen targets after the surface or the depth texture has changed.
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+    ) {
+        self.color_view = Self::create_target_view(device, config, self.scene_format, "Post Color");
+        self.velocity_view =
+            Self::create_target_view(device, config, VELOCITY_FORMAT, "Post Velocity");
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.color_view,
+            &self.velocity_view,
+            &self.sampler,
+            depth_view,
+            &self.uniform_buffer,
+        );
+    }
+
+    /// Get the format of the view the scene is rendered into.
+    pub fn scene_format(&self) -> wgpu::TextureFormat {
+        self.scene_format
+    }
+
+    /// Get the view the scene should be rendered into.
+    pub fn color_view(&self) -> &wgpu::TextureView {
+        &self.color_view
+    }
+
+    /// Get the view the base pass writes the screen-space velocity into.
+    pub fn velocity_view(&self) -> &wgpu::TextureView {
+        &self.velocity_view
+    }
+
+    pub fn update(
+        &self,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        settings: &PostProcessSettings,
+        znear: f32,
+        zfar: f32,
+    ) {
+        let dof = &settings.depth_of_field;
+        let motion_blur = &settings.motion_blur;
+        let uniform = PostUniform {
+            focus_distance: dof.focus_distance,
+            focus_range: dof.focus_range,
+            max_blur: dof.max_blur * dof.aperture,
+            dof_enabled: if dof.enabled { 1.0 } else { 0.0 },
+            znear,
+            zfar,
+            texel_size: [
+                1.0 / config.width.max(1) as f32,
+                1.0 / config.height.max(1) as f32,
+            ],
+            motion_blur_enabled: if motion_blur.enabled { 1.0 } else { 0.0 },
+            motion_blur_strength: motion_blur.strength,
+            motion_blur_samples: motion_blur.samples.max(1) as f32,
+            motion_blur_max: motion_blur.max_blur,
+            exposure: settings.tonemapping.exposure.max(0.0),
+            tonemap_enabled: if self.scene_format == HDR_FORMAT {
+                1.0
+            } else {
+                0.0
+            },
+            _padding: [0.0; 2],
+        };
+
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    /// Run the chain and write the result into the target.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Post Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,132 @@
+// Post-process pass: depth of field, motion blur and the tone mapping of the HDR scene
+
+struct PostUniform {
+    focus_distance: f32,
+    focus_range: f32,
+    max_blur: f32,
+    dof_enabled: f32,
+    znear: f32,
+    zfar: f32,
+    texel_size: vec2<f32>,
+    motion_blur_enabled: f32,
+    motion_blur_strength: f32,
+    motion_blur_samples: f32,
+    motion_blur_max: f32,
+    exposure: f32,
+    tonemap_enabled: f32,
+    _padding: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var t_color: texture_2d<f32>;
+@group(0) @binding(1)
+var s_color: sampler;
+@group(0) @binding(2)
+var t_depth: texture_depth_2d;
+@group(0) @binding(3)
+var<uniform> post: PostUniform;
+@group(0) @binding(4)
+var t_velocity: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// A single triangle covering the whole screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let pos = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(pos * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(pos.x, 1.0 - pos.y);
+    return out;
+}
+
+fn linear_depth(uv: vec2<f32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(t_depth));
+    let coord = clamp(vec2<i32>(uv * vec2<f32>(size)), vec2<i32>(0), size - 1);
+    let depth = textureLoad(t_depth, coord, 0);
+
+    return post.znear * post.zfar / (post.zfar - depth * (post.zfar - post.znear));
+}
+
+// The radius of the circle of confusion in pixels
+fn circle_of_confusion(uv: vec2<f32>) -> f32 {
+    let distance = abs(linear_depth(uv) - post.focus_distance);
+    return clamp(distance / max(post.focus_range, 0.0001), 0.0, 1.0) * post.max_blur;
+}
+
+const SAMPLE_COUNT: i32 = 32;
+const GOLDEN_ANGLE: f32 = 2.39996323;
+
+fn depth_of_field(uv: vec2<f32>) -> vec4<f32> {
+    let center = textureSampleLevel(t_color, s_color, uv, 0.0);
+    if post.dof_enabled < 0.5 {
+        return center;
+    }
+
+    let radius = circle_of_confusion(uv);
+    if radius < 0.5 {
+        return center;
+    }
+
+    // Gather the samples on a spiral, a sample only contributes if it is blurred enough to reach this pixel
+    var color = center.rgb;
+    var total = 1.0;
+    for (var i = 1; i < SAMPLE_COUNT; i++) {
+        let distance = radius * sqrt(f32(i) / f32(SAMPLE_COUNT));
+        let angle = f32(i) * GOLDEN_ANGLE;
+        let offset = vec2<f32>(cos(angle), sin(angle)) * distance * post.texel_size;
+        let sample_uv = uv + offset;
+
+        let sample_radius = circle_of_confusion(sample_uv);
+        let weight = clamp(sample_radius - distance + 1.0, 0.0, 1.0);
+        color += textureSampleLevel(t_color, s_color, sample_uv, 0.0).rgb * weight;
+        total += weight;
+    }
+
+    return vec4<f32>(color / total, center.a);
+}
+
+// Map the HDR color into the range of the display, the ACES filmic curve fitted by Krzysztof Narkowicz
+fn tonemap(color: vec3<f32>) -> vec3<f32> {
+    if post.tonemap_enabled < 0.5 {
+        return color;
+    }
+    let x = color * post.exposure;
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = motion_blur(in.uv, depth_of_field(in.uv));
+    return vec4<f32>(tonemap(color.rgb), color.a);
+}
+
+fn motion_blur(uv: vec2<f32>, color: vec4<f32>) -> vec4<f32> {
+    if post.motion_blur_enabled < 0.5 {
+        return color;
+    }
+
+    // Clamp the blur length in pixels so fast motion or camera cuts do not smear the whole screen
+    var velocity = textureSampleLevel(t_velocity, s_color, uv, 0.0).xy * post.motion_blur_strength;
+    let length_px = length(velocity / post.texel_size);
+    if length_px < 0.5 {
+        return color;
+    }
+    if length_px > post.motion_blur_max {
+        velocity *= post.motion_blur_max / length_px;
+    }
+
+    // Sample along the velocity centered on the pixel, the center keeps the depth of field result
+    let samples = i32(post.motion_blur_samples);
+    var result = color.rgb;
+    for (var i = 0; i < samples; i++) {
+        let t = (f32(i) + 0.5) / f32(samples) - 0.5;
+        result += textureSampleLevel(t_color, s_color, uv + velocity * t, 0.0).rgb;
+    }
+
+    return vec4<f32>(result / f32(samples + 1), color.a);
+}
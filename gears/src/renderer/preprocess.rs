@@ -0,0 +1,217 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// The shaders and the chunks they can `#include`, by name.
+const SOURCES: &[(&str, &str)] = &[
+    ("camera", include_str!("camera.wgsl")),
+    ("lighting", include_str!("lighting.wgsl")),
+    ("shadows", include_str!("shadows.wgsl")),
+    ("shader", include_str!("shader.wgsl")),
+    ("crowd", include_str!("crowd.wgsl")),
+    ("shadow_caster", include_str!("shadow_caster.wgsl")),
+    ("wireframe", include_str!("wireframe.wgsl")),
+];
+
+/// The defines a shader is built with, e.g. from the features of a material or a pipeline.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub(crate) struct ShaderDefines(BTreeSet<String>);
+
+impl ShaderDefines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, name: impl Into<String>) -> Self {
+        self.0.insert(name.into());
+        self
+    }
+}
+
+/// A `#ifdef` or `#ifndef` block being read.
+struct Condition {
+    /// The lines of the current branch are kept.
+    active: bool,
+    /// The lines around the block are kept, a block inside an inactive one stays inactive.
+    parent_active: bool,
+    has_else: bool,
+}
+
+/// Expand the directives of a WGSL shader.
+///
+/// * `#include "name"` inserts a chunk of `SOURCES`, a chunk included twice is only inserted once.
+/// * `#define NAME` adds a define for the rest of the shader.
+/// * `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif` keep the lines depending on the defines.
+///
+/// # Returns
+///
+/// An error for an unknown chunk or directive, an include cycle or an unbalanced block.
+pub(crate) fn preprocess(source: &str, defines: &ShaderDefines) -> anyhow::Result<String> {
+    let mut defines = defines.0.clone();
+    let mut included = Vec::new();
+    let mut output = String::with_capacity(source.len());
+    expand(
+        source,
+        &mut defines,
+        &mut included,
+        &mut Vec::new(),
+        &mut output,
+    )?;
+    Ok(output)
+}
+
+fn expand<'a>(
+    source: &'a str,
+    defines: &mut BTreeSet<String>,
+    included: &mut Vec<&'a str>,
+    stack: &mut Vec<&'a str>,
+    output: &mut String,
+) -> anyhow::Result<()> {
+    let mut conditions: Vec<Condition> = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let active = conditions.last().is_none_or(|condition| condition.active);
+        let Some(directive) = line.trim_start().strip_prefix('#') else {
+            if active {
+                output.push_str(line);
+                output.push('\n');
+            }
+            continue;
+        };
+        let (name, argument) = directive
+            .split_once(char::is_whitespace)
+            .map_or((directive.trim(), ""), |(name, argument)| {
+                (name, argument.trim())
+            });
+        let error = |message: &str| anyhow::anyhow!("line {}: {}", number + 1, message);
+
+        match name {
+            "ifdef" | "ifndef" => {
+                let defined = defines.contains(argument);
+                conditions.push(Condition {
+                    active: active && defined == (name == "ifdef"),
+                    parent_active: active,
+                    has_else: false,
+                });
+            }
+            "else" => {
+                let condition = conditions
+                    .last_mut()
+                    .filter(|condition| !condition.has_else)
+                    .ok_or_else(|| error("#else without #ifdef"))?;
+                condition.active = condition.parent_active && !condition.active;
+                condition.has_else = true;
+            }
+            "endif" => {
+                conditions
+                    .pop()
+                    .ok_or_else(|| error("#endif without #ifdef"))?;
+            }
+            _ if !active => {}
+            "define" => {
+                defines.insert(argument.to_string());
+            }
+            "include" => {
+                let chunk = argument.trim_matches('"');
+                let &(chunk, chunk_source) = SOURCES
+                    .iter()
+                    .find(|(name, _)| *name == chunk)
+                    .ok_or_else(|| error(&format!("unknown chunk \"{}\"", chunk)))?;
+                if stack.contains(&chunk) {
+                    return Err(error(&format!("\"{}\" includes itself", chunk)));
+                }
+                if included.contains(&chunk) {
+                    continue;
+                }
+                included.push(chunk);
+                stack.push(chunk);
+                expand(chunk_source, defines, included, stack, output)
+                    .map_err(|err| anyhow::anyhow!("in \"{}\", {}", chunk, err))?;
+                stack.pop();
+            }
+            _ => return Err(error(&format!("unknown directive #{}", name))),
+        }
+    }
+    if !conditions.is_empty() {
+        anyhow::bail!("#ifdef without #endif");
+    }
+    Ok(())
+}
+
+type SourceCache = Mutex<HashMap<(&'static str, ShaderDefines), Arc<str>>>;
+
+/// Build a shader of `SOURCES` with a set of defines.
+/// The results are cached by the name and the defines, so the renderers built later reuse them.
+pub(crate) fn shader_source(
+    name: &'static str,
+    defines: &ShaderDefines,
+) -> anyhow::Result<Arc<str>> {
+    static CACHE: OnceLock<SourceCache> = OnceLock::new();
+    let cache = CACHE.get_or_init(Default::default);
+
+    let key = (name, defines.clone());
+    if let Some(source) = cache.lock().unwrap().get(&key) {
+        return Ok(Arc::clone(source));
+    }
+    let (_, source) = SOURCES
+        .iter()
+        .find(|(source, _)| *source == name)
+        .ok_or_else(|| anyhow::anyhow!("unknown shader \"{}\"", name))?;
+    let source: Arc<str> = preprocess(source, defines)
+        .map_err(|err| anyhow::anyhow!("in \"{}\", {}", name, err))?
+        .into();
+    cache.lock().unwrap().insert(key, Arc::clone(&source));
+    Ok(source)
+}
+
+/// Get the descriptor of a shader of `SOURCES` built with a set of defines.
+///
+/// # Panics
+///
+/// If the directives of the shader are invalid, the shaders are part of the engine.
+pub(crate) fn shader_module(
+    label: &'static str,
+    name: &'static str,
+    defines: &ShaderDefines,
+) -> wgpu::ShaderModuleDescriptor<'static> {
+    let source = shader_source(name, defines)
+        .unwrap_or_else(|err| panic!("[Renderer] Failed to preprocess a shader {}", err));
+    wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(source.to_string().into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preprocess() {
+        let source = "#include \"camera\"\n\
+                      #include \"camera\"\n\
+                      #ifdef SPECULAR\n\
+                      specular\n\
+                      #ifndef NESTED\n\
+                      nested\n\
+                      #endif\n\
+                      #else\n\
+                      diffuse\n\
+                      #endif\n";
+        let diffuse = preprocess(source, &ShaderDefines::new()).unwrap();
+        assert_eq!(diffuse.matches("struct Camera").count(), 1);
+        assert!(diffuse.contains("diffuse") && !diffuse.contains("specular"));
+        let specular = preprocess(source, &ShaderDefines::new().with("SPECULAR")).unwrap();
+        assert!(specular.contains("specular\nnested") && !specular.contains("diffuse"));
+
+        assert!(preprocess("#include \"missing\"", &ShaderDefines::new()).is_err());
+        assert!(preprocess("#ifdef A\n", &ShaderDefines::new()).is_err());
+        assert!(preprocess("#endif\n", &ShaderDefines::new()).is_err());
+
+        // The engine shaders expand without directives left
+        for defines in [ShaderDefines::new(), ShaderDefines::new().with("SPECULAR")] {
+            for name in ["shader", "crowd", "wireframe"] {
+                let source = shader_source(name, &defines).unwrap();
+                assert!(!source.contains("#include") && !source.contains("#ifdef"));
+            }
+        }
+    }
+}
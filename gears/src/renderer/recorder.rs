@@ -0,0 +1,241 @@
+use crate::core::config::RecordingConfig;
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+
+/// Send to start recording the presented frames into a video.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StartRecording(pub RecordingConfig);
+
+/// Send to stop the recording and finish the video.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StopRecording;
+
+/// Get the bytes per row of a readback buffer, wgpu requires the rows to be aligned.
+pub(crate) fn padded_bytes_per_row(width: u32) -> u32 {
+    let unpadded = width * 4;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    unpadded.div_ceil(align) * align
+}
+
+/// Remove the row padding of a readback buffer.
+pub(crate) fn unpad_rows(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+) -> Vec<u8> {
+    let unpadded = (width * 4) as usize;
+    data.chunks(padded_bytes_per_row as usize)
+        .take(height as usize)
+        .flat_map(|row| &row[..unpadded])
+        .copied()
+        .collect()
+}
+
+/// Get the ffmpeg pixel format of a surface format, `None` if it can not be encoded.
+fn pixel_format(format: wgpu::TextureFormat) -> Option<&'static str> {
+    match format {
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => Some("bgra"),
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => Some("rgba"),
+        _ => None,
+    }
+}
+
+fn ffmpeg_args(config: &RecordingConfig, width: u32, height: u32, pix_fmt: &str) -> Vec<String> {
+    [
+        "-y",
+        "-f",
+        "rawvideo",
+        "-pix_fmt",
+        pix_fmt,
+        "-s",
+        &format!("{}x{}", width, height),
+        "-r",
+        &config.frame_rate.to_string(),
+        "-i",
+        "-",
+        "-c:v",
+        "libx264",
+        "-pix_fmt",
+        "yuv420p",
+    ]
+    .into_iter()
+    .map(String::from)
+    .chain(std::iter::once(config.path.to_string_lossy().to_string()))
+    .collect()
+}
+
+/// Copies the presented frames to the CPU and pipes them into an ffmpeg subprocess.
+/// The readback waits for the GPU, so the recording is meant for offline capture, not for gameplay.
+pub(crate) struct FrameRecorder {
+    config: RecordingConfig,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    buffer: wgpu::Buffer,
+    ffmpeg: Child,
+    frames: u64,
+}
+
+impl FrameRecorder {
+    pub fn start(
+        device: &wgpu::Device,
+        surface_config: &wgpu::SurfaceConfiguration,
+        config: RecordingConfig,
+    ) -> anyhow::Result<Self> {
+        if !surface_config.usage.contains(wgpu::TextureUsages::COPY_SRC) {
+            anyhow::bail!("The surface does not support copying the frames");
+        }
+        let pix_fmt = pixel_format(surface_config.format).ok_or_else(|| {
+            anyhow::anyhow!(
+                "The surface format {:?} can not be recorded",
+                surface_config.format
+            )
+        })?;
+
+        let (width, height) = (surface_config.width, surface_config.height);
+        let padded_bytes_per_row = padded_bytes_per_row(width);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Recording Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let ffmpeg = Command::new(&config.ffmpeg)
+            .args(ffmpeg_args(&config, width, height, pix_fmt))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to start {}: {}", config.ffmpeg, e))?;
+
+        log::info!(
+            "[Recorder] Recording {}x{} at {} fps into {}",
+            width,
+            height,
+            config.frame_rate,
+            config.path.display()
+        );
+
+        Ok(Self {
+            config,
+            width,
+            height,
+            padded_bytes_per_row,
+            buffer,
+            ffmpeg,
+            frames: 0,
+        })
+    }
+
+    /// The fixed delta time of a recorded frame, the simulation runs at the video frame rate.
+    pub fn frame_time(&self) -> instant::Duration {
+        instant::Duration::from_secs_f64(1.0 / self.config.frame_rate.max(1) as f64)
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Record the copy of the frame, must be called before the encoder is submitted.
+    pub fn copy_frame(&self, encoder: &mut wgpu::CommandEncoder, frame: &wgpu::Texture) {
+        encoder.copy_texture_to_buffer(
+            frame.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_bytes_per_row),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Read the copied frame back and write it to the encoder, must be called after the submit.
+    pub fn write_frame(&mut self, device: &wgpu::Device) -> anyhow::Result<()> {
+        let slice = self.buffer.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        rx.recv()??;
+
+        let frame = unpad_rows(
+            &slice.get_mapped_range(),
+            self.width,
+            self.height,
+            self.padded_bytes_per_row,
+        );
+        self.buffer.unmap();
+
+        let stdin = self
+            .ffmpeg
+            .stdin
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("The encoder has no input"))?;
+        stdin.write_all(&frame)?;
+        self.frames += 1;
+
+        Ok(())
+    }
+
+    /// Close the input of the encoder and wait for it to finish the video.
+    pub fn finish(mut self) -> anyhow::Result<()> {
+        drop(self.ffmpeg.stdin.take());
+        let status = self.ffmpeg.wait()?;
+        if !status.success() {
+            anyhow::bail!("The encoder exited with {}", status);
+        }
+
+        log::info!(
+            "[Recorder] Recorded {} frames into {}",
+            self.frames,
+            self.config.path.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unpad_rows() {
+        let width = 3;
+        let padded = padded_bytes_per_row(width);
+        assert_eq!(padded, 256);
+
+        let mut data = vec![0u8; (padded * 2) as usize];
+        data[..12].copy_from_slice(&[1; 12]);
+        data[padded as usize..padded as usize + 12].copy_from_slice(&[2; 12]);
+
+        let frame = unpad_rows(&data, width, 2, padded);
+        assert_eq!(frame.len(), 24);
+        assert!(frame[..12].iter().all(|b| *b == 1));
+        assert!(frame[12..].iter().all(|b| *b == 2));
+    }
+
+    #[test]
+    fn test_ffmpeg_args() {
+        let config = RecordingConfig::new("trailer.mp4").with_frame_rate(30);
+        let args = ffmpeg_args(&config, 1920, 1080, "bgra");
+
+        assert!(args.windows(2).any(|w| w == ["-s", "1920x1080"]));
+        assert!(args.windows(2).any(|w| w == ["-r", "30"]));
+        assert!(args.windows(2).any(|w| w == ["-pix_fmt", "bgra"]));
+        assert_eq!(args.last().unwrap(), "trailer.mp4");
+        assert_eq!(
+            pixel_format(wgpu::TextureFormat::Bgra8UnormSrgb),
+            Some("bgra")
+        );
+    }
+}
@@ -1,4 +1,9 @@
-use super::{model, texture};
+use super::atlas::AtlasBuilder;
+use super::streaming::StreamedTexture;
+use super::{gltf_loader, mesh_optimizer, model, texture};
+use crate::core::config::TextureAtlasConfig;
+use crate::core::vfs;
+use crate::ecs::components::Bounds;
 use anyhow::Context;
 use image::GenericImageView;
 use std::io::{BufReader, Cursor};
@@ -6,17 +11,11 @@ use std::path::Path;
 use wgpu::util::DeviceExt;
 
 pub(crate) async fn load_string(file_path: &str) -> anyhow::Result<String> {
-    let path = std::path::Path::new(env!("OUT_DIR")).join(file_path);
-    let txt = std::fs::read_to_string(path)?;
-
-    Ok(txt)
+    vfs::read_to_string(file_path)
 }
 
 pub(crate) async fn load_binary(file_path: &str) -> anyhow::Result<Vec<u8>> {
-    let path = std::path::Path::new(env!("OUT_DIR")).join(file_path);
-    let data = std::fs::read(path)?;
-
-    Ok(data)
+    vfs::read(file_path)
 }
 
 pub(crate) async fn load_texture(
@@ -29,17 +28,95 @@ pub(crate) async fn load_texture(
     texture::Texture::from_bytes(device, queue, &data, file_path)
 }
 
+/// An asset which failed to load and was replaced by a placeholder.
+pub(crate) struct LoadFailure {
+    pub path: String,
+    pub error: anyhow::Error,
+}
+
+/// Create the material of a missing texture.
+fn missing_material(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    name: String,
+) -> model::Material {
+    let diffuse_texture = texture::Texture::missing(device, queue);
+    let bind_group = model::Material::create_bind_group(device, layout, &diffuse_texture);
+    model::Material {
+        name,
+        diffuse_texture,
+        bind_group,
+        stream: None,
+    }
+}
+
+/// Decode a texture if it is small enough for an atlas.
+fn decode_small_image(data: &[u8], max_size: u32) -> Option<image::RgbaImage> {
+    let (width, height) = image::ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()?;
+    if width == 0 || height == 0 || width > max_size || height > max_size {
+        return None;
+    }
+    image::load_from_memory(data)
+        .ok()
+        .map(|image| image.to_rgba8())
+}
+
+/// Where the texture of an OBJ material ended up.
+#[derive(Debug, Copy, Clone)]
+enum MaterialSlot {
+    Material(usize),
+    /// The index of the texture in the atlas.
+    Atlas(usize),
+}
+
+/// Load an OBJ or a glTF model with its materials, the .gltf and .glb files are loaded by `gltf_loader`.
+/// The textures which fail to load are replaced by the missing texture and added to `failures`,
+/// so a model with missing content is still drawn.
+/// With an atlas config the small textures of an OBJ are packed into a single material and the UVs of
+/// the meshes are moved into their regions.
+///
+/// # Returns
+///
+/// The model, or an error if the file itself can not be loaded.
 pub(crate) async fn load_model(
     file_path: &str,
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     layout: &wgpu::BindGroupLayout,
+    stream_textures: bool,
+    atlas: Option<&TextureAtlasConfig>,
+    failures: &mut Vec<LoadFailure>,
 ) -> anyhow::Result<model::Model> {
     let path = Path::new(file_path);
-    let model_root_dir = path.parent().unwrap();
-    let file_name = model_root_dir.file_name().unwrap().to_str().unwrap();
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    if matches!(extension.as_deref(), Some("gltf" | "glb")) {
+        return gltf_loader::load_model(
+            file_path,
+            device,
+            queue,
+            layout,
+            stream_textures,
+            failures,
+        )
+        .await;
+    }
+    let model_root_dir = path.parent().unwrap_or(Path::new(""));
+    let file_name = model_root_dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(file_path);
 
-    let obj_text = load_string(file_path).await?;
+    let obj_text = load_string(file_path)
+        .await
+        .with_context(|| format!("Failed to load the model {}", file_path))?;
     let obj_cursor = Cursor::new(obj_text);
     let mut obj_reader = BufReader::new(obj_cursor);
 
@@ -51,87 +128,229 @@ pub(crate) async fn load_model(
             ..Default::default()
         },
         |p| async move {
-            let mat_text = load_string(model_root_dir.join(&p).to_str().unwrap())
+            let mat_text = load_string(&model_root_dir.join(&p).to_string_lossy())
                 .await
                 .unwrap_or_default();
 
             tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
         },
     )
-    .await?;
+    .await
+    .with_context(|| format!("Failed to parse the model {}", file_path))?;
 
-    let mut materials = Vec::new();
-    for m in obj_materials? {
-        let diffuse_texture = load_texture(
-            model_root_dir
-                .join(m.diffuse_texture.as_ref().unwrap())
-                .to_str()
-                .unwrap(),
-            device,
-            queue,
-        )
-        .await?;
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                },
-            ],
-            label: None,
+    let obj_materials = obj_materials.unwrap_or_else(|e| {
+        failures.push(LoadFailure {
+            path: file_path.to_string(),
+            error: anyhow::anyhow!("Failed to load the materials: {}", e),
         });
+        Vec::new()
+    });
+
+    // The meshes keep the names of their materials, the atlas merges the materials
+    let material_names = obj_materials
+        .iter()
+        .map(|m| m.name.clone())
+        .collect::<Vec<_>>();
+
+    // A texture is only packed if the UVs using it stay in 0 to 1, the others tile
+    let mut tiled = vec![false; obj_materials.len()];
+    for m in &models {
+        if let Some(tiled) = m.mesh.material_id.and_then(|id| tiled.get_mut(id)) {
+            *tiled |= m.mesh.texcoords.iter().any(|t| !(0.0..=1.0).contains(t));
+        }
+    }
+
+    let mut materials = Vec::new();
+    let mut slots = Vec::with_capacity(obj_materials.len());
+    let mut atlas_builder =
+        atlas.map(|config| AtlasBuilder::new(config.atlas_size, config.padding));
+    let mut atlas_names = Vec::new();
+    for (i, m) in obj_materials.into_iter().enumerate() {
+        let Some(texture_file) = m.diffuse_texture.as_ref() else {
+            failures.push(LoadFailure {
+                path: file_path.to_string(),
+                error: anyhow::anyhow!("The material {} has no diffuse texture", m.name),
+            });
+            slots.push(MaterialSlot::Material(materials.len()));
+            materials.push(missing_material(device, queue, layout, m.name));
+            continue;
+        };
+        let texture_path = model_root_dir
+            .join(texture_file)
+            .to_string_lossy()
+            .into_owned();
+        let data = match load_binary(&texture_path).await {
+            Ok(data) => data,
+            Err(error) => {
+                failures.push(LoadFailure {
+                    path: texture_path,
+                    error,
+                });
+                slots.push(MaterialSlot::Material(materials.len()));
+                materials.push(missing_material(device, queue, layout, m.name));
+                continue;
+            }
+        };
+
+        if let (Some(config), Some(builder)) = (atlas, atlas_builder.as_mut()) {
+            if let Some(image) = (!tiled[i])
+                .then(|| decode_small_image(&data, config.max_texture_size))
+                .flatten()
+            {
+                slots.push(MaterialSlot::Atlas(builder.add(image)));
+                atlas_names.push(m.name);
+                continue;
+            }
+        }
 
+        let loaded = if stream_textures {
+            Ok((
+                StreamedTexture::placeholder(device, queue),
+                Some(StreamedTexture::load(&texture_path, data)),
+            ))
+        } else {
+            texture::Texture::from_bytes(device, queue, &data, &texture_path)
+                .map(|texture| (texture, None))
+        };
+        let (diffuse_texture, stream) = match loaded {
+            Ok(loaded) => loaded,
+            Err(error) => {
+                failures.push(LoadFailure {
+                    path: texture_path,
+                    error,
+                });
+                (texture::Texture::missing(device, queue), None)
+            }
+        };
+        let bind_group = model::Material::create_bind_group(device, layout, &diffuse_texture);
+
+        slots.push(MaterialSlot::Material(materials.len()));
         materials.push(model::Material {
             name: m.name,
             diffuse_texture,
             bind_group,
+            stream,
         })
     }
 
+    // The material index and the atlas region of each OBJ material
+    let mut material_map = Vec::with_capacity(slots.len());
+    let builder = atlas_builder.filter(|builder| !builder.is_empty());
+    let packed = builder.as_ref().and_then(AtlasBuilder::build);
+    if let Some(packed) = &packed {
+        log::info!(
+            "[Atlas] Packed {} textures of {} into {}x{}",
+            packed.rects.len(),
+            file_name,
+            packed.image.width(),
+            packed.image.height()
+        );
+        let diffuse_texture = texture::Texture::from_mips(
+            device,
+            queue,
+            std::slice::from_ref(&packed.image),
+            Some(&format!("{} atlas", file_name)),
+        )?;
+        let bind_group = model::Material::create_bind_group(device, layout, &diffuse_texture);
+        materials.push(model::Material {
+            name: atlas_names.join(", "),
+            diffuse_texture,
+            bind_group,
+            stream: None,
+        });
+    }
+    let atlas_material = materials.len().saturating_sub(1);
+    for slot in slots {
+        material_map.push(match (slot, &packed) {
+            (MaterialSlot::Material(i), _) => (i, None),
+            (MaterialSlot::Atlas(i), Some(packed)) => (atlas_material, Some(packed.rects[i])),
+            // The textures did not fit, each gets its own material
+            (MaterialSlot::Atlas(i), None) => {
+                let image = &builder.as_ref().unwrap().images()[i];
+                let diffuse_texture =
+                    texture::Texture::from_mips(device, queue, std::slice::from_ref(image), None)?;
+                let bind_group =
+                    model::Material::create_bind_group(device, layout, &diffuse_texture);
+                materials.push(model::Material {
+                    name: atlas_names[i].clone(),
+                    diffuse_texture,
+                    bind_group,
+                    stream: None,
+                });
+                (materials.len() - 1, None)
+            }
+        });
+    }
+
+    // The meshes index the materials, so there is at least one
+    let used_materials = models
+        .iter()
+        .map(|m| m.mesh.material_id.unwrap_or(0) + 1)
+        .max()
+        .unwrap_or(1);
+    while material_map.len() < used_materials {
+        material_map.push((materials.len(), None));
+        materials.push(missing_material(
+            device,
+            queue,
+            layout,
+            "Missing material".to_string(),
+        ));
+    }
+
+    let bounds = Bounds::from_points(models.iter().flat_map(|m| {
+        m.mesh
+            .positions
+            .chunks_exact(3)
+            .map(|p| cgmath::Vector3::new(p[0], p[1], p[2]))
+    }));
+
     let meshes = models
         .into_iter()
         .map(|m| {
+            let (material, rect) = material_map[m.mesh.material_id.unwrap_or(0)];
+            let material_name = m
+                .mesh
+                .material_id
+                .and_then(|id| material_names.get(id))
+                .cloned()
+                .unwrap_or_default();
+            let bounds = Bounds::from_points(
+                m.mesh
+                    .positions
+                    .chunks_exact(3)
+                    .map(|p| cgmath::Vector3::new(p[0], p[1], p[2])),
+            );
+            let texcoord = |i: usize| m.mesh.texcoords.get(i).copied().unwrap_or(0.0);
             let vertices = (0..m.mesh.positions.len() / 3)
                 .map(|i| {
-                    if m.mesh.normals.is_empty() {
-                        model::ModelVertex {
-                            position: [
-                                m.mesh.positions[i * 3],
-                                m.mesh.positions[i * 3 + 1],
-                                m.mesh.positions[i * 3 + 2],
-                            ],
-                            tex_coords: [
-                                m.mesh.texcoords[i * 2],
-                                1.0 - m.mesh.texcoords[i * 2 + 1],
-                            ],
-                            normal: [0.0, 0.0, 0.0],
-                        }
-                    } else {
-                        model::ModelVertex {
-                            position: [
-                                m.mesh.positions[i * 3],
-                                m.mesh.positions[i * 3 + 1],
-                                m.mesh.positions[i * 3 + 2],
-                            ],
-                            tex_coords: [
-                                m.mesh.texcoords[i * 2],
-                                1.0 - m.mesh.texcoords[i * 2 + 1],
-                            ],
-                            normal: [
+                    let tex_coords = [texcoord(i * 2), 1.0 - texcoord(i * 2 + 1)];
+                    model::ModelVertex {
+                        position: [
+                            m.mesh.positions[i * 3],
+                            m.mesh.positions[i * 3 + 1],
+                            m.mesh.positions[i * 3 + 2],
+                        ],
+                        tex_coords: rect.map_or(tex_coords, |rect| rect.map_uv(tex_coords)),
+                        normal: if m.mesh.normals.is_empty() {
+                            [0.0, 0.0, 0.0]
+                        } else {
+                            [
                                 m.mesh.normals[i * 3],
                                 m.mesh.normals[i * 3 + 1],
                                 m.mesh.normals[i * 3 + 2],
-                            ],
-                        }
+                            ]
+                        },
                     }
                 })
                 .collect::<Vec<_>>();
 
+            // Reorder the triangles for the vertex cache, then the vertices in the order they are used
+            let mut vertices = vertices;
+            let mut indices =
+                mesh_optimizer::optimize_vertex_cache(&m.mesh.indices, vertices.len());
+            mesh_optimizer::optimize_vertex_fetch(&mut vertices, &mut indices);
+
             let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some(&format!("{:?} Vertex Buffer", file_name)),
                 contents: bytemuck::cast_slice(&vertices),
@@ -139,20 +358,91 @@ pub(crate) async fn load_model(
             });
             let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some(&format!("{:?} Index Buffer", file_name)),
-                contents: bytemuck::cast_slice(&m.mesh.indices),
+                contents: bytemuck::cast_slice(&indices),
                 usage: wgpu::BufferUsages::INDEX,
             });
 
             log::info!("Mesh: {}", m.name);
             model::Mesh {
-                name: file_name.to_string(),
+                name: m.name,
                 vertex_buffer,
                 index_buffer,
-                num_elements: m.mesh.indices.len() as u32,
-                material: m.mesh.material_id.unwrap_or(0),
+                num_elements: indices.len() as u32,
+                material,
+                material_name,
+                bounds,
             }
         })
         .collect::<Vec<_>>();
 
-    Ok(model::Model { meshes, materials })
+    Ok(model::Model {
+        meshes,
+        materials,
+        bounds,
+    })
+}
+
+/// Create the placeholder of a model which failed to load, a unit cube with the missing texture.
+pub(crate) fn missing_model(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+) -> model::Model {
+    // The normal and the two axes spanning each face
+    const FACES: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+        ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ];
+
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::<u32>::with_capacity(36);
+    for (normal, u, v) in FACES {
+        let base = vertices.len() as u32;
+        for (su, sv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            vertices.push(model::ModelVertex {
+                position: std::array::from_fn(|i| 0.5 * (normal[i] + su * u[i] + sv * v[i])),
+                tex_coords: [(su + 1.0) / 2.0, (1.0 - sv) / 2.0],
+                normal,
+            });
+        }
+        indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Missing model Vertex Buffer"),
+        contents: bytemuck::cast_slice(&vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Missing model Index Buffer"),
+        contents: bytemuck::cast_slice(&indices),
+        usage: wgpu::BufferUsages::INDEX,
+    });
+
+    let bounds = Some(Bounds::new(
+        cgmath::Vector3::new(-0.5, -0.5, -0.5),
+        cgmath::Vector3::new(0.5, 0.5, 0.5),
+    ));
+    model::Model {
+        meshes: vec![model::Mesh {
+            name: "Missing model".to_string(),
+            vertex_buffer,
+            index_buffer,
+            num_elements: indices.len() as u32,
+            material: 0,
+            material_name: String::new(),
+            bounds,
+        }],
+        materials: vec![missing_material(
+            device,
+            queue,
+            layout,
+            "Missing material".to_string(),
+        )],
+        bounds,
+    }
 }
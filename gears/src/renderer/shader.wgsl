@@ -1,21 +1,5 @@
-struct Camera {
-    view_pos: vec4<f32>,
-    view_proj: mat4x4<f32>,
-}
-
-struct Light {
-    position: vec3<f32>,
-    light_type: u32,
-    color: vec3<f32>,
-    radius: f32,
-    direction: vec3<f32>,
-    intensity: f32,
-}
-
-struct LightData {
-    lights: array<Light, 20>,
-    num_lights: u32,
-}
+#include "camera"
+#include "lighting"
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
@@ -30,6 +14,11 @@ struct InstanceInput {
     @location(9) normal_matrix_0: vec3<f32>,
     @location(10) normal_matrix_1: vec3<f32>,
     @location(11) normal_matrix_2: vec3<f32>,
+    @location(12) prev_model_matrix_0: vec4<f32>,
+    @location(13) prev_model_matrix_1: vec4<f32>,
+    @location(14) prev_model_matrix_2: vec4<f32>,
+    @location(15) prev_model_matrix_3: vec4<f32>,
+    @location(16) motion_blur: f32,
 }
 
 struct VertexOutput {
@@ -37,6 +26,13 @@ struct VertexOutput {
     @location(0) tex_coords: vec2<f32>,
     @location(1) world_normal: vec3<f32>,
     @location(2) world_position: vec3<f32>,
+    @location(3) current_clip: vec4<f32>,
+    @location(4) previous_clip: vec4<f32>,
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) velocity: vec2<f32>,
 }
 
 @group(0) @binding(0)
@@ -49,7 +45,23 @@ var s_diffuse: sampler;
 var<uniform> camera: Camera;
 
 @group(2) @binding(0)
-var<uniform> light_data: LightData;
+var<storage, read> lights: array<Light>;
+@group(2) @binding(1)
+var<uniform> light_grid: LightGrid;
+// The offset and the count of the light indices of every tile, then the indices
+@group(2) @binding(2)
+var<storage, read> light_tiles: array<u32>;
+
+#ifdef SHADOWS
+@group(3) @binding(0)
+var<uniform> shadow: Shadows;
+@group(3) @binding(1)
+var shadow_map: texture_depth_2d_array;
+@group(3) @binding(2)
+var shadow_sampler: sampler_comparison;
+@group(3) @binding(3)
+var point_shadow_map: texture_depth_2d_array;
+#endif
 
 // Vertex shader
 
@@ -77,62 +89,41 @@ fn vs_main(
     var world_position: vec4<f32> = model_matrix * vec4<f32>(model.position, 1.0);
     out.world_position = world_position.xyz;
     out.clip_position = camera.view_proj * world_position;
-    
+
+    // Entities that opted out of the motion blur get no velocity
+    let prev_model_matrix = mat4x4<f32>(
+        instance.prev_model_matrix_0,
+        instance.prev_model_matrix_1,
+        instance.prev_model_matrix_2,
+        instance.prev_model_matrix_3,
+    );
+    let prev_world_position = prev_model_matrix * vec4<f32>(model.position, 1.0);
+    out.current_clip = out.clip_position;
+    out.previous_clip = mix(out.clip_position, camera.prev_view_proj * prev_world_position, instance.motion_blur);
+
     return out;
 }
 
 // Fragment shader
 
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+fn fs_main(in: VertexOutput) -> FragmentOutput {
     let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
     
-    var result_color: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
-
-    for (var i = 0u; i < light_data.num_lights; i = i + 1u) {
-        let light = light_data.lights[i];
-
-        if (light.light_type == 0u) { // Point light
-            let distance = length(light.position - in.world_position);
-            let attenuation = clamp(1.0 - (distance / light.radius) * (distance / light.radius), 0.0, 1.0);
-
-            if (attenuation > 0.0) {
-                let light_dir = normalize(light.position - in.world_position);
-                let view_dir = normalize(camera.view_pos.xyz - in.world_position);
-                let half_dir = normalize(view_dir + light_dir);
-
-                // Diffuse component
-                let diffuse_strength = max(dot(in.world_normal, light_dir), 0.0);
-                let diffuse_color = light.color * light.intensity * diffuse_strength * attenuation;
-
-                // Specular component
-                let specular_strength = pow(max(dot(in.world_normal, half_dir), 0.0), 32.0);
-                let specular_color = light.color * light.intensity * specular_strength * attenuation;
-
-                // Blending object color and light color for more balance
-                result_color = result_color + (diffuse_color + specular_color) * mix(object_color.xyz, light.color, 0.3);
-            }
-        } else if (light.light_type == 1u) { // Ambient light
-            let ambient_color = light.color * light.intensity;
-
-            result_color = result_color + ambient_color * object_color.xyz;
-        } else if (light.light_type == 2u) { // Directional light
-            let light_dir = normalize(light.position + light.direction); // Calculate the direction from the light's position
-            let view_dir = normalize(camera.view_pos.xyz - in.world_position);
-            let half_dir = normalize(view_dir + light_dir);
-
-            // Diffuse component
-            let diffuse_strength = max(dot(in.world_normal, light_dir), 0.0);
-            let diffuse_color = light.color * light.intensity * diffuse_strength;
-
-            // Specular component
-            let specular_strength = pow(max(dot(in.world_normal, half_dir), 0.0), 32.0);
-            let specular_color = light.color * light.intensity * specular_strength;
-
-            // Blending object color and light color for more balance
-            result_color = result_color + (diffuse_color + specular_color) * object_color.xyz;
-        }
-    }
-
-    return vec4<f32>(result_color, object_color.a);
+    let result_color = shade(
+        in.clip_position.xy,
+        object_color.xyz,
+        in.world_position,
+        in.world_normal,
+        camera.view_pos.xyz,
+    );
+
+    // The screen-space motion since the last frame in uv units
+    let current = in.current_clip.xy / in.current_clip.w;
+    let previous = in.previous_clip.xy / in.previous_clip.w;
+
+    var out: FragmentOutput;
+    out.color = vec4<f32>(result_color, object_color.a);
+    out.velocity = (current - previous) * vec2<f32>(0.5, -0.5);
+    return out;
 }
\ No newline at end of file
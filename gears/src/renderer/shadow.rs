@@ -0,0 +1,856 @@
+use super::model::{self, DrawModel, Vertex};
+use super::preprocess::{self, ShaderDefines};
+use super::{instance, texture};
+use crate::ecs::traits::Component;
+use crate::ecs::Entity;
+use bytemuck::Zeroable;
+use cgmath::{
+    ortho, perspective, Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, Transform, Vector3,
+    Zero,
+};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, RwLock};
+use wgpu::util::DeviceExt;
+
+/// The most cascades the shadow map has layers for.
+pub const MAX_CASCADES: usize = 4;
+/// The most point lights casting shadows, each one has the 6 faces of a cube in the point shadow map.
+pub const MAX_POINT_SHADOWS: usize = 4;
+/// The width and the height of a cascade in texels.
+const SHADOW_MAP_SIZE: u32 = 2048;
+/// The width and the height of a face of a point light cube in texels.
+const POINT_SHADOW_MAP_SIZE: u32 = 512;
+/// The layers of the point shadow map.
+const POINT_LAYERS: usize = MAX_POINT_SHADOWS * 6;
+/// The offset between the light matrices of the caster passes, the alignment of a dynamic offset.
+const CASTER_STRIDE: u64 = 256;
+/// Maps the depth of an OpenGL style projection from -1..1 to the 0..1 of wgpu.
+#[rustfmt::skip]
+const OPENGL_TO_WGPU: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+/// The cascaded shadows of the first directional light and the cube shadows of the point lights.
+/// Add it to any entity to adjust the shadows at runtime, the first one found is used.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShadowSettings {
+    pub enabled: bool,
+    /// The number of cascades the view is split into, from 2 to 4.
+    pub cascades: u32,
+    /// The distance from the camera the shadows reach.
+    pub distance: f32,
+    /// Blends the split distances from uniform at 0 to logarithmic at 1,
+    /// the near cascades get more of the resolution the closer it is to 1.
+    pub split_lambda: f32,
+    /// The depth bias against shadow acne in texels of a cascade, scaled to the size of each cascade.
+    pub depth_bias: f32,
+    /// The distance the surfaces are moved along their normal before the lookup in texels of a cascade.
+    pub normal_bias: f32,
+    /// Tint the scene by the cascade it is shadowed from, to tune the splits.
+    pub debug_cascades: bool,
+    /// The number of point lights casting shadows, up to `MAX_POINT_SHADOWS`.
+    /// The most important point lights of the frame are picked.
+    pub point_lights: u32,
+    /// The distance the surfaces are moved towards a point light in texels of its cube.
+    pub point_depth_bias: f32,
+    /// The distance the surfaces are moved along their normal in texels of a point light cube.
+    pub point_normal_bias: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cascades: 4,
+            distance: 100.0,
+            split_lambda: 0.7,
+            depth_bias: 1.0,
+            normal_bias: 1.5,
+            debug_cascades: false,
+            point_lights: MAX_POINT_SHADOWS as u32,
+            point_depth_bias: 1.0,
+            point_normal_bias: 1.5,
+        }
+    }
+}
+
+impl Component for ShadowSettings {}
+
+/// The camera the cascades are fitted to.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct CameraFrustum {
+    pub eye: Vector3<f32>,
+    pub forward: Vector3<f32>,
+    pub fovy: Rad<f32>,
+    pub aspect: f32,
+    pub znear: f32,
+}
+
+impl CameraFrustum {
+    /// Get the right and the up directions of the camera, like the view matrix of the camera.
+    fn basis(&self) -> (Vector3<f32>, Vector3<f32>) {
+        let right = self.forward.cross(Vector3::unit_y()).normalize();
+        (right, right.cross(self.forward))
+    }
+}
+
+/// A slice of the view frustum with its own part of the shadow map.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct Cascade {
+    pub view_proj: Matrix4<f32>,
+    /// The view distance the cascade ends at.
+    pub far: f32,
+    /// The depth bias in the depth units of the cascade.
+    pub depth_bias: f32,
+    /// The distance the surfaces are moved along their normal before the lookup.
+    pub normal_offset: f32,
+}
+
+/// Get the view distances the cascades end at, the practical split scheme.
+///
+/// # Arguments
+///
+/// * `near` - The distance the first cascade starts at.
+/// * `far` - The distance the last cascade ends at.
+/// * `count` - The number of cascades.
+/// * `lambda` - The blend from the uniform to the logarithmic splits.
+pub(crate) fn split_distances(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    let lambda = lambda.clamp(0.0, 1.0);
+    (1..=count)
+        .map(|i| {
+            let fraction = i as f32 / count as f32;
+            let logarithmic = near * (far / near).powf(fraction);
+            let uniform = near + (far - near) * fraction;
+            lambda * logarithmic + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// Fit the cascades to the view of a camera, each one covers a slice of the frustum seen from the light.
+///
+/// The cascades are stable: a cascade bounds its slice with a sphere, so its size does not change as the
+/// camera turns, and it moves in whole texels, so the shadow edges do not shimmer as the camera moves.
+///
+/// # Arguments
+///
+/// * `frustum` - The camera.
+/// * `to_light` - The direction towards the directional light.
+/// * `settings` - The shadow settings.
+pub(crate) fn fit_cascades(
+    frustum: &CameraFrustum,
+    to_light: Vector3<f32>,
+    settings: &ShadowSettings,
+) -> Vec<Cascade> {
+    let count = (settings.cascades as usize).clamp(2, MAX_CASCADES);
+    let near = frustum.znear;
+    let far = settings.distance.max(near * 2.0);
+    let splits = split_distances(near, far, count, settings.split_lambda);
+
+    let to_light = to_light.normalize();
+    let light_up = if to_light.y.abs() > 0.99 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+    let light_view = Matrix4::look_to_rh(Point3::origin(), -to_light, light_up);
+
+    let (right, up) = frustum.basis();
+    let tan_y = (frustum.fovy.0 * 0.5).tan();
+    let tan_x = tan_y * frustum.aspect;
+
+    let mut cascades = Vec::with_capacity(count);
+    let mut start = near;
+    for &end in &splits {
+        let mut corners = Vec::with_capacity(8);
+        for distance in [start, end] {
+            let center = frustum.eye + frustum.forward * distance;
+            for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+                corners.push(center + right * (x * distance * tan_x) + up * (y * distance * tan_y));
+            }
+        }
+        let center = corners
+            .iter()
+            .fold(Vector3::zero(), |sum, &corner| sum + corner)
+            / 8.0;
+        let radius = corners
+            .iter()
+            .map(|&corner| (corner - center).magnitude())
+            .fold(0.0, f32::max);
+        // Rounded up, so the float error of a turning camera does not change the size
+        let radius = (radius * 16.0).ceil() / 16.0;
+        let texel = 2.0 * radius / SHADOW_MAP_SIZE as f32;
+
+        let light_center = light_view.transform_point(Point3::from_vec(center));
+        let x = (light_center.x / texel).floor() * texel;
+        let y = (light_center.y / texel).floor() * texel;
+        // The casters between the light and the slice are kept up to the shadow distance
+        let znear = -light_center.z - radius - far;
+        let zfar = -light_center.z + radius;
+        let projection =
+            OPENGL_TO_WGPU * ortho(x - radius, x + radius, y - radius, y + radius, znear, zfar);
+
+        cascades.push(Cascade {
+            view_proj: projection * light_view,
+            far: end,
+            depth_bias: settings.depth_bias * texel / (zfar - znear),
+            normal_offset: settings.normal_bias * texel,
+        });
+        start = end;
+    }
+    cascades
+}
+
+/// Get the light matrices of the 6 faces of a point light cube, in the order +x, -x, +y, -y, +z, -z
+/// the shaders pick the face of a direction by.
+///
+/// # Arguments
+///
+/// * `position` - The position of the light.
+/// * `radius` - The range of the light, the cube ends at it.
+pub(crate) fn point_faces(position: Vector3<f32>, radius: f32) -> [Matrix4<f32>; 6] {
+    let far = radius.max(0.1);
+    let projection = OPENGL_TO_WGPU * perspective(Deg(90.0), 1.0, far * 0.001, far);
+    let eye = Point3::from_vec(position);
+    [
+        (Vector3::unit_x(), -Vector3::unit_y()),
+        (-Vector3::unit_x(), -Vector3::unit_y()),
+        (Vector3::unit_y(), Vector3::unit_z()),
+        (-Vector3::unit_y(), -Vector3::unit_z()),
+        (Vector3::unit_z(), -Vector3::unit_y()),
+        (-Vector3::unit_z(), -Vector3::unit_y()),
+    ]
+    .map(|(forward, up)| projection * Matrix4::look_to_rh(eye, forward, up))
+}
+
+/// A point light casting shadows.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct PointCaster {
+    /// The index of the light in the light buffer.
+    pub light: u32,
+    pub position: Vector3<f32>,
+    pub radius: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowUniform {
+    view_proj: [[[f32; 4]; 4]; MAX_CASCADES],
+    /// The eye and the forward direction of the camera, the view distance of a fragment picks its cascade.
+    eye: [f32; 4],
+    forward: [f32; 4],
+    splits: [f32; MAX_CASCADES],
+    depth_bias: [f32; MAX_CASCADES],
+    normal_offset: [f32; MAX_CASCADES],
+    count: u32,
+    /// The index of the shadowed light in the light buffer.
+    light: u32,
+    enabled: u32,
+    debug: u32,
+    point_view_proj: [[[f32; 4]; 4]; POINT_LAYERS],
+    /// The indices of the point lights casting shadows in the light buffer.
+    point_lights: [u32; MAX_POINT_SHADOWS],
+    point_count: u32,
+    /// The biases in texels at a distance of 1 from the light, they grow with the distance.
+    point_depth_bias: f32,
+    point_normal_offset: f32,
+    _padding: u32,
+}
+
+/// The light matrix and the casters each layer of the shadow map was last drawn with.
+/// A layer is only drawn again once its cascade moves, the light turns or a caster changes,
+/// so the static scenes seen by a still camera are drawn into the shadow map once.
+#[derive(Debug, Default)]
+pub(crate) struct CascadeCache {
+    layers: Vec<Option<(Matrix4<f32>, u64)>>,
+}
+
+impl CascadeCache {
+    /// Check whether a layer has to be drawn, and remember that it is drawn if so.
+    ///
+    /// # Arguments
+    ///
+    /// * `layer` - The layer of the cascade.
+    /// * `view_proj` - The light matrix of the cascade.
+    /// * `casters` - The hash of the shadow casters, see `hash_caster`.
+    pub fn is_stale(&mut self, layer: usize, view_proj: Matrix4<f32>, casters: u64) -> bool {
+        let state = Some((view_proj, casters));
+        if self.layers.len() <= layer {
+            self.layers.resize(layer + 1, None);
+        }
+        let stale = self.layers[layer] != state;
+        self.layers[layer] = state;
+        stale
+    }
+}
+
+/// Add a shadow caster to the hash of the casters the cascades are cached by.
+/// The meshes are hashed by their identity, so a model swapped at the same transform,
+/// e.g. a placeholder replaced by the streamed model, draws the cascades again.
+/// The materials are left out, the casters are only drawn into the depth.
+///
+/// # Arguments
+///
+/// * `hasher` - The hasher of the casters of the frame.
+/// * `entity` - The entity of the caster.
+/// * `meshes` - The identities of the meshes of its model, e.g. the ids of their buffers.
+/// * `model_matrix` - The transform of its instance, `None` if it has none.
+pub(crate) fn hash_caster<M: Hash>(
+    hasher: &mut impl Hasher,
+    entity: Entity,
+    meshes: impl ExactSizeIterator<Item = M>,
+    model_matrix: Option<Matrix4<f32>>,
+) {
+    entity.hash(hasher);
+    hasher.write_usize(meshes.len());
+    for mesh in meshes {
+        mesh.hash(hasher);
+    }
+    if let Some(model_matrix) = model_matrix {
+        let matrix: [[f32; 4]; 4] = model_matrix.into();
+        hasher.write(bytemuck::cast_slice(&matrix));
+    }
+}
+
+/// The shadow map of the cascades, a layer for each, the point shadow map, 6 layers for each point light,
+/// and the pass drawing the models into them.
+pub(crate) struct ShadowMaps {
+    layers: Vec<wgpu::TextureView>,
+    point_layers: Vec<wgpu::TextureView>,
+    caster_buffer: wgpu::Buffer,
+    caster_bind_group: wgpu::BindGroup,
+    caster_pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    /// The cascades of the frame, empty when nothing casts shadows.
+    cascades: Vec<Cascade>,
+    /// The light matrices of the faces of the point lights of the frame.
+    point_faces: Vec<Matrix4<f32>>,
+    cache: CascadeCache,
+    point_cache: CascadeCache,
+}
+
+impl ShadowMaps {
+    /// Create a depth texture array, with a view of the whole array and a view of each layer.
+    fn create_map(
+        device: &wgpu::Device,
+        label: &str,
+        size: u32,
+        layers: usize,
+    ) -> (wgpu::TextureView, Vec<wgpu::TextureView>) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: layers as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: texture::Texture::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let layer_views = (0..layers as u32)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Shadow Layer View"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Shadow Map View"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        (view, layer_views)
+    }
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let (view, layers) = Self::create_map(device, "Shadow Map", SHADOW_MAP_SIZE, MAX_CASCADES);
+        let (point_view, point_layers) = Self::create_map(
+            device,
+            "Point Shadow Map",
+            POINT_SHADOW_MAP_SIZE,
+            POINT_LAYERS,
+        );
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        // Disabled until the first frame with a directional or a point light
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shadow Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[ShadowUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("shadow_bind_group_layout"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&point_view),
+                },
+            ],
+            label: Some("shadow_bind_group"),
+        });
+
+        // ! The caster pass, a light matrix for each cascade and point light face picked by a dynamic offset
+        let matrix_size = wgpu::BufferSize::new(std::mem::size_of::<[[f32; 4]; 4]>() as u64);
+        let caster_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Caster Buffer"),
+            size: CASTER_STRIDE * (MAX_CASCADES + POINT_LAYERS) as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let caster_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: matrix_size,
+                    },
+                    count: None,
+                }],
+                label: Some("shadow_caster_bind_group_layout"),
+            });
+        let caster_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &caster_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &caster_buffer,
+                    offset: 0,
+                    size: matrix_size,
+                }),
+            }],
+            label: Some("shadow_caster_bind_group"),
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Caster Pipeline Layout"),
+            bind_group_layouts: &[&caster_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(preprocess::shader_module(
+            "Shadow Caster Shader",
+            "shadow_caster",
+            &ShaderDefines::new(),
+        ));
+        let caster_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Caster Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[model::ModelVertex::desc(), instance::InstanceRaw::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                // Both sides cast, so the open meshes and the planes do not leak light
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                // The surfaces steep to the light need more bias than the per-cascade one
+                bias: wgpu::DepthBiasState {
+                    constant: 0,
+                    slope_scale: 1.5,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            layers,
+            point_layers,
+            caster_buffer,
+            caster_bind_group,
+            caster_pipeline,
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+            cascades: Vec::new(),
+            point_faces: Vec::new(),
+            cache: CascadeCache::default(),
+            point_cache: CascadeCache::default(),
+        }
+    }
+
+    /// Fit the cascades to the camera, place the cubes of the point lights and upload them.
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` - The queue the buffers are written with.
+    /// * `frustum` - The camera, `None` turns the cascades off, e.g. for a split frame.
+    /// * `caster` - The index of the shadowed light in the light buffer and the direction towards it.
+    /// * `points` - The point lights, the most important first, the first `point_lights` of the settings cast shadows.
+    /// * `settings` - The shadow settings.
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        frustum: Option<&CameraFrustum>,
+        caster: Option<(u32, Vector3<f32>)>,
+        points: &[PointCaster],
+        settings: &ShadowSettings,
+    ) {
+        let mut uniform = ShadowUniform::zeroed();
+        self.cascades.clear();
+        self.point_faces.clear();
+        if let Some((frustum, (light, to_light))) = frustum.zip(caster).filter(|_| settings.enabled)
+        {
+            self.cascades = fit_cascades(frustum, to_light, settings);
+            for (i, cascade) in self.cascades.iter().enumerate() {
+                uniform.view_proj[i] = cascade.view_proj.into();
+                uniform.splits[i] = cascade.far;
+                uniform.depth_bias[i] = cascade.depth_bias;
+                uniform.normal_offset[i] = cascade.normal_offset;
+                queue.write_buffer(
+                    &self.caster_buffer,
+                    i as u64 * CASTER_STRIDE,
+                    bytemuck::cast_slice(&[uniform.view_proj[i]]),
+                );
+            }
+            uniform.eye = frustum.eye.extend(1.0).into();
+            uniform.forward = frustum.forward.extend(0.0).into();
+            uniform.count = self.cascades.len() as u32;
+            uniform.light = light;
+            uniform.enabled = 1;
+            uniform.debug = settings.debug_cascades as u32;
+        }
+
+        let count = (settings.point_lights as usize).min(MAX_POINT_SHADOWS);
+        let points = points.iter().take(if settings.enabled { count } else { 0 });
+        for (i, point) in points.enumerate() {
+            for (face, view_proj) in point_faces(point.position, point.radius)
+                .into_iter()
+                .enumerate()
+            {
+                let layer = i * 6 + face;
+                uniform.point_view_proj[layer] = view_proj.into();
+                queue.write_buffer(
+                    &self.caster_buffer,
+                    (MAX_CASCADES + layer) as u64 * CASTER_STRIDE,
+                    bytemuck::cast_slice(&[uniform.point_view_proj[layer]]),
+                );
+                self.point_faces.push(view_proj);
+            }
+            uniform.point_lights[i] = point.light;
+            uniform.point_count += 1;
+        }
+        // A texel of a face is 2 / size wide at a distance of 1, the 90 degrees cover 2 units
+        let texel = 2.0 / POINT_SHADOW_MAP_SIZE as f32;
+        uniform.point_depth_bias = settings.point_depth_bias * texel;
+        uniform.point_normal_offset = settings.point_normal_bias * texel;
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.cascades.is_empty() || !self.point_faces.is_empty()
+    }
+
+    /// Draw the depth of the models seen from the lights into the cascades and the point light faces which changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder` - The encoder of the frame.
+    /// * `models` - The models casting the shadows, with their instance buffers.
+    /// * `casters` - The hash of the models, see `hash_caster`, a layer is drawn again once it changes.
+    pub fn render(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        models: &[(&model::Model, Arc<RwLock<wgpu::Buffer>>)],
+        casters: u64,
+    ) {
+        let cascades = self
+            .layers
+            .iter()
+            .zip(&self.cascades)
+            .enumerate()
+            .filter(|(i, (_, cascade))| self.cache.is_stale(*i, cascade.view_proj, casters))
+            .map(|(i, (layer, _))| (layer, i))
+            .collect::<Vec<_>>();
+        let faces = self
+            .point_layers
+            .iter()
+            .zip(&self.point_faces)
+            .enumerate()
+            .filter(|(i, (_, view_proj))| self.point_cache.is_stale(*i, **view_proj, casters))
+            .map(|(i, (layer, _))| (layer, MAX_CASCADES + i))
+            .collect::<Vec<_>>();
+
+        for (layer, slot) in cascades.into_iter().chain(faces) {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: layer,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&self.caster_pipeline);
+            render_pass.set_bind_group(
+                0,
+                &self.caster_bind_group,
+                &[(slot as u64 * CASTER_STRIDE) as u32],
+            );
+            for (model, instance_buffer) in models {
+                render_pass.set_vertex_buffer(1, instance_buffer.read().unwrap().slice(..));
+                for mesh in &model.meshes {
+                    render_pass.draw_mesh_geometry(mesh, 0..1);
+                }
+            }
+        }
+    }
+
+    pub fn gpu_memory(&self) -> u64 {
+        let texel = texture::Texture::DEPTH_FORMAT
+            .block_copy_size(None)
+            .unwrap_or(4) as u64;
+        let cascades = SHADOW_MAP_SIZE as u64 * SHADOW_MAP_SIZE as u64 * MAX_CASCADES as u64;
+        let points =
+            POINT_SHADOW_MAP_SIZE as u64 * POINT_SHADOW_MAP_SIZE as u64 * POINT_LAYERS as u64;
+        (cascades + points) * texel
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector4;
+
+    fn frustum(eye: Vector3<f32>, forward: Vector3<f32>) -> CameraFrustum {
+        CameraFrustum {
+            eye,
+            forward: forward.normalize(),
+            fovy: Rad(1.0),
+            aspect: 16.0 / 9.0,
+            znear: 0.1,
+        }
+    }
+
+    #[test]
+    fn test_split_distances() {
+        let splits = split_distances(0.1, 100.0, 4, 0.7);
+        assert_eq!(splits.len(), 4);
+        assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((splits[3] - 100.0).abs() < 1e-3);
+
+        // Without the logarithmic part the splits are even
+        let uniform = split_distances(0.0001, 100.0, 4, 0.0);
+        assert!((uniform[0] - 25.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_cascades_cover_their_slices() {
+        let settings = ShadowSettings::default();
+        let camera = frustum(Vector3::new(3.0, 2.0, -5.0), Vector3::new(1.0, -0.2, 0.5));
+        let to_light = Vector3::new(0.3, 1.0, 0.2);
+        let cascades = fit_cascades(&camera, to_light, &settings);
+        assert_eq!(cascades.len(), 4);
+
+        // The far corners of a slice land inside its cascade, give or take the texel it was snapped by
+        let edge = 1.0 + 2.0 / SHADOW_MAP_SIZE as f32;
+        let (right, up) = camera.basis();
+        let tan_y = (camera.fovy.0 * 0.5).tan();
+        for cascade in &cascades {
+            let center = camera.eye + camera.forward * cascade.far;
+            for (x, y) in [(-1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                let corner = center
+                    + right * (x * cascade.far * tan_y * camera.aspect)
+                    + up * (y * cascade.far * tan_y);
+                let clip = cascade.view_proj * corner.extend(1.0);
+                assert!(clip.x.abs() <= edge && clip.y.abs() <= edge);
+                assert!((0.0..=1.0).contains(&clip.z));
+            }
+        }
+    }
+
+    #[test]
+    fn test_cascade_cache() {
+        let mut cache = CascadeCache::default();
+        let settings = ShadowSettings::default();
+        let camera = frustum(Vector3::new(0.0, 2.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        let cascades = fit_cascades(&camera, Vector3::new(0.3, 1.0, 0.2), &settings);
+        assert!(cascades
+            .iter()
+            .enumerate()
+            .all(|(i, cascade)| cache.is_stale(i, cascade.view_proj, 1)));
+
+        // Nothing changed, so nothing is drawn again
+        assert!(cascades
+            .iter()
+            .enumerate()
+            .all(|(i, cascade)| !cache.is_stale(i, cascade.view_proj, 1)));
+        // A moved caster draws all of them again
+        assert!(cache.is_stale(0, cascades[0].view_proj, 2));
+
+        // Turning the light moves every cascade
+        let turned = fit_cascades(&camera, Vector3::new(-0.3, 1.0, 0.2), &settings);
+        assert!(cache.is_stale(1, turned[1].view_proj, 1));
+    }
+
+    #[test]
+    fn test_swapped_mesh_invalidates_the_cache() {
+        let mut cache = CascadeCache::default();
+        let camera = frustum(Vector3::new(0.0, 2.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        let cascades = fit_cascades(&camera, Vector3::new(0.3, 1.0, 0.2), &Default::default());
+        let ecs = crate::ecs::Manager::default();
+        let entity = ecs.create_entity();
+        let transform = Some(Matrix4::from_translation(Vector3::new(1.0, 0.0, 2.0)));
+        let casters = |meshes: &[u64]| {
+            let mut hasher = crate::core::checksum::StableHasher::default();
+            hash_caster(&mut hasher, entity, meshes.iter(), transform);
+            hasher.finish()
+        };
+
+        let placeholder = casters(&[1]);
+        assert!(cache.is_stale(0, cascades[0].view_proj, placeholder));
+        assert!(!cache.is_stale(0, cascades[0].view_proj, casters(&[1])));
+
+        // The streamed model replaces the placeholder at the same transform
+        assert!(cache.is_stale(0, cascades[0].view_proj, casters(&[2, 3])));
+        // A mesh removed by an override
+        assert!(cache.is_stale(0, cascades[0].view_proj, casters(&[2])));
+    }
+
+    #[test]
+    fn test_point_faces() {
+        let position = Vector3::new(1.0, 2.0, 3.0);
+        let faces = point_faces(position, 10.0);
+        let directions = [
+            Vector3::unit_x(),
+            -Vector3::unit_x(),
+            Vector3::unit_y(),
+            -Vector3::unit_y(),
+            Vector3::unit_z(),
+            -Vector3::unit_z(),
+        ];
+        for (face, direction) in faces.iter().zip(directions) {
+            // The axis of a face is its center, a point off to the side is still inside it
+            let center = face * (position + direction * 5.0).extend(1.0);
+            assert!((center.x / center.w).abs() < 1e-5 && (center.y / center.w).abs() < 1e-5);
+            assert!((0.0..=1.0).contains(&(center.z / center.w)));
+            let side = direction.cross(Vector3::new(0.3, 0.5, 0.7)).normalize() * 2.0;
+            let clip = face * (position + direction * 5.0 + side).extend(1.0);
+            assert!((clip.x / clip.w).abs() <= 1.0 && (clip.y / clip.w).abs() <= 1.0);
+
+            // Beyond the range of the light
+            let beyond = face * (position + direction * 11.0).extend(1.0);
+            assert!(beyond.z / beyond.w > 1.0);
+        }
+    }
+
+    #[test]
+    fn test_cascades_are_stable() {
+        let settings = ShadowSettings::default();
+        let to_light = Vector3::new(0.3, 1.0, 0.2);
+        let a = fit_cascades(
+            &frustum(Vector3::new(0.0, 2.0, 0.0), Vector3::new(1.0, 0.0, 0.0)),
+            to_light,
+            &settings,
+        );
+        let b = fit_cascades(
+            &frustum(Vector3::new(0.37, 2.0, 0.11), Vector3::new(0.2, 0.0, 1.0)),
+            to_light,
+            &settings,
+        );
+
+        let origin = Vector4::new(0.0, 0.0, 0.0, 1.0);
+        for (a, b) in a.iter().zip(&b) {
+            // Turning the camera keeps the size of the cascades
+            assert!((a.view_proj.x.x - b.view_proj.x.x).abs() < 1e-6);
+            // Moving the camera moves the cascades by whole texels
+            let texels = ((a.view_proj * origin).x - (b.view_proj * origin).x)
+                * SHADOW_MAP_SIZE as f32
+                / 2.0;
+            assert!((texels - texels.round()).abs() < 0.05);
+        }
+    }
+}
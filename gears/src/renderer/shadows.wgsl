@@ -0,0 +1,128 @@
+// The cascaded shadows of a directional light and the cube shadows of the point lights,
+// included by `lighting` when SHADOWS is defined.
+// The including shader declares the shadow bindings at its own group:
+//   var<uniform> shadow: Shadows;
+//   var shadow_map: texture_depth_2d_array;
+//   var shadow_sampler: sampler_comparison;
+//   var point_shadow_map: texture_depth_2d_array;
+
+struct Shadows {
+    view_proj: array<mat4x4<f32>, 4>,
+    eye: vec4<f32>,
+    forward: vec4<f32>,
+    // The view distance each cascade ends at
+    splits: vec4<f32>,
+    depth_bias: vec4<f32>,
+    normal_offset: vec4<f32>,
+    count: u32,
+    // The index of the shadowed light
+    light: u32,
+    enabled: u32,
+    debug: u32,
+    // The 6 faces of each point light cube, +x, -x, +y, -y, +z, -z
+    point_view_proj: array<mat4x4<f32>, 24>,
+    // The indices of the shadowed point lights
+    point_lights: vec4<u32>,
+    point_count: u32,
+    // The biases at a distance of 1 from the light
+    point_depth_bias: f32,
+    point_normal_offset: f32,
+    _padding: u32,
+}
+
+// Get the cascade a point is shadowed from, the count of the cascades beyond the shadow distance
+fn shadow_cascade(position: vec3<f32>) -> u32 {
+    let depth = dot(position - shadow.eye.xyz, shadow.forward.xyz);
+    var cascade = 0u;
+    while (cascade < shadow.count && depth > shadow.splits[cascade]) {
+        cascade = cascade + 1u;
+    }
+    return cascade;
+}
+
+// The fraction of the light reaching a point, filtered over 3x3 texels of its cascade
+fn shadow_factor(position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let cascade = shadow_cascade(position);
+    if (cascade >= shadow.count) {
+        return 1.0;
+    }
+    let offset_position = position + normalize(normal) * shadow.normal_offset[cascade];
+    let clip = shadow.view_proj[cascade] * vec4<f32>(offset_position, 1.0);
+    let uv = clip.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    let reference = clip.z - shadow.depth_bias[cascade];
+    let texel = 1.0 / vec2<f32>(textureDimensions(shadow_map));
+
+    var lit = 0.0;
+    for (var y = -1; y <= 1; y = y + 1) {
+        for (var x = -1; x <= 1; x = x + 1) {
+            lit = lit + textureSampleCompareLevel(
+                shadow_map,
+                shadow_sampler,
+                uv + vec2<f32>(f32(x), f32(y)) * texel,
+                cascade,
+                reference,
+            );
+        }
+    }
+    return lit / 9.0;
+}
+
+// Get the slot of a shadowed point light, the count of the point shadows if it casts none
+fn point_shadow_slot(index: u32) -> u32 {
+    var slot = 0u;
+    while (slot < shadow.point_count && shadow.point_lights[slot] != index) {
+        slot = slot + 1u;
+    }
+    return slot;
+}
+
+// The fraction of a point light reaching a point, filtered over 3x3 texels of the face of the cube it is in
+fn point_shadow_factor(slot: u32, light_position: vec3<f32>, position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let offset = position - light_position;
+    let distance = length(offset);
+    // The texels grow with the distance, so do the biases
+    let to_light = -offset / max(distance, 0.0001);
+    let biased = position + normalize(normal) * shadow.point_normal_offset * distance
+        + to_light * shadow.point_depth_bias * distance;
+
+    let axis = abs(offset);
+    var face = 0u;
+    if (axis.x >= axis.y && axis.x >= axis.z) {
+        face = select(1u, 0u, offset.x > 0.0);
+    } else if (axis.y >= axis.z) {
+        face = select(3u, 2u, offset.y > 0.0);
+    } else {
+        face = select(5u, 4u, offset.z > 0.0);
+    }
+    let layer = slot * 6u + face;
+    let clip = shadow.point_view_proj[layer] * vec4<f32>(biased, 1.0);
+    let ndc = clip.xyz / clip.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    let texel = 1.0 / vec2<f32>(textureDimensions(point_shadow_map));
+
+    var lit = 0.0;
+    for (var y = -1; y <= 1; y = y + 1) {
+        for (var x = -1; x <= 1; x = x + 1) {
+            lit = lit + textureSampleCompareLevel(
+                point_shadow_map,
+                shadow_sampler,
+                uv + vec2<f32>(f32(x), f32(y)) * texel,
+                layer,
+                ndc.z,
+            );
+        }
+    }
+    return lit / 9.0;
+}
+
+// The tint of the cascade a point is shadowed from, to see the splits
+fn cascade_color(position: vec3<f32>) -> vec3<f32> {
+    var colors = array<vec3<f32>, 5>(
+        vec3<f32>(1.0, 0.5, 0.5),
+        vec3<f32>(0.5, 1.0, 0.5),
+        vec3<f32>(0.5, 0.5, 1.0),
+        vec3<f32>(1.0, 1.0, 0.5),
+        vec3<f32>(1.0, 1.0, 1.0),
+    );
+    return colors[shadow_cascade(position)];
+}
@@ -0,0 +1,118 @@
+use cgmath::{EuclideanSpace, Point3, Rad};
+use wgpu::util::DeviceExt;
+
+use super::camera::{Camera, CameraUniform, Projection};
+use crate::ecs::{self, components};
+use crate::input::players::{LocalPlayers, PlayerCamera};
+
+/// The camera of a local player and the part of the frame it is drawn to.
+pub(crate) struct PlayerView {
+    /// The x, y, width and height in 0..1 from the top left corner.
+    pub viewport: [f32; 4],
+    uniform: CameraUniform,
+    buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl PlayerView {
+    /// Get the viewport in pixels of a frame.
+    pub fn pixels(&self, width: u32, height: u32) -> [f32; 4] {
+        let [x, y, w, h] = self.viewport;
+        let (width, height) = (width as f32, height as f32);
+        [x * width, y * height, w * width, h * height]
+    }
+}
+
+/// The split-screen views of the local players with a camera entity, empty when the frame is not split.
+#[derive(Default)]
+pub(crate) struct PlayerViews {
+    views: Vec<PlayerView>,
+    /// The number of views drawn this frame, the buffers of the rest are kept for later.
+    active: usize,
+}
+
+impl PlayerViews {
+    pub fn active(&self) -> &[PlayerView] {
+        &self.views[..self.active]
+    }
+
+    pub fn is_split(&self) -> bool {
+        self.active > 0
+    }
+
+    /// Update the cameras of the players from their entities, a view is created for each new player.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        ecs: &ecs::Manager,
+        width: u32,
+        height: u32,
+        fovy: Rad<f32>,
+        znear: f32,
+        zfar: f32,
+    ) {
+        let Some(players) = ecs.get_resource::<LocalPlayers>() else {
+            self.active = 0;
+            return;
+        };
+        let players = players.read().unwrap();
+        let cameras = players
+            .cameras()
+            .filter_map(|(player, entity)| {
+                let pos = ecs.get_component_from_entity::<components::Pos3>(entity)?;
+                let view = ecs.get_component_from_entity::<PlayerCamera>(entity)?;
+                let pos = pos.read().unwrap().pos;
+                let view = *view.read().unwrap();
+                Some((
+                    player,
+                    Camera::new(Point3::from_vec(pos), view.yaw, view.pitch),
+                ))
+            })
+            .collect::<Vec<_>>();
+        let viewports = players.layout.viewports(cameras.len());
+        self.active = viewports.len();
+
+        for (i, ((_, camera), viewport)) in cameras.iter().zip(viewports).enumerate() {
+            if i == self.views.len() {
+                self.views.push(Self::create_view(device, layout));
+            }
+            let view = &mut self.views[i];
+            view.viewport = viewport;
+            let projection = Projection::new(
+                ((viewport[2] * width as f32) as u32).max(1),
+                ((viewport[3] * height as f32) as u32).max(1),
+                fovy,
+                znear,
+                zfar,
+            );
+            view.uniform.update_view_proj(camera, &projection);
+            queue.write_buffer(&view.buffer, 0, bytemuck::cast_slice(&[view.uniform]));
+        }
+    }
+
+    fn create_view(device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> PlayerView {
+        let uniform = CameraUniform::new();
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Player Camera Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("player_camera_bind_group"),
+        });
+        PlayerView {
+            viewport: [0.0, 0.0, 1.0, 1.0],
+            uniform,
+            buffer,
+            bind_group,
+        }
+    }
+}
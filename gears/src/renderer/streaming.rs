@@ -0,0 +1,375 @@
+use super::{model, texture};
+use crate::core::config::TextureStreamingConfig;
+use crate::ecs;
+use crate::ecs::components::Pos3;
+use cgmath::{MetricSpace, Vector3};
+use image::imageops::FilterType;
+use image::RgbaImage;
+use std::thread::JoinHandle;
+
+/// Get the mip chain of an image, from the full resolution down to 1x1.
+pub(crate) fn mip_chain(image: RgbaImage) -> Vec<RgbaImage> {
+    let mut mips = vec![image];
+    loop {
+        let (width, height) = mips.last().unwrap().dimensions();
+        if width <= 1 && height <= 1 {
+            return mips;
+        }
+        let next = image::imageops::resize(
+            mips.last().unwrap(),
+            (width / 2).max(1),
+            (height / 2).max(1),
+            FilterType::Triangle,
+        );
+        mips.push(next);
+    }
+}
+
+/// Get the mip level wanted at a distance from the camera.
+///
+/// # Arguments
+///
+/// * `distance` - The distance of the textured model from the camera.
+/// * `full_resolution_distance` - The distance up to which the full resolution is wanted.
+/// * `mip_count` - The number of levels of the texture.
+pub(crate) fn wanted_level(distance: f32, full_resolution_distance: f32, mip_count: u32) -> u32 {
+    if full_resolution_distance <= 0.0 || distance <= full_resolution_distance {
+        return 0;
+    }
+    ((distance / full_resolution_distance).log2().floor() as u32).min(mip_count.saturating_sub(1))
+}
+
+/// The streaming state of a texture, the input of `plan`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StreamState {
+    pub distance: f32,
+    /// The largest level on the GPU.
+    pub resident: u32,
+    pub wanted: u32,
+    /// The largest level which is never evicted.
+    pub floor: u32,
+    /// The size of the mip chain starting at each level in bytes.
+    pub chain_bytes: Vec<u64>,
+}
+
+/// Decide the resident level of the streamed textures for this frame.
+/// Over the budget, the levels nobody wants are dropped first, furthest textures first.
+/// Then the closest textures are refined by a level, evicting levels of further textures to fit in the budget.
+///
+/// # Arguments
+///
+/// * `states` - The textures.
+/// * `budget` - The GPU memory the textures can use in bytes.
+/// * `max_changes` - The largest number of textures to reallocate.
+///
+/// # Returns
+///
+/// The new resident level of each texture.
+pub(crate) fn plan(states: &[StreamState], budget: u64, max_changes: usize) -> Vec<u32> {
+    let bytes = |i: usize, level: u32| states[i].chain_bytes[level as usize];
+    let mut resident = states
+        .iter()
+        .map(|state| state.resident)
+        .collect::<Vec<_>>();
+    let mut total: u64 = (0..states.len()).map(|i| bytes(i, resident[i])).sum();
+    let mut changed = vec![false; states.len()];
+    let mut changes = 0;
+    let mut change = |i: usize, changed: &mut Vec<bool>| {
+        if !changed[i] {
+            changed[i] = true;
+            changes += 1;
+        }
+        changes <= max_changes
+    };
+
+    let mut furthest = (0..states.len()).collect::<Vec<_>>();
+    furthest.sort_by(|a, b| states[*b].distance.total_cmp(&states[*a].distance));
+
+    for &i in &furthest {
+        if total <= budget {
+            break;
+        }
+        if resident[i] < states[i].wanted && change(i, &mut changed) {
+            total -= bytes(i, resident[i]) - bytes(i, states[i].wanted);
+            resident[i] = states[i].wanted;
+        }
+    }
+
+    let mut closest = (0..states.len())
+        .filter(|i| states[*i].wanted < resident[*i])
+        .collect::<Vec<_>>();
+    closest.sort_by(|a, b| states[*a].distance.total_cmp(&states[*b].distance));
+
+    'refine: for i in closest {
+        let level = resident[i] - 1;
+        let extra = bytes(i, level) - bytes(i, resident[i]);
+
+        while total + extra > budget {
+            let victim = furthest.iter().copied().find(|j| {
+                states[*j].distance > states[i].distance && resident[*j] < states[*j].floor
+            });
+            match victim {
+                Some(j) if change(j, &mut changed) => {
+                    total -= bytes(j, resident[j]) - bytes(j, resident[j] + 1);
+                    resident[j] += 1;
+                }
+                _ => break 'refine,
+            }
+        }
+        if !change(i, &mut changed) {
+            break;
+        }
+        resident[i] = level;
+        total += extra;
+    }
+
+    resident
+}
+
+enum Source {
+    Decoding(JoinHandle<anyhow::Result<Vec<RgbaImage>>>),
+    Ready(Vec<RgbaImage>),
+    Failed,
+}
+
+/// A texture decoded in the background, with only some of its mip levels on the GPU.
+/// The whole mip chain is kept in memory to upload the levels on demand.
+pub(crate) struct StreamedTexture {
+    label: String,
+    source: Source,
+    resident: u32,
+}
+
+impl StreamedTexture {
+    /// Decode and mipmap the image on a background thread.
+    pub fn load(label: &str, bytes: Vec<u8>) -> Self {
+        let decode = move || Ok(mip_chain(image::load_from_memory(&bytes)?.to_rgba8()));
+
+        Self {
+            label: label.to_string(),
+            source: Source::Decoding(std::thread::spawn(decode)),
+            resident: 0,
+        }
+    }
+
+    /// A grey texture to show until the image is decoded.
+    pub fn placeholder(device: &wgpu::Device, queue: &wgpu::Queue) -> texture::Texture {
+        let pixel = RgbaImage::from_pixel(1, 1, image::Rgba([128, 128, 128, 255]));
+        texture::Texture::from_mips(device, queue, &[pixel], Some("Streaming placeholder")).unwrap()
+    }
+
+    /// Check the background decoding.
+    ///
+    /// # Returns
+    ///
+    /// True if the image was decoded since the last call.
+    fn poll(&mut self) -> bool {
+        if !matches!(&self.source, Source::Decoding(handle) if handle.is_finished()) {
+            return false;
+        }
+        let Source::Decoding(handle) = std::mem::replace(&mut self.source, Source::Failed) else {
+            return false;
+        };
+
+        match handle.join() {
+            Ok(Ok(mips)) => {
+                self.source = Source::Ready(mips);
+                true
+            }
+            Ok(Err(e)) => {
+                log::error!("[Streaming] Failed to decode {}: {}", self.label, e);
+                false
+            }
+            Err(_) => {
+                log::error!("[Streaming] The decoding of {} panicked", self.label);
+                false
+            }
+        }
+    }
+
+    fn state(&self, distance: f32, config: &TextureStreamingConfig) -> Option<StreamState> {
+        let Source::Ready(mips) = &self.source else {
+            return None;
+        };
+
+        let floor = mips
+            .iter()
+            .position(|mip| mip.width().max(mip.height()) <= config.resident_size)
+            .unwrap_or(mips.len() - 1) as u32;
+        let mut chain_bytes = mips
+            .iter()
+            .rev()
+            .scan(0, |bytes, mip| {
+                *bytes += mip.as_raw().len() as u64;
+                Some(*bytes)
+            })
+            .collect::<Vec<_>>();
+        chain_bytes.reverse();
+
+        Some(StreamState {
+            distance,
+            resident: self.resident,
+            wanted: wanted_level(distance, config.full_resolution_distance, mips.len() as u32)
+                .min(floor),
+            floor,
+            chain_bytes,
+        })
+    }
+
+    /// Upload the mip chain from a level, replacing the previous allocation.
+    fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        level: u32,
+    ) -> Option<texture::Texture> {
+        let Source::Ready(mips) = &self.source else {
+            return None;
+        };
+        let level = level.min(mips.len() as u32 - 1);
+        self.resident = level;
+
+        texture::Texture::from_mips(device, queue, &mips[level as usize..], Some(&self.label))
+            .inspect_err(|e| log::error!("[Streaming] Failed to upload {}: {}", self.label, e))
+            .ok()
+    }
+}
+
+/// Refines and evicts the mip levels of the streamed model textures every frame.
+pub(crate) struct TextureStreamer {
+    config: TextureStreamingConfig,
+    resident_bytes: u64,
+}
+
+impl TextureStreamer {
+    pub fn new(config: TextureStreamingConfig) -> Self {
+        Self {
+            config,
+            resident_bytes: 0,
+        }
+    }
+
+    /// Get the GPU memory used by the streamed textures in bytes.
+    pub fn resident_bytes(&self) -> u64 {
+        self.resident_bytes
+    }
+
+    /// Upload the decoded textures, then refine the textures close to the camera and evict
+    /// the levels of the far ones to stay within the budget.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        ecs: &ecs::Manager,
+        camera: Vector3<f32>,
+    ) {
+        let models = ecs.get_all_components_of_type::<model::Model>();
+        let mut textures = Vec::new();
+        let mut states = Vec::new();
+
+        for (entity, model) in &models {
+            let distance = ecs
+                .get_component_from_entity::<Pos3>(*entity)
+                .map_or(0.0, |pos| pos.read().unwrap().pos.distance(camera));
+
+            let mut model_lock = model.write().unwrap();
+            for (index, material) in model_lock.materials.iter_mut().enumerate() {
+                let Some(stream) = &mut material.stream else {
+                    continue;
+                };
+
+                // Start from the low resolution levels, they are refined over the next frames
+                if stream.poll() {
+                    let floor = stream
+                        .state(distance, &self.config)
+                        .map_or(0, |state| state.floor);
+                    if let Some(texture) = stream.upload(device, queue, floor) {
+                        material.bind_group =
+                            model::Material::create_bind_group(device, layout, &texture);
+                        material.diffuse_texture = texture;
+                    }
+                }
+
+                if let Some(state) = stream.state(distance, &self.config) {
+                    states.push(state);
+                    textures.push((model.clone(), index));
+                }
+            }
+        }
+
+        let levels = plan(&states, self.config.budget, self.config.uploads_per_frame);
+        for ((model, index), (state, level)) in textures.iter().zip(states.iter().zip(&levels)) {
+            if *level == state.resident {
+                continue;
+            }
+
+            let mut model_lock = model.write().unwrap();
+            let material = &mut model_lock.materials[*index];
+            if let Some(texture) = material
+                .stream
+                .as_mut()
+                .and_then(|stream| stream.upload(device, queue, *level))
+            {
+                material.bind_group = model::Material::create_bind_group(device, layout, &texture);
+                material.diffuse_texture = texture;
+            }
+        }
+
+        self.resident_bytes = states
+            .iter()
+            .zip(&levels)
+            .map(|(state, level)| state.chain_bytes[*level as usize])
+            .sum();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(distance: f32, resident: u32, wanted: u32) -> StreamState {
+        // A 4x4 texture, 64 + 16 + 4 bytes
+        StreamState {
+            distance,
+            resident,
+            wanted,
+            floor: 2,
+            chain_bytes: vec![84, 20, 4],
+        }
+    }
+
+    #[test]
+    fn test_mip_levels() {
+        let mips = mip_chain(RgbaImage::new(8, 2));
+        assert_eq!(
+            mips.iter().map(|mip| mip.dimensions()).collect::<Vec<_>>(),
+            vec![(8, 2), (4, 1), (2, 1), (1, 1)]
+        );
+
+        assert_eq!(wanted_level(5.0, 10.0, 4), 0);
+        assert_eq!(wanted_level(25.0, 10.0, 4), 1);
+        assert_eq!(wanted_level(45.0, 10.0, 4), 2);
+        assert_eq!(wanted_level(1000.0, 10.0, 4), 3);
+    }
+
+    #[test]
+    fn test_plan() {
+        // Everything fits, each texture is refined by one level per frame
+        let states = vec![state(1.0, 2, 0), state(2.0, 2, 0)];
+        assert_eq!(plan(&states, 1000, 4), vec![1, 1]);
+        // The closest texture is refined first
+        assert_eq!(plan(&states, 1000, 1), vec![1, 2]);
+
+        // Over the budget the far texture is evicted for the close one
+        let states = vec![state(50.0, 0, 0), state(1.0, 1, 0)];
+        assert_eq!(plan(&states, 104, 4), vec![1, 0]);
+        // A far texture is not refined at the cost of a closer one
+        let states = vec![state(1.0, 0, 0), state(50.0, 1, 0)];
+        assert_eq!(plan(&states, 104, 4), vec![0, 1]);
+
+        // Unwanted levels are dropped when over the budget
+        let states = vec![state(1.0, 0, 0), state(50.0, 0, 2)];
+        assert_eq!(plan(&states, 100, 4), vec![0, 2]);
+    }
+}
@@ -122,4 +122,84 @@ impl Texture {
             sampler,
         })
     }
+
+    /// Create the magenta checker texture used in place of a texture which failed to load.
+    pub fn missing(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        const SIZE: u32 = 64;
+        const CHECK: u32 = 8;
+        let checker = image::RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+            if (x / CHECK + y / CHECK).is_multiple_of(2) {
+                image::Rgba([255, 0, 255, 255])
+            } else {
+                image::Rgba([0, 0, 0, 255])
+            }
+        });
+        Self::from_mips(device, queue, &[checker], Some("Missing texture")).unwrap()
+    }
+
+    /// Create a texture with a mip chain, the first image is the largest level.
+    pub fn from_mips(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mips: &[image::RgbaImage],
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let Some(first) = mips.first() else {
+            bail!("The mip chain of {:?} is empty", label);
+        };
+        let size = wgpu::Extent3d {
+            width: first.width(),
+            height: first.height(),
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: mips.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (level, mip) in mips.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                mip,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * mip.width()),
+                    rows_per_image: Some(mip.height()),
+                },
+                wgpu::Extent3d {
+                    width: mip.width(),
+                    height: mip.height(),
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
 }
@@ -0,0 +1,505 @@
+use super::headless::HeadlessRenderer;
+use crate::core::config::Config;
+use crate::core::vfs::{self, AssetLoadFailed};
+use crate::core::Dt;
+use crate::ecs::{self, components};
+use cgmath::{Deg, InnerSpace, Matrix3, Vector2, Vector3, Zero};
+use image::imageops::FilterType;
+use image::RgbaImage;
+use std::collections::HashMap;
+use std::io::{BufReader, Cursor};
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+
+/// The yaw of the turntable frame of a model thumbnail.
+pub const DEFAULT_YAW: Deg<f32> = Deg(35.0);
+
+/// The triangles of a mesh and its material, as the thumbnail rasterizer draws them.
+#[derive(Debug, Clone, Default)]
+pub struct ThumbnailMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub tex_coords: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+    pub texture: Option<RgbaImage>,
+    pub color: [f32; 3],
+}
+
+impl ThumbnailMesh {
+    fn albedo(&self, indices: [usize; 3], barycentric: [f32; 3]) -> [f32; 3] {
+        let Some(texture) = &self.texture else {
+            return self.color;
+        };
+        if indices.iter().any(|i| *i >= self.tex_coords.len()) {
+            return self.color;
+        }
+
+        let uv = indices
+            .iter()
+            .zip(barycentric)
+            .map(|(i, weight)| Vector2::from(self.tex_coords[*i]) * weight)
+            .fold(Vector2::new(0.0, 0.0), |sum, uv| sum + uv);
+        let x = (uv.x * texture.width() as f32).floor() as i64;
+        let y = ((1.0 - uv.y) * texture.height() as f32).floor() as i64;
+        let pixel = texture.get_pixel(
+            x.rem_euclid(texture.width() as i64) as u32,
+            y.rem_euclid(texture.height() as i64) as u32,
+        );
+        [0, 1, 2].map(|c| pixel[c] as f32 / 255.0)
+    }
+}
+
+/// The pitch of the turntable frame, the models are seen slightly from above.
+const PITCH: Deg<f32> = Deg(25.0);
+
+/// Renders the turntable frames of the models on the GPU with a `HeadlessRenderer`,
+/// so they are shaded like in the game.
+pub struct ThumbnailRenderer {
+    ecs: Arc<Mutex<ecs::Manager>>,
+    renderer: HeadlessRenderer,
+    size: u32,
+    /// The components hold static strings, so the path of each model is leaked once.
+    paths: HashMap<String, &'static str>,
+}
+
+impl ThumbnailRenderer {
+    /// Set up a headless renderer lit by an ambient and a directional light.
+    /// The frames are rendered at twice the size of the thumbnails and downsampled.
+    ///
+    /// # Returns
+    ///
+    /// An error if the size is empty or there is no GPU.
+    pub async fn new(size: u32) -> anyhow::Result<Self> {
+        let ecs = Arc::new(Mutex::new(ecs::Manager::default()));
+        {
+            let ecs = ecs.lock().unwrap();
+            let light = Vector3::new(0.4, 0.6, 0.7).normalize();
+            for light in [
+                components::Light::Ambient { intensity: 0.35 },
+                components::Light::Directional {
+                    direction: (-light).into(),
+                    intensity: 0.8,
+                },
+            ] {
+                let entity = ecs.create_entity();
+                ecs.add_component_to_entity(entity, components::Pos3::new(Vector3::zero()));
+                ecs.add_component_to_entity(entity, light);
+            }
+        }
+
+        let mut settings = Config::default().renderer();
+        settings.window.size = Some((size * 2, size * 2));
+        let renderer = HeadlessRenderer::new(Arc::clone(&ecs), settings).await?;
+        Ok(Self {
+            ecs,
+            renderer,
+            size,
+            paths: HashMap::new(),
+        })
+    }
+
+    /// Render a turntable frame of a model, fitted to the image.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The VFS path of the model.
+    /// * `yaw` - The rotation of the turntable.
+    ///
+    /// # Returns
+    ///
+    /// The thumbnail, or an error if the model can not be loaded.
+    pub async fn render(&mut self, path: &str, yaw: Deg<f32>) -> anyhow::Result<egui::ColorImage> {
+        let obj_path = *self
+            .paths
+            .entry(path.to_string())
+            .or_insert_with(|| Box::leak(path.to_string().into_boxed_str()));
+        let entity = {
+            let ecs = self.ecs.lock().unwrap();
+            let entity = ecs.create_entity();
+            ecs.add_component_to_entity(entity, components::Name("Thumbnail"));
+            ecs.add_component_to_entity(entity, components::Pos3::new(Vector3::zero()));
+            ecs.add_component_to_entity(entity, components::Model::Static { obj_path });
+            entity
+        };
+
+        // The model is loaded in the first frame, the second one is framed by its bounds
+        let dt = Dt::from_secs_f32(1.0 / 60.0);
+        self.renderer.render_frame(dt).await;
+        let (bounds, failure) = {
+            let ecs = self.ecs.lock().unwrap();
+            let failure = ecs
+                .drain_events::<AssetLoadFailed>()
+                .into_iter()
+                .find(|failure| failure.path == path);
+            let bounds = ecs
+                .get_component_from_entity::<components::Bounds>(entity)
+                .map(|bounds| *bounds.read().unwrap());
+            (bounds, failure)
+        };
+        let frame = match (failure, bounds) {
+            (Some(failure), _) => Err(anyhow::anyhow!("{}", failure.error)),
+            (None, None) => Err(anyhow::anyhow!("The model {} has no vertices", path)),
+            (None, Some(bounds)) => {
+                let center = (bounds.min + bounds.max) / 2.0;
+                let radius = ((bounds.max - bounds.min).magnitude() / 2.0).max(f32::EPSILON);
+                // The sphere around the model fits the field of view, with a small margin
+                let distance = radius / (self.renderer.fovy() / 2.0).0.sin() * 1.1;
+                let direction =
+                    Matrix3::from_angle_y(-yaw) * Matrix3::from_angle_x(-PITCH) * Vector3::unit_z();
+                self.renderer.look_at(center + direction * distance, center);
+                self.renderer.render_frame(dt).await;
+                self.renderer.read_pixels()
+            }
+        };
+        self.ecs.lock().unwrap().remove_entity(entity);
+
+        let (width, height) = self.renderer.size();
+        let frame = RgbaImage::from_raw(width, height, frame?)
+            .ok_or_else(|| anyhow::anyhow!("The frame of {} is incomplete", path))?;
+        Ok(fit_image(&frame, self.size))
+    }
+}
+
+/// Draw a turntable frame of meshes, fitted to the image, on a transparent background.
+/// The meshes are rasterized on the CPU with a fixed light, it is used when there is no GPU
+/// for a `ThumbnailRenderer`.
+///
+/// # Arguments
+///
+/// * `meshes` - The meshes to draw.
+/// * `size` - The width and height of the image.
+/// * `yaw` - The rotation of the turntable.
+pub fn rasterize(meshes: &[ThumbnailMesh], size: u32, yaw: Deg<f32>) -> egui::ColorImage {
+    // Render at twice the size and downsample for antialiasing
+    let scale = 2;
+    let target = (size * scale) as usize;
+    let mut color = vec![[0.0f32; 4]; target * target];
+    let mut depth = vec![f32::MIN; target * target];
+
+    let positions = meshes.iter().flat_map(|mesh| &mesh.positions);
+    let (min, max) = positions.fold(
+        (Vector3::from([f32::MAX; 3]), Vector3::from([f32::MIN; 3])),
+        |(min, max), p| {
+            (
+                Vector3::new(min.x.min(p[0]), min.y.min(p[1]), min.z.min(p[2])),
+                Vector3::new(max.x.max(p[0]), max.y.max(p[1]), max.z.max(p[2])),
+            )
+        },
+    );
+    let center = (min + max) / 2.0;
+    let radius = ((max - min).magnitude() / 2.0).max(f32::EPSILON);
+    let rotation = Matrix3::from_angle_x(PITCH) * Matrix3::from_angle_y(yaw);
+    let light = Vector3::new(0.4, 0.6, 0.7).normalize();
+
+    for mesh in meshes {
+        let view = mesh
+            .positions
+            .iter()
+            .map(|p| rotation * (Vector3::from(*p) - center) / radius)
+            .collect::<Vec<_>>();
+        let screen = view
+            .iter()
+            .map(|p| {
+                Vector2::new(
+                    (p.x * 0.9 + 1.0) / 2.0 * target as f32,
+                    (1.0 - p.y * 0.9) / 2.0 * target as f32,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            let indices = [0, 1, 2].map(|i| triangle[i] as usize);
+            let [a, b, c] = indices.map(|i| view[i]);
+            let normal = (b - a).cross(c - a);
+            // Cull the triangles facing away, the camera looks down -z
+            if normal.z <= 0.0 {
+                continue;
+            }
+            let shade = 0.35 + 0.65 * normal.normalize().dot(light).max(0.0);
+
+            let [sa, sb, sc] = indices.map(|i| screen[i]);
+            let area = (sb - sa).perp_dot(sc - sa);
+            if area.abs() < f32::EPSILON {
+                continue;
+            }
+            let x0 = sa.x.min(sb.x).min(sc.x).floor().max(0.0) as usize;
+            let x1 = (sa.x.max(sb.x).max(sc.x).ceil() as usize).min(target);
+            let y0 = sa.y.min(sb.y).min(sc.y).floor().max(0.0) as usize;
+            let y1 = (sa.y.max(sb.y).max(sc.y).ceil() as usize).min(target);
+
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let p = Vector2::new(x as f32 + 0.5, y as f32 + 0.5);
+                    let barycentric = [
+                        (sc - sb).perp_dot(p - sb) / area,
+                        (sa - sc).perp_dot(p - sc) / area,
+                        (sb - sa).perp_dot(p - sa) / area,
+                    ];
+                    if barycentric.iter().any(|w| *w < 0.0) {
+                        continue;
+                    }
+
+                    let z = a.z * barycentric[0] + b.z * barycentric[1] + c.z * barycentric[2];
+                    let pixel = y * target + x;
+                    if z <= depth[pixel] {
+                        continue;
+                    }
+                    depth[pixel] = z;
+                    let albedo = mesh.albedo(indices, barycentric);
+                    color[pixel] = [albedo[0] * shade, albedo[1] * shade, albedo[2] * shade, 1.0];
+                }
+            }
+        }
+    }
+
+    let mut pixels = Vec::with_capacity((size * size) as usize);
+    for y in 0..size as usize {
+        for x in 0..size as usize {
+            let mut sum = [0.0f32; 4];
+            for sy in 0..scale as usize {
+                for sx in 0..scale as usize {
+                    let sample = color[(y * 2 + sy) * target + x * 2 + sx];
+                    // Premultiplied, so the background does not darken the edges
+                    for c in 0..3 {
+                        sum[c] += sample[c] * sample[3];
+                    }
+                    sum[3] += sample[3];
+                }
+            }
+            let samples = (scale * scale) as f32;
+            pixels.push(egui::Color32::from_rgba_premultiplied(
+                (sum[0] / samples * 255.0) as u8,
+                (sum[1] / samples * 255.0) as u8,
+                (sum[2] / samples * 255.0) as u8,
+                (sum[3] / samples * 255.0) as u8,
+            ));
+        }
+    }
+
+    egui::ColorImage {
+        size: [size as usize; 2],
+        pixels,
+    }
+}
+
+/// Load the meshes of an OBJ model through the VFS.
+pub fn load_thumbnail_meshes(path: &str) -> anyhow::Result<Vec<ThumbnailMesh>> {
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    let obj = vfs::read_to_string(path)?;
+    let (models, materials) = tobj::load_obj_buf(
+        &mut BufReader::new(Cursor::new(obj)),
+        &tobj::LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ..Default::default()
+        },
+        |p| {
+            let mtl =
+                vfs::read_to_string(dir.join(p).to_str().unwrap_or_default()).unwrap_or_default();
+            tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mtl)))
+        },
+    )?;
+    let materials = materials.unwrap_or_default();
+
+    Ok(models
+        .into_iter()
+        .map(|model| {
+            let material = model.mesh.material_id.and_then(|id| materials.get(id));
+            let texture = material
+                .and_then(|material| material.diffuse_texture.as_ref())
+                .and_then(|texture| vfs::read(dir.join(texture).to_str()?).ok())
+                .and_then(|data| image::load_from_memory(&data).ok())
+                .map(|image| image.to_rgba8());
+
+            ThumbnailMesh {
+                positions: model
+                    .mesh
+                    .positions
+                    .chunks_exact(3)
+                    .map(|p| [p[0], p[1], p[2]])
+                    .collect(),
+                tex_coords: model
+                    .mesh
+                    .texcoords
+                    .chunks_exact(2)
+                    .map(|t| [t[0], t[1]])
+                    .collect(),
+                indices: model.mesh.indices,
+                texture,
+                color: material
+                    .and_then(|material| material.diffuse)
+                    .unwrap_or([0.8, 0.8, 0.8]),
+            }
+        })
+        .collect())
+}
+
+/// Scale an image to fit a square, keeping its aspect ratio.
+pub fn fit_image(image: &RgbaImage, size: u32) -> egui::ColorImage {
+    let scale = size as f32 / image.width().max(image.height()).max(1) as f32;
+    let width = ((image.width() as f32 * scale).round() as u32).clamp(1, size);
+    let height = ((image.height() as f32 * scale).round() as u32).clamp(1, size);
+    let scaled = image::imageops::resize(image, width, height, FilterType::Triangle);
+
+    let mut out = RgbaImage::new(size, size);
+    image::imageops::overlay(
+        &mut out,
+        &scaled,
+        ((size - width) / 2) as i64,
+        ((size - height) / 2) as i64,
+    );
+    egui::ColorImage::from_rgba_unmultiplied([size as usize; 2], out.as_raw())
+}
+
+/// Render the preview of an asset, a turntable frame of a model or a quicklook of a texture.
+///
+/// # Arguments
+///
+/// * `path` - The VFS path of the asset.
+/// * `size` - The width and height of the thumbnail.
+/// * `renderer` - The GPU renderer of the models, they are rasterized on the CPU without it.
+pub fn thumbnail(
+    path: &str,
+    size: u32,
+    renderer: Option<&mut ThumbnailRenderer>,
+) -> anyhow::Result<egui::ColorImage> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    match (extension.as_str(), renderer) {
+        ("obj" | "gltf" | "glb", Some(renderer)) => {
+            futures::executor::block_on(renderer.render(path, DEFAULT_YAW))
+        }
+        ("obj", None) => Ok(rasterize(&load_thumbnail_meshes(path)?, size, DEFAULT_YAW)),
+        ("png" | "jpg" | "jpeg", _) => {
+            let image = image::load_from_memory(&vfs::read(path)?)?;
+            Ok(fit_image(&image.to_rgba8(), size))
+        }
+        _ => anyhow::bail!("There is no thumbnail for {}", path),
+    }
+}
+
+/// Renders the thumbnails on a background thread and keeps them as egui textures.
+/// The models are rendered on the GPU by a `ThumbnailRenderer` of the thread, or on the CPU
+/// if it can't be set up.
+pub struct Thumbnails {
+    size: u32,
+    textures: HashMap<String, Option<egui::TextureHandle>>,
+    pending: usize,
+    requests: mpsc::Sender<String>,
+    results: mpsc::Receiver<(String, anyhow::Result<egui::ColorImage>)>,
+}
+
+impl Thumbnails {
+    pub fn new(size: u32) -> Self {
+        let (requests, rx_requests) = mpsc::channel::<String>();
+        let (tx_results, results) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let mut renderer = futures::executor::block_on(ThumbnailRenderer::new(size))
+                .inspect_err(|e| {
+                    log::warn!("[Thumbnails] Rasterizing the models on the CPU: {}", e)
+                })
+                .ok();
+            for path in rx_requests {
+                let thumbnail = thumbnail(&path, size, renderer.as_mut());
+                if tx_results.send((path, thumbnail)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            size,
+            textures: HashMap::new(),
+            pending: 0,
+            requests,
+            results,
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Get the thumbnail of an asset, it is requested on the first call.
+    ///
+    /// # Returns
+    ///
+    /// The texture to show, `None` while it renders or if the asset has no thumbnail.
+    pub fn get(&mut self, ctx: &egui::Context, path: &str) -> Option<egui::TextureHandle> {
+        for (path, thumbnail) in self.results.try_iter() {
+            self.pending -= 1;
+            let texture = thumbnail
+                .inspect_err(|e| log::debug!("[Thumbnails] {}: {}", path, e))
+                .ok()
+                .map(|image| {
+                    ctx.load_texture(
+                        format!("thumbnail:{}", path),
+                        image,
+                        egui::TextureOptions::LINEAR,
+                    )
+                });
+            self.textures.insert(path, texture);
+        }
+
+        if !self.textures.contains_key(path) {
+            self.textures.insert(path.to_string(), None);
+            if self.requests.send(path.to_string()).is_ok() {
+                self.pending += 1;
+            }
+        }
+        if self.pending > 0 {
+            ctx.request_repaint();
+        }
+
+        self.textures.get(path).cloned().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rasterize() {
+        // A quad facing the camera at a yaw of 0
+        let quad = ThumbnailMesh {
+            positions: vec![
+                [-1.0, -1.0, 0.0],
+                [1.0, -1.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [-1.0, 1.0, 0.0],
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3],
+            color: [1.0, 0.0, 0.0],
+            ..Default::default()
+        };
+
+        let image = rasterize(std::slice::from_ref(&quad), 32, Deg(0.0));
+        let center = image.pixels[16 * 32 + 16];
+        assert_eq!(center.a(), 255);
+        assert!(center.r() > 0 && center.g() == 0 && center.b() == 0);
+        assert_eq!(image.pixels[0], egui::Color32::TRANSPARENT);
+
+        // The back face is culled
+        let image = rasterize(&[quad], 32, Deg(180.0));
+        assert!(image
+            .pixels
+            .iter()
+            .all(|p| *p == egui::Color32::TRANSPARENT));
+    }
+
+    #[test]
+    fn test_fit_image() {
+        let image = RgbaImage::from_pixel(40, 20, image::Rgba([0, 255, 0, 255]));
+        let thumbnail = fit_image(&image, 16);
+
+        assert_eq!(thumbnail.size, [16, 16]);
+        // Letterboxed, 16x8 in the middle
+        assert_eq!(thumbnail.pixels[8 * 16 + 8], egui::Color32::GREEN);
+        assert_eq!(thumbnail.pixels[0], egui::Color32::TRANSPARENT);
+    }
+}
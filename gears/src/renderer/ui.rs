@@ -0,0 +1,280 @@
+use super::{thumbnail, State};
+use crate::core::schedule::ScheduleInfo;
+use crate::ecs;
+use crate::ecs::components::{self, Name};
+use crate::editor;
+use crate::gameplay::dialogue::{self, DialoguePlayer};
+use crate::gui::asset_browser::{self, AssetBrowser, AssetSpawned};
+use crate::gui::layout::{self, HudAnchor, SafeArea};
+use crate::gui::loading_screen::{self, LoadingScreen};
+use crate::gui::stats_overlay;
+use cgmath::EuclideanSpace;
+use egui_wgpu::ScreenDescriptor;
+use log::info;
+use std::sync::Arc;
+
+impl State<'_> {
+    /// Draw the UI over the frame: the letterbox, the HUD, the editor tools, the overlays and the custom windows.
+    /// Nothing is drawn when the UI is disabled in the `RenderPassesConfig`.
+    pub(super) fn render_ui(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        let Some(target) = &mut self.window else {
+            return;
+        };
+        let Some(egui_renderer) = &mut target.egui_renderer else {
+            return;
+        };
+
+        // ! Egui render pass for the custom UI windows
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: [self.config.width, self.config.height],
+            pixels_per_point: target.window.scale_factor() as f32,
+        };
+
+        // * The safe area of the frame, the HUD and the custom windows anchor to it
+        let screen = egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(self.config.width as f32, self.config.height as f32)
+                / screen_descriptor.pixels_per_point,
+        );
+        let safe_area = SafeArea::new(screen, &self.display);
+        safe_area.store(egui_renderer.context());
+
+        // * Letterbox and pillarbox bars of the target aspect ratio and the playing cutscene
+        if self.letterbox || safe_area.viewport != safe_area.screen {
+            let cutscene = self.letterbox;
+            egui_renderer.draw_ui_full(
+                &self.device,
+                &self.queue,
+                encoder,
+                target.window,
+                view,
+                &screen_descriptor,
+                &mut |ctx: &egui::Context| {
+                    let screen = safe_area.screen;
+                    let mut viewport = safe_area.viewport;
+                    if cutscene {
+                        viewport = viewport.shrink2(egui::vec2(0.0, viewport.height() * 0.12));
+                    }
+
+                    let painter = ctx.layer_painter(egui::LayerId::background());
+                    let bars = [
+                        egui::Rect::from_min_max(screen.min, [screen.max.x, viewport.min.y].into()),
+                        egui::Rect::from_min_max([screen.min.x, viewport.max.y].into(), screen.max),
+                        egui::Rect::from_min_max(
+                            [screen.min.x, viewport.min.y].into(),
+                            [viewport.min.x, viewport.max.y].into(),
+                        ),
+                        egui::Rect::from_min_max(
+                            [viewport.max.x, viewport.min.y].into(),
+                            [screen.max.x, viewport.max.y].into(),
+                        ),
+                    ];
+                    for bar in bars.into_iter().filter(|bar| bar.is_positive()) {
+                        painter.rect_filled(bar, 0.0, egui::Color32::BLACK);
+                    }
+                },
+            );
+        }
+
+        // * Interaction prompt of the focused entity
+        if let Some(prompt) = self.interaction.prompt() {
+            let prompt = crate::tr!(prompt);
+            egui_renderer.draw_ui_full(
+                &self.device,
+                &self.queue,
+                encoder,
+                target.window,
+                view,
+                &screen_descriptor,
+                &mut |ctx: &egui::Context| {
+                    HudAnchor::new(egui::Align2::CENTER_CENTER)
+                        .with_offset([0.0, 40.0])
+                        .in_space(layout::AnchorSpace::Viewport)
+                        .area(ctx, "interaction_prompt")
+                        .show(ctx, |ui| {
+                            ui.label(egui::RichText::new(&prompt).strong());
+                        });
+                },
+            );
+        }
+
+        // * Subtitles of the playing dialogue lines
+        let subtitles: Vec<(ecs::Entity, dialogue::DialogueLine, dialogue::SubtitleStyle)> = {
+            let ecs_lock = self.ecs.lock().unwrap();
+            ecs_lock
+                .get_all_components_of_type::<DialoguePlayer>()
+                .into_iter()
+                .filter_map(|(entity, player)| {
+                    let player = player.read().unwrap();
+                    let line = player.current()?.clone();
+                    Some((entity, line, player.style.clone()))
+                })
+                .collect()
+        };
+        if !subtitles.is_empty() {
+            egui_renderer.draw_ui_full(
+                &self.device,
+                &self.queue,
+                encoder,
+                target.window,
+                view,
+                &screen_descriptor,
+                &mut |ctx: &egui::Context| {
+                    for (entity, line, style) in subtitles.iter() {
+                        let id = egui::Id::new(("subtitle", entity.0));
+                        dialogue::show_subtitle(ctx, id, line, style);
+                    }
+                },
+            );
+        }
+
+        // * Editor tools
+        if editor::has_editor_ui(&self.ecs.lock().unwrap()) {
+            let ecs = Arc::clone(&self.ecs);
+            egui_renderer.draw_ui_full(
+                &self.device,
+                &self.queue,
+                encoder,
+                target.window,
+                view,
+                &screen_descriptor,
+                &mut |ctx: &egui::Context| {
+                    editor::show_editor_ui(ctx, &ecs.lock().unwrap());
+                },
+            );
+        }
+
+        // * Asset browser, a model dragged onto the viewport is spawned on the ground
+        let browser = {
+            let ecs_lock = self.ecs.lock().unwrap();
+            ecs_lock
+                .get_all_components_of_type::<AssetBrowser>()
+                .into_iter()
+                .map(|(_, browser)| browser)
+                .find(|browser| browser.read().unwrap().open)
+        };
+        let mut dropped_asset = None;
+        if let Some(browser) = browser {
+            let thumbnails = self
+                .thumbnails
+                .get_or_insert_with(|| thumbnail::Thumbnails::new(128));
+            egui_renderer.draw_ui_full(
+                &self.device,
+                &self.queue,
+                encoder,
+                target.window,
+                view,
+                &screen_descriptor,
+                &mut |ctx: &egui::Context| {
+                    let mut browser = browser.write().unwrap();
+                    dropped_asset =
+                        asset_browser::show_asset_browser(ctx, &mut browser, thumbnails);
+                },
+            );
+        }
+
+        // * Stats overlay
+        if self.stats_overlay {
+            let schedule = self
+                .ecs
+                .lock()
+                .unwrap()
+                .get_all_components_of_type::<ScheduleInfo>()
+                .pop()
+                .map(|(_, schedule)| schedule.read().unwrap().clone());
+            let stats = &self.frame_stats;
+            egui_renderer.draw_ui_full(
+                &self.device,
+                &self.queue,
+                encoder,
+                target.window,
+                view,
+                &screen_descriptor,
+                &mut |ctx: &egui::Context| {
+                    stats_overlay::show_stats_overlay(ctx, stats, schedule.as_ref());
+                },
+            );
+        }
+
+        // * Loading screen over everything until the pipelines are warmed up
+        let progress = self.model_pipelines.progress();
+        let loading_screen = (!progress.is_done())
+            .then(|| {
+                let ecs_lock = self.ecs.lock().unwrap();
+                ecs_lock
+                    .get_all_components_of_type::<LoadingScreen>()
+                    .first()
+                    .map(|(_, screen)| screen.read().unwrap().clone())
+                    .unwrap_or_default()
+            })
+            .filter(|screen| screen.enabled);
+        if let Some(screen) = loading_screen {
+            egui_renderer.draw_ui_full(
+                &self.device,
+                &self.queue,
+                encoder,
+                target.window,
+                view,
+                &screen_descriptor,
+                &mut |ctx: &egui::Context| {
+                    loading_screen::show_loading_screen(ctx, &screen, &progress);
+                },
+            );
+        }
+
+        if !self.egui_windows.is_empty() {
+            // * if a custom ui is present
+            for window in self.egui_windows.iter_mut() {
+                egui_renderer.draw_ui_full(
+                    &self.device,
+                    &self.queue,
+                    encoder,
+                    target.window,
+                    view,
+                    &screen_descriptor,
+                    window,
+                );
+            }
+        }
+
+        // The window is borrowed while the UI is drawn
+        if let Some((path, pointer)) = dropped_asset {
+            self.spawn_asset(path, pointer, screen);
+        }
+    }
+
+    /// Spawn a model dropped from the asset browser at the ground point under the pointer.
+    fn spawn_asset(&mut self, path: String, pointer: egui::Pos2, screen: egui::Rect) {
+        let ndc = [
+            (pointer.x - screen.min.x) / screen.width() * 2.0 - 1.0,
+            1.0 - (pointer.y - screen.min.y) / screen.height() * 2.0,
+        ];
+        let view_proj = self.camera_projection.calc_matrix() * self.camera.calc_matrix();
+        // In front of the camera when the ground is not under the pointer
+        let position = asset_browser::pick_ground(view_proj, ndc)
+            .unwrap_or_else(|| self.camera.position.to_vec() + self.camera.forward() * 10.0);
+
+        // The components hold static strings, the few paths spawned from the editor are leaked
+        let obj_path: &'static str = Box::leak(path.clone().into_boxed_str());
+        let name = std::path::Path::new(obj_path)
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or(obj_path);
+
+        let ecs_lock = self.ecs.lock().unwrap();
+        let entity = ecs_lock.create_entity();
+        ecs_lock.add_component_to_entity(entity, Name(name));
+        ecs_lock.add_component_to_entity(entity, components::Pos3::new(position));
+        ecs_lock.add_component_to_entity(entity, components::Model::Dynamic { obj_path });
+        ecs_lock.send_event(AssetSpawned {
+            entity,
+            path,
+            position,
+        });
+        info!("[Assets] Spawned {} at {:?}", obj_path, position);
+    }
+}
@@ -0,0 +1,140 @@
+use super::model::{self, DrawModel, Vertex};
+use super::preprocess::{self, ShaderDefines};
+use super::{draw_list, instance, texture};
+use std::sync::{Arc, RwLock};
+
+/// The device feature the wireframe needs, it is requested when the adapter supports it.
+pub(crate) const FEATURES: wgpu::Features = wgpu::Features::POLYGON_MODE_LINE;
+
+/// The wireframe pass, draws the edges of the models over them, shown with the `RuntimeConfig`.
+pub(crate) struct WireframeRenderer {
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl WireframeRenderer {
+    /// Create the pipeline of the pass.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the device cannot draw the triangles as lines.
+    pub fn new(
+        device: &wgpu::Device,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        color_format: wgpu::TextureFormat,
+    ) -> Option<Self> {
+        if !device.features().contains(FEATURES) {
+            return None;
+        }
+        let shader = device.create_shader_module(preprocess::shader_module(
+            "Wireframe Shader",
+            "wireframe",
+            &ShaderDefines::new(),
+        ));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Wireframe Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Wireframe Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[model::ModelVertex::desc(), instance::InstanceRaw::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Line,
+                ..Default::default()
+            },
+            // The edges are tested against the depth of the models, pulled forward so they are not hidden by their faces
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState {
+                    constant: -2,
+                    slope_scale: -1.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Some(Self { pipeline })
+    }
+
+    /// Draw the edges of the opaque draws over the scene.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder` - The encoder of the frame.
+    /// * `color_view` - The scene color.
+    /// * `depth_view` - The depth of the scene.
+    /// * `views` - The camera bind groups and the viewports in pixels, `None` for the whole frame.
+    /// * `models` - The models of the frame with their instance buffers.
+    /// * `draws` - The opaque draws.
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        views: &[(&wgpu::BindGroup, Option<[f32; 4]>)],
+        models: &[(&model::Model, Arc<RwLock<wgpu::Buffer>>)],
+        draws: &[draw_list::OpaqueDraw],
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Wireframe Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+
+        for &(camera_bind_group, viewport) in views {
+            if let Some([x, y, width, height]) = viewport {
+                render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+            }
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            let mut bound_model = None;
+            for draw in draws {
+                let (model, instance_buffer) = &models[draw.model];
+                if bound_model != Some(draw.model) {
+                    render_pass.set_vertex_buffer(1, instance_buffer.read().unwrap().slice(..));
+                    bound_model = Some(draw.model);
+                }
+                render_pass.draw_mesh_geometry(&model.meshes[draw.mesh], 0..1);
+            }
+        }
+    }
+}